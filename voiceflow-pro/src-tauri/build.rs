@@ -1,3 +1,10 @@
 fn main() {
+    // A release bundle without the update signing key could never verify an update, so it refuses them all
+    if std::env::var("PROFILE").as_deref() == Ok("release") {
+        let config = std::fs::read_to_string("tauri.conf.json").unwrap_or_default();
+        if config.contains("\"pubkey\": \"\"") {
+            println!("cargo:warning=tauri.updater.pubkey is empty; this build will not update itself");
+        }
+    }
     tauri_build::build()
-}
\ No newline at end of file
+}
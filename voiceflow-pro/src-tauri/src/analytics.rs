@@ -0,0 +1,285 @@
+//! Local dictation analytics for VoiceFlow Pro
+//! Aggregates daily word counts, AI correction feedback and time-saved estimates without storing any text
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use chrono::{Duration as ChronoDuration, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+use crate::encryption::get_data_vault;
+use crate::errors::AppError;
+use crate::storage::{active_profile_id, profile_data_path, DataDir};
+use crate::unicode_text::word_count;
+
+const STATS_FILE: &str = "dictation_stats.json";
+/// Average conversational speaking rate, used to estimate time spent dictating
+const SPEAKING_WPM: f64 = 150.0;
+/// Results awaiting accept/reject feedback
+const MAX_PENDING_FEEDBACK: usize = 100;
+/// Application label used when the foreground app cannot be detected
+pub const UNKNOWN_APP: &str = "unknown";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnalyticsSettings {
+    /// Record dictation statistics; nothing is collected when off
+    pub enabled: bool,
+    /// Typing speed the time-saved estimate compares against
+    pub typing_wpm: u32,
+}
+
+impl Default for AnalyticsSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            typing_wpm: 40,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StatsRange {
+    Today,
+    Week,
+    Month,
+    Year,
+    All,
+}
+
+impl StatsRange {
+    fn first_day(self, today: NaiveDate) -> Option<NaiveDate> {
+        match self {
+            StatsRange::Today => Some(today),
+            StatsRange::Week => Some(today - ChronoDuration::days(6)),
+            StatsRange::Month => Some(today - ChronoDuration::days(29)),
+            StatsRange::Year => Some(today - ChronoDuration::days(364)),
+            StatsRange::All => None,
+        }
+    }
+}
+
+/// Totals for one calendar day
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct DayStats {
+    words: u64,
+    dictations: u64,
+    words_by_app: HashMap<String, u64>,
+    words_by_language: HashMap<String, u64>,
+    /// AI changes the user kept
+    changes_accepted: u64,
+    /// AI changes the user undid or replaced with their original text
+    changes_rejected: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct StatsStore {
+    /// Keyed by ISO date so the map iterates chronologically
+    days: BTreeMap<String, DayStats>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DailyWords {
+    pub date: String,
+    pub words: u64,
+    pub dictations: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WordShare {
+    pub name: String,
+    pub words: u64,
+}
+
+/// Dashboard data for a date range
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DictationStats {
+    pub range: StatsRange,
+    pub enabled: bool,
+    pub total_words: u64,
+    pub total_dictations: u64,
+    pub average_words_per_dictation: f64,
+    /// One entry per day in the range, including days without dictation
+    pub words_per_day: Vec<DailyWords>,
+    pub by_app: Vec<WordShare>,
+    pub by_language: Vec<WordShare>,
+    pub changes_accepted: u64,
+    pub changes_rejected: u64,
+    /// Share of reviewed AI changes that were kept
+    pub acceptance_rate: Option<f64>,
+    pub estimated_minutes_saved: f64,
+}
+
+/// Collects statistics for the active profile
+#[derive(Debug, Default)]
+pub struct DictationAnalytics {
+    profile_id: Option<String>,
+    store: StatsStore,
+    /// Result id -> (day, number of AI changes) for results that can still receive feedback
+    pending_feedback: VecDeque<(String, String, u64)>,
+}
+
+impl DictationAnalytics {
+    /// Count a finished dictation; `changes` is how many edits the AI made to it
+    pub fn record_dictation(&mut self, result_id: &str, text: &str, app: &str, language: &str, changes: u64) {
+        self.sync_profile();
+        // Han and kana count a character per word, so CJK dictation shows up in the totals
+        let words = word_count(text) as u64;
+        if words == 0 {
+            return;
+        }
+        let today = Local::now().date_naive().to_string();
+        let day = self.store.days.entry(today.clone()).or_default();
+        day.words += words;
+        day.dictations += 1;
+        *day.words_by_app.entry(app.to_string()).or_default() += words;
+        *day.words_by_language.entry(language.to_string()).or_default() += words;
+
+        if changes > 0 {
+            self.pending_feedback.push_back((result_id.to_string(), today, changes));
+            if self.pending_feedback.len() > MAX_PENDING_FEEDBACK {
+                self.pending_feedback.pop_front();
+            }
+        }
+        self.save();
+    }
+
+    /// Record whether the user kept or rejected the AI changes of a result
+    pub fn record_feedback(&mut self, result_id: &str, accepted: bool) -> Result<(), AppError> {
+        self.sync_profile();
+        let position = self
+            .pending_feedback
+            .iter()
+            .position(|(id, _, _)| id == result_id)
+            .ok_or_else(|| AppError::Configuration(format!("No recent result with id {}", result_id)))?;
+        let Some((_, day, changes)) = self.pending_feedback.remove(position) else {
+            return Ok(());
+        };
+        let day = self.store.days.entry(day).or_default();
+        if accepted {
+            day.changes_accepted += changes;
+        } else {
+            day.changes_rejected += changes;
+        }
+        self.save();
+        Ok(())
+    }
+
+    pub fn stats(&mut self, range: StatsRange, settings: &AnalyticsSettings) -> DictationStats {
+        self.sync_profile();
+        let today = Local::now().date_naive();
+        let first_day = range.first_day(today).or_else(|| {
+            self.store
+                .days
+                .keys()
+                .next()
+                .and_then(|d| d.parse::<NaiveDate>().ok())
+        });
+
+        let mut words_per_day = Vec::new();
+        let mut by_app: HashMap<String, u64> = HashMap::new();
+        let mut by_language: HashMap<String, u64> = HashMap::new();
+        let (mut accepted, mut rejected) = (0, 0);
+
+        if let Some(first_day) = first_day {
+            let mut date = first_day.min(today);
+            while date <= today {
+                let key = date.to_string();
+                let day = self.store.days.get(&key).cloned().unwrap_or_default();
+                for (app, words) in &day.words_by_app {
+                    *by_app.entry(app.clone()).or_default() += words;
+                }
+                for (language, words) in &day.words_by_language {
+                    *by_language.entry(language.clone()).or_default() += words;
+                }
+                accepted += day.changes_accepted;
+                rejected += day.changes_rejected;
+                words_per_day.push(DailyWords {
+                    date: key,
+                    words: day.words,
+                    dictations: day.dictations,
+                });
+                date += ChronoDuration::days(1);
+            }
+        }
+
+        let total_words: u64 = words_per_day.iter().map(|d| d.words).sum();
+        let total_dictations: u64 = words_per_day.iter().map(|d| d.dictations).sum();
+        let typing_minutes = total_words as f64 / settings.typing_wpm.max(1) as f64;
+        let speaking_minutes = total_words as f64 / SPEAKING_WPM;
+
+        DictationStats {
+            range,
+            enabled: settings.enabled,
+            total_words,
+            total_dictations,
+            average_words_per_dictation: if total_dictations > 0 {
+                total_words as f64 / total_dictations as f64
+            } else {
+                0.0
+            },
+            words_per_day,
+            by_app: ranked(by_app),
+            by_language: ranked(by_language),
+            changes_accepted: accepted,
+            changes_rejected: rejected,
+            acceptance_rate: (accepted + rejected > 0).then(|| accepted as f64 / (accepted + rejected) as f64),
+            estimated_minutes_saved: (typing_minutes - speaking_minutes).max(0.0),
+        }
+    }
+
+    /// Forget everything in memory; used after history files were purged from disk
+    pub fn reset(&mut self) {
+        self.store = StatsStore::default();
+        self.pending_feedback.clear();
+    }
+
+    /// Load the active profile's statistics if the profile changed since the last call
+    fn sync_profile(&mut self) {
+        let active = active_profile_id();
+        if self.profile_id.as_deref() == Some(active.as_str()) {
+            return;
+        }
+        self.store = load_store(&active);
+        self.pending_feedback.clear();
+        self.profile_id = Some(active);
+    }
+
+    fn save(&self) {
+        let Some(profile_id) = &self.profile_id else {
+            return;
+        };
+        let result = profile_data_path(profile_id, DataDir::History).and_then(|dir| {
+            std::fs::create_dir_all(&dir).map_err(|e| AppError::Internal(e.to_string()))?;
+            let json = serde_json::to_vec(&self.store).map_err(|e| AppError::Internal(e.to_string()))?;
+            get_data_vault().write_file(&dir.join(STATS_FILE), &json)
+        });
+        if let Err(e) = result {
+            log::warn!("Failed to save dictation statistics: {}", e);
+        }
+    }
+}
+
+static ANALYTICS: std::sync::OnceLock<tokio::sync::Mutex<DictationAnalytics>> = std::sync::OnceLock::new();
+
+pub fn get_dictation_analytics() -> &'static tokio::sync::Mutex<DictationAnalytics> {
+    ANALYTICS.get_or_init(|| tokio::sync::Mutex::new(DictationAnalytics::default()))
+}
+
+/// Largest share first
+fn ranked(counts: HashMap<String, u64>) -> Vec<WordShare> {
+    let mut shares: Vec<WordShare> = counts.into_iter().map(|(name, words)| WordShare { name, words }).collect();
+    shares.sort_by(|a, b| b.words.cmp(&a.words).then_with(|| a.name.cmp(&b.name)));
+    shares
+}
+
+fn load_store(profile_id: &str) -> StatsStore {
+    profile_data_path(profile_id, DataDir::History)
+        .ok()
+        .map(|dir| dir.join(STATS_FILE))
+        .filter(|path| path.exists())
+        .and_then(|path| get_data_vault().read_file(&path).ok())
+        .and_then(|data| serde_json::from_slice(&data).ok())
+        .unwrap_or_default()
+}
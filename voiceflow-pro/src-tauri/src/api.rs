@@ -0,0 +1,777 @@
+//! Versioned command API for VoiceFlow Pro
+//! Request and response types the frontend is written against, the version handshake and shims to the internal types
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize};
+use uuid::Uuid;
+
+use crate::errors::{AppError, ValidationError};
+use crate::integrations::ai_ml_api::{
+    AIMLResponse, EnhancedProcessingOptions, EnhancedTextResult, TextOperation, VoiceConfiguration, VoiceOutputFormat,
+    VoiceQuality, VoiceSelection,
+};
+use crate::integrations::ai_text_processor::{
+    ChangeType, ProcessingContext, ProcessingOptions, ProcessingRequest, ProcessingResult, TextChange, ToneType,
+};
+use crate::integrations::voice_generation::VoiceResult;
+use crate::integrations::{
+    EnhancedContext, FormalityLevel, TranslationContext, TranslationDomain, TranslationOptions, TranslationResult,
+};
+use crate::validation::{validate_language_code, validate_text, CONTEXT_NAMES, TONE_NAMES};
+
+/// Version of the command API this backend speaks; bumped when a versioned type changes incompatibly
+pub const API_VERSION: u32 = 1;
+/// Oldest frontend API version the backend still serves
+pub const MIN_CLIENT_VERSION: u32 = 1;
+/// Commands kept for older frontends, and what to call instead
+pub const DEPRECATED_COMMANDS: &[(&str, &str)] = &[
+    ("process_text", "process_text_v1"),
+    ("process_enhanced_text", "enhance_text_v1"),
+    ("translate_with_enhancement", "translate_v1"),
+    ("generate_enhanced_voice", "synthesize_v1"),
+];
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HandshakeRequest {
+    /// `API_VERSION` the frontend was built against
+    pub client_version: u32,
+    /// Frontend build, for the log
+    #[serde(default)]
+    pub client_build: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeprecatedCommand {
+    pub command: String,
+    pub replacement: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HandshakeResponse {
+    pub api_version: u32,
+    pub min_client_version: u32,
+    pub app_version: String,
+    /// Whether the frontend may go on; when not, `message` says which side needs updating
+    pub compatible: bool,
+    pub deprecated: Vec<DeprecatedCommand>,
+    pub message: Option<String>,
+}
+
+pub fn handshake(request: &HandshakeRequest) -> HandshakeResponse {
+    let message = if request.client_version < MIN_CLIENT_VERSION {
+        Some(format!(
+            "This window was built for API version {}, but the app needs at least {}; reload the window",
+            request.client_version, MIN_CLIENT_VERSION
+        ))
+    } else if request.client_version > API_VERSION {
+        Some(format!(
+            "This window expects API version {}, but the app only speaks {}; update VoiceFlow Pro",
+            request.client_version, API_VERSION
+        ))
+    } else {
+        None
+    };
+    match &message {
+        Some(message) => log::warn!("Frontend handshake failed ({:?}): {}", request.client_build, message),
+        None => log::info!(
+            "Frontend {:?} speaks API version {}",
+            request.client_build,
+            request.client_version
+        ),
+    }
+    HandshakeResponse {
+        api_version: API_VERSION,
+        min_client_version: MIN_CLIENT_VERSION,
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        compatible: message.is_none(),
+        deprecated: DEPRECATED_COMMANDS
+            .iter()
+            .map(|(command, replacement)| DeprecatedCommand {
+                command: command.to_string(),
+                replacement: replacement.to_string(),
+            })
+            .collect(),
+        message,
+    }
+}
+
+/// Deserialize a field through its validating `FromStr`, so a bad name fails with the validator's message
+fn from_name<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr<Err = AppError>,
+{
+    let name = String::deserialize(deserializer)?;
+    name.parse().map_err(D::Error::custom)
+}
+
+fn language<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let code = String::deserialize(deserializer)?;
+    validate_language_code(&code).map_err(D::Error::custom)
+}
+
+fn optional_language<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|code| validate_language_code(&code).map_err(D::Error::custom))
+        .transpose()
+}
+
+fn invalid(message: String) -> AppError {
+    AppError::Validation(ValidationError::InvalidConfigValue(message))
+}
+
+fn context_name(context: &ProcessingContext) -> &'static str {
+    CONTEXT_NAMES
+        .iter()
+        .find(|(_, known)| known == context)
+        .map_or("email", |(name, _)| *name)
+}
+
+fn tone_name(tone: &ToneType) -> &'static str {
+    TONE_NAMES
+        .iter()
+        .find(|(_, known)| known == tone)
+        .map_or("professional", |(name, _)| *name)
+}
+
+/// Stable wire names, so renaming an internal variant cannot break the frontend
+fn change_kind(change_type: &ChangeType) -> &'static str {
+    match change_type {
+        ChangeType::Grammar => "grammar",
+        ChangeType::Punctuation => "punctuation",
+        ChangeType::Spelling => "spelling",
+        ChangeType::Tone => "tone",
+        ChangeType::FillerRemoval => "filler_removal",
+        ChangeType::Formatting => "formatting",
+        ChangeType::Capitalization => "capitalization",
+        ChangeType::Style => "style",
+        ChangeType::Preservation => "preservation",
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct TextOptionsV1 {
+    pub aggressiveness: f32,
+    pub remove_fillers: bool,
+    pub preserve_formatting: bool,
+    pub smart_punctuation: bool,
+    pub auto_correct: bool,
+}
+
+impl Default for TextOptionsV1 {
+    fn default() -> Self {
+        Self {
+            aggressiveness: 0.7,
+            remove_fillers: true,
+            preserve_formatting: false,
+            smart_punctuation: true,
+            auto_correct: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProcessTextRequestV1 {
+    pub text: String,
+    /// One of the names in `CONTEXT_NAMES`, e.g. "email"
+    #[serde(deserialize_with = "from_name")]
+    pub context: ProcessingContext,
+    /// One of the names in `TONE_NAMES`, e.g. "professional"
+    #[serde(deserialize_with = "from_name")]
+    pub tone: ToneType,
+    #[serde(default)]
+    pub options: TextOptionsV1,
+}
+
+impl ProcessTextRequestV1 {
+    /// Shim for the unversioned `process_text(text, context, tone)` command
+    pub fn from_legacy(text: String, context: &str, tone: &str) -> Result<Self, AppError> {
+        Ok(Self {
+            text,
+            context: context.parse()?,
+            tone: tone.parse()?,
+            options: TextOptionsV1::default(),
+        })
+    }
+
+    pub fn into_request(self) -> Result<ProcessingRequest, AppError> {
+        let text = validate_text(&self.text, Some(1), Some(50000))?;
+        let options = self.options;
+        Ok(ProcessingRequest {
+            id: Uuid::new_v4().to_string(),
+            text,
+            context: self.context,
+            tone: self.tone,
+            options: ProcessingOptions {
+                aggressiveness: options.aggressiveness.clamp(0.0, 1.0),
+                remove_fillers: options.remove_fillers,
+                preserve_formatting: options.preserve_formatting,
+                smart_punctuation: options.smart_punctuation,
+                auto_correct: options.auto_correct,
+                length: None,
+            },
+            timestamp: chrono::Utc::now().timestamp().max(0) as u64,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TextChangeV1 {
+    /// e.g. "grammar" or "filler_removal"
+    pub kind: String,
+    pub original: String,
+    pub replacement: String,
+    pub position: usize,
+    pub confidence: f32,
+}
+
+impl From<TextChange> for TextChangeV1 {
+    fn from(change: TextChange) -> Self {
+        Self {
+            kind: change_kind(&change.change_type).to_string(),
+            original: change.original,
+            replacement: change.replacement,
+            position: change.position,
+            confidence: change.confidence,
+        }
+    }
+}
+
+/// The parts of a processing result the frontend relies on; internal fields stay behind
+#[derive(Debug, Clone, Serialize)]
+pub struct ProcessTextResponseV1 {
+    pub id: String,
+    pub text: String,
+    pub original_text: String,
+    pub changes: Vec<TextChangeV1>,
+    pub confidence: f32,
+    pub processing_time_ms: u64,
+    pub context: String,
+    pub tone: String,
+}
+
+impl From<ProcessingResult> for ProcessTextResponseV1 {
+    fn from(result: ProcessingResult) -> Self {
+        Self {
+            context: context_name(&result.context_used).to_string(),
+            tone: tone_name(&result.tone_applied).to_string(),
+            id: result.id,
+            text: result.processed_text,
+            original_text: result.original_text,
+            changes: result.changes_made.into_iter().map(TextChangeV1::from).collect(),
+            confidence: result.confidence_score,
+            processing_time_ms: result.processing_time_ms,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TextOperationV1 {
+    Enhance,
+    /// Into `target_language`, which must then be set
+    Translate,
+    Summarize,
+    Analyze,
+    Rewrite,
+    GrammarCheck,
+    StyleImprove,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EnhanceTextRequestV1 {
+    pub text: String,
+    pub operations: Vec<TextOperationV1>,
+    #[serde(default, deserialize_with = "optional_language")]
+    pub source_language: Option<String>,
+    #[serde(default, deserialize_with = "optional_language")]
+    pub target_language: Option<String>,
+    /// One of the names in `TONE_NAMES`; adds a tone adjustment after the other operations
+    #[serde(default, deserialize_with = "optional_name")]
+    pub tone: Option<ToneType>,
+    #[serde(default)]
+    pub audience: Option<String>,
+    #[serde(default)]
+    pub purpose: Option<String>,
+    /// Distinct rewrites to return besides the main result; none by default
+    #[serde(default)]
+    pub alternatives: u8,
+    #[serde(default)]
+    pub idempotency_key: Option<String>,
+}
+
+fn optional_name<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr<Err = AppError>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|name| name.parse().map_err(D::Error::custom))
+        .transpose()
+}
+
+impl EnhanceTextRequestV1 {
+    pub fn internal_operations(&self) -> Result<Vec<TextOperation>, AppError> {
+        if self.operations.is_empty() && self.tone.is_none() {
+            return Err(invalid("At least one operation or a tone is needed".to_string()));
+        }
+        let mut operations = Vec::with_capacity(self.operations.len() + 1);
+        for operation in &self.operations {
+            operations.push(match operation {
+                TextOperationV1::Enhance => TextOperation::Enhance,
+                TextOperationV1::Translate if self.target_language.is_none() => {
+                    return Err(invalid("Translation needs a target_language".to_string()));
+                }
+                TextOperationV1::Translate => TextOperation::Translate {
+                    context: TranslationContext::default(),
+                    options: TranslationOptions::default(),
+                },
+                TextOperationV1::Summarize => TextOperation::Summarize,
+                TextOperationV1::Analyze => TextOperation::Analyze,
+                TextOperationV1::Rewrite => TextOperation::Rewrite,
+                TextOperationV1::GrammarCheck => TextOperation::GrammarCheck,
+                TextOperationV1::StyleImprove => TextOperation::StyleImprove,
+            });
+        }
+        if let Some(tone) = &self.tone {
+            operations.push(TextOperation::ToneAdjust(tone_name(tone).to_string()));
+        }
+        Ok(operations)
+    }
+
+    pub fn context(&self) -> EnhancedContext {
+        EnhancedContext {
+            audience: self.audience.clone(),
+            purpose: self.purpose.clone(),
+            ..EnhancedContext::default()
+        }
+    }
+
+    pub fn options(&self) -> EnhancedProcessingOptions {
+        EnhancedProcessingOptions {
+            include_confidence_scores: true,
+            include_suggestions: true,
+            preserve_formatting: true,
+            generate_alternatives: self.alternatives > 0,
+            number_of_alternatives: self.alternatives,
+            apply_multilingual_optimization: true,
+            enable_real_time_processing: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EnhanceTextResponseV1 {
+    pub id: String,
+    pub text: String,
+    pub original_text: String,
+    /// Served from the result cache rather than generated now
+    pub cached: bool,
+    /// Operations that failed while the rest went through
+    pub warnings: Vec<String>,
+    pub translation: Option<TranslateResponseV1>,
+    pub alternatives: Vec<String>,
+    pub suggestions: Vec<String>,
+    pub processing_time_ms: u64,
+}
+
+impl TryFrom<AIMLResponse<EnhancedTextResult>> for EnhanceTextResponseV1 {
+    type Error = AppError;
+
+    fn try_from(response: AIMLResponse<EnhancedTextResult>) -> Result<Self, AppError> {
+        let (result, cached, warnings) = match response {
+            AIMLResponse::Success(result) => (result, false, Vec::new()),
+            AIMLResponse::Cached(result) => (result, true, Vec::new()),
+            AIMLResponse::Partial(result, warnings) => (result, false, warnings),
+            AIMLResponse::Failure(message) => return Err(AppError::Internal(message)),
+        };
+        Ok(Self {
+            id: result.id,
+            text: result.processed_text,
+            original_text: result.original_text,
+            cached,
+            warnings,
+            translation: result.translation.map(TranslateResponseV1::from),
+            alternatives: result.alternative_versions.into_iter().map(|version| version.text).collect(),
+            suggestions: result.suggestions,
+            processing_time_ms: result.processing_time_ms,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TranslationDomainV1 {
+    #[default]
+    General,
+    Technical,
+    Medical,
+    Legal,
+    Business,
+    Academic,
+    Literary,
+    Scientific,
+    Software,
+    Marketing,
+}
+
+impl From<TranslationDomainV1> for TranslationDomain {
+    fn from(domain: TranslationDomainV1) -> Self {
+        match domain {
+            TranslationDomainV1::General => TranslationDomain::General,
+            TranslationDomainV1::Technical => TranslationDomain::Technical,
+            TranslationDomainV1::Medical => TranslationDomain::Medical,
+            TranslationDomainV1::Legal => TranslationDomain::Legal,
+            TranslationDomainV1::Business => TranslationDomain::Business,
+            TranslationDomainV1::Academic => TranslationDomain::Academic,
+            TranslationDomainV1::Literary => TranslationDomain::Literary,
+            TranslationDomainV1::Scientific => TranslationDomain::Scientific,
+            TranslationDomainV1::Software => TranslationDomain::Software,
+            TranslationDomainV1::Marketing => TranslationDomain::Marketing,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FormalityV1 {
+    VeryFormal,
+    Formal,
+    #[default]
+    Neutral,
+    Informal,
+    VeryInformal,
+}
+
+impl From<FormalityV1> for FormalityLevel {
+    fn from(formality: FormalityV1) -> Self {
+        match formality {
+            FormalityV1::VeryFormal => FormalityLevel::VeryFormal,
+            FormalityV1::Formal => FormalityLevel::Formal,
+            FormalityV1::Neutral => FormalityLevel::Neutral,
+            FormalityV1::Informal => FormalityLevel::Informal,
+            FormalityV1::VeryInformal => FormalityLevel::VeryInformal,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TranslateRequestV1 {
+    pub text: String,
+    /// Detected when omitted
+    #[serde(default, deserialize_with = "optional_language")]
+    pub source_language: Option<String>,
+    #[serde(deserialize_with = "language")]
+    pub target_language: String,
+    #[serde(default)]
+    pub domain: TranslationDomainV1,
+    /// Neutral, or the user's default for the target language, when omitted
+    #[serde(default)]
+    pub formality: Option<FormalityV1>,
+    /// Required renderings for specific source terms
+    #[serde(default)]
+    pub glossary: BTreeMap<String, String>,
+    #[serde(default)]
+    pub back_translation_check: bool,
+    #[serde(default)]
+    pub idempotency_key: Option<String>,
+}
+
+impl TranslateRequestV1 {
+    pub fn context(&self) -> TranslationContext {
+        let mut context = TranslationContext {
+            domain: self.domain.into(),
+            ..TranslationContext::default()
+        };
+        if let Some(formality) = self.formality {
+            context.formality_level = formality.into();
+        }
+        context
+    }
+
+    pub fn options(&self) -> TranslationOptions {
+        TranslationOptions {
+            glossary: self.glossary.clone(),
+            back_translation_check: self.back_translation_check,
+            ..TranslationOptions::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TranslateResponseV1 {
+    pub id: String,
+    pub text: String,
+    pub original_text: String,
+    pub source_language: String,
+    pub target_language: String,
+    pub detected_language: Option<String>,
+    pub confidence: f32,
+    /// Overall quality estimate, 0.0 to 1.0
+    pub quality: f32,
+    pub issues: Vec<String>,
+    pub processing_time_ms: u64,
+}
+
+impl From<TranslationResult> for TranslateResponseV1 {
+    fn from(result: TranslationResult) -> Self {
+        Self {
+            id: result.id,
+            text: result.translated_text,
+            original_text: result.original_text,
+            source_language: result.source_language,
+            target_language: result.target_language,
+            detected_language: result.detected_language,
+            confidence: result.confidence,
+            quality: result.translation_quality.overall_score,
+            issues: result.translation_quality.issues,
+            processing_time_ms: result.processing_time_ms,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AudioFormatV1 {
+    #[default]
+    Mp3,
+    Wav,
+    Ogg,
+    Flac,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SynthesizeRequestV1 {
+    pub text: String,
+    #[serde(deserialize_with = "language")]
+    pub language: String,
+    /// Provider voice name; the model's default when omitted
+    #[serde(default)]
+    pub voice: Option<String>,
+    /// Id of a registered custom voice; takes the place of `voice`
+    #[serde(default)]
+    pub custom_voice: Option<String>,
+    #[serde(default)]
+    pub speed: Option<f32>,
+    #[serde(default)]
+    pub pitch: Option<f32>,
+    #[serde(default)]
+    pub format: AudioFormatV1,
+    #[serde(default)]
+    pub idempotency_key: Option<String>,
+}
+
+impl SynthesizeRequestV1 {
+    /// `model` is the configured voice model; a custom voice brings its own
+    pub fn voice_configuration(&self, model: String) -> Result<VoiceConfiguration, AppError> {
+        if self.voice.is_some() && self.custom_voice.is_some() {
+            return Err(invalid("Set either voice or custom_voice, not both".to_string()));
+        }
+        Ok(VoiceConfiguration {
+            model,
+            voice_id: self.voice.clone(),
+            language_code: self.language.clone(),
+            use_neural_voices: true,
+            apply_ssml: false,
+            enable_emotion: false,
+            quality_level: VoiceQuality::High,
+            voice: match &self.custom_voice {
+                Some(profile_id) => VoiceSelection::CustomVoice {
+                    profile_id: profile_id.clone(),
+                },
+                None => VoiceSelection::Standard,
+            },
+        })
+    }
+
+    pub fn output_format(&self) -> VoiceOutputFormat {
+        match self.format {
+            AudioFormatV1::Mp3 => VoiceOutputFormat::MP3 { bitrate: None },
+            AudioFormatV1::Wav => VoiceOutputFormat::WAV { sample_rate: None },
+            AudioFormatV1::Ogg => VoiceOutputFormat::OGG { quality: None },
+            AudioFormatV1::Flac => VoiceOutputFormat::FLAC {
+                compression_level: None,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SynthesizeResponseV1 {
+    pub id: String,
+    pub audio_base64: String,
+    /// e.g. "mp3" or "wav"
+    pub format: String,
+    pub duration_seconds: f32,
+    pub sample_rate: u32,
+    pub voice: String,
+}
+
+impl From<VoiceResult> for SynthesizeResponseV1 {
+    fn from(result: VoiceResult) -> Self {
+        use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+
+        Self {
+            id: result.id,
+            audio_base64: BASE64.encode(&result.audio_data),
+            format: format!("{:?}", result.format).to_lowercase(),
+            duration_seconds: result.duration_seconds,
+            sample_rate: result.sample_rate,
+            voice: result.voice_used,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn handshake_rejects_versions_outside_the_supported_range() {
+        let compatible = handshake(&HandshakeRequest {
+            client_version: API_VERSION,
+            client_build: None,
+        });
+        assert!(compatible.compatible);
+        assert!(compatible.message.is_none());
+        assert_eq!(compatible.deprecated.len(), DEPRECATED_COMMANDS.len());
+
+        let newer = handshake(&HandshakeRequest {
+            client_version: API_VERSION + 1,
+            client_build: Some("next".to_string()),
+        });
+        assert!(!newer.compatible);
+        assert!(newer.message.unwrap().contains("update VoiceFlow Pro"));
+    }
+
+    #[test]
+    fn every_deprecated_command_names_a_versioned_replacement() {
+        for (command, replacement) in DEPRECATED_COMMANDS {
+            assert!(replacement.ends_with("_v1"), "{} -> {}", command, replacement);
+            assert_ne!(command, replacement);
+        }
+    }
+
+    #[test]
+    fn requests_reject_unknown_fields_and_bad_names() {
+        let unknown = serde_json::from_value::<TranslateRequestV1>(json!({
+            "text": "hola",
+            "target_language": "en",
+            "tone": "friendly",
+        }));
+        assert!(unknown.is_err());
+
+        let bad_language = serde_json::from_value::<SynthesizeRequestV1>(json!({
+            "text": "hello",
+            "language": "english",
+        }));
+        assert!(bad_language.is_err());
+
+        let bad_tone = serde_json::from_value::<EnhanceTextRequestV1>(json!({
+            "text": "hello",
+            "operations": ["enhance"],
+            "tone": "sarcastic",
+        }));
+        assert!(bad_tone.is_err());
+    }
+
+    #[test]
+    fn enhance_operations_map_to_the_internal_ones() {
+        let request: EnhanceTextRequestV1 = serde_json::from_value(json!({
+            "text": "hello",
+            "operations": ["grammar_check", "translate"],
+            "target_language": "de",
+            "tone": "friendly",
+            "alternatives": 2,
+        }))
+        .unwrap();
+        let operations = request.internal_operations().unwrap();
+        assert!(matches!(operations[0], TextOperation::GrammarCheck));
+        assert!(matches!(operations[1], TextOperation::Translate { .. }));
+        assert!(matches!(&operations[2], TextOperation::ToneAdjust(tone) if tone == "friendly"));
+        let options = request.options();
+        assert!(options.generate_alternatives);
+        assert_eq!(options.number_of_alternatives, 2);
+    }
+
+    #[test]
+    fn translation_needs_a_target_and_something_must_be_asked_for() {
+        let untargeted: EnhanceTextRequestV1 = serde_json::from_value(json!({
+            "text": "hello",
+            "operations": ["translate"],
+        }))
+        .unwrap();
+        assert!(untargeted.internal_operations().is_err());
+
+        let empty: EnhanceTextRequestV1 = serde_json::from_value(json!({
+            "text": "hello",
+            "operations": [],
+        }))
+        .unwrap();
+        assert!(empty.internal_operations().is_err());
+    }
+
+    #[test]
+    fn translate_request_fills_context_and_options() {
+        let request: TranslateRequestV1 = serde_json::from_value(json!({
+            "text": "Bitte senden Sie den Bericht",
+            "source_language": "de",
+            "target_language": "en-US",
+            "domain": "legal",
+            "formality": "very_formal",
+            "glossary": { "Bericht": "report" },
+        }))
+        .unwrap();
+        let context = request.context();
+        assert!(matches!(context.domain, TranslationDomain::Legal));
+        assert_eq!(context.formality_level, FormalityLevel::VeryFormal);
+        let options = request.options();
+        assert_eq!(options.glossary.get("Bericht").map(String::as_str), Some("report"));
+        assert!(!options.back_translation_check);
+    }
+
+    #[test]
+    fn synthesize_request_picks_the_voice_and_format() {
+        let request: SynthesizeRequestV1 = serde_json::from_value(json!({
+            "text": "hello",
+            "language": "en-GB",
+            "custom_voice": "my-voice",
+            "format": "wav",
+        }))
+        .unwrap();
+        let config = request.voice_configuration("tts-1".to_string()).unwrap();
+        assert_eq!(config.language_code, "en-GB");
+        assert_eq!(
+            config.voice,
+            VoiceSelection::CustomVoice {
+                profile_id: "my-voice".to_string()
+            }
+        );
+        assert!(matches!(request.output_format(), VoiceOutputFormat::WAV { .. }));
+
+        let both: SynthesizeRequestV1 = serde_json::from_value(json!({
+            "text": "hello",
+            "language": "en",
+            "voice": "alloy",
+            "custom_voice": "my-voice",
+        }))
+        .unwrap();
+        assert!(both.voice_configuration("tts-1".to_string()).is_err());
+    }
+}
@@ -0,0 +1,609 @@
+//! Voice assistant sessions for VoiceFlow Pro
+//! Answers spoken requests and carries out actionable ones by letting the model call registered tools
+
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock, RwLock};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use crate::errors::{AppError, ValidationError};
+use crate::history::get_transcript_history;
+use crate::integrations::content_filter::{apply_content_filter, OutputTarget};
+use crate::integrations::destinations::{send_to_destination, DestinationMetadata};
+use crate::integrations::speech_stream::{self, SpeechStreamStatus};
+use crate::integrations::voice_profiles::resolve_voice;
+use crate::integrations::{EnhancedContext, UserIntent};
+use crate::screen_ocr::{read_screen, refers_to_screen, ScreenRegion};
+use crate::system_activity::clipboard_text;
+use crate::undo_history::{self, TextAction};
+use crate::AppState;
+
+const SYSTEM_PROMPT: &str = "You are the VoiceFlow voice assistant. Replies are read aloud, so answer in one to three short, plain sentences without markdown. When the user asks for something one of your tools can do, call the tool; never claim an action happened unless a tool reported it.";
+/// Messages kept per session besides the system prompt; older turns are dropped
+const MAX_SESSION_MESSAGES: usize = 40;
+/// Tool output passed back to the model is cut here
+const MAX_TOOL_RESULT_CHARS: usize = 4000;
+const MAX_HISTORY_RESULTS: usize = 20;
+/// Offered only on turns where the user's own words refer to the screen
+const SCREEN_TOOL: &str = "read_screen";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AssistantSettings {
+    /// Read replies aloud as well as returning them
+    pub speak_responses: bool,
+    pub voice: Option<String>,
+    /// Tool calls allowed before a turn gives up
+    pub max_tool_rounds: usize,
+    pub webhook_tools: Vec<WebhookToolConfig>,
+}
+
+impl Default for AssistantSettings {
+    fn default() -> Self {
+        Self {
+            speak_responses: true,
+            voice: None,
+            max_tool_rounds: 4,
+            webhook_tools: Vec::new(),
+        }
+    }
+}
+
+/// A user-registered tool that POSTs the model's arguments as JSON to `url`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WebhookToolConfig {
+    pub name: String,
+    /// Tells the model when to use the tool
+    pub description: String,
+    pub url: String,
+    /// JSON schema of the arguments
+    #[serde(default = "empty_parameters")]
+    pub parameters: Value,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+}
+
+fn empty_parameters() -> Value {
+    json!({ "type": "object", "properties": {} })
+}
+
+/// What a tool can reach while it runs
+pub struct ToolContext {
+    pub state: AppState,
+    pub app: AppHandle,
+}
+
+/// Something the assistant can do on the user's behalf
+#[async_trait]
+pub trait AssistantTool: Send + Sync {
+    fn name(&self) -> &str;
+    fn description(&self) -> &str;
+    /// JSON schema of the arguments
+    fn parameters(&self) -> Value;
+    async fn call(&self, arguments: Value, context: &ToolContext) -> Result<Value, AppError>;
+
+    /// Definition in the chat-completions tool format
+    fn definition(&self) -> Value {
+        json!({
+            "type": "function",
+            "function": {
+                "name": self.name(),
+                "description": self.description(),
+                "parameters": self.parameters(),
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssistantToolInfo {
+    pub name: String,
+    pub description: String,
+    /// Registered by the user as a webhook rather than built in
+    pub webhook: bool,
+}
+
+/// One tool call made during a turn, sent as "assistant-tool-call" as it completes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolInvocation {
+    pub session_id: String,
+    pub tool: String,
+    pub arguments: Value,
+    pub result: Option<Value>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssistantSessionInfo {
+    pub session_id: String,
+    pub tools: Vec<AssistantToolInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssistantReply {
+    pub session_id: String,
+    pub text: String,
+    pub intent: Option<UserIntent>,
+    pub tool_calls: Vec<ToolInvocation>,
+    /// Playback of the reply, when replies are spoken
+    pub speech: Option<SpeechStreamStatus>,
+}
+
+struct AssistantSession {
+    messages: Vec<Value>,
+}
+
+static SESSIONS: OnceLock<Mutex<HashMap<String, AssistantSession>>> = OnceLock::new();
+static REGISTERED_TOOLS: OnceLock<RwLock<Vec<Arc<dyn AssistantTool>>>> = OnceLock::new();
+
+fn sessions() -> &'static Mutex<HashMap<String, AssistantSession>> {
+    SESSIONS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn registered_tools() -> &'static RwLock<Vec<Arc<dyn AssistantTool>>> {
+    REGISTERED_TOOLS.get_or_init(|| {
+        RwLock::new(vec![
+            Arc::new(HistorySearchTool) as Arc<dyn AssistantTool>,
+            Arc::new(ClipboardTool),
+            Arc::new(ReminderTool),
+            Arc::new(ScreenTextTool),
+        ])
+    })
+}
+
+/// Make a tool available to every assistant session, replacing one with the same name
+pub fn register_tool(tool: Arc<dyn AssistantTool>) {
+    let mut tools = match registered_tools().write() {
+        Ok(tools) => tools,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    tools.retain(|known| known.name() != tool.name());
+    tools.push(tool);
+}
+
+/// Built-in and code-registered tools followed by the user's webhook tools
+fn available_tools(settings: &AssistantSettings) -> Vec<Arc<dyn AssistantTool>> {
+    let mut tools: Vec<Arc<dyn AssistantTool>> = match registered_tools().read() {
+        Ok(tools) => tools.clone(),
+        Err(poisoned) => poisoned.into_inner().clone(),
+    };
+    for webhook in &settings.webhook_tools {
+        if !tools.iter().any(|tool| tool.name() == webhook.name) {
+            tools.push(Arc::new(WebhookTool(webhook.clone())));
+        }
+    }
+    tools
+}
+
+pub fn list_tools(settings: &AssistantSettings) -> Vec<AssistantToolInfo> {
+    available_tools(settings)
+        .iter()
+        .map(|tool| AssistantToolInfo {
+            name: tool.name().to_string(),
+            description: tool.description().to_string(),
+            webhook: settings.webhook_tools.iter().any(|webhook| webhook.name == tool.name()),
+        })
+        .collect()
+}
+
+/// Check a webhook tool before it is saved
+pub fn validate_webhook_tool(tool: &WebhookToolConfig) -> Result<(), AppError> {
+    let invalid = |message: String| AppError::Validation(ValidationError::InvalidConfigValue(message));
+    // The chat API only accepts these characters in function names
+    let valid_name = !tool.name.is_empty()
+        && tool.name.len() <= 64
+        && tool.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if !valid_name {
+        return Err(invalid(format!(
+            "Tool name '{}' must be 1-64 letters, digits, '_' or '-'",
+            tool.name
+        )));
+    }
+    let builtin = registered_tools()
+        .read()
+        .map(|tools| tools.iter().any(|known| known.name() == tool.name))
+        .unwrap_or(false);
+    if builtin {
+        return Err(invalid(format!("'{}' is the name of a built-in tool", tool.name)));
+    }
+    if !(tool.url.starts_with("https://") || tool.url.starts_with("http://")) {
+        return Err(invalid(format!("Webhook URL must be http or https: {}", tool.url)));
+    }
+    if tool.description.trim().is_empty() {
+        return Err(invalid("Describe when the assistant should use the tool".to_string()));
+    }
+    if !tool.parameters.is_object() {
+        return Err(invalid("Tool parameters must be a JSON schema object".to_string()));
+    }
+    Ok(())
+}
+
+pub async fn start_session(settings: &AssistantSettings) -> AssistantSessionInfo {
+    let session_id = Uuid::new_v4().to_string();
+    sessions().lock().await.insert(
+        session_id.clone(),
+        AssistantSession {
+            messages: vec![json!({ "role": "system", "content": SYSTEM_PROMPT })],
+        },
+    );
+    AssistantSessionInfo {
+        session_id,
+        tools: list_tools(settings),
+    }
+}
+
+pub async fn end_session(session_id: &str) -> bool {
+    sessions().lock().await.remove(session_id).is_some()
+}
+
+/// Intents that ask for something to be done or looked up; other turns are answered without tools
+fn is_actionable(intent: &UserIntent) -> bool {
+    matches!(
+        intent,
+        UserIntent::Command
+            | UserIntent::Request
+            | UserIntent::Instruction
+            | UserIntent::InformationSeeking
+            | UserIntent::Question
+    )
+}
+
+/// Answer one utterance, running whatever tools the model asks for along the way
+pub async fn assistant_turn(
+    state: &AppState,
+    app: &AppHandle,
+    session_id: &str,
+    utterance: &str,
+) -> Result<AssistantReply, AppError> {
+    let mut messages = sessions()
+        .lock()
+        .await
+        .get(session_id)
+        .map(|session| session.messages.clone())
+        .ok_or_else(|| AppError::Configuration(format!("Assistant session {} has ended", session_id)))?;
+    let settings = state.settings.snapshot();
+    let assistant = settings.assistant.clone();
+
+    let intent = {
+        crate::startup::ensure_started(state, crate::startup::Service::AiGateway).await?;
+        let gateway_state = state.ai_ml_gateway.lock().await;
+        let gateway = gateway_state
+            .as_ref()
+            .ok_or_else(|| AppError::NotInitialized("AI ML API".to_string()))?;
+        match gateway.predict_intent(utterance.to_string(), EnhancedContext::default()).await {
+            Ok(intent) => Some(intent),
+            Err(e) => {
+                log::warn!("Intent classification failed, offering tools anyway: {}", e);
+                None
+            }
+        }
+    };
+    let mut tools = if intent.as_ref().map_or(true, is_actionable) {
+        available_tools(&assistant)
+    } else {
+        Vec::new()
+    };
+    // The screen is read only when the user asks about it, never on the strength of text the model has seen,
+    // such as a clipboard or webhook result telling it to capture the screen and send it on
+    if !refers_to_screen(utterance) {
+        tools.retain(|tool| tool.name() != SCREEN_TOOL);
+    }
+    let definitions: Vec<Value> = tools.iter().map(|tool| tool.definition()).collect();
+    let context = ToolContext {
+        state: state.clone(),
+        app: app.clone(),
+    };
+
+    messages.push(json!({ "role": "user", "content": utterance }));
+    let mut invocations = Vec::new();
+    let mut text = None;
+    for _ in 0..=assistant.max_tool_rounds {
+        let reply = {
+            crate::startup::ensure_started(state, crate::startup::Service::AiGateway).await?;
+            let gateway_state = state.ai_ml_gateway.lock().await;
+            let gateway = gateway_state
+                .as_ref()
+                .ok_or_else(|| AppError::NotInitialized("AI ML API".to_string()))?;
+            gateway
+                .chat_with_tools(messages.clone(), definitions.clone())
+                .await
+                .map_err(AppError::from)?
+        };
+        messages.push(reply.message.clone());
+        if reply.tool_calls.is_empty() {
+            text = reply.content;
+            break;
+        }
+
+        for call in reply.tool_calls {
+            let outcome = match tools.iter().find(|tool| tool.name() == call.name) {
+                Some(tool) => tool.call(call.arguments.clone(), &context).await,
+                None => Err(AppError::Configuration(format!("Unknown tool: {}", call.name))),
+            };
+            let invocation = ToolInvocation {
+                session_id: session_id.to_string(),
+                tool: call.name.clone(),
+                arguments: call.arguments,
+                result: outcome.as_ref().ok().cloned(),
+                error: outcome.as_ref().err().map(|e| e.to_string()),
+            };
+            let _ = app.emit_all("assistant-tool-call", &invocation);
+
+            let content = match &outcome {
+                Ok(result) => result.to_string(),
+                Err(e) => json!({ "error": e.to_string() }).to_string(),
+            };
+            messages.push(json!({
+                "role": "tool",
+                "tool_call_id": call.id,
+                "content": content.chars().take(MAX_TOOL_RESULT_CHARS).collect::<String>(),
+            }));
+            invocations.push(invocation);
+        }
+    }
+    let text = text
+        .filter(|text| !text.trim().is_empty())
+        .unwrap_or_else(|| "Sorry, I could not finish that request.".to_string());
+
+    if let Some(session) = sessions().lock().await.get_mut(session_id) {
+        if messages.len() > MAX_SESSION_MESSAGES + 1 {
+            messages.drain(1..messages.len() - MAX_SESSION_MESSAGES);
+            // A tool result cannot lead the history without the call that produced it
+            while messages.get(1).map_or(false, |message| message["role"] == "tool") {
+                messages.remove(1);
+            }
+        }
+        session.messages = messages;
+    }
+
+    let speech = if assistant.speak_responses {
+        let filter_config = settings.content_filters.config_for(None);
+        let spoken = apply_content_filter(&text, &filter_config, OutputTarget::Speech).text;
+        let (model, voice) = resolve_voice(
+            &settings.voices.custom_voices,
+            &settings.ai_ml_settings.voice_model,
+            assistant.voice.clone(),
+        );
+        match speech_stream::start_stream(app.clone(), state.ai_ml_gateway.clone(), &spoken, model, voice, settings.language.clone())
+            .await
+        {
+            Ok(status) => Some(status),
+            Err(e) => {
+                log::warn!("Could not speak the assistant reply: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let reply = AssistantReply {
+        session_id: session_id.to_string(),
+        text,
+        intent,
+        tool_calls: invocations,
+        speech,
+    };
+    let _ = app.emit_all("assistant-reply", &reply);
+    Ok(reply)
+}
+
+/// Finds earlier dictations containing the query
+struct HistorySearchTool;
+
+#[async_trait]
+impl AssistantTool for HistorySearchTool {
+    fn name(&self) -> &str {
+        "search_history"
+    }
+    fn description(&self) -> &str {
+        "Search the user's recent dictations for text or topic labels containing the query, newest first."
+    }
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "query": { "type": "string", "description": "Words to look for" },
+                "limit": { "type": "integer", "minimum": 1, "maximum": MAX_HISTORY_RESULTS }
+            },
+            "required": ["query"]
+        })
+    }
+
+    async fn call(&self, arguments: Value, _context: &ToolContext) -> Result<Value, AppError> {
+        let query = arguments["query"].as_str().unwrap_or_default().to_lowercase();
+        let limit = arguments["limit"]
+            .as_u64()
+            .map_or(5, |limit| limit as usize)
+            .clamp(1, MAX_HISTORY_RESULTS);
+        let segments = get_transcript_history().lock().await.list(usize::MAX, None);
+        let matches: Vec<Value> = segments
+            .into_iter()
+            .filter(|segment| {
+                segment.text.to_lowercase().contains(&query)
+                    || segment.topic.as_ref().map_or(false, |topic| topic.to_lowercase().contains(&query))
+            })
+            .take(limit)
+            .map(|segment| {
+                json!({
+                    "text": segment.text,
+                    "topic": segment.topic,
+                    "created_at": segment.created_at,
+                })
+            })
+            .collect();
+        Ok(json!({ "matches": matches }))
+    }
+}
+
+/// Puts text on the clipboard through the frontend, which owns clipboard access
+struct ClipboardTool;
+
+#[async_trait]
+impl AssistantTool for ClipboardTool {
+    fn name(&self) -> &str {
+        "copy_to_clipboard"
+    }
+    fn description(&self) -> &str {
+        "Copy text to the user's clipboard."
+    }
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": { "text": { "type": "string" } },
+            "required": ["text"]
+        })
+    }
+
+    async fn call(&self, arguments: Value, context: &ToolContext) -> Result<Value, AppError> {
+        let text = arguments["text"].as_str().unwrap_or_default();
+        if text.is_empty() {
+            return Err(AppError::Validation(ValidationError::InvalidConfigValue(
+                "Nothing to copy".to_string(),
+            )));
+        }
+        let previous = clipboard_text().await;
+        context
+            .app
+            .emit_all("clipboard-write", json!({ "text": text }))
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+        undo_history::record(TextAction::ClipboardWrite {
+            text: text.to_string(),
+            previous,
+        })
+        .await;
+        Ok(json!({ "copied": true }))
+    }
+}
+
+/// Creates a calendar entry at the reminder time
+struct ReminderTool;
+
+#[async_trait]
+impl AssistantTool for ReminderTool {
+    fn name(&self) -> &str {
+        "set_reminder"
+    }
+    fn description(&self) -> &str {
+        "Set a reminder as a calendar entry, either a number of minutes from now or at an RFC 3339 time."
+    }
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "text": { "type": "string", "description": "What to be reminded of" },
+                "minutes_from_now": { "type": "integer", "minimum": 1 },
+                "at": { "type": "string", "description": "RFC 3339 date and time" }
+            },
+            "required": ["text"]
+        })
+    }
+
+    async fn call(&self, arguments: Value, context: &ToolContext) -> Result<Value, AppError> {
+        let text = arguments["text"].as_str().unwrap_or("Reminder").to_string();
+        let start = match (arguments["at"].as_str(), arguments["minutes_from_now"].as_i64()) {
+            (Some(at), _) => DateTime::parse_from_rfc3339(at)
+                .map(|at| at.with_timezone(&Utc))
+                .map_err(|e| AppError::Validation(ValidationError::InvalidConfigValue(format!("Invalid time {}: {}", at, e))))?,
+            (None, Some(minutes)) => Utc::now() + ChronoDuration::minutes(minutes.max(1)),
+            (None, None) => Utc::now() + ChronoDuration::minutes(60),
+        };
+        let metadata = DestinationMetadata {
+            subject: Some(text.clone()),
+            start: Some(start.to_rfc3339()),
+            duration_minutes: Some(15),
+            ..Default::default()
+        };
+        let destinations = context.state.settings.snapshot().destinations.clone();
+        let receipt = send_to_destination("calendar", &text, &metadata, &destinations).await?;
+        Ok(json!({ "scheduled_for": start.to_rfc3339(), "message": receipt.message }))
+    }
+}
+
+/// Reads the text on screen so requests like "summarize this" can refer to what the user is looking at
+struct ScreenTextTool;
+
+#[async_trait]
+impl AssistantTool for ScreenTextTool {
+    fn name(&self) -> &str {
+        SCREEN_TOOL
+    }
+    fn description(&self) -> &str {
+        "Read the text currently visible on the user's screen, or in a region of it, when they refer to \"this\" or what they are looking at."
+    }
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "region": {
+                    "type": "object",
+                    "description": "Part of the screen in pixels; the whole screen when omitted",
+                    "properties": {
+                        "x": { "type": "integer" },
+                        "y": { "type": "integer" },
+                        "width": { "type": "integer", "minimum": 1 },
+                        "height": { "type": "integer", "minimum": 1 }
+                    },
+                    "required": ["x", "y", "width", "height"]
+                }
+            }
+        })
+    }
+
+    async fn call(&self, arguments: Value, context: &ToolContext) -> Result<Value, AppError> {
+        let settings = context.state.settings.snapshot();
+        if settings.voice_recognition.privacy_mode {
+            return Err(AppError::Permission("Screen text is not shared with the assistant in privacy mode".to_string()));
+        }
+        let region = match arguments.get("region").filter(|region| !region.is_null()) {
+            Some(region) => Some(serde_json::from_value::<ScreenRegion>(region.clone()).map_err(|e| {
+                AppError::Validation(ValidationError::InvalidConfigValue(format!("Invalid screen region: {}", e)))
+            })?),
+            None => None,
+        };
+        let screen = read_screen(&settings.screen_ocr, region).await?;
+        Ok(json!({ "text": screen.text, "truncated": screen.truncated }))
+    }
+}
+
+struct WebhookTool(WebhookToolConfig);
+
+#[async_trait]
+impl AssistantTool for WebhookTool {
+    fn name(&self) -> &str {
+        &self.0.name
+    }
+    fn description(&self) -> &str {
+        &self.0.description
+    }
+    fn parameters(&self) -> Value {
+        self.0.parameters.clone()
+    }
+
+    async fn call(&self, arguments: Value, _context: &ToolContext) -> Result<Value, AppError> {
+        post_json(&self.0.url, &self.0.headers, &arguments).await
+    }
+}
+
+/// POST `body` to a user-configured webhook, returning its status and parsed response
+pub(crate) async fn post_json(url: &str, headers: &HashMap<String, String>, body: &Value) -> Result<Value, AppError> {
+    let client = crate::network::client(std::time::Duration::from_secs(15))?;
+    let mut request = client.post(url).json(body);
+    for (name, value) in headers {
+        request = request.header(name, value);
+    }
+    let response = request.send().await.map_err(|e| AppError::Network(e.to_string()))?;
+    let status = response.status().as_u16();
+    let text = response.text().await.unwrap_or_default();
+    let response_body = serde_json::from_str::<Value>(&text).unwrap_or(Value::String(text));
+    if !(200..300).contains(&status) {
+        return Err(AppError::Network(format!("Webhook {} returned {}: {}", url, status, response_body)));
+    }
+    Ok(json!({ "status": status, "body": response_body }))
+}
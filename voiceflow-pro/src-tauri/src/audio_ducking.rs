@@ -0,0 +1,361 @@
+//! Audio ducking for VoiceFlow Pro
+//! Lowers other applications' volume while the user dictates or speech plays, and restores it afterwards
+
+use std::collections::BTreeSet;
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tokio::sync::{watch, Mutex};
+
+use crate::integrations::voice_recognition::VoiceEngineStatus;
+
+#[cfg(any(target_os = "macos", target_os = "linux", target_os = "windows"))]
+use crate::system_activity::command_output;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DuckingSettings {
+    pub enabled: bool,
+    pub while_dictating: bool,
+    pub while_speaking: bool,
+    /// Fraction of their volume other applications keep while ducked
+    pub level: f32,
+    /// Players ducked on macOS, which has no per-application volume for other apps
+    pub media_apps: Vec<String>,
+}
+
+impl Default for DuckingSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            while_dictating: true,
+            while_speaking: true,
+            level: 0.3,
+            media_apps: vec!["Music".to_string(), "Spotify".to_string()],
+        }
+    }
+}
+
+/// Why other audio is lowered; ducking lasts until every reason is released
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuckReason {
+    Dictation,
+    Speech,
+}
+
+/// Sent as "audio-ducking" whenever ducking starts or ends
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DuckingStatus {
+    pub active: bool,
+    pub reasons: Vec<DuckReason>,
+    /// Audio sessions or players whose volume was lowered
+    pub ducked_sessions: usize,
+}
+
+/// A session's volume before ducking, keyed the way the platform addresses it
+#[derive(Debug, Clone)]
+struct SavedVolume {
+    key: String,
+    volume: f32,
+}
+
+#[derive(Debug, Default)]
+struct AudioDucker {
+    reasons: BTreeSet<DuckReason>,
+    saved: Vec<SavedVolume>,
+}
+
+impl AudioDucker {
+    fn status(&self) -> DuckingStatus {
+        DuckingStatus {
+            active: !self.reasons.is_empty(),
+            reasons: self.reasons.iter().copied().collect(),
+            ducked_sessions: self.saved.len(),
+        }
+    }
+}
+
+static AUDIO_DUCKER: OnceLock<Mutex<AudioDucker>> = OnceLock::new();
+
+fn ducker() -> &'static Mutex<AudioDucker> {
+    AUDIO_DUCKER.get_or_init(|| Mutex::new(AudioDucker::default()))
+}
+
+pub async fn ducking_status() -> DuckingStatus {
+    ducker().lock().await.status()
+}
+
+/// Lower other audio for `reason`, if the settings ask for it; the first reason does the ducking
+pub async fn engage(reason: DuckReason, settings: &DuckingSettings, app: &AppHandle) {
+    let wanted = settings.enabled
+        && match reason {
+            DuckReason::Dictation => settings.while_dictating,
+            DuckReason::Speech => settings.while_speaking,
+        };
+    if !wanted {
+        return;
+    }
+
+    let mut ducker = ducker().lock().await;
+    if !ducker.reasons.insert(reason) {
+        return;
+    }
+    if ducker.reasons.len() == 1 {
+        ducker.saved = duck_other_audio(settings.level.clamp(0.0, 1.0), &settings.media_apps).await;
+        log::debug!("Ducked {} audio sessions for {:?}", ducker.saved.len(), reason);
+    }
+    let _ = app.emit_all("audio-ducking", ducker.status());
+}
+
+/// Duck for dictation until the engine stops listening, by any path: a stop or pause command, silence,
+/// a muted microphone, an error or a profile switch
+pub async fn engage_while_listening(
+    mut engine_status: watch::Receiver<VoiceEngineStatus>,
+    settings: &DuckingSettings,
+    app: &AppHandle,
+) {
+    engage(DuckReason::Dictation, settings, app).await;
+    if !ducker().lock().await.reasons.contains(&DuckReason::Dictation) {
+        return;
+    }
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            if !engine_status.borrow_and_update().state.is_listening() {
+                break;
+            }
+            // A closed channel means the engine is gone, which stops listening too
+            if engine_status.changed().await.is_err() {
+                break;
+            }
+        }
+        release(DuckReason::Dictation, &app).await;
+    });
+}
+
+/// Drop `reason`; volumes are restored once no reason is left
+pub async fn release(reason: DuckReason, app: &AppHandle) {
+    let mut ducker = ducker().lock().await;
+    if !ducker.reasons.remove(&reason) {
+        return;
+    }
+    if ducker.reasons.is_empty() {
+        restore_volumes(&std::mem::take(&mut ducker.saved)).await;
+    }
+    let _ = app.emit_all("audio-ducking", ducker.status());
+}
+
+/// Restore every ducked volume regardless of reason, e.g. before the app quits
+pub async fn restore_all() {
+    let mut ducker = ducker().lock().await;
+    ducker.reasons.clear();
+    restore_volumes(&std::mem::take(&mut ducker.saved)).await;
+}
+
+/// Parse the "pid volume" lines the session script prints
+#[cfg(target_os = "windows")]
+fn parse_saved(output: &str) -> Vec<SavedVolume> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (key, volume) = line.trim().rsplit_once(' ')?;
+            Some(SavedVolume {
+                key: key.trim().to_string(),
+                volume: volume.trim().parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+#[cfg(target_os = "windows")]
+const SESSION_VOLUME_SOURCE: &str = r#"
+using System;
+using System.Collections.Generic;
+using System.Globalization;
+using System.Runtime.InteropServices;
+using System.Text;
+
+[ComImport, Guid("BCDE0395-E52F-467C-8E3D-C4579291692E")] class MMDeviceEnumerator {}
+[ComImport, Guid("A95664D2-9614-4F35-A746-DE8DB63617E6"), InterfaceType(ComInterfaceType.InterfaceIsIUnknown)]
+interface IMMDeviceEnumerator { int EnumAudioEndpoints(int flow, int mask, out IntPtr devices); int GetDefaultAudioEndpoint(int flow, int role, out IMMDevice device); }
+[ComImport, Guid("D666063F-1587-4E43-81F1-B948E807363F"), InterfaceType(ComInterfaceType.InterfaceIsIUnknown)]
+interface IMMDevice { int Activate(ref Guid iid, int context, IntPtr parameters, [MarshalAs(UnmanagedType.IUnknown)] out object target); }
+[ComImport, Guid("77AA99A0-1BD6-484F-8BC7-2C654C9A9B6F"), InterfaceType(ComInterfaceType.InterfaceIsIUnknown)]
+interface IAudioSessionManager2 { int GetAudioSessionControl(IntPtr guid, int flags, out IntPtr control); int GetSimpleAudioVolume(IntPtr guid, int flags, out IntPtr volume); int GetSessionEnumerator(out IAudioSessionEnumerator sessions); }
+[ComImport, Guid("E2F5BB11-0570-40CA-ACDD-3AA01277DEE8"), InterfaceType(ComInterfaceType.InterfaceIsIUnknown)]
+interface IAudioSessionEnumerator { int GetCount(out int count); int GetSession(int index, out IAudioSessionControl2 session); }
+[ComImport, Guid("bfb7ff88-7239-4fc9-8fa2-07c950be9c6d"), InterfaceType(ComInterfaceType.InterfaceIsIUnknown)]
+interface IAudioSessionControl2 {
+    int GetState(out int state); int GetDisplayName(out IntPtr name); int SetDisplayName(IntPtr name, ref Guid context);
+    int GetIconPath(out IntPtr path); int SetIconPath(IntPtr path, ref Guid context); int GetGroupingParam(out Guid grouping);
+    int SetGroupingParam(ref Guid grouping, ref Guid context); int RegisterAudioSessionNotification(IntPtr client);
+    int UnregisterAudioSessionNotification(IntPtr client); int GetSessionIdentifier(out IntPtr id);
+    int GetSessionInstanceIdentifier(out IntPtr id); int GetProcessId(out uint pid);
+}
+[ComImport, Guid("87CE5498-68D6-44E5-9215-6F24A7C3D6E4"), InterfaceType(ComInterfaceType.InterfaceIsIUnknown)]
+interface ISimpleAudioVolume { int SetMasterVolume(float level, ref Guid context); int GetMasterVolume(out float level); }
+
+public static class SessionVolumes {
+    static IAudioSessionEnumerator Sessions() {
+        var devices = (IMMDeviceEnumerator)(new MMDeviceEnumerator());
+        IMMDevice device; devices.GetDefaultAudioEndpoint(0, 1, out device);
+        Guid iid = typeof(IAudioSessionManager2).GUID; object manager;
+        device.Activate(ref iid, 23, IntPtr.Zero, out manager);
+        IAudioSessionEnumerator sessions; ((IAudioSessionManager2)manager).GetSessionEnumerator(out sessions);
+        return sessions;
+    }
+    public static string Duck(float level, uint[] keep) {
+        var saved = new StringBuilder(); var sessions = Sessions(); int count; sessions.GetCount(out count); Guid context = Guid.Empty;
+        for (int i = 0; i < count; i++) {
+            IAudioSessionControl2 session; sessions.GetSession(i, out session); uint pid;
+            if (session.GetProcessId(out pid) != 0 || pid == 0 || Array.IndexOf(keep, pid) >= 0) continue;
+            var volume = (ISimpleAudioVolume)session; float current; volume.GetMasterVolume(out current);
+            saved.AppendLine(pid + " " + current.ToString(CultureInfo.InvariantCulture));
+            volume.SetMasterVolume(current * level, ref context);
+        }
+        return saved.ToString();
+    }
+    public static void Restore(string saved) {
+        var levels = new Dictionary<uint, float>();
+        foreach (var line in saved.Split(new[] { ';' }, StringSplitOptions.RemoveEmptyEntries)) {
+            var parts = line.Split(' '); levels[uint.Parse(parts[0])] = float.Parse(parts[1], CultureInfo.InvariantCulture);
+        }
+        var sessions = Sessions(); int count; sessions.GetCount(out count); Guid context = Guid.Empty;
+        for (int i = 0; i < count; i++) {
+            IAudioSessionControl2 session; sessions.GetSession(i, out session); uint pid; float level;
+            if (session.GetProcessId(out pid) == 0 && levels.TryGetValue(pid, out level)) ((ISimpleAudioVolume)session).SetMasterVolume(level, ref context);
+        }
+    }
+}
+"#;
+
+#[cfg(target_os = "windows")]
+async fn run_session_script(call: &str) -> Option<String> {
+    // Our own process tree is kept at full volume; speech plays from the WebView's child processes
+    let script = format!(
+        "Add-Type -TypeDefinition @'\n{}\n'@; $all = Get-CimInstance Win32_Process | Select-Object ProcessId, ParentProcessId; $keep = @([uint32]{}); do {{ $more = @($all | Where-Object {{ $keep -contains $_.ParentProcessId -and $keep -notcontains $_.ProcessId }} | ForEach-Object {{ [uint32]$_.ProcessId }}); $keep += $more }} while ($more.Count -gt 0); {}",
+        SESSION_VOLUME_SOURCE,
+        std::process::id(),
+        call
+    );
+    command_output("powershell", &["-NoProfile", "-NonInteractive", "-Command", &script]).await
+}
+
+#[cfg(target_os = "windows")]
+async fn duck_other_audio(level: f32, _media_apps: &[String]) -> Vec<SavedVolume> {
+    let call = format!("[SessionVolumes]::Duck([float]{}, [uint32[]]$keep)", level);
+    run_session_script(&call).await.map(|output| parse_saved(&output)).unwrap_or_default()
+}
+
+#[cfg(target_os = "windows")]
+async fn restore_volumes(saved: &[SavedVolume]) {
+    if saved.is_empty() {
+        return;
+    }
+    let levels: Vec<String> = saved.iter().map(|s| format!("{} {}", s.key, s.volume)).collect();
+    let call = format!("[SessionVolumes]::Restore('{}')", levels.join(";"));
+    if run_session_script(&call).await.is_none() {
+        log::warn!("Could not restore the volume of {} audio sessions", saved.len());
+    }
+}
+
+#[cfg(target_os = "macos")]
+async fn duck_other_audio(level: f32, media_apps: &[String]) -> Vec<SavedVolume> {
+    let Some(running) = command_output(
+        "osascript",
+        &["-e", "tell application \"System Events\" to get name of every application process"],
+    )
+    .await
+    else {
+        return Vec::new();
+    };
+    let running: Vec<&str> = running.trim().split(", ").collect();
+
+    let mut saved = Vec::new();
+    // Only running players are addressed; naming one that is not installed would open a file dialog
+    for app in media_apps.iter().filter(|app| running.contains(&app.as_str())) {
+        let get = format!("tell application \"{}\" to get sound volume", app);
+        let Some(volume) = command_output("osascript", &["-e", &get]).await.and_then(|v| v.trim().parse::<f32>().ok()) else {
+            continue;
+        };
+        let set = format!("tell application \"{}\" to set sound volume to {}", app, (volume * level).round() as u32);
+        if command_output("osascript", &["-e", &set]).await.is_some() {
+            saved.push(SavedVolume { key: app.clone(), volume });
+        }
+    }
+    saved
+}
+
+#[cfg(target_os = "macos")]
+async fn restore_volumes(saved: &[SavedVolume]) {
+    for entry in saved {
+        let set = format!("tell application \"{}\" to set sound volume to {}", entry.key, entry.volume.round() as u32);
+        if command_output("osascript", &["-e", &set]).await.is_none() {
+            log::warn!("Could not restore the volume of {}", entry.key);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+async fn duck_other_audio(level: f32, _media_apps: &[String]) -> Vec<SavedVolume> {
+    let Some(listing) = command_output("pactl", &["list", "sink-inputs"]).await else {
+        return Vec::new();
+    };
+    let own_pid = std::process::id();
+
+    let mut saved = Vec::new();
+    for block in listing.split("Sink Input #").skip(1) {
+        let Some(index) = block.lines().next().map(|line| line.trim().to_string()) else {
+            continue;
+        };
+        let pid = block
+            .lines()
+            .find_map(|line| line.trim().strip_prefix("application.process.id = "))
+            .and_then(|pid| pid.trim_matches('"').parse::<u32>().ok());
+        // Our WebView's web process plays the speech, so the app's own children keep their volume
+        if pid.map_or(false, |pid| pid == own_pid || parent_pid(pid) == Some(own_pid)) {
+            continue;
+        }
+        let Some(volume) = block
+            .lines()
+            .find(|line| line.trim_start().starts_with("Volume:"))
+            .and_then(|line| line.split('/').nth(1))
+            .and_then(|percent| percent.trim().trim_end_matches('%').parse::<f32>().ok())
+        else {
+            continue;
+        };
+        let ducked = format!("{}%", (volume * level).round() as u32);
+        if command_output("pactl", &["set-sink-input-volume", &index, &ducked]).await.is_some() {
+            saved.push(SavedVolume { key: index, volume });
+        }
+    }
+    saved
+}
+
+#[cfg(target_os = "linux")]
+fn parent_pid(pid: u32) -> Option<u32> {
+    std::fs::read_to_string(format!("/proc/{}/status", pid))
+        .ok()?
+        .lines()
+        .find_map(|line| line.strip_prefix("PPid:"))
+        .and_then(|ppid| ppid.trim().parse().ok())
+}
+
+#[cfg(target_os = "linux")]
+async fn restore_volumes(saved: &[SavedVolume]) {
+    for entry in saved {
+        let volume = format!("{}%", entry.volume.round() as u32);
+        // The stream may have ended while ducked, which is fine
+        let _ = command_output("pactl", &["set-sink-input-volume", &entry.key, &volume]).await;
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
+async fn duck_other_audio(_level: f32, _media_apps: &[String]) -> Vec<SavedVolume> {
+    Vec::new()
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
+async fn restore_volumes(_saved: &[SavedVolume]) {}
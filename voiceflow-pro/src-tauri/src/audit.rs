@@ -0,0 +1,348 @@
+//! Request audit log for VoiceFlow Pro
+//! Records every outbound AI request with its model, token counts and outcome, never the text itself
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::mpsc;
+
+use crate::errors::AppError;
+use crate::integrations::content_filter::{redact_pii, PiiKind};
+use crate::integrations::{AIMLError, AIMLUsage};
+use crate::storage::{data_path, ensure_data_dir, DataDir};
+
+const LOG_FILE: &str = "audit.jsonl";
+/// Entries returned by `get_audit_log` when the filter sets no limit
+const DEFAULT_LIMIT: usize = 500;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuditSettings {
+    pub enabled: bool,
+    /// The log is rotated once it grows past this size
+    pub max_file_kb: u64,
+    /// Rotated files kept besides the current one; older ones are deleted
+    pub max_files: usize,
+}
+
+impl Default for AuditSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_file_kb: 1024,
+            max_files: 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditService {
+    Chat,
+    ToolChat,
+    Transcription,
+    Speech,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub timestamp: u64,
+    pub service: AuditService,
+    pub model: String,
+    /// SHA-256 of the prompt after PII redaction, so identical prompts can be matched without storing them;
+    /// absent for audio uploads
+    pub prompt_hash: Option<String>,
+    pub prompt_chars: usize,
+    pub prompt_tokens: Option<u32>,
+    pub completion_tokens: Option<u32>,
+    pub total_tokens: Option<u32>,
+    /// HTTP status when the service answered
+    pub status: Option<u16>,
+    pub success: bool,
+    /// Error code as sent to the frontend, see `errors::ErrorCode`
+    pub error_code: Option<String>,
+    pub duration_ms: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuditFilter {
+    pub service: Option<AuditService>,
+    pub model: Option<String>,
+    /// Unix seconds, inclusive
+    pub since: Option<u64>,
+    pub until: Option<u64>,
+    pub failures_only: bool,
+    pub limit: Option<usize>,
+}
+
+impl AuditFilter {
+    fn matches(&self, entry: &AuditEntry) -> bool {
+        self.service.map_or(true, |service| service == entry.service)
+            && self.model.as_deref().map_or(true, |model| model.eq_ignore_ascii_case(&entry.model))
+            && self.since.map_or(true, |since| entry.timestamp >= since)
+            && self.until.map_or(true, |until| entry.timestamp <= until)
+            && (!self.failures_only || !entry.success)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditExportFormat {
+    Json,
+    Csv,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditExport {
+    pub path: String,
+    pub entries: usize,
+}
+
+static SETTINGS: OnceLock<Mutex<AuditSettings>> = OnceLock::new();
+static WRITER: OnceLock<mpsc::UnboundedSender<(AuditSettings, AuditEntry)>> = OnceLock::new();
+
+fn settings() -> &'static Mutex<AuditSettings> {
+    SETTINGS.get_or_init(|| Mutex::new(AuditSettings::default()))
+}
+
+/// Entries are appended and rotated on one dedicated thread, in the order they were recorded, so requests
+/// never wait on the disk and concurrent lines never interleave
+fn writer() -> &'static mpsc::UnboundedSender<(AuditSettings, AuditEntry)> {
+    WRITER.get_or_init(|| {
+        let (tx, mut rx) = mpsc::unbounded_channel::<(AuditSettings, AuditEntry)>();
+        let spawned = std::thread::Builder::new().name("audit-writer".to_string()).spawn(move || {
+            while let Some((audit, entry)) = rx.blocking_recv() {
+                if let Err(e) = append(&audit, &entry) {
+                    log::warn!("Failed to write audit log: {}", e);
+                }
+            }
+        });
+        if let Err(e) = spawned {
+            log::warn!("Audit log writer did not start: {}", e);
+        }
+        tx
+    })
+}
+
+/// Apply the active profile's audit settings
+pub fn configure(audit: &AuditSettings) {
+    let mut current = match settings().lock() {
+        Ok(current) => current,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    *current = audit.clone();
+}
+
+/// Queue one request for the log if auditing is enabled; failures to write are logged, never returned
+pub fn record(
+    service: AuditService,
+    model: &str,
+    prompt: &str,
+    started: Instant,
+    outcome: Result<Option<&AIMLUsage>, &AIMLError>,
+) {
+    let audit = match settings().lock() {
+        Ok(audit) => audit.clone(),
+        Err(poisoned) => poisoned.into_inner().clone(),
+    };
+    if !audit.enabled {
+        return;
+    }
+
+    let (status, error_code) = match outcome {
+        Ok(_) => (Some(200), None),
+        Err(e) => (error_status(e), Some(error_code(e).to_string())),
+    };
+    let usage = outcome.ok().flatten();
+    let entry = AuditEntry {
+        timestamp: now_secs(),
+        service,
+        model: model.to_string(),
+        prompt_hash: (!prompt.is_empty()).then(|| prompt_hash(prompt)),
+        prompt_chars: prompt.chars().count(),
+        prompt_tokens: usage.map(|usage| usage.prompt_tokens),
+        completion_tokens: usage.map(|usage| usage.completion_tokens),
+        total_tokens: usage.map(|usage| usage.total_tokens),
+        status,
+        success: outcome.is_ok(),
+        error_code,
+        duration_ms: started.elapsed().as_millis() as u64,
+    };
+
+    if writer().send((audit, entry)).is_err() {
+        log::warn!("Audit log writer is not running; entry dropped");
+    }
+}
+
+/// Entries matching `filter`, newest first
+pub fn read(filter: &AuditFilter) -> Result<Vec<AuditEntry>, AppError> {
+    let dir = data_path(DataDir::Audit)?;
+    let max_files = settings().lock().map(|audit| audit.max_files).unwrap_or(0);
+    let mut entries = Vec::new();
+    for path in (0..=max_files).rev().map(|index| log_path(&dir, index)).filter(|path| path.exists()) {
+        let contents = fs::read_to_string(&path)
+            .map_err(|e| AppError::Internal(format!("Failed to read {}: {}", path.display(), e)))?;
+        // A line cut short by a crash is skipped rather than failing the whole log
+        entries.extend(
+            contents
+                .lines()
+                .filter_map(|line| serde_json::from_str::<AuditEntry>(line).ok())
+                .filter(|entry| filter.matches(entry)),
+        );
+    }
+    entries.reverse();
+    entries.truncate(filter.limit.unwrap_or(DEFAULT_LIMIT));
+    Ok(entries)
+}
+
+/// Write the matching entries to `path`, which must be absolute
+pub fn export(path: &Path, format: AuditExportFormat, filter: &AuditFilter) -> Result<AuditExport, AppError> {
+    if !path.is_absolute() || path.components().any(|c| c == std::path::Component::ParentDir) {
+        return Err(AppError::Validation(
+            "Export path must be an absolute path without '..'".to_string().into(),
+        ));
+    }
+    let filter = AuditFilter {
+        limit: Some(filter.limit.unwrap_or(usize::MAX)),
+        ..filter.clone()
+    };
+    let entries = read(&filter)?;
+    let contents = match format {
+        AuditExportFormat::Json => serde_json::to_string_pretty(&entries)?,
+        AuditExportFormat::Csv => to_csv(&entries),
+    };
+    fs::write(path, contents)
+        .map_err(|e| AppError::Internal(format!("Failed to write {}: {}", path.display(), e)))?;
+    Ok(AuditExport {
+        path: path.display().to_string(),
+        entries: entries.len(),
+    })
+}
+
+fn append(audit: &AuditSettings, entry: &AuditEntry) -> Result<(), AppError> {
+    let dir = ensure_data_dir(DataDir::Audit)?;
+    let current = log_path(&dir, 0);
+    let size = fs::metadata(&current).map(|m| m.len()).unwrap_or(0);
+    if size >= audit.max_file_kb.max(1) * 1024 {
+        rotate(&dir, audit.max_files)?;
+    }
+
+    let mut line = serde_json::to_vec(entry)?;
+    line.push(b'\n');
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&current)
+        .and_then(|mut file| file.write_all(&line))
+        .map_err(|e| AppError::Internal(format!("Failed to append to {}: {}", current.display(), e)))
+}
+
+/// Shift audit.N.jsonl up by one, dropping the oldest, and start a fresh current file
+fn rotate(dir: &Path, max_files: usize) -> Result<(), AppError> {
+    let _ = fs::remove_file(log_path(dir, max_files));
+    for index in (0..max_files).rev() {
+        let from = log_path(dir, index);
+        if from.exists() {
+            fs::rename(&from, log_path(dir, index + 1))
+                .map_err(|e| AppError::Internal(format!("Failed to rotate {}: {}", from.display(), e)))?;
+        }
+    }
+    Ok(())
+}
+
+/// The current log at index 0, rotated files after it from newest to oldest
+fn log_path(dir: &Path, index: usize) -> PathBuf {
+    match index {
+        0 => dir.join(LOG_FILE),
+        n => dir.join(format!("audit.{}.jsonl", n)),
+    }
+}
+
+fn prompt_hash(prompt: &str) -> String {
+    let redacted = redact_pii(prompt, &PiiKind::ALL).text;
+    Sha256::digest(redacted.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn error_status(error: &AIMLError) -> Option<u16> {
+    match error {
+        AIMLError::ApiError { status, .. } => Some(*status),
+        AIMLError::AuthError(_) => Some(401),
+        AIMLError::RateLimitExceeded { .. } => Some(429),
+        AIMLError::HttpClientError(e) => e.status().map(|status| status.as_u16()),
+        _ => None,
+    }
+}
+
+/// Same codes the frontend sees for the error, without consuming it
+fn error_code(error: &AIMLError) -> &'static str {
+    match error {
+        AIMLError::HttpClientError(e) if e.is_timeout() => "timeout",
+        AIMLError::HttpClientError(_) | AIMLError::NetworkError(_) => "network",
+        AIMLError::ApiError { status: 401 | 403, .. } | AIMLError::AuthError(_) => "auth_failed",
+        AIMLError::ApiError { status: 408 | 504, .. } | AIMLError::Timeout(_) => "timeout",
+        AIMLError::ApiError { status: 429, .. } | AIMLError::RateLimitExceeded { .. } => "rate_limited",
+        AIMLError::ApiError { status: 502 | 503, .. } | AIMLError::ServiceUnavailable(_) => "service_unavailable",
+        AIMLError::ApiError { .. } => "upstream_error",
+        AIMLError::InvalidModel(_) => "invalid_model",
+        AIMLError::MissingParameter(_) => "validation",
+        AIMLError::JsonError(_) => "internal",
+    }
+}
+
+fn to_csv(entries: &[AuditEntry]) -> String {
+    let mut csv = String::from(
+        "timestamp,service,model,prompt_hash,prompt_chars,prompt_tokens,completion_tokens,total_tokens,status,success,error_code,duration_ms\n",
+    );
+    let optional = |value: Option<u32>| value.map(|v| v.to_string()).unwrap_or_default();
+    for entry in entries {
+        let service = serde_json::to_value(entry.service)
+            .ok()
+            .and_then(|value| value.as_str().map(str::to_string))
+            .unwrap_or_default();
+        let fields = [
+            entry.timestamp.to_string(),
+            service,
+            csv_field(&entry.model),
+            entry.prompt_hash.clone().unwrap_or_default(),
+            entry.prompt_chars.to_string(),
+            optional(entry.prompt_tokens),
+            optional(entry.completion_tokens),
+            optional(entry.total_tokens),
+            entry.status.map(|s| s.to_string()).unwrap_or_default(),
+            entry.success.to_string(),
+            csv_field(entry.error_code.as_deref().unwrap_or_default()),
+            entry.duration_ms.to_string(),
+        ];
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+/// Quote fields containing separators; leading formula characters are escaped for spreadsheet apps
+fn csv_field(value: &str) -> String {
+    let value = match value.starts_with(['=', '+', '-', '@']) {
+        true => format!("'{}", value),
+        false => value.to_string(),
+    };
+    match value.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", value.replace('"', "\"\"")),
+        false => value,
+    }
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
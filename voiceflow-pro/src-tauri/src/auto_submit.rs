@@ -0,0 +1,250 @@
+//! Silence auto-submit for VoiceFlow Pro
+//! Ends a dictation segment after the speaker falls silent, so text appears without touching the keyboard
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tokio::time::Instant;
+
+use crate::audio_ducking::{self, DuckReason};
+use crate::errors::{AppError, ValidationError};
+use crate::integrations::voice_recognition::EngineState;
+use crate::AppState;
+
+/// Part of the profile's settings, so each profile keeps its own thresholds: a quiet office and a
+/// noisy car need different amounts of silence
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutoSubmitSettings {
+    pub enabled: bool,
+    /// Silence after speech that ends the segment
+    pub silence_ms: u64,
+    /// Speech needed before silence counts, so a cough or a click does not submit an empty segment
+    pub min_speech_ms: u64,
+    pub action: AutoSubmitAction,
+    /// Leave the microphone open after finalizing, ready for the next segment
+    pub keep_listening: bool,
+}
+
+impl Default for AutoSubmitSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            silence_ms: 2_000,
+            min_speech_ms: 400,
+            action: AutoSubmitAction::Submit,
+            keep_listening: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AutoSubmitAction {
+    /// Only stop listening; the segment is handled as when the user stops
+    Stop,
+    /// Finalize the segment and run the pipeline, holding the result for review instead of typing it
+    Finalize,
+    /// Finalize the segment, run the pipeline and type the result
+    Submit,
+}
+
+pub fn validate(settings: &AutoSubmitSettings) -> Result<(), AppError> {
+    let invalid = |message: String| AppError::Validation(ValidationError::InvalidConfigValue(message));
+    if !(300..=30_000).contains(&settings.silence_ms) {
+        return Err(invalid(format!(
+            "Auto-submit silence must be 300-30000 ms, got {}",
+            settings.silence_ms
+        )));
+    }
+    if settings.min_speech_ms > 10_000 {
+        return Err(invalid(format!(
+            "Auto-submit minimum speech must be at most 10000 ms, got {}",
+            settings.min_speech_ms
+        )));
+    }
+    if settings.keep_listening && settings.action == AutoSubmitAction::Stop {
+        return Err(invalid("Auto-submit cannot stop listening and keep listening".to_string()));
+    }
+    Ok(())
+}
+
+/// Sent as "auto-submit". For `finalize` and `submit` the segment has already been through the pipeline, so
+/// the frontend drops its copy; for `stop` it handles the segment as when the user stops
+#[derive(Debug, Clone, Serialize)]
+pub struct AutoSubmitEvent {
+    pub session_id: String,
+    pub action: AutoSubmitAction,
+    pub silence_ms: u64,
+    /// Speech heard in the segment
+    pub speech_ms: u64,
+    /// Result should be held for review rather than typed
+    pub preview: bool,
+    pub stopped_listening: bool,
+    /// The segment went through the pipeline; false for `stop`, an empty segment or a failed run
+    pub submitted: bool,
+}
+
+/// Session currently watched, so starting an already running session does not add a second watcher
+static WATCHED_SESSION: Mutex<Option<String>> = Mutex::new(None);
+/// Newest interim transcript of the segment being dictated, submitted when silence ends it
+static SEGMENT: Mutex<Option<String>> = Mutex::new(None);
+
+/// Keep the recognizer's latest interim transcript; each one replaces the last
+pub fn observe_interim(text: &str) {
+    if let Ok(mut segment) = SEGMENT.lock() {
+        *segment = Some(text.to_string());
+    }
+}
+
+/// Forget the current segment once it has been submitted, by auto-submit or by the frontend
+pub fn end_segment() {
+    take_segment();
+}
+
+fn take_segment() -> Option<String> {
+    SEGMENT.lock().ok()?.take().filter(|text| !text.trim().is_empty())
+}
+
+/// Watch a dictation session for the end of speech; returns at once when auto-submit is off
+pub async fn watch_session(state: &AppState, app: &AppHandle, session_id: &str) {
+    let settings = state.settings.snapshot().auto_submit.clone();
+    if !settings.enabled {
+        return;
+    }
+    let Some(engine) = state.voice_engine.lock().await.clone() else {
+        return;
+    };
+    {
+        let Ok(mut watched) = WATCHED_SESSION.lock() else {
+            return;
+        };
+        if watched.as_deref() == Some(session_id) {
+            return;
+        }
+        *watched = Some(session_id.to_string());
+    }
+    // Interim text from an earlier session is not part of this one
+    end_segment();
+
+    let state = state.clone();
+    let app = app.clone();
+    let session_id = session_id.to_string();
+    let mut status = engine.watch_status();
+    tauri::async_runtime::spawn(async move {
+        let silence = Duration::from_millis(settings.silence_ms);
+        let min_speech = Duration::from_millis(settings.min_speech_ms);
+        let mut speech_since: Option<Instant> = None;
+        let mut heard = Duration::ZERO;
+        let mut silent_since: Option<Instant> = None;
+
+        loop {
+            let deadline = silent_since.filter(|_| heard >= min_speech).map(|since| since + silence);
+            let timed_out = tokio::select! {
+                changed = status.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                    false
+                }
+                _ = tokio::time::sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => true,
+            };
+
+            let current = status.borrow().clone();
+            if current.session_id != session_id || matches!(current.state, EngineState::Idle | EngineState::Error(_)) {
+                break;
+            }
+            if current.state != EngineState::Listening {
+                // Paused or handed to monitoring; the segment starts over when listening resumes
+                speech_since = None;
+                silent_since = None;
+                heard = Duration::ZERO;
+                continue;
+            }
+
+            if timed_out {
+                let event = fire(&state, &app, &settings, &session_id, heard).await;
+                let _ = app.emit_all("auto-submit", &event);
+                if event.stopped_listening {
+                    break;
+                }
+                speech_since = None;
+                silent_since = None;
+                heard = Duration::ZERO;
+                continue;
+            }
+
+            if current.speech_detected {
+                speech_since.get_or_insert_with(Instant::now);
+                silent_since = None;
+            } else if let Some(since) = speech_since.take() {
+                heard += since.elapsed();
+                silent_since = Some(Instant::now());
+            }
+        }
+
+        if let Ok(mut watched) = WATCHED_SESSION.lock() {
+            if watched.as_deref() == Some(session_id.as_str()) {
+                *watched = None;
+            }
+        }
+    });
+}
+
+async fn fire(
+    state: &AppState,
+    app: &AppHandle,
+    settings: &AutoSubmitSettings,
+    session_id: &str,
+    heard: Duration,
+) -> AutoSubmitEvent {
+    log::info!(
+        "Auto-submit after {} ms of silence ({} ms of speech)",
+        settings.silence_ms,
+        heard.as_millis()
+    );
+    // Taken before stopping, so nothing the recognizer sends while it winds down is counted twice
+    let segment = take_segment();
+    let mut stopped_listening = false;
+    if !settings.keep_listening {
+        if let Some(engine) = state.voice_engine.lock().await.clone() {
+            match engine.stop().await {
+                Ok(status) => {
+                    stopped_listening = true;
+                    audio_ducking::release(DuckReason::Dictation, app).await;
+                    let _ = app.emit_all("voice-status", status.state.as_str());
+                }
+                Err(e) => log::warn!("Auto-submit could not stop listening: {}", e),
+            }
+        }
+    }
+
+    let preview = settings.action == AutoSubmitAction::Finalize;
+    let mut submitted = false;
+    let segment = segment.filter(|_| settings.action != AutoSubmitAction::Stop);
+    if let (Some(transcript), Some(window)) = (segment, app.get_window("main")) {
+        // The same path as an utterance the frontend finalizes: commands, pipeline, preview and injection
+        let outcome =
+            crate::process_utterance(state, &window, transcript, None, None, preview.then_some(true), None, None, None)
+                .await;
+        match outcome {
+            Ok(_) => submitted = true,
+            Err(e) => {
+                log::warn!("Auto-submit could not process the segment: {}", e);
+                let _ = window.emit("auto-submit-error", e.to_string());
+            }
+        }
+    }
+
+    AutoSubmitEvent {
+        session_id: session_id.to_string(),
+        action: settings.action,
+        silence_ms: settings.silence_ms,
+        speech_ms: heard.as_millis() as u64,
+        preview,
+        stopped_listening,
+        submitted,
+    }
+}
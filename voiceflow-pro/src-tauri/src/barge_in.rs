@@ -0,0 +1,176 @@
+//! Barge-in for VoiceFlow Pro
+//! Stops or pauses speech playback when the user starts talking over it, and hands the microphone to dictation
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tokio::sync::watch;
+
+use crate::audio_ducking::{self, DuckReason};
+use crate::integrations::speech_stream::{self, SpeechStreamAction};
+use crate::integrations::voice_recognition::EngineState;
+use crate::AppState;
+
+/// Speaker output picked up by the microphone also counts as speech, so barge-in is off until the
+/// user opts in, ideally with headphones or echo cancellation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BargeInSettings {
+    pub enabled: bool,
+    /// What happens to the speech being played
+    pub action: BargeInAction,
+    /// Start dictation once playback is interrupted
+    pub start_listening: bool,
+    /// Speech must last this long before it interrupts, so a cough does not
+    pub min_speech_ms: u64,
+}
+
+impl Default for BargeInSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            action: BargeInAction::Stop,
+            start_listening: true,
+            min_speech_ms: 300,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BargeInAction {
+    Pause,
+    Stop,
+}
+
+impl From<BargeInAction> for SpeechStreamAction {
+    fn from(action: BargeInAction) -> Self {
+        match action {
+            BargeInAction::Pause => SpeechStreamAction::Pause,
+            BargeInAction::Stop => SpeechStreamAction::Stop,
+        }
+    }
+}
+
+/// Sent as "barge-in" so the frontend applies the action to whatever it is playing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BargeInEvent {
+    pub action: BargeInAction,
+    pub stream_ids: Vec<String>,
+    pub started_listening: bool,
+}
+
+static PLAYBACK_ACTIVE: OnceLock<watch::Sender<bool>> = OnceLock::new();
+/// Set while the engine is monitoring on barge-in's behalf, so only that monitoring is ended afterwards
+static MONITORING: AtomicBool = AtomicBool::new(false);
+
+fn playback() -> &'static watch::Sender<bool> {
+    PLAYBACK_ACTIVE.get_or_init(|| watch::channel(false).0)
+}
+
+/// Speech playback began; watch for the user talking over it
+pub async fn playback_started(state: &AppState, app: &AppHandle) {
+    if playback().send_replace(true) {
+        return;
+    }
+    let settings = state.settings.snapshot().barge_in.clone();
+    if !settings.enabled {
+        return;
+    }
+    let Some(engine) = state.voice_engine.lock().await.clone() else {
+        return;
+    };
+
+    match engine.status().state {
+        EngineState::Listening | EngineState::Monitoring => {}
+        // A paused microphone stays paused
+        EngineState::Paused => return,
+        EngineState::Idle | EngineState::Error(_) => {
+            crate::bluetooth_audio::route_engine_input(state, &engine).await;
+            match engine.monitor().await {
+                Ok(_) => MONITORING.store(true, Ordering::SeqCst),
+                Err(e) => {
+                    log::warn!("Barge-in unavailable: {}", e);
+                    return;
+                }
+            }
+        }
+    }
+
+    let state = state.clone();
+    let app = app.clone();
+    let mut status = engine.watch_status();
+    let mut playing = playback().subscribe();
+    tauri::async_runtime::spawn(async move {
+        let min_speech = Duration::from_millis(settings.min_speech_ms);
+        let mut speech_since: Option<Instant> = None;
+        loop {
+            tokio::select! {
+                changed = status.changed() => if changed.is_err() { return; },
+                changed = playing.changed() => if changed.is_err() || !*playing.borrow() { return; },
+            }
+            if !status.borrow().speech_detected {
+                speech_since = None;
+                continue;
+            }
+            let since = *speech_since.get_or_insert_with(Instant::now);
+            if since.elapsed() >= min_speech {
+                break;
+            }
+        }
+
+        let stream_ids = speech_stream::control_all(&app, settings.action.into()).await;
+        playback().send_replace(false);
+        audio_ducking::release(DuckReason::Speech, &app).await;
+
+        let mut started_listening = false;
+        if settings.start_listening {
+            match engine.start().await {
+                Ok(status) => {
+                    MONITORING.store(false, Ordering::SeqCst);
+                    started_listening = true;
+                    let ducking = state.settings.snapshot().ducking.clone();
+                    audio_ducking::engage_while_listening(engine.watch_status(), &ducking, &app).await;
+                    crate::auto_submit::watch_session(&state, &app, &status.session_id).await;
+                    let _ = app.emit_all("voice-status", status.state.as_str());
+                }
+                Err(e) => log::warn!("Barge-in could not start listening: {}", e),
+            }
+        }
+        if !started_listening {
+            stop_monitoring(&state).await;
+        }
+        let _ = app.emit_all(
+            "barge-in",
+            BargeInEvent {
+                action: settings.action,
+                stream_ids,
+                started_listening,
+            },
+        );
+    });
+}
+
+/// Speech playback ended on its own or was stopped by the user
+pub async fn playback_ended(state: &AppState) {
+    playback().send_replace(false);
+    stop_monitoring(state).await;
+}
+
+async fn stop_monitoring(state: &AppState) {
+    if !MONITORING.swap(false, Ordering::SeqCst) {
+        return;
+    }
+    let Some(engine) = state.voice_engine.lock().await.clone() else {
+        return;
+    };
+    // The user may have started dictating meanwhile; only monitoring is ended here
+    if engine.status().state == EngineState::Monitoring {
+        if let Err(e) = engine.stop().await {
+            log::warn!("Could not stop voice activity monitoring: {}", e);
+        }
+    }
+}
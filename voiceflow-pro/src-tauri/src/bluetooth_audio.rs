@@ -0,0 +1,330 @@
+//! Bluetooth headset awareness for VoiceFlow Pro
+//! Notices a headset dropping from A2DP to the narrowband hands-free profile, warns about it and can keep dictation on the built-in mic
+
+use std::sync::RwLock;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::api::notification::Notification;
+use tauri::{AppHandle, Manager};
+
+use crate::integrations::audio_capture::{self, AudioCaptureSettings};
+use crate::integrations::voice_recognition::{VoiceEngineHandle, VoiceRecognitionConfig};
+use crate::system_activity::command_output;
+use crate::AppState;
+
+/// `system_profiler` takes about a second, so profiles are checked rarely; a headset only drops to hands-free
+/// while its microphone is open, so they are checked more often during capture
+const IDLE_POLL_INTERVAL: Duration = Duration::from_secs(30);
+const CAPTURING_POLL_INTERVAL: Duration = Duration::from_secs(10);
+/// Input names that belong to the machine rather than to an accessory
+const BUILTIN_HINTS: &[&str] = &["built-in", "builtin", "internal", "macbook", "microphone array", "card=pch"];
+/// ALSA names routed through the sound server's default source, which may be the headset itself
+const ROUTED_INPUTS: &[&str] = &["default", "pulse", "pipewire", "jack"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HeadsetProfile {
+    /// High-quality stereo playback; the headset microphone is off
+    A2dp,
+    /// Hands-free (HFP/HSP): the microphone is on and both directions run at 8 or 16 kHz
+    Hfp,
+    /// Connected, but the profile could not be told
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BluetoothAudioSettings {
+    /// Emit "bluetooth-hfp-warning" and show a notification when a headset drops to HFP while listening
+    pub warn_on_hfp: bool,
+    /// Record from the built-in microphone while a Bluetooth headset is connected, so the headset stays on A2DP for playback
+    pub prefer_builtin_mic: bool,
+}
+
+impl Default for BluetoothAudioSettings {
+    fn default() -> Self {
+        Self {
+            warn_on_hfp: true,
+            prefer_builtin_mic: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BluetoothAudioStatus {
+    /// Connected Bluetooth headset, if any
+    pub headset: Option<String>,
+    pub profile: Option<HeadsetProfile>,
+    /// Whether the system input is the headset microphone
+    pub input_is_headset: bool,
+    /// Sample rate the headset runs at, where the platform reports it
+    pub sample_rate: Option<u32>,
+    /// Built-in microphone dictation can switch to
+    pub builtin_mic: Option<String>,
+    /// Shown to the user while recognition accuracy is at risk
+    pub warning: Option<String>,
+}
+
+impl BluetoothAudioStatus {
+    fn narrowband_input(&self) -> bool {
+        self.input_is_headset && self.profile == Some(HeadsetProfile::Hfp)
+    }
+}
+
+static STATUS: RwLock<Option<BluetoothAudioStatus>> = RwLock::new(None);
+
+/// Latest evaluated status; no headset is assumed until the first probe has run
+pub fn current_status() -> BluetoothAudioStatus {
+    STATUS.read().ok().and_then(|status| status.clone()).unwrap_or_default()
+}
+
+/// Profile of the headset dictation records from, for audio metrics; `None` when the input is not a headset
+pub fn active_input_profile() -> Option<HeadsetProfile> {
+    let status = current_status();
+    if status.input_is_headset {
+        status.profile
+    } else {
+        None
+    }
+}
+
+/// What a platform probe reports about the connected headset
+#[derive(Debug, Default)]
+struct HeadsetProbe {
+    name: Option<String>,
+    profile: Option<HeadsetProfile>,
+    input_is_headset: bool,
+    sample_rate: Option<u32>,
+}
+
+/// Probe the headset and work out whether dictation is at risk. `capturing` matters on Windows,
+/// which only switches to hands-free while the headset microphone is open
+pub async fn evaluate(capturing: bool) -> BluetoothAudioStatus {
+    let probe = probe_headset(capturing).await;
+    let builtin_mic = probe
+        .name
+        .as_deref()
+        .and_then(|headset| builtin_input(&audio_capture::input_device_names(), headset));
+    let mut status = BluetoothAudioStatus {
+        headset: probe.name,
+        profile: probe.profile,
+        input_is_headset: probe.input_is_headset,
+        sample_rate: probe.sample_rate,
+        builtin_mic,
+        warning: None,
+    };
+    if status.narrowband_input() {
+        let headset = status.headset.as_deref().unwrap_or("The Bluetooth headset");
+        status.warning = Some(match &status.builtin_mic {
+            Some(mic) => format!(
+                "{} switched to its hands-free profile, which lowers recognition accuracy. Dictate with {} to keep it in high quality",
+                headset, mic
+            ),
+            None => format!(
+                "{} switched to its hands-free profile, which lowers recognition accuracy. A wired or built-in microphone works better",
+                headset
+            ),
+        });
+    }
+    status
+}
+
+/// Re-evaluate now and publish the result
+pub async fn refresh(capturing: bool) -> BluetoothAudioStatus {
+    let status = evaluate(capturing).await;
+    if let Ok(mut current) = STATUS.write() {
+        *current = Some(status.clone());
+    }
+    status
+}
+
+/// Capture settings with the built-in mic swapped in when the user prefers it over a connected headset.
+/// An explicitly chosen device is left alone
+pub fn apply_input_preference(capture: &AudioCaptureSettings, settings: &BluetoothAudioSettings) -> AudioCaptureSettings {
+    let mut capture = capture.clone();
+    if settings.prefer_builtin_mic && capture.device.is_none() {
+        let status = current_status();
+        if status.headset.is_some() {
+            capture.device = status.builtin_mic;
+        }
+    }
+    capture
+}
+
+/// Point the recognizer at the input `apply_input_preference` picks before it opens the microphone, and return the
+/// capture settings the rest of the session records with
+pub async fn route_engine_input(state: &AppState, engine: &VoiceEngineHandle) -> AudioCaptureSettings {
+    let capture = {
+        let settings = state.settings.snapshot();
+        apply_input_preference(&settings.audio_capture, &settings.bluetooth_audio)
+    };
+    let config = engine.status().config;
+    if config.input_device != capture.device {
+        let config = VoiceRecognitionConfig {
+            input_device: capture.device.clone(),
+            ..config
+        };
+        if let Err(e) = engine.reconfigure(config).await {
+            tracing::warn!("Could not switch dictation to {:?}: {}", capture.device, e);
+        }
+    }
+    capture
+}
+
+/// First input that is part of the machine, preferring names that say so
+fn builtin_input(names: &[String], headset: &str) -> Option<String> {
+    let candidates: Vec<&String> = names
+        .iter()
+        .filter(|name| !is_bluetooth_input(name) && !name.contains(headset))
+        .filter(|name| !ROUTED_INPUTS.iter().any(|routed| name.to_lowercase().starts_with(routed)))
+        .collect();
+    candidates
+        .iter()
+        .find(|name| BUILTIN_HINTS.iter().any(|hint| name.to_lowercase().contains(hint)))
+        .or_else(|| candidates.first())
+        .map(|name| name.to_string())
+}
+
+fn is_bluetooth_input(name: &str) -> bool {
+    let name = name.to_lowercase();
+    name.contains("hands-free") || name.contains("bluez") || name.contains("airpods") || name.contains("bluetooth")
+}
+
+#[cfg(target_os = "macos")]
+async fn probe_headset(_capturing: bool) -> HeadsetProbe {
+    // macOS only exposes a headset's microphone in hands-free mode, where the device drops to 8 or 16 kHz
+    let Some(output) = command_output("system_profiler", &["SPAudioDataType"]).await else {
+        return HeadsetProbe::default();
+    };
+    // Devices are listed at eight spaces of indentation, their fields at ten
+    let mut devices: Vec<(String, Vec<&str>)> = Vec::new();
+    for line in output.lines() {
+        let indent = line.len() - line.trim_start().len();
+        match (indent, line.trim().strip_suffix(':')) {
+            (8, Some(name)) => devices.push((name.to_string(), Vec::new())),
+            (10.., _) => {
+                if let Some((_, fields)) = devices.last_mut() {
+                    fields.push(line.trim());
+                }
+            }
+            _ => {}
+        }
+    }
+    let mut probe = HeadsetProbe::default();
+    for (name, fields) in devices.iter().filter(|(_, fields)| fields.contains(&"Transport: Bluetooth")) {
+        let sample_rate = fields
+            .iter()
+            .find_map(|field| field.strip_prefix("Current SampleRate:"))
+            .and_then(|rate| rate.trim().parse::<u32>().ok());
+        let input_is_headset = fields.contains(&"Default Input Device: Yes");
+        // The output and input halves of one headset are listed separately; the input half decides
+        if probe.name.is_some() && !input_is_headset {
+            continue;
+        }
+        probe = HeadsetProbe {
+            name: Some(name.clone()),
+            profile: Some(match sample_rate {
+                Some(rate) if rate <= 16_000 => HeadsetProfile::Hfp,
+                Some(_) => HeadsetProfile::A2dp,
+                None => HeadsetProfile::Unknown,
+            }),
+            input_is_headset,
+            sample_rate,
+        };
+    }
+    probe
+}
+
+#[cfg(target_os = "windows")]
+async fn probe_headset(capturing: bool) -> HeadsetProbe {
+    use cpal::traits::{DeviceTrait, HostTrait};
+
+    // Windows exposes a "Hands-Free" endpoint for the headset mic and switches to HFP while it is open
+    let default_input = cpal::default_host().default_input_device().and_then(|device| device.name().ok());
+    let Some(name) = audio_capture::input_device_names().into_iter().find(|name| is_bluetooth_input(name)) else {
+        return HeadsetProbe::default();
+    };
+    let input_is_headset = default_input.as_deref() == Some(name.as_str());
+    HeadsetProbe {
+        profile: Some(if input_is_headset && capturing { HeadsetProfile::Hfp } else { HeadsetProfile::A2dp }),
+        name: Some(name),
+        input_is_headset,
+        sample_rate: None,
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+async fn probe_headset(_capturing: bool) -> HeadsetProbe {
+    // PulseAudio and PipeWire expose each headset as a bluez card whose active profile names the mode
+    let Some(cards) = command_output("pactl", &["list", "cards"]).await else {
+        return HeadsetProbe::default();
+    };
+    let Some(card) = cards.split("Card #").skip(1).find(|card| card.contains("bluez_card.")) else {
+        return HeadsetProbe::default();
+    };
+    let field = |prefix: &str| card.lines().find_map(|line| line.trim().strip_prefix(prefix).map(|value| value.trim().to_string()));
+    let profile = field("Active Profile:").map(|profile| {
+        let profile = profile.to_lowercase();
+        if profile.contains("head-unit") || profile.contains("head_unit") || profile.contains("handsfree") {
+            HeadsetProfile::Hfp
+        } else if profile.contains("a2dp") {
+            HeadsetProfile::A2dp
+        } else {
+            HeadsetProfile::Unknown
+        }
+    });
+    let default_source = command_output("pactl", &["info"]).await.and_then(|info| {
+        info.lines()
+            .find_map(|line| line.strip_prefix("Default Source:").map(|source| source.trim().to_string()))
+    });
+    HeadsetProbe {
+        name: field("device.description = ").map(|name| name.trim_matches('"').to_string()),
+        profile,
+        input_is_headset: default_source.map_or(false, |source| source.starts_with("bluez_")),
+        sample_rate: None,
+    }
+}
+
+/// Poll the headset, emitting "bluetooth-audio-changed" on every change and warning when it drops to HFP while listening
+pub async fn run_bluetooth_monitor(state: AppState, app: AppHandle) {
+    let mut previous: Option<BluetoothAudioStatus> = None;
+    loop {
+        let (settings, notifications, resources) = {
+            let settings = state.settings.snapshot();
+            (settings.bluetooth_audio.clone(), settings.notifications, settings.resources.clone())
+        };
+        let capturing = match state.voice_engine.lock().await.clone() {
+            Some(engine) => engine.status().state.captures_audio(),
+            None => false,
+        };
+        let current = refresh(capturing).await;
+        if previous.as_ref() != Some(&current) {
+            let _ = app.emit_all("bluetooth-audio-changed", &current);
+            let was_narrowband = previous.as_ref().map_or(false, BluetoothAudioStatus::narrowband_input);
+            if current.narrowband_input() && !was_narrowband && capturing && settings.warn_on_hfp {
+                warn_hfp(&app, &current, notifications);
+            }
+            previous = Some(current);
+        }
+        let interval = if capturing { CAPTURING_POLL_INTERVAL } else { IDLE_POLL_INTERVAL };
+        tokio::time::sleep(crate::idle::adjust_interval(interval, &resources)).await;
+    }
+}
+
+fn warn_hfp(app: &AppHandle, status: &BluetoothAudioStatus, notifications: bool) {
+    let _ = app.emit_all("bluetooth-hfp-warning", status);
+    let Some(warning) = &status.warning else {
+        return;
+    };
+    tracing::warn!("{}", warning);
+    if crate::focus::notifications_allowed(notifications) {
+        let shown = Notification::new(&app.config().tauri.bundle.identifier)
+            .title("Headset microphone quality dropped")
+            .body(warning)
+            .show();
+        if let Err(e) = shown {
+            tracing::warn!("Could not show the Bluetooth headset notification: {}", e);
+        }
+    }
+}
@@ -0,0 +1,460 @@
+//! Text caret tracking for VoiceFlow Pro
+//! Finds the caret in the focused app through the platform accessibility APIs and keeps the dictation overlay next to it across monitors
+
+#[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, Monitor, Window, WindowBuilder, WindowUrl};
+
+use crate::errors::{AppError, ValidationError};
+#[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
+use crate::system_activity::{ScriptSession, SESSION_END_MARKER};
+use crate::AppState;
+
+pub const OVERLAY_LABEL: &str = "overlay";
+/// How often follow mode looks again while it is off or the overlay is hidden
+const IDLE_POLL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OverlaySettings {
+    /// Move the overlay window to the text caret of the focused app
+    pub follow_caret: bool,
+    /// Logical pixels between the caret and the overlay
+    pub gap: u32,
+    pub poll_interval_ms: u64,
+}
+
+impl Default for OverlaySettings {
+    fn default() -> Self {
+        Self {
+            follow_caret: false,
+            gap: 8,
+            poll_interval_ms: 150,
+        }
+    }
+}
+
+pub fn validate(settings: &OverlaySettings) -> Result<(), AppError> {
+    let invalid = |message: String| AppError::Validation(ValidationError::InvalidConfigValue(message));
+    if !(50..=2000).contains(&settings.poll_interval_ms) {
+        return Err(invalid(format!(
+            "Caret poll interval must be 50-2000 ms, got {}",
+            settings.poll_interval_ms
+        )));
+    }
+    if settings.gap > 200 {
+        return Err(invalid(format!("Overlay gap must be at most 200 pixels, got {}", settings.gap)));
+    }
+    Ok(())
+}
+
+/// Whether the rectangle is the caret itself or only the focused control, for apps that do not expose one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaretSource {
+    Caret,
+    FocusedElement,
+}
+
+/// Screen space the platform reports rectangles in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CoordinateSpace {
+    /// Device pixels across the whole virtual desktop
+    Physical,
+    /// Points, scaled by each monitor's own factor (macOS)
+    Logical,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MonitorInfo {
+    pub name: Option<String>,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub scale_factor: f64,
+}
+
+impl From<&Monitor> for MonitorInfo {
+    fn from(monitor: &Monitor) -> Self {
+        Self {
+            name: monitor.name().cloned(),
+            x: monitor.position().x,
+            y: monitor.position().y,
+            width: monitor.size().width,
+            height: monitor.size().height,
+            scale_factor: monitor.scale_factor(),
+        }
+    }
+}
+
+impl MonitorInfo {
+    fn contains(&self, x: f64, y: f64) -> bool {
+        x >= self.x as f64
+            && y >= self.y as f64
+            && x < self.x as f64 + self.width as f64
+            && y < self.y as f64 + self.height as f64
+    }
+
+    /// The monitor's bounds in points, the space macOS accessibility coordinates use
+    fn contains_logical(&self, x: f64, y: f64) -> bool {
+        let (left, top) = (self.x as f64 / self.scale_factor, self.y as f64 / self.scale_factor);
+        x >= left
+            && y >= top
+            && x < left + self.width as f64 / self.scale_factor
+            && y < top + self.height as f64 / self.scale_factor
+    }
+
+    fn distance_squared(&self, x: f64, y: f64) -> f64 {
+        let dx = (self.x as f64 - x).max(x - (self.x as f64 + self.width as f64)).max(0.0);
+        let dy = (self.y as f64 - y).max(y - (self.y as f64 + self.height as f64)).max(0.0);
+        dx * dx + dy * dy
+    }
+}
+
+/// Caret rectangle in physical pixels of the virtual desktop, with the monitor it is on
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CaretPosition {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub source: CaretSource,
+    pub monitor: Option<MonitorInfo>,
+}
+
+/// A rectangle as the platform probe printed it
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ProbedRect {
+    source: CaretSource,
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
+/// Probe output is one line: "caret|element x y width height"
+fn parse_probe(output: &str) -> Option<ProbedRect> {
+    let mut fields = output.split_whitespace();
+    let source = match fields.next()? {
+        "caret" => CaretSource::Caret,
+        "element" => CaretSource::FocusedElement,
+        _ => return None,
+    };
+    let mut number = || fields.next()?.parse::<f64>().ok().filter(|value| value.is_finite());
+    let rect = ProbedRect {
+        source,
+        x: number()?,
+        y: number()?,
+        width: number()?.max(0.0),
+        height: number()?.max(0.0),
+    };
+    // Off-screen or collapsed controls report an all-zero rectangle
+    Some(rect).filter(|rect| rect.x != 0.0 || rect.y != 0.0 || rect.width != 0.0 || rect.height != 0.0)
+}
+
+/// Convert a probed rectangle to physical pixels and find the monitor it is on
+fn locate(rect: ProbedRect, space: CoordinateSpace, monitors: &[MonitorInfo]) -> CaretPosition {
+    let monitor = match space {
+        CoordinateSpace::Physical => monitors
+            .iter()
+            .find(|monitor| monitor.contains(rect.x, rect.y))
+            .or_else(|| {
+                monitors
+                    .iter()
+                    .min_by(|a, b| a.distance_squared(rect.x, rect.y).total_cmp(&b.distance_squared(rect.x, rect.y)))
+            }),
+        CoordinateSpace::Logical => monitors
+            .iter()
+            .find(|monitor| monitor.contains_logical(rect.x, rect.y))
+            .or_else(|| monitors.first()),
+    };
+    let scale = match space {
+        CoordinateSpace::Physical => 1.0,
+        CoordinateSpace::Logical => monitor.map_or(1.0, |monitor| monitor.scale_factor),
+    };
+    CaretPosition {
+        x: (rect.x * scale).round() as i32,
+        y: (rect.y * scale).round() as i32,
+        width: (rect.width * scale).round() as u32,
+        height: (rect.height * scale).round() as u32,
+        source: rect.source,
+        monitor: monitor.cloned(),
+    }
+}
+
+/// Top-left corner for an overlay of `size` physical pixels: under the caret, or above it when the
+/// monitor has no room below, and kept on the caret's monitor
+pub fn overlay_position(caret: &CaretPosition, size: (u32, u32), gap: u32) -> (i32, i32) {
+    let Some(monitor) = &caret.monitor else {
+        return (caret.x, caret.y + caret.height as i32 + gap as i32);
+    };
+    let gap = (gap as f64 * monitor.scale_factor).round() as i32;
+    let (width, height) = (size.0 as i32, size.1 as i32);
+    let right = monitor.x + monitor.width as i32;
+    let bottom = monitor.y + monitor.height as i32;
+
+    // A focused control without a caret gets the overlay inside its bottom edge rather than below it
+    let anchor_bottom = match caret.source {
+        CaretSource::Caret => caret.y + caret.height as i32,
+        CaretSource::FocusedElement => caret.y + (caret.height as i32 - height - gap).max(0),
+    };
+    let below = anchor_bottom + gap;
+    let y = if below + height <= bottom {
+        below
+    } else {
+        (caret.y - gap - height).max(monitor.y)
+    };
+    let x = caret.x.min(right - width).max(monitor.x);
+    (x, y)
+}
+
+/// Where the caret is in the focused app, or `None` when no accessible text field has focus
+pub async fn caret_position(window: &Window) -> Option<CaretPosition> {
+    let (rect, space) = probe_caret().await?;
+    let monitors: Vec<MonitorInfo> = window
+        .available_monitors()
+        .map(|monitors| monitors.iter().map(MonitorInfo::from).collect())
+        .unwrap_or_default();
+    Some(locate(rect, space, &monitors))
+}
+
+/// The probe runs every poll, so it is answered by one long-lived interpreter rather than a process per lookup
+#[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
+fn probe_session() -> &'static ScriptSession {
+    static SESSION: OnceLock<ScriptSession> = OnceLock::new();
+    SESSION.get_or_init(new_probe_session)
+}
+
+/// Uses the AX API through the JavaScript-for-Automation ObjC bridge; needs the Accessibility permission
+#[cfg(target_os = "macos")]
+fn new_probe_session() -> ScriptSession {
+    let script = r#"
+ObjC.import('Foundation');
+ObjC.import('ApplicationServices');
+function attribute(element, name) {
+    const value = Ref();
+    return $.AXUIElementCopyAttributeValue(element, name, value) === 0 ? value[0] : null;
+}
+function rect(value) {
+    const out = Ref();
+    return $.AXValueGetValue(value, $.kAXValueCGRectType, out) ? out[0] : null;
+}
+function probe() {
+    const focused = attribute($.AXUIElementCreateSystemWide(), 'AXFocusedUIElement');
+    if (!focused) return '';
+    const range = attribute(focused, 'AXSelectedTextRange');
+    const bounds = Ref();
+    let caret = null;
+    if (range && $.AXUIElementCopyParameterizedAttributeValue(focused, 'AXBoundsForRange', range, bounds) === 0) {
+        caret = rect(bounds[0]);
+    }
+    if (caret && (caret.size.height > 0 || caret.origin.x !== 0)) {
+        return `caret ${caret.origin.x} ${caret.origin.y} ${caret.size.width} ${caret.size.height}`;
+    }
+    const position = Ref(), size = Ref();
+    $.AXValueGetValue(attribute(focused, 'AXPosition'), $.kAXValueCGPointType, position);
+    $.AXValueGetValue(attribute(focused, 'AXSize'), $.kAXValueCGSizeType, size);
+    return `element ${position[0].x} ${position[0].y} ${size[0].width} ${size[0].height}`;
+}
+const input = $.NSFileHandle.fileHandleWithStandardInput;
+const output = $.NSFileHandle.fileHandleWithStandardOutput;
+function write(text) {
+    output.writeData($(text + '\n').dataUsingEncoding($.NSUTF8StringEncoding));
+}
+while (input.availableData.length > 0) {
+    let line = '';
+    try { line = probe(); } catch (e) {}
+    if (line) write(line);
+    write('END_MARKER');
+}"#;
+    ScriptSession::new(
+        "osascript",
+        vec!["-l".to_string(), "JavaScript".to_string(), "-e".to_string(), script.replace("END_MARKER", SESSION_END_MARKER)],
+    )
+}
+
+#[cfg(target_os = "macos")]
+async fn probe_caret() -> Option<(ProbedRect, CoordinateSpace)> {
+    let output = probe_session().query("caret").await?;
+    Some((parse_probe(&output)?, CoordinateSpace::Logical))
+}
+
+/// UI Automation text selection, with the process made per-monitor DPI aware so rectangles come back in device pixels
+#[cfg(target_os = "windows")]
+fn new_probe_session() -> ScriptSession {
+    ScriptSession::powershell(
+        r#"
+Add-Type -AssemblyName UIAutomationClient,UIAutomationTypes
+Add-Type -Name D -Namespace U -MemberDefinition '[DllImport("user32.dll")] public static extern bool SetProcessDpiAwarenessContext(System.IntPtr c);'
+[void][U.D]::SetProcessDpiAwarenessContext([System.IntPtr](-4))
+$inv = [System.Globalization.CultureInfo]::InvariantCulture
+function Get-Caret {
+    $e = [System.Windows.Automation.AutomationElement]::FocusedElement
+    if (-not $e) { return }
+    $p = $null
+    if ($e.TryGetCurrentPattern([System.Windows.Automation.TextPattern]::Pattern, [ref]$p)) {
+        $s = $p.GetSelection()
+        if ($s.Length -gt 0) {
+            $r = $s[0].Clone()
+            $b = $r.GetBoundingRectangles()
+            if ($b.Length -eq 0) {
+                [void]$r.ExpandToEnclosingUnit([System.Windows.Automation.Text.TextUnit]::Character)
+                $b = $r.GetBoundingRectangles()
+            }
+            if ($b.Length -gt 0) {
+                $c = $b[$b.Length - 1]
+                return [string]::Format($inv, 'caret {0} {1} {2} {3}', $c.X, $c.Y, $c.Width, $c.Height)
+            }
+        }
+    }
+    $c = $e.Current.BoundingRectangle
+    [string]::Format($inv, 'element {0} {1} {2} {3}', $c.X, $c.Y, $c.Width, $c.Height)
+}"#,
+    )
+}
+
+#[cfg(target_os = "windows")]
+async fn probe_caret() -> Option<(ProbedRect, CoordinateSpace)> {
+    let output = probe_session().query("Get-Caret").await?;
+    Some((parse_probe(&output)?, CoordinateSpace::Physical))
+}
+
+/// AT-SPI through its Python bindings; Wayland compositors may report window-relative positions
+#[cfg(target_os = "linux")]
+fn new_probe_session() -> ScriptSession {
+    let script = r#"
+import sys
+import gi
+gi.require_version('Atspi', '2.0')
+from gi.repository import Atspi
+
+def focused(node, depth=0):
+    if node is None or depth > 40:
+        return None
+    if node.get_state_set().contains(Atspi.StateType.FOCUSED):
+        return node
+    for i in range(min(node.get_child_count(), 500)):
+        found = focused(node.get_child_at_index(i), depth + 1)
+        if found is not None:
+            return found
+    return None
+
+def probe():
+    target = None
+    desktop = Atspi.get_desktop(0)
+    for i in range(desktop.get_child_count()):
+        app = desktop.get_child_at_index(i)
+        for j in range(app.get_child_count() if app is not None else 0):
+            window = app.get_child_at_index(j)
+            if window is not None and window.get_state_set().contains(Atspi.StateType.ACTIVE):
+                target = focused(window)
+                break
+        if target is not None:
+            break
+    if target is None:
+        return None
+
+    if target.get_text_iface() is not None:
+        offset = Atspi.Text.get_caret_offset(target)
+        r = Atspi.Text.get_character_extents(target, offset, Atspi.CoordType.SCREEN)
+        if r.width or r.height:
+            return 'caret %d %d %d %d' % (r.x, r.y, r.width, r.height)
+        if offset > 0:
+            # At the end of the text there is no character under the caret; use the right edge of the last one
+            r = Atspi.Text.get_character_extents(target, offset - 1, Atspi.CoordType.SCREEN)
+            if r.width or r.height:
+                return 'caret %d %d %d %d' % (r.x + r.width, r.y, 0, r.height)
+    r = Atspi.Component.get_extents(target, Atspi.CoordType.SCREEN)
+    return 'element %d %d %d %d' % (r.x, r.y, r.width, r.height)
+
+for _ in sys.stdin:
+    try:
+        line = probe()
+    except Exception:
+        line = None
+    if line:
+        print(line)
+    print('END_MARKER', flush=True)"#;
+    ScriptSession::new("python3", vec!["-c".to_string(), script.replace("END_MARKER", SESSION_END_MARKER)])
+}
+
+#[cfg(target_os = "linux")]
+async fn probe_caret() -> Option<(ProbedRect, CoordinateSpace)> {
+    let output = probe_session().query("caret").await?;
+    Some((parse_probe(&output)?, CoordinateSpace::Physical))
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+async fn probe_caret() -> Option<(ProbedRect, CoordinateSpace)> {
+    None
+}
+
+/// The overlay window, created hidden, borderless and unfocusable the first time it is needed
+pub fn overlay_window(app: &AppHandle) -> Result<Window, AppError> {
+    if let Some(window) = app.get_window(OVERLAY_LABEL) {
+        return Ok(window);
+    }
+    WindowBuilder::new(app, OVERLAY_LABEL, WindowUrl::App("index.html#/overlay".into()))
+        .title("VoiceFlow Pro Overlay")
+        .inner_size(360.0, 72.0)
+        .decorations(false)
+        .transparent(true)
+        .always_on_top(true)
+        .skip_taskbar(true)
+        .resizable(false)
+        .focused(false)
+        .visible(false)
+        .build()
+        .map_err(|e| AppError::Internal(format!("Failed to create overlay window: {}", e)))
+}
+
+/// Move the overlay next to the caret; returns where the caret was found
+async fn follow_once(overlay: &Window, settings: &OverlaySettings) -> Option<CaretPosition> {
+    let caret = caret_position(overlay).await?;
+    // The overlay keeps its logical size, so it grows on a monitor with a larger scale factor
+    let scale = overlay.scale_factor().unwrap_or(1.0);
+    let target_scale = caret.monitor.as_ref().map_or(scale, |monitor| monitor.scale_factor);
+    let size = overlay.outer_size().ok()?;
+    let size = (
+        (size.width as f64 / scale * target_scale).round() as u32,
+        (size.height as f64 / scale * target_scale).round() as u32,
+    );
+    let (x, y) = overlay_position(&caret, size, settings.gap);
+    // macOS positions windows in points, converting with the scale of the monitor the window is on now;
+    // handing it points computed for the target monitor keeps the move right across mixed-DPI setups
+    let position: tauri::Position = if cfg!(target_os = "macos") {
+        tauri::LogicalPosition::new(x as f64 / target_scale, y as f64 / target_scale).into()
+    } else {
+        tauri::PhysicalPosition::new(x, y).into()
+    };
+    if let Err(e) = overlay.set_position(position) {
+        tracing::debug!("Overlay not moved: {}", e);
+    }
+    Some(caret)
+}
+
+/// Keep the overlay at the caret while follow mode is on and the overlay is showing, emitting "caret-moved" to it
+pub async fn run_caret_follower(state: AppState, app: AppHandle) {
+    let mut previous: Option<CaretPosition> = None;
+    loop {
+        let settings = state.settings.snapshot().overlay.clone();
+        let overlay = app
+            .get_window(OVERLAY_LABEL)
+            .filter(|window| settings.follow_caret && window.is_visible().unwrap_or(false));
+        let Some(overlay) = overlay else {
+            previous = None;
+            tokio::time::sleep(IDLE_POLL).await;
+            continue;
+        };
+        let current = follow_once(&overlay, &settings).await;
+        if let Some(caret) = current.as_ref().filter(|caret| previous.as_ref() != Some(*caret)) {
+            let _ = overlay.emit("caret-moved", caret);
+        }
+        previous = current;
+        tokio::time::sleep(Duration::from_millis(settings.poll_interval_ms)).await;
+    }
+}
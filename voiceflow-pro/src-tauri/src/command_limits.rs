@@ -0,0 +1,386 @@
+//! Command rate limiting for VoiceFlow Pro
+//! Token buckets per command category in front of the invoke handler, so a runaway frontend loop cannot burn the API budget
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tauri::{Invoke, Runtime};
+
+use crate::errors::{AppError, ValidationError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CommandCategory {
+    /// Calls that reach a paid AI service
+    Ai,
+    /// Downloads, installs, exports and diagnostics that take seconds each
+    Heavy,
+    /// Writes to settings, profiles and the pipeline
+    Settings,
+    /// Streaming acknowledgements that arrive many times a second by design; never limited
+    Streaming,
+    /// Reads of local state, e.g. status polling
+    Other,
+}
+
+impl CommandCategory {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CommandCategory::Ai => "ai",
+            CommandCategory::Heavy => "heavy",
+            CommandCategory::Settings => "settings",
+            CommandCategory::Streaming => "streaming",
+            CommandCategory::Other => "other",
+        }
+    }
+}
+
+const AI_COMMANDS: &[&str] = &[
+    "process_text",
+    "process_text_v1",
+    "process_speech_with_ai",
+    "analyze_utterance",
+    // Runs the health checks, which spend tokens
+    "get_ai_ml_health_status",
+    "process_enhanced_text",
+    "enhance_text_v1",
+    "generate_enhanced_voice",
+    "synthesize_v1",
+    "translate_with_enhancement",
+    "translate_v1",
+    "process_context_aware",
+    "process_long_text",
+    "transcribe_long_audio",
+    "compare_processing",
+    "retranscribe_segment",
+    "generate_voice_variations",
+    "batch_generate_voice",
+    "test_pronunciation",
+    "speak_text_streaming",
+    "read_selection_aloud",
+    "send_assistant_message",
+    "submit_form_answer",
+    "extract_entities",
+    "refine_last_result",
+    "start_stt_stream",
+    // Kanji and hanzi go to the text model
+    "transliterate_text",
+    // Screen capture and OCR, then the text model for instructions
+    "read_screen_text",
+    "process_screen_text",
+];
+
+const HEAVY_COMMANDS: &[&str] = &[
+    "download_language_resources",
+    "download_model",
+    "install_domain_pack",
+    "install_plugin",
+    "check_for_updates",
+    "install_update",
+    "upload_crash_report",
+    "export_audit_log",
+    "export_all_user_data",
+    "test_network_connectivity",
+    "run_system_checks",
+    "diagnose_audio_capture",
+];
+
+const SETTINGS_COMMANDS: &[&str] = &[
+    "update_settings",
+    "create_user_profile",
+    "switch_user_profile",
+    "set_pipeline",
+    "set_focus_rules",
+    "set_boost_keywords",
+    "register_assistant_tool",
+    "add_intent_rule",
+    "save_snippet",
+    "register_custom_voice",
+];
+
+const STREAMING_COMMANDS: &[&str] = &[
+    "push_caption_text",
+    "push_interim_transcript",
+    "ack_speech_chunk",
+    "control_speech_stream",
+    "report_injection_latency",
+    "set_speech_playback_active",
+    "push_playback_reference",
+    "push_stt_audio",
+];
+
+/// Commands named like this only read local state
+const READ_PREFIXES: &[&str] = &["get_", "list_", "is_", "has_", "search_", "preview_"];
+
+/// The category `command` is limited under. Anything neither listed nor named as a read may reach a paid service,
+/// so it gets the AI limits until it is listed elsewhere
+pub fn category_for(command: &str) -> CommandCategory {
+    let listed = |commands: &[&str]| commands.contains(&command);
+    if listed(AI_COMMANDS) {
+        CommandCategory::Ai
+    } else if listed(HEAVY_COMMANDS) {
+        CommandCategory::Heavy
+    } else if listed(SETTINGS_COMMANDS) {
+        CommandCategory::Settings
+    } else if listed(STREAMING_COMMANDS) {
+        CommandCategory::Streaming
+    } else if READ_PREFIXES.iter().any(|prefix| command.starts_with(prefix)) {
+        CommandCategory::Other
+    } else {
+        CommandCategory::Ai
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BucketLimit {
+    /// Calls allowed back to back before the rate applies
+    pub burst: u32,
+    /// Sustained calls per minute
+    pub per_minute: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CommandLimitSettings {
+    pub enabled: bool,
+    pub ai: BucketLimit,
+    pub heavy: BucketLimit,
+    pub settings: BucketLimit,
+    pub other: BucketLimit,
+}
+
+impl Default for CommandLimitSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            ai: BucketLimit { burst: 10, per_minute: 60 },
+            heavy: BucketLimit { burst: 3, per_minute: 10 },
+            settings: BucketLimit { burst: 10, per_minute: 120 },
+            // Status polling from several panels at once stays well inside this
+            other: BucketLimit { burst: 100, per_minute: 3000 },
+        }
+    }
+}
+
+impl CommandLimitSettings {
+    fn limit(&self, category: CommandCategory) -> Option<BucketLimit> {
+        match category {
+            CommandCategory::Ai => Some(self.ai),
+            CommandCategory::Heavy => Some(self.heavy),
+            CommandCategory::Settings => Some(self.settings),
+            CommandCategory::Other => Some(self.other),
+            CommandCategory::Streaming => None,
+        }
+    }
+}
+
+pub fn validate(settings: &CommandLimitSettings) -> Result<(), AppError> {
+    let invalid = |message: String| AppError::Validation(ValidationError::InvalidConfigValue(message));
+    for (name, limit) in [
+        ("ai", settings.ai),
+        ("heavy", settings.heavy),
+        ("settings", settings.settings),
+        ("other", settings.other),
+    ] {
+        if !(1..=1000).contains(&limit.burst) {
+            return Err(invalid(format!("{} command burst must be 1-1000, got {}", name, limit.burst)));
+        }
+        if !(1..=60_000).contains(&limit.per_minute) {
+            return Err(invalid(format!(
+                "{} command rate must be 1-60000 per minute, got {}",
+                name, limit.per_minute
+            )));
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+    allowed: u64,
+    throttled: u64,
+    /// Whether the previous call was turned away, so a throttled burst is logged once rather than per call
+    throttling: bool,
+    last_throttled: Option<(String, DateTime<Utc>)>,
+}
+
+impl Bucket {
+    fn new(limit: BucketLimit) -> Self {
+        Self {
+            tokens: limit.burst as f64,
+            refilled_at: Instant::now(),
+            allowed: 0,
+            throttled: 0,
+            throttling: false,
+            last_throttled: None,
+        }
+    }
+
+    fn refill(&mut self, limit: BucketLimit) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.refilled_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * limit.per_minute as f64 / 60.0).min(limit.burst as f64);
+        self.refilled_at = now;
+    }
+
+    /// Milliseconds until the next whole token
+    fn retry_after_ms(&self, limit: BucketLimit) -> u64 {
+        let missing = (1.0 - self.tokens).max(0.0);
+        (missing * 60_000.0 / limit.per_minute as f64).ceil() as u64
+    }
+}
+
+struct Limiter {
+    settings: CommandLimitSettings,
+    buckets: HashMap<CommandCategory, Bucket>,
+}
+
+static LIMITER: Mutex<Option<Limiter>> = Mutex::new(None);
+
+/// Apply new limits; buckets keep their counters but never hold more than the new burst
+pub fn configure(settings: &CommandLimitSettings) {
+    let Ok(mut limiter) = LIMITER.lock() else {
+        return;
+    };
+    let limiter = limiter.get_or_insert_with(|| Limiter {
+        settings: settings.clone(),
+        buckets: HashMap::new(),
+    });
+    limiter.settings = settings.clone();
+    for (category, bucket) in limiter.buckets.iter_mut() {
+        if let Some(limit) = settings.limit(*category) {
+            bucket.tokens = bucket.tokens.min(limit.burst as f64);
+        }
+    }
+}
+
+/// Take a token for `command`, or say how long to wait. Called for every invoke before the command runs
+pub fn check(command: &str) -> Result<(), AppError> {
+    let category = category_for(command);
+    let Ok(mut limiter) = LIMITER.lock() else {
+        return Ok(());
+    };
+    let limiter = limiter.get_or_insert_with(|| Limiter {
+        settings: CommandLimitSettings::default(),
+        buckets: HashMap::new(),
+    });
+    let (enabled, limit) = (limiter.settings.enabled, limiter.settings.limit(category));
+    let Some(limit) = limit.filter(|_| enabled) else {
+        return Ok(());
+    };
+    let bucket = limiter.buckets.entry(category).or_insert_with(|| Bucket::new(limit));
+    bucket.refill(limit);
+    if bucket.tokens >= 1.0 {
+        bucket.tokens -= 1.0;
+        bucket.allowed += 1;
+        bucket.throttling = false;
+        return Ok(());
+    }
+
+    bucket.throttled += 1;
+    bucket.last_throttled = Some((command.to_string(), Utc::now()));
+    if !bucket.throttling {
+        log::warn!(
+            "Throttling {} commands: {} arrived faster than {} per minute",
+            category.as_str(),
+            command,
+            limit.per_minute
+        );
+        bucket.throttling = true;
+    }
+    Err(AppError::CommandThrottled {
+        command: command.to_string(),
+        retry_after_ms: bucket.retry_after_ms(limit),
+    })
+}
+
+/// Wrap the generated invoke handler so every command passes `check` first; throttled calls are rejected without running
+pub fn limited<R: Runtime>(
+    handler: impl Fn(Invoke<R>) + Send + Sync + 'static,
+) -> impl Fn(Invoke<R>) + Send + Sync + 'static {
+    move |invoke| {
+        if let Err(error) = check(invoke.message.command()) {
+            invoke.resolver.reject(error);
+            return;
+        }
+        handler(invoke)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CategoryMetrics {
+    pub category: CommandCategory,
+    pub allowed: u64,
+    pub throttled: u64,
+    /// Calls that could go out right now
+    pub available: u32,
+    pub last_throttled_command: Option<String>,
+    pub last_throttled_at: Option<DateTime<Utc>>,
+}
+
+/// Counters for every category that has seen a call since startup
+pub fn metrics() -> Vec<CategoryMetrics> {
+    let Ok(mut limiter) = LIMITER.lock() else {
+        return Vec::new();
+    };
+    let Some(limiter) = limiter.as_mut() else {
+        return Vec::new();
+    };
+    let settings = limiter.settings.clone();
+    let mut metrics: Vec<CategoryMetrics> = limiter
+        .buckets
+        .iter_mut()
+        .map(|(category, bucket)| {
+            if let Some(limit) = settings.limit(*category) {
+                bucket.refill(limit);
+            }
+            CategoryMetrics {
+                category: *category,
+                allowed: bucket.allowed,
+                throttled: bucket.throttled,
+                available: bucket.tokens.floor() as u32,
+                last_throttled_command: bucket.last_throttled.as_ref().map(|(command, _)| command.clone()),
+                last_throttled_at: bucket.last_throttled.as_ref().map(|(_, at)| *at),
+            }
+        })
+        .collect();
+    metrics.sort_by_key(|metrics| metrics.category.as_str());
+    metrics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn listed_commands_keep_their_category() {
+        assert_eq!(category_for("analyze_utterance"), CommandCategory::Ai);
+        // Listing wins over a read-like name
+        assert_eq!(category_for("get_ai_ml_health_status"), CommandCategory::Ai);
+        assert_eq!(category_for("download_model"), CommandCategory::Heavy);
+        assert_eq!(category_for("update_settings"), CommandCategory::Settings);
+        assert_eq!(category_for("push_stt_audio"), CommandCategory::Streaming);
+    }
+
+    #[test]
+    fn reads_are_other_and_unknown_commands_are_limited_like_ai() {
+        assert_eq!(category_for("get_settings"), CommandCategory::Other);
+        assert_eq!(category_for("list_models"), CommandCategory::Other);
+        assert_eq!(category_for("summarize_everything"), CommandCategory::Ai);
+        assert_eq!(category_for(""), CommandCategory::Ai);
+    }
+
+    #[test]
+    fn no_command_is_listed_twice() {
+        let lists = [AI_COMMANDS, HEAVY_COMMANDS, SETTINGS_COMMANDS, STREAMING_COMMANDS];
+        let mut seen = std::collections::HashSet::new();
+        for command in lists.iter().flat_map(|list| list.iter()) {
+            assert!(seen.insert(*command), "{} is listed twice", command);
+        }
+    }
+}
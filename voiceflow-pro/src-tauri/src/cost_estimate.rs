@@ -0,0 +1,315 @@
+//! Cost estimates for VoiceFlow Pro
+//! Prices long transcriptions, document runs and speech batches before they start, and holds back those above a threshold
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{AppError, ValidationError};
+use crate::integrations::model_registry::{get_model_registry, ModelPricing};
+use crate::integrations::stt_providers::SttProviderKind;
+use crate::integrations::text_chunker::{split_into_chunks, ChunkingConfig, LongTextOperation};
+use crate::integrations::token_budget::count_tokens;
+use crate::Settings;
+
+/// Rates for models the registry does not price, on the expensive side so surprises are pleasant
+const DEFAULT_TEXT_PRICE: (f64, f64) = (5.0, 15.0);
+const DEFAULT_SPEECH_PRICE: f64 = 30.0;
+const DEFAULT_TRANSCRIPTION_PRICE: f64 = 0.02;
+
+/// Instructions sent with every document chunk on top of its text
+const PROMPT_TOKENS_PER_CHUNK: u64 = 250;
+/// Translations come out somewhat longer than their source
+const TRANSLATION_GROWTH: f64 = 1.2;
+/// Bitrate assumed for recordings whose container does not declare a length
+const FALLBACK_AUDIO_BYTES_PER_SEC: f64 = 16_000.0;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CostGuardSettings {
+    /// Batch commands above the threshold fail with `confirmation_required` until called again with `confirmed`
+    pub require_confirmation: bool,
+    pub threshold_usd: f64,
+}
+
+impl Default for CostGuardSettings {
+    fn default() -> Self {
+        Self {
+            require_confirmation: false,
+            threshold_usd: 1.0,
+        }
+    }
+}
+
+pub fn validate(settings: &CostGuardSettings) -> Result<(), AppError> {
+    if !(0.0..=10_000.0).contains(&settings.threshold_usd) {
+        return Err(AppError::Validation(ValidationError::InvalidConfigValue(format!(
+            "Cost confirmation threshold must be $0-10000, got {}",
+            settings.threshold_usd
+        ))));
+    }
+    Ok(())
+}
+
+/// An operation to price; give either the content or a path to it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CostRequest {
+    Transcription {
+        path: Option<String>,
+        duration_secs: Option<f64>,
+        language: Option<String>,
+        /// Defaults to the provider set for the language
+        provider: Option<SttProviderKind>,
+        /// Sent in overlapping windows, whose overlap is billed twice
+        #[serde(default)]
+        windowed: bool,
+    },
+    LongText {
+        text: Option<String>,
+        path: Option<String>,
+        operation: LongTextOperation,
+    },
+    Speech {
+        items: Vec<SpeechItem>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpeechItem {
+    pub text: String,
+    /// Defaults to the configured voice model
+    pub model: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CostEstimate {
+    pub operation: String,
+    pub model: String,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub audio_minutes: f64,
+    pub characters: u64,
+    pub cost_usd: f64,
+    /// The model registry has no price for the model, so a default rate was used
+    pub default_rate: bool,
+    pub requires_confirmation: bool,
+    pub threshold_usd: Option<f64>,
+}
+
+fn pricing(model: &str) -> ModelPricing {
+    get_model_registry()
+        .get(model)
+        .map(|entry| entry.pricing.clone())
+        .unwrap_or_default()
+}
+
+/// Length of a recording from its container, or from its size when the container does not say.
+/// Probing reads the file, so it runs on the blocking pool
+async fn audio_secs(path: &Path) -> Result<f64, AppError> {
+    let probe_path = path.to_path_buf();
+    let probed = tokio::task::spawn_blocking(move || crate::integrations::long_audio::probe_duration(&probe_path))
+        .await
+        .map_err(|e| AppError::Internal(format!("Audio probe failed: {}", e)))?;
+    if let Some(secs) = probed {
+        return Ok(secs);
+    }
+    let bytes = tokio::fs::metadata(path)
+        .await
+        .map_err(|e| AppError::Validation(ValidationError::InvalidConfigValue(format!(
+            "Cannot read {}: {}",
+            path.display(),
+            e
+        ))))?
+        .len();
+    Ok(bytes as f64 / FALLBACK_AUDIO_BYTES_PER_SEC)
+}
+
+async fn document_text(text: Option<String>, path: Option<String>) -> Result<String, AppError> {
+    if let Some(text) = text {
+        return Ok(text);
+    }
+    let path = path.ok_or_else(|| {
+        AppError::Validation(ValidationError::InvalidConfigValue(
+            "A document estimate needs its text or a path".to_string(),
+        ))
+    })?;
+    let size = tokio::fs::metadata(&path).await.map(|metadata| metadata.len()).unwrap_or_default();
+    if size > crate::jobs::MAX_DOCUMENT_BYTES {
+        return Err(AppError::Validation(ValidationError::InvalidConfigValue(format!(
+            "{} is larger than {} MB",
+            path,
+            crate::jobs::MAX_DOCUMENT_BYTES / (1024 * 1024)
+        ))));
+    }
+    tokio::fs::read_to_string(&path)
+        .await
+        .map_err(|e| AppError::Validation(ValidationError::InvalidConfigValue(format!("Cannot read {}: {}", path, e))))
+}
+
+/// Expected usage and price of `request` with the given settings' models and providers
+pub async fn estimate(request: CostRequest, settings: &Settings) -> Result<CostEstimate, AppError> {
+    let mut estimate = match request {
+        CostRequest::Transcription {
+            path,
+            duration_secs,
+            language,
+            provider,
+            windowed,
+        } => {
+            let secs = match (duration_secs, path) {
+                (Some(secs), _) => secs,
+                (None, Some(path)) => audio_secs(Path::new(&path)).await?,
+                (None, None) => {
+                    return Err(AppError::Validation(ValidationError::InvalidConfigValue(
+                        "A transcription estimate needs a duration or a path".to_string(),
+                    )))
+                }
+            };
+            let provider = provider.unwrap_or_else(|| {
+                let language = language.unwrap_or_else(|| settings.language.clone());
+                settings.speech_to_text.provider_for(&language)
+            });
+            let long_audio = &settings.long_audio;
+            let billed_secs = if windowed {
+                let windows = crate::integrations::long_audio::window_count(secs, long_audio);
+                secs + windows.saturating_sub(1) as f64 * long_audio.overlap_secs as f64
+            } else {
+                secs
+            };
+            let per_minute = pricing(provider.label()).usd_per_audio_minute;
+            CostEstimate {
+                operation: "transcription".to_string(),
+                model: provider.label().to_string(),
+                audio_minutes: billed_secs / 60.0,
+                cost_usd: billed_secs / 60.0 * per_minute.unwrap_or(DEFAULT_TRANSCRIPTION_PRICE),
+                default_rate: per_minute.is_none(),
+                ..CostEstimate::default()
+            }
+        }
+        CostRequest::LongText { text, path, operation } => {
+            let text = document_text(text, path).await?;
+            let (model, growth) = match &operation {
+                LongTextOperation::Enhance { .. } => (settings.ai_ml_settings.text_model.clone(), 1.0),
+                LongTextOperation::Translate { .. } => {
+                    (settings.ai_ml_settings.translation_model.clone(), TRANSLATION_GROWTH)
+                }
+            };
+            let mut input_tokens = 0u64;
+            let mut text_tokens = 0u64;
+            for chunk in split_into_chunks(&text, &ChunkingConfig::default()) {
+                let content = count_tokens(&model, &chunk.content) as u64;
+                let context = chunk
+                    .preceding_context
+                    .as_deref()
+                    .map_or(0, |context| count_tokens(&model, context) as u64);
+                text_tokens += content;
+                input_tokens += content + context + PROMPT_TOKENS_PER_CHUNK;
+            }
+            let output_tokens = (text_tokens as f64 * growth).ceil() as u64;
+            let rates = pricing(&model);
+            let price = rates.input_usd_per_mtok.zip(rates.output_usd_per_mtok);
+            let (input_price, output_price) = price.unwrap_or(DEFAULT_TEXT_PRICE);
+            CostEstimate {
+                operation: match operation {
+                    LongTextOperation::Enhance { .. } => "enhance_document".to_string(),
+                    LongTextOperation::Translate { .. } => "translate_document".to_string(),
+                },
+                input_tokens,
+                output_tokens,
+                cost_usd: (input_tokens as f64 * input_price + output_tokens as f64 * output_price) / 1_000_000.0,
+                default_rate: price.is_none(),
+                model,
+                ..CostEstimate::default()
+            }
+        }
+        CostRequest::Speech { items } => {
+            let mut estimate = CostEstimate {
+                operation: "speech".to_string(),
+                ..CostEstimate::default()
+            };
+            let mut models: Vec<String> = Vec::new();
+            for item in items {
+                let model = item.model.unwrap_or_else(|| settings.ai_ml_settings.voice_model.clone());
+                let characters = item.text.chars().count() as u64;
+                let price = pricing(&model).usd_per_mchars;
+                estimate.characters += characters;
+                estimate.cost_usd += characters as f64 * price.unwrap_or(DEFAULT_SPEECH_PRICE) / 1_000_000.0;
+                estimate.default_rate |= price.is_none();
+                if !models.contains(&model) {
+                    models.push(model);
+                }
+            }
+            estimate.model = models.join(", ");
+            estimate
+        }
+    };
+
+    let guard = &settings.cost_guard;
+    estimate.threshold_usd = guard.require_confirmation.then_some(guard.threshold_usd);
+    estimate.requires_confirmation = guard.require_confirmation && estimate.cost_usd > guard.threshold_usd;
+    Ok(estimate)
+}
+
+/// Hold back an operation estimated above the threshold unless the user has confirmed it
+pub async fn require_confirmation(request: CostRequest, settings: &Settings, confirmed: bool) -> Result<(), AppError> {
+    if confirmed || !settings.cost_guard.require_confirmation {
+        return Ok(());
+    }
+    let estimate = estimate(request, settings).await?;
+    if estimate.requires_confirmation {
+        return Err(AppError::ConfirmationRequired {
+            estimated_usd: estimate.cost_usd,
+            threshold_usd: settings.cost_guard.threshold_usd,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn speech(characters: usize) -> CostRequest {
+        CostRequest::Speech {
+            items: vec![SpeechItem {
+                text: "a".repeat(characters),
+                model: Some("unpriced-voice-model".to_string()),
+            }],
+        }
+    }
+
+    #[test]
+    fn confirmation_is_opt_in() {
+        assert!(!CostGuardSettings::default().require_confirmation);
+    }
+
+    #[tokio::test]
+    async fn expensive_batches_run_unless_the_guard_is_on() {
+        let mut settings = Settings::default();
+        // 100k characters at the default $30 per million is $3
+        let estimate = estimate(speech(100_000), &settings).await.unwrap();
+        assert!(estimate.default_rate);
+        assert!((estimate.cost_usd - 3.0).abs() < 1e-9);
+        assert!(!estimate.requires_confirmation);
+        assert!(require_confirmation(speech(100_000), &settings, false).await.is_ok());
+
+        settings.cost_guard.require_confirmation = true;
+        assert!(matches!(
+            require_confirmation(speech(100_000), &settings, false).await,
+            Err(AppError::ConfirmationRequired { .. })
+        ));
+        assert!(require_confirmation(speech(100_000), &settings, true).await.is_ok());
+        assert!(require_confirmation(speech(1_000), &settings, false).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn unreadable_container_is_sized_by_bytes() {
+        let path = std::env::temp_dir().join(format!("voiceflow-cost-{}.bin", uuid::Uuid::new_v4()));
+        std::fs::write(&path, vec![0u8; 32_000]).unwrap();
+        let secs = audio_secs(&path).await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!((secs - 2.0).abs() < 1e-9);
+        assert!(audio_secs(&path).await.is_err());
+    }
+}
@@ -0,0 +1,583 @@
+//! Crash reports for VoiceFlow Pro
+//! Writes panics, native crashes with a minidump and unclean exits to local reports with a backtrace and recent
+//! log lines, never dictated text
+
+use std::backtrace::Backtrace;
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::{Mutex, MutexGuard, OnceLock, TryLockError};
+use std::time::Duration;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tauri::Window;
+use tracing_subscriber::fmt::MakeWriter;
+use uuid::Uuid;
+
+use crate::errors::{AppError, ValidationError};
+use crate::integrations::content_filter::{redact_pii, PiiKind};
+use crate::storage::{data_path, ensure_data_dir, DataDir};
+
+/// Present while the app runs; finding it at startup means the previous run never exited cleanly
+const RUNNING_MARKER: &str = "running.json";
+/// Recent log lines mirrored to disk, so an unclean exit still has them
+const RECENT_LOG_FILE: &str = "recent.log";
+/// Log lines kept in memory and attached to each report
+const LOG_LINES: usize = 200;
+/// Reports kept on disk; the oldest are deleted when a new one is written
+const MAX_REPORTS: usize = 50;
+const LOG_FLUSH_INTERVAL: Duration = Duration::from_secs(2);
+const UPLOAD_TIMEOUT: Duration = Duration::from_secs(15);
+/// First argument of the copy of the app that writes minidumps for the main process
+const MONITOR_ARG: &str = "--crash-monitor";
+/// How long the main process waits for the monitor to accept connections
+const MONITOR_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CrashReportSettings {
+    /// Send new reports to `endpoint`; nothing leaves the machine unless this is on
+    pub upload_enabled: bool,
+    /// HTTPS URL reports are POSTed to as JSON
+    pub endpoint: Option<String>,
+}
+
+impl Default for CrashReportSettings {
+    fn default() -> Self {
+        Self {
+            upload_enabled: false,
+            endpoint: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CrashKind {
+    Panic,
+    /// A native crash such as a segfault, captured by the monitor process with a minidump
+    Native,
+    /// The process ended without shutting down, e.g. a native crash or being killed; there is no backtrace
+    UncleanExit,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrashReport {
+    pub id: String,
+    pub kind: CrashKind,
+    pub timestamp: u64,
+    /// The run the crash happened in
+    pub run_id: String,
+    pub app_version: String,
+    pub os: String,
+    pub arch: String,
+    pub thread: Option<String>,
+    /// Panic message with quoted values removed, since they can hold user text
+    pub message: Option<String>,
+    pub location: Option<String>,
+    pub backtrace: Option<String>,
+    /// The last log lines before the crash, with quoted values and personal data redacted
+    pub log_lines: Vec<String>,
+    /// File name of the minidump in the crash folder. Dumps hold raw stack memory, which can include dictated
+    /// text, so they stay on this machine and are never uploaded
+    #[serde(default)]
+    pub minidump: Option<String>,
+    /// Shown to the user after the crash
+    pub seen: bool,
+    pub uploaded_at: Option<u64>,
+}
+
+/// Contents of the running marker
+#[derive(Debug, Serialize, Deserialize)]
+struct RunMarker {
+    run_id: String,
+    pid: u32,
+    started_at: u64,
+    app_version: String,
+}
+
+#[derive(Default)]
+struct RecentLog {
+    lines: VecDeque<String>,
+    /// Lines were added since the last flush to disk
+    dirty: bool,
+}
+
+static RUN_ID: OnceLock<String> = OnceLock::new();
+static RECENT_LOG: OnceLock<Mutex<RecentLog>> = OnceLock::new();
+static QUOTED: OnceLock<Option<Regex>> = OnceLock::new();
+
+fn run_id() -> &'static str {
+    RUN_ID.get_or_init(|| Uuid::new_v4().to_string())
+}
+
+fn recent_log() -> MutexGuard<'static, RecentLog> {
+    let log = RECENT_LOG.get_or_init(|| Mutex::new(RecentLog::default()));
+    match log.lock() {
+        Ok(log) => log,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+/// Formatted log output goes to stderr as before and into the recent log
+#[derive(Debug, Clone, Copy)]
+struct RecentLogWriter;
+
+impl<'a> MakeWriter<'a> for RecentLogWriter {
+    type Writer = RecentLogWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        *self
+    }
+}
+
+impl Write for RecentLogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let _ = io::stderr().write_all(buf);
+        let text = String::from_utf8_lossy(buf);
+        let mut log = recent_log();
+        for line in text.lines().filter(|line| !line.trim().is_empty()) {
+            if log.lines.len() == LOG_LINES {
+                log.lines.pop_front();
+            }
+            log.lines.push_back(redact(line));
+        }
+        log.dirty = true;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()
+    }
+}
+
+/// Set up logging and the panic hook and turn a leftover running marker into a report;
+/// call first thing in `main`
+pub fn install() {
+    let _ = tracing_subscriber::fmt()
+        .with_writer(RecentLogWriter)
+        .with_ansi(false)
+        .with_max_level(tracing::Level::INFO)
+        .try_init();
+
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let message = info
+            .payload()
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| info.payload().downcast_ref::<String>().cloned());
+        let location = info
+            .location()
+            .map(|location| format!("{}:{}:{}", location.file(), location.line(), location.column()));
+        write_panic_report(message, location);
+        previous(info);
+    }));
+
+    if let Err(e) = check_previous_run() {
+        tracing::warn!("Could not check how the last run ended: {}", e);
+    }
+
+    std::thread::spawn(|| loop {
+        std::thread::sleep(LOG_FLUSH_INTERVAL);
+        flush_recent_log();
+    });
+
+    if let Err(e) = start_minidump_capture() {
+        tracing::warn!("Native crashes will be reported without a minidump: {}", e);
+    }
+}
+
+/// When this process was started as the crash monitor, serve minidump requests until the main process exits
+/// and return true; call before anything else in `main`
+pub fn run_as_monitor() -> bool {
+    let mut args = std::env::args().skip(1);
+    if args.next().as_deref() != Some(MONITOR_ARG) {
+        return false;
+    }
+    let (Some(socket), Some(run_id)) = (args.next(), args.next()) else {
+        return true;
+    };
+    let server = match minidumper::Server::with_name(minidumper::SocketName::Path(Path::new(&socket))) {
+        Ok(server) => server,
+        Err(e) => {
+            eprintln!("Crash monitor could not listen on {}: {}", socket, e);
+            return true;
+        }
+    };
+    let shutdown = AtomicBool::new(false);
+    if let Err(e) = server.run(Box::new(MinidumpHandler { run_id }), &shutdown, None) {
+        eprintln!("Crash monitor stopped: {}", e);
+    }
+    let _ = fs::remove_file(&socket);
+    true
+}
+
+/// A native crash cannot be written from inside the crashing process, so a second copy of the app runs as
+/// a monitor and dumps this one when the crash handler asks it to
+fn start_minidump_capture() -> Result<(), AppError> {
+    let socket = std::env::temp_dir().join(format!("voiceflow-crash-{}.sock", std::process::id()));
+    let _ = fs::remove_file(&socket);
+    let exe = std::env::current_exe().map_err(|e| AppError::Internal(format!("Unknown executable path: {}", e)))?;
+    let monitor = std::process::Command::new(exe)
+        .arg(MONITOR_ARG)
+        .arg(&socket)
+        .arg(run_id())
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .spawn()
+        .map_err(|e| AppError::Internal(format!("Crash monitor did not start: {}", e)))?;
+
+    let started = std::time::Instant::now();
+    let client = loop {
+        match minidumper::Client::with_name(minidumper::SocketName::Path(&socket)) {
+            Ok(client) => break client,
+            Err(e) if started.elapsed() >= MONITOR_CONNECT_TIMEOUT => {
+                return Err(AppError::Internal(format!("Crash monitor did not answer: {}", e)));
+            }
+            Err(_) => std::thread::sleep(Duration::from_millis(50)),
+        }
+    };
+
+    // Safety: the closure only sends the crash context to the monitor and allocates nothing
+    let handler = crash_handler::CrashHandler::attach(unsafe {
+        crash_handler::make_crash_event(move |context: &crash_handler::CrashContext| {
+            crash_handler::CrashEventResult::Handled(client.request_dump(context).is_ok())
+        })
+    })
+    .map_err(|e| AppError::Internal(format!("Crash handler not installed: {}", e)))?;
+    // Yama ptrace restrictions would otherwise stop the monitor from reading this process
+    #[cfg(target_os = "linux")]
+    handler.set_ptracer(Some(monitor.id()));
+    #[cfg(not(target_os = "linux"))]
+    let _ = monitor;
+    // Installed for the life of the process; dropping it would detach the handler
+    std::mem::forget(handler);
+    Ok(())
+}
+
+/// Runs in the monitor: writes the dump and its report, then exits along with the crashed process
+struct MinidumpHandler {
+    run_id: String,
+}
+
+impl minidumper::ServerHandler for MinidumpHandler {
+    fn create_minidump_file(&self) -> Result<(fs::File, PathBuf), io::Error> {
+        let dir = ensure_data_dir(DataDir::Crashes).map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+        let path = dir.join(format!("{}.dmp", Uuid::new_v4()));
+        Ok((fs::File::create(&path)?, path))
+    }
+
+    fn on_minidump_created(&self, result: Result<minidumper::MinidumpBinary, minidumper::Error>) -> minidumper::LoopAction {
+        match result {
+            Ok(binary) => {
+                // The crashed process no longer flushes its log, so the last lines it wrote to disk are used
+                let log_lines = data_path(DataDir::Crashes)
+                    .and_then(|dir| {
+                        fs::read_to_string(dir.join(RECENT_LOG_FILE)).map_err(|e| AppError::Internal(e.to_string()))
+                    })
+                    .map(|log| log.lines().map(str::to_string).collect())
+                    .unwrap_or_default();
+                let report = CrashReport {
+                    minidump: binary.path.file_name().map(|name| name.to_string_lossy().to_string()),
+                    log_lines,
+                    ..new_report(CrashKind::Native, self.run_id.clone(), now_secs())
+                };
+                if let Err(e) = save(&report) {
+                    eprintln!("Failed to write crash report: {}", e);
+                }
+            }
+            Err(e) => eprintln!("Failed to write minidump: {}", e),
+        }
+        minidumper::LoopAction::Exit
+    }
+
+    fn on_message(&self, _kind: u32, _buffer: Vec<u8>) {}
+
+    fn on_client_disconnected(&self, clients: usize) -> minidumper::LoopAction {
+        if clients == 0 {
+            minidumper::LoopAction::Exit
+        } else {
+            minidumper::LoopAction::Continue
+        }
+    }
+}
+
+/// Remove the running marker; the next launch then finds nothing to report
+pub fn mark_clean_exit() {
+    if let Ok(dir) = data_path(DataDir::Crashes) {
+        let _ = fs::remove_file(dir.join(RUNNING_MARKER));
+        let _ = fs::remove_file(dir.join(RECENT_LOG_FILE));
+    }
+}
+
+fn write_panic_report(message: Option<String>, location: Option<String>) {
+    // The panic may have happened while the log was locked, so never wait for it here
+    let log_lines = match RECENT_LOG.get().map(Mutex::try_lock) {
+        Some(Ok(log)) => log.lines.iter().cloned().collect(),
+        Some(Err(TryLockError::Poisoned(poisoned))) => poisoned.into_inner().lines.iter().cloned().collect(),
+        _ => Vec::new(),
+    };
+    let report = CrashReport {
+        thread: std::thread::current().name().map(str::to_string),
+        message: message.map(|message| redact(&message)),
+        location,
+        backtrace: Some(Backtrace::force_capture().to_string()),
+        log_lines,
+        ..new_report(CrashKind::Panic, run_id().to_string(), now_secs())
+    };
+    if let Err(e) = save(&report) {
+        eprintln!("Failed to write crash report: {}", e);
+    }
+}
+
+fn check_previous_run() -> Result<(), AppError> {
+    let dir = ensure_data_dir(DataDir::Crashes)?;
+    let marker_path = dir.join(RUNNING_MARKER);
+
+    if let Ok(contents) = fs::read_to_string(&marker_path) {
+        if let Ok(marker) = serde_json::from_str::<RunMarker>(&contents) {
+            // A panic that took the process down already has its own report
+            let reported = list()?.iter().any(|report| report.run_id == marker.run_id);
+            if !reported {
+                let log_lines = fs::read_to_string(dir.join(RECENT_LOG_FILE))
+                    .map(|log| log.lines().map(str::to_string).collect())
+                    .unwrap_or_default();
+                let report = CrashReport {
+                    app_version: marker.app_version,
+                    log_lines,
+                    ..new_report(CrashKind::UncleanExit, marker.run_id, now_secs())
+                };
+                save(&report)?;
+                tracing::warn!("The previous run (pid {}) did not exit cleanly", marker.pid);
+            }
+        }
+    }
+
+    let marker = RunMarker {
+        run_id: run_id().to_string(),
+        pid: std::process::id(),
+        started_at: now_secs(),
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+    };
+    let _ = fs::remove_file(dir.join(RECENT_LOG_FILE));
+    fs::write(&marker_path, serde_json::to_vec(&marker)?)
+        .map_err(|e| AppError::Internal(format!("Failed to write {}: {}", marker_path.display(), e)))
+}
+
+fn flush_recent_log() {
+    let lines = {
+        let mut log = recent_log();
+        if !log.dirty {
+            return;
+        }
+        log.dirty = false;
+        log.lines.iter().cloned().collect::<Vec<_>>()
+    };
+    if let Ok(dir) = data_path(DataDir::Crashes) {
+        let mut contents = lines.join("\n");
+        contents.push('\n');
+        let _ = fs::write(dir.join(RECENT_LOG_FILE), contents);
+    }
+}
+
+fn new_report(kind: CrashKind, run_id: String, timestamp: u64) -> CrashReport {
+    CrashReport {
+        id: Uuid::new_v4().to_string(),
+        kind,
+        timestamp,
+        run_id,
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        os: std::env::consts::OS.to_string(),
+        arch: std::env::consts::ARCH.to_string(),
+        thread: None,
+        message: None,
+        location: None,
+        backtrace: None,
+        log_lines: Vec::new(),
+        minidump: None,
+        seen: false,
+        uploaded_at: None,
+    }
+}
+
+/// Quoted values in log lines and panic messages are where dictated text would show up
+fn redact(line: &str) -> String {
+    let quoted = QUOTED.get_or_init(|| Regex::new(r#""(?:[^"\\]|\\.)*"|`[^`]*`"#).ok());
+    let line = match quoted {
+        Some(regex) => regex.replace_all(line, "\"[redacted]\"").into_owned(),
+        None => line.to_string(),
+    };
+    redact_pii(&line, &PiiKind::ALL).text
+}
+
+/// Reports on disk, newest first
+pub fn list() -> Result<Vec<CrashReport>, AppError> {
+    let dir = data_path(DataDir::Crashes)?;
+    let Ok(entries) = fs::read_dir(&dir) else {
+        return Ok(Vec::new());
+    };
+    let mut reports: Vec<CrashReport> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().map_or(false, |ext| ext == "json") && !path.ends_with(RUNNING_MARKER))
+        .filter_map(|path| fs::read(&path).ok())
+        .filter_map(|bytes| serde_json::from_slice(&bytes).ok())
+        .collect();
+    reports.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+    Ok(reports)
+}
+
+/// Mark reports as shown to the user; no ids marks all of them
+pub fn mark_seen(ids: &[String]) -> Result<usize, AppError> {
+    let mut marked = 0;
+    for mut report in list()? {
+        if !report.seen && (ids.is_empty() || ids.contains(&report.id)) {
+            report.seen = true;
+            save(&report)?;
+            marked += 1;
+        }
+    }
+    Ok(marked)
+}
+
+pub fn delete(id: &str) -> Result<bool, AppError> {
+    let path = report_path(id)?;
+    if !path.exists() {
+        return Ok(false);
+    }
+    let minidump = fs::read(&path)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice::<CrashReport>(&bytes).ok())
+        .and_then(|report| report.minidump);
+    fs::remove_file(&path).map_err(|e| AppError::Internal(format!("Failed to delete {}: {}", path.display(), e)))?;
+    remove_minidump(minidump.as_deref());
+    Ok(true)
+}
+
+fn remove_minidump(name: Option<&str>) {
+    // Only bare file names written by the monitor are joined onto the crash folder
+    let Some(name) = name.filter(|name| name.ends_with(".dmp") && !name.contains(['/', '\\']) && !name.contains("..")) else {
+        return;
+    };
+    if let Ok(dir) = data_path(DataDir::Crashes) {
+        let _ = fs::remove_file(dir.join(name));
+    }
+}
+
+/// Tell the frontend about reports the user has not seen yet, once the main window has loaded
+pub fn notify_unseen(window: &Window) {
+    let unseen: Vec<CrashReport> = list().unwrap_or_default().into_iter().filter(|report| !report.seen).collect();
+    if !unseen.is_empty() {
+        let _ = window.emit("crash-reports-found", &unseen);
+    }
+}
+
+/// POST one report to the configured endpoint; only allowed once the user has opted in
+pub async fn upload(id: &str, settings: &CrashReportSettings) -> Result<CrashReport, AppError> {
+    let endpoint = upload_endpoint(settings)?;
+    let mut report: CrashReport = {
+        let path = report_path(id)?;
+        let bytes = fs::read(&path)
+            .map_err(|_| AppError::Validation(ValidationError::InvalidConfigValue(format!("Unknown crash report {:?}", id))))?;
+        serde_json::from_slice(&bytes)?
+    };
+
+    crate::network::client(UPLOAD_TIMEOUT)?
+        .post(endpoint)
+        .json(&report)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| AppError::Network(format!("Crash report upload failed: {}", e)))?;
+
+    report.uploaded_at = Some(now_secs());
+    save(&report)?;
+    Ok(report)
+}
+
+/// Upload every report not sent yet; does nothing unless uploads are enabled
+pub async fn upload_pending(settings: &CrashReportSettings) -> usize {
+    if upload_endpoint(settings).is_err() {
+        return 0;
+    }
+    let pending: Vec<String> = list()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|report| report.uploaded_at.is_none())
+        .map(|report| report.id)
+        .collect();
+    let mut uploaded = 0;
+    for id in pending {
+        match upload(&id, settings).await {
+            Ok(_) => uploaded += 1,
+            Err(e) => {
+                tracing::warn!("{}", e);
+                break;
+            }
+        }
+    }
+    uploaded
+}
+
+/// Uploads may be switched on only with a usable endpoint
+pub fn validate(settings: &CrashReportSettings) -> Result<(), AppError> {
+    if settings.upload_enabled {
+        upload_endpoint(settings)?;
+    }
+    Ok(())
+}
+
+fn upload_endpoint(settings: &CrashReportSettings) -> Result<&str, AppError> {
+    let invalid = |message: &str| AppError::Validation(ValidationError::InvalidConfigValue(message.to_string()));
+    if !settings.upload_enabled {
+        return Err(invalid("Crash report upload is turned off"));
+    }
+    let endpoint = settings
+        .endpoint
+        .as_deref()
+        .map(str::trim)
+        .filter(|endpoint| !endpoint.is_empty())
+        .ok_or_else(|| invalid("No crash report endpoint is configured"))?;
+    let local = endpoint.starts_with("http://localhost") || endpoint.starts_with("http://127.0.0.1");
+    if !endpoint.starts_with("https://") && !local {
+        return Err(invalid("Crash report endpoint must use https"));
+    }
+    Ok(endpoint)
+}
+
+fn save(report: &CrashReport) -> Result<(), AppError> {
+    ensure_data_dir(DataDir::Crashes)?;
+    let path = report_path(&report.id)?;
+    fs::write(&path, serde_json::to_vec_pretty(report)?)
+        .map_err(|e| AppError::Internal(format!("Failed to write {}: {}", path.display(), e)))?;
+
+    let reports = list()?;
+    for old in reports.iter().skip(MAX_REPORTS) {
+        if let Ok(path) = report_path(&old.id) {
+            let _ = fs::remove_file(path);
+        }
+        remove_minidump(old.minidump.as_deref());
+    }
+    Ok(())
+}
+
+fn report_path(id: &str) -> Result<PathBuf, AppError> {
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_hexdigit() || c == '-') {
+        return Err(AppError::Validation(ValidationError::InvalidConfigValue(format!(
+            "Invalid crash report id {:?}",
+            id
+        ))));
+    }
+    Ok(data_path(DataDir::Crashes)?.join(format!("{}.json", id)))
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
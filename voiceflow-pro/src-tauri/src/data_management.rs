@@ -0,0 +1,531 @@
+//! User data management for VoiceFlow Pro
+//! Exports everything the app keeps about the user and purges it on request
+
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
+
+use crate::encryption::get_data_vault;
+use crate::errors::AppError;
+use crate::job_store::JobDatabase;
+use crate::storage::{data_path, profile_dirs_all, DataDir};
+use crate::{AppState, Settings};
+
+/// Block size used when overwriting files before removal
+const WIPE_BLOCK_SIZE: usize = 64 * 1024;
+
+/// Kinds of user data the app stores
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DataCategory {
+    History,
+    Recordings,
+    Memories,
+    Settings,
+    Cache,
+    AuditLog,
+    CrashReports,
+}
+
+impl DataCategory {
+    /// On-disk locations, for categories stored as files
+    fn data_dirs(self) -> &'static [DataDir] {
+        match self {
+            DataCategory::History => &[DataDir::History, DataDir::Drafts],
+            // Recorded sessions hold dictation audio alongside what was said
+            DataCategory::Recordings => &[DataDir::Recordings, DataDir::Sessions],
+            DataCategory::Cache => &[DataDir::Cache],
+            DataCategory::AuditLog => &[DataDir::Audit],
+            DataCategory::CrashReports => &[DataDir::Crashes],
+            DataCategory::Memories | DataCategory::Settings => &[],
+        }
+    }
+
+    /// Files at the root of each profile's directory, loaded again at startup and on profile switch
+    fn profile_files(self) -> &'static [&'static str] {
+        match self {
+            DataCategory::Settings => &[
+                crate::profiles::SETTINGS_FILE,
+                crate::integrations::voice_calibration::PROFILE_FILE,
+            ],
+            DataCategory::Memories => &[crate::profiles::MEMORIES_FILE],
+            _ => &[],
+        }
+    }
+
+    /// Audio and cached model output may contain verbatim speech, so they are overwritten before removal
+    fn requires_secure_delete(self) -> bool {
+        matches!(self, DataCategory::Recordings | DataCategory::Cache)
+    }
+}
+
+/// A file written by an export
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportedFile {
+    pub category: DataCategory,
+    pub relative_path: String,
+    pub bytes: u64,
+}
+
+/// Result of `export_all_user_data`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportReport {
+    pub export_path: String,
+    pub files: Vec<ExportedFile>,
+    pub total_bytes: u64,
+    pub exported_at: u64,
+}
+
+/// Something deleted (or that would be deleted in a dry run)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PurgeItem {
+    pub category: DataCategory,
+    pub target: String,
+    pub bytes: u64,
+    pub secure: bool,
+}
+
+/// Result of `purge_all_user_data`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PurgeReport {
+    pub dry_run: bool,
+    pub items: Vec<PurgeItem>,
+    pub total_bytes: u64,
+    pub errors: Vec<String>,
+}
+
+/// Export history, recordings, memories and settings into a new folder under `destination`
+pub async fn export_all_user_data(destination: &Path, state: &AppState) -> Result<ExportReport, AppError> {
+    if !destination.is_absolute() || destination.components().any(|c| c == std::path::Component::ParentDir) {
+        return Err(AppError::Validation(
+            "Export destination must be an absolute path without '..'".to_string().into(),
+        ));
+    }
+
+    let exported_at = now_secs();
+    let export_root = destination.join(format!("voiceflow-export-{}", exported_at));
+    fs::create_dir_all(&export_root)
+        .map_err(|e| AppError::Internal(format!("Failed to create {}: {}", export_root.display(), e)))?;
+
+    let mut files = Vec::new();
+
+    // Settings are exported with credentials removed
+    let mut settings = Settings::clone(&state.settings.snapshot());
+    redact_secrets(&mut settings);
+    files.push(write_json(&export_root, "settings.json", DataCategory::Settings, &settings)?);
+
+    let memory = match state.ai_ml_gateway.lock().await.as_ref() {
+        Some(gateway) => Some(gateway.conversation_memory().await),
+        None => None,
+    };
+    if let Some(memory) = memory {
+        files.push(write_json(&export_root, "memories.json", DataCategory::Memories, &memory)?);
+    }
+
+    let root = export_root.clone();
+    let copied = tokio::task::spawn_blocking(move || -> Result<Vec<ExportedFile>, AppError> {
+        let mut copied = Vec::new();
+        for category in [
+            DataCategory::History,
+            DataCategory::Recordings,
+            DataCategory::AuditLog,
+            DataCategory::CrashReports,
+        ] {
+            for dir in category.data_dirs() {
+                copied.extend(copy_tree(&data_path(*dir)?, &root, category)?);
+            }
+        }
+        // What every profile saved, not just the active one held in memory above
+        for category in [DataCategory::Settings, DataCategory::Memories] {
+            copied.extend(copy_profile_files(category, &root)?);
+        }
+        copied.extend(export_job_databases(&root)?);
+        Ok(copied)
+    })
+    .await
+    .map_err(|e| AppError::Internal(format!("Export task failed: {}", e)))??;
+    files.extend(copied);
+
+    let report = ExportReport {
+        export_path: export_root.display().to_string(),
+        total_bytes: files.iter().map(|f| f.bytes).sum(),
+        files,
+        exported_at,
+    };
+    fs::write(
+        export_root.join("manifest.json"),
+        serde_json::to_vec_pretty(&report).map_err(|e| AppError::Internal(e.to_string()))?,
+    )
+    .map_err(|e| AppError::Internal(format!("Failed to write manifest: {}", e)))?;
+
+    Ok(report)
+}
+
+/// Delete the selected categories; with `dry_run` only report what would be removed
+pub async fn purge_all_user_data(categories: &[DataCategory], dry_run: bool, state: &AppState) -> PurgeReport {
+    let mut report = PurgeReport {
+        dry_run,
+        items: Vec::new(),
+        total_bytes: 0,
+        errors: Vec::new(),
+    };
+
+    for &category in categories {
+        match category {
+            DataCategory::Memories => {
+                if let Some(gateway) = state.ai_ml_gateway.lock().await.as_ref() {
+                    let memory = gateway.conversation_memory().await;
+                    report.items.push(PurgeItem {
+                        category,
+                        target: format!("conversation memory ({} messages)", memory.messages.len()),
+                        bytes: 0,
+                        secure: false,
+                    });
+                    if !dry_run {
+                        gateway.clear_conversation_memory().await;
+                    }
+                }
+                purge_profile_files_blocking(category, dry_run, &mut report).await;
+            }
+            DataCategory::Settings => {
+                report.items.push(PurgeItem {
+                    category,
+                    target: "application settings (reset to defaults)".to_string(),
+                    bytes: 0,
+                    secure: false,
+                });
+                if !dry_run {
+                    *state.settings.write().await = Settings::default();
+                    forget_profile_secrets(&mut report);
+                }
+                purge_profile_files_blocking(category, dry_run, &mut report).await;
+            }
+            DataCategory::History
+            | DataCategory::Recordings
+            | DataCategory::Cache
+            | DataCategory::AuditLog
+            | DataCategory::CrashReports => {
+                if category == DataCategory::Cache {
+                    if let Some(gateway) = state.ai_ml_gateway.lock().await.as_ref() {
+                        report.items.push(PurgeItem {
+                            category,
+                            target: format!("in-memory result caches ({} entries)", gateway.cached_entries().await),
+                            bytes: 0,
+                            secure: false,
+                        });
+                    }
+                }
+
+                let outcome = tokio::task::spawn_blocking(move || purge_directory(category, dry_run)).await;
+                if category == DataCategory::Cache && !dry_run {
+                    // The result files were securely wiped above; this drops the memory copies and disk index
+                    if let Some(gateway) = state.ai_ml_gateway.lock().await.as_ref() {
+                        gateway.clear_caches().await;
+                    }
+                }
+                if category == DataCategory::History && !dry_run {
+                    // Statistics, ratings and transcript segments live in the history folder; drop the copies held in memory too
+                    crate::analytics::get_dictation_analytics().lock().await.reset();
+                    crate::feedback::get_feedback_loop().lock().await.reset();
+                    crate::history::get_transcript_history().lock().await.reset();
+                    // Dictation waiting for approval is history that was never typed
+                    crate::preview::clear().await;
+                }
+                if category == DataCategory::History {
+                    // Background jobs keep partial transcripts and document outputs in each profile's job database
+                    purge_job_databases(dry_run, &mut report).await;
+                }
+                match outcome {
+                    Ok((items, errors)) => {
+                        report.items.extend(items);
+                        report.errors.extend(errors);
+                    }
+                    Err(e) => report.errors.push(format!("{:?} purge task failed: {}", category, e)),
+                }
+            }
+        }
+    }
+
+    report.total_bytes = report.items.iter().map(|item| item.bytes).sum();
+    report
+}
+
+async fn purge_job_databases(dry_run: bool, report: &mut PurgeReport) {
+    let databases = match JobDatabase::existing() {
+        Ok(databases) => databases,
+        Err(e) => {
+            report.errors.push(e.to_string());
+            return;
+        }
+    };
+    if !dry_run {
+        if let Err(e) = crate::jobs::get_job_scheduler().lock().await.clear() {
+            report.errors.push(format!("active job database: {}", e));
+        }
+    }
+    for (profile_id, bytes) in databases {
+        if !dry_run {
+            let id = profile_id.clone();
+            let erased = tokio::task::spawn_blocking(move || JobDatabase::open(&id)?.erase()).await;
+            match erased {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => {
+                    report.errors.push(format!("job database of profile {}: {}", profile_id, e));
+                    continue;
+                }
+                Err(e) => {
+                    report.errors.push(format!("History purge task failed: {}", e));
+                    continue;
+                }
+            }
+        }
+        report.items.push(PurgeItem {
+            category: DataCategory::History,
+            target: format!("background jobs of profile {}", profile_id),
+            bytes,
+            secure: true,
+        });
+    }
+}
+
+/// Profile credentials live in the keychain beside the settings file, and go with it
+fn forget_profile_secrets(report: &mut PurgeReport) {
+    match profile_dirs_all() {
+        Ok(dirs) => {
+            for (id, _) in dirs {
+                if let Err(e) = crate::profiles::forget_profile_secrets(&id) {
+                    report.errors.push(format!("credentials of profile {}: {}", id, e));
+                }
+            }
+        }
+        Err(e) => report.errors.push(e.to_string()),
+    }
+}
+
+async fn purge_profile_files_blocking(category: DataCategory, dry_run: bool, report: &mut PurgeReport) {
+    match tokio::task::spawn_blocking(move || purge_profile_files(category, dry_run)).await {
+        Ok((items, errors)) => {
+            report.items.extend(items);
+            report.errors.extend(errors);
+        }
+        Err(e) => report.errors.push(format!("{:?} purge task failed: {}", category, e)),
+    }
+}
+
+/// Remove a category's files from every profile, so the next start does not load them back
+fn purge_profile_files(category: DataCategory, dry_run: bool) -> (Vec<PurgeItem>, Vec<String>) {
+    let mut items = Vec::new();
+    let mut errors = Vec::new();
+    let dirs = match profile_dirs_all() {
+        Ok(dirs) => dirs,
+        Err(e) => return (items, vec![e.to_string()]),
+    };
+
+    for (_, dir) in dirs {
+        for name in category.profile_files() {
+            let path = dir.join(name);
+            let Ok(metadata) = fs::metadata(&path) else {
+                continue;
+            };
+            if !dry_run {
+                if let Err(e) = fs::remove_file(&path) {
+                    errors.push(format!("{}: {}", path.display(), e));
+                    continue;
+                }
+            }
+            items.push(PurgeItem {
+                category,
+                target: path.display().to_string(),
+                bytes: metadata.len(),
+                secure: false,
+            });
+        }
+    }
+
+    (items, errors)
+}
+
+/// Remove every file under a category's directory, wiping them first where required
+fn purge_directory(category: DataCategory, dry_run: bool) -> (Vec<PurgeItem>, Vec<String>) {
+    let mut items = Vec::new();
+    let mut errors = Vec::new();
+
+    for dir in category.data_dirs() {
+        match data_path(*dir) {
+            Ok(root) if root.exists() => purge_tree(&root, category, dry_run, &mut items, &mut errors),
+            Ok(_) => {}
+            Err(e) => errors.push(e.to_string()),
+        }
+    }
+
+    (items, errors)
+}
+
+fn purge_tree(root: &Path, category: DataCategory, dry_run: bool, items: &mut Vec<PurgeItem>, errors: &mut Vec<String>) {
+    let secure = category.requires_secure_delete();
+    for entry in WalkDir::new(root).into_iter().filter_map(|e| e.ok()) {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let path = entry.path();
+        let bytes = entry.metadata().map(|m| m.len()).unwrap_or(0);
+
+        if !dry_run {
+            let result = if secure { secure_delete(path) } else { fs::remove_file(path) };
+            if let Err(e) = result {
+                errors.push(format!("{}: {}", path.display(), e));
+                continue;
+            }
+        }
+        items.push(PurgeItem {
+            category,
+            target: path.display().to_string(),
+            bytes,
+            secure,
+        });
+    }
+
+    if !dry_run {
+        // Leave the (now empty) directory tree in place for the app to reuse
+        for entry in WalkDir::new(root).contents_first(true).min_depth(1).into_iter().filter_map(|e| e.ok()) {
+            if entry.file_type().is_dir() {
+                let _ = fs::remove_dir(entry.path());
+            }
+        }
+    }
+}
+
+/// Overwrite a file with zeros, flush it to disk, then unlink it.
+/// On SSDs and copy-on-write filesystems this is best effort; full-disk encryption is the real guarantee.
+pub(crate) fn secure_delete(path: &Path) -> std::io::Result<()> {
+    let len = fs::metadata(path)?.len();
+    {
+        let mut file = OpenOptions::new().write(true).open(path)?;
+        let zeros = vec![0u8; WIPE_BLOCK_SIZE];
+        let mut remaining = len;
+        while remaining > 0 {
+            let chunk = remaining.min(WIPE_BLOCK_SIZE as u64) as usize;
+            file.write_all(&zeros[..chunk])?;
+            remaining -= chunk as u64;
+        }
+        file.sync_all()?;
+    }
+    fs::remove_file(path)
+}
+
+fn copy_tree(source: &Path, export_root: &Path, category: DataCategory) -> Result<Vec<ExportedFile>, AppError> {
+    let mut files = Vec::new();
+    if !source.exists() {
+        return Ok(files);
+    }
+    let folder = source.file_name().map(PathBuf::from).unwrap_or_default();
+
+    for entry in WalkDir::new(source).into_iter().filter_map(|e| e.ok()) {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let relative = folder.join(entry.path().strip_prefix(source).unwrap_or(entry.path()));
+        let target = export_root.join(&relative);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).map_err(|e| AppError::Internal(e.to_string()))?;
+        }
+        // Exports are always plaintext so they remain readable outside the app
+        let contents = get_data_vault().read_file(entry.path())?;
+        fs::write(&target, &contents)
+            .map_err(|e| AppError::Internal(format!("Failed to write {}: {}", target.display(), e)))?;
+        let bytes = contents.len() as u64;
+        files.push(ExportedFile {
+            category,
+            relative_path: relative.display().to_string(),
+            bytes,
+        });
+    }
+    Ok(files)
+}
+
+/// Each profile's background jobs with their partial outputs, as `profiles/<id>/jobs.json`
+fn export_job_databases(export_root: &Path) -> Result<Vec<ExportedFile>, AppError> {
+    let mut files = Vec::new();
+    for (id, _) in JobDatabase::existing()? {
+        let jobs = JobDatabase::open(&id)?.export()?;
+        let dir = export_root.join("profiles").join(&id);
+        fs::create_dir_all(&dir).map_err(|e| AppError::Internal(e.to_string()))?;
+        let mut file = write_json(&dir, "jobs.json", DataCategory::History, &jobs)?;
+        file.relative_path = PathBuf::from("profiles").join(&id).join("jobs.json").display().to_string();
+        files.push(file);
+    }
+    Ok(files)
+}
+
+/// Each profile's saved copy of a category's files, under `profiles/<id>/` in the export
+fn copy_profile_files(category: DataCategory, export_root: &Path) -> Result<Vec<ExportedFile>, AppError> {
+    let mut files = Vec::new();
+    for (id, dir) in profile_dirs_all()? {
+        for name in category.profile_files() {
+            let source = dir.join(name);
+            if !source.exists() {
+                continue;
+            }
+            let relative = PathBuf::from("profiles").join(&id).join(name);
+            let target = export_root.join(&relative);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent).map_err(|e| AppError::Internal(e.to_string()))?;
+            }
+            let mut contents = get_data_vault().read_file(&source)?;
+            // Saved settings leave with their credentials removed, like the active settings
+            if *name == crate::profiles::SETTINGS_FILE {
+                if let Ok(mut settings) = serde_json::from_slice::<Settings>(&contents) {
+                    redact_secrets(&mut settings);
+                    contents = serde_json::to_vec_pretty(&settings).map_err(|e| AppError::Internal(e.to_string()))?;
+                }
+            }
+            fs::write(&target, &contents)
+                .map_err(|e| AppError::Internal(format!("Failed to write {}: {}", target.display(), e)))?;
+            files.push(ExportedFile {
+                category,
+                relative_path: relative.display().to_string(),
+                bytes: contents.len() as u64,
+            });
+        }
+    }
+    Ok(files)
+}
+
+fn write_json<T: Serialize>(root: &Path, name: &str, category: DataCategory, value: &T) -> Result<ExportedFile, AppError> {
+    let json = serde_json::to_vec_pretty(value).map_err(|e| AppError::Internal(e.to_string()))?;
+    let mut file = File::create(root.join(name))
+        .map_err(|e| AppError::Internal(format!("Failed to create {}: {}", name, e)))?;
+    file.write_all(&json)
+        .map_err(|e| AppError::Internal(format!("Failed to write {}: {}", name, e)))?;
+    Ok(ExportedFile {
+        category,
+        relative_path: name.to_string(),
+        bytes: json.len() as u64,
+    })
+}
+
+fn redact_secrets(settings: &mut Settings) {
+    if !settings.ai_ml_settings.api_key.is_empty() {
+        settings.ai_ml_settings.api_key = "<redacted>".to_string();
+    }
+    if settings.destinations.graph_access_token.is_some() {
+        settings.destinations.graph_access_token = Some("<redacted>".to_string());
+    }
+    if settings.streaming.obs_password.is_some() {
+        settings.streaming.obs_password = Some("<redacted>".to_string());
+    }
+    if settings.network.proxy_password.is_some() {
+        settings.network.proxy_password = Some("<redacted>".to_string());
+    }
+    settings.speech_to_text.redact_keys();
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
@@ -0,0 +1,398 @@
+//! Domain packs for VoiceFlow Pro
+//! Installs signed bundles of vocabulary, glossaries, prompt templates and profile presets for verticals such as medical or legal
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{AppError, ValidationError};
+use crate::integrations::keyword_boost::{clean_keywords, KeywordBoostSettings};
+use crate::storage::{data_path, ensure_data_dir, DataDir};
+
+const MAX_PACK_BYTES: usize = 2 * 1024 * 1024;
+const MAX_GLOSSARY_IN_PROMPT: usize = 200;
+
+/// A pack as distributed: the pack itself and an optional publisher signature over it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DomainPackFile {
+    /// Kept as JSON so the signed bytes can be reproduced exactly
+    pub pack: serde_json::Value,
+    #[serde(default)]
+    pub signature: Option<PackSignature>,
+}
+
+/// Ed25519 signature over the compact JSON of `pack` with object keys in sorted order
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackSignature {
+    pub key_id: String,
+    /// Base64 signature bytes
+    pub signature: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DomainPack {
+    /// Lowercase identifier such as "medical"; installing the same id again upgrades the pack
+    pub id: String,
+    pub name: String,
+    /// "major.minor.patch"
+    pub version: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub publisher: String,
+    /// Terms passed to recognition as hints and kept verbatim by enhancement
+    #[serde(default)]
+    pub vocabulary: Vec<String>,
+    /// Domain terms and what they mean, given to the model as context
+    #[serde(default)]
+    pub glossary: BTreeMap<String, String>,
+    #[serde(default)]
+    pub prompts: Vec<PromptTemplate>,
+    #[serde(default)]
+    pub presets: Vec<ProfilePreset>,
+}
+
+/// Extra instructions for text enhancement, e.g. how to write a clinical note
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptTemplate {
+    pub name: String,
+    pub instructions: String,
+}
+
+/// A ready-made setup within a pack, e.g. "radiology" in the medical pack
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProfilePreset {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Boosted on top of the pack vocabulary
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    /// Name of one of the pack's prompt templates
+    #[serde(default)]
+    pub prompt: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InstalledPack {
+    pub id: String,
+    pub name: String,
+    pub version: String,
+    pub publisher: String,
+    /// Key that verified the pack, `None` for an unsigned pack
+    pub signed_by: Option<String>,
+    /// Keyword boost profiles the pack added, removed again on uninstall
+    pub boost_profiles: Vec<String>,
+    pub installed_at: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DomainPackSettings {
+    pub installed: Vec<InstalledPack>,
+    pub active_pack: Option<String>,
+    pub active_preset: Option<String>,
+    /// Publishers whose packs are accepted, key id to base64 Ed25519 public key
+    pub trusted_keys: BTreeMap<String, String>,
+    /// Install packs without a valid signature; off by default
+    pub allow_unsigned: bool,
+}
+
+impl DomainPackSettings {
+    pub fn find(&self, id: &str) -> Option<&InstalledPack> {
+        self.installed.iter().find(|pack| pack.id == id)
+    }
+}
+
+/// Check a pack file, verify its signature and store it, replacing an older version
+pub fn install_pack(
+    settings: &mut DomainPackSettings,
+    boost: &mut KeywordBoostSettings,
+    contents: &str,
+    allow_downgrade: bool,
+) -> Result<InstalledPack, AppError> {
+    if contents.len() > MAX_PACK_BYTES {
+        return Err(invalid(format!("Domain packs are limited to {} bytes", MAX_PACK_BYTES)));
+    }
+    let file: DomainPackFile =
+        serde_json::from_str(contents).map_err(|e| invalid(format!("Not a domain pack: {}", e)))?;
+    let signed_by = verify_signature(&file, settings)?;
+    let pack: DomainPack =
+        serde_json::from_value(file.pack.clone()).map_err(|e| invalid(format!("Invalid domain pack: {}", e)))?;
+    validate_pack(&pack)?;
+
+    if let Some(existing) = settings.find(&pack.id) {
+        let (new, old) = (parse_version(&pack.version)?, parse_version(&existing.version)?);
+        if new < old && !allow_downgrade {
+            return Err(invalid(format!(
+                "{} {} is already installed; installing {} would downgrade it",
+                existing.name, existing.version, pack.version
+            )));
+        }
+    }
+
+    let boost_profiles = boost_profiles_for(&pack)?;
+    let dir = ensure_data_dir(DataDir::DomainPacks)?;
+    std::fs::write(pack_path(dir, &pack.id)?, contents)
+        .map_err(|e| AppError::Internal(format!("Failed to save domain pack {}: {}", pack.id, e)))?;
+
+    if let Some(previous) = settings.find(&pack.id).cloned() {
+        for profile in &previous.boost_profiles {
+            boost.profiles.remove(profile);
+        }
+        settings.installed.retain(|installed| installed.id != pack.id);
+    }
+    let installed = InstalledPack {
+        id: pack.id.clone(),
+        name: pack.name.clone(),
+        version: pack.version.clone(),
+        publisher: pack.publisher.clone(),
+        signed_by,
+        boost_profiles: boost_profiles.iter().map(|(name, _)| name.clone()).collect(),
+        installed_at: now_secs(),
+    };
+    boost.profiles.extend(boost_profiles);
+    // An upgrade may drop the preset that was being boosted
+    if boost.active_profile.as_ref().map_or(false, |profile| !boost.profiles.contains_key(profile)) {
+        boost.active_profile = None;
+    }
+    settings.installed.push(installed.clone());
+    Ok(installed)
+}
+
+/// Remove a pack, its stored file and the keyword profiles it added
+pub fn uninstall_pack(
+    settings: &mut DomainPackSettings,
+    boost: &mut KeywordBoostSettings,
+    id: &str,
+) -> Result<InstalledPack, AppError> {
+    let installed = settings
+        .find(id)
+        .cloned()
+        .ok_or_else(|| AppError::Configuration(format!("Domain pack '{}' is not installed", id)))?;
+    for profile in &installed.boost_profiles {
+        boost.profiles.remove(profile);
+        if boost.active_profile.as_deref() == Some(profile.as_str()) {
+            boost.active_profile = None;
+        }
+    }
+    settings.installed.retain(|pack| pack.id != id);
+    if settings.active_pack.as_deref() == Some(id) {
+        settings.active_pack = None;
+        settings.active_preset = None;
+    }
+    let path = pack_path(data_path(DataDir::DomainPacks)?, id)?;
+    if path.exists() {
+        std::fs::remove_file(&path)
+            .map_err(|e| AppError::Internal(format!("Failed to remove {}: {}", path.display(), e)))?;
+    }
+    Ok(installed)
+}
+
+/// Read an installed pack back from storage
+pub fn load_pack(id: &str) -> Result<DomainPack, AppError> {
+    let path = pack_path(data_path(DataDir::DomainPacks)?, id)?;
+    let contents = std::fs::read_to_string(&path)
+        .map_err(|e| AppError::Configuration(format!("Domain pack '{}' could not be read: {}", id, e)))?;
+    let file: DomainPackFile =
+        serde_json::from_str(&contents).map_err(|e| AppError::Internal(format!("Domain pack '{}' is damaged: {}", id, e)))?;
+    serde_json::from_value(file.pack).map_err(|e| AppError::Internal(format!("Domain pack '{}' is damaged: {}", id, e)))
+}
+
+/// Make `pack_id` the active pack, or none; returns the keyword profile to boost
+pub fn activate(
+    settings: &mut DomainPackSettings,
+    pack_id: Option<String>,
+    preset: Option<String>,
+) -> Result<Option<String>, AppError> {
+    let Some(pack_id) = pack_id else {
+        settings.active_pack = None;
+        settings.active_preset = None;
+        return Ok(None);
+    };
+    let installed = settings
+        .find(&pack_id)
+        .ok_or_else(|| AppError::Configuration(format!("Domain pack '{}' is not installed", pack_id)))?;
+    let profile = profile_name(&pack_id, preset.as_deref());
+    if !installed.boost_profiles.contains(&profile) {
+        return Err(AppError::Configuration(format!(
+            "Domain pack '{}' has no preset '{}'",
+            pack_id,
+            preset.unwrap_or_default()
+        )));
+    }
+    settings.active_pack = Some(pack_id);
+    settings.active_preset = preset;
+    Ok(Some(profile))
+}
+
+/// Enhancement instructions from the active pack: its glossary and the preset's prompt template
+pub fn active_instructions(settings: &DomainPackSettings) -> Option<String> {
+    let pack_id = settings.active_pack.as_ref()?;
+    let pack = match load_pack(pack_id) {
+        Ok(pack) => pack,
+        Err(e) => {
+            tracing::warn!("Active domain pack not applied: {}", e);
+            return None;
+        }
+    };
+    let template = settings
+        .active_preset
+        .as_ref()
+        .and_then(|preset| pack.presets.iter().find(|p| &p.name == preset))
+        .and_then(|preset| preset.prompt.as_ref())
+        .or_else(|| pack.prompts.first().map(|template| &template.name))
+        .and_then(|name| pack.prompts.iter().find(|template| &template.name == name));
+
+    let mut lines = Vec::new();
+    if let Some(template) = template {
+        lines.push(template.instructions.trim().to_string());
+    }
+    if !pack.glossary.is_empty() {
+        lines.push(format!("Domain glossary ({}):", pack.name));
+        lines.extend(
+            pack.glossary
+                .iter()
+                .take(MAX_GLOSSARY_IN_PROMPT)
+                .map(|(term, meaning)| format!("- {}: {}", term, meaning)),
+        );
+    }
+    (!lines.is_empty()).then(|| lines.join("\n"))
+}
+
+/// Keyword profile for a pack preset, or for the bare pack vocabulary
+pub fn profile_name(pack_id: &str, preset: Option<&str>) -> String {
+    match preset {
+        Some(preset) => format!("{}/{}", pack_id, preset),
+        None => pack_id.to_string(),
+    }
+}
+
+/// Which trusted key signed the pack; unsigned or unverifiable packs are refused unless allowed
+fn verify_signature(file: &DomainPackFile, settings: &DomainPackSettings) -> Result<Option<String>, AppError> {
+    let Some(signature) = &file.signature else {
+        return match settings.allow_unsigned {
+            true => Ok(None),
+            false => Err(invalid("The domain pack is not signed".to_string())),
+        };
+    };
+    let public_key = settings
+        .trusted_keys
+        .get(&signature.key_id)
+        .ok_or_else(|| invalid(format!("The domain pack is signed by an untrusted key '{}'", signature.key_id)))?;
+    let key = decode_public_key(&signature.key_id, public_key)?;
+    let signature_bytes = BASE64
+        .decode(signature.signature.trim())
+        .map_err(|_| invalid("The domain pack signature is not valid base64".to_string()))?;
+    let parsed = Signature::from_slice(&signature_bytes)
+        .map_err(|_| invalid("The domain pack signature is malformed".to_string()))?;
+    let signed_bytes =
+        serde_json::to_vec(&file.pack).map_err(|e| AppError::Internal(format!("Could not encode the pack: {}", e)))?;
+    key.verify(&signed_bytes, &parsed)
+        .map_err(|_| invalid("The domain pack signature does not match its contents".to_string()))?;
+    Ok(Some(signature.key_id.clone()))
+}
+
+/// Parse a base64 Ed25519 public key
+pub fn decode_public_key(key_id: &str, public_key: &str) -> Result<VerifyingKey, AppError> {
+    let not_a_key = || invalid(format!("Key '{}' is not a valid Ed25519 public key", key_id));
+    let bytes: [u8; 32] = BASE64
+        .decode(public_key.trim())
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(not_a_key)?;
+    VerifyingKey::from_bytes(&bytes).map_err(|_| not_a_key())
+}
+
+/// Pack ids name files in the packs folder, so path separators and ".." can never get through
+fn validate_pack_id(id: &str) -> Result<(), AppError> {
+    let id_ok = !id.is_empty()
+        && id.len() <= 64
+        && id.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    if !id_ok {
+        return Err(invalid(format!("Pack id '{}' must be lowercase letters, digits and hyphens", id)));
+    }
+    Ok(())
+}
+
+fn validate_pack(pack: &DomainPack) -> Result<(), AppError> {
+    validate_pack_id(&pack.id)?;
+    if pack.name.trim().is_empty() {
+        return Err(invalid(format!("Pack '{}' needs a name", pack.id)));
+    }
+    parse_version(&pack.version)?;
+    for preset in &pack.presets {
+        if preset.name.trim().is_empty() || preset.name.contains('/') {
+            return Err(invalid(format!("Pack '{}' has a preset without a valid name", pack.id)));
+        }
+        if let Some(prompt) = &preset.prompt {
+            if !pack.prompts.iter().any(|template| &template.name == prompt) {
+                return Err(invalid(format!(
+                    "Preset '{}' uses the unknown prompt template '{}'",
+                    preset.name, prompt
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Keyword profiles for the pack: one per preset, or one with the vocabulary when it has none
+fn boost_profiles_for(pack: &DomainPack) -> Result<Vec<(String, Vec<String>)>, AppError> {
+    let mut profiles = vec![(profile_name(&pack.id, None), clean_keywords(pack.vocabulary.clone()).map_err(invalid)?)];
+    for preset in &pack.presets {
+        let keywords = pack.vocabulary.iter().chain(&preset.keywords).cloned().collect();
+        profiles.push((
+            profile_name(&pack.id, Some(&preset.name)),
+            clean_keywords(keywords).map_err(invalid)?,
+        ));
+    }
+    Ok(profiles)
+}
+
+fn parse_version(version: &str) -> Result<(u64, u64, u64), AppError> {
+    let parts: Vec<u64> = version
+        .trim()
+        .split('.')
+        .map(|part| part.parse::<u64>())
+        .collect::<Result<_, _>>()
+        .map_err(|_| invalid(format!("'{}' is not a version like 1.2.0", version)))?;
+    match parts.as_slice() {
+        [major, minor, patch] => Ok((*major, *minor, *patch)),
+        _ => Err(invalid(format!("'{}' is not a version like 1.2.0", version))),
+    }
+}
+
+fn pack_path(dir: PathBuf, id: &str) -> Result<PathBuf, AppError> {
+    validate_pack_id(id)?;
+    Ok(dir.join(format!("{}.json", id)))
+}
+
+fn invalid(message: String) -> AppError {
+    AppError::Validation(ValidationError::InvalidConfigValue(message))
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pack_paths_stay_in_the_packs_folder() {
+        let dir = PathBuf::from("packs");
+        assert_eq!(pack_path(dir.clone(), "legal-us").unwrap(), dir.join("legal-us.json"));
+        for id in ["../settings", "..", "a/b", "a\\b", "", "Legal"] {
+            assert!(pack_path(dir.clone(), id).is_err(), "{}", id);
+        }
+    }
+}
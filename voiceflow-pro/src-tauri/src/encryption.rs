@@ -0,0 +1,398 @@
+//! Encryption at rest for VoiceFlow Pro
+//! AES-256-GCM file encryption for history, drafts and recordings with the key held in the OS keychain
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
+
+use crate::errors::AppError;
+use crate::storage::{app_data_dir, data_paths_all_profiles, DataDir};
+
+/// Header identifying an encrypted file; followed by the nonce and ciphertext
+const MAGIC: &[u8] = b"VFENC1";
+const NONCE_LEN: usize = 12;
+const KEYCHAIN_SERVICE: &str = "com.voiceflow.pro";
+const KEYCHAIN_ACCOUNT: &str = "data-encryption-key";
+const CONFIG_FILE: &str = "encryption.json";
+/// Sealed into the config when encryption is turned on, so a passphrase can be checked before any data is
+const VERIFIER_PLAINTEXT: &[u8] = b"voiceflow-data-key";
+
+/// Directories holding sensitive dictation content
+const ENCRYPTED_DIRS: [DataDir; 3] = [DataDir::History, DataDir::Drafts, DataDir::Recordings];
+
+/// Where the data key comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeySource {
+    /// Random key generated and kept only in the keychain
+    Keychain,
+    /// Key derived from a user passphrase; cached in the keychain, recoverable with the passphrase
+    Passphrase,
+}
+
+/// Persisted encryption settings (never contains key material)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct EncryptionConfig {
+    enabled: bool,
+    key_source: Option<KeySource>,
+    /// Argon2 salt for passphrase-derived keys
+    salt: Option<String>,
+    enabled_at: Option<u64>,
+    /// `VERIFIER_PLAINTEXT` sealed with the data key, base64
+    #[serde(default)]
+    verifier: Option<String>,
+}
+
+/// Status reported to the settings UI
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncryptionStatus {
+    pub enabled: bool,
+    /// False when encryption is on but the key could not be loaded (e.g. keychain entry removed)
+    pub unlocked: bool,
+    pub key_source: Option<KeySource>,
+    pub enabled_at: Option<u64>,
+}
+
+/// Outcome of migrating existing files to encrypted storage
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MigrationReport {
+    pub files_encrypted: usize,
+    pub files_already_encrypted: usize,
+    pub bytes_processed: u64,
+    pub errors: Vec<String>,
+}
+
+/// Holds the data key while the app is running
+pub struct DataVault {
+    key: RwLock<Option<Key<Aes256Gcm>>>,
+}
+
+impl DataVault {
+    fn new() -> Self {
+        Self { key: RwLock::new(None) }
+    }
+
+    /// Load the key from the keychain if encryption was enabled in a previous run
+    pub fn initialize(&self) -> Result<(), AppError> {
+        let config = load_config()?;
+        if config.enabled {
+            match keychain_get()? {
+                Some(key) => self.set_key(key),
+                None => tracing::warn!("Encryption is enabled but the key is missing from the keychain; unlock required"),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn status(&self) -> EncryptionStatus {
+        let config = load_config().unwrap_or_default();
+        EncryptionStatus {
+            enabled: config.enabled,
+            unlocked: self.key.read().map(|k| k.is_some()).unwrap_or(false),
+            key_source: config.key_source,
+            enabled_at: config.enabled_at,
+        }
+    }
+
+    /// Turn on encryption and migrate existing plaintext files in place
+    pub fn enable(&self, passphrase: Option<&str>) -> Result<MigrationReport, AppError> {
+        let mut config = load_config()?;
+        if config.enabled {
+            return Err(AppError::Configuration("Encryption is already enabled".to_string()));
+        }
+
+        let key = match passphrase {
+            Some(passphrase) => {
+                let mut salt = [0u8; 16];
+                OsRng.fill_bytes(&mut salt);
+                config.salt = Some(BASE64.encode(salt));
+                config.key_source = Some(KeySource::Passphrase);
+                derive_key(passphrase, &salt)?
+            }
+            None => {
+                config.key_source = Some(KeySource::Keychain);
+                Aes256Gcm::generate_key(OsRng)
+            }
+        };
+
+        keychain_set(&key)?;
+        config.verifier = Some(BASE64.encode(encrypt_with(&key, VERIFIER_PLAINTEXT)?));
+        self.set_key(key);
+
+        // Record the switch before migrating so a crash mid-way still reads the encrypted files
+        config.enabled = true;
+        config.enabled_at = Some(now_secs());
+        save_config(&config)?;
+
+        Ok(self.migrate())
+    }
+
+    /// Re-derive a passphrase key when the keychain entry is gone
+    pub fn unlock(&self, passphrase: &str) -> Result<(), AppError> {
+        let mut config = load_config()?;
+        let salt = match (config.enabled, config.key_source, config.salt.clone()) {
+            (true, Some(KeySource::Passphrase), Some(salt)) => BASE64
+                .decode(salt)
+                .map_err(|e| AppError::Configuration(format!("Corrupt encryption salt: {}", e)))?,
+            (true, _, _) => {
+                return Err(AppError::Configuration(
+                    "Data is protected by a keychain-only key; restore the keychain entry to unlock".to_string(),
+                ))
+            }
+            (false, _, _) => return Err(AppError::Configuration("Encryption is not enabled".to_string())),
+        };
+
+        let key = derive_key(passphrase, &salt)?;
+        let incorrect = || AppError::Security("Incorrect passphrase".to_string());
+        match &config.verifier {
+            Some(verifier) => {
+                let sealed = BASE64.decode(verifier).map_err(|_| incorrect())?;
+                if !is_encrypted(&sealed) || decrypt_with(&key, &sealed).map_err(|_| incorrect())? != VERIFIER_PLAINTEXT {
+                    return Err(incorrect());
+                }
+            }
+            // Enabled before configs carried a verifier: check an encrypted file, then record one
+            None => {
+                if let Some(sample) = first_encrypted_file() {
+                    let data = fs::read(&sample).map_err(|e| AppError::Internal(e.to_string()))?;
+                    decrypt_with(&key, &data).map_err(|_| incorrect())?;
+                }
+                config.verifier = Some(BASE64.encode(encrypt_with(&key, VERIFIER_PLAINTEXT)?));
+                save_config(&config)?;
+            }
+        }
+
+        keychain_set(&key)?;
+        self.set_key(key);
+        Ok(())
+    }
+
+    /// Read a file, decrypting it if it was stored encrypted
+    pub fn read_file(&self, path: &Path) -> Result<Vec<u8>, AppError> {
+        let data = fs::read(path)
+            .map_err(|e| AppError::Internal(format!("Failed to read {}: {}", path.display(), e)))?;
+        self.open(data)
+    }
+
+    /// Write a file, encrypting it when encryption is enabled
+    pub fn write_file(&self, path: &Path, contents: &[u8]) -> Result<(), AppError> {
+        write_atomically(path, &self.seal(contents)?)
+    }
+
+    /// Encrypt a value kept outside a file of its own, e.g. a database column, when encryption is enabled.
+    /// With encryption on but no key loaded nothing is written, rather than writing it in plaintext
+    pub fn seal(&self, contents: &[u8]) -> Result<Vec<u8>, AppError> {
+        match self.key.read().ok().and_then(|k| k.clone()) {
+            Some(key) => encrypt_with(&key, contents),
+            None if load_config()?.enabled => Err(AppError::Security(
+                "Encrypted storage is locked; unlock encryption first".to_string(),
+            )),
+            None => Ok(contents.to_vec()),
+        }
+    }
+
+    /// Decrypt a value from `seal`; values stored before encryption was enabled pass through
+    pub fn open(&self, data: Vec<u8>) -> Result<Vec<u8>, AppError> {
+        if !is_encrypted(&data) {
+            return Ok(data);
+        }
+        let guard = self.key.read().map_err(|_| AppError::Internal("Encryption key lock poisoned".to_string()))?;
+        let key = guard
+            .as_ref()
+            .ok_or_else(|| AppError::Security("Encrypted data is locked; unlock encryption first".to_string()))?;
+        decrypt_with(key, &data)
+    }
+
+    fn set_key(&self, key: Key<Aes256Gcm>) {
+        if let Ok(mut slot) = self.key.write() {
+            *slot = Some(key);
+        }
+    }
+
+    fn migrate(&self) -> MigrationReport {
+        let mut report = MigrationReport {
+            files_encrypted: 0,
+            files_already_encrypted: 0,
+            bytes_processed: 0,
+            errors: Vec::new(),
+        };
+        let Some(key) = self.key.read().ok().and_then(|k| k.clone()) else {
+            report.errors.push("No encryption key loaded".to_string());
+            return report;
+        };
+
+        for path in sensitive_files() {
+            let result = fs::read(&path).map_err(|e| e.to_string()).and_then(|data| {
+                if is_encrypted(&data) {
+                    report.files_already_encrypted += 1;
+                    return Ok(());
+                }
+                let encrypted = encrypt_with(&key, &data).map_err(|e| e.to_string())?;
+                // Wipe the plaintext copy rather than leaving its blocks behind after the rename
+                let staged = staging_path(&path);
+                write_file_synced(&staged, &encrypted).map_err(|e| e.to_string())?;
+                crate::data_management::secure_delete(&path).map_err(|e| e.to_string())?;
+                fs::rename(&staged, &path).map_err(|e| e.to_string())?;
+                report.files_encrypted += 1;
+                report.bytes_processed += data.len() as u64;
+                Ok(())
+            });
+            if let Err(e) = result {
+                report.errors.push(format!("{}: {}", path.display(), e));
+            }
+        }
+        report
+    }
+}
+
+static DATA_VAULT: std::sync::OnceLock<DataVault> = std::sync::OnceLock::new();
+
+/// Get the global data vault
+pub fn get_data_vault() -> &'static DataVault {
+    DATA_VAULT.get_or_init(DataVault::new)
+}
+
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.len() >= MAGIC.len() + NONCE_LEN && data.starts_with(MAGIC)
+}
+
+fn encrypt_with(key: &Key<Aes256Gcm>, plaintext: &[u8]) -> Result<Vec<u8>, AppError> {
+    let cipher = Aes256Gcm::new(key);
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| AppError::Security("Encryption failed".to_string()))?;
+
+    let mut out = Vec::with_capacity(MAGIC.len() + NONCE_LEN + ciphertext.len());
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(&ciphertext);
+    Ok(out)
+}
+
+fn decrypt_with(key: &Key<Aes256Gcm>, data: &[u8]) -> Result<Vec<u8>, AppError> {
+    let body = &data[MAGIC.len()..];
+    let (nonce, ciphertext) = body.split_at(NONCE_LEN);
+    Aes256Gcm::new(key)
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| AppError::Security("Decryption failed: wrong key or corrupted file".to_string()))
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<Key<Aes256Gcm>, AppError> {
+    if passphrase.chars().count() < 8 {
+        return Err(AppError::Validation("Passphrase must be at least 8 characters".to_string().into()));
+    }
+    let mut key = [0u8; 32];
+    argon2::Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| AppError::Security(format!("Key derivation failed: {}", e)))?;
+    Ok(key.into())
+}
+
+fn keychain_entry() -> Result<keyring::Entry, AppError> {
+    keyring::Entry::new(KEYCHAIN_SERVICE, KEYCHAIN_ACCOUNT)
+        .map_err(|e| AppError::Security(format!("Keychain unavailable: {}", e)))
+}
+
+fn keychain_get() -> Result<Option<Key<Aes256Gcm>>, AppError> {
+    match keychain_entry()?.get_password() {
+        Ok(encoded) => {
+            let bytes = BASE64
+                .decode(encoded)
+                .map_err(|e| AppError::Security(format!("Corrupt keychain entry: {}", e)))?;
+            if bytes.len() != 32 {
+                return Err(AppError::Security("Corrupt keychain entry: wrong key length".to_string()));
+            }
+            Ok(Some(*Key::<Aes256Gcm>::from_slice(&bytes)))
+        }
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(AppError::Security(format!("Keychain read failed: {}", e))),
+    }
+}
+
+fn keychain_set(key: &Key<Aes256Gcm>) -> Result<(), AppError> {
+    keychain_entry()?
+        .set_password(&BASE64.encode(key))
+        .map_err(|e| AppError::Security(format!("Keychain write failed: {}", e)))
+}
+
+fn config_path() -> Result<PathBuf, AppError> {
+    Ok(app_data_dir()?.join(CONFIG_FILE))
+}
+
+fn load_config() -> Result<EncryptionConfig, AppError> {
+    let path = config_path()?;
+    if !path.exists() {
+        return Ok(EncryptionConfig::default());
+    }
+    let data = fs::read(&path).map_err(|e| AppError::Internal(format!("Failed to read {}: {}", path.display(), e)))?;
+    serde_json::from_slice(&data).map_err(|e| AppError::Configuration(format!("Invalid {}: {}", CONFIG_FILE, e)))
+}
+
+fn save_config(config: &EncryptionConfig) -> Result<(), AppError> {
+    let data = serde_json::to_vec_pretty(config).map_err(|e| AppError::Internal(e.to_string()))?;
+    write_atomically(&config_path()?, &data)
+}
+
+fn sensitive_files() -> Vec<PathBuf> {
+    // Every profile's data is covered, not only the active one
+    ENCRYPTED_DIRS
+        .iter()
+        .filter_map(|dir| data_paths_all_profiles(*dir).ok())
+        .flatten()
+        .filter(|root| root.exists())
+        .flat_map(|root| {
+            WalkDir::new(root)
+                .into_iter()
+                .filter_map(|e| e.ok())
+                .filter(|e| e.file_type().is_file())
+                .map(|e| e.into_path())
+                .filter(|p| !p.to_string_lossy().ends_with(".vfenc-tmp"))
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+fn first_encrypted_file() -> Option<PathBuf> {
+    sensitive_files().into_iter().find(|path| {
+        fs::read(path).map(|data| is_encrypted(&data)).unwrap_or(false)
+    })
+}
+
+fn staging_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".vfenc-tmp");
+    path.with_file_name(name)
+}
+
+fn write_file_synced(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    let mut file = fs::File::create(path)?;
+    file.write_all(data)?;
+    file.sync_all()
+}
+
+fn write_atomically(path: &Path, data: &[u8]) -> Result<(), AppError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| AppError::Internal(e.to_string()))?;
+    }
+    let staged = staging_path(path);
+    write_file_synced(&staged, data)
+        .and_then(|_| fs::rename(&staged, path))
+        .map_err(|e| AppError::Internal(format!("Failed to write {}: {}", path.display(), e)))
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
@@ -1,9 +1,12 @@
 //! Error handling module for VoiceFlow Pro
 //! Defines comprehensive error types for all application components
 
+use serde::ser::{Serialize, SerializeStruct, Serializer};
 use thiserror::Error;
 use std::fmt;
 
+use crate::integrations::AIMLError;
+
 /// Application-level error type
 #[derive(Error, Debug)]
 pub enum AppError {
@@ -33,6 +36,120 @@ pub enum AppError {
     
     #[error("Internal error: {0}")]
     Internal(String),
+
+    #[error("Authentication failed: {0}")]
+    Authentication(String),
+
+    #[error("Rate limit exceeded")]
+    RateLimited { retry_after_secs: Option<u64> },
+
+    /// Our own limit on how often the frontend may call a command, as opposed to the AI service's
+    #[error("{command} was called too often; try again in {retry_after_ms} ms")]
+    CommandThrottled { command: String, retry_after_ms: u64 },
+
+    #[error("Request timed out: {0}")]
+    Timeout(String),
+
+    #[error("Service unavailable: {0}")]
+    ServiceUnavailable(String),
+
+    #[error("AI service error {status}: {message}")]
+    Upstream { status: u16, message: String },
+
+    #[error("Unknown model: {0}")]
+    InvalidModel(String),
+
+    #[error("{0} is not initialized")]
+    NotInitialized(String),
+
+    /// The operation is estimated above the user's cost threshold and needs their go-ahead
+    #[error("Estimated cost ${estimated_usd:.2} is above the ${threshold_usd:.2} confirmation threshold")]
+    ConfirmationRequired { estimated_usd: f64, threshold_usd: f64 },
+}
+
+/// Machine-readable error kind sent to the frontend
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    VoiceRecognition,
+    TextProcessing,
+    Configuration,
+    Validation,
+    Resource,
+    Security,
+    Network,
+    Permission,
+    Internal,
+    AuthFailed,
+    RateLimited,
+    Timeout,
+    ServiceUnavailable,
+    UpstreamError,
+    InvalidModel,
+    NotInitialized,
+    ConfirmationRequired,
+}
+
+impl AppError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            AppError::VoiceRecognition(VoiceError::NotInitialized)
+            | AppError::TextProcessing(TextProcessingError::NotInitialized)
+            | AppError::NotInitialized(_) => ErrorCode::NotInitialized,
+            AppError::VoiceRecognition(VoiceError::Timeout)
+            | AppError::TextProcessing(TextProcessingError::ProcessingTimeout(_))
+            | AppError::Timeout(_) => ErrorCode::Timeout,
+            AppError::VoiceRecognition(_) => ErrorCode::VoiceRecognition,
+            AppError::TextProcessing(_) => ErrorCode::TextProcessing,
+            AppError::Configuration(_) => ErrorCode::Configuration,
+            AppError::Validation(_) => ErrorCode::Validation,
+            AppError::Resource(_) => ErrorCode::Resource,
+            AppError::Security(_) => ErrorCode::Security,
+            AppError::Network(_) => ErrorCode::Network,
+            AppError::Permission(_) => ErrorCode::Permission,
+            AppError::Internal(_) => ErrorCode::Internal,
+            AppError::Authentication(_) => ErrorCode::AuthFailed,
+            AppError::RateLimited { .. } | AppError::CommandThrottled { .. } => ErrorCode::RateLimited,
+            AppError::ServiceUnavailable(_) => ErrorCode::ServiceUnavailable,
+            AppError::Upstream { .. } => ErrorCode::UpstreamError,
+            AppError::InvalidModel(_) => ErrorCode::InvalidModel,
+            AppError::ConfirmationRequired { .. } => ErrorCode::ConfirmationRequired,
+        }
+    }
+
+    /// Whether the same request may succeed if sent again later
+    pub fn retryable(&self) -> bool {
+        match self {
+            AppError::Upstream { status, .. } => *status >= 500,
+            AppError::Resource(ResourceError::ResourceLocked(_)) => true,
+            AppError::VoiceRecognition(VoiceError::DeviceBusy(_)) => true,
+            _ => matches!(
+                self.code(),
+                ErrorCode::Network | ErrorCode::RateLimited | ErrorCode::Timeout | ErrorCode::ServiceUnavailable
+            ),
+        }
+    }
+
+    /// How long the service asked us to wait before retrying, when it said
+    pub fn retry_after_ms(&self) -> Option<u64> {
+        match self {
+            AppError::RateLimited { retry_after_secs } => retry_after_secs.map(|secs| secs.saturating_mul(1000)),
+            AppError::CommandThrottled { retry_after_ms, .. } => Some(*retry_after_ms),
+            _ => None,
+        }
+    }
+}
+
+/// Command errors reach the frontend as `{ code, message, retryable, retry_after_ms }`
+impl Serialize for AppError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut error = serializer.serialize_struct("AppError", 4)?;
+        error.serialize_field("code", &self.code())?;
+        error.serialize_field("message", &self.to_string())?;
+        error.serialize_field("retryable", &self.retryable())?;
+        error.serialize_field("retry_after_ms", &self.retry_after_ms())?;
+        error.end()
+    }
 }
 
 /// Voice recognition specific errors
@@ -43,12 +160,18 @@ pub enum VoiceError {
     
     #[error("Engine already initialized")]
     AlreadyInitialized,
+
+    #[error("Configuration change requires restarting the engine: {0}")]
+    RestartRequired(String),
     
     #[error("Invalid language code: {0}")]
     InvalidLanguage(String),
     
     #[error("Audio capture failed: {0}")]
     AudioCaptureFailed(String),
+
+    #[error("Audio device busy: {0}")]
+    DeviceBusy(String),
     
     #[error("Speech recognition timeout")]
     Timeout,
@@ -64,6 +187,9 @@ pub enum VoiceError {
     
     #[error("Memory allocation failed for audio buffer")]
     AudioMemoryError,
+
+    #[error("Voice calibration already in progress")]
+    CalibrationInProgress,
 }
 
 /// Text processing specific errors
@@ -92,6 +218,9 @@ pub enum TextProcessingError {
     
     #[error("Invalid processing options: {0}")]
     InvalidOptions(String),
+
+    #[error("Could not find '{0}' in the recent transcript")]
+    CorrectionTargetNotFound(String),
 }
 
 /// Input validation errors
@@ -160,6 +289,38 @@ impl From<serde_json::Error> for AppError {
     }
 }
 
+impl From<rusqlite::Error> for AppError {
+    fn from(error: rusqlite::Error) -> Self {
+        AppError::Internal(format!("Database error: {}", error))
+    }
+}
+
+impl From<AIMLError> for AppError {
+    fn from(error: AIMLError) -> Self {
+        match error {
+            AIMLError::HttpClientError(e) if e.is_timeout() => AppError::Timeout(e.to_string()),
+            AIMLError::HttpClientError(e) => AppError::Network(e.to_string()),
+            AIMLError::ApiError { status, message } => match status {
+                401 | 403 => AppError::Authentication(message),
+                408 | 504 => AppError::Timeout(message),
+                429 => AppError::RateLimited { retry_after_secs: None },
+                502 | 503 => AppError::ServiceUnavailable(message),
+                _ => AppError::Upstream { status, message },
+            },
+            AIMLError::AuthError(message) => AppError::Authentication(message),
+            AIMLError::RateLimitExceeded { retry_after_secs } => AppError::RateLimited { retry_after_secs },
+            AIMLError::InvalidModel(model) => AppError::InvalidModel(model),
+            AIMLError::MissingParameter(name) => {
+                AppError::Validation(ValidationError::InvalidConfigValue(format!("missing {}", name)))
+            }
+            AIMLError::JsonError(e) => AppError::Internal(format!("Unreadable AI service response: {}", e)),
+            AIMLError::Timeout(message) => AppError::Timeout(message),
+            AIMLError::NetworkError(message) => AppError::Network(message),
+            AIMLError::ServiceUnavailable(message) => AppError::ServiceUnavailable(message),
+        }
+    }
+}
+
 impl From<tokio::sync::mpsc::error::SendError<VoiceEvent>> for AppError {
     fn from(error: tokio::sync::mpsc::error::SendError<VoiceEvent>) -> Self {
         AppError::Internal(format!("Event send error: {}", error))
@@ -0,0 +1,248 @@
+//! Bounded event channels for VoiceFlow Pro
+//! Caps queued events when the UI falls behind by merging interim updates while always delivering final ones
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::error::{TryRecvError, TrySendError};
+use tokio::sync::{mpsc, Notify};
+
+/// Held events past this many times the channel capacity are dropped, oldest first, so a consumer that
+/// never catches up cannot grow memory without limit
+const MAX_OVERFLOW_FACTOR: usize = 8;
+
+/// Events that may be merged when the consumer lags
+pub trait Coalesce {
+    /// Queued events with the same key are replaced by the newest one; `None` means the event is always delivered
+    fn coalesce_key(&self) -> Option<&'static str>;
+}
+
+/// Counters for one channel, reported through the resource status
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChannelMetrics {
+    pub name: String,
+    pub capacity: usize,
+    pub queued: u64,
+    pub sent: u64,
+    /// Interim events replaced by a newer one before the consumer read them, and held events dropped past the overflow limit
+    pub dropped: u64,
+    /// Events that must be delivered, held past capacity until the consumer catches up
+    pub overflowed: u64,
+}
+
+#[derive(Debug)]
+struct ChannelStats {
+    name: &'static str,
+    capacity: usize,
+    queued: AtomicU64,
+    sent: AtomicU64,
+    dropped: AtomicU64,
+    overflowed: AtomicU64,
+    closed: AtomicBool,
+}
+
+impl ChannelStats {
+    fn snapshot(&self) -> ChannelMetrics {
+        ChannelMetrics {
+            name: self.name.to_string(),
+            capacity: self.capacity,
+            queued: self.queued.load(Ordering::Relaxed),
+            sent: self.sent.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            overflowed: self.overflowed.load(Ordering::Relaxed),
+        }
+    }
+}
+
+static CHANNEL_STATS: OnceLock<Mutex<Vec<Arc<ChannelStats>>>> = OnceLock::new();
+
+fn registry() -> &'static Mutex<Vec<Arc<ChannelStats>>> {
+    CHANNEL_STATS.get_or_init(|| Mutex::new(Vec::new()))
+}
+
+/// Metrics for every open event channel
+pub fn channel_metrics() -> Vec<ChannelMetrics> {
+    let Ok(mut channels) = registry().lock() else {
+        return Vec::new();
+    };
+    channels.retain(|stats| !stats.closed.load(Ordering::Relaxed));
+    channels.iter().map(|stats| stats.snapshot()).collect()
+}
+
+struct Shared<T> {
+    /// Events that arrived while the channel was full, in arrival order, up to `MAX_OVERFLOW_FACTOR` times capacity
+    overflow: Mutex<VecDeque<T>>,
+    notify: Notify,
+    stats: Arc<ChannelStats>,
+}
+
+pub struct EventSender<T> {
+    tx: mpsc::Sender<T>,
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Clone for EventSender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            tx: self.tx.clone(),
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for EventSender<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventSender").field("stats", &self.shared.stats).finish()
+    }
+}
+
+pub struct EventReceiver<T> {
+    rx: mpsc::Receiver<T>,
+    shared: Arc<Shared<T>>,
+}
+
+/// Create a channel holding at most `capacity` events before interim events start being merged
+pub fn event_channel<T: Coalesce>(name: &'static str, capacity: usize) -> (EventSender<T>, EventReceiver<T>) {
+    let (tx, rx) = mpsc::channel(capacity.max(1));
+    let stats = Arc::new(ChannelStats {
+        name,
+        capacity: capacity.max(1),
+        queued: AtomicU64::new(0),
+        sent: AtomicU64::new(0),
+        dropped: AtomicU64::new(0),
+        overflowed: AtomicU64::new(0),
+        closed: AtomicBool::new(false),
+    });
+    if let Ok(mut channels) = registry().lock() {
+        channels.push(stats.clone());
+    }
+    let shared = Arc::new(Shared {
+        overflow: Mutex::new(VecDeque::new()),
+        notify: Notify::new(),
+        stats,
+    });
+    (
+        EventSender {
+            tx,
+            shared: shared.clone(),
+        },
+        EventReceiver { rx, shared },
+    )
+}
+
+impl<T: Coalesce> EventSender<T> {
+    /// Queue an event without waiting; fails only when the receiver is gone
+    pub fn send(&self, event: T) -> Result<(), String> {
+        let stats = &self.shared.stats;
+        let mut overflow = self
+            .shared
+            .overflow
+            .lock()
+            .map_err(|_| format!("{} event channel poisoned", stats.name))?;
+
+        // Held events move into the channel, oldest first, as soon as the consumer has made room
+        while let Some(held) = overflow.pop_front() {
+            match self.tx.try_send(held) {
+                Ok(()) => {}
+                Err(TrySendError::Full(held)) => {
+                    overflow.push_front(held);
+                    break;
+                }
+                Err(TrySendError::Closed(_)) => return Err(format!("{} event channel closed", stats.name)),
+            }
+        }
+
+        // Once events are held back, newer ones queue behind them to keep order
+        let event = if overflow.is_empty() {
+            match self.tx.try_send(event) {
+                Ok(()) => {
+                    stats.sent.fetch_add(1, Ordering::Relaxed);
+                    stats.queued.fetch_add(1, Ordering::Relaxed);
+                    return Ok(());
+                }
+                Err(TrySendError::Closed(_)) => return Err(format!("{} event channel closed", stats.name)),
+                Err(TrySendError::Full(event)) => event,
+            }
+        } else if self.tx.is_closed() {
+            return Err(format!("{} event channel closed", stats.name));
+        } else {
+            event
+        };
+
+        stats.sent.fetch_add(1, Ordering::Relaxed);
+        let key = event.coalesce_key();
+        match key.and_then(|key| overflow.iter_mut().find(|queued| queued.coalesce_key() == Some(key))) {
+            Some(queued) => {
+                *queued = event;
+                stats.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+            None => {
+                if key.is_none() {
+                    stats.overflowed.fetch_add(1, Ordering::Relaxed);
+                }
+                overflow.push_back(event);
+                stats.queued.fetch_add(1, Ordering::Relaxed);
+                if overflow.len() > stats.capacity * MAX_OVERFLOW_FACTOR && overflow.pop_front().is_some() {
+                    stats.queued.fetch_sub(1, Ordering::Relaxed);
+                    stats.dropped.fetch_add(1, Ordering::Relaxed);
+                    log::warn!("{} event channel is full; dropped its oldest held event", stats.name);
+                }
+            }
+        }
+        drop(overflow);
+        self.shared.notify.notify_one();
+        Ok(())
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.tx.is_closed()
+    }
+}
+
+impl<T> EventReceiver<T> {
+    /// Next event, or `None` once every sender is gone and nothing is queued
+    pub async fn recv(&mut self) -> Option<T> {
+        loop {
+            match self.try_recv() {
+                Ok(event) => return Some(event),
+                Err(TryRecvError::Disconnected) => return None,
+                Err(TryRecvError::Empty) => {}
+            }
+            tokio::select! {
+                event = self.rx.recv() => match event {
+                    Some(event) => {
+                        self.shared.stats.queued.fetch_sub(1, Ordering::Relaxed);
+                        return Some(event);
+                    }
+                    None => return self.pop_overflow(),
+                },
+                _ = self.shared.notify.notified() => {}
+            }
+        }
+    }
+
+    /// Channel events come first; they are older than anything held in overflow
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        match self.rx.try_recv() {
+            Ok(event) => {
+                self.shared.stats.queued.fetch_sub(1, Ordering::Relaxed);
+                Ok(event)
+            }
+            Err(error) => self.pop_overflow().ok_or(error),
+        }
+    }
+
+    fn pop_overflow(&mut self) -> Option<T> {
+        let event = self.shared.overflow.lock().ok()?.pop_front()?;
+        self.shared.stats.queued.fetch_sub(1, Ordering::Relaxed);
+        Some(event)
+    }
+}
+
+impl<T> Drop for EventReceiver<T> {
+    fn drop(&mut self) {
+        self.shared.stats.closed.store(true, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,377 @@
+//! Result feedback for VoiceFlow Pro
+//! Stores thumbs-up/down ratings of processing results and routes future requests to the prompt and model users rate best
+
+use std::collections::{BTreeMap, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+use crate::encryption::get_data_vault;
+use crate::errors::AppError;
+use crate::integrations::ai_text_processor::ToneType;
+use crate::storage::{active_profile_id, profile_data_path, DataDir};
+
+const FEEDBACK_FILE: &str = "feedback.json";
+/// Results that can still be rated
+const MAX_PENDING: usize = 100;
+/// Ratings kept with their text; route statistics keep counting after older ones are dropped
+const MAX_RECORDS: usize = 1000;
+const RECENT_IN_SUMMARY: usize = 20;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FeedbackSettings {
+    /// Collect ratings; routing keeps using what was learned while off
+    pub enabled: bool,
+    /// Pick among the candidates below by rating; otherwise the first candidate is always used
+    pub adaptive_routing: bool,
+    /// How strongly rarely rated routes are tried again, the UCB exploration constant
+    pub exploration: f64,
+    /// Tones dictation may be processed with
+    pub dictation_tones: Vec<ToneType>,
+    /// Models refinements may use; empty uses the configured text model
+    pub refinement_models: Vec<String>,
+}
+
+impl Default for FeedbackSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            adaptive_routing: true,
+            exploration: 0.5,
+            dictation_tones: vec![ToneType::Professional],
+            refinement_models: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeedbackTask {
+    Dictation,
+    Refinement,
+}
+
+/// The prompt template and model a result was produced with
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Route {
+    pub template: String,
+    pub model: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeedbackRecord {
+    pub result_id: String,
+    pub task: FeedbackTask,
+    pub positive: bool,
+    pub original: String,
+    pub result: String,
+    pub profile: String,
+    pub route: Route,
+    pub comment: Option<String>,
+    pub created_at: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct RouteStats {
+    up: u64,
+    down: u64,
+}
+
+impl RouteStats {
+    fn rated(&self) -> u64 {
+        self.up + self.down
+    }
+
+    /// Share of positive ratings with one imagined rating each way, so new routes start at 0.5
+    fn score(&self) -> f64 {
+        (self.up as f64 + 1.0) / (self.rated() as f64 + 2.0)
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct FeedbackStore {
+    records: VecDeque<FeedbackRecord>,
+    /// Keyed by task, then route
+    routes: BTreeMap<String, BTreeMap<String, (Route, RouteStats)>>,
+}
+
+#[derive(Debug, Clone)]
+struct PendingResult {
+    result_id: String,
+    task: FeedbackTask,
+    original: String,
+    result: String,
+    route: Route,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RouteSummary {
+    pub task: FeedbackTask,
+    pub route: Route,
+    pub up: u64,
+    pub down: u64,
+    /// Smoothed share of positive ratings, 0.0 to 1.0
+    pub score: f64,
+    /// Best rated route of its task
+    pub preferred: bool,
+}
+
+/// What has been learned, for the transparency view
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeedbackSummary {
+    pub total_up: u64,
+    pub total_down: u64,
+    pub routes: Vec<RouteSummary>,
+    /// Newest first
+    pub recent: Vec<FeedbackRecord>,
+}
+
+/// Ratings and route statistics for the active profile
+#[derive(Debug, Default)]
+pub struct FeedbackLoop {
+    profile_id: Option<String>,
+    store: FeedbackStore,
+    pending: VecDeque<PendingResult>,
+}
+
+impl FeedbackLoop {
+    /// Pick the route for the next request: the best upper confidence bound, first candidate on ties
+    pub fn choose(&mut self, task: FeedbackTask, candidates: &[Route], settings: &FeedbackSettings) -> Option<Route> {
+        self.sync_profile();
+        if !settings.adaptive_routing || candidates.len() < 2 {
+            return candidates.first().cloned();
+        }
+        let stats = self.store.routes.get(&task_key(task));
+        let lookup = |route: &Route| {
+            stats
+                .and_then(|routes| routes.get(&route_key(route)))
+                .map(|(_, stats)| stats.clone())
+                .unwrap_or_default()
+        };
+        let total: u64 = candidates.iter().map(|route| lookup(route).rated()).sum();
+        let bound = |route: &Route| {
+            let stats = lookup(route);
+            stats.score() + settings.exploration * ((total as f64 + 1.0).ln() / (stats.rated() as f64 + 1.0)).sqrt()
+        };
+        candidates
+            .iter()
+            .fold(None::<(&Route, f64)>, |best, route| {
+                let value = bound(route);
+                match best {
+                    Some((_, best_value)) if best_value >= value => best,
+                    _ => Some((route, value)),
+                }
+            })
+            .map(|(route, _)| route.clone())
+    }
+
+    /// Remember how a result was produced so a later rating can be attributed to its route
+    pub fn note_result(&mut self, result_id: &str, task: FeedbackTask, original: &str, result: &str, route: Route) {
+        self.sync_profile();
+        self.pending.push_back(PendingResult {
+            result_id: result_id.to_string(),
+            task,
+            original: original.to_string(),
+            result: result.to_string(),
+            route,
+        });
+        if self.pending.len() > MAX_PENDING {
+            self.pending.pop_front();
+        }
+    }
+
+    /// Rate a recent result; rating it again replaces the earlier rating
+    pub fn rate(&mut self, result_id: &str, positive: bool, comment: Option<String>) -> Result<FeedbackRecord, AppError> {
+        self.sync_profile();
+        let pending = self
+            .pending
+            .iter()
+            .find(|pending| pending.result_id == result_id)
+            .cloned()
+            .ok_or_else(|| AppError::Configuration(format!("No recent result with id {}", result_id)))?;
+
+        if let Some(position) = self.store.records.iter().position(|record| record.result_id == result_id) {
+            if let Some(previous) = self.store.records.remove(position) {
+                let stats = self.route_stats(previous.task, &previous.route);
+                match previous.positive {
+                    true => stats.up = stats.up.saturating_sub(1),
+                    false => stats.down = stats.down.saturating_sub(1),
+                }
+            }
+        }
+
+        let stats = self.route_stats(pending.task, &pending.route);
+        match positive {
+            true => stats.up += 1,
+            false => stats.down += 1,
+        }
+        let record = FeedbackRecord {
+            result_id: pending.result_id,
+            task: pending.task,
+            positive,
+            original: pending.original,
+            result: pending.result,
+            profile: self.profile_id.clone().unwrap_or_default(),
+            route: pending.route,
+            comment: comment.map(|c| c.trim().to_string()).filter(|c| !c.is_empty()),
+            created_at: now_secs(),
+        };
+        self.store.records.push_back(record.clone());
+        while self.store.records.len() > MAX_RECORDS {
+            self.store.records.pop_front();
+        }
+        self.save();
+        Ok(record)
+    }
+
+    pub fn summary(&mut self) -> FeedbackSummary {
+        self.sync_profile();
+        let mut routes = Vec::new();
+        for (task, task_routes) in &self.store.routes {
+            let Ok(task) = serde_json::from_value::<FeedbackTask>(serde_json::Value::String(task.clone())) else {
+                continue;
+            };
+            let best = task_routes
+                .values()
+                .filter(|(_, stats)| stats.rated() > 0)
+                .map(|(_, stats)| stats.score())
+                .fold(None, |best: Option<f64>, score| Some(best.map_or(score, |b| b.max(score))));
+            for (route, stats) in task_routes.values() {
+                routes.push(RouteSummary {
+                    task,
+                    route: route.clone(),
+                    up: stats.up,
+                    down: stats.down,
+                    score: stats.score(),
+                    preferred: stats.rated() > 0 && Some(stats.score()) == best,
+                });
+            }
+        }
+        FeedbackSummary {
+            total_up: routes.iter().map(|route| route.up).sum(),
+            total_down: routes.iter().map(|route| route.down).sum(),
+            routes,
+            recent: self.store.records.iter().rev().take(RECENT_IN_SUMMARY).cloned().collect(),
+        }
+    }
+
+    /// Forget everything in memory; used after history files were purged from disk
+    pub fn reset(&mut self) {
+        self.store = FeedbackStore::default();
+        self.pending.clear();
+    }
+
+    fn route_stats(&mut self, task: FeedbackTask, route: &Route) -> &mut RouteStats {
+        &mut self
+            .store
+            .routes
+            .entry(task_key(task))
+            .or_default()
+            .entry(route_key(route))
+            .or_insert_with(|| (route.clone(), RouteStats::default()))
+            .1
+    }
+
+    /// Load the active profile's feedback if the profile changed since the last call
+    fn sync_profile(&mut self) {
+        let active = active_profile_id();
+        if self.profile_id.as_deref() == Some(active.as_str()) {
+            return;
+        }
+        self.store = load_store(&active);
+        self.pending.clear();
+        self.profile_id = Some(active);
+    }
+
+    fn save(&self) {
+        let Some(profile_id) = &self.profile_id else {
+            return;
+        };
+        let result = profile_data_path(profile_id, DataDir::History).and_then(|dir| {
+            std::fs::create_dir_all(&dir).map_err(|e| AppError::Internal(e.to_string()))?;
+            let json = serde_json::to_vec(&self.store).map_err(|e| AppError::Internal(e.to_string()))?;
+            get_data_vault().write_file(&dir.join(FEEDBACK_FILE), &json)
+        });
+        if let Err(e) = result {
+            log::warn!("Failed to save result feedback: {}", e);
+        }
+    }
+}
+
+static FEEDBACK: std::sync::OnceLock<tokio::sync::Mutex<FeedbackLoop>> = std::sync::OnceLock::new();
+
+pub fn get_feedback_loop() -> &'static tokio::sync::Mutex<FeedbackLoop> {
+    FEEDBACK.get_or_init(|| tokio::sync::Mutex::new(FeedbackLoop::default()))
+}
+
+/// Candidate routes for dictation: one per configured tone, all on the on-device processor
+pub fn dictation_routes(settings: &FeedbackSettings) -> Vec<Route> {
+    let tones = match settings.dictation_tones.is_empty() {
+        true => vec![ToneType::Professional],
+        false => settings.dictation_tones.clone(),
+    };
+    tones
+        .iter()
+        .map(|tone| Route {
+            template: template_name(tone),
+            model: "local".to_string(),
+        })
+        .collect()
+}
+
+/// Candidate routes for refinement: one per configured model, or the default text model
+pub fn refinement_routes(settings: &FeedbackSettings, text_model: &str) -> Vec<Route> {
+    let models = match settings.refinement_models.is_empty() {
+        true => vec![text_model.to_string()],
+        false => settings.refinement_models.clone(),
+    };
+    models
+        .into_iter()
+        .map(|model| Route {
+            template: "refine".to_string(),
+            model,
+        })
+        .collect()
+}
+
+/// The tone a dictation route stands for
+pub fn route_tone(route: &Route) -> ToneType {
+    serde_json::from_value(serde_json::Value::String(route.template.clone())).unwrap_or(ToneType::Professional)
+}
+
+fn template_name(tone: &ToneType) -> String {
+    serde_json::to_value(tone)
+        .ok()
+        .and_then(|value| value.as_str().map(str::to_string))
+        .unwrap_or_else(|| format!("{:?}", tone))
+}
+
+fn task_key(task: FeedbackTask) -> String {
+    serde_json::to_value(task)
+        .ok()
+        .and_then(|value| value.as_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+fn route_key(route: &Route) -> String {
+    format!("{}|{}", route.template, route.model)
+}
+
+fn load_store(profile_id: &str) -> FeedbackStore {
+    profile_data_path(profile_id, DataDir::History)
+        .ok()
+        .map(|dir| dir.join(FEEDBACK_FILE))
+        .filter(|path| path.exists())
+        .and_then(|path| get_data_vault().read_file(&path).ok())
+        .and_then(|data| serde_json::from_slice(&data).ok())
+        .unwrap_or_default()
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
@@ -0,0 +1,140 @@
+//! Focus awareness for VoiceFlow Pro
+//! Quiets notifications, defers background jobs and guards the hotkey while the user is presenting or in do-not-disturb
+
+use std::sync::RwLock;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::system_activity::{do_not_disturb_enabled, frontmost_application, frontmost_is_fullscreen};
+use crate::AppState;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FocusSettings {
+    /// Follow the OS do-not-disturb / focus mode
+    pub respect_do_not_disturb: bool,
+    /// Hold back desktop notifications, such as finished jobs or headset warnings, while quiet
+    pub suppress_notifications: bool,
+    pub defer_background_jobs: bool,
+    /// Ignore the dictation hotkey while a full-screen window has focus
+    pub guard_hotkey_when_fullscreen: bool,
+    /// Apps in which the hotkey is ignored, matched case-insensitively by name
+    pub hotkey_blocked_apps: Vec<String>,
+    /// Apps treated like do-not-disturb while focused
+    pub quiet_apps: Vec<String>,
+}
+
+impl Default for FocusSettings {
+    fn default() -> Self {
+        Self {
+            respect_do_not_disturb: true,
+            suppress_notifications: true,
+            defer_background_jobs: true,
+            guard_hotkey_when_fullscreen: true,
+            hotkey_blocked_apps: vec![
+                "PowerPoint".to_string(),
+                "Keynote".to_string(),
+                "soffice.bin".to_string(),
+            ],
+            quiet_apps: Vec::new(),
+        }
+    }
+}
+
+/// What the app should hold back right now, and why
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FocusState {
+    pub do_not_disturb: bool,
+    pub fullscreen: bool,
+    pub focused_app: Option<String>,
+    pub suppress_notifications: bool,
+    pub defer_jobs: bool,
+    pub block_hotkey: bool,
+    pub reason: Option<String>,
+}
+
+static FOCUS_STATE: RwLock<Option<FocusState>> = RwLock::new(None);
+
+/// Latest evaluated focus state; nothing is held back until the first probe has run
+pub fn current_focus_state() -> FocusState {
+    FOCUS_STATE
+        .read()
+        .ok()
+        .and_then(|state| state.clone())
+        .unwrap_or_default()
+}
+
+/// Whether a desktop notification may be shown now; `enabled` is the app-wide notifications setting,
+/// and the focus rules hold notifications back while the user is presenting or in do-not-disturb
+pub fn notifications_allowed(enabled: bool) -> bool {
+    enabled && !current_focus_state().suppress_notifications
+}
+
+/// Probe the OS and apply the rules
+pub async fn evaluate_focus(settings: &FocusSettings) -> FocusState {
+    let (dnd, fullscreen, focused_app) = tokio::join!(
+        do_not_disturb_enabled(),
+        frontmost_is_fullscreen(),
+        frontmost_application()
+    );
+    let do_not_disturb = settings.respect_do_not_disturb && dnd.unwrap_or(false);
+    let matches = |apps: &[String]| {
+        focused_app.as_deref().map_or(false, |app| {
+            let app = app.to_lowercase();
+            apps.iter().any(|name| !name.is_empty() && app.contains(&name.to_lowercase()))
+        })
+    };
+    let quiet_app = matches(&settings.quiet_apps);
+    let blocked_app = matches(&settings.hotkey_blocked_apps);
+    let quiet = do_not_disturb || quiet_app;
+
+    let reason = if blocked_app || quiet_app {
+        focused_app.as_ref().map(|app| format!("{} is focused", app))
+    } else if fullscreen && settings.guard_hotkey_when_fullscreen {
+        Some("A full-screen window is focused".to_string())
+    } else if do_not_disturb {
+        Some("Do not disturb is on".to_string())
+    } else {
+        None
+    };
+
+    FocusState {
+        do_not_disturb,
+        fullscreen,
+        focused_app,
+        suppress_notifications: quiet && settings.suppress_notifications,
+        defer_jobs: quiet && settings.defer_background_jobs,
+        block_hotkey: blocked_app || (fullscreen && settings.guard_hotkey_when_fullscreen),
+        reason,
+    }
+}
+
+/// Re-evaluate now, e.g. after the rules changed, and publish the result
+pub async fn refresh_focus(settings: &FocusSettings) -> FocusState {
+    let state = evaluate_focus(settings).await;
+    if let Ok(mut current) = FOCUS_STATE.write() {
+        *current = Some(state.clone());
+    }
+    state
+}
+
+/// Poll focus in the background, emitting "focus-changed" whenever the outcome changes
+pub async fn run_focus_monitor(state: AppState, app: AppHandle) {
+    let mut previous: Option<FocusState> = None;
+    loop {
+        let (settings, resources) = {
+            let settings = state.settings.snapshot();
+            (settings.focus.clone(), settings.resources.clone())
+        };
+        let current = refresh_focus(&settings).await;
+        if previous.as_ref() != Some(&current) {
+            let _ = app.emit_all("focus-changed", &current);
+            previous = Some(current);
+        }
+        tokio::time::sleep(crate::idle::adjust_interval(POLL_INTERVAL, &resources)).await;
+    }
+}
@@ -0,0 +1,529 @@
+//! Form filling for VoiceFlow Pro
+//! Walks the user through a form field by field, validating each dictated answer into a structured object
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use chrono::{Duration as ChronoDuration, Local, NaiveDate};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Number, Value};
+use tauri::{AppHandle, Manager};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use crate::errors::{AppError, ValidationError};
+use crate::integrations::speech_stream;
+use crate::integrations::voice_profiles::resolve_voice;
+use crate::AppState;
+
+const MAX_FIELDS: usize = 100;
+const DATE_FORMATS: &[&str] = &["%Y-%m-%d", "%B %d %Y", "%d %B %Y", "%b %d %Y", "%d %b %Y", "%m/%d/%Y", "%d.%m.%Y"];
+
+/// The fields to fill, in the order they are asked
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FormSchema {
+    pub title: String,
+    pub fields: Vec<FormField>,
+    /// Read each prompt aloud as well as sending it as an event
+    #[serde(default)]
+    pub speak_prompts: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FormField {
+    /// Key in the completed object
+    pub name: String,
+    /// What the user is asked; defaults to the field name
+    #[serde(default)]
+    pub prompt: Option<String>,
+    #[serde(flatten)]
+    pub kind: FieldKind,
+    #[serde(default = "default_required")]
+    pub required: bool,
+}
+
+fn default_required() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FieldKind {
+    Text {
+        #[serde(default)]
+        max_length: Option<usize>,
+        /// Regular expression the whole answer must match
+        #[serde(default)]
+        pattern: Option<String>,
+    },
+    Number {
+        #[serde(default)]
+        min: Option<f64>,
+        #[serde(default)]
+        max: Option<f64>,
+        /// Only whole numbers are accepted
+        #[serde(default)]
+        integer: bool,
+    },
+    /// Stored as YYYY-MM-DD
+    Date,
+    Boolean,
+    Enum { options: Vec<String> },
+}
+
+impl FormField {
+    fn prompt(&self) -> String {
+        let base = self
+            .prompt
+            .clone()
+            .unwrap_or_else(|| self.name.replace('_', " "));
+        match &self.kind {
+            FieldKind::Enum { options } => format!("{} Options: {}.", base, options.join(", ")),
+            _ => base,
+        }
+    }
+}
+
+/// Sent as "form-field-prompt" whenever the session moves to a field
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldPrompt {
+    pub session_id: String,
+    pub field: String,
+    pub prompt: String,
+    pub index: usize,
+    pub total: usize,
+    pub required: bool,
+    /// Why the previous answer for this field was not accepted
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FormSessionStatus {
+    pub session_id: String,
+    pub title: String,
+    pub values: Map<String, Value>,
+    /// The field waiting for an answer, or `None` once the form is complete
+    pub current: Option<FieldPrompt>,
+    pub complete: bool,
+    pub cancelled: bool,
+}
+
+/// Sent as "form-field-accepted" or "form-field-rejected" for every answer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldAnswer {
+    pub session_id: String,
+    pub field: String,
+    pub answer: String,
+    pub value: Option<Value>,
+    pub error: Option<String>,
+}
+
+struct FormSession {
+    schema: FormSchema,
+    index: usize,
+    values: Map<String, Value>,
+}
+
+impl FormSession {
+    fn status(&self, id: &str, error: Option<String>) -> FormSessionStatus {
+        let current = self.schema.fields.get(self.index).map(|field| FieldPrompt {
+            session_id: id.to_string(),
+            field: field.name.clone(),
+            prompt: field.prompt(),
+            index: self.index,
+            total: self.schema.fields.len(),
+            required: field.required,
+            error,
+        });
+        FormSessionStatus {
+            session_id: id.to_string(),
+            title: self.schema.title.clone(),
+            values: self.values.clone(),
+            complete: current.is_none(),
+            current,
+            cancelled: false,
+        }
+    }
+}
+
+#[derive(Default)]
+struct FormSessions {
+    sessions: HashMap<String, FormSession>,
+    /// Session that dictation answers; the most recently started one still open
+    active: Option<String>,
+}
+
+static FORM_SESSIONS: OnceLock<Mutex<FormSessions>> = OnceLock::new();
+
+fn form_sessions() -> &'static Mutex<FormSessions> {
+    FORM_SESSIONS.get_or_init(|| Mutex::new(FormSessions::default()))
+}
+
+/// Spoken navigation understood in place of an answer
+enum Navigation {
+    Skip,
+    Back,
+    Cancel,
+}
+
+fn parse_navigation(answer: &str) -> Option<Navigation> {
+    let normalized: String = answer
+        .trim()
+        .trim_end_matches(['.', '!'])
+        .to_lowercase();
+    match normalized.as_str() {
+        "skip" | "skip it" | "skip this" | "next" | "next field" | "pass" => Some(Navigation::Skip),
+        "back" | "go back" | "previous" | "previous field" => Some(Navigation::Back),
+        "cancel" | "cancel form" | "stop form" | "cancel the form" => Some(Navigation::Cancel),
+        _ => None,
+    }
+}
+
+pub fn validate_schema(schema: &FormSchema) -> Result<(), AppError> {
+    let invalid = |message: String| AppError::Validation(ValidationError::InvalidConfigValue(message));
+    if schema.fields.is_empty() || schema.fields.len() > MAX_FIELDS {
+        return Err(invalid(format!("Forms need between 1 and {} fields", MAX_FIELDS)));
+    }
+    for (index, field) in schema.fields.iter().enumerate() {
+        if field.name.trim().is_empty() {
+            return Err(invalid(format!("Field {} has no name", index + 1)));
+        }
+        if schema.fields[..index].iter().any(|other| other.name == field.name) {
+            return Err(invalid(format!("Field '{}' appears twice", field.name)));
+        }
+        match &field.kind {
+            FieldKind::Enum { options } if options.is_empty() => {
+                return Err(invalid(format!("Field '{}' has no options", field.name)));
+            }
+            FieldKind::Text { pattern: Some(pattern), .. } if Regex::new(pattern).is_err() => {
+                return Err(invalid(format!("Field '{}' has an invalid pattern", field.name)));
+            }
+            FieldKind::Number { min: Some(min), max: Some(max), .. } if min > max => {
+                return Err(invalid(format!("Field '{}' has a minimum above its maximum", field.name)));
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Open a form session, make it the one dictation answers, and ask for the first field
+pub async fn start_session(state: &AppState, app: &AppHandle, schema: FormSchema) -> Result<FormSessionStatus, AppError> {
+    validate_schema(&schema)?;
+    let id = Uuid::new_v4().to_string();
+    let session = FormSession {
+        schema,
+        index: 0,
+        values: Map::new(),
+    };
+    let status = session.status(&id, None);
+    let speak = session.schema.speak_prompts;
+    {
+        let mut sessions = form_sessions().lock().await;
+        sessions.sessions.insert(id.clone(), session);
+        sessions.active = Some(id.clone());
+    }
+    announce(state, app, &status, speak).await;
+    Ok(status)
+}
+
+/// Whether dictation should currently go to a form instead of being typed
+pub async fn has_active_session() -> bool {
+    form_sessions().lock().await.active.is_some()
+}
+
+/// Answer the current field of `session_id`, or of the active session when `None`
+pub async fn answer(
+    state: &AppState,
+    app: &AppHandle,
+    session_id: Option<&str>,
+    answer: &str,
+) -> Result<FormSessionStatus, AppError> {
+    let mut sessions = form_sessions().lock().await;
+    let id = match session_id {
+        Some(id) => id.to_string(),
+        None => sessions
+            .active
+            .clone()
+            .ok_or_else(|| AppError::Configuration("No form is being filled".to_string()))?,
+    };
+    let session = sessions
+        .sessions
+        .get_mut(&id)
+        .ok_or_else(|| AppError::Configuration(format!("Form session {} has ended", id)))?;
+    let field = session
+        .schema
+        .fields
+        .get(session.index)
+        .cloned()
+        .ok_or_else(|| AppError::Configuration("The form is already complete".to_string()))?;
+
+    let mut error = None;
+    match parse_navigation(answer) {
+        Some(Navigation::Cancel) => {
+            let status = FormSessionStatus {
+                current: None,
+                cancelled: true,
+                ..session.status(&id, None)
+            };
+            sessions.sessions.remove(&id);
+            if sessions.active.as_deref() == Some(id.as_str()) {
+                sessions.active = None;
+            }
+            drop(sessions);
+            let _ = app.emit_all("form-cancelled", &id);
+            return Ok(status);
+        }
+        Some(Navigation::Back) => session.index = session.index.saturating_sub(1),
+        Some(Navigation::Skip) if !field.required => {
+            session.values.insert(field.name.clone(), Value::Null);
+            session.index += 1;
+        }
+        Some(Navigation::Skip) => error = Some(format!("{} is required", field.name.replace('_', " "))),
+        None => {
+            let outcome = parse_answer(&field.kind, answer);
+            let event = FieldAnswer {
+                session_id: id.clone(),
+                field: field.name.clone(),
+                answer: answer.to_string(),
+                value: outcome.as_ref().ok().cloned(),
+                error: outcome.as_ref().err().cloned(),
+            };
+            match outcome {
+                Ok(value) => {
+                    let _ = app.emit_all("form-field-accepted", &event);
+                    session.values.insert(field.name.clone(), value);
+                    session.index += 1;
+                }
+                Err(e) => {
+                    let _ = app.emit_all("form-field-rejected", &event);
+                    error = Some(e);
+                }
+            }
+        }
+    }
+
+    let status = session.status(&id, error);
+    let speak = session.schema.speak_prompts;
+    if status.complete {
+        sessions.sessions.remove(&id);
+        if sessions.active.as_deref() == Some(id.as_str()) {
+            sessions.active = None;
+        }
+    }
+    drop(sessions);
+
+    if status.complete {
+        let _ = app.emit_all("form-completed", &status);
+    } else {
+        announce(state, app, &status, speak).await;
+    }
+    Ok(status)
+}
+
+pub async fn session_status(session_id: &str) -> Option<FormSessionStatus> {
+    let sessions = form_sessions().lock().await;
+    sessions.sessions.get(session_id).map(|session| session.status(session_id, None))
+}
+
+pub async fn cancel_session(app: &AppHandle, session_id: &str) -> bool {
+    let mut sessions = form_sessions().lock().await;
+    let removed = sessions.sessions.remove(session_id).is_some();
+    if sessions.active.as_deref() == Some(session_id) {
+        sessions.active = None;
+    }
+    if removed {
+        let _ = app.emit_all("form-cancelled", session_id);
+    }
+    removed
+}
+
+/// Send the current field's prompt, and speak it when the form asks for that
+async fn announce(state: &AppState, app: &AppHandle, status: &FormSessionStatus, speak: bool) {
+    let Some(prompt) = &status.current else {
+        return;
+    };
+    let _ = app.emit_all("form-field-prompt", prompt);
+    if !speak {
+        return;
+    }
+    let settings = state.settings.snapshot();
+    let text = match &prompt.error {
+        Some(error) => format!("{}. {}", error, prompt.prompt),
+        None => prompt.prompt.clone(),
+    };
+    let (model, voice) = resolve_voice(&settings.voices.custom_voices, &settings.ai_ml_settings.voice_model, None);
+    if let Err(e) =
+        speech_stream::start_stream(app.clone(), state.ai_ml_gateway.clone(), &text, model, voice, settings.language.clone()).await
+    {
+        log::warn!("Could not speak the form prompt: {}", e);
+    }
+}
+
+/// Turn a dictated answer into the field's value, or explain why it does not fit
+pub fn parse_answer(kind: &FieldKind, answer: &str) -> Result<Value, String> {
+    let answer = answer.trim().trim_end_matches(['.', '!']).trim();
+    if answer.is_empty() {
+        return Err("No answer was heard".to_string());
+    }
+    match kind {
+        FieldKind::Text { max_length, pattern } => {
+            if let Some(max_length) = max_length {
+                if answer.chars().count() > *max_length {
+                    return Err(format!("Keep the answer under {} characters", max_length));
+                }
+            }
+            if let Some(pattern) = pattern {
+                let matches = Regex::new(&format!("^(?:{})$", pattern))
+                    .map(|pattern| pattern.is_match(answer))
+                    .unwrap_or(false);
+                if !matches {
+                    return Err(format!("'{}' is not in the expected format", answer));
+                }
+            }
+            Ok(Value::String(answer.to_string()))
+        }
+        FieldKind::Number { min, max, integer } => {
+            let number = parse_number(answer).ok_or_else(|| format!("'{}' is not a number", answer))?;
+            if *integer && number.fract() != 0.0 {
+                return Err(format!("{} is not a whole number", number));
+            }
+            if min.map_or(false, |min| number < min) || max.map_or(false, |max| number > max) {
+                return Err(format!(
+                    "{} is outside the allowed range{}{}",
+                    number,
+                    min.map(|min| format!(" from {}", min)).unwrap_or_default(),
+                    max.map(|max| format!(" to {}", max)).unwrap_or_default(),
+                ));
+            }
+            let value = if *integer {
+                Number::from(number as i64)
+            } else {
+                Number::from_f64(number).ok_or_else(|| format!("{} cannot be stored", number))?
+            };
+            Ok(Value::Number(value))
+        }
+        FieldKind::Date => parse_date(answer)
+            .map(|date| Value::String(date.format("%Y-%m-%d").to_string()))
+            .ok_or_else(|| format!("'{}' is not a date I understand", answer)),
+        FieldKind::Boolean => match answer.to_lowercase().as_str() {
+            "yes" | "yeah" | "yep" | "true" | "correct" | "affirmative" => Ok(Value::Bool(true)),
+            "no" | "nope" | "false" | "negative" => Ok(Value::Bool(false)),
+            _ => Err("Answer yes or no".to_string()),
+        },
+        FieldKind::Enum { options } => {
+            let spoken = normalize(answer);
+            options
+                .iter()
+                .find(|option| normalize(option) == spoken)
+                .or_else(|| {
+                    let partial: Vec<&String> = options
+                        .iter()
+                        .filter(|option| spoken.contains(&normalize(option)) || normalize(option).contains(&spoken))
+                        .collect();
+                    (partial.len() == 1).then(|| partial[0])
+                })
+                .map(|option| Value::String(option.clone()))
+                .ok_or_else(|| format!("Choose one of: {}", options.join(", ")))
+        }
+    }
+}
+
+fn normalize(text: &str) -> String {
+    text.to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Digits ("1,250.5") or English number words ("twelve hundred and fifty", "minus three point five")
+fn parse_number(answer: &str) -> Option<f64> {
+    if let Ok(number) = answer.replace(',', "").parse::<f64>() {
+        return Some(number);
+    }
+    let words: Vec<String> = normalize(&answer.replace('-', " "))
+        .split_whitespace()
+        .filter(|word| *word != "and")
+        .map(str::to_string)
+        .collect();
+    let (negative, words) = match words.first().map(String::as_str) {
+        Some("minus") | Some("negative") => (true, &words[1..]),
+        _ => (false, &words[..]),
+    };
+    let (whole, fraction) = match words.iter().position(|word| word == "point") {
+        Some(point) => (&words[..point], Some(&words[point + 1..])),
+        None => (words, None),
+    };
+    let mut number = parse_whole(whole)? as f64;
+    if let Some(fraction) = fraction {
+        let digits: String = fraction.iter().map(|word| digit_value(word)).collect::<Option<_>>()?;
+        number += format!("0.{}", digits).parse::<f64>().ok()?;
+    }
+    Some(if negative { -number } else { number })
+}
+
+fn parse_whole(words: &[String]) -> Option<u64> {
+    if words.is_empty() {
+        return None;
+    }
+    let (mut total, mut current) = (0u64, 0u64);
+    for word in words {
+        match word.as_str() {
+            "hundred" => current = current.max(1) * 100,
+            "thousand" | "million" | "billion" => {
+                let scale = match word.as_str() {
+                    "thousand" => 1_000,
+                    "million" => 1_000_000,
+                    _ => 1_000_000_000,
+                };
+                total += current.max(1) * scale;
+                current = 0;
+            }
+            _ => current += small_number(word)?,
+        }
+    }
+    Some(total + current)
+}
+
+fn small_number(word: &str) -> Option<u64> {
+    const UNITS: &[&str] = &[
+        "zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine", "ten", "eleven", "twelve",
+        "thirteen", "fourteen", "fifteen", "sixteen", "seventeen", "eighteen", "nineteen",
+    ];
+    const TENS: &[&str] = &["twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety"];
+    if let Some(value) = UNITS.iter().position(|unit| *unit == word) {
+        return Some(value as u64);
+    }
+    if let Some(value) = TENS.iter().position(|ten| *ten == word) {
+        return Some((value as u64 + 2) * 10);
+    }
+    word.parse().ok()
+}
+
+fn digit_value(word: &str) -> Option<char> {
+    let value = small_number(word).filter(|value| *value < 10)?;
+    char::from_digit(value as u32, 10)
+}
+
+/// ISO and common written dates, plus "today", "tomorrow" and "yesterday"
+fn parse_date(answer: &str) -> Option<NaiveDate> {
+    let lowered = answer.to_lowercase();
+    let today = Local::now().date_naive();
+    match lowered.as_str() {
+        "today" => return Some(today),
+        "tomorrow" => return Some(today + ChronoDuration::days(1)),
+        "yesterday" => return Some(today - ChronoDuration::days(1)),
+        _ => {}
+    }
+    // "March 5th, 2024" -> "March 5 2024"
+    let ordinals = Regex::new(r"(?i)\b(\d{1,2})(?:st|nd|rd|th)\b").ok()?;
+    let cleaned = ordinals.replace_all(answer, "$1").replace(',', " ").replace(" of ", " ");
+    let cleaned = cleaned.split_whitespace().collect::<Vec<_>>().join(" ");
+    DATE_FORMATS
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(&cleaned, format).ok())
+}
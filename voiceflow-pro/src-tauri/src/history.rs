@@ -0,0 +1,307 @@
+//! Transcript history for VoiceFlow Pro
+//! Keeps recent dictation segments with their audio so a segment can be re-transcribed by a stronger engine
+
+use std::collections::VecDeque;
+use std::path::PathBuf;
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::data_management::secure_delete;
+use crate::encryption::get_data_vault;
+use crate::errors::{AppError, ValidationError};
+use crate::storage::{active_profile_id, profile_data_path, DataDir};
+
+const SEGMENTS_FILE: &str = "segments.json";
+const MAX_SEGMENTS: usize = 500;
+const MAX_SEGMENT_AUDIO_BYTES: usize = 25 * 1024 * 1024;
+const AUDIO_FORMATS: &[&str] = &["wav", "webm", "ogg", "mp3", "m4a", "flac"];
+
+/// Higher-accuracy engines a segment can be escalated to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RetranscriptionEngine {
+    /// OpenAI Whisper large through aimlapi.com
+    WhisperLarge,
+    /// Deepgram Nova-2 through aimlapi.com
+    Nova2,
+}
+
+impl RetranscriptionEngine {
+    pub fn model_id(self) -> &'static str {
+        match self {
+            RetranscriptionEngine::WhisperLarge => "#g1_whisper-large",
+            RetranscriptionEngine::Nova2 => "#g1_nova-2-general",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            RetranscriptionEngine::WhisperLarge => "cloud-whisper-large",
+            RetranscriptionEngine::Nova2 => "cloud-nova-2",
+        }
+    }
+}
+
+/// Audio captured with a dictation, as sent by the frontend
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SegmentAudio {
+    pub data_base64: String,
+    /// File extension such as "wav" or "webm"
+    pub format: String,
+}
+
+/// An earlier text of a segment, kept when a better engine replaces it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SegmentRevision {
+    pub text: String,
+    pub engine: String,
+    pub replaced_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptSegment {
+    pub id: String,
+    pub text: String,
+    /// Engine that produced `text`
+    pub engine: String,
+    pub language: String,
+    pub created_at: u64,
+    /// Audio file name in the recordings folder, when audio was kept
+    pub audio_file: Option<String>,
+    #[serde(default)]
+    pub revisions: Vec<SegmentRevision>,
+    /// Topic label from the session's topic tracker, for filtering history
+    #[serde(default)]
+    pub topic: Option<String>,
+    /// Label of the microphone the segment was spoken into, when recording from two
+    #[serde(default)]
+    pub speaker: Option<String>,
+}
+
+/// A topic label and how many stored segments carry it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TopicCount {
+    pub topic: String,
+    pub segments: usize,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SegmentStore {
+    segments: VecDeque<TranscriptSegment>,
+}
+
+/// Recent segments of the active profile
+#[derive(Debug, Default)]
+pub struct TranscriptHistory {
+    profile_id: Option<String>,
+    store: SegmentStore,
+}
+
+impl TranscriptHistory {
+    /// Record a transcript, storing its audio in the (encrypted) recordings folder
+    pub fn add_segment(
+        &mut self,
+        text: &str,
+        engine: &str,
+        language: &str,
+        topic: Option<&str>,
+        speaker: Option<&str>,
+        audio: Option<SegmentAudio>,
+    ) -> Result<TranscriptSegment, AppError> {
+        self.sync_profile();
+        let id = Uuid::new_v4().to_string();
+        let audio_file = match audio {
+            Some(audio) => Some(self.save_audio(&id, &audio)?),
+            None => None,
+        };
+
+        let segment = TranscriptSegment {
+            id,
+            text: text.to_string(),
+            engine: engine.to_string(),
+            language: language.to_string(),
+            created_at: now_secs(),
+            audio_file,
+            revisions: Vec::new(),
+            topic: topic.map(str::to_string),
+            speaker: speaker.map(str::to_string),
+        };
+        self.store.segments.push_back(segment.clone());
+        while self.store.segments.len() > MAX_SEGMENTS {
+            if let Some(old) = self.store.segments.pop_front() {
+                self.delete_audio(&old);
+            }
+        }
+        self.save();
+        Ok(segment)
+    }
+
+    /// Newest first, optionally only segments whose topic label mentions `topic`
+    pub fn list(&mut self, limit: usize, topic: Option<&str>) -> Vec<TranscriptSegment> {
+        self.sync_profile();
+        let topic = topic.map(|topic| topic.trim().to_lowercase()).filter(|topic| !topic.is_empty());
+        self.store
+            .segments
+            .iter()
+            .rev()
+            .filter(|segment| match &topic {
+                Some(topic) => segment.topic.as_ref().map_or(false, |label| label.to_lowercase().contains(topic)),
+                None => true,
+            })
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    /// Topic labels in stored history, most used first
+    pub fn topics(&mut self) -> Vec<TopicCount> {
+        self.sync_profile();
+        let mut counts: Vec<TopicCount> = Vec::new();
+        for label in self.store.segments.iter().filter_map(|segment| segment.topic.as_deref()) {
+            match counts.iter_mut().find(|count| count.topic == label) {
+                Some(count) => count.segments += 1,
+                None => counts.push(TopicCount {
+                    topic: label.to_string(),
+                    segments: 1,
+                }),
+            }
+        }
+        counts.sort_by(|a, b| b.segments.cmp(&a.segments).then_with(|| a.topic.cmp(&b.topic)));
+        counts
+    }
+
+    pub fn get(&mut self, id: &str) -> Result<TranscriptSegment, AppError> {
+        self.sync_profile();
+        self.store
+            .segments
+            .iter()
+            .find(|s| s.id == id)
+            .cloned()
+            .ok_or_else(|| AppError::Configuration(format!("Unknown transcript segment: {}", id)))
+    }
+
+    /// Stored audio of a segment together with its file name
+    pub fn load_audio(&mut self, id: &str) -> Result<(Vec<u8>, String), AppError> {
+        let segment = self.get(id)?;
+        let file = segment
+            .audio_file
+            .ok_or_else(|| AppError::Configuration("This segment has no stored audio to re-transcribe".to_string()))?;
+        let path = self.recordings_dir()?.join(&file);
+        let audio = get_data_vault().read_file(&path)?;
+        Ok((audio, file))
+    }
+
+    /// Replace a segment's text with a better transcription, keeping the old text as a revision
+    pub fn apply_revision(&mut self, id: &str, text: &str, engine: &str) -> Result<TranscriptSegment, AppError> {
+        self.sync_profile();
+        let segment = self
+            .store
+            .segments
+            .iter_mut()
+            .find(|s| s.id == id)
+            .ok_or_else(|| AppError::Configuration(format!("Unknown transcript segment: {}", id)))?;
+        let previous = SegmentRevision {
+            text: std::mem::replace(&mut segment.text, text.to_string()),
+            engine: std::mem::replace(&mut segment.engine, engine.to_string()),
+            replaced_at: now_secs(),
+        };
+        segment.revisions.push(previous);
+        let segment = segment.clone();
+        self.save();
+        Ok(segment)
+    }
+
+    /// Forget everything in memory; used after history files were purged from disk
+    pub fn reset(&mut self) {
+        self.store = SegmentStore::default();
+    }
+
+    fn save_audio(&self, id: &str, audio: &SegmentAudio) -> Result<String, AppError> {
+        let format = audio.format.trim_start_matches('.').to_lowercase();
+        if !AUDIO_FORMATS.contains(&format.as_str()) {
+            return Err(AppError::Validation(ValidationError::InvalidConfigValue(format!(
+                "Unsupported audio format: {}",
+                audio.format
+            ))));
+        }
+        let bytes = BASE64
+            .decode(&audio.data_base64)
+            .map_err(|e| AppError::Validation(ValidationError::InvalidConfigValue(format!("Invalid audio data: {}", e))))?;
+        if bytes.len() > MAX_SEGMENT_AUDIO_BYTES {
+            return Err(AppError::Validation(ValidationError::InvalidConfigValue(
+                "Segment audio is larger than 25 MB".to_string(),
+            )));
+        }
+
+        let dir = self.recordings_dir()?;
+        std::fs::create_dir_all(&dir).map_err(|e| AppError::Internal(e.to_string()))?;
+        let file = format!("{}.{}", id, format);
+        get_data_vault().write_file(&dir.join(&file), &bytes)?;
+        Ok(file)
+    }
+
+    fn delete_audio(&self, segment: &TranscriptSegment) {
+        let (Some(file), Ok(dir)) = (&segment.audio_file, self.recordings_dir()) else {
+            return;
+        };
+        let path = dir.join(file);
+        if path.exists() {
+            if let Err(e) = secure_delete(&path) {
+                log::warn!("Could not delete {}: {}", path.display(), e);
+            }
+        }
+    }
+
+    fn recordings_dir(&self) -> Result<PathBuf, AppError> {
+        profile_data_path(self.profile_id.as_deref().unwrap_or(&active_profile_id()), DataDir::Recordings)
+    }
+
+    /// Load the active profile's segments if the profile changed since the last call
+    fn sync_profile(&mut self) {
+        let active = active_profile_id();
+        if self.profile_id.as_deref() == Some(active.as_str()) {
+            return;
+        }
+        self.store = load_store(&active);
+        self.profile_id = Some(active);
+    }
+
+    fn save(&self) {
+        let Some(profile_id) = &self.profile_id else {
+            return;
+        };
+        let result = profile_data_path(profile_id, DataDir::History).and_then(|dir| {
+            std::fs::create_dir_all(&dir).map_err(|e| AppError::Internal(e.to_string()))?;
+            let json = serde_json::to_vec(&self.store).map_err(|e| AppError::Internal(e.to_string()))?;
+            get_data_vault().write_file(&dir.join(SEGMENTS_FILE), &json)
+        });
+        if let Err(e) = result {
+            log::warn!("Failed to save transcript history: {}", e);
+        }
+    }
+}
+
+static TRANSCRIPT_HISTORY: std::sync::OnceLock<tokio::sync::Mutex<TranscriptHistory>> = std::sync::OnceLock::new();
+
+pub fn get_transcript_history() -> &'static tokio::sync::Mutex<TranscriptHistory> {
+    TRANSCRIPT_HISTORY.get_or_init(|| tokio::sync::Mutex::new(TranscriptHistory::default()))
+}
+
+fn load_store(profile_id: &str) -> SegmentStore {
+    profile_data_path(profile_id, DataDir::History)
+        .ok()
+        .map(|dir| dir.join(SEGMENTS_FILE))
+        .filter(|path| path.exists())
+        .and_then(|path| get_data_vault().read_file(&path).ok())
+        .and_then(|data| serde_json::from_slice(&data).ok())
+        .unwrap_or_default()
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
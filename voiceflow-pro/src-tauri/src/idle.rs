@@ -0,0 +1,156 @@
+//! Idle resource management for VoiceFlow Pro
+//! Frees models and caches after a stretch without dictation and slows background polling until the user is back
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::event_channel::{channel_metrics, ChannelMetrics};
+use crate::memory::get_resource_manager;
+use crate::AppState;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+static LAST_ACTIVITY: AtomicU64 = AtomicU64::new(0);
+static IDLE: AtomicBool = AtomicBool::new(false);
+static IDLE_SINCE: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResourceSettings {
+    pub unload_when_idle: bool,
+    pub idle_after_minutes: u64,
+    /// Entries each AI cache keeps while idle
+    pub idle_cache_entries: usize,
+    /// Background polling runs this many times less often while idle
+    pub idle_timer_factor: u32,
+}
+
+impl Default for ResourceSettings {
+    fn default() -> Self {
+        Self {
+            unload_when_idle: true,
+            idle_after_minutes: 10,
+            idle_cache_entries: 10,
+            idle_timer_factor: 6,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResourceStatus {
+    pub idle: bool,
+    pub idle_since: Option<u64>,
+    pub seconds_since_activity: u64,
+    pub model_loaded: bool,
+    pub cached_entries: usize,
+    pub estimated_memory_bytes: u64,
+    pub active_voice_engines: usize,
+    pub active_text_processors: usize,
+    pub event_channels: Vec<ChannelMetrics>,
+}
+
+/// Record dictation activity; an idle app wakes up on the monitor's next check
+pub fn mark_activity() {
+    LAST_ACTIVITY.store(now_secs(), Ordering::SeqCst);
+}
+
+pub fn is_idle() -> bool {
+    IDLE.load(Ordering::SeqCst)
+}
+
+/// Stretch a background polling interval while idle
+pub fn adjust_interval(interval: Duration, settings: &ResourceSettings) -> Duration {
+    if is_idle() {
+        interval * settings.idle_timer_factor.max(1)
+    } else {
+        interval
+    }
+}
+
+pub async fn resource_status(state: &AppState) -> ResourceStatus {
+    let model_loaded = match state.voice_engine.lock().await.as_ref() {
+        Some(engine) => engine.status().model_loaded,
+        None => false,
+    };
+    let cached_entries = match state.ai_ml_gateway.lock().await.as_ref() {
+        Some(gateway) => gateway.cached_entries().await,
+        None => 0,
+    };
+    let manager = get_resource_manager().lock().await;
+    let (active_voice_engines, active_text_processors) = manager.get_active_resources();
+    let idle = is_idle();
+    ResourceStatus {
+        idle,
+        idle_since: idle.then(|| IDLE_SINCE.load(Ordering::SeqCst)),
+        seconds_since_activity: now_secs().saturating_sub(LAST_ACTIVITY.load(Ordering::SeqCst)),
+        model_loaded,
+        cached_entries,
+        estimated_memory_bytes: manager.get_memory_usage(),
+        active_voice_engines,
+        active_text_processors,
+        event_channels: channel_metrics(),
+    }
+}
+
+/// Watch for inactivity, releasing resources on the way into idle and restoring them on the way out
+pub async fn run_idle_monitor(state: AppState, app: AppHandle) {
+    mark_activity();
+    loop {
+        tokio::time::sleep(CHECK_INTERVAL).await;
+        let settings = state.settings.snapshot().resources.clone();
+        let listening = match state.voice_engine.lock().await.as_ref() {
+            Some(engine) => engine.status().is_listening,
+            None => false,
+        };
+        if listening {
+            mark_activity();
+        }
+
+        let inactive_for = now_secs().saturating_sub(LAST_ACTIVITY.load(Ordering::SeqCst));
+        let should_idle = settings.unload_when_idle && inactive_for >= settings.idle_after_minutes.max(1) * 60;
+
+        if should_idle && !is_idle() {
+            enter_idle(&state, &settings).await;
+        } else if !should_idle && is_idle() {
+            leave_idle(&state).await;
+        } else {
+            continue;
+        }
+        let _ = app.emit_all("resource-state-changed", resource_status(&state).await);
+    }
+}
+
+async fn enter_idle(state: &AppState, settings: &ResourceSettings) {
+    log::info!("No dictation for {} minutes; releasing resources", settings.idle_after_minutes);
+    IDLE.store(true, Ordering::SeqCst);
+    IDLE_SINCE.store(now_secs(), Ordering::SeqCst);
+
+    if let Some(engine) = state.voice_engine.lock().await.as_ref() {
+        if let Err(e) = engine.unload_model().await {
+            log::debug!("Recognition model left loaded: {}", e);
+        }
+    }
+    if let Some(gateway) = state.ai_ml_gateway.lock().await.as_ref() {
+        gateway.limit_caches(Some(settings.idle_cache_entries)).await;
+    }
+    get_resource_manager().lock().await.cleanup().await;
+}
+
+/// The model itself is reloaded lazily by the next Start
+async fn leave_idle(state: &AppState) {
+    log::info!("Activity resumed; restoring caches and timers");
+    IDLE.store(false, Ordering::SeqCst);
+    if let Some(gateway) = state.ai_ml_gateway.lock().await.as_ref() {
+        gateway.limit_caches(None).await;
+    }
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
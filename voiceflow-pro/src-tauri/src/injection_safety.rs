@@ -0,0 +1,160 @@
+//! Injection safety for VoiceFlow Pro
+//! Holds back typing and recording while macOS Secure Input is on, e.g. while a password field has focus
+
+use std::sync::RwLock;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::integrations::voice_recognition::{EngineState, PauseReason};
+use crate::system_activity::{process_name, secure_input_pid};
+use crate::AppState;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InjectionSafetySettings {
+    /// Never type into other apps while Secure Input is on
+    pub suppress_injection: bool,
+    /// Pause listening while Secure Input is on, so a spoken password is never transcribed
+    pub pause_recording: bool,
+}
+
+impl Default for InjectionSafetySettings {
+    fn default() -> Self {
+        Self {
+            suppress_injection: true,
+            pause_recording: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct InjectionSafetyStatus {
+    /// Whether this platform can report Secure Input at all
+    pub supported: bool,
+    pub secure_input: bool,
+    pub holder_pid: Option<u32>,
+    /// App that turned Secure Input on, when it could be named
+    pub holder_app: Option<String>,
+    pub injection_allowed: bool,
+    pub recording_allowed: bool,
+    /// Shown to the user while something is held back
+    pub reason: Option<String>,
+}
+
+/// Sent as "injection-suppressed" in place of "voice-response" while typing is held back
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SuppressedInjection {
+    pub text: String,
+    pub reason: String,
+}
+
+static STATUS: RwLock<Option<InjectionSafetyStatus>> = RwLock::new(None);
+
+/// Latest evaluated status; everything is allowed until the first probe has run
+pub fn current_status() -> InjectionSafetyStatus {
+    STATUS
+        .read()
+        .ok()
+        .and_then(|status| status.clone())
+        .unwrap_or(InjectionSafetyStatus {
+            supported: cfg!(target_os = "macos"),
+            injection_allowed: true,
+            recording_allowed: true,
+            ..InjectionSafetyStatus::default()
+        })
+}
+
+/// Why typing into other apps is held back right now, if it is
+pub fn injection_blocked() -> Option<String> {
+    let status = current_status();
+    if status.injection_allowed {
+        None
+    } else {
+        status.reason
+    }
+}
+
+/// Why recording is held back right now, if it is
+pub fn recording_blocked() -> Option<String> {
+    let status = current_status();
+    if status.recording_allowed {
+        None
+    } else {
+        status.reason
+    }
+}
+
+/// Probe Secure Input and apply the settings
+pub async fn evaluate(settings: &InjectionSafetySettings) -> InjectionSafetyStatus {
+    let holder_pid = secure_input_pid().await;
+    let holder_app = match holder_pid {
+        Some(pid) => process_name(pid).await,
+        None => None,
+    };
+    let secure_input = holder_pid.is_some();
+    let reason = secure_input.then(|| match &holder_app {
+        Some(app) => format!("Secure Input is on in {}, usually for a password field", app),
+        None => "Secure Input is on, usually for a password field".to_string(),
+    });
+    InjectionSafetyStatus {
+        supported: cfg!(target_os = "macos"),
+        secure_input,
+        holder_pid,
+        holder_app,
+        injection_allowed: !(secure_input && settings.suppress_injection),
+        recording_allowed: !(secure_input && settings.pause_recording),
+        reason,
+    }
+}
+
+/// Re-evaluate now, e.g. after the settings changed, and publish the result
+pub async fn refresh(settings: &InjectionSafetySettings) -> InjectionSafetyStatus {
+    let status = evaluate(settings).await;
+    if let Ok(mut current) = STATUS.write() {
+        *current = Some(status.clone());
+    }
+    status
+}
+
+/// Poll Secure Input, emitting "injection-safety-changed" on every change and pausing listening while it blocks recording
+pub async fn run_safety_monitor(state: AppState, app: AppHandle) {
+    if !cfg!(target_os = "macos") {
+        return;
+    }
+    let mut previous: Option<InjectionSafetyStatus> = None;
+    loop {
+        let settings = state.settings.snapshot().injection_safety.clone();
+        let current = refresh(&settings).await;
+        if previous.as_ref() != Some(&current) {
+            let _ = app.emit_all("injection-safety-changed", &current);
+            apply_recording_hold(&state, &app, &current).await;
+            previous = Some(current);
+        }
+        // Not slowed when idle: dictation resuming must not record through a Secure Input field
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// Pause a listening engine while recording is blocked, and resume it afterwards unless the user paused it too
+async fn apply_recording_hold(state: &AppState, app: &AppHandle, status: &InjectionSafetyStatus) {
+    let Some(engine) = state.voice_engine.lock().await.clone() else {
+        return;
+    };
+    let engine_status = engine.status();
+    let outcome = match (&engine_status.state, &engine_status.pause_reason, status.recording_allowed) {
+        (EngineState::Listening, _, false) => engine.pause(PauseReason::SecureInput(status.holder_app.clone())).await,
+        (EngineState::Paused, Some(PauseReason::SecureInput(_)), true) => engine.resume().await,
+        _ => return,
+    };
+    match outcome {
+        Ok(engine_status) => {
+            let _ = app.emit_all("voice-status", engine_status.state.as_str());
+            let _ = app.emit_all("voice-auto-pause", &engine_status);
+        }
+        Err(e) => tracing::warn!("Secure Input pause transition failed: {}", e),
+    }
+}
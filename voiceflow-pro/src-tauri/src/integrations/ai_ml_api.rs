@@ -7,25 +7,51 @@ use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use uuid::Uuid;
-use reqwest::Client as HttpClient;
 use tokio::time::{timeout, Duration};
 
+use super::alternatives::{self, AlternativePreferences, AlternativeVersion};
+use super::audio_upload::UploadCompressionSettings;
+use super::idempotency::{IdempotencyScope, IdempotencyStore, Outcome};
+use super::mock_provider::{MockProvider, MockSettings};
+use super::pronunciation::PronunciationSettings;
+use super::provider_failover::{self, ProviderFailoverSettings, ProviderService, SYSTEM_VOICE};
+use super::result_cache::{CacheKind, CacheStats};
+use super::transliteration::{self, Transliteration, TransliterationScheme};
+
 // Re-export AI service types for easy access
-pub use ai_ml_core::{AIMLClient, AIMLConfig, AIMLError, AIMLService};
+pub use ai_ml_core::{
+    AIMLChoice, AIMLClient, AIMLConfig, AIMLError, AIMLMessage, AIMLRequest, AIMLResponse, AIMLService, AIMLUsage, ToolCall,
+    ToolChatReply, TranscriptionResponse,
+};
 pub use text_enhancement::{TextEnhancer, EnhancementRequest, EnhancementResult, TextEnhancementService};
-pub use voice_generation::{VoiceGenerator, VoiceRequest, VoiceResult, VoiceGenerationService};
-pub use translation_service::{Translator, TranslationRequest, TranslationResult, TranslationService};
-pub use context_processor::{ContextProcessor, ContextAwareRequest, ContextAwareResult, ContextProcessingService};
+pub use voice_generation::{
+    AudioQuality, BatchSynthesisItem, BatchSynthesisProgress, VoiceGenerator, VoiceModel, VoiceRequest, VoiceResult, VoiceGenerationService,
+};
+pub use translation_service::{
+    FormalityLevel, OutputFormat, TranslationContext, TranslationDomain, TranslationOptions, Translator, TranslationRequest,
+    TranslationResult, TranslationService,
+};
+pub use context_processor::{ContextProcessor, ContextAwareRequest, ContextAwareResult, ContextProcessingService, ConversationMemory, IntentClassification, UserIntent, SentimentPolarity};
+pub use super::text_chunker::{ChunkingConfig, ChunkProgress, LongTextOperation, LongTextResult};
+pub use super::long_audio::{LongAudioError, LongAudioProgress, LongAudioSettings, LongTranscription};
+pub use super::context::{EnhancedContext, SessionContext, UserProfile};
+pub use entity_extraction::{extract_locally, EntityExtraction, EntitySpan, ExtractionSource};
 
 // Core AI ML API module
 mod ai_ml_core;
 mod text_enhancement;
 mod voice_generation;
 mod translation_service;
+mod translation_quality;
 mod context_processor;
+mod entity_extraction;
+
+/// Speech-to-text model used for file transcription
+const TRANSCRIPTION_MODEL: &str = "#g1_whisper-large";
 
-/// AI ML API Gateway - Main entry point for all AI services
-#[derive(Debug)]
+/// AI ML API Gateway - Main entry point for all AI services.
+/// Clones share the same client and caches, so a long job can hold one without keeping the state lock
+#[derive(Debug, Clone)]
 pub struct AIMLAPIGateway {
     client: Arc<Mutex<AIMLClient>>,
     text_enhancer: Arc<Mutex<TextEnhancer>>,
@@ -34,6 +60,13 @@ pub struct AIMLAPIGateway {
     context_processor: Arc<Mutex<ContextProcessor>>,
     config: AIMLGatewayConfig,
     health_status: Arc<Mutex<HealthStatus>>,
+    /// Model ids from the last models request, reused until `capability_ttl_secs` passes
+    capabilities: Arc<Mutex<Option<(std::time::Instant, Vec<String>)>>>,
+    health_spend: Arc<Mutex<HealthCheckSpend>>,
+    /// Styles the user picked among alternatives, so favourites are generated first
+    alternative_preferences: Arc<Mutex<AlternativePreferences>>,
+    /// Results remembered per client idempotency key, so retried requests are not paid for twice
+    idempotency: Arc<IdempotencyStore>,
 }
 
 /// Configuration for AI ML API Gateway
@@ -52,6 +85,60 @@ pub struct AIMLGatewayConfig {
     pub voice_model: String,
     pub translation_model: String,
     pub context_model: String,
+    #[serde(default)]
+    pub health_checks: HealthCheckSettings,
+    #[serde(default)]
+    pub mock: MockSettings,
+    #[serde(default)]
+    pub upload_compression: UploadCompressionSettings,
+    #[serde(default)]
+    pub failover: ProviderFailoverSettings,
+}
+
+/// How a service's health is checked
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthCheckMode {
+    /// Not checked and left out of the overall status
+    Off,
+    /// Free: the API answers the models request and lists the service's model
+    #[default]
+    Synthetic,
+    /// Runs a real request through the service, which is billed
+    Full,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HealthCheckSettings {
+    pub text_enhancement: HealthCheckMode,
+    pub voice_generation: HealthCheckMode,
+    pub translation: HealthCheckMode,
+    pub context_processing: HealthCheckMode,
+    /// How long the model list from a synthetic check is reused
+    pub capability_ttl_secs: u64,
+}
+
+impl Default for HealthCheckSettings {
+    fn default() -> Self {
+        Self {
+            text_enhancement: HealthCheckMode::Synthetic,
+            voice_generation: HealthCheckMode::Synthetic,
+            translation: HealthCheckMode::Synthetic,
+            context_processing: HealthCheckMode::Synthetic,
+            capability_ttl_secs: 600,
+        }
+    }
+}
+
+/// Requests made by health checks, counted apart from the user's own usage
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HealthCheckSpend {
+    /// Free models requests; cached answers are not counted
+    pub synthetic_requests: u64,
+    /// Billed requests per service from full checks
+    pub full_checks: HashMap<String, u64>,
 }
 
 /// Health status monitoring for AI services
@@ -65,6 +152,11 @@ pub struct HealthStatus {
     pub context_processing_healthy: bool,
     pub response_times: HashMap<String, u64>,
     pub error_counts: HashMap<String, u32>,
+    /// Services whose health check is off; their flags keep the last known value
+    #[serde(default)]
+    pub unchecked: Vec<String>,
+    #[serde(default)]
+    pub spend: HealthCheckSpend,
 }
 
 /// Unified API response for all AI operations
@@ -93,25 +185,21 @@ pub struct EnhancedTextRequest {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum TextOperation {
     Enhance,
-    Translate,
+    /// Translate into the request's target language; context and options default when omitted
+    Translate {
+        #[serde(default)]
+        context: TranslationContext,
+        #[serde(default)]
+        options: TranslationOptions,
+    },
     Summarize,
     Analyze,
     Rewrite,
     ToneAdjust(String),
     GrammarCheck,
     StyleImprove,
-}
-
-/// Enhanced context for AI processing
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct EnhancedContext {
-    pub user_intent: Option<String>,
-    pub domain: Option<String>,
-    pub audience: Option<String>,
-    pub purpose: Option<String>,
-    pub constraints: Vec<String>,
-    pub previous_messages: Vec<String>,
-    pub conversation_history: Vec<String>,
+    /// Write the text in Latin script with the given scheme; on-device where the script allows it
+    Transliterate { scheme: TransliterationScheme },
 }
 
 /// Enhanced processing options
@@ -136,7 +224,8 @@ pub struct EnhancedTextResult {
     pub translation: Option<TranslationResult>,
     pub confidence_scores: HashMap<String, f32>,
     pub processing_time_ms: u64,
-    pub alternative_versions: Vec<String>,
+    /// Distinct rewrites of the input when `generate_alternatives` is set
+    pub alternative_versions: Vec<AlternativeVersion>,
     pub suggestions: Vec<String>,
     pub metadata: EnhancedMetadata,
 }
@@ -187,6 +276,18 @@ pub struct VoiceConfiguration {
     pub apply_ssml: bool,
     pub enable_emotion: bool,
     pub quality_level: VoiceQuality,
+    #[serde(default)]
+    pub voice: VoiceSelection,
+}
+
+/// Where the voice comes from
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub enum VoiceSelection {
+    /// A stock voice of `model`, named by `voice_id`
+    #[default]
+    Standard,
+    /// A voice the user registered from a provider's cloning service; replaces `model` and `voice_id`
+    CustomVoice { profile_id: String },
 }
 
 /// Voice output formats
@@ -223,16 +324,18 @@ pub enum VoicePostProcessing {
 impl AIMLAPIGateway {
     /// Create a new AI ML API Gateway
     pub async fn new(config: AIMLGatewayConfig) -> Result<Self, AIMLError> {
-        let http_client = HttpClient::builder()
+        let http_client = crate::network::client_builder()
             .timeout(Duration::from_secs(config.timeout_seconds))
             .build()
             .map_err(AIMLError::HttpClientError)?;
 
-        let client = Arc::new(Mutex::new(AIMLClient::new(
-            config.api_key.clone(),
-            config.base_url.clone(),
-            http_client,
-        )));
+        let client = AIMLClient::new(config.api_key.clone(), config.base_url.clone(), http_client)
+            .with_mock(MockProvider::from_settings(&config.mock))
+            .with_upload_compression(config.upload_compression.clone());
+        if client.is_mock() {
+            log::info!("AI ML API gateway is in mock mode; no requests leave this machine");
+        }
+        let client = Arc::new(Mutex::new(client));
 
         let text_enhancer = Arc::new(Mutex::new(TextEnhancer::new(client.clone(), config.text_model.clone())));
         let voice_generator = Arc::new(Mutex::new(VoiceGenerator::new(client.clone(), config.voice_model.clone())));
@@ -255,7 +358,13 @@ impl AIMLAPIGateway {
                 context_processing_healthy: false,
                 response_times: HashMap::new(),
                 error_counts: HashMap::new(),
+                unchecked: Vec::new(),
+                spend: HealthCheckSpend::default(),
             })),
+            capabilities: Arc::new(Mutex::new(None)),
+            health_spend: Arc::new(Mutex::new(HealthCheckSpend::default())),
+            alternative_preferences: Arc::new(Mutex::new(AlternativePreferences::default())),
+            idempotency: Arc::new(IdempotencyStore::new()),
         })
     }
 
@@ -263,59 +372,13 @@ impl AIMLAPIGateway {
     pub async fn initialize(&self) -> Result<(), AIMLError> {
         let start_time = std::time::Instant::now();
 
-        // Initialize core client
-        {
-            let client = self.client.lock().await;
-            client.initialize().await.map_err(AIMLError::from)?;
-        }
-
-        // Initialize individual services
-        let mut all_healthy = true;
-
-        // Test text enhancement
-        {
-            let enhancer = self.text_enhancer.lock().await;
-            if let Err(e) = enhancer.health_check().await {
-                log::warn!("Text enhancement service health check failed: {:?}", e);
-                all_healthy = false;
-            }
-        }
-
-        // Test voice generation
-        {
-            let generator = self.voice_generator.lock().await;
-            if let Err(e) = generator.health_check().await {
-                log::warn!("Voice generation service health check failed: {:?}", e);
-                all_healthy = false;
-            }
-        }
-
-        // Test translation
-        {
-            let translator = self.translator.lock().await;
-            if let Err(e) = translator.health_check().await {
-                log::warn!("Translation service health check failed: {:?}", e);
-                all_healthy = false;
-            }
-        }
-
-        // Test context processing
-        {
-            let processor = self.context_processor.lock().await;
-            if let Err(e) = processor.health_check().await {
-                log::warn!("Context processing service health check failed: {:?}", e);
-                all_healthy = false;
-            }
-        }
-
-        // Update health status
-        {
-            let mut status = self.health_status.lock().await;
-            status.overall_healthy = all_healthy;
-            status.last_check = std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap_or_default()
-                .as_secs();
+        // Free requests verify reachability and the key; only services set to full checks spend tokens
+        *self.capabilities.lock().await = None;
+        self.available_models().await?;
+        self.client.lock().await.verify_key().await?;
+        let status = self.check_health().await;
+        if !status.overall_healthy {
+            log::warn!("Some AI services failed their health check: {:?}", status.error_counts);
         }
 
         log::info!("AI ML API Gateway initialized in {:?}", start_time.elapsed());
@@ -331,7 +394,6 @@ impl AIMLAPIGateway {
 
         // Collect results and errors
         let mut applied_operations = Vec::new();
-        let mut alternative_versions = Vec::new();
         let mut suggestions = Vec::new();
         let mut confidence_scores = HashMap::new();
         let mut errors = Vec::new();
@@ -345,11 +407,6 @@ impl AIMLAPIGateway {
                 Ok(result) => {
                     applied_operations.push(result.clone());
                     confidence_scores.insert(format!("{:?}", operation), result.confidence);
-                    
-                    // Store alternative versions if requested
-                    if request.options.generate_alternatives {
-                        alternative_versions.push(result.result.clone());
-                    }
                 }
                 Err(e) => {
                     let error_msg = format!("Failed to execute {:?}: {}", operation, e);
@@ -380,7 +437,7 @@ impl AIMLAPIGateway {
             // Use the result from the most important operation (usually enhancement)
             applied_operations
                 .iter()
-                .find(|op| op.operation == TextOperation::Enhance)
+                .find(|op| matches!(op.operation, TextOperation::Enhance))
                 .or(applied_operations.first())
                 .map(|op| op.result.clone())
                 .unwrap_or_else(|| request.text.clone())
@@ -388,6 +445,17 @@ impl AIMLAPIGateway {
             request.text.clone()
         };
 
+        let alternative_versions = if request.options.generate_alternatives && successful_operations > 0 {
+            let styles = self.alternative_preferences.lock().await.styles(request.options.number_of_alternatives);
+            let versions =
+                alternatives::generate_alternatives(self, &self.config.text_model, &request.text, &processed_text, &styles)
+                    .await;
+            alternatives::remember(&request_id, &versions).await;
+            versions
+        } else {
+            Vec::new()
+        };
+
         let result = EnhancedTextResult {
             id: request_id,
             original_text: request.text,
@@ -400,7 +468,7 @@ impl AIMLAPIGateway {
             suggestions,
             metadata: EnhancedMetadata {
                 model_used: self.config.default_model.clone(),
-                tokens_consumed: self.estimate_tokens(&processed_text),
+                tokens_consumed: super::token_budget::count_tokens(&self.config.default_model, &processed_text) as u32,
                 cache_hit: false, // TODO: Implement caching
                 error_count: errors.len() as u32,
                 service_health: self.health_status.lock().await.clone(),
@@ -418,16 +486,204 @@ impl AIMLAPIGateway {
         }
     }
 
-    /// Generate enhanced voice synthesis
+    /// Run `request` at most once per idempotency key: a duplicate gets the first result, or waits for it while it runs.
+    /// Without a key the request simply runs
+    /// `inputs` are what the request asks for, without ids or timestamps; a key only replays a request with the same ones
+    pub async fn once<O, F>(&self, scope: IdempotencyScope, key: Option<&str>, inputs: &impl Serialize, request: F) -> O
+    where
+        O: Outcome,
+        F: std::future::Future<Output = O>,
+    {
+        match key {
+            Some(key) => {
+                let fingerprint = super::idempotency::fingerprint(inputs);
+                self.idempotency.run(scope, key, &fingerprint, request).await
+            }
+            None => request.await,
+        }
+    }
+
+    /// Generate enhanced voice synthesis, moving to the fallback voices while the request's model keeps failing
     pub async fn generate_enhanced_voice(&self, request: EnhancedVoiceRequest) -> Result<VoiceResult, AIMLError> {
         let generator = self.voice_generator.lock().await;
-        generator.generate_voice(request).await
+        let (generator, request) = (&*generator, &request);
+        let primary = &request.voice_config.model;
+        provider_failover::run(ProviderService::Tts, primary, &self.config.failover, move |model| async move {
+            if model == SYSTEM_VOICE {
+                return generator
+                    .generate_system_voice(request.id.clone(), &request.text, &request.language)
+                    .await;
+            }
+            let mut request = request.clone();
+            if &model != primary {
+                request.voice_config.model = model;
+                request.voice_config.voice_id = None;
+            }
+            generator.generate_voice(request).await
+        })
+        .await
+    }
+
+    /// Synthesize a plain voice request
+    pub async fn synthesize(&self, request: VoiceRequest) -> Result<VoiceResult, AIMLError> {
+        self.voice_generator.lock().await.generate_voice(request).await
+    }
+
+    /// Synthesize with the request's voice model, moving to the fallback voice models while it keeps failing
+    pub async fn synthesize_with_failover(&self, request: VoiceRequest) -> Result<VoiceResult, AIMLError> {
+        let generator = self.voice_generator.lock().await;
+        let (generator, request) = (&*generator, &request);
+        let primary = &request.voice_config.model;
+        provider_failover::run(ProviderService::Tts, primary, &self.config.failover, move |model| async move {
+            if model == SYSTEM_VOICE {
+                return generator
+                    .generate_system_voice(request.id.clone(), &request.text, &request.voice_config.language_code)
+                    .await;
+            }
+            let mut request = request.clone();
+            if &model != primary {
+                // A fallback model does not know the chosen voice and speaks with its own default
+                request.voice_config.model = model;
+                request.voice_config.voice_id = None;
+            }
+            generator.generate_voice(request).await
+        })
+        .await
+    }
+
+    /// Voices the TTS model offers
+    pub async fn list_voices(&self) -> Result<Vec<VoiceModel>, AIMLError> {
+        self.voice_generator.lock().await.get_available_voices().await
+    }
+
+    /// Several takes of the same text with small rate and pitch differences
+    pub async fn generate_voice_variations(&self, request: VoiceRequest, variations: u8) -> Result<Vec<VoiceResult>, AIMLError> {
+        self.voice_generator.lock().await.generate_variations(request, variations).await
+    }
+
+    /// Synthesize several requests, keeping a separate outcome for each
+    pub async fn batch_generate_voice<F: FnMut(usize, &Result<VoiceResult, AIMLError>)>(
+        &self,
+        requests: Vec<VoiceRequest>,
+        on_item: F,
+    ) -> Vec<Result<VoiceResult, AIMLError>> {
+        self.voice_generator.lock().await.batch_synthesize(requests, on_item).await
     }
 
     /// Translate text with AI enhancement
-    pub async fn translate_with_enhancement(&self, text: String, from: Option<String>, to: String) -> Result<TranslationResult, AIMLError> {
+    pub async fn translate_with_enhancement(
+        &self,
+        text: String,
+        from: Option<String>,
+        to: String,
+        context: TranslationContext,
+        options: TranslationOptions,
+    ) -> Result<TranslationResult, AIMLError> {
+        self.translate_with_failover(TranslationRequest {
+            id: Uuid::new_v4().to_string(),
+            text,
+            source_language: from,
+            target_language: to,
+            context,
+            options,
+        })
+        .await
+    }
+
+    /// Translate on the translation model, moving to the fallback text models while it keeps failing
+    async fn translate_with_failover(&self, request: TranslationRequest) -> Result<TranslationResult, AIMLError> {
         let translator = self.translator.lock().await;
-        translator.translate_with_enhancement(text, from, to).await
+        let (translator, request) = (&*translator, &request);
+        provider_failover::run(
+            ProviderService::Llm,
+            &self.config.translation_model,
+            &self.config.failover,
+            move |model| async move { translator.translate_with_model(request.clone(), &model).await },
+        )
+        .await
+    }
+
+    /// One assistant turn on the default model with the given tools available
+    pub async fn chat_with_tools(&self, messages: Vec<Value>, tools: Vec<Value>) -> Result<ToolChatReply, AIMLError> {
+        let client = self.client.lock().await.clone();
+        client
+            .chat_with_tools(self.config.default_model.clone(), messages, tools, Some(1000))
+            .await
+    }
+
+    /// Transcribe recorded audio, e.g. a file dropped into a watch folder
+    pub async fn transcribe_audio(
+        &self,
+        audio: Vec<u8>,
+        file_name: String,
+        language: Option<String>,
+    ) -> Result<TranscriptionResponse, AIMLError> {
+        self.transcribe_audio_with_model(audio, file_name, TRANSCRIPTION_MODEL, language)
+            .await
+    }
+
+    /// Transcribe a recording of any length from disk, decoded in overlapping windows that are sent in parallel
+    pub async fn transcribe_long_audio<F>(
+        &self,
+        request_id: String,
+        path: &std::path::Path,
+        language: Option<String>,
+        settings: &LongAudioSettings,
+        completed: std::collections::BTreeMap<usize, String>,
+        on_window: F,
+    ) -> Result<LongTranscription, LongAudioError>
+    where
+        F: FnMut(LongAudioProgress, &str),
+    {
+        let transcribe_window = |audio: Vec<u8>, file_name: String, language: Option<String>| async move {
+            self.transcribe_audio(audio, file_name, language)
+                .await
+                .map(|response| response.text)
+                .map_err(crate::errors::AppError::from)
+        };
+        super::long_audio::transcribe(transcribe_window, request_id, path, language, settings, completed, on_window).await
+    }
+
+    /// Transcribe audio with a specific speech-to-text model
+    pub async fn transcribe_audio_with_model(
+        &self,
+        audio: Vec<u8>,
+        file_name: String,
+        model: &str,
+        language: Option<String>,
+    ) -> Result<TranscriptionResponse, AIMLError> {
+        let client = self.client.lock().await.clone();
+        client
+            .transcribe_audio(audio, file_name, model.to_string(), language)
+            .await
+    }
+
+    /// Run one prompt on a specific model, bypassing the enhancement cache
+    pub async fn complete_with_model(
+        &self,
+        model: String,
+        system_prompt: String,
+        text: String,
+        temperature: Option<f32>,
+    ) -> Result<(String, Option<AIMLUsage>), AIMLError> {
+        // A cloned client lets comparison runs go out in parallel instead of queueing on the lock
+        let client = self.client.lock().await.clone();
+        client.complete(model, system_prompt, text, temperature, Some(2000)).await
+    }
+
+    /// Run one prompt, moving to the fallback text models while `model` keeps failing
+    pub async fn complete_with_failover(
+        &self,
+        model: String,
+        system_prompt: String,
+        text: String,
+        temperature: Option<f32>,
+    ) -> Result<(String, Option<AIMLUsage>), AIMLError> {
+        let (system_prompt, text) = (&system_prompt, &text);
+        provider_failover::run(ProviderService::Llm, &model, &self.config.failover, move |model| {
+            self.complete_with_model(model, system_prompt.clone(), text.clone(), temperature)
+        })
+        .await
     }
 
     /// Perform context-aware processing
@@ -436,6 +692,193 @@ impl AIMLAPIGateway {
         processor.process_with_context(request).await
     }
 
+    /// Process a long document chunk by chunk, carrying overlapping context between chunks
+    pub async fn process_long_text<F>(
+        &self,
+        request_id: String,
+        text: String,
+        operation: LongTextOperation,
+        chunking: ChunkingConfig,
+        on_progress: F,
+    ) -> Result<LongTextResult, AIMLError>
+    where
+        F: Fn(ChunkProgress),
+    {
+        self.process_long_text_from(request_id, text, operation, chunking, Vec::new(), |progress, _| {
+            on_progress(progress)
+        })
+        .await
+    }
+
+    /// Like `process_long_text`, but the first chunks are taken from `completed`, the outputs of an earlier
+    /// interrupted run. `on_chunk` sees each chunk's output as it is produced, so a caller can checkpoint it
+    pub async fn process_long_text_from<F>(
+        &self,
+        request_id: String,
+        text: String,
+        operation: LongTextOperation,
+        mut chunking: ChunkingConfig,
+        completed: Vec<String>,
+        mut on_chunk: F,
+    ) -> Result<LongTextResult, AIMLError>
+    where
+        F: FnMut(ChunkProgress, &str),
+    {
+        let start_time = std::time::Instant::now();
+        // A translation's source language tells the chunker which abbreviations don't end sentences
+        if let (None, LongTextOperation::Translate { source_language, .. }) = (&chunking.language, &operation) {
+            chunking.language = source_language.clone();
+        }
+        let chunks = super::text_chunker::split_into_chunks(&text, &chunking);
+        let total_chunks = chunks.len();
+
+        // A checkpoint from a differently chunked document cannot be trusted; start over
+        let completed = if completed.len() <= total_chunks {
+            completed
+        } else {
+            log::warn!("Ignoring a checkpoint of {} chunks for a document of {}", completed.len(), total_chunks);
+            Vec::new()
+        };
+        let resumed = completed.len();
+        let mut processed = completed;
+        processed.reserve(total_chunks - resumed);
+        let mut failed_chunks = Vec::new();
+
+        for chunk in &chunks[resumed..] {
+            let outcome = if chunk.content.is_empty() {
+                Ok(String::new())
+            } else {
+                self.process_chunk(chunk, &operation).await
+            };
+
+            let succeeded = outcome.is_ok();
+            match outcome {
+                Ok(body) => processed.push(body),
+                Err(e) => {
+                    // Keep the original text for this chunk so the document stays complete
+                    log::warn!("Chunk {} of {} failed: {}", chunk.index + 1, total_chunks, e);
+                    failed_chunks.push(chunk.index);
+                    processed.push(chunk.content.clone());
+                }
+            }
+
+            let progress = ChunkProgress {
+                request_id: request_id.clone(),
+                chunk_index: chunk.index,
+                total_chunks,
+                succeeded,
+                progress: (chunk.index + 1) as f32 / total_chunks.max(1) as f32 * 100.0,
+            };
+            on_chunk(progress, processed.last().map(String::as_str).unwrap_or_default());
+        }
+
+        if total_chunks > 0 && failed_chunks.len() == total_chunks {
+            return Err(AIMLError::ServiceUnavailable(format!(
+                "All {} chunks failed to process",
+                total_chunks
+            )));
+        }
+
+        Ok(LongTextResult {
+            id: request_id,
+            processed_text: super::text_chunker::reassemble_chunks(&chunks, &processed),
+            original_text: text,
+            total_chunks,
+            failed_chunks,
+            processing_time_ms: start_time.elapsed().as_millis() as u64,
+        })
+    }
+
+    /// Run a single chunk through the requested operation
+    async fn process_chunk(&self, chunk: &super::text_chunker::TextChunk, operation: &LongTextOperation) -> Result<String, AIMLError> {
+        match operation {
+            LongTextOperation::Enhance { tone } => {
+                let mut constraints = vec![
+                    "Return only the enhanced version of the current passage".to_string(),
+                    "Preserve paragraph breaks, lists, and other formatting".to_string(),
+                ];
+                if let Some(preceding) = &chunk.preceding_context {
+                    constraints.push(format!(
+                        "This passage continues from the following text, provided for continuity only and not to be repeated: \"{}\"",
+                        preceding
+                    ));
+                }
+
+                let enhancer = self.text_enhancer.lock().await;
+                let result = enhancer.enhance_text(EnhancementRequest {
+                    id: Uuid::new_v4().to_string(),
+                    text: chunk.content.clone(),
+                    context: text_enhancement::EnhancementContext {
+                        domain: "general".to_string(),
+                        audience: "general".to_string(),
+                        purpose: "long document enhancement".to_string(),
+                        format: "text".to_string(),
+                        constraints,
+                        examples: vec![],
+                    },
+                    tone: tone.clone(),
+                    options: text_enhancement::EnhancementOptions {
+                        improve_clarity: true,
+                        fix_grammar: true,
+                        enhance_style: true,
+                        adjust_tone: true,
+                        remove_redundancy: false,
+                        improve_readability: true,
+                        preserve_meaning: true,
+                        maintain_length: true,
+                    },
+                }).await?;
+                Ok(result.enhanced_text)
+            }
+            LongTextOperation::Translate { source_language, target_language } => {
+                let result = self.translate_with_failover(TranslationRequest {
+                    id: Uuid::new_v4().to_string(),
+                    text: chunk.content.clone(),
+                    source_language: source_language.clone(),
+                    target_language: target_language.clone(),
+                    context: translation_service::TranslationContext {
+                        domain: translation_service::TranslationDomain::General,
+                        audience: "general".to_string(),
+                        purpose: "long document translation".to_string(),
+                        formality_level: translation_service::FormalityLevel::Neutral,
+                        cultural_considerations: true,
+                        technical_terminology: false,
+                        preceding_context: chunk.preceding_context.clone(),
+                    },
+                    options: translation_service::TranslationOptions {
+                        preserve_formatting: true,
+                        maintain_style: true,
+                        include_comments: false,
+                        preserve_code_blocks: true,
+                        cultural_adaptation: true,
+                        technical_accuracy: true,
+                        creative_freedom: 0.2,
+                        back_translation_check: false,
+                        glossary: std::collections::BTreeMap::new(),
+                    },
+                }).await?;
+                Ok(result.translated_text)
+            }
+        }
+    }
+
+    /// Entities in `text` for highlighting, from the context model
+    pub async fn extract_entities(&self, text: String) -> EntityExtraction {
+        let processor = self.context_processor.lock().await;
+        processor.extract_entities(&text).await
+    }
+
+    /// `text` in Latin script, from the scheme's tables or, for kanji and hanzi, the text model
+    pub async fn transliterate(&self, text: String, scheme: TransliterationScheme) -> Result<Transliteration, AIMLError> {
+        transliteration::transliterate(Some((self, &self.config.text_model)), &text, scheme).await
+    }
+
+    /// Predict the primary intent of a single utterance
+    pub async fn predict_intent(&self, text: String, context: EnhancedContext) -> Result<UserIntent, AIMLError> {
+        let processor = self.context_processor.lock().await;
+        processor.predict_intent(text, &context).await
+    }
+
     /// Execute individual text operations
     async fn execute_operation(&self, operation: TextOperation, request: &EnhancedTextRequest) -> Result<TextOperationResult, AIMLError> {
         let start_time = std::time::Instant::now();
@@ -451,7 +894,15 @@ impl AIMLAPIGateway {
                     options: request.options.clone().into(),
                 };
                 
-                let enhancement = enhancer.enhance_text(enhancement_req).await?;
+                let enhancement_req = &enhancement_req;
+                let enhancer = &*enhancer;
+                let enhancement = provider_failover::run(
+                    ProviderService::Llm,
+                    &self.config.text_model,
+                    &self.config.failover,
+                    move |model| async move { enhancer.enhance_text_with_model(enhancement_req.clone(), &model).await },
+                )
+                .await?;
                 
                 Ok(TextOperationResult {
                     operation: TextOperation::Enhance,
@@ -463,21 +914,21 @@ impl AIMLAPIGateway {
                 })
             }
             
-            TextOperation::Translate => {
+            TextOperation::Translate { ref context, ref options } => {
                 if let Some(target_lang) = &request.target_language {
-                    let translator = self.translator.lock().await;
                     let translation_req = TranslationRequest {
                         id: Uuid::new_v4().to_string(),
                         text: request.text.clone(),
                         source_language: request.source_language.clone(),
                         target_language: target_lang.clone(),
-                        preserve_formatting: request.options.preserve_formatting,
+                        context: context.clone(),
+                        options: options.clone(),
                     };
                     
-                    let translation = translator.translate(translation_req).await?;
+                    let translation = self.translate_with_failover(translation_req).await?;
                     
                     Ok(TextOperationResult {
-                        operation: TextOperation::Translate,
+                        operation: operation.clone(),
                         success: true,
                         result: translation.translated_text,
                         confidence: translation.confidence,
@@ -590,6 +1041,21 @@ impl AIMLAPIGateway {
                         errors: vec![],
                     })
             }
+
+            TextOperation::Transliterate { scheme } => {
+                let transliteration = self.transliterate(request.text.clone(), scheme).await?;
+                Ok(TextOperationResult {
+                    operation: operation.clone(),
+                    success: true,
+                    result: transliteration.text,
+                    confidence: match transliteration.source {
+                        transliteration::TransliterationSource::Local => 0.95,
+                        transliteration::TransliterationSource::Model => 0.8,
+                    },
+                    processing_time_ms: start_time.elapsed().as_millis() as u64,
+                    errors: vec![],
+                })
+            }
         }
     }
 
@@ -603,6 +1069,7 @@ impl AIMLAPIGateway {
                 requires_understanding: true,
                 include_sentiment: true,
                 include_intent: true,
+                memory_retention: false,
             })
         } else {
             None
@@ -622,44 +1089,47 @@ impl AIMLAPIGateway {
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap_or_default()
             .as_secs();
+        status.unchecked.clear();
 
-        // Check each service
-        let services = vec![
-            ("text_enhancement", &self.text_enhancer),
-            ("voice_generation", &self.voice_generator),
-            ("translation", &self.translator),
-            ("context_processing", &self.context_processor),
+        let models = self.available_models().await.map_err(|e| {
+            log::warn!("AI ML API model list unavailable: {}", e);
+        });
+        let checks = &self.config.health_checks;
+        let services = [
+            ("text_enhancement", checks.text_enhancement, &self.config.text_model),
+            ("voice_generation", checks.voice_generation, &self.config.voice_model),
+            ("translation", checks.translation, &self.config.translation_model),
+            ("context_processing", checks.context_processing, &self.config.context_model),
         ];
 
-        for (service_name, service) in services {
+        for (service_name, mode, model) in services {
             let health_start = std::time::Instant::now();
-            
-            let is_healthy = match service_name {
-                "text_enhancement" => {
-                    let enhancer = service.lock().await;
-                    enhancer.health_check().await.is_ok()
-                }
-                "voice_generation" => {
-                    let generator = service.lock().await;
-                    generator.health_check().await.is_ok()
-                }
-                "translation" => {
-                    let translator = service.lock().await;
-                    translator.health_check().await.is_ok()
+
+            let is_healthy = match mode {
+                HealthCheckMode::Off => {
+                    status.unchecked.push(service_name.to_string());
+                    continue;
                 }
-                "context_processing" => {
-                    let processor = service.lock().await;
-                    processor.health_check().await.is_ok()
+                HealthCheckMode::Synthetic => models.as_ref().map_or(false, |models| lists_model(models, model)),
+                HealthCheckMode::Full => {
+                    *self.health_spend.lock().await.full_checks.entry(service_name.to_string()).or_insert(0) += 1;
+                    self.full_check(service_name).await
                 }
-                _ => false,
             };
 
             let response_time = health_start.elapsed().as_millis() as u64;
             status.response_times.insert(service_name.to_string(), response_time);
             
             match service_name {
-                "text_enhancement" => status.text_enhancement_healthy = is_healthy,
-                "voice_generation" => status.voice_generation_healthy = is_healthy,
+                // Health results steer failover, so traffic returns to a primary that passes again
+                "text_enhancement" => {
+                    provider_failover::record_health(ProviderService::Llm, model, is_healthy, &self.config.failover);
+                    status.text_enhancement_healthy = is_healthy;
+                }
+                "voice_generation" => {
+                    provider_failover::record_health(ProviderService::Tts, model, is_healthy, &self.config.failover);
+                    status.voice_generation_healthy = is_healthy;
+                }
                 "translation" => status.translation_healthy = is_healthy,
                 "context_processing" => status.context_processing_healthy = is_healthy,
                 _ => {}
@@ -670,19 +1140,143 @@ impl AIMLAPIGateway {
             }
         }
 
-        status.overall_healthy = status.text_enhancement_healthy 
-            && status.voice_generation_healthy 
-            && status.translation_healthy 
-            && status.context_processing_healthy;
+        let checked = |name: &str| !status.unchecked.iter().any(|unchecked| unchecked == name);
+        status.overall_healthy = (!checked("text_enhancement") || status.text_enhancement_healthy)
+            && (!checked("voice_generation") || status.voice_generation_healthy)
+            && (!checked("translation") || status.translation_healthy)
+            && (!checked("context_processing") || status.context_processing_healthy);
+        status.spend = self.health_spend.lock().await.clone();
 
         *self.health_status.lock().await = status.clone();
         status
     }
 
-    /// Estimate token count for text (rough approximation)
-    fn estimate_tokens(&self, text: &str) -> u32 {
-        // Rough estimation: ~4 characters per token
-        (text.len() / 4) as u32
+    /// Model ids the key can use, from cache while fresh so repeated checks cost nothing
+    async fn available_models(&self) -> Result<Vec<String>, AIMLError> {
+        let mut capabilities = self.capabilities.lock().await;
+        let ttl = Duration::from_secs(self.config.health_checks.capability_ttl_secs);
+        if let Some((fetched, models)) = capabilities.as_ref() {
+            if fetched.elapsed() < ttl {
+                return Ok(models.clone());
+            }
+        }
+        let models = self.client.lock().await.list_model_ids().await;
+        self.health_spend.lock().await.synthetic_requests += 1;
+        let models = models?;
+        *capabilities = Some((std::time::Instant::now(), models.clone()));
+        Ok(models)
+    }
+
+    /// Run a real request through one service
+    async fn full_check(&self, service_name: &str) -> bool {
+        let result = match service_name {
+            "text_enhancement" => self.text_enhancer.lock().await.health_check().await,
+            "voice_generation" => self.voice_generator.lock().await.health_check().await,
+            "translation" => self.translator.lock().await.health_check().await,
+            "context_processing" => self.context_processor.lock().await.health_check().await,
+            _ => Ok(false),
+        };
+        match result {
+            Ok(healthy) => healthy,
+            Err(e) => {
+                log::warn!("{} health check failed: {}", service_name, e);
+                false
+            }
+        }
+    }
+
+    /// Total cached results across all services
+    pub async fn cached_entries(&self) -> usize {
+        self.text_enhancer.lock().await.cached_entries().await
+            + self.voice_generator.lock().await.cached_entries().await
+            + self.translator.lock().await.cached_entries().await
+            + self.context_processor.lock().await.cached_entries().await
+    }
+
+    /// Clear every service cache, returning how many entries were removed
+    pub async fn clear_caches(&self) -> usize {
+        self.text_enhancer.lock().await.clear_cache().await
+            + self.voice_generator.lock().await.clear_cache().await
+            + self.translator.lock().await.clear_cache().await
+            + self.context_processor.lock().await.clear_cache().await
+    }
+
+    /// Clear one result cache from memory and disk, or all of them with `None`
+    pub async fn clear_cache(&self, kind: Option<CacheKind>) -> usize {
+        match kind {
+            Some(CacheKind::Enhancement) => self.text_enhancer.lock().await.clear_cache().await,
+            Some(CacheKind::Translation) => self.translator.lock().await.clear_cache().await,
+            Some(CacheKind::Synthesis) => self.voice_generator.lock().await.clear_cache().await,
+            None => self.clear_caches().await,
+        }
+    }
+
+    pub async fn cache_stats(&self) -> Vec<CacheStats> {
+        vec![
+            self.text_enhancer.lock().await.cache_stats().await,
+            self.translator.lock().await.cache_stats().await,
+            self.voice_generator.lock().await.cache_stats().await,
+        ]
+    }
+
+    /// Load results saved by earlier runs into the result caches, returning how many were loaded
+    pub async fn warm_caches(&self) -> usize {
+        self.text_enhancer.lock().await.warm_cache().await
+            + self.translator.lock().await.warm_cache().await
+            + self.voice_generator.lock().await.warm_cache().await
+    }
+
+    /// Rebuild the HTTP client so new proxy and CA settings take effect without restarting the gateway
+    pub async fn reload_network(&self) -> Result<(), AIMLError> {
+        let http_client = crate::network::client_builder()
+            .timeout(Duration::from_secs(self.config.timeout_seconds))
+            .build()
+            .map_err(AIMLError::HttpClientError)?;
+        self.client.lock().await.set_http_client(http_client);
+        Ok(())
+    }
+
+    /// Replace the TTS pronunciation lexicons
+    pub async fn set_pronunciations(&self, pronunciations: PronunciationSettings) {
+        self.voice_generator.lock().await.set_pronunciations(pronunciations).await;
+    }
+
+    /// Replace the terms text enhancement must keep verbatim
+    pub async fn set_preserved_terms(&self, terms: Vec<String>) {
+        self.text_enhancer.lock().await.set_preserved_terms(terms).await;
+    }
+
+    /// Replace the learned alternative style preferences
+    pub async fn set_alternative_preferences(&self, preferences: AlternativePreferences) {
+        *self.alternative_preferences.lock().await = preferences;
+    }
+
+    /// Replace the domain pack instructions added to text enhancement
+    pub async fn set_domain_instructions(&self, instructions: Option<String>) {
+        self.text_enhancer.lock().await.set_domain_instructions(instructions).await;
+    }
+
+    /// Shrink every service cache to at most `limit` entries, or restore normal sizes with `None`
+    pub async fn limit_caches(&self, limit: Option<usize>) {
+        self.text_enhancer.lock().await.limit_cache(limit).await;
+        self.voice_generator.lock().await.limit_cache(limit).await;
+        self.translator.lock().await.limit_cache(limit).await;
+        self.context_processor.lock().await.limit_cache(limit).await;
+    }
+
+    /// Snapshot of the context processor's conversation memory
+    pub async fn conversation_memory(&self) -> ConversationMemory {
+        self.context_processor.lock().await.conversation_memory().await
+    }
+
+    /// Replace the context processor's conversation memory
+    pub async fn restore_conversation_memory(&self, memory: ConversationMemory) {
+        self.context_processor.lock().await.restore_conversation_memory(memory).await
+    }
+
+    /// Forget all conversation memory
+    pub async fn clear_conversation_memory(&self) {
+        self.context_processor.lock().await.clear_conversation_memory().await
     }
 
     /// Get current configuration
@@ -712,5 +1306,15 @@ pub fn create_default_config() -> AIMLGatewayConfig {
         voice_model: "gpt-4o-mini-tts".to_string(),
         translation_model: "claude-3-5-haiku".to_string(),
         context_model: "gpt-5-pro".to_string(),
+        health_checks: HealthCheckSettings::default(),
+        mock: MockSettings::default(),
+        upload_compression: UploadCompressionSettings::default(),
+        failover: ProviderFailoverSettings::default(),
     }
 }
+
+/// Whether a models listing covers `model`; ids may carry a provider prefix such as "openai/"
+fn lists_model(models: &[String], model: &str) -> bool {
+    // An empty listing proves the API is reachable but says nothing about individual models
+    models.is_empty() || models.iter().any(|id| id == model || id.rsplit('/').next() == Some(model))
+}
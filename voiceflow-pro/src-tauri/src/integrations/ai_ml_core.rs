@@ -4,9 +4,17 @@
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::collections::HashMap;
+use std::time::Instant;
 use reqwest::Client as HttpClient;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
 use tokio::time::{timeout, Duration};
 
+use crate::audit::{self, AuditService};
+use crate::session_recording::{self, AiPayload};
+use crate::integrations::audio_upload::{self, UploadCompressionSettings};
+use crate::integrations::mock_provider::MockProvider;
+use crate::integrations::traffic::{self, TrafficService};
+
 /// Error types for AI ML API operations
 #[derive(Debug, thiserror::Error)]
 pub enum AIMLError {
@@ -20,7 +28,7 @@ pub enum AIMLError {
     AuthError(String),
     
     #[error("Rate limit exceeded")]
-    RateLimitExceeded,
+    RateLimitExceeded { retry_after_secs: Option<u64> },
     
     #[error("Invalid model: {0}")]
     InvalidModel(String),
@@ -42,7 +50,7 @@ pub enum AIMLError {
 }
 
 /// Core AI ML API client
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct AIMLClient {
     api_key: String,
     base_url: String,
@@ -50,6 +58,10 @@ pub struct AIMLClient {
     request_count: u64,
     rate_limit_remaining: Option<u32>,
     rate_limit_reset: Option<u64>,
+    /// Answers every request locally instead of calling the API
+    mock: Option<MockProvider>,
+    /// How PCM audio is compressed before transcription uploads
+    upload_compression: UploadCompressionSettings,
 }
 
 /// API request structure
@@ -67,7 +79,7 @@ pub struct AIMLRequest {
 }
 
 /// Chat message format
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AIMLMessage {
     pub role: String, // "system", "user", "assistant"
     pub content: String,
@@ -93,7 +105,7 @@ pub struct AIMLChoice {
 }
 
 /// Usage statistics
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AIMLUsage {
     pub prompt_tokens: u32,
     pub completion_tokens: u32,
@@ -124,31 +136,114 @@ impl AIMLClient {
             request_count: 0,
             rate_limit_remaining: None,
             rate_limit_reset: None,
+            mock: None,
+            upload_compression: UploadCompressionSettings::default(),
         }
     }
 
+    /// Route requests to `mock` instead of the network
+    pub fn with_mock(mut self, mock: Option<MockProvider>) -> Self {
+        self.mock = mock;
+        self
+    }
+
+    pub fn with_upload_compression(mut self, upload_compression: UploadCompressionSettings) -> Self {
+        self.upload_compression = upload_compression;
+        self
+    }
+
+    pub fn is_mock(&self) -> bool {
+        self.mock.is_some()
+    }
+
+    pub fn set_http_client(&mut self, http_client: HttpClient) {
+        self.http_client = http_client;
+    }
+
     /// Initialize the client
     pub async fn initialize(&self) -> Result<(), AIMLError> {
-        // Test API connectivity
-        let test_request = self.create_chat_request(
-            "gpt-4o".to_string(),
-            vec![AIMLMessage {
-                role: "user".to_string(),
-                content: "Hello".to_string(),
-            }],
-            Some(10),
-        )?;
-
-        let response = self.send_request(test_request).await?;
-        
-        if response.choices.is_empty() {
-            return Err(AIMLError::ServiceUnavailable("No choices in response".to_string()));
-        }
+        // The models list is public, so the key is verified separately; neither request spends tokens
+        self.list_model_ids().await?;
+        self.verify_key().await?;
 
         log::info!("AI ML API client initialized successfully");
         Ok(())
     }
 
+    /// Ids of the models the API offers; the models endpoint is not billed and does not check the key
+    pub async fn list_model_ids(&self) -> Result<Vec<String>, AIMLError> {
+        if let Some(mock) = &self.mock {
+            return Ok(mock.list_model_ids().await);
+        }
+        let url = format!("{}/models", self.base_url);
+        let response = timeout(Duration::from_secs(10), async {
+            self.http_client
+                .get(&url)
+                .header("Authorization", format!("Bearer {}", self.api_key))
+                .send()
+                .await
+        }).await.map_err(|_| AIMLError::Timeout("Model list request timeout".to_string()))?
+        .map_err(AIMLError::HttpClientError)?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let retry_after_secs = retry_after(&response);
+            let error_text = response.text().await.unwrap_or_default();
+            traffic::record(TrafficService::Models, 0, error_text.len(), None);
+            return match status.as_u16() {
+                401 => Err(AIMLError::AuthError("Invalid API key".to_string())),
+                429 => Err(AIMLError::RateLimitExceeded { retry_after_secs }),
+                503 => Err(AIMLError::ServiceUnavailable("Service temporarily unavailable".to_string())),
+                _ => Err(AIMLError::ApiError {
+                    status: status.as_u16(),
+                    message: error_text,
+                }),
+            };
+        }
+
+        let bytes = response.bytes().await.map_err(AIMLError::HttpClientError)?;
+        traffic::record(TrafficService::Models, 0, bytes.len(), None);
+        let reply: Value = serde_json::from_slice(&bytes).map_err(AIMLError::JsonError)?;
+        // OpenAI-style `{ "data": [...] }`, or a bare array
+        let models = reply["data"].as_array().or_else(|| reply.as_array()).cloned().unwrap_or_default();
+        Ok(models
+            .iter()
+            .filter_map(|model| model["id"].as_str().map(str::to_string))
+            .collect())
+    }
+
+    /// Check the key against an authenticated route without spending tokens: a completion with no messages
+    /// is refused as invalid once the key is accepted, and as unauthorized otherwise
+    pub async fn verify_key(&self) -> Result<(), AIMLError> {
+        if self.mock.is_some() {
+            return Ok(());
+        }
+        let url = format!("{}/chat/completions", self.base_url);
+        let body = json!({ "model": "gpt-4o-mini", "messages": [], "max_tokens": 1 });
+        let response = timeout(Duration::from_secs(10), async {
+            self.http_client
+                .post(&url)
+                .header("Authorization", format!("Bearer {}", self.api_key))
+                .header("Content-Type", "application/json")
+                .json(&body)
+                .send()
+                .await
+        }).await.map_err(|_| AIMLError::Timeout("Key check timeout".to_string()))?
+        .map_err(AIMLError::HttpClientError)?;
+
+        let status = response.status();
+        let retry_after_secs = retry_after(&response);
+        let error_text = response.text().await.unwrap_or_default();
+        traffic::record(TrafficService::Models, 0, error_text.len(), None);
+        match status.as_u16() {
+            200..=299 | 400 | 404 | 422 => Ok(()),
+            401 | 403 => Err(AIMLError::AuthError("Invalid API key".to_string())),
+            429 => Err(AIMLError::RateLimitExceeded { retry_after_secs }),
+            503 => Err(AIMLError::ServiceUnavailable("Service temporarily unavailable".to_string())),
+            status => Err(AIMLError::ApiError { status, message: error_text }),
+        }
+    }
+
     /// Send a chat completion request
     pub async fn chat_completion(&self, request: AIMLRequest) -> Result<AIMLResponse, AIMLError> {
         self.send_request(request).await
@@ -187,6 +282,140 @@ impl AIMLClient {
         }
     }
 
+    /// Run a system prompt over `text` on the given model, returning the reply and token usage
+    pub async fn complete(
+        &self,
+        model: String,
+        system_prompt: String,
+        text: String,
+        temperature: Option<f32>,
+        max_tokens: Option<u32>,
+    ) -> Result<(String, Option<AIMLUsage>), AIMLError> {
+        let messages = vec![
+            AIMLMessage {
+                role: "system".to_string(),
+                content: system_prompt,
+            },
+            AIMLMessage {
+                role: "user".to_string(),
+                content: text,
+            },
+        ];
+        let mut request = self.create_chat_request(model, messages, max_tokens)?;
+        if temperature.is_some() {
+            request.temperature = temperature;
+        }
+
+        let response = self.send_request(request).await?;
+        let content = response
+            .choices
+            .first()
+            .map(|choice| choice.message.content.clone())
+            .ok_or_else(|| AIMLError::ServiceUnavailable("No choices in response".to_string()))?;
+        Ok((content, response.usage))
+    }
+
+    /// One chat turn in which the model may call the given tools instead of answering.
+    /// `messages` and `tools` use the OpenAI chat format, so tool results can be sent back as "tool" messages.
+    pub async fn chat_with_tools(
+        &self,
+        model: String,
+        messages: Vec<Value>,
+        tools: Vec<Value>,
+        max_tokens: Option<u32>,
+    ) -> Result<ToolChatReply, AIMLError> {
+        if model.trim().is_empty() {
+            return Err(AIMLError::InvalidModel("Model cannot be empty".to_string()));
+        }
+        let mut body = json!({
+            "model": model,
+            "messages": messages,
+            "max_tokens": max_tokens,
+            "temperature": 0.3,
+            "stream": false,
+        });
+        if !tools.is_empty() {
+            body["tools"] = Value::Array(tools);
+            body["tool_choice"] = json!("auto");
+        }
+
+        let prompt = messages
+            .iter()
+            .filter_map(|message| message["content"].as_str())
+            .collect::<Vec<_>>()
+            .join("\n");
+        if let Some(replayed) = session_recording::replay_ai(AuditService::ToolChat, &model, &prompt) {
+            return match replayed? {
+                AiPayload::ToolChat { message, usage } => Ok(tool_chat_reply(message, usage)),
+                _ => Err(replay_mismatch()),
+            };
+        }
+
+        let started = Instant::now();
+        let result = self.post_tool_chat(&body).await;
+        audit::record(
+            AuditService::ToolChat,
+            &model,
+            &prompt,
+            started,
+            result.as_ref().map(|reply| reply.usage.as_ref()),
+        );
+        if session_recording::is_recording() {
+            let payload = result.as_ref().map(|reply| AiPayload::ToolChat {
+                message: reply.message.clone(),
+                usage: reply.usage.clone(),
+            });
+            session_recording::record_ai(AuditService::ToolChat, &model, &prompt, payload);
+        }
+        result
+    }
+
+    async fn post_tool_chat(&self, body: &Value) -> Result<ToolChatReply, AIMLError> {
+        if let Some(mock) = &self.mock {
+            let messages = body["messages"].as_array().cloned().unwrap_or_default();
+            let tools = body["tools"].as_array().cloned().unwrap_or_default();
+            return Ok(mock.tool_chat(&messages, &tools).await);
+        }
+        let url = format!("{}/chat/completions", self.base_url);
+        let payload = serde_json::to_vec(body).map_err(AIMLError::JsonError)?;
+        let sent = payload.len();
+        let response = timeout(Duration::from_secs(30), async {
+            self.http_client
+                .post(&url)
+                .header("Authorization", format!("Bearer {}", self.api_key))
+                .header("Content-Type", "application/json")
+                .body(payload)
+                .send()
+                .await
+        }).await.map_err(|_| AIMLError::Timeout("Request timeout".to_string()))?
+        .map_err(AIMLError::HttpClientError)?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let retry_after_secs = retry_after(&response);
+            let error_text = response.text().await.unwrap_or_default();
+            traffic::record(TrafficService::ToolChat, sent, error_text.len(), None);
+            return match status.as_u16() {
+                401 => Err(AIMLError::AuthError("Invalid API key".to_string())),
+                429 => Err(AIMLError::RateLimitExceeded { retry_after_secs }),
+                503 => Err(AIMLError::ServiceUnavailable("Service temporarily unavailable".to_string())),
+                _ => Err(AIMLError::ApiError {
+                    status: status.as_u16(),
+                    message: error_text,
+                }),
+            };
+        }
+
+        let bytes = response.bytes().await.map_err(AIMLError::HttpClientError)?;
+        traffic::record(TrafficService::ToolChat, sent, bytes.len(), None);
+        let reply: Value = serde_json::from_slice(&bytes).map_err(AIMLError::JsonError)?;
+        let message = reply["choices"]
+            .get(0)
+            .map(|choice| choice["message"].clone())
+            .ok_or_else(|| AIMLError::ServiceUnavailable("No choices in response".to_string()))?;
+        Ok(tool_chat_reply(message, serde_json::from_value(reply["usage"].clone()).ok()))
+    }
+
     /// Generate voice using TTS
     pub async fn generate_voice(&self, text: String, voice_config: VoiceConfig) -> Result<Vec<u8>, AIMLError> {
         let endpoint = format!("{}/audio/speech", self.base_url);
@@ -199,8 +428,125 @@ impl AIMLClient {
             "speed": voice_config.speed.unwrap_or(1.0),
         });
 
-        let response = self.send_audio_request(&endpoint, request_body).await?;
-        Ok(response)
+        if let Some(replayed) = session_recording::replay_ai(AuditService::Speech, &voice_config.model, &text) {
+            return match replayed? {
+                AiPayload::Speech { audio_base64 } => BASE64
+                    .decode(audio_base64)
+                    .map_err(|e| AIMLError::ServiceUnavailable(format!("Recorded audio is not valid base64: {}", e))),
+                _ => Err(replay_mismatch()),
+            };
+        }
+
+        let started = Instant::now();
+        let result = self.send_audio_request(&endpoint, request_body).await;
+        audit::record(AuditService::Speech, &voice_config.model, &text, started, result.as_ref().map(|_| None));
+        if session_recording::is_recording() {
+            let payload = result.as_ref().map(|audio| AiPayload::Speech {
+                audio_base64: BASE64.encode(audio),
+            });
+            session_recording::record_ai(AuditService::Speech, &voice_config.model, &text, payload);
+        }
+        result
+    }
+
+    /// Transcribe an audio file with a speech-to-text model
+    pub async fn transcribe_audio(
+        &self,
+        audio: Vec<u8>,
+        file_name: String,
+        model: String,
+        language: Option<String>,
+    ) -> Result<TranscriptionResponse, AIMLError> {
+        if audio.is_empty() {
+            return Err(AIMLError::MissingParameter("audio".to_string()));
+        }
+        // Audio has no prompt text, so recorded transcriptions are matched by model and order
+        if let Some(replayed) = session_recording::replay_ai(AuditService::Transcription, &model, "") {
+            return match replayed? {
+                AiPayload::Transcription { response } => Ok(response),
+                _ => Err(replay_mismatch()),
+            };
+        }
+
+        let started = Instant::now();
+        let audit_model = model.clone();
+        let result = self.post_transcription(audio, file_name, model, language).await;
+        // Audio has no prompt text to hash
+        audit::record(AuditService::Transcription, &audit_model, "", started, result.as_ref().map(|_| None));
+        if session_recording::is_recording() {
+            let payload = result.as_ref().map(|response| AiPayload::Transcription {
+                response: response.clone(),
+            });
+            session_recording::record_ai(AuditService::Transcription, &audit_model, "", payload);
+        }
+        result
+    }
+
+    async fn post_transcription(
+        &self,
+        audio: Vec<u8>,
+        file_name: String,
+        model: String,
+        language: Option<String>,
+    ) -> Result<TranscriptionResponse, AIMLError> {
+        if let Some(mock) = &self.mock {
+            return Ok(mock.transcribe(&audio, language).await);
+        }
+        let endpoint = format!("{}/audio/transcriptions", self.base_url);
+
+        let upload = audio_upload::prepare(audio, file_name, &self.upload_compression);
+        if let Some(bitrate) = upload.bitrate_kbps {
+            log::debug!(
+                "Uploading {} bytes of Opus at {} kbps instead of {} bytes of PCM",
+                upload.audio.len(),
+                bitrate,
+                upload.original_bytes
+            );
+        }
+        let sent = upload.audio.len();
+        let uncompressed = upload.bitrate_kbps.map(|_| upload.original_bytes);
+        let (body, meter) = audio_upload::metered_body(upload.audio);
+        let file = reqwest::multipart::Part::stream_with_length(body, sent as u64).file_name(upload.file_name);
+        let mut form = reqwest::multipart::Form::new()
+            .part("file", file)
+            .text("model", model)
+            .text("response_format", "verbose_json");
+        if let Some(language) = language {
+            // The API expects ISO 639-1 ("en"), not a locale ("en-US")
+            let base = language.split(['-', '_']).next().unwrap_or(&language).to_string();
+            form = form.text("language", base);
+        }
+
+        // Audio uploads and decoding take far longer than chat requests
+        let response = timeout(Duration::from_secs(300), async {
+            self.http_client
+                .post(&endpoint)
+                .header("Authorization", format!("Bearer {}", self.api_key))
+                .multipart(form)
+                .send()
+                .await
+        }).await.map_err(|_| AIMLError::Timeout("Transcription request timeout".to_string()))?
+        .map_err(AIMLError::HttpClientError)?;
+        meter.finish();
+
+        let status = response.status();
+        if !status.is_success() {
+            let retry_after_secs = retry_after(&response);
+            let error_text = response.text().await.unwrap_or_default();
+            traffic::record(TrafficService::Transcription, sent, error_text.len(), uncompressed);
+            return match status.as_u16() {
+                401 => Err(AIMLError::AuthError("Invalid API key".to_string())),
+                429 => Err(AIMLError::RateLimitExceeded { retry_after_secs }),
+                _ => Err(AIMLError::ApiError {
+                    status: status.as_u16(),
+                    message: error_text,
+                }),
+            };
+        }
+
+        let bytes = response.bytes().await.map_err(AIMLError::HttpClientError)?;
+        traffic::record(TrafficService::Transcription, sent, bytes.len(), uncompressed);
+        serde_json::from_slice::<TranscriptionResponse>(&bytes).map_err(AIMLError::JsonError)
     }
 
     /// Translate text
@@ -290,7 +636,7 @@ impl AIMLClient {
         }
     }
 
-    /// Check API health
+    /// Check API health with a real completion, which is billed; `list_model_ids` is the free alternative
     pub async fn health_check(&self) -> Result<bool, AIMLError> {
         let test_request = self.create_chat_request(
             "gpt-4o".to_string(),
@@ -307,14 +653,56 @@ impl AIMLClient {
 
     /// Send HTTP request to AI ML API
     async fn send_request(&self, request: AIMLRequest) -> Result<AIMLResponse, AIMLError> {
+        let prompt = request
+            .messages
+            .iter()
+            .map(|message| message.content.as_str())
+            .collect::<Vec<_>>()
+            .join("\n");
+        if let Some(replayed) = session_recording::replay_ai(AuditService::Chat, &request.model, &prompt) {
+            return match replayed? {
+                AiPayload::Chat { content, usage } => Ok(recorded_chat_response(&request.model, content, usage)),
+                _ => Err(replay_mismatch()),
+            };
+        }
+
+        let started = Instant::now();
+        let result = self.post_chat(&request).await;
+        audit::record(
+            AuditService::Chat,
+            &request.model,
+            &prompt,
+            started,
+            result.as_ref().map(|response| response.usage.as_ref()),
+        );
+        if session_recording::is_recording() {
+            let payload = result.as_ref().map(|response| AiPayload::Chat {
+                content: response
+                    .choices
+                    .first()
+                    .map(|choice| choice.message.content.clone())
+                    .unwrap_or_default(),
+                usage: response.usage.clone(),
+            });
+            session_recording::record_ai(AuditService::Chat, &request.model, &prompt, payload);
+        }
+        result
+    }
+
+    async fn post_chat(&self, request: &AIMLRequest) -> Result<AIMLResponse, AIMLError> {
+        if let Some(mock) = &self.mock {
+            return Ok(mock.chat(request).await);
+        }
         let url = format!("{}/chat/completions", self.base_url);
-        
+        let payload = serde_json::to_vec(request).map_err(AIMLError::JsonError)?;
+        let sent = payload.len();
+
         let response = timeout(Duration::from_secs(30), async {
             self.http_client
                 .post(&url)
                 .header("Authorization", format!("Bearer {}", self.api_key))
                 .header("Content-Type", "application/json")
-                .json(&request)
+                .body(payload)
                 .send()
                 .await
         }).await.map_err(|_| AIMLError::Timeout("Request timeout".to_string()))?
@@ -323,10 +711,12 @@ impl AIMLClient {
         let status = response.status();
         
         if !status.is_success() {
+            let retry_after_secs = retry_after(&response);
             let error_text = response.text().await.unwrap_or_default();
+            traffic::record(TrafficService::Chat, sent, error_text.len(), None);
             return match status.as_u16() {
                 401 => Err(AIMLError::AuthError("Invalid API key".to_string())),
-                429 => Err(AIMLError::RateLimitExceeded),
+                429 => Err(AIMLError::RateLimitExceeded { retry_after_secs }),
                 503 => Err(AIMLError::ServiceUnavailable("Service temporarily unavailable".to_string())),
                 _ => Err(AIMLError::ApiError {
                     status: status.as_u16(),
@@ -336,6 +726,7 @@ impl AIMLClient {
         }
 
         let response_text = response.text().await.map_err(AIMLError::HttpClientError)?;
+        traffic::record(TrafficService::Chat, sent, response_text.len(), None);
         
         match serde_json::from_str::<AIMLResponse>(&response_text) {
             Ok(parsed) => {
@@ -351,12 +742,17 @@ impl AIMLClient {
 
     /// Send audio request for TTS
     async fn send_audio_request(&self, endpoint: &str, body: Value) -> Result<Vec<u8>, AIMLError> {
+        if let Some(mock) = &self.mock {
+            return Ok(mock.speech(body["input"].as_str().unwrap_or_default()).await);
+        }
+        let payload = serde_json::to_vec(&body).map_err(AIMLError::JsonError)?;
+        let sent = payload.len();
         let response = timeout(Duration::from_secs(30), async {
             self.http_client
                 .post(endpoint)
                 .header("Authorization", format!("Bearer {}", self.api_key))
                 .header("Content-Type", "application/json")
-                .json(&body)
+                .body(payload)
                 .send()
                 .await
         }).await.map_err(|_| AIMLError::Timeout("TTS request timeout".to_string()))?
@@ -366,13 +762,16 @@ impl AIMLClient {
         
         if !status.is_success() {
             let error_text = response.text().await.unwrap_or_default();
+            traffic::record(TrafficService::Speech, sent, error_text.len(), None);
             return Err(AIMLError::ApiError {
                 status: status.as_u16(),
                 message: error_text,
             });
         }
 
-        response.bytes().await.map_err(AIMLError::HttpClientError).map(|b| b.to_vec())
+        let audio = response.bytes().await.map_err(AIMLError::HttpClientError)?;
+        traffic::record(TrafficService::Speech, sent, audio.len(), None);
+        Ok(audio.to_vec())
     }
 
     /// Create a chat completion request
@@ -404,6 +803,88 @@ impl AIMLClient {
     }
 }
 
+/// Seconds from a `Retry-After` header; the HTTP-date form is not used by the API
+fn retry_after(response: &reqwest::Response) -> Option<u64> {
+    response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok())
+}
+
+/// Tool calls and text of an assistant message in the OpenAI chat format
+fn tool_chat_reply(message: Value, usage: Option<AIMLUsage>) -> ToolChatReply {
+    let tool_calls = message["tool_calls"]
+        .as_array()
+        .map(|calls| {
+            calls
+                .iter()
+                .filter_map(|call| {
+                    let function = &call["function"];
+                    // Arguments arrive as a JSON-encoded string
+                    let arguments = function["arguments"]
+                        .as_str()
+                        .and_then(|raw| serde_json::from_str(raw).ok())
+                        .unwrap_or_else(|| json!({}));
+                    Some(ToolCall {
+                        id: call["id"].as_str()?.to_string(),
+                        name: function["name"].as_str()?.to_string(),
+                        arguments,
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    ToolChatReply {
+        content: message["content"].as_str().map(str::to_string),
+        tool_calls,
+        message,
+        usage,
+    }
+}
+
+/// A recorded chat answer in the shape the API returns
+fn recorded_chat_response(model: &str, content: String, usage: Option<AIMLUsage>) -> AIMLResponse {
+    AIMLResponse {
+        id: "replayed".to_string(),
+        object: "chat.completion".to_string(),
+        created: 0,
+        model: model.to_string(),
+        choices: vec![AIMLChoice {
+            index: 0,
+            message: AIMLMessage {
+                role: "assistant".to_string(),
+                content,
+            },
+            finish_reason: Some("stop".to_string()),
+        }],
+        usage,
+    }
+}
+
+fn replay_mismatch() -> AIMLError {
+    AIMLError::ServiceUnavailable("The replayed session recorded a different kind of response".to_string())
+}
+
+/// A tool the model asked to run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCall {
+    pub id: String,
+    pub name: String,
+    pub arguments: Value,
+}
+
+/// Reply to a tool-enabled chat turn: either text, tool calls, or both
+#[derive(Debug, Clone)]
+pub struct ToolChatReply {
+    pub content: Option<String>,
+    pub tool_calls: Vec<ToolCall>,
+    /// The assistant message as returned, to be replayed in the next turn's history
+    pub message: Value,
+    pub usage: Option<AIMLUsage>,
+}
+
 /// Voice configuration for TTS
 #[derive(Debug, Clone)]
 pub struct VoiceConfig {
@@ -414,6 +895,17 @@ pub struct VoiceConfig {
     pub pitch: Option<f32>,
 }
 
+/// Speech-to-text result
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptionResponse {
+    pub text: String,
+    #[serde(default)]
+    pub language: Option<String>,
+    /// Audio length in seconds
+    #[serde(default)]
+    pub duration: Option<f32>,
+}
+
 /// Context analysis result
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ContextAnalysis {
@@ -2,11 +2,16 @@
 // Bridges the Rust backend with Python AI text processor
 
 use serde::{Deserialize, Serialize};
-use std::process::{Command, Stdio};
-use std::io::{BufRead, BufReader, Write};
-use tokio::sync::mpsc;
 use uuid::Uuid;
 
+use crate::event_channel::{event_channel, Coalesce, EventReceiver, EventSender};
+use super::code_dictation::{format_code, CodeLanguage};
+use super::length_limit::{LengthCompliance, LengthConstraint};
+use super::python_bridge::{BridgeHealth, PythonBridge, PythonBridgeConfig};
+use crate::unicode_text::{grapheme_count, split_sentences, word_count, words};
+
+pub const PROCESSING_EVENT_CAPACITY: usize = 64;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TextProcessingConfig {
     pub context: ProcessingContext,
@@ -17,7 +22,7 @@ pub struct TextProcessingConfig {
     pub max_cache_size: usize,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum ProcessingContext {
     Email,
     Code,
@@ -29,7 +34,7 @@ pub enum ProcessingContext {
     Creative,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum ToneType {
     Professional,
     Friendly,
@@ -58,6 +63,9 @@ pub struct ProcessingOptions {
     pub preserve_formatting: bool,
     pub smart_punctuation: bool,
     pub auto_correct: bool,
+    /// How long the result may be; the dictation pipeline shortens results that come back over it
+    #[serde(default)]
+    pub length: Option<LengthConstraint>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -71,6 +79,12 @@ pub struct ProcessingResult {
     pub context_used: ProcessingContext,
     pub tone_applied: ToneType,
     pub metadata: ProcessingMetadata,
+    /// Where the time went for a dictated utterance, filled in by the dictation pipeline
+    #[serde(default)]
+    pub latency: Option<crate::latency::LatencyBreakdown>,
+    /// Per-stage timings of the dictation pipeline that produced the result
+    #[serde(default)]
+    pub pipeline: Option<crate::pipeline::PipelineReport>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -92,6 +106,8 @@ pub enum ChangeType {
     Formatting,
     Capitalization,
     Style,
+    /// A quote, citation, number or name put back after enhancement altered it
+    Preservation,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -103,6 +119,12 @@ pub struct ProcessingMetadata {
     pub sentences_processed: usize,
     pub errors_corrected: usize,
     pub filler_words_removed: usize,
+    /// Produced in verbatim mode: the words are exactly as recognized, with no enhancement or normalization
+    #[serde(default)]
+    pub verbatim: bool,
+    /// Whether the result kept to the profile's length constraint, when it has one
+    #[serde(default)]
+    pub length: Option<LengthCompliance>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -153,9 +175,11 @@ pub struct TextStatistics {
 
 pub struct AITextProcessor {
     config: TextProcessingConfig,
-    python_process: Option<std::process::Child>,
-    event_sender: mpsc::UnboundedSender<ProcessingEvent>,
-    pending_requests: tokio::sync::Mutex<std::collections::HashMap<String, oneshot::Sender<ProcessingResult>>>,
+    /// Python helper doing the processing; text is processed in-process while it is not running
+    python_bridge: Option<PythonBridge>,
+    event_sender: EventSender<ProcessingEvent>,
+    /// Language that `ProcessingContext::Code` text is formatted for
+    code_language: CodeLanguage,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -167,46 +191,70 @@ pub enum ProcessingEvent {
     BatchCompleted(Vec<ProcessingResult>),
 }
 
+impl Coalesce for ProcessingEvent {
+    fn coalesce_key(&self) -> Option<&'static str> {
+        match self {
+            ProcessingEvent::ProcessingProgress(..) => Some("progress"),
+            _ => None,
+        }
+    }
+}
+
 impl AITextProcessor {
     pub fn new(
         config: TextProcessingConfig,
-        event_sender: mpsc::UnboundedSender<ProcessingEvent>,
+        event_sender: EventSender<ProcessingEvent>,
     ) -> Self {
         Self {
             config,
-            python_process: None,
+            python_bridge: None,
             event_sender,
-            pending_requests: tokio::sync::Mutex::new(std::collections::HashMap::new()),
+            code_language: CodeLanguage::TypeScript,
         }
     }
 
     pub async fn initialize(&mut self) -> Result<(), String> {
-        // Initialize Python text processor
-        // This would start the Python process and establish communication
-        
-        // For demonstration, we'll simulate initialization
-        println!("Initializing AI Text Processor...");
-        
-        // In a real implementation, you would:
-        // 1. Start Python process with the text processor module
-        // 2. Establish IPC communication
-        // 3. Send initialization commands
-        // 4. Verify the process is ready
-        
+        // The Python helper is optional; without one configured, text is processed in-process
+        let Some(bridge_config) = PythonBridgeConfig::from_env() else {
+            log::info!("No Python text processor configured; processing text in-process");
+            return Ok(());
+        };
+        match PythonBridge::spawn(bridge_config).await {
+            Ok(bridge) => {
+                log::info!("Python text processor is ready");
+                self.python_bridge = Some(bridge);
+            }
+            Err(e) => log::warn!("Python text processor unavailable, processing text in-process: {}", e),
+        }
         Ok(())
     }
 
+    /// Stop the Python helper, if one is running
+    pub async fn shutdown(&mut self) {
+        if let Some(bridge) = self.python_bridge.take() {
+            bridge.shutdown().await;
+        }
+    }
+
     pub async fn process_text(&self, request: ProcessingRequest) -> Result<ProcessingResult, String> {
-        let (sender, receiver) = oneshot::channel();
-        let request_id = request.id.clone();
-        
-        // Store the response channel
-        // Note: In a real implementation, you'd need to manage this properly
-        // For now, we'll simulate the processing
-        
-        let result = self.simulate_processing(request).await?;
-        
-        Ok(result)
+        // Prose clean-up would mangle code, so code goes through the code formatter instead
+        if let ProcessingContext::Code = request.context {
+            return Ok(self.format_code_request(request));
+        }
+
+        if let Some(bridge) = self.python_bridge.as_ref().filter(|bridge| bridge.health() == BridgeHealth::Ready) {
+            let payload = serde_json::to_value(&request).map_err(|e| e.to_string())?;
+            match bridge.request("process_text", payload).await {
+                Ok(reply) => {
+                    return serde_json::from_value(reply)
+                        .map_err(|e| format!("Text processor sent an unexpected result: {}", e))
+                }
+                // A busy or stopped helper should not cost the user their text
+                Err(e) => log::warn!("Python text processor failed, processing in-process: {}", e),
+            }
+        }
+
+        self.simulate_processing(request).await
     }
 
     pub async fn process_batch(&self, requests: Vec<ProcessingRequest>) -> Result<Vec<ProcessingResult>, String> {
@@ -246,14 +294,14 @@ impl AITextProcessor {
             ],
             keywords: vec!["meeting".to_string(), "project".to_string()],
             statistics: TextStatistics {
-                word_count: text.split_whitespace().count(),
-                sentence_count: text.matches('.').count() + text.matches('!').count() + text.matches('?').count(),
+                word_count: word_count(&text),
+                sentence_count: split_sentences(&text, "").len(),
                 paragraph_count: text.split("\n\n").count(),
-                character_count: text.len(),
+                character_count: grapheme_count(&text),
                 avg_sentence_length: 12.5,
                 avg_word_length: 4.2,
-                unique_words: text.split_whitespace().collect::<std::collections::HashSet<_>>().len(),
-                reading_time_seconds: text.split_whitespace().count() / 200 * 60,
+                unique_words: words(&text).into_iter().collect::<std::collections::HashSet<_>>().len(),
+                reading_time_seconds: word_count(&text) / 200 * 60,
             },
             summary: "Professional email discussing project updates".to_string(),
             suggestions: vec![
@@ -273,6 +321,48 @@ impl AITextProcessor {
         self.config = new_config;
     }
 
+    pub fn set_code_language(&mut self, language: CodeLanguage) {
+        self.code_language = language;
+    }
+
+    fn format_code_request(&self, request: ProcessingRequest) -> ProcessingResult {
+        let started = std::time::Instant::now();
+        let formatted = format_code(&request.text, self.code_language);
+        let mut changes_made = Vec::new();
+        if formatted.code != request.text {
+            changes_made.push(TextChange {
+                change_type: ChangeType::Formatting,
+                original: request.text.clone(),
+                replacement: formatted.code.clone(),
+                position: 0,
+                confidence: if formatted.valid { 0.9 } else { 0.5 },
+            });
+        }
+        ProcessingResult {
+            id: request.id,
+            metadata: ProcessingMetadata {
+                readability_before: 0.0,
+                readability_after: 0.0,
+                word_count_before: request.text.split_whitespace().count(),
+                word_count_after: formatted.code.split_whitespace().count(),
+                sentences_processed: formatted.code.lines().count(),
+                errors_corrected: 0,
+                filler_words_removed: 0,
+                verbatim: false,
+                length: None,
+            },
+            original_text: request.text,
+            processed_text: formatted.code,
+            changes_made,
+            confidence_score: if formatted.valid { 0.9 } else { 0.5 },
+            processing_time_ms: started.elapsed().as_millis() as u64,
+            context_used: request.context,
+            tone_applied: request.tone,
+            latency: None,
+            pipeline: None,
+        }
+    }
+
     async fn simulate_processing(&self, request: ProcessingRequest) -> Result<ProcessingResult, String> {
         // Simulate processing delay
         tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
@@ -356,6 +446,18 @@ impl AITextProcessor {
             _ => {}
         }
         
+        // Counted before the texts move into the result
+        let metadata = ProcessingMetadata {
+            readability_before: 60.0,
+            readability_after: 75.0,
+            word_count_before: word_count(&request.text),
+            word_count_after: word_count(&processed_text),
+            sentences_processed: split_sentences(&request.text, "").len(),
+            errors_corrected: changes_made.iter().filter(|c| c.change_type == ChangeType::Grammar || c.change_type == ChangeType::Spelling).count(),
+            filler_words_removed: changes_made.iter().filter(|c| c.change_type == ChangeType::FillerRemoval).count(),
+            verbatim: false,
+            length: None,
+        };
         let result = ProcessingResult {
             id: request.id,
             original_text: request.text,
@@ -365,15 +467,9 @@ impl AITextProcessor {
             processing_time_ms: 150,
             context_used: request.context,
             tone_applied: request.tone,
-            metadata: ProcessingMetadata {
-                readability_before: 60.0,
-                readability_after: 75.0,
-                word_count_before: request.text.split_whitespace().count(),
-                word_count_after: processed_text.split_whitespace().count(),
-                sentences_processed: request.text.matches('.').count() + 1,
-                errors_corrected: changes_made.iter().filter(|c| c.change_type == ChangeType::Grammar || c.change_type == ChangeType::Spelling).count(),
-                filler_words_removed: changes_made.iter().filter(|c| c.change_type == ChangeType::FillerRemoval).count(),
-            },
+            metadata,
+            latency: None,
+            pipeline: None,
         };
         
         Ok(result)
@@ -382,8 +478,8 @@ impl AITextProcessor {
 
 pub fn create_ai_text_processor(
     config: TextProcessingConfig,
-) -> Result<(AITextProcessor, mpsc::UnboundedReceiver<ProcessingEvent>), String> {
-    let (event_sender, event_receiver) = mpsc::unbounded_channel();
+) -> Result<(AITextProcessor, EventReceiver<ProcessingEvent>), String> {
+    let (event_sender, event_receiver) = event_channel("processing", PROCESSING_EVENT_CAPACITY);
     let processor = AITextProcessor::new(config, event_sender);
     Ok((processor, event_receiver))
 }
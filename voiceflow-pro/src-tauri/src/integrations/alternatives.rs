@@ -0,0 +1,173 @@
+// Enhancement Alternatives Module
+// Distinct rewrites of enhanced text, each from its own style prompt and temperature, scored by how much they differ
+
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::OnceLock;
+
+use futures_util::future::join_all;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use super::ai_ml_api::AIMLAPIGateway;
+use super::model_comparison::word_diff;
+
+pub const MAX_ALTERNATIVES: u8 = 5;
+/// Rewrites closer than this to a kept version are dropped as duplicates
+const MIN_DIVERSITY: f32 = 0.05;
+/// Results whose alternatives can still be picked
+const REMEMBERED_RESULTS: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlternativeStyle {
+    Faithful,
+    Concise,
+    Polished,
+    Conversational,
+    Restructured,
+}
+
+impl AlternativeStyle {
+    pub const ALL: [AlternativeStyle; 5] = [
+        AlternativeStyle::Faithful,
+        AlternativeStyle::Concise,
+        AlternativeStyle::Polished,
+        AlternativeStyle::Conversational,
+        AlternativeStyle::Restructured,
+    ];
+
+    fn prompt(self) -> &'static str {
+        match self {
+            AlternativeStyle::Faithful => "Rewrite the dictated text with the fewest possible changes: fix errors and punctuation only and keep the speaker's wording.",
+            AlternativeStyle::Concise => "Rewrite the dictated text as briefly as possible without losing any information.",
+            AlternativeStyle::Polished => "Rewrite the dictated text in polished, professional prose with precise word choice.",
+            AlternativeStyle::Conversational => "Rewrite the dictated text in a relaxed, conversational voice, as the speaker would write to a colleague.",
+            AlternativeStyle::Restructured => "Rewrite the dictated text with a clearer structure: lead with the main point and reorder or split sentences as needed.",
+        }
+    }
+
+    /// Styles that should stay close to the input run cooler
+    fn temperature(self) -> f32 {
+        match self {
+            AlternativeStyle::Faithful => 0.2,
+            AlternativeStyle::Concise => 0.5,
+            AlternativeStyle::Polished => 0.6,
+            AlternativeStyle::Conversational => 0.9,
+            AlternativeStyle::Restructured => 0.8,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlternativeVersion {
+    pub index: usize,
+    pub text: String,
+    pub style: AlternativeStyle,
+    pub temperature: f32,
+    /// Word-level distance to the closest other version, including the main result, 0.0 to 1.0
+    pub diversity: f32,
+}
+
+/// How often the user picked each style, used to generate favourites first
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AlternativePreferences {
+    pub picks: BTreeMap<AlternativeStyle, u32>,
+}
+
+impl AlternativePreferences {
+    /// The `count` styles to generate, most picked first; ties keep the default order
+    pub fn styles(&self, count: u8) -> Vec<AlternativeStyle> {
+        let mut styles = AlternativeStyle::ALL.to_vec();
+        styles.sort_by_key(|style| std::cmp::Reverse(self.picks.get(style).copied().unwrap_or(0)));
+        styles.truncate(count.min(MAX_ALTERNATIVES) as usize);
+        styles
+    }
+
+    pub fn record_pick(&mut self, style: AlternativeStyle) {
+        *self.picks.entry(style).or_insert(0) += 1;
+    }
+}
+
+static RECENT: OnceLock<Mutex<VecDeque<(String, Vec<AlternativeVersion>)>>> = OnceLock::new();
+
+fn recent() -> &'static Mutex<VecDeque<(String, Vec<AlternativeVersion>)>> {
+    RECENT.get_or_init(|| Mutex::new(VecDeque::new()))
+}
+
+/// Rewrite `text` once per style in parallel, dropping rewrites that repeat the main result or each other
+pub async fn generate_alternatives(
+    gateway: &AIMLAPIGateway,
+    model: &str,
+    text: &str,
+    primary: &str,
+    styles: &[AlternativeStyle],
+) -> Vec<AlternativeVersion> {
+    let rewrites = join_all(styles.iter().map(|style| {
+        let system_prompt = format!("{} Return only the rewritten text.", style.prompt());
+        gateway.complete_with_model(model.to_string(), system_prompt, text.to_string(), Some(style.temperature()))
+    }))
+    .await;
+
+    let mut kept: Vec<(AlternativeStyle, String)> = Vec::new();
+    for (style, rewrite) in styles.iter().zip(rewrites) {
+        let rewrite = match rewrite {
+            Ok((rewrite, _usage)) => rewrite.trim().to_string(),
+            Err(e) => {
+                log::warn!("{:?} alternative failed: {}", style, e);
+                continue;
+            }
+        };
+        let duplicate = std::iter::once(primary)
+            .chain(kept.iter().map(|(_, text)| text.as_str()))
+            .any(|other| word_diff(other, &rewrite).change_ratio < MIN_DIVERSITY);
+        if !rewrite.is_empty() && !duplicate {
+            kept.push((*style, rewrite));
+        }
+    }
+
+    kept.iter()
+        .enumerate()
+        .map(|(index, (style, rewrite))| {
+            let diversity = std::iter::once(primary)
+                .chain(
+                    kept.iter()
+                        .enumerate()
+                        .filter(|(other, _)| *other != index)
+                        .map(|(_, (_, text))| text.as_str()),
+                )
+                .map(|other| word_diff(other, rewrite).change_ratio)
+                .fold(1.0_f32, f32::min);
+            AlternativeVersion {
+                index,
+                text: rewrite.clone(),
+                style: *style,
+                temperature: style.temperature(),
+                diversity,
+            }
+        })
+        .collect()
+}
+
+/// Keep a result's alternatives so the user can pick one later
+pub async fn remember(result_id: &str, alternatives: &[AlternativeVersion]) {
+    if alternatives.is_empty() {
+        return;
+    }
+    let mut recent = recent().lock().await;
+    recent.push_back((result_id.to_string(), alternatives.to_vec()));
+    while recent.len() > REMEMBERED_RESULTS {
+        recent.pop_front();
+    }
+}
+
+/// The alternative at `index` of a recent result
+pub async fn find(result_id: &str, index: usize) -> Option<AlternativeVersion> {
+    recent()
+        .lock()
+        .await
+        .iter()
+        .rev()
+        .find(|(id, _)| id == result_id)
+        .and_then(|(_, alternatives)| alternatives.get(index).cloned())
+}
@@ -0,0 +1,482 @@
+// Audio Capture Module
+// Opens the input device in a format it accepts, converts to the recognizer's rate and tells a busy device apart from a broken one
+
+use std::time::Duration;
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{BuildStreamError, Device, Sample, SampleFormat, SizedSample, StreamConfig, SupportedStreamConfig};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, oneshot};
+
+use super::echo_cancellation::{self, EchoCancellationMetrics, EchoCancellationSettings};
+use super::multi_mic::MultiMicSettings;
+use crate::errors::{AppError, ValidationError, VoiceError};
+
+/// Rate the recognizers expect; devices running at anything else are resampled
+pub const TARGET_SAMPLE_RATE: u32 = 16_000;
+/// WASAPI's AUDCLNT_E_DEVICE_IN_USE: another app holds the endpoint in exclusive mode
+const DEVICE_IN_USE_HRESULT: &str = "0x8889000a";
+/// How long `diagnose` listens once the stream is open
+const DIAGNOSE_LISTEN: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioCaptureSettings {
+    /// Input device by name; the system default when unset
+    pub device: Option<String>,
+    /// Attempts made after the first while another app holds the device
+    pub busy_retries: u32,
+    pub retry_delay_ms: u64,
+    /// A second microphone recorded alongside or instead of `device`
+    pub multi_mic: MultiMicSettings,
+    /// Remove the app's own speech playback from what the microphone hears
+    pub echo_cancellation: EchoCancellationSettings,
+}
+
+impl Default for AudioCaptureSettings {
+    fn default() -> Self {
+        Self {
+            device: None,
+            busy_retries: 3,
+            retry_delay_ms: 750,
+            multi_mic: MultiMicSettings::default(),
+            echo_cancellation: EchoCancellationSettings::default(),
+        }
+    }
+}
+
+pub fn validate(settings: &AudioCaptureSettings) -> Result<(), AppError> {
+    let invalid = |message: String| AppError::Validation(ValidationError::InvalidConfigValue(message));
+    if settings.busy_retries > 10 {
+        return Err(invalid(format!("Busy device retries must be at most 10, got {}", settings.busy_retries)));
+    }
+    if !(100..=10_000).contains(&settings.retry_delay_ms) {
+        return Err(invalid(format!(
+            "Busy device retry delay must be 100-10000 ms, got {}",
+            settings.retry_delay_ms
+        )));
+    }
+    echo_cancellation::validate(&settings.echo_cancellation)?;
+    super::multi_mic::validate(settings)
+}
+
+/// The format a capture stream was opened with; cpal opens every backend in shared mode, so other apps keep the device
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NegotiatedFormat {
+    pub host: String,
+    pub device: String,
+    pub device_sample_rate: u32,
+    pub channels: u16,
+    pub sample_format: String,
+    /// Rate frames are delivered at after conversion
+    pub sample_rate: u32,
+    pub resampled: bool,
+    /// Attempts it took to open the device, counting the first
+    pub attempts: u32,
+    /// Why the format differs from the one asked for
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaptureDiagnosis {
+    pub format: Option<NegotiatedFormat>,
+    pub input_devices: Vec<String>,
+    pub default_device: Option<String>,
+    /// Samples received at `sample_rate` while listening
+    pub samples_received: usize,
+    pub peak_level: f32,
+    pub device_busy: bool,
+    pub error: Option<String>,
+    /// Measured while speech was playing; `erle_db` stays empty until playback has been heard
+    pub echo_cancellation: EchoCancellationMetrics,
+}
+
+/// Taps in the anti-alias filter; at 48 kHz the delay it adds is well under a millisecond
+const LOW_PASS_TAPS: usize = 63;
+/// Cutoff as a fraction of the output Nyquist rate, leaving room for the filter's transition band
+const LOW_PASS_CUTOFF: f64 = 0.9;
+
+/// Windowed-sinc FIR low-pass applied before downsampling, so content above the output Nyquist rate is removed
+/// rather than folded back into the speech band
+#[derive(Debug, Clone)]
+struct LowPass {
+    taps: Vec<f32>,
+    /// The last `taps.len() - 1` input samples, so the filter runs across block boundaries
+    history: Vec<f32>,
+}
+
+impl LowPass {
+    /// `cutoff` in cycles per input sample
+    fn new(cutoff: f64) -> Self {
+        let middle = (LOW_PASS_TAPS / 2) as f64;
+        let mut taps: Vec<f64> = (0..LOW_PASS_TAPS)
+            .map(|n| {
+                let t = n as f64 - middle;
+                let sinc = if t == 0.0 {
+                    2.0 * cutoff
+                } else {
+                    (2.0 * std::f64::consts::PI * cutoff * t).sin() / (std::f64::consts::PI * t)
+                };
+                let phase = 2.0 * std::f64::consts::PI * n as f64 / (LOW_PASS_TAPS - 1) as f64;
+                let blackman = 0.42 - 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos();
+                sinc * blackman
+            })
+            .collect();
+        let gain: f64 = taps.iter().sum();
+        taps.iter_mut().for_each(|tap| *tap /= gain);
+        Self {
+            taps: taps.into_iter().map(|tap| tap as f32).collect(),
+            history: vec![0.0; LOW_PASS_TAPS - 1],
+        }
+    }
+
+    fn process(&mut self, input: &[f32]) -> Vec<f32> {
+        let mut window = std::mem::take(&mut self.history);
+        window.extend_from_slice(input);
+        let output = window
+            .windows(self.taps.len())
+            .map(|span| span.iter().zip(&self.taps).map(|(sample, tap)| sample * tap).sum())
+            .collect();
+        self.history = window.split_off(window.len() - (self.taps.len() - 1));
+        output
+    }
+}
+
+/// Streaming resampler for mono frames: low-pass filtered when downsampling, then linearly interpolated.
+/// Keeps its phase and filter state between callbacks
+#[derive(Debug, Clone)]
+pub struct Resampler {
+    /// Input samples per output sample
+    step: f64,
+    /// Position of the next output sample, relative to `previous`
+    position: f64,
+    previous: Option<f32>,
+    anti_alias: Option<LowPass>,
+}
+
+impl Resampler {
+    pub fn new(from_rate: u32, to_rate: u32) -> Self {
+        let step = from_rate as f64 / to_rate as f64;
+        Self {
+            step,
+            position: 0.0,
+            previous: None,
+            anti_alias: (step > 1.0).then(|| LowPass::new(LOW_PASS_CUTOFF * 0.5 / step)),
+        }
+    }
+
+    pub fn is_passthrough(&self) -> bool {
+        self.step == 1.0
+    }
+
+    pub fn process(&mut self, input: &[f32]) -> Vec<f32> {
+        if self.is_passthrough() || input.is_empty() {
+            return input.to_vec();
+        }
+        let filtered;
+        let input = match &mut self.anti_alias {
+            Some(filter) => {
+                filtered = filter.process(input);
+                &filtered[..]
+            }
+            None => input,
+        };
+        // Index -1 is the last sample of the previous block, so interpolation spans block boundaries
+        let previous = self.previous.unwrap_or(input[0]);
+        let at = |index: isize| if index < 0 { previous } else { input[index as usize] };
+        let mut output = Vec::with_capacity((input.len() as f64 / self.step) as usize + 1);
+        let last = input.len() as f64 - 1.0;
+        while self.position <= last {
+            let base = self.position.floor();
+            let fraction = (self.position - base) as f32;
+            let index = base as isize - 1;
+            output.push(at(index) * (1.0 - fraction) + at(index + 1) * fraction);
+            self.position += self.step;
+        }
+        self.position -= input.len() as f64;
+        self.previous = input.last().copied();
+        output
+    }
+}
+
+fn downmix(data: &[f32], channels: usize) -> Vec<f32> {
+    if channels <= 1 {
+        return data.to_vec();
+    }
+    data.chunks_exact(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect()
+}
+
+/// A running capture; frames stop when it is dropped
+pub struct CaptureHandle {
+    pub format: NegotiatedFormat,
+    stop: Option<std::sync::mpsc::Sender<()>>,
+}
+
+impl Drop for CaptureHandle {
+    fn drop(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(());
+        }
+    }
+}
+
+pub fn input_device_names() -> Vec<String> {
+    cpal::default_host()
+        .input_devices()
+        .map(|devices| devices.filter_map(|device| device.name().ok()).collect())
+        .unwrap_or_default()
+}
+
+pub fn default_input_device_name() -> Option<String> {
+    cpal::default_host().default_input_device().and_then(|device| device.name().ok())
+}
+
+fn find_device(settings: &AudioCaptureSettings) -> Result<Device, AppError> {
+    let host = cpal::default_host();
+    let device = match &settings.device {
+        Some(name) => host
+            .input_devices()
+            .map_err(|e| AppError::VoiceRecognition(VoiceError::AudioCaptureFailed(e.to_string())))?
+            .find(|device| device.name().map_or(false, |device_name| device_name == *name)),
+        None => host.default_input_device(),
+    };
+    device.ok_or(AppError::VoiceRecognition(VoiceError::NoAudioInput))
+}
+
+/// Prefer a mono 16 kHz format the device accepts as is; otherwise its default, which in shared mode is the mix format
+fn choose_config(device: &Device) -> Result<SupportedStreamConfig, AppError> {
+    let target = cpal::SampleRate(TARGET_SAMPLE_RATE);
+    let direct = device.supported_input_configs().ok().and_then(|mut ranges| {
+        ranges.find(|range| {
+            range.channels() == 1
+                && range.min_sample_rate() <= target
+                && range.max_sample_rate() >= target
+                && range.sample_format() == SampleFormat::F32
+        })
+    });
+    match direct {
+        Some(range) => Ok(range.with_sample_rate(target)),
+        None => device
+            .default_input_config()
+            .map_err(|e| AppError::VoiceRecognition(VoiceError::AudioCaptureFailed(e.to_string()))),
+    }
+}
+
+fn is_device_busy(error: &BuildStreamError) -> bool {
+    match error {
+        BuildStreamError::DeviceNotAvailable => true,
+        BuildStreamError::BackendSpecific { err } => {
+            let description = err.description.to_lowercase();
+            description.contains(DEVICE_IN_USE_HRESULT) || description.contains("in use") || description.contains("exclusive")
+        }
+        _ => false,
+    }
+}
+
+fn busy_error(device: &str) -> AppError {
+    AppError::VoiceRecognition(VoiceError::DeviceBusy(format!(
+        "{} is in use by another app, probably in exclusive mode. Close that app, or clear \"Allow applications to take exclusive control of this device\" under the device's Advanced properties in Sound settings",
+        device
+    )))
+}
+
+fn build_stream<T>(
+    device: &Device,
+    config: &StreamConfig,
+    device_rate: u32,
+    frames: mpsc::UnboundedSender<Vec<f32>>,
+) -> Result<cpal::Stream, BuildStreamError>
+where
+    T: SizedSample,
+    f32: cpal::FromSample<T>,
+{
+    let channels = config.channels as usize;
+    let mut resampler = Resampler::new(device_rate, TARGET_SAMPLE_RATE);
+    device.build_input_stream(
+        config,
+        move |data: &[T], _: &cpal::InputCallbackInfo| {
+            let samples: Vec<f32> = data.iter().map(|sample| sample.to_sample::<f32>()).collect();
+            let _ = frames.send(resampler.process(&downmix(&samples, channels)));
+        },
+        |e| log::warn!("Audio capture stream error: {}", e),
+        None,
+    )
+}
+
+fn open_stream(
+    device: &Device,
+    supported: &SupportedStreamConfig,
+    frames: mpsc::UnboundedSender<Vec<f32>>,
+) -> Result<cpal::Stream, BuildStreamError> {
+    let config = supported.config();
+    let rate = supported.sample_rate().0;
+    match supported.sample_format() {
+        SampleFormat::I16 => build_stream::<i16>(device, &config, rate, frames),
+        SampleFormat::U16 => build_stream::<u16>(device, &config, rate, frames),
+        SampleFormat::I32 => build_stream::<i32>(device, &config, rate, frames),
+        _ => build_stream::<f32>(device, &config, rate, frames),
+    }
+}
+
+/// Open the input device and start delivering mono frames at `TARGET_SAMPLE_RATE`.
+/// A busy device is retried `busy_retries` times; the stream lives on its own thread because it cannot cross threads
+pub async fn start_capture(
+    settings: &AudioCaptureSettings,
+    frames: mpsc::UnboundedSender<Vec<f32>>,
+) -> Result<CaptureHandle, AppError> {
+    let settings = settings.clone();
+    let (ready, opened) = oneshot::channel::<Result<NegotiatedFormat, AppError>>();
+    let (stop, stopped) = std::sync::mpsc::channel::<()>();
+
+    std::thread::spawn(move || {
+        let opened = (|| -> Result<(cpal::Stream, NegotiatedFormat), AppError> {
+            let device = find_device(&settings)?;
+            let name = device.name().unwrap_or_else(|_| "Unknown device".to_string());
+            let mut supported = choose_config(&device)?;
+            let mut note = None;
+            let mut attempts = 0;
+            let mut fell_back = false;
+            loop {
+                attempts += 1;
+                match open_stream(&device, &supported, frames.clone()) {
+                    Ok(stream) => {
+                        stream
+                            .play()
+                            .map_err(|e| AppError::VoiceRecognition(VoiceError::AudioCaptureFailed(e.to_string())))?;
+                        let format = NegotiatedFormat {
+                            host: cpal::default_host().id().name().to_string(),
+                            device: name,
+                            device_sample_rate: supported.sample_rate().0,
+                            channels: supported.channels(),
+                            sample_format: format!("{:?}", supported.sample_format()).to_lowercase(),
+                            sample_rate: TARGET_SAMPLE_RATE,
+                            resampled: supported.sample_rate().0 != TARGET_SAMPLE_RATE,
+                            attempts,
+                            note,
+                        };
+                        return Ok((stream, format));
+                    }
+                    Err(BuildStreamError::StreamConfigNotSupported) if !fell_back => {
+                        // The 16 kHz format was advertised but refused; the mix format always works in shared mode
+                        supported = device
+                            .default_input_config()
+                            .map_err(|e| AppError::VoiceRecognition(VoiceError::AudioCaptureFailed(e.to_string())))?;
+                        note = Some("The device refused 16 kHz capture; its own rate is converted".to_string());
+                        fell_back = true;
+                    }
+                    Err(e) if is_device_busy(&e) => {
+                        if attempts > settings.busy_retries {
+                            return Err(busy_error(&name));
+                        }
+                        log::info!("{} is busy, retrying capture (attempt {})", name, attempts);
+                        std::thread::sleep(Duration::from_millis(settings.retry_delay_ms));
+                    }
+                    Err(e) => return Err(AppError::VoiceRecognition(VoiceError::AudioCaptureFailed(e.to_string()))),
+                }
+            }
+        })();
+        match opened {
+            Ok((stream, format)) => {
+                let _ = ready.send(Ok(format));
+                let _ = stopped.recv();
+                drop(stream);
+            }
+            Err(e) => {
+                let _ = ready.send(Err(e));
+            }
+        }
+    });
+
+    let format = opened
+        .await
+        .map_err(|_| AppError::VoiceRecognition(VoiceError::AudioCaptureFailed("Capture thread exited".to_string())))??;
+    Ok(CaptureHandle {
+        format,
+        stop: Some(stop),
+    })
+}
+
+/// Open the configured device briefly and report the negotiated format and whether audio arrives
+pub async fn diagnose(settings: &AudioCaptureSettings) -> CaptureDiagnosis {
+    let input_devices = input_device_names();
+    let default_device = default_input_device_name();
+    let (sender, mut frames) = mpsc::unbounded_channel();
+    let mut diagnosis = CaptureDiagnosis {
+        format: None,
+        input_devices,
+        default_device,
+        samples_received: 0,
+        peak_level: 0.0,
+        device_busy: false,
+        error: None,
+        echo_cancellation: EchoCancellationMetrics::default(),
+    };
+
+    let handle = match start_capture(settings, sender).await {
+        Ok(handle) => handle,
+        Err(e) => {
+            diagnosis.device_busy = matches!(e, AppError::VoiceRecognition(VoiceError::DeviceBusy(_)));
+            diagnosis.error = Some(e.to_string());
+            diagnosis.echo_cancellation = echo_cancellation::metrics(&settings.echo_cancellation);
+            return diagnosis;
+        }
+    };
+    let deadline = tokio::time::Instant::now() + DIAGNOSE_LISTEN;
+    while let Ok(Some(mut frame)) = tokio::time::timeout_at(deadline, frames.recv()).await {
+        echo_cancellation::process_capture(&mut frame);
+        diagnosis.samples_received += frame.len();
+        diagnosis.peak_level = frame.iter().fold(diagnosis.peak_level, |peak, sample| peak.max(sample.abs()));
+    }
+    if diagnosis.samples_received == 0 {
+        diagnosis.error = Some("The device opened but delivered no audio".to_string());
+    }
+    diagnosis.format = Some(handle.format.clone());
+    diagnosis.echo_cancellation = echo_cancellation::metrics(&settings.echo_cancellation);
+    diagnosis
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tone(frequency: f64, rate: u32, seconds: f64) -> Vec<f32> {
+        (0..(rate as f64 * seconds) as usize)
+            .map(|n| (2.0 * std::f64::consts::PI * frequency * n as f64 / rate as f64).sin() as f32)
+            .collect()
+    }
+
+    /// Peak level after the filter has settled, resampling in blocks the size a device callback delivers
+    fn resampled_peak(input: &[f32], from_rate: u32) -> (usize, f32) {
+        let mut resampler = Resampler::new(from_rate, TARGET_SAMPLE_RATE);
+        let output: Vec<f32> = input.chunks(480).flat_map(|block| resampler.process(block)).collect();
+        let peak = output[output.len() / 2..].iter().fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+        (output.len(), peak)
+    }
+
+    #[test]
+    fn matching_rates_pass_through() {
+        let input = tone(440.0, TARGET_SAMPLE_RATE, 0.1);
+        let mut resampler = Resampler::new(TARGET_SAMPLE_RATE, TARGET_SAMPLE_RATE);
+        assert!(resampler.is_passthrough());
+        assert_eq!(resampler.process(&input), input);
+    }
+
+    #[test]
+    fn downsampling_keeps_speech_and_removes_what_would_alias() {
+        let (length, speech) = resampled_peak(&tone(1000.0, 48_000, 1.0), 48_000);
+        assert!((length as i64 - TARGET_SAMPLE_RATE as i64).abs() <= 1, "{} samples", length);
+        assert!(speech > 0.9, "speech band attenuated to {}", speech);
+
+        // 12 kHz is above the 8 kHz output Nyquist rate and would fold back to 4 kHz unfiltered
+        let (_, aliased) = resampled_peak(&tone(12_000.0, 48_000, 1.0), 48_000);
+        assert!(aliased < 0.01, "12 kHz leaked through at {}", aliased);
+    }
+
+    #[test]
+    fn upsampling_is_not_filtered() {
+        let resampler = Resampler::new(8_000, TARGET_SAMPLE_RATE);
+        assert!(resampler.anti_alias.is_none());
+    }
+}
@@ -0,0 +1,327 @@
+// Audio Upload Module
+// Compresses PCM audio to Ogg Opus before cloud transcription, at a bitrate picked from measured upload throughput
+
+use std::io::Cursor;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use audiopus::coder::Encoder;
+use audiopus::{Application, Bitrate, Channels, SampleRate};
+use futures_util::StreamExt;
+use ogg::writing::{PacketWriteEndInfo, PacketWriter};
+use serde::{Deserialize, Serialize};
+
+/// Opus works at these rates; anything else is resampled to `FALLBACK_RATE`
+const OPUS_RATES: [u32; 5] = [8_000, 12_000, 16_000, 24_000, 48_000];
+const FALLBACK_RATE: u32 = 16_000;
+/// Ogg Opus granule positions always count 48 kHz samples
+const GRANULE_RATE: u64 = 48_000;
+const FRAMES_PER_SECOND: u32 = 50;
+const MAX_PACKET_BYTES: usize = 4_000;
+const OGG_SERIAL: u32 = 0x5646_4f50;
+/// Uploads smaller than this finish inside socket buffers and say nothing about the link
+const MIN_MEASURED_BYTES: u64 = 64 * 1024;
+/// Weight of the newest throughput sample in the running estimate
+const THROUGHPUT_SMOOTHING: f64 = 0.3;
+/// Upload bodies are streamed in chunks of this size so the time to send them can be measured
+const UPLOAD_CHUNK_BYTES: usize = 16 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CompressionMode {
+    /// Compress unless the link is measured to be fast enough for raw audio
+    Auto,
+    Always,
+    Off,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UploadCompressionSettings {
+    pub mode: CompressionMode,
+    pub min_bitrate_kbps: u32,
+    pub max_bitrate_kbps: u32,
+    /// In auto mode, links measured faster than this get raw audio
+    pub raw_above_kbps: u32,
+}
+
+impl Default for UploadCompressionSettings {
+    fn default() -> Self {
+        Self {
+            mode: CompressionMode::Auto,
+            // Speech stays intelligible to recognizers down to about 12 kbps Opus
+            min_bitrate_kbps: 12,
+            max_bitrate_kbps: 32,
+            raw_above_kbps: 20_000,
+        }
+    }
+}
+
+/// What is actually sent for one transcription request
+#[derive(Debug, Clone)]
+pub struct PreparedUpload {
+    pub audio: Vec<u8>,
+    pub file_name: String,
+    pub original_bytes: usize,
+    /// Set when the audio was re-encoded
+    pub bitrate_kbps: Option<u32>,
+}
+
+static THROUGHPUT_KBPS: OnceLock<Mutex<Option<f64>>> = OnceLock::new();
+
+fn throughput() -> std::sync::MutexGuard<'static, Option<f64>> {
+    let throughput = THROUGHPUT_KBPS.get_or_init(|| Mutex::new(None));
+    match throughput.lock() {
+        Ok(throughput) => throughput,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+/// Feed in how long an upload body took to send; small bodies are ignored
+pub fn record_upload(bytes: u64, elapsed: Duration) {
+    if bytes < MIN_MEASURED_BYTES || elapsed.is_zero() {
+        return;
+    }
+    let sample = bytes as f64 * 8.0 / 1000.0 / elapsed.as_secs_f64();
+    let mut estimate = throughput();
+    *estimate = Some(match *estimate {
+        Some(previous) => previous + THROUGHPUT_SMOOTHING * (sample - previous),
+        None => sample,
+    });
+}
+
+/// Times when the first and last chunk of an upload body were handed to the connection
+#[derive(Debug, Clone, Default)]
+pub struct UploadMeter {
+    bytes: u64,
+    times: Arc<Mutex<(Option<Instant>, Option<Instant>)>>,
+}
+
+impl UploadMeter {
+    fn touch(&self) {
+        let now = Instant::now();
+        let mut times = match self.times.lock() {
+            Ok(times) => times,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        times.0.get_or_insert(now);
+        times.1 = Some(now);
+    }
+
+    /// Record the throughput once the response has arrived, i.e. the whole body was sent
+    pub fn finish(&self) {
+        let times = match self.times.lock() {
+            Ok(times) => *times,
+            Err(poisoned) => *poisoned.into_inner(),
+        };
+        if let (Some(first), Some(last)) = times {
+            // Timing starts when the first chunk was handed over, so it is not counted
+            record_upload(self.bytes.saturating_sub(UPLOAD_CHUNK_BYTES as u64), last - first);
+        }
+    }
+}
+
+/// A request body that streams `data` in chunks and notes when they are sent
+pub fn metered_body(data: Vec<u8>) -> (reqwest::Body, UploadMeter) {
+    let meter = UploadMeter {
+        bytes: data.len() as u64,
+        ..UploadMeter::default()
+    };
+    let chunks: Vec<Result<Vec<u8>, std::io::Error>> =
+        data.chunks(UPLOAD_CHUNK_BYTES).map(|chunk| Ok(chunk.to_vec())).collect();
+    let tracker = meter.clone();
+    let stream = futures_util::stream::iter(chunks).inspect(move |_| tracker.touch());
+    (reqwest::Body::wrap_stream(stream), meter)
+}
+
+/// Smoothed upload throughput seen so far, if anything large enough was sent
+pub fn measured_throughput_kbps() -> Option<f64> {
+    *throughput()
+}
+
+/// Opus bitrate for the current link, or `None` to send the audio as it is
+pub fn choose_bitrate(settings: &UploadCompressionSettings, throughput_kbps: Option<f64>) -> Option<u32> {
+    let min = settings.min_bitrate_kbps.clamp(6, 510);
+    let max = settings.max_bitrate_kbps.clamp(min, 510);
+    match (settings.mode, throughput_kbps) {
+        (CompressionMode::Off, _) => None,
+        (CompressionMode::Auto, Some(kbps)) if kbps >= settings.raw_above_kbps as f64 => None,
+        // Unknown links get the best quality; the first large upload measures the link
+        (_, None) => Some(max),
+        // Audio takes at most an eighth of the link, so uploads run well ahead of real time
+        (_, Some(kbps)) => Some(((kbps / 8.0) as u32).clamp(min, max)),
+    }
+}
+
+/// Re-encode 16-bit PCM WAV as Ogg Opus when `settings` call for it; other formats, and audio that
+/// fails to encode, are passed through unchanged
+pub fn prepare(audio: Vec<u8>, file_name: String, settings: &UploadCompressionSettings) -> PreparedUpload {
+    let original_bytes = audio.len();
+    let passthrough = |audio: Vec<u8>, file_name: String| PreparedUpload {
+        audio,
+        file_name,
+        original_bytes,
+        bitrate_kbps: None,
+    };
+    let Some(bitrate_kbps) = choose_bitrate(settings, measured_throughput_kbps()) else {
+        return passthrough(audio, file_name);
+    };
+    let Some(pcm) = parse_wav(&audio) else {
+        return passthrough(audio, file_name);
+    };
+
+    match encode_ogg_opus(&pcm, bitrate_kbps) {
+        Ok(encoded) if encoded.len() < original_bytes => {
+            let stem = file_name.rsplit_once('.').map_or(file_name.as_str(), |(stem, _)| stem);
+            PreparedUpload {
+                file_name: format!("{}.ogg", stem),
+                audio: encoded,
+                original_bytes,
+                bitrate_kbps: Some(bitrate_kbps),
+            }
+        }
+        Ok(_) => passthrough(audio, file_name),
+        Err(e) => {
+            log::warn!("Opus encoding failed, uploading raw audio: {}", e);
+            passthrough(audio, file_name)
+        }
+    }
+}
+
+/// Mono samples decoded from a WAV file
+#[derive(Debug, Clone, PartialEq)]
+struct Pcm {
+    sample_rate: u32,
+    samples: Vec<i16>,
+}
+
+/// Sample rate and length in seconds of a 16-bit PCM WAV file
+pub fn wav_duration(data: &[u8]) -> Option<(u32, f32)> {
+    parse_wav(data).map(|pcm| (pcm.sample_rate, pcm.samples.len() as f32 / pcm.sample_rate as f32))
+}
+
+/// 16-bit PCM WAV (plain or extensible), down-mixed to mono
+fn parse_wav(data: &[u8]) -> Option<Pcm> {
+    if data.len() < 12 || &data[0..4] != b"RIFF" || &data[8..12] != b"WAVE" {
+        return None;
+    }
+    let mut format = None;
+    let mut offset = 12;
+    while offset + 8 <= data.len() {
+        let id = &data[offset..offset + 4];
+        let size = u32::from_le_bytes(data[offset + 4..offset + 8].try_into().ok()?) as usize;
+        let body = &data[offset + 8..data.len().min(offset + 8 + size)];
+        if id == b"fmt " && body.len() >= 16 {
+            let audio_format = u16::from_le_bytes([body[0], body[1]]);
+            let channels = u16::from_le_bytes([body[2], body[3]]);
+            let sample_rate = u32::from_le_bytes([body[4], body[5], body[6], body[7]]);
+            let bits = u16::from_le_bytes([body[14], body[15]]);
+            // 1 is PCM, 0xFFFE the extensible header recorders use for the same samples
+            if !matches!(audio_format, 1 | 0xFFFE) || bits != 16 || channels == 0 || sample_rate == 0 {
+                return None;
+            }
+            format = Some((channels as usize, sample_rate));
+        } else if id == b"data" {
+            let (channels, sample_rate) = format?;
+            let samples = body
+                .chunks_exact(2 * channels)
+                .map(|frame| {
+                    let sum: i32 = frame
+                        .chunks_exact(2)
+                        .map(|sample| i16::from_le_bytes([sample[0], sample[1]]) as i32)
+                        .sum();
+                    (sum / channels as i32) as i16
+                })
+                .collect();
+            return Some(Pcm { sample_rate, samples });
+        }
+        // Chunks are padded to an even length
+        offset += 8 + size + (size & 1);
+    }
+    None
+}
+
+/// Linear resampling; plenty for speech headed to a recognizer
+fn resample(pcm: &Pcm, rate: u32) -> Vec<i16> {
+    if pcm.sample_rate == rate || pcm.samples.is_empty() {
+        return pcm.samples.clone();
+    }
+    let ratio = pcm.sample_rate as f64 / rate as f64;
+    let len = (pcm.samples.len() as f64 / ratio) as usize;
+    let last = pcm.samples.len() - 1;
+    (0..len)
+        .map(|i| {
+            let position = i as f64 * ratio;
+            let index = (position as usize).min(last);
+            let next = (index + 1).min(last);
+            let fraction = position - index as f64;
+            (pcm.samples[index] as f64 * (1.0 - fraction) + pcm.samples[next] as f64 * fraction) as i16
+        })
+        .collect()
+}
+
+fn encode_ogg_opus(pcm: &Pcm, bitrate_kbps: u32) -> Result<Vec<u8>, String> {
+    let rate = if OPUS_RATES.contains(&pcm.sample_rate) { pcm.sample_rate } else { FALLBACK_RATE };
+    let mut samples = resample(pcm, rate);
+    let sample_rate = SampleRate::try_from(rate as i32).map_err(|e| e.to_string())?;
+
+    let mut encoder = Encoder::new(sample_rate, Channels::Mono, Application::Voip).map_err(|e| e.to_string())?;
+    encoder
+        .set_bitrate(Bitrate::BitsPerSecond(bitrate_kbps as i32 * 1000))
+        .map_err(|e| e.to_string())?;
+    let lookahead = encoder.lookahead().map_err(|e| e.to_string())? as usize;
+
+    let to_granule = |count: usize| count as u64 * GRANULE_RATE / rate as u64;
+    let pre_skip = to_granule(lookahead);
+    let end_granule = pre_skip + to_granule(samples.len());
+    // Encode past the end by the encoder delay so the last real samples come out, then fill the final frame
+    let frame = (rate / FRAMES_PER_SECOND) as usize;
+    let padded = (samples.len() + lookahead + frame - 1) / frame * frame;
+    samples.resize(padded, 0);
+
+    let mut writer = PacketWriter::new(Cursor::new(Vec::new()));
+    let io_error = |e: std::io::Error| e.to_string();
+    writer
+        .write_packet(opus_head(rate, pre_skip as u16).into_boxed_slice(), OGG_SERIAL, PacketWriteEndInfo::EndPage, 0)
+        .map_err(io_error)?;
+    writer
+        .write_packet(opus_tags().into_boxed_slice(), OGG_SERIAL, PacketWriteEndInfo::EndPage, 0)
+        .map_err(io_error)?;
+
+    let mut packet = vec![0u8; MAX_PACKET_BYTES];
+    let frames = samples.len() / frame;
+    for (index, chunk) in samples.chunks_exact(frame).enumerate() {
+        let len = encoder.encode(chunk, &mut packet).map_err(|e| e.to_string())?;
+        let last = index + 1 == frames;
+        let granule = if last { end_granule } else { to_granule((index + 1) * frame) };
+        let end = if last { PacketWriteEndInfo::EndStream } else { PacketWriteEndInfo::NormalPacket };
+        writer
+            .write_packet(packet[..len].to_vec().into_boxed_slice(), OGG_SERIAL, end, granule)
+            .map_err(io_error)?;
+    }
+    Ok(writer.into_inner().into_inner())
+}
+
+/// Identification header, RFC 7845 section 5.1
+fn opus_head(input_rate: u32, pre_skip: u16) -> Vec<u8> {
+    let mut head = Vec::with_capacity(19);
+    head.extend_from_slice(b"OpusHead");
+    head.push(1); // version
+    head.push(1); // mono
+    head.extend_from_slice(&pre_skip.to_le_bytes());
+    head.extend_from_slice(&input_rate.to_le_bytes());
+    head.extend_from_slice(&0i16.to_le_bytes()); // output gain
+    head.push(0); // channel mapping family
+    head
+}
+
+/// Comment header with no comments, RFC 7845 section 5.2
+fn opus_tags() -> Vec<u8> {
+    let vendor = concat!("VoiceFlow Pro ", env!("CARGO_PKG_VERSION"));
+    let mut tags = Vec::with_capacity(16 + vendor.len());
+    tags.extend_from_slice(b"OpusTags");
+    tags.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+    tags.extend_from_slice(vendor.as_bytes());
+    tags.extend_from_slice(&0u32.to_le_bytes());
+    tags
+}
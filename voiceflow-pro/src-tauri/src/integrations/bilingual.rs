@@ -0,0 +1,324 @@
+// Bilingual Module
+// Original and translated text paired sentence by sentence, rendered interleaved or side by side for review and language learning
+
+use serde::{Deserialize, Serialize};
+
+use super::ai_ml_api::OutputFormat;
+use crate::unicode_text::{break_units, display_width, split_sentences};
+use crate::validation::sanitize_for_html;
+
+/// Column width of each side in plain-text side-by-side output
+const COLUMN_WIDTH: usize = 38;
+const COLUMN_GAP: &str = "  │  ";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BilingualLayout {
+    /// Each original sentence followed by its translation
+    Interleaved,
+    /// Original and translation in two columns, a row per sentence
+    SideBySide,
+}
+
+/// An original sentence, or a run of them, with its translation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SentencePair {
+    pub original: String,
+    pub translation: String,
+}
+
+/// Original and translation paired up, as sent in "live-translation" events and used for export
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BilingualText {
+    pub source_language: String,
+    pub target_language: String,
+    pub pairs: Vec<SentencePair>,
+}
+
+/// Cumulative share of the whole text at the end of each sentence
+fn boundaries(sentences: &[String]) -> Vec<f64> {
+    let total: usize = sentences.iter().map(|s| s.chars().count()).sum();
+    let mut seen = 0;
+    sentences
+        .iter()
+        .map(|sentence| {
+            seen += sentence.chars().count();
+            seen as f64 / total.max(1) as f64
+        })
+        .collect()
+}
+
+impl BilingualText {
+    /// Pair sentences one to one when both sides split into as many; otherwise each translated sentence
+    /// goes to the original sentence at the same relative position, and original sentences left without
+    /// one join the next pair, so merged or split sentences stay together
+    pub fn align(original: &str, translation: &str, source_language: &str, target_language: &str) -> Self {
+        let clean = |sentences: Vec<String>| -> Vec<String> {
+            sentences
+                .into_iter()
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect()
+        };
+        let originals = clean(split_sentences(original, source_language));
+        let translations = clean(split_sentences(translation, target_language));
+
+        let pairs = if originals.len() == translations.len() {
+            originals
+                .into_iter()
+                .zip(translations)
+                .map(|(original, translation)| SentencePair { original, translation })
+                .collect()
+        } else {
+            let original_ends = boundaries(&originals);
+            let mut grouped: Vec<Vec<String>> = vec![Vec::new(); originals.len()];
+            let mut start = 0.0;
+            for (sentence, end) in translations.iter().zip(boundaries(&translations)) {
+                let middle = (start + end) / 2.0;
+                start = end;
+                let index = original_ends
+                    .iter()
+                    .position(|original_end| middle <= *original_end)
+                    .unwrap_or(originals.len().saturating_sub(1));
+                if let Some(group) = grouped.get_mut(index) {
+                    group.push(sentence.clone());
+                }
+            }
+            let mut pairs: Vec<SentencePair> = Vec::new();
+            let mut waiting: Vec<String> = Vec::new();
+            for (original, group) in originals.into_iter().zip(grouped) {
+                waiting.push(original);
+                if !group.is_empty() {
+                    pairs.push(SentencePair {
+                        original: waiting.drain(..).collect::<Vec<_>>().join(" "),
+                        translation: group.join(" "),
+                    });
+                }
+            }
+            if !waiting.is_empty() {
+                match pairs.last_mut() {
+                    Some(last) => {
+                        last.original.push(' ');
+                        last.original.push_str(&waiting.join(" "));
+                    }
+                    None => pairs.push(SentencePair {
+                        original: waiting.join(" "),
+                        translation: translations.join(" "),
+                    }),
+                }
+            }
+            pairs
+        };
+
+        Self {
+            source_language: source_language.to_string(),
+            target_language: target_language.to_string(),
+            pairs,
+        }
+    }
+
+    pub fn render(&self, layout: BilingualLayout, format: &OutputFormat) -> String {
+        match format {
+            OutputFormat::PlainText => self.render_plain(layout),
+            OutputFormat::Markdown => self.render_markdown(layout),
+            OutputFormat::HTML => self.render_html(layout),
+            OutputFormat::SSML => self.render_ssml(),
+            OutputFormat::JSON => serde_json::to_string_pretty(self).unwrap_or_default(),
+        }
+    }
+
+    fn render_plain(&self, layout: BilingualLayout) -> String {
+        match layout {
+            BilingualLayout::Interleaved => self
+                .pairs
+                .iter()
+                .map(|pair| format!("{}\n{}", pair.original, pair.translation))
+                .collect::<Vec<_>>()
+                .join("\n\n"),
+            BilingualLayout::SideBySide => self
+                .pairs
+                .iter()
+                .map(|pair| {
+                    let left = wrap(&pair.original, COLUMN_WIDTH);
+                    let right = wrap(&pair.translation, COLUMN_WIDTH);
+                    (0..left.len().max(right.len()))
+                        .map(|row| {
+                            let left = left.get(row).map(String::as_str).unwrap_or_default();
+                            let right = right.get(row).map(String::as_str).unwrap_or_default();
+                            let padding = " ".repeat(COLUMN_WIDTH.saturating_sub(display_width(left)));
+                            format!("{}{}{}{}", left, padding, COLUMN_GAP, right).trim_end().to_string()
+                        })
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                })
+                .collect::<Vec<_>>()
+                .join("\n\n"),
+        }
+    }
+
+    fn render_markdown(&self, layout: BilingualLayout) -> String {
+        match layout {
+            BilingualLayout::Interleaved => self
+                .pairs
+                .iter()
+                .map(|pair| format!("{}\n\n> {}", pair.original, pair.translation))
+                .collect::<Vec<_>>()
+                .join("\n\n"),
+            BilingualLayout::SideBySide => {
+                let cell = |text: &str| text.replace('|', "\\|");
+                let mut table = format!(
+                    "| {} | {} |\n| --- | --- |",
+                    self.source_language, self.target_language
+                );
+                for pair in &self.pairs {
+                    table.push_str(&format!("\n| {} | {} |", cell(&pair.original), cell(&pair.translation)));
+                }
+                table
+            }
+        }
+    }
+
+    fn render_html(&self, layout: BilingualLayout) -> String {
+        let source = sanitize_for_html(&self.source_language);
+        let target = sanitize_for_html(&self.target_language);
+        match layout {
+            BilingualLayout::Interleaved => {
+                let mut html = String::from("<div class=\"bilingual interleaved\">");
+                for pair in &self.pairs {
+                    html.push_str(&format!(
+                        "<p class=\"original\" lang=\"{}\" dir=\"auto\">{}</p><p class=\"translation\" lang=\"{}\" dir=\"auto\">{}</p>",
+                        source,
+                        sanitize_for_html(&pair.original),
+                        target,
+                        sanitize_for_html(&pair.translation)
+                    ));
+                }
+                html.push_str("</div>");
+                html
+            }
+            BilingualLayout::SideBySide => {
+                let mut html = format!(
+                    "<table class=\"bilingual side-by-side\"><thead><tr><th>{}</th><th>{}</th></tr></thead><tbody>",
+                    source, target
+                );
+                for pair in &self.pairs {
+                    html.push_str(&format!(
+                        "<tr><td lang=\"{}\" dir=\"auto\">{}</td><td lang=\"{}\" dir=\"auto\">{}</td></tr>",
+                        source,
+                        sanitize_for_html(&pair.original),
+                        target,
+                        sanitize_for_html(&pair.translation)
+                    ));
+                }
+                html.push_str("</tbody></table>");
+                html
+            }
+        }
+    }
+
+    /// Spoken output is always interleaved, each sentence read in its own language
+    fn render_ssml(&self) -> String {
+        let source = sanitize_for_html(&self.source_language);
+        let target = sanitize_for_html(&self.target_language);
+        let mut ssml = String::from("<speak>");
+        for pair in &self.pairs {
+            ssml.push_str(&format!(
+                "<lang xml:lang=\"{}\">{}</lang><break time=\"400ms\"/><lang xml:lang=\"{}\">{}</lang><break time=\"800ms\"/>",
+                source,
+                sanitize_for_html(&pair.original),
+                target,
+                sanitize_for_html(&pair.translation)
+            ));
+        }
+        ssml.push_str("</speak>");
+        ssml
+    }
+}
+
+/// Lines of at most `width` columns; CJK breaks between characters
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    for unit in break_units(text) {
+        let space = !line.is_empty() && unit.space_before;
+        if !line.is_empty() && display_width(&line) + usize::from(space) + display_width(unit.text) > width {
+            lines.push(std::mem::take(&mut line));
+        } else if space {
+            line.push(' ');
+        }
+        line.push_str(unit.text);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(original: &str, translation: &str) -> Vec<(String, String)> {
+        BilingualText::align(original, translation, "en", "de")
+            .pairs
+            .into_iter()
+            .map(|pair| (pair.original, pair.translation))
+            .collect()
+    }
+
+    fn pair(original: &str, translation: &str) -> (String, String) {
+        (original.to_string(), translation.to_string())
+    }
+
+    #[test]
+    fn equal_counts_pair_one_to_one() {
+        assert_eq!(
+            pairs("Hello there. How are you?", "Hallo. Wie geht es dir?"),
+            vec![pair("Hello there.", "Hallo."), pair("How are you?", "Wie geht es dir?")]
+        );
+    }
+
+    #[test]
+    fn merged_sentences_keep_their_originals_together() {
+        assert_eq!(
+            pairs(
+                "I woke up. It was raining. We stayed in.",
+                "Ich wachte auf und es regnete. Wir blieben drinnen."
+            ),
+            vec![
+                pair("I woke up. It was raining.", "Ich wachte auf und es regnete."),
+                pair("We stayed in.", "Wir blieben drinnen."),
+            ]
+        );
+    }
+
+    #[test]
+    fn a_split_sentence_keeps_its_translations_together() {
+        assert_eq!(
+            pairs("We stayed in because it was raining.", "Wir blieben drinnen. Es regnete."),
+            vec![pair("We stayed in because it was raining.", "Wir blieben drinnen. Es regnete.")]
+        );
+    }
+
+    #[test]
+    fn trailing_originals_join_the_last_pair() {
+        assert_eq!(
+            pairs("A short one. Another short one. And a third.", "Alles in einem Satz."),
+            vec![pair("A short one. Another short one. And a third.", "Alles in einem Satz.")]
+        );
+    }
+
+    #[test]
+    fn an_empty_translation_still_pairs_the_original() {
+        assert_eq!(pairs("One. Two.", "  "), vec![pair("One. Two.", "")]);
+    }
+
+    #[test]
+    fn interleaved_plain_text_puts_each_translation_under_its_sentence() {
+        let text = BilingualText::align("Hello there. How are you?", "Hallo. Wie geht es dir?", "en", "de");
+        assert_eq!(
+            text.render(BilingualLayout::Interleaved, &OutputFormat::PlainText),
+            "Hello there.\nHallo.\n\nHow are you?\nWie geht es dir?"
+        );
+    }
+}
@@ -0,0 +1,233 @@
+// Caption Profanity Filter Module
+// On-device masking for live captions, applied to every interim hypothesis before it is broadcast
+
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{AppError, ValidationError};
+
+const MAX_CUSTOM_WORDS: usize = 500;
+const MAX_WORD_CHARS: usize = 64;
+
+/// Endings stripped to find the listed stem, longest first
+const SUFFIXES: &[&str] = &["'s", "ings", "ing", "ers", "er", "ed", "es", "in", "s", "y"];
+
+/// Built-in list; slurs and other audience-specific terms belong in `custom_words`
+const BUILT_IN_WORDS: &[(&str, CaptionSeverity)] = &[
+    ("damn", CaptionSeverity::Mild),
+    ("hell", CaptionSeverity::Mild),
+    ("crap", CaptionSeverity::Mild),
+    ("piss", CaptionSeverity::Mild),
+    ("bloody", CaptionSeverity::Mild),
+    ("bugger", CaptionSeverity::Mild),
+    ("arse", CaptionSeverity::Mild),
+    ("ass", CaptionSeverity::Moderate),
+    ("asshole", CaptionSeverity::Moderate),
+    ("shit", CaptionSeverity::Moderate),
+    ("bullshit", CaptionSeverity::Moderate),
+    ("bitch", CaptionSeverity::Moderate),
+    ("bastard", CaptionSeverity::Moderate),
+    ("dick", CaptionSeverity::Moderate),
+    ("dickhead", CaptionSeverity::Moderate),
+    ("prick", CaptionSeverity::Moderate),
+    ("wanker", CaptionSeverity::Moderate),
+    ("slut", CaptionSeverity::Moderate),
+    ("whore", CaptionSeverity::Moderate),
+    ("fuck", CaptionSeverity::Severe),
+    ("motherfucker", CaptionSeverity::Severe),
+    ("cunt", CaptionSeverity::Severe),
+    ("twat", CaptionSeverity::Severe),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptionSeverity {
+    Mild,
+    Moderate,
+    Severe,
+}
+
+/// What a masked word looks like on air
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptionMaskStyle {
+    /// "f***"
+    FirstLetter,
+    /// "****"
+    Full,
+    /// Dropped from the caption altogether
+    Remove,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CaptionWord {
+    pub word: String,
+    pub severity: CaptionSeverity,
+}
+
+/// Caption-only profanity rules, separate from the output content filter
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CaptionFilterSettings {
+    pub enabled: bool,
+    /// Words at this severity and above are masked
+    pub min_severity: CaptionSeverity,
+    pub mask_style: CaptionMaskStyle,
+    pub use_built_in_list: bool,
+    /// Added to the built-in list, or overriding its severity for the same word
+    pub custom_words: Vec<CaptionWord>,
+    /// Never masked, e.g. "hell" for a sermon stream
+    pub allowed_words: Vec<String>,
+}
+
+impl Default for CaptionFilterSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_severity: CaptionSeverity::Moderate,
+            mask_style: CaptionMaskStyle::FirstLetter,
+            use_built_in_list: true,
+            custom_words: Vec::new(),
+            allowed_words: Vec::new(),
+        }
+    }
+}
+
+pub fn validate(settings: &CaptionFilterSettings) -> Result<(), AppError> {
+    let invalid = |message: String| AppError::Validation(ValidationError::InvalidConfigValue(message));
+    if settings.custom_words.len() > MAX_CUSTOM_WORDS {
+        return Err(invalid(format!("At most {} custom caption words are allowed", MAX_CUSTOM_WORDS)));
+    }
+    let words = settings
+        .custom_words
+        .iter()
+        .map(|entry| &entry.word)
+        .chain(settings.allowed_words.iter());
+    for word in words {
+        let word = word.trim();
+        if word.is_empty() || word.chars().count() > MAX_WORD_CHARS {
+            return Err(invalid(format!(
+                "Caption filter words must be 1-{} characters",
+                MAX_WORD_CHARS
+            )));
+        }
+        if word.chars().any(|c| !is_word_char(c)) {
+            return Err(invalid(format!("Caption filter word '{}' must be a single word", word)));
+        }
+    }
+    Ok(())
+}
+
+/// Compiled word list; built once per settings change so masking an interim update is a single pass
+#[derive(Debug)]
+pub struct CaptionFilter {
+    enabled: bool,
+    mask_style: CaptionMaskStyle,
+    words: HashMap<String, CaptionSeverity>,
+    allowed: HashSet<String>,
+}
+
+impl CaptionFilter {
+    pub fn new(settings: &CaptionFilterSettings) -> Self {
+        let built_in = BUILT_IN_WORDS
+            .iter()
+            .filter(|_| settings.use_built_in_list)
+            .map(|(word, severity)| (word.to_string(), *severity));
+        let custom = settings
+            .custom_words
+            .iter()
+            .map(|entry| (entry.word.trim().to_lowercase(), entry.severity));
+        let mut words: HashMap<String, CaptionSeverity> = built_in.chain(custom).collect();
+        words.retain(|_, severity| *severity >= settings.min_severity);
+        Self {
+            enabled: settings.enabled,
+            mask_style: settings.mask_style,
+            words,
+            allowed: settings.allowed_words.iter().map(|w| w.trim().to_lowercase()).collect(),
+        }
+    }
+
+    /// Mask listed words in `text`, returning the text and how many words were masked
+    pub fn apply(&self, text: &str) -> (String, usize) {
+        if !self.enabled || self.words.is_empty() {
+            return (text.to_string(), 0);
+        }
+        let style = self.mask_style;
+        let mut output = String::with_capacity(text.len());
+        let mut masked = 0;
+        let mut lowered = String::new();
+        let mut rest = text;
+        while !rest.is_empty() {
+            let word_len = rest.find(|c: char| !is_word_char(c)).unwrap_or(rest.len());
+            if word_len == 0 {
+                let gap_len = rest.find(is_word_char).unwrap_or(rest.len());
+                output.push_str(&rest[..gap_len]);
+                rest = &rest[gap_len..];
+                continue;
+            }
+
+            let word = &rest[..word_len];
+            lowered.clear();
+            lowered.extend(word.chars().flat_map(char::to_lowercase));
+            if self.is_listed(&lowered) {
+                masked += 1;
+                push_masked(&mut output, word, style);
+            } else {
+                output.push_str(word);
+            }
+            rest = &rest[word_len..];
+        }
+
+        if style == CaptionMaskStyle::Remove && masked > 0 {
+            output = output.split_whitespace().collect::<Vec<_>>().join(" ");
+            // A removed word can leave a space before punctuation
+            for mark in [",", ".", ";", ":", "!", "?"] {
+                output = output.replace(&format!(" {}", mark), mark);
+            }
+        }
+        (output, masked)
+    }
+
+    fn is_listed(&self, word: &str) -> bool {
+        if self.allowed.contains(word) {
+            return false;
+        }
+        if self.words.contains_key(word) {
+            return true;
+        }
+        SUFFIXES.iter().any(|suffix| {
+            let Some(stem) = word.strip_suffix(suffix) else {
+                return false;
+            };
+            if stem.len() < 3 {
+                return false;
+            }
+            // "shitty", "twatting" and the like double the last letter before the suffix
+            let mut last = stem.char_indices().rev();
+            let undoubled = match (last.next(), last.next()) {
+                (Some((at, a)), Some((_, b))) if a == b => Some(&stem[..at]),
+                _ => None,
+            };
+            self.words.contains_key(stem) || undoubled.map_or(false, |stem| self.words.contains_key(stem))
+        })
+    }
+}
+
+fn push_masked(output: &mut String, word: &str, style: CaptionMaskStyle) {
+    match style {
+        CaptionMaskStyle::FirstLetter => {
+            let mut chars = word.chars();
+            if let Some(first) = chars.next() {
+                output.push(first);
+            }
+            output.extend(chars.map(|_| '*'));
+        }
+        CaptionMaskStyle::Full => output.extend(word.chars().map(|_| '*')),
+        CaptionMaskStyle::Remove => {}
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '\''
+}
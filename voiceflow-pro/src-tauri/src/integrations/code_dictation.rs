@@ -0,0 +1,507 @@
+// Code Dictation Module
+// Turns spoken code into source text: symbol names become tokens, words become identifiers in the language's style
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CodeLanguage {
+    Rust,
+    Python,
+    JavaScript,
+    TypeScript,
+    Go,
+    Java,
+    CSharp,
+    Cpp,
+    Ruby,
+    Shell,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NamingStyle {
+    Camel,
+    Snake,
+    Pascal,
+    ScreamingSnake,
+    Kebab,
+}
+
+impl CodeLanguage {
+    /// How multi-word variable and function names are written
+    pub fn identifier_style(&self) -> NamingStyle {
+        match self {
+            CodeLanguage::Rust | CodeLanguage::Python | CodeLanguage::Ruby | CodeLanguage::Shell => NamingStyle::Snake,
+            _ => NamingStyle::Camel,
+        }
+    }
+
+    /// What "arrow" means: closures and switch arms in some languages, return types and members in others
+    fn arrow(&self) -> &'static str {
+        match self {
+            CodeLanguage::JavaScript | CodeLanguage::TypeScript | CodeLanguage::CSharp => "=>",
+            _ => "->",
+        }
+    }
+
+    fn line_comment(&self) -> &'static str {
+        match self {
+            CodeLanguage::Python | CodeLanguage::Ruby | CodeLanguage::Shell => "#",
+            _ => "//",
+        }
+    }
+
+    fn keywords(&self) -> &'static [&'static str] {
+        match self {
+            CodeLanguage::Rust => &[
+                "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "false", "fn",
+                "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return",
+                "self", "static", "struct", "trait", "true", "type", "unsafe", "use", "where", "while",
+            ],
+            CodeLanguage::Python => &[
+                "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del", "elif", "else",
+                "except", "finally", "for", "from", "global", "if", "import", "in", "is", "lambda", "none", "not",
+                "or", "pass", "raise", "return", "self", "true", "false", "try", "while", "with", "yield",
+            ],
+            CodeLanguage::JavaScript | CodeLanguage::TypeScript => &[
+                "async", "await", "break", "case", "catch", "class", "const", "continue", "default", "delete", "do",
+                "else", "export", "extends", "false", "finally", "for", "from", "function", "if", "import", "in",
+                "instanceof", "interface", "let", "new", "null", "of", "return", "switch", "this", "throw", "true",
+                "try", "type", "typeof", "undefined", "var", "void", "while", "yield",
+            ],
+            CodeLanguage::Go => &[
+                "break", "case", "chan", "const", "continue", "default", "defer", "else", "false", "for", "func",
+                "go", "if", "import", "interface", "map", "nil", "package", "range", "return", "select", "struct",
+                "switch", "true", "type", "var",
+            ],
+            CodeLanguage::Java | CodeLanguage::CSharp => &[
+                "abstract", "async", "await", "boolean", "bool", "break", "case", "catch", "class", "const",
+                "continue", "default", "do", "else", "enum", "extends", "false", "final", "finally", "for",
+                "foreach", "if", "implements", "import", "in", "int", "interface", "namespace", "new", "null",
+                "private", "protected", "public", "return", "static", "string", "switch", "this", "throw", "true",
+                "try", "using", "var", "void", "while",
+            ],
+            CodeLanguage::Cpp => &[
+                "auto", "bool", "break", "case", "catch", "char", "class", "const", "continue", "default", "delete",
+                "do", "double", "else", "enum", "false", "float", "for", "if", "include", "int", "namespace", "new",
+                "nullptr", "private", "public", "return", "static", "struct", "switch", "template", "this", "throw",
+                "true", "try", "typename", "using", "void", "while",
+            ],
+            CodeLanguage::Ruby => &[
+                "begin", "break", "case", "class", "def", "do", "else", "elsif", "end", "ensure", "false", "for",
+                "if", "in", "module", "next", "nil", "not", "or", "and", "rescue", "return", "self", "then", "true",
+                "unless", "until", "when", "while", "yield",
+            ],
+            CodeLanguage::Shell => &[
+                "case", "do", "done", "echo", "elif", "else", "esac", "export", "fi", "for", "function", "if", "in",
+                "local", "return", "then", "while",
+            ],
+        }
+    }
+
+    /// Keywords after which the next name is a type
+    fn type_keywords(&self) -> &'static [&'static str] {
+        &["class", "struct", "enum", "trait", "interface", "impl", "extends", "implements", "new"]
+    }
+
+    /// Keywords that open a block and need a trailing colon
+    fn colon_block_keywords(&self) -> &'static [&'static str] {
+        match self {
+            CodeLanguage::Python => &[
+                "if", "elif", "else", "for", "while", "def", "class", "try", "except", "finally", "with",
+            ],
+            _ => &[],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CodeDictationSettings {
+    /// Treat dictation as code instead of prose
+    pub enabled: bool,
+    pub language: CodeLanguage,
+    /// Keep code with syntax problems from being typed; it is still reported
+    pub hold_invalid: bool,
+}
+
+impl Default for CodeDictationSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            language: CodeLanguage::TypeScript,
+            hold_invalid: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyntaxIssue {
+    /// 1-based position in the formatted code
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CodeFormatResult {
+    pub spoken: String,
+    pub code: String,
+    pub language: CodeLanguage,
+    pub issues: Vec<SyntaxIssue>,
+    pub valid: bool,
+}
+
+/// Spoken names of symbols, longest phrases first so "double equals" is not read as "double" + "equals"
+const SYMBOLS: &[(&str, &str)] = &[
+    ("not equals", "!="),
+    ("triple equals", "==="),
+    ("double equals", "=="),
+    ("less or equal", "<="),
+    ("greater or equal", ">="),
+    ("plus equals", "+="),
+    ("minus equals", "-="),
+    ("fat arrow", "=>"),
+    ("double colon", "::"),
+    ("and and", "&&"),
+    ("or or", "||"),
+    ("open paren", "("),
+    ("close paren", ")"),
+    ("open brace", "{"),
+    ("close brace", "}"),
+    ("open bracket", "["),
+    ("close bracket", "]"),
+    ("open angle", "<"),
+    ("close angle", ">"),
+    ("less than", "<"),
+    ("greater than", ">"),
+    ("equal sign", "="),
+    ("question mark", "?"),
+    ("at sign", "@"),
+    ("dollar sign", "$"),
+    ("new line", "\n"),
+    ("equals", "="),
+    ("plus", "+"),
+    ("minus", "-"),
+    ("star", "*"),
+    ("times", "*"),
+    ("slash", "/"),
+    ("backslash", "\\"),
+    ("percent", "%"),
+    ("colon", ":"),
+    ("semicolon", ";"),
+    ("comma", ","),
+    ("dot", "."),
+    ("ampersand", "&"),
+    ("pipe", "|"),
+    ("bang", "!"),
+    ("hash", "#"),
+    ("underscore", "_"),
+    ("tilde", "~"),
+    ("caret", "^"),
+    ("tab", "\t"),
+];
+
+const CASE_COMMANDS: &[(&str, NamingStyle)] = &[
+    ("camel case", NamingStyle::Camel),
+    ("snake case", NamingStyle::Snake),
+    ("pascal case", NamingStyle::Pascal),
+    ("constant case", NamingStyle::ScreamingSnake),
+    ("kebab case", NamingStyle::Kebab),
+];
+
+const QUOTES: &[(&str, char)] = &[("single quote", '\''), ("backtick", '`'), ("quote", '"')];
+const STRING_END: &[(&str, ())] = &[("end quote", ()), ("close quote", ()), ("end string", ())];
+const FILLERS: &[&str] = &["um", "uh", "er", "erm", "hmm"];
+/// Symbols never preceded by a space
+const TIGHT_BEFORE: &[&str] = &[")", "]", ",", ";", ".", "::", ":", "?", "\n"];
+/// Symbols never followed by a space
+const TIGHT_AFTER: &[&str] = &["(", "[", ".", "::", "!", "@", "$", "\n", "\t", "_", "~"];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Keyword(String),
+    Name(String),
+    Symbol(String),
+}
+
+/// Format spoken code for `language` and check the result
+pub fn format_code(spoken: &str, language: CodeLanguage) -> CodeFormatResult {
+    let code = render(&tokenize(spoken, language));
+    let issues = validate_syntax(&code, language);
+    CodeFormatResult {
+        spoken: spoken.to_string(),
+        valid: issues.is_empty(),
+        code,
+        language,
+        issues,
+    }
+}
+
+fn tokenize(spoken: &str, language: CodeLanguage) -> Vec<Token> {
+    // Recognizers add sentence punctuation that means nothing here. Commands are matched lowercased,
+    // while string literals keep the words as dictated
+    let (words, dictated): (Vec<String>, Vec<&str>) = spoken
+        .split_whitespace()
+        .map(|word| {
+            let trimmed = word.trim_end_matches(['.', ',', '?', '!']);
+            let word = if trimmed.is_empty() { word } else { trimmed };
+            (word.to_lowercase(), word)
+        })
+        .filter(|(word, _)| !FILLERS.contains(&word.as_str()))
+        .unzip();
+    let keywords = language.keywords();
+
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < words.len() {
+        let rest = &words[i..];
+        if let Some((len, quote)) = match_phrase(rest, QUOTES) {
+            i += len;
+            let mut literal = Vec::new();
+            while i < words.len() {
+                if let Some((len, ())) = match_phrase(&words[i..], STRING_END) {
+                    i += len;
+                    break;
+                }
+                if let Some((len, closing)) = match_phrase(&words[i..], QUOTES) {
+                    if closing == quote {
+                        i += len;
+                        break;
+                    }
+                }
+                literal.push(dictated[i]);
+                i += 1;
+            }
+            tokens.push(Token::Name(format!("{}{}{}", quote, literal.join(" "), quote)));
+            continue;
+        }
+        if let Some((len, style)) = match_phrase(rest, CASE_COMMANDS) {
+            i += len;
+            let start = i;
+            while i < words.len() && !is_command(&words[i..]) {
+                i += 1;
+            }
+            if i > start {
+                tokens.push(Token::Name(apply_style(&words[start..i], style)));
+            }
+            continue;
+        }
+        if rest[0] == "arrow" {
+            tokens.push(Token::Symbol(language.arrow().to_string()));
+            i += 1;
+            continue;
+        }
+        if let Some((len, symbol)) = match_phrase(rest, SYMBOLS) {
+            tokens.push(Token::Symbol(symbol.to_string()));
+            i += len;
+            continue;
+        }
+        if (rest[0] == "literal" || rest[0] == "verbatim") && rest.len() > 1 {
+            tokens.push(Token::Name(rest[1].clone()));
+            i += 2;
+            continue;
+        }
+        // Symbols the recognizer already wrote out
+        if rest[0].chars().all(|c| c.is_ascii_punctuation()) {
+            tokens.push(Token::Symbol(rest[0].clone()));
+            i += 1;
+            continue;
+        }
+        if keywords.contains(&rest[0].as_str()) || rest[0].chars().all(|c| !c.is_alphabetic()) {
+            tokens.push(Token::Keyword(rest[0].clone()));
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        while i < words.len()
+            && !is_command(&words[i..])
+            && !keywords.contains(&words[i].as_str())
+            && words[i].chars().any(char::is_alphabetic)
+        {
+            i += 1;
+        }
+        let after_type_keyword = matches!(
+            tokens.last(),
+            Some(Token::Keyword(keyword)) if language.type_keywords().contains(&keyword.as_str())
+        );
+        let style = if after_type_keyword {
+            NamingStyle::Pascal
+        } else {
+            language.identifier_style()
+        };
+        tokens.push(Token::Name(apply_style(&words[start..i], style)));
+    }
+    tokens
+}
+
+/// Whether the words start with a symbol, quote or casing command
+fn is_command(words: &[String]) -> bool {
+    words[0] == "arrow"
+        || words[0] == "literal"
+        || words[0] == "verbatim"
+        || match_phrase(words, SYMBOLS).is_some()
+        || match_phrase(words, QUOTES).is_some()
+        || match_phrase(words, CASE_COMMANDS).is_some()
+}
+
+fn match_phrase<T: Copy>(words: &[String], table: &[(&str, T)]) -> Option<(usize, T)> {
+    table.iter().find_map(|(phrase, value)| {
+        let parts: Vec<&str> = phrase.split(' ').collect();
+        let matches = parts.len() <= words.len() && parts.iter().zip(words).all(|(part, word)| part == word);
+        matches.then_some((parts.len(), *value))
+    })
+}
+
+fn apply_style(words: &[String], style: NamingStyle) -> String {
+    let capitalized = |word: &String| {
+        let mut chars = word.chars();
+        chars
+            .next()
+            .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+            .unwrap_or_default()
+    };
+    match style {
+        NamingStyle::Camel => words
+            .iter()
+            .enumerate()
+            .map(|(index, word)| if index == 0 { word.clone() } else { capitalized(word) })
+            .collect(),
+        NamingStyle::Pascal => words.iter().map(capitalized).collect(),
+        NamingStyle::Snake => words.join("_"),
+        NamingStyle::ScreamingSnake => words.join("_").to_uppercase(),
+        NamingStyle::Kebab => words.join("-"),
+    }
+}
+
+fn render(tokens: &[Token]) -> String {
+    let mut code = String::new();
+    let mut previous: Option<&Token> = None;
+    for token in tokens {
+        let text = match token {
+            Token::Keyword(text) | Token::Name(text) | Token::Symbol(text) => text.as_str(),
+        };
+        let space = match previous {
+            None => false,
+            Some(Token::Symbol(before)) if TIGHT_AFTER.contains(&before.as_str()) => false,
+            _ if matches!(token, Token::Symbol(_)) && TIGHT_BEFORE.contains(&text) => false,
+            // Calls and indexing hug the name; keywords such as `if (` keep their space
+            Some(Token::Name(_)) | Some(Token::Symbol(_)) if text == "(" || text == "[" => {
+                matches!(previous, Some(Token::Symbol(before)) if !matches!(before.as_str(), ")" | "]"))
+            }
+            _ => true,
+        };
+        if space {
+            code.push(' ');
+        }
+        code.push_str(text);
+        previous = Some(token);
+    }
+    code
+}
+
+/// Problems that would stop the code from parsing: unbalanced brackets, unterminated strings,
+/// and for Python, block statements without their colon
+pub fn validate_syntax(code: &str, language: CodeLanguage) -> Vec<SyntaxIssue> {
+    let mut issues = Vec::new();
+    let mut open: Vec<(char, usize, usize)> = Vec::new();
+    // Rust uses the single quote for lifetimes as well as characters
+    let quotes: &[char] = match language {
+        CodeLanguage::Rust => &['"'],
+        CodeLanguage::JavaScript | CodeLanguage::TypeScript | CodeLanguage::Go | CodeLanguage::Shell => {
+            &['"', '\'', '`']
+        }
+        _ => &['"', '\''],
+    };
+    let comment = language.line_comment();
+    let mut in_string: Option<(char, usize, usize)> = None;
+
+    for (line_index, line) in code.lines().enumerate() {
+        let line_number = line_index + 1;
+        let mut chars = line.char_indices().peekable();
+        while let Some((offset, c)) = chars.next() {
+            let column = offset + 1;
+            if let Some((quote, ..)) = in_string {
+                if c == '\\' {
+                    chars.next();
+                } else if c == quote {
+                    in_string = None;
+                }
+                continue;
+            }
+            if line[offset..].starts_with(comment) {
+                break;
+            }
+            if quotes.contains(&c) {
+                in_string = Some((c, line_number, column));
+                continue;
+            }
+            match c {
+                '(' | '[' | '{' => open.push((c, line_number, column)),
+                ')' | ']' | '}' => {
+                    let expected = match c {
+                        ')' => '(',
+                        ']' => '[',
+                        _ => '{',
+                    };
+                    match open.pop() {
+                        Some((opener, ..)) if opener == expected => {}
+                        Some((opener, open_line, open_column)) => issues.push(SyntaxIssue {
+                            line: line_number,
+                            column,
+                            message: format!(
+                                "'{}' closes '{}' opened at {}:{}",
+                                c, opener, open_line, open_column
+                            ),
+                        }),
+                        None => issues.push(SyntaxIssue {
+                            line: line_number,
+                            column,
+                            message: format!("'{}' has nothing to close", c),
+                        }),
+                    }
+                }
+                _ => {}
+            }
+        }
+        // Only template literals may continue onto the next line
+        if let Some((quote, string_line, string_column)) = in_string {
+            if quote != '`' {
+                issues.push(SyntaxIssue {
+                    line: string_line,
+                    column: string_column,
+                    message: format!("String starting with {} is not closed", quote),
+                });
+                in_string = None;
+            }
+        }
+
+        let trimmed = line.trim();
+        let first_word = trimmed.split(|c: char| !c.is_alphanumeric() && c != '_').next().unwrap_or_default();
+        if language.colon_block_keywords().contains(&first_word) && !trimmed.ends_with(':') {
+            issues.push(SyntaxIssue {
+                line: line_number,
+                column: line.trim_end().len(),
+                message: format!("'{}' statement needs a ':' at the end of the line", first_word),
+            });
+        }
+    }
+
+    if let Some((quote, line, column)) = in_string {
+        issues.push(SyntaxIssue {
+            line,
+            column,
+            message: format!("String starting with {} is not closed", quote),
+        });
+    }
+    for (opener, line, column) in open {
+        issues.push(SyntaxIssue {
+            line,
+            column,
+            message: format!("'{}' is never closed", opener),
+        });
+    }
+    issues
+}
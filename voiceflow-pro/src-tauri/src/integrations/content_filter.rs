@@ -0,0 +1,350 @@
+// Content Filter Module
+// Final post-processing stage applied to text before it is injected, copied or spoken
+
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// Built-in profanity stems; inflected forms are matched by suffix
+const PROFANITY_STEMS: &[&str] = &[
+    "fuck", "shit", "bitch", "bastard", "asshole", "cunt", "motherfucker", "bullshit", "dickhead",
+    "piss", "crap", "wanker", "twat", "prick",
+];
+
+/// Where filtered text is going
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputTarget {
+    Injection,
+    Clipboard,
+    Speech,
+}
+
+/// How banned phrases are handled
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum BannedPhraseAction {
+    Remove,
+    Mask,
+    Replace(String),
+}
+
+/// Filter rules for one profile
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContentFilterConfig {
+    pub enabled: bool,
+    pub mask_profanity: bool,
+    /// Extra words masked alongside the built-in list
+    pub custom_profanity: Vec<String>,
+    pub banned_phrases: Vec<String>,
+    pub banned_phrase_action: BannedPhraseAction,
+    pub max_length: Option<usize>,
+    pub truncation_notice: String,
+    pub targets: Vec<OutputTarget>,
+}
+
+impl Default for ContentFilterConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            mask_profanity: false,
+            custom_profanity: Vec::new(),
+            banned_phrases: Vec::new(),
+            banned_phrase_action: BannedPhraseAction::Remove,
+            max_length: None,
+            truncation_notice: " […truncated]".to_string(),
+            targets: vec![OutputTarget::Injection, OutputTarget::Clipboard, OutputTarget::Speech],
+        }
+    }
+}
+
+/// Named filter profiles with one active at a time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContentFilterSettings {
+    pub active_profile: String,
+    pub profiles: HashMap<String, ContentFilterConfig>,
+}
+
+impl Default for ContentFilterSettings {
+    fn default() -> Self {
+        let mut profiles = HashMap::new();
+        profiles.insert("default".to_string(), ContentFilterConfig::default());
+        Self {
+            active_profile: "default".to_string(),
+            profiles,
+        }
+    }
+}
+
+impl ContentFilterSettings {
+    /// Rules for `profile`, or for the active profile when `None`
+    pub fn config_for(&self, profile: Option<&str>) -> ContentFilterConfig {
+        let name = profile.unwrap_or(&self.active_profile);
+        self.profiles
+            .get(name)
+            .or_else(|| self.profiles.get("default"))
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+/// Outcome of filtering a piece of text
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContentFilterResult {
+    pub text: String,
+    pub target: OutputTarget,
+    pub original_length: usize,
+    pub profanity_masked: usize,
+    pub banned_phrases_found: Vec<String>,
+    pub truncated: bool,
+    /// Human-readable summary of what changed, for a toast or status line
+    pub notice: Option<String>,
+}
+
+impl ContentFilterResult {
+    pub fn changed(&self) -> bool {
+        self.profanity_masked > 0 || !self.banned_phrases_found.is_empty() || self.truncated
+    }
+}
+
+/// Apply the filter rules to text bound for `target`
+pub fn apply_content_filter(text: &str, config: &ContentFilterConfig, target: OutputTarget) -> ContentFilterResult {
+    let mut result = ContentFilterResult {
+        text: text.to_string(),
+        target,
+        original_length: text.chars().count(),
+        profanity_masked: 0,
+        banned_phrases_found: Vec::new(),
+        truncated: false,
+        notice: None,
+    };
+
+    if !config.enabled || !config.targets.contains(&target) {
+        return result;
+    }
+
+    for phrase in config.banned_phrases.iter().filter(|p| !p.trim().is_empty()) {
+        let Some(pattern) = phrase_regex(phrase) else {
+            continue;
+        };
+        if !pattern.is_match(&result.text) {
+            continue;
+        }
+        result.banned_phrases_found.push(phrase.clone());
+        result.text = match &config.banned_phrase_action {
+            BannedPhraseAction::Remove => pattern.replace_all(&result.text, "").into_owned(),
+            BannedPhraseAction::Mask => pattern
+                .replace_all(&result.text, |caps: &regex::Captures| "*".repeat(caps[0].chars().count()))
+                .into_owned(),
+            BannedPhraseAction::Replace(replacement) => {
+                pattern.replace_all(&result.text, regex::NoExpand(replacement)).into_owned()
+            }
+        };
+    }
+    if !result.banned_phrases_found.is_empty() && config.banned_phrase_action == BannedPhraseAction::Remove {
+        result.text = tidy_whitespace(&result.text);
+    }
+
+    if config.mask_profanity {
+        let (masked, count) = mask_profanity(&result.text, &config.custom_profanity);
+        result.text = masked;
+        result.profanity_masked = count;
+    }
+
+    if let Some(max_length) = config.max_length.filter(|max| *max > 0) {
+        if result.text.chars().count() > max_length {
+            result.text = truncate_at_word(&result.text, max_length);
+            result.text.push_str(&config.truncation_notice);
+            result.truncated = true;
+        }
+    }
+
+    result.notice = describe_changes(&result, config.max_length);
+    result
+}
+
+/// Kinds of personal data `redact_pii` can find
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PiiKind {
+    Email,
+    /// Card numbers that pass the Luhn check
+    CreditCard,
+    /// US social security numbers in their dashed form
+    NationalId,
+    Phone,
+    IpAddress,
+}
+
+impl PiiKind {
+    pub const ALL: [PiiKind; 5] = [
+        PiiKind::Email,
+        PiiKind::CreditCard,
+        PiiKind::NationalId,
+        PiiKind::Phone,
+        PiiKind::IpAddress,
+    ];
+
+    fn pattern(self) -> &'static str {
+        match self {
+            PiiKind::Email => r"(?i)\b[a-z0-9._%+-]+@[a-z0-9.-]+\.[a-z]{2,}\b",
+            PiiKind::CreditCard => r"\b\d(?:[ -]?\d){12,18}\b",
+            PiiKind::NationalId => r"\b\d{3}-\d{2}-\d{4}\b",
+            PiiKind::Phone => r"(?:\+\d{1,3}[ .-]?)?(?:\(\d{2,4}\)[ .-]?)?\d{2,4}(?:[ .-]?\d{2,4}){2,3}\b",
+            PiiKind::IpAddress => r"\b(?:(?:25[0-5]|2[0-4]\d|1?\d?\d)\.){3}(?:25[0-5]|2[0-4]\d|1?\d?\d)\b",
+        }
+    }
+
+    fn placeholder(self) -> &'static str {
+        match self {
+            PiiKind::Email => "[email]",
+            PiiKind::CreditCard => "[card number]",
+            PiiKind::NationalId => "[id number]",
+            PiiKind::Phone => "[phone]",
+            PiiKind::IpAddress => "[ip address]",
+        }
+    }
+
+    /// Rejects matches of the pattern that are not really this kind, e.g. numbers failing the card checksum
+    fn accepts(self, matched: &str) -> bool {
+        let digits: Vec<u32> = matched.chars().filter_map(|c| c.to_digit(10)).collect();
+        match self {
+            PiiKind::CreditCard => luhn_valid(&digits),
+            // Shorter runs are more likely amounts, years or times than phone numbers
+            PiiKind::Phone => digits.len() >= 9 && digits.len() <= 15,
+            _ => true,
+        }
+    }
+}
+
+/// Outcome of redacting personal data
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PiiRedaction {
+    pub text: String,
+    pub found: BTreeMap<PiiKind, usize>,
+}
+
+impl PiiRedaction {
+    pub fn changed(&self) -> bool {
+        !self.found.is_empty()
+    }
+}
+
+/// Replace personal data of the given kinds with placeholders such as "[email]".
+/// Kinds are applied in `PiiKind::ALL` order so card and id numbers are not mistaken for phone numbers.
+pub fn redact_pii(text: &str, kinds: &[PiiKind]) -> PiiRedaction {
+    let mut redaction = PiiRedaction {
+        text: text.to_string(),
+        found: BTreeMap::new(),
+    };
+    for kind in PiiKind::ALL.into_iter().filter(|kind| kinds.contains(kind)) {
+        let Ok(regex) = Regex::new(kind.pattern()) else {
+            continue;
+        };
+        let mut count = 0;
+        redaction.text = regex
+            .replace_all(&redaction.text, |caps: &regex::Captures| {
+                if kind.accepts(&caps[0]) {
+                    count += 1;
+                    kind.placeholder().to_string()
+                } else {
+                    caps[0].to_string()
+                }
+            })
+            .into_owned();
+        if count > 0 {
+            redaction.found.insert(kind, count);
+        }
+    }
+    redaction
+}
+
+fn luhn_valid(digits: &[u32]) -> bool {
+    if !(13..=19).contains(&digits.len()) {
+        return false;
+    }
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &d)| match i % 2 {
+            1 if d * 2 > 9 => d * 2 - 9,
+            1 => d * 2,
+            _ => d,
+        })
+        .sum();
+    sum % 10 == 0
+}
+
+fn phrase_regex(phrase: &str) -> Option<Regex> {
+    let escaped = regex::escape(phrase.trim());
+    // Only anchor on word boundaries where the phrase starts/ends with a word character
+    let starts_word = phrase.trim().chars().next().map(|c| c.is_alphanumeric()).unwrap_or(false);
+    let ends_word = phrase.trim().chars().last().map(|c| c.is_alphanumeric()).unwrap_or(false);
+    let pattern = format!(
+        "{}{}{}",
+        if starts_word { r"\b" } else { "" },
+        escaped,
+        if ends_word { r"\b" } else { "" }
+    );
+    RegexBuilder::new(&pattern).case_insensitive(true).build().ok()
+}
+
+fn mask_profanity(text: &str, custom: &[String]) -> (String, usize) {
+    let words: Vec<String> = PROFANITY_STEMS
+        .iter()
+        .map(|w| regex::escape(w))
+        .chain(custom.iter().filter(|w| !w.trim().is_empty()).map(|w| regex::escape(w.trim())))
+        .collect();
+    let pattern = format!(r"\b(?:{})(?:s|es|ed|er|ers|ing|ings|y)?\b", words.join("|"));
+    let Ok(regex) = RegexBuilder::new(&pattern).case_insensitive(true).build() else {
+        return (text.to_string(), 0);
+    };
+
+    let mut count = 0;
+    let masked = regex
+        .replace_all(text, |caps: &regex::Captures| {
+            count += 1;
+            let word = &caps[0];
+            let mut chars = word.chars();
+            let first = chars.next().map(String::from).unwrap_or_default();
+            first + &"*".repeat(chars.count())
+        })
+        .into_owned();
+    (masked, count)
+}
+
+/// Cut to at most `max_chars`, backing up to a word boundary when one is close
+fn truncate_at_word(text: &str, max_chars: usize) -> String {
+    let cut: String = text.chars().take(max_chars).collect();
+    match cut.rfind(char::is_whitespace) {
+        Some(pos) if cut[..pos].chars().count() >= max_chars * 4 / 5 => cut[..pos].trim_end().to_string(),
+        _ => cut,
+    }
+}
+
+fn tidy_whitespace(text: &str) -> String {
+    let collapsed = Regex::new(r"[ \t]{2,}").map(|re| re.replace_all(text, " ").into_owned());
+    let collapsed = collapsed.unwrap_or_else(|_| text.to_string());
+    // Removing a phrase can leave a space before punctuation
+    Regex::new(r" +([,.;:!?])")
+        .map(|re| re.replace_all(&collapsed, "$1").trim().to_string())
+        .unwrap_or(collapsed)
+}
+
+fn describe_changes(result: &ContentFilterResult, max_length: Option<usize>) -> Option<String> {
+    let mut parts = Vec::new();
+    if result.profanity_masked > 0 {
+        parts.push(format!("{} word(s) masked", result.profanity_masked));
+    }
+    if !result.banned_phrases_found.is_empty() {
+        parts.push(format!("{} banned phrase(s) filtered", result.banned_phrases_found.len()));
+    }
+    if result.truncated {
+        parts.push(format!(
+            "truncated from {} to {} characters",
+            result.original_length,
+            max_length.unwrap_or_default()
+        ));
+    }
+    (!parts.is_empty()).then(|| format!("Content filter: {}", parts.join(", ")))
+}
@@ -0,0 +1,119 @@
+// Shared Context Module
+// Conversation, session and user context passed between the gateway, context processor and commands
+
+use serde::{Deserialize, Serialize};
+
+/// Enhanced context for AI processing.
+/// Session and profile fields default when absent, so context saved before they existed still loads.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EnhancedContext {
+    #[serde(default)]
+    pub user_intent: Option<String>,
+    #[serde(default)]
+    pub domain: Option<String>,
+    #[serde(default)]
+    pub audience: Option<String>,
+    #[serde(default)]
+    pub purpose: Option<String>,
+    #[serde(default)]
+    pub constraints: Vec<String>,
+    #[serde(default)]
+    pub previous_messages: Vec<String>,
+    #[serde(default)]
+    pub conversation_history: Vec<String>,
+    #[serde(default)]
+    pub session_context: SessionContext,
+    #[serde(default)]
+    pub user_profile: UserProfile,
+    /// Text the user is looking at, read from the screen; given to the model as data, never as instructions
+    #[serde(default)]
+    pub screen_text: Option<String>,
+}
+
+/// Session context information
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionContext {
+    pub session_id: String,
+    pub start_time: u64,
+    pub interaction_count: u32,
+    pub topic_transitions: Vec<String>,
+    pub current_focus: Option<String>,
+    pub emotional_state: EmotionalState,
+}
+
+/// Emotional state tracking
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EmotionalState {
+    pub primary_emotion: String,
+    pub intensity: f32, // 0.0 to 1.0
+    pub stability: f32, // 0.0 to 1.0
+    pub trending: EmotionTrend,
+}
+
+impl Default for EmotionalState {
+    fn default() -> Self {
+        Self {
+            primary_emotion: "neutral".to_string(),
+            intensity: 0.5,
+            stability: 0.8,
+            trending: EmotionTrend::Stable,
+        }
+    }
+}
+
+/// Emotion trend tracking
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EmotionTrend {
+    Rising,
+    #[default]
+    Stable,
+    Falling,
+    Volatile,
+}
+
+/// User profile for personalization
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UserProfile {
+    pub language_preference: String,
+    pub communication_style: CommunicationStyle,
+    pub expertise_level: ExpertiseLevel,
+    pub cultural_background: Option<String>,
+    pub accessibility_needs: Vec<String>,
+}
+
+impl Default for UserProfile {
+    fn default() -> Self {
+        Self {
+            language_preference: "en".to_string(),
+            communication_style: CommunicationStyle::Casual,
+            expertise_level: ExpertiseLevel::Intermediate,
+            cultural_background: None,
+            accessibility_needs: Vec::new(),
+        }
+    }
+}
+
+/// Communication styles
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CommunicationStyle {
+    Formal,
+    Professional,
+    Casual,
+    Technical,
+    Creative,
+    Academic,
+    Conversational,
+}
+
+/// Expertise levels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExpertiseLevel {
+    Beginner,
+    Intermediate,
+    Advanced,
+    Expert,
+    Specialist,
+}
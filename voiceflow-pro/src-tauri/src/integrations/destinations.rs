@@ -0,0 +1,531 @@
+// Destinations Module
+// Hands processed text to other apps: email drafts, notes and calendar entries
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration as ChronoDuration, Timelike, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::path::PathBuf;
+
+use crate::errors::AppError;
+
+const GRAPH_MESSAGES_URL: &str = "https://graph.microsoft.com/v1.0/me/messages";
+/// Subjects and titles derived from the text are cut to this many characters
+const DERIVED_TITLE_CHARS: usize = 60;
+
+/// Destination configuration kept in Settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DestinationSettings {
+    /// Destination used when the user just says "email"
+    pub default_email: String,
+    pub default_recipients: Vec<String>,
+    /// Folder for plain-text notes, e.g. an Obsidian vault
+    pub notes_folder: Option<String>,
+    /// Microsoft Graph token with Mail.ReadWrite, for creating Outlook drafts directly
+    pub graph_access_token: Option<String>,
+}
+
+impl Default for DestinationSettings {
+    fn default() -> Self {
+        Self {
+            default_email: "mailto".to_string(),
+            default_recipients: Vec::new(),
+            notes_folder: None,
+            graph_access_token: None,
+        }
+    }
+}
+
+/// Optional details accompanying the text
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DestinationMetadata {
+    pub subject: Option<String>,
+    pub to: Vec<String>,
+    /// RFC 3339 start time for calendar entries
+    pub start: Option<String>,
+    pub duration_minutes: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DestinationKind {
+    Email,
+    Note,
+    Calendar,
+}
+
+/// What a destination reports back after handing text off
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DestinationReceipt {
+    pub destination_id: String,
+    pub kind: DestinationKind,
+    /// Draft link, note file or similar, when the destination provides one
+    pub location: Option<String>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DestinationInfo {
+    pub id: String,
+    pub name: String,
+    pub kind: DestinationKind,
+}
+
+/// A place processed text can be sent
+#[async_trait]
+pub trait Destination: Send + Sync {
+    fn id(&self) -> &str;
+    fn name(&self) -> &str;
+    fn kind(&self) -> DestinationKind;
+    async fn send(&self, text: &str, metadata: &DestinationMetadata) -> Result<DestinationReceipt, AppError>;
+
+    fn info(&self) -> DestinationInfo {
+        DestinationInfo {
+            id: self.id().to_string(),
+            name: self.name().to_string(),
+            kind: self.kind(),
+        }
+    }
+
+    fn receipt(&self, location: Option<String>, message: String) -> DestinationReceipt {
+        DestinationReceipt {
+            destination_id: self.id().to_string(),
+            kind: self.kind(),
+            location,
+            message,
+        }
+    }
+}
+
+/// Compose window of the default mail client
+struct MailtoDestination;
+
+#[async_trait]
+impl Destination for MailtoDestination {
+    fn id(&self) -> &'static str {
+        "mailto"
+    }
+    fn name(&self) -> &'static str {
+        "Default mail app"
+    }
+    fn kind(&self) -> DestinationKind {
+        DestinationKind::Email
+    }
+
+    async fn send(&self, text: &str, metadata: &DestinationMetadata) -> Result<DestinationReceipt, AppError> {
+        let url = format!(
+            "mailto:{}?subject={}&body={}",
+            metadata.to.iter().map(|to| percent_encode(to)).collect::<Vec<_>>().join(","),
+            percent_encode(&subject_for(text, metadata)),
+            percent_encode(text)
+        );
+        open_with_os(&url).await?;
+        Ok(self.receipt(None, "Opened a new draft in your mail app".to_string()))
+    }
+}
+
+/// Gmail compose page in the browser
+struct GmailDestination;
+
+#[async_trait]
+impl Destination for GmailDestination {
+    fn id(&self) -> &'static str {
+        "gmail"
+    }
+    fn name(&self) -> &'static str {
+        "Gmail"
+    }
+    fn kind(&self) -> DestinationKind {
+        DestinationKind::Email
+    }
+
+    async fn send(&self, text: &str, metadata: &DestinationMetadata) -> Result<DestinationReceipt, AppError> {
+        let url = format!(
+            "https://mail.google.com/mail/?view=cm&fs=1&to={}&su={}&body={}",
+            percent_encode(&metadata.to.join(",")),
+            percent_encode(&subject_for(text, metadata)),
+            percent_encode(text)
+        );
+        open_with_os(&url).await?;
+        Ok(self.receipt(Some(url), "Opened a Gmail draft in your browser".to_string()))
+    }
+}
+
+/// Outlook on the web compose page
+struct OutlookWebDestination;
+
+#[async_trait]
+impl Destination for OutlookWebDestination {
+    fn id(&self) -> &'static str {
+        "outlook_web"
+    }
+    fn name(&self) -> &'static str {
+        "Outlook (web)"
+    }
+    fn kind(&self) -> DestinationKind {
+        DestinationKind::Email
+    }
+
+    async fn send(&self, text: &str, metadata: &DestinationMetadata) -> Result<DestinationReceipt, AppError> {
+        let url = format!(
+            "https://outlook.office.com/mail/deeplink/compose?to={}&subject={}&body={}",
+            percent_encode(&metadata.to.join(";")),
+            percent_encode(&subject_for(text, metadata)),
+            percent_encode(text)
+        );
+        open_with_os(&url).await?;
+        Ok(self.receipt(Some(url), "Opened an Outlook draft in your browser".to_string()))
+    }
+}
+
+/// Saves a draft in the user's Outlook mailbox through Microsoft Graph without opening anything
+struct GraphDraftDestination {
+    access_token: String,
+}
+
+#[async_trait]
+impl Destination for GraphDraftDestination {
+    fn id(&self) -> &'static str {
+        "outlook_graph"
+    }
+    fn name(&self) -> &'static str {
+        "Outlook drafts (Microsoft 365)"
+    }
+    fn kind(&self) -> DestinationKind {
+        DestinationKind::Email
+    }
+
+    async fn send(&self, text: &str, metadata: &DestinationMetadata) -> Result<DestinationReceipt, AppError> {
+        let body = json!({
+            "subject": subject_for(text, metadata),
+            "body": { "contentType": "Text", "content": text },
+            "toRecipients": metadata
+                .to
+                .iter()
+                .map(|address| json!({ "emailAddress": { "address": address } }))
+                .collect::<Vec<_>>(),
+        });
+        let response = crate::network::client_builder()
+            .build()
+            .map_err(|e| AppError::Network(format!("Failed to build HTTP client: {}", e)))?
+            .post(GRAPH_MESSAGES_URL)
+            .bearer_auth(&self.access_token)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| AppError::Network(format!("Microsoft Graph request failed: {}", e)))?;
+
+        let status = response.status();
+        if status.as_u16() == 401 {
+            return Err(AppError::Configuration("Microsoft Graph token expired or invalid".to_string()));
+        }
+        if !status.is_success() {
+            let detail = response.text().await.unwrap_or_default();
+            return Err(AppError::Network(format!("Microsoft Graph returned {}: {}", status, detail)));
+        }
+        let created: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| AppError::Network(format!("Unreadable Microsoft Graph response: {}", e)))?;
+        let link = created.get("webLink").and_then(|v| v.as_str()).map(str::to_string);
+        Ok(self.receipt(link, "Saved a draft in Outlook".to_string()))
+    }
+}
+
+/// New note in Apple Notes
+#[cfg(target_os = "macos")]
+struct AppleNotesDestination;
+
+#[cfg(target_os = "macos")]
+#[async_trait]
+impl Destination for AppleNotesDestination {
+    fn id(&self) -> &'static str {
+        "apple_notes"
+    }
+    fn name(&self) -> &'static str {
+        "Apple Notes"
+    }
+    fn kind(&self) -> DestinationKind {
+        DestinationKind::Note
+    }
+
+    async fn send(&self, text: &str, metadata: &DestinationMetadata) -> Result<DestinationReceipt, AppError> {
+        let html_body = html_escape(text).replace('\n', "<br>");
+        // Text is passed as arguments, never spliced into the script
+        let status = tokio::process::Command::new("osascript")
+            .args([
+                "-e",
+                "on run argv",
+                "-e",
+                "tell application \"Notes\" to make new note with properties {name:item 1 of argv, body:item 2 of argv}",
+                "-e",
+                "end run",
+            ])
+            .arg(subject_for(text, metadata))
+            .arg(html_body)
+            .status()
+            .await
+            .map_err(|e| AppError::Internal(format!("Could not run osascript: {}", e)))?;
+        if !status.success() {
+            return Err(AppError::Internal(
+                "Apple Notes refused the note; check Automation permissions in System Settings".to_string(),
+            ));
+        }
+        Ok(self.receipt(None, "Created a note in Apple Notes".to_string()))
+    }
+}
+
+/// Markdown file in a notes folder
+struct NotesFolderDestination {
+    folder: PathBuf,
+}
+
+#[async_trait]
+impl Destination for NotesFolderDestination {
+    fn id(&self) -> &'static str {
+        "notes_folder"
+    }
+    fn name(&self) -> &'static str {
+        "Notes folder"
+    }
+    fn kind(&self) -> DestinationKind {
+        DestinationKind::Note
+    }
+
+    async fn send(&self, text: &str, metadata: &DestinationMetadata) -> Result<DestinationReceipt, AppError> {
+        let title = subject_for(text, metadata);
+        let slug: String = sanitize_filename::sanitize(&title).chars().take(40).collect();
+        let path = self
+            .folder
+            .join(format!("{} {}.md", Utc::now().format("%Y-%m-%d %H%M%S"), slug.trim()));
+        tokio::fs::create_dir_all(&self.folder)
+            .await
+            .map_err(|e| AppError::Internal(format!("Cannot create {}: {}", self.folder.display(), e)))?;
+        tokio::fs::write(&path, format!("# {}\n\n{}\n", title, text))
+            .await
+            .map_err(|e| AppError::Internal(format!("Cannot write {}: {}", path.display(), e)))?;
+        Ok(self.receipt(Some(path.display().to_string()), "Saved the note".to_string()))
+    }
+}
+
+/// Calendar entry handed to the default calendar app as an .ics file
+struct CalendarDestination;
+
+#[async_trait]
+impl Destination for CalendarDestination {
+    fn id(&self) -> &'static str {
+        "calendar"
+    }
+    fn name(&self) -> &'static str {
+        "Calendar"
+    }
+    fn kind(&self) -> DestinationKind {
+        DestinationKind::Calendar
+    }
+
+    async fn send(&self, text: &str, metadata: &DestinationMetadata) -> Result<DestinationReceipt, AppError> {
+        let start = match &metadata.start {
+            Some(start) => DateTime::parse_from_rfc3339(start)
+                .map(|dt| dt.with_timezone(&Utc))
+                .map_err(|e| AppError::Validation(crate::errors::ValidationError::InvalidConfigValue(format!(
+                    "Invalid start time '{}': {}",
+                    start, e
+                ))))?,
+            // Default to the top of the next hour
+            None => {
+                let now = Utc::now();
+                now + ChronoDuration::minutes(60 - now.minute() as i64) - ChronoDuration::seconds(now.second() as i64)
+            }
+        };
+        let end = start + ChronoDuration::minutes(metadata.duration_minutes.unwrap_or(30).max(1) as i64);
+
+        let format = "%Y%m%dT%H%M%SZ";
+        let lines = [
+            "BEGIN:VCALENDAR".to_string(),
+            "VERSION:2.0".to_string(),
+            "PRODID:-//VoiceFlow Pro//Destinations//EN".to_string(),
+            "BEGIN:VEVENT".to_string(),
+            format!("UID:{}@voiceflow.pro", uuid::Uuid::new_v4()),
+            format!("DTSTAMP:{}", Utc::now().format(format)),
+            format!("DTSTART:{}", start.format(format)),
+            format!("DTEND:{}", end.format(format)),
+            format!("SUMMARY:{}", ics_escape(&subject_for(text, metadata))),
+            format!("DESCRIPTION:{}", ics_escape(text)),
+            "END:VEVENT".to_string(),
+            "END:VCALENDAR".to_string(),
+        ];
+        let ics: String = lines.iter().map(|line| fold_ics_line(line)).collect::<Vec<_>>().join("\r\n") + "\r\n";
+
+        let path = std::env::temp_dir().join(format!("voiceflow-{}.ics", uuid::Uuid::new_v4()));
+        tokio::fs::write(&path, ics)
+            .await
+            .map_err(|e| AppError::Internal(format!("Cannot write {}: {}", path.display(), e)))?;
+        open_with_os(&path.display().to_string()).await?;
+        Ok(self.receipt(
+            Some(path.display().to_string()),
+            format!("Opened a calendar entry for {}", start.format("%Y-%m-%d %H:%M UTC")),
+        ))
+    }
+}
+
+/// Destinations usable on this platform with the current settings
+pub fn available_destinations(settings: &DestinationSettings) -> Vec<Box<dyn Destination>> {
+    let mut destinations: Vec<Box<dyn Destination>> = vec![
+        Box::new(MailtoDestination),
+        Box::new(GmailDestination),
+        Box::new(OutlookWebDestination),
+    ];
+    if let Some(token) = settings.graph_access_token.as_ref().filter(|t| !t.is_empty()) {
+        destinations.push(Box::new(GraphDraftDestination {
+            access_token: token.clone(),
+        }));
+    }
+    #[cfg(target_os = "macos")]
+    destinations.push(Box::new(AppleNotesDestination));
+    if let Some(folder) = settings.notes_folder.as_ref().filter(|f| !f.is_empty()) {
+        destinations.push(Box::new(NotesFolderDestination {
+            folder: PathBuf::from(folder),
+        }));
+    }
+    destinations.push(Box::new(CalendarDestination));
+    destinations.extend(crate::plugins::plugin_destinations());
+    destinations
+}
+
+/// Send text to the destination with `destination_id`
+pub async fn send_to_destination(
+    destination_id: &str,
+    text: &str,
+    metadata: &DestinationMetadata,
+    settings: &DestinationSettings,
+) -> Result<DestinationReceipt, AppError> {
+    let destination = available_destinations(settings)
+        .into_iter()
+        .find(|d| d.id() == destination_id)
+        .ok_or_else(|| AppError::Configuration(format!("Destination '{}' is not available", destination_id)))?;
+
+    let mut metadata = metadata.clone();
+    if metadata.to.is_empty() && destination.kind() == DestinationKind::Email {
+        metadata.to = settings.default_recipients.clone();
+    }
+    destination.send(text, &metadata).await
+}
+
+/// Recognize spoken requests such as "send this as an email draft" or "save this as a note"
+pub fn parse_destination_command(utterance: &str, settings: &DestinationSettings) -> Option<String> {
+    let pattern = Regex::new(
+        r"(?i)^\s*(?:please\s+)?(?:send|save|put|add|make|turn)\s+(?:this|that|it)\s+(?:as|to|into|in)\s+(?:an?\s+|my\s+|the\s+)?(gmail|outlook|email|e-mail|mail|note|notes|calendar)(?:\s+(?:draft|note|event|entry))?\s*[.!]?\s*$",
+    )
+    .ok()?;
+    let target = pattern.captures(utterance)?.get(1)?.as_str().to_lowercase();
+    let id = match target.as_str() {
+        "gmail" => "gmail".to_string(),
+        "outlook" if settings.graph_access_token.is_some() => "outlook_graph".to_string(),
+        "outlook" => "outlook_web".to_string(),
+        "email" | "e-mail" | "mail" => settings.default_email.clone(),
+        "note" | "notes" if settings.notes_folder.is_some() => "notes_folder".to_string(),
+        "note" | "notes" if cfg!(target_os = "macos") => "apple_notes".to_string(),
+        "calendar" => "calendar".to_string(),
+        _ => return None,
+    };
+    Some(id)
+}
+
+/// Explicit subject, or the first line of the text shortened to a title
+fn subject_for(text: &str, metadata: &DestinationMetadata) -> String {
+    if let Some(subject) = metadata.subject.as_ref().filter(|s| !s.trim().is_empty()) {
+        return subject.trim().to_string();
+    }
+    let first_line = text.lines().find(|l| !l.trim().is_empty()).unwrap_or("").trim();
+    let first_sentence = first_line
+        .split_inclusive(['.', '!', '?'])
+        .next()
+        .unwrap_or(first_line)
+        .trim_end_matches(['.', '!', '?']);
+    if first_sentence.chars().count() <= DERIVED_TITLE_CHARS {
+        return first_sentence.to_string();
+    }
+    let cut: String = first_sentence.chars().take(DERIVED_TITLE_CHARS).collect();
+    match cut.rfind(' ') {
+        Some(pos) => format!("{}…", &cut[..pos]),
+        None => format!("{}…", cut),
+    }
+}
+
+/// RFC 3986 percent-encoding of everything but unreserved characters
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn ics_escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// iCalendar lines are limited to 75 octets; continuation lines start with a space
+fn fold_ics_line(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + line.len() / 74 * 3);
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            width = 1;
+        }
+        folded.push(c);
+        width += c.len_utf8();
+    }
+    folded
+}
+
+#[cfg(target_os = "macos")]
+fn html_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Open a URL or file with its default handler
+async fn open_with_os(target: &str) -> Result<(), AppError> {
+    #[cfg(target_os = "macos")]
+    let mut command = {
+        let mut command = tokio::process::Command::new("open");
+        command.arg(target);
+        command
+    };
+    #[cfg(target_os = "windows")]
+    let mut command = {
+        // `start` would treat & in URLs as a command separator
+        let mut command = tokio::process::Command::new("rundll32");
+        command.args(["url.dll,FileProtocolHandler", target]);
+        command
+    };
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    let mut command = {
+        let mut command = tokio::process::Command::new("xdg-open");
+        command.arg(target);
+        command
+    };
+
+    let status = command
+        .status()
+        .await
+        .map_err(|e| AppError::Internal(format!("Could not open {}: {}", target, e)))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(AppError::Internal("No application is registered to handle this destination".to_string()))
+    }
+}
@@ -0,0 +1,277 @@
+// Echo Cancellation Module
+// Subtracts the app's own speech playback from the microphone so assistant replies and live translations are not transcribed again
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use super::audio_capture::{Resampler, TARGET_SAMPLE_RATE};
+use crate::errors::{AppError, ValidationError};
+
+/// NLMS step size; lower converges slower but distorts less while both sides talk
+const STEP: f32 = 0.4;
+/// Keeps the normalization finite while the reference is silent
+const REGULARIZATION: f32 = 1e-3;
+/// Geigel double-talk detection: the microphone louder than this share of the recent reference
+/// peak means the user is talking, and the filter stops adapting
+const DOUBLE_TALK_RATIO: f32 = 0.6;
+/// Reference level below which playback counts as silent
+const REFERENCE_FLOOR: f32 = 1e-3;
+/// Reference waiting for capture to catch up; more means capture is not running
+const MAX_PENDING_SECS: usize = 2;
+/// Playback is considered active while references arrived this recently
+const REFERENCE_FRESH: Duration = Duration::from_millis(500);
+/// Share of the previous energy kept per measured frame, for a metric that follows the last few seconds
+const METRIC_DECAY: f64 = 0.98;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EchoCancellationSettings {
+    pub enabled: bool,
+    /// Longest echo path cancelled, covering output latency and the room's reverberation
+    pub filter_ms: u32,
+}
+
+impl Default for EchoCancellationSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            filter_ms: 128,
+        }
+    }
+}
+
+pub fn validate(settings: &EchoCancellationSettings) -> Result<(), AppError> {
+    if !(32..=500).contains(&settings.filter_ms) {
+        return Err(AppError::Validation(ValidationError::InvalidConfigValue(format!(
+            "Echo cancellation filter must be 32-500 ms, got {}",
+            settings.filter_ms
+        ))));
+    }
+    Ok(())
+}
+
+/// How well cancellation is working, for audio diagnostics
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EchoCancellationMetrics {
+    pub enabled: bool,
+    /// Playback reference is arriving, so capture is being cancelled right now
+    pub active: bool,
+    /// Echo return loss enhancement: how much quieter the app's own speech is after cancellation.
+    /// Around 20 dB and up means it is no longer picked up as speech
+    pub erle_db: Option<f32>,
+    /// Playback audio received as reference
+    pub reference_secs: f32,
+    /// Share of cancelled audio during which the user talked over playback
+    pub double_talk_ratio: f32,
+    pub filter_taps: usize,
+}
+
+/// Time-domain NLMS echo canceller in the manner of speex's, for mono frames at `TARGET_SAMPLE_RATE`
+#[derive(Debug)]
+pub struct EchoCanceller {
+    weights: Vec<f32>,
+    /// Reference history stored twice, so the newest `taps` samples are always one contiguous slice
+    history: Vec<f32>,
+    position: usize,
+    /// Sum of squares of the reference history, kept incrementally
+    history_power: f32,
+    reference_peak: f32,
+    peak_decay: f32,
+    pending: VecDeque<f32>,
+    resampler: Option<(u32, Resampler)>,
+    last_reference: Option<Instant>,
+    reference_samples: u64,
+    echo_energy: f64,
+    residual_energy: f64,
+    cancelled_samples: u64,
+    double_talk_samples: u64,
+}
+
+impl EchoCanceller {
+    pub fn new(filter_ms: u32) -> Self {
+        let taps = (TARGET_SAMPLE_RATE as usize * filter_ms as usize / 1000).max(1);
+        Self {
+            weights: vec![0.0; taps],
+            history: vec![0.0; taps * 2],
+            position: 0,
+            history_power: 0.0,
+            reference_peak: 0.0,
+            // The peak fades over one filter length, as Geigel's detector looks back over the echo path
+            peak_decay: (0.001f32).powf(1.0 / taps as f32),
+            pending: VecDeque::new(),
+            resampler: None,
+            last_reference: None,
+            reference_samples: 0,
+            echo_energy: 0.0,
+            residual_energy: 0.0,
+            cancelled_samples: 0,
+            double_talk_samples: 0,
+        }
+    }
+
+    pub fn taps(&self) -> usize {
+        self.weights.len()
+    }
+
+    /// Playback about to reach the speakers, at its own rate and mixed down to mono
+    pub fn push_reference(&mut self, samples: &[f32], sample_rate: u32) {
+        if self.resampler.as_ref().map_or(true, |(rate, _)| *rate != sample_rate) {
+            self.resampler = Some((sample_rate, Resampler::new(sample_rate, TARGET_SAMPLE_RATE)));
+        }
+        let Some((_, resampler)) = self.resampler.as_mut() else {
+            return;
+        };
+        let samples = resampler.process(samples);
+        self.reference_samples += samples.len() as u64;
+        self.pending.extend(samples);
+        let overflow = self.pending.len().saturating_sub(MAX_PENDING_SECS * TARGET_SAMPLE_RATE as usize);
+        self.pending.drain(..overflow);
+        self.last_reference = Some(Instant::now());
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.last_reference.map_or(false, |at| at.elapsed() < REFERENCE_FRESH)
+    }
+
+    /// Playback stopped; queued reference belongs to speech that was never played
+    pub fn clear_reference(&mut self) {
+        self.pending.clear();
+        self.last_reference = None;
+    }
+
+    /// Remove the echo from a captured frame in place
+    pub fn process(&mut self, capture: &mut [f32]) {
+        if !self.is_active() && self.history_power <= REFERENCE_FLOOR {
+            return;
+        }
+        let taps = self.weights.len();
+        let mut echo_energy = 0.0f64;
+        let mut residual_energy = 0.0f64;
+        for sample in capture.iter_mut() {
+            let reference = self.pending.pop_front().unwrap_or(0.0);
+            let oldest = self.history[self.position + taps - 1];
+            self.position = if self.position == 0 { taps - 1 } else { self.position - 1 };
+            self.history[self.position] = reference;
+            self.history[self.position + taps] = reference;
+            self.history_power = (self.history_power + reference * reference - oldest * oldest).max(0.0);
+            self.reference_peak = (self.reference_peak * self.peak_decay).max(reference.abs());
+
+            let window = &self.history[self.position..self.position + taps];
+            let estimate: f32 = self.weights.iter().zip(window).map(|(weight, x)| weight * x).sum();
+            let residual = *sample - estimate;
+
+            if self.reference_peak > REFERENCE_FLOOR {
+                self.cancelled_samples += 1;
+                if sample.abs() > DOUBLE_TALK_RATIO * self.reference_peak {
+                    self.double_talk_samples += 1;
+                } else {
+                    let gain = STEP * residual / (self.history_power + REGULARIZATION);
+                    for (weight, x) in self.weights.iter_mut().zip(window) {
+                        *weight += gain * x;
+                    }
+                    echo_energy += (*sample as f64).powi(2);
+                    residual_energy += (residual as f64).powi(2);
+                }
+            }
+            *sample = residual;
+        }
+        if echo_energy > 0.0 {
+            self.echo_energy = self.echo_energy * METRIC_DECAY + echo_energy;
+            self.residual_energy = self.residual_energy * METRIC_DECAY + residual_energy;
+        }
+    }
+
+    /// Share of echo energy left after cancellation, between 0 and 1
+    pub fn residual_ratio(&self) -> f32 {
+        if self.echo_energy <= 0.0 {
+            return 1.0;
+        }
+        (self.residual_energy / self.echo_energy).clamp(0.0, 1.0) as f32
+    }
+
+    pub fn metrics(&self) -> EchoCancellationMetrics {
+        EchoCancellationMetrics {
+            enabled: true,
+            active: self.is_active(),
+            erle_db: (self.echo_energy > 0.0)
+                .then(|| (10.0 * (self.echo_energy / self.residual_energy.max(1e-12)).log10()) as f32),
+            reference_secs: self.reference_samples as f32 / TARGET_SAMPLE_RATE as f32,
+            double_talk_ratio: if self.cancelled_samples == 0 {
+                0.0
+            } else {
+                self.double_talk_samples as f32 / self.cancelled_samples as f32
+            },
+            filter_taps: self.taps(),
+        }
+    }
+}
+
+/// Shared by every capture path; `None` while echo cancellation is off
+static CANCELLER: Mutex<Option<EchoCanceller>> = Mutex::new(None);
+
+/// Feed playback reference; turning the setting off drops the canceller and what it learned
+pub fn push_reference(settings: &EchoCancellationSettings, samples: &[f32], sample_rate: u32) {
+    let Ok(mut canceller) = CANCELLER.lock() else {
+        return;
+    };
+    if !settings.enabled {
+        *canceller = None;
+        return;
+    }
+    let taps = TARGET_SAMPLE_RATE as usize * settings.filter_ms as usize / 1000;
+    if canceller.as_ref().map_or(true, |canceller| canceller.taps() != taps.max(1)) {
+        *canceller = Some(EchoCanceller::new(settings.filter_ms));
+    }
+    if let Some(canceller) = canceller.as_mut() {
+        canceller.push_reference(samples, sample_rate);
+    }
+}
+
+/// Playback ended; the learned echo path is kept for the next reply
+pub fn playback_ended() {
+    if let Ok(mut canceller) = CANCELLER.lock() {
+        if let Some(canceller) = canceller.as_mut() {
+            canceller.clear_reference();
+        }
+    }
+}
+
+/// Cancel echo in a captured frame; untouched while there is no reference
+pub fn process_capture(frame: &mut [f32]) {
+    if let Ok(mut canceller) = CANCELLER.lock() {
+        if let Some(canceller) = canceller.as_mut() {
+            canceller.process(frame);
+        }
+    }
+}
+
+/// Factor for the recognizer's input level while playback runs, so its own speech does not count as the user's
+pub fn capture_level_factor() -> f32 {
+    CANCELLER
+        .lock()
+        .ok()
+        .and_then(|canceller| {
+            canceller
+                .as_ref()
+                .filter(|canceller| canceller.is_active())
+                .map(|canceller| canceller.residual_ratio().sqrt())
+        })
+        .unwrap_or(1.0)
+}
+
+pub fn metrics(settings: &EchoCancellationSettings) -> EchoCancellationMetrics {
+    let measured = CANCELLER
+        .lock()
+        .ok()
+        .and_then(|canceller| canceller.as_ref().map(EchoCanceller::metrics));
+    match measured {
+        Some(metrics) if settings.enabled => metrics,
+        _ => EchoCancellationMetrics {
+            enabled: settings.enabled,
+            ..EchoCancellationMetrics::default()
+        },
+    }
+}
@@ -0,0 +1,429 @@
+// Emotion Tracking Module
+// Opt-in, on-device estimate of each utterance's emotion from its words and the frontend's prosody features,
+// folded into the session's emotional state
+
+use std::collections::VecDeque;
+use std::sync::{Mutex, OnceLock};
+
+use serde::{Deserialize, Serialize};
+
+use super::context::{EmotionTrend, EmotionalState};
+use crate::errors::{AppError, ValidationError};
+
+/// Estimates the session trend is computed from
+const TREND_WINDOW: usize = 8;
+/// Weight of the newest utterance in the session intensity
+const INTENSITY_SMOOTHING: f32 = 0.4;
+/// Mean intensity change between halves of the window that counts as rising or falling
+const TREND_DELTA: f32 = 0.15;
+/// Below this stability the session is volatile
+const VOLATILE_STABILITY: f32 = 0.4;
+/// Weight of each prosody baseline update
+const BASELINE_SMOOTHING: f32 = 0.1;
+
+const INTENSIFIERS: &[&str] = &["very", "really", "extremely", "so", "totally", "absolutely", "completely", "super"];
+const NEGATIONS: &[&str] = &["not", "no", "never", "don't", "isn't", "wasn't", "can't", "won't", "didn't", "hardly"];
+
+/// Emotion words; valence and arousal of each emotion are in `EMOTIONS`
+const LEXICON: &[(&str, &[&str])] = &[
+    (
+        "joy",
+        &[
+            "happy", "glad", "love", "excited", "awesome", "wonderful", "delighted", "thrilled", "fantastic", "yay",
+            "excellent", "pleased", "enjoy", "enjoyed", "great", "perfect", "brilliant", "relieved", "proud",
+        ],
+    ),
+    (
+        "anger",
+        &[
+            "angry", "furious", "annoyed", "mad", "hate", "ridiculous", "unacceptable", "outraged", "irritated",
+            "pissed", "infuriating", "sick", "fed", "frustrated", "frustrating", "useless", "stupid",
+        ],
+    ),
+    (
+        "sadness",
+        &[
+            "sad", "unhappy", "disappointed", "sorry", "miss", "lonely", "depressed", "upset", "unfortunately",
+            "regret", "heartbroken", "tired", "exhausted", "hopeless", "lost",
+        ],
+    ),
+    (
+        "fear",
+        &[
+            "worried", "afraid", "scared", "anxious", "nervous", "concerned", "panic", "terrified", "stress",
+            "stressed", "overwhelmed", "uneasy", "dread",
+        ],
+    ),
+    (
+        "surprise",
+        &["wow", "surprised", "unexpected", "amazing", "shocked", "unbelievable", "whoa", "incredible", "suddenly"],
+    ),
+];
+
+/// (emotion, valence -1..1, arousal 0..1)
+const EMOTIONS: &[(&str, f32, f32)] = &[
+    ("neutral", 0.0, 0.3),
+    ("joy", 0.8, 0.6),
+    ("anger", -0.7, 0.85),
+    ("sadness", -0.6, 0.2),
+    ("fear", -0.6, 0.7),
+    ("surprise", 0.2, 0.8),
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EmotionTrackingSettings {
+    /// Off unless the user turns it on; nothing is estimated or kept while off
+    pub enabled: bool,
+    /// Blend in pitch, energy and speech rate when the audio frontend sends them
+    pub use_prosody: bool,
+    /// Intensity change since the last `emotion-changed` event that warrants a new one
+    pub change_threshold: f32,
+}
+
+impl Default for EmotionTrackingSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            use_prosody: true,
+            change_threshold: 0.3,
+        }
+    }
+}
+
+pub fn validate(settings: &EmotionTrackingSettings) -> Result<(), AppError> {
+    if !(0.05..=1.0).contains(&settings.change_threshold) {
+        return Err(AppError::Validation(ValidationError::InvalidConfigValue(
+            "Emotion change threshold must be between 0.05 and 1".to_string(),
+        )));
+    }
+    Ok(())
+}
+
+/// Per-utterance voice features measured by the audio frontend; any may be missing
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProsodyFeatures {
+    /// Mean fundamental frequency in Hz
+    pub pitch_hz: Option<f32>,
+    /// Standard deviation of pitch in semitones
+    pub pitch_variability: Option<f32>,
+    /// RMS loudness, 0-1
+    pub energy: Option<f32>,
+    /// Words per second
+    pub speech_rate: Option<f32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmotionEstimate {
+    pub emotion: String,
+    pub confidence: f32,
+    pub intensity: f32,
+    /// -1 (negative) to 1 (positive)
+    pub valence: f32,
+    /// 0 (calm) to 1 (agitated)
+    pub arousal: f32,
+    pub used_prosody: bool,
+    pub timestamp: u64,
+}
+
+/// Emitted as `emotion-changed` when the session's emotion or intensity moves significantly
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmotionChange {
+    pub session_id: Option<String>,
+    pub previous: EmotionalState,
+    pub current: EmotionalState,
+    pub estimate: EmotionEstimate,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmotionSessionState {
+    pub session_id: Option<String>,
+    pub state: EmotionalState,
+    /// Newest last
+    pub recent: Vec<EmotionEstimate>,
+}
+
+/// Running averages of the speaker's prosody, so features are judged against their own voice
+#[derive(Debug, Default, Clone)]
+struct ProsodyBaseline {
+    pitch_variability: Option<f32>,
+    energy: Option<f32>,
+    speech_rate: Option<f32>,
+}
+
+impl ProsodyBaseline {
+    /// Arousal 0-1 from how far the features sit above or below the baseline, then update the baseline
+    fn arousal(&mut self, features: &ProsodyFeatures) -> Option<f32> {
+        let deltas: Vec<f32> = [
+            (features.pitch_variability, &mut self.pitch_variability),
+            (features.energy, &mut self.energy),
+            (features.speech_rate, &mut self.speech_rate),
+        ]
+        .into_iter()
+        .filter_map(|(value, baseline)| {
+            let value = value.filter(|value| value.is_finite() && *value >= 0.0)?;
+            let delta = match baseline {
+                Some(base) if *base > 0.0 => ((value - *base) / *base).clamp(-1.0, 1.0),
+                _ => 0.0,
+            };
+            *baseline = Some(match baseline {
+                Some(base) => *base + (value - *base) * BASELINE_SMOOTHING,
+                None => value,
+            });
+            Some(delta)
+        })
+        .collect();
+        if deltas.is_empty() {
+            return None;
+        }
+        let mean = deltas.iter().sum::<f32>() / deltas.len() as f32;
+        Some((0.5 + mean * 0.5).clamp(0.0, 1.0))
+    }
+}
+
+#[derive(Debug, Default)]
+struct EmotionSession {
+    session_id: Option<String>,
+    state: EmotionalState,
+    recent: VecDeque<EmotionEstimate>,
+    baseline: ProsodyBaseline,
+    /// State reported in the last event, or the starting state
+    last_reported: Option<EmotionalState>,
+}
+
+static SESSION: OnceLock<Mutex<EmotionSession>> = OnceLock::new();
+
+fn session() -> std::sync::MutexGuard<'static, EmotionSession> {
+    let session = SESSION.get_or_init(|| Mutex::new(EmotionSession::default()));
+    match session.lock() {
+        Ok(session) => session,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+/// Start a fresh trend for `session_id`; resuming the current session keeps it
+pub fn start_session(session_id: &str) {
+    let mut session = session();
+    if session.session_id.as_deref() != Some(session_id) {
+        *session = EmotionSession {
+            session_id: Some(session_id.to_string()),
+            ..EmotionSession::default()
+        };
+    }
+}
+
+/// Apply changed settings; turning tracking off forgets everything estimated so far
+pub fn configure(settings: &EmotionTrackingSettings) {
+    if !settings.enabled {
+        let mut session = session();
+        let session_id = session.session_id.take();
+        *session = EmotionSession {
+            session_id,
+            ..EmotionSession::default()
+        };
+    }
+}
+
+/// The session's emotional state, when tracking is on and something has been observed
+pub fn current_state() -> Option<EmotionSessionState> {
+    let session = session();
+    (!session.recent.is_empty()).then(|| EmotionSessionState {
+        session_id: session.session_id.clone(),
+        state: session.state.clone(),
+        recent: session.recent.iter().cloned().collect(),
+    })
+}
+
+/// Estimate an utterance and update the session; returns a change worth an `emotion-changed` event
+pub fn observe(
+    text: &str,
+    prosody: Option<&ProsodyFeatures>,
+    settings: &EmotionTrackingSettings,
+) -> Option<EmotionChange> {
+    if !settings.enabled {
+        return None;
+    }
+    let mut session = session();
+    let prosody_arousal = prosody
+        .filter(|_| settings.use_prosody)
+        .and_then(|features| session.baseline.arousal(features));
+    let estimate = estimate(text, prosody_arousal);
+
+    let previous = session.state.clone();
+    session.recent.push_back(estimate.clone());
+    while session.recent.len() > TREND_WINDOW {
+        session.recent.pop_front();
+    }
+    session.state = trend(&session.recent, previous.intensity);
+
+    // Measured from a calm, neutral start, so a neutral first utterance is not news
+    let reported = session
+        .last_reported
+        .get_or_insert_with(|| EmotionalState {
+            intensity: 0.0,
+            ..EmotionalState::default()
+        })
+        .clone();
+    let significant = session.state.primary_emotion != reported.primary_emotion
+        || (session.state.intensity - reported.intensity).abs() >= settings.change_threshold
+        || (session.state.trending == EmotionTrend::Volatile && reported.trending != EmotionTrend::Volatile);
+    if !significant {
+        return None;
+    }
+    session.last_reported = Some(session.state.clone());
+    Some(EmotionChange {
+        session_id: session.session_id.clone(),
+        previous: reported,
+        current: session.state.clone(),
+        estimate,
+    })
+}
+
+/// Lexicon scores per emotion, with intensifiers, negation and emphasis; `prosody_arousal` shifts intensity
+pub fn estimate(text: &str, prosody_arousal: Option<f32>) -> EmotionEstimate {
+    let words: Vec<String> = text
+        .split_whitespace()
+        .map(|w| w.trim_matches(|c: char| !c.is_alphanumeric() && c != '\'').to_lowercase())
+        .filter(|w| !w.is_empty())
+        .collect();
+
+    let mut scores = vec![0.0f32; LEXICON.len()];
+    let mut multiplier = 1.0f32;
+    let mut negate = false;
+    for word in &words {
+        let word = word.as_str();
+        if INTENSIFIERS.contains(&word) {
+            multiplier = 1.5;
+            continue;
+        }
+        if NEGATIONS.contains(&word) {
+            negate = true;
+            continue;
+        }
+        if let Some(index) = LEXICON.iter().position(|(_, words)| words.contains(&word)) {
+            // "not happy" says little about which other emotion is meant, so it only weakens the named one
+            if negate {
+                scores[index] -= 0.5;
+            } else {
+                scores[index] += multiplier;
+            }
+        }
+        multiplier = 1.0;
+        negate = false;
+    }
+
+    let exclamations = text.matches('!').count().min(3) as f32;
+    let letters: Vec<char> = text.chars().filter(|c| c.is_alphabetic()).collect();
+    let shouting = letters.len() >= 8 && letters.iter().all(|c| !c.is_lowercase());
+    let emphasis = exclamations * 0.1 + if shouting { 0.2 } else { 0.0 };
+
+    let best = scores
+        .iter()
+        .enumerate()
+        .filter(|(_, score)| **score > 0.0)
+        .max_by(|(_, a), (_, b)| a.total_cmp(b));
+    let (emotion, strength) = match best {
+        Some((index, score)) => (LEXICON[index].0, *score),
+        None => ("neutral", 0.0),
+    };
+    let (_, valence, text_arousal) = EMOTIONS
+        .iter()
+        .find(|(name, _, _)| *name == emotion)
+        .copied()
+        .unwrap_or(EMOTIONS[0]);
+
+    let total: f32 = scores.iter().filter(|score| **score > 0.0).sum();
+    let text_confidence = if emotion == "neutral" {
+        if words.len() >= 4 { 0.5 } else { 0.3 }
+    } else {
+        (0.4 + 0.3 * (strength / total) + 0.05 * strength).min(0.9)
+    };
+    let text_intensity = ((strength / 3.0).min(1.0) * 0.7 + emphasis).clamp(0.0, 1.0);
+
+    let arousal = match prosody_arousal {
+        Some(prosody) => (text_arousal + emphasis).min(1.0) * 0.5 + prosody * 0.5,
+        None => (text_arousal + emphasis).min(1.0),
+    };
+    let intensity = match prosody_arousal {
+        // Calm delivery softens strong words; an agitated one strengthens them and counts on its own
+        Some(prosody) => (text_intensity * (0.5 + prosody) + (prosody - 0.5).max(0.0) * 0.4).clamp(0.0, 1.0),
+        None => text_intensity,
+    };
+
+    EmotionEstimate {
+        emotion: emotion.to_string(),
+        confidence: if prosody_arousal.is_some() { (text_confidence + 0.05).min(0.95) } else { text_confidence },
+        intensity,
+        valence,
+        arousal,
+        used_prosody: prosody_arousal.is_some(),
+        timestamp: now_secs(),
+    }
+}
+
+/// Session state from the recent estimates, newest weighted most
+fn trend(recent: &VecDeque<EmotionEstimate>, previous_intensity: f32) -> EmotionalState {
+    let newest = recent.back().map_or(0.0, |estimate| estimate.intensity);
+    let intensity = if recent.len() == 1 {
+        newest
+    } else {
+        previous_intensity + (newest - previous_intensity) * INTENSITY_SMOOTHING
+    };
+
+    // Recency-weighted vote among non-neutral readings
+    let mut votes: Vec<(&str, f32)> = Vec::new();
+    for (age, estimate) in recent.iter().rev().enumerate().take(5) {
+        if estimate.emotion == "neutral" {
+            continue;
+        }
+        let weight = estimate.confidence * (0.3 + estimate.intensity) / (1.0 + age as f32).powi(2);
+        match votes.iter_mut().find(|(emotion, _)| *emotion == estimate.emotion) {
+            Some((_, total)) => *total += weight,
+            None => votes.push((&estimate.emotion, weight)),
+        }
+    }
+    let primary_emotion = votes
+        .into_iter()
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map_or("neutral", |(emotion, _)| emotion)
+        .to_string();
+
+    // Spread of signed affect: mild swings barely count, joy to anger does
+    let affect: Vec<f32> = recent.iter().map(|estimate| estimate.valence * estimate.intensity).collect();
+    let count = affect.len() as f32;
+    let mean = affect.iter().sum::<f32>() / count;
+    let variance = affect.iter().map(|value| (value - mean).powi(2)).sum::<f32>() / count;
+    let stability = (1.0 - variance.sqrt() / 0.5).clamp(0.0, 1.0);
+
+    let trending = if recent.len() >= 3 && stability < VOLATILE_STABILITY {
+        EmotionTrend::Volatile
+    } else if recent.len() >= 4 {
+        let half = recent.len() / 2;
+        let earlier = recent.iter().take(half).map(|estimate| estimate.intensity).sum::<f32>() / half as f32;
+        let later = recent.iter().skip(recent.len() - half).map(|estimate| estimate.intensity).sum::<f32>() / half as f32;
+        if later - earlier > TREND_DELTA {
+            EmotionTrend::Rising
+        } else if earlier - later > TREND_DELTA {
+            EmotionTrend::Falling
+        } else {
+            EmotionTrend::Stable
+        }
+    } else {
+        EmotionTrend::Stable
+    };
+
+    EmotionalState {
+        primary_emotion,
+        intensity,
+        stability,
+        trending,
+    }
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
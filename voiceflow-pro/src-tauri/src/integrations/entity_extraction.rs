@@ -0,0 +1,451 @@
+// Entity Extraction Module
+// Named entities, concepts and relationships via a schema-bound tool call, with a local recognizer as fallback
+
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+use regex::Regex;
+use serde_json::{json, Value};
+
+use super::ai_ml_core::{AIMLClient, AIMLError};
+use super::context_processor::{Concept, EntityType, RelationshipType, TextEntity, TextRelationship};
+
+const EXTRACTION_TOOL: &str = "record_entities";
+const EXTRACTION_MAX_TOKENS: u32 = 1500;
+
+/// Names as serialized, offered to the model as the schema's enum
+const ENTITY_TYPE_NAMES: &[&str] = &[
+    "Person",
+    "Organization",
+    "Location",
+    "Product",
+    "Concept",
+    "Event",
+    "Date",
+    "Number",
+    "TechnicalTerm",
+    "CulturalReference",
+];
+const RELATIONSHIP_TYPE_NAMES: &[&str] = &[
+    "Defines",
+    "Explains",
+    "Contradicts",
+    "Supports",
+    "Precedes",
+    "Follows",
+    "SimilarTo",
+    "DifferentFrom",
+    "Causes",
+    "Results",
+];
+
+const PERSON_TITLES: &[&str] = &["Mr", "Mrs", "Ms", "Miss", "Dr", "Prof", "Professor", "Sir", "Dame", "President"];
+const ORGANIZATION_WORDS: &[&str] = &[
+    "Inc", "Ltd", "LLC", "Corp", "Corporation", "Company", "Co", "Group", "University", "College", "Institute",
+    "Bank", "Foundation", "Association", "Agency", "Ministry", "Department", "Council", "Committee", "Labs",
+];
+const LOCATION_WORDS: &[&str] = &[
+    "City", "County", "Street", "St", "Avenue", "Road", "River", "Mountain", "Mountains", "Lake", "Island",
+    "Islands", "Park", "Republic", "Kingdom", "State", "States", "Province", "Valley", "Bay",
+];
+const EVENT_WORDS: &[&str] = &["Conference", "Summit", "Festival", "Olympics", "War", "Cup", "Expo", "Championship"];
+/// Prepositions that usually introduce a place
+const LOCATION_CUES: &[&str] = &["in", "at", "from", "to", "near", "across", "visit", "visiting", "around"];
+/// Capitalized words that open sentences or stand for dates rather than names
+const NOT_NAMES: &[&str] = &[
+    "I", "The", "A", "An", "This", "That", "These", "Those", "It", "We", "You", "He", "She", "They", "My", "Our",
+    "Your", "His", "Her", "Their", "Its", "If", "When", "While", "But", "And", "Or", "So", "Then", "Now", "Also",
+    "However", "Meanwhile", "After", "Before", "Since", "Because", "Although", "Maybe", "Perhaps", "Later",
+    "Yesterday", "Today", "Tomorrow", "Here", "There", "What", "Why", "How", "Who", "Where", "Which", "Let",
+    "Can", "Could", "Would", "Should", "Will", "Do", "Does", "Did", "Is", "Are", "Was", "Were", "Just", "First",
+    "Next", "Finally", "Please", "Thanks", "Thank", "Sorry", "Dear", "Hi", "Hey", "Hello", "Yes", "No", "OK",
+    "Okay", "Sure", "Well", "Oh", "In", "On", "At", "For", "With", "From", "By", "To", "As",
+    "January", "February", "March", "April", "May", "June", "July", "August", "September", "October", "November",
+    "December", "Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday",
+];
+
+/// Where an entity sits in the text, in UTF-16 code units so the webview can slice the string directly
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct EntitySpan {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExtractionSource {
+    /// Returned by the model through the extraction schema
+    Model,
+    /// Found on-device, when the model is unavailable or its reply did not fit the schema
+    Local,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct EntityExtraction {
+    pub entities: Vec<TextEntity>,
+    pub concepts: Vec<Concept>,
+    pub relationships: Vec<TextRelationship>,
+    pub source: ExtractionSource,
+}
+
+fn extraction_tool() -> Value {
+    json!({
+        "type": "function",
+        "function": {
+            "name": EXTRACTION_TOOL,
+            "description": "Record the named entities, key concepts and relationships found in the text",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "entities": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "text": { "type": "string", "description": "The entity exactly as written in the text" },
+                                "type": { "type": "string", "enum": ENTITY_TYPE_NAMES },
+                                "confidence": { "type": "number", "minimum": 0, "maximum": 1 },
+                                "relevance": { "type": "number", "minimum": 0, "maximum": 1 }
+                            },
+                            "required": ["text", "type"]
+                        }
+                    },
+                    "concepts": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "name": { "type": "string" },
+                                "category": { "type": "string" },
+                                "abstractness": { "type": "number", "minimum": 0, "maximum": 1 },
+                                "relevance": { "type": "number", "minimum": 0, "maximum": 1 },
+                                "related": { "type": "array", "items": { "type": "string" } }
+                            },
+                            "required": ["name"]
+                        }
+                    },
+                    "relationships": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "from": { "type": "string" },
+                                "to": { "type": "string" },
+                                "type": { "type": "string", "enum": RELATIONSHIP_TYPE_NAMES },
+                                "strength": { "type": "number", "minimum": 0, "maximum": 1 }
+                            },
+                            "required": ["from", "to", "type"]
+                        }
+                    }
+                },
+                "required": ["entities", "concepts", "relationships"]
+            }
+        }
+    })
+}
+
+/// Entities in `text` from `model`, falling back to the local recognizer when that fails
+pub async fn extract_entities(client: &AIMLClient, model: &str, text: &str) -> EntityExtraction {
+    match extract_with_model(client, model, text).await {
+        Ok(extraction) => extraction,
+        Err(e) => {
+            log::warn!("Entity extraction fell back to the local recognizer: {}", e);
+            extract_locally(text)
+        }
+    }
+}
+
+async fn extract_with_model(client: &AIMLClient, model: &str, text: &str) -> Result<EntityExtraction, AIMLError> {
+    let messages = vec![
+        json!({
+            "role": "system",
+            "content": format!(
+                "You extract named entities, key concepts and the relationships between them. \
+                 Call {} once with everything you find. Copy each entity exactly as it is written in the text.",
+                EXTRACTION_TOOL
+            ),
+        }),
+        json!({ "role": "user", "content": text }),
+    ];
+    let reply = client
+        .chat_with_tools(model.to_string(), messages, vec![extraction_tool()], Some(EXTRACTION_MAX_TOKENS))
+        .await?;
+
+    let arguments = match reply.tool_calls.into_iter().find(|call| call.name == EXTRACTION_TOOL) {
+        Some(call) => call.arguments,
+        // Some models answer with the JSON itself instead of calling the tool
+        None => reply
+            .content
+            .as_deref()
+            .and_then(|content| serde_json::from_str(strip_code_fence(content)).ok())
+            .ok_or_else(|| AIMLError::ServiceUnavailable("No structured entities in the reply".to_string()))?,
+    };
+    Ok(from_arguments(text, &arguments))
+}
+
+fn strip_code_fence(content: &str) -> &str {
+    let content = content.trim();
+    content
+        .strip_prefix("```json")
+        .or_else(|| content.strip_prefix("```"))
+        .and_then(|inner| inner.strip_suffix("```"))
+        .map_or(content, str::trim)
+}
+
+fn score(value: &Value, default: f32) -> f32 {
+    value.as_f64().map_or(default, |score| score.clamp(0.0, 1.0) as f32)
+}
+
+/// Read the tool arguments; entities not found in `text` are dropped, so spans are always real
+fn from_arguments(text: &str, arguments: &Value) -> EntityExtraction {
+    let mut entities: Vec<TextEntity> = Vec::new();
+    for entity in arguments["entities"].as_array().into_iter().flatten() {
+        let Some(name) = entity["text"].as_str().map(str::trim).filter(|name| !name.is_empty()) else {
+            continue;
+        };
+        if entities.iter().any(|known| known.text == name) {
+            continue;
+        }
+        let spans = find_spans(text, name);
+        if spans.is_empty() {
+            continue;
+        }
+        entities.push(TextEntity {
+            text: name.to_string(),
+            entity_type: serde_json::from_value(entity["type"].clone()).unwrap_or(EntityType::Concept),
+            confidence: score(&entity["confidence"], 0.8),
+            context_relevance: score(&entity["relevance"], 0.5),
+            spans,
+        });
+    }
+
+    let concepts = arguments["concepts"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|concept| {
+            let name = concept["name"].as_str()?.trim();
+            (!name.is_empty()).then(|| Concept {
+                name: name.to_string(),
+                category: concept["category"].as_str().unwrap_or("general").to_string(),
+                abstractness: score(&concept["abstractness"], 0.5),
+                domain_relevance: score(&concept["relevance"], 0.5),
+                relationships: concept["related"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|related| related.as_str().map(str::to_string))
+                    .collect(),
+            })
+        })
+        .collect();
+
+    let lowered = text.to_lowercase();
+    let relationships = arguments["relationships"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|relationship| {
+            let from = relationship["from"].as_str()?.trim();
+            let to = relationship["to"].as_str()?.trim();
+            let relationship_type: RelationshipType = serde_json::from_value(relationship["type"].clone()).ok()?;
+            let mentioned = |name: &str| !name.is_empty() && lowered.contains(&name.to_lowercase());
+            (mentioned(from) && mentioned(to)).then(|| TextRelationship {
+                entity1: from.to_string(),
+                entity2: to.to_string(),
+                relationship_type,
+                strength: score(&relationship["strength"], 0.5),
+            })
+        })
+        .collect();
+
+    EntityExtraction {
+        entities,
+        concepts,
+        relationships,
+        source: ExtractionSource::Model,
+    }
+}
+
+fn is_boundary(c: Option<char>) -> bool {
+    c.map_or(true, |c| !c.is_alphanumeric())
+}
+
+fn utf16_offset(text: &str, byte: usize) -> usize {
+    text[..byte].encode_utf16().count()
+}
+
+fn span(text: &str, start: usize, end: usize) -> EntitySpan {
+    EntitySpan {
+        start: utf16_offset(text, start),
+        end: utf16_offset(text, end),
+    }
+}
+
+/// Every whole-word occurrence of `needle`, matching case only when an exact match exists
+fn find_spans(text: &str, needle: &str) -> Vec<EntitySpan> {
+    let whole_word = |start: usize, end: usize| {
+        is_boundary(text[..start].chars().next_back()) && is_boundary(text[end..].chars().next())
+    };
+    let exact: Vec<EntitySpan> = text
+        .match_indices(needle)
+        .filter(|(start, _)| whole_word(*start, start + needle.len()))
+        .map(|(start, _)| span(text, start, start + needle.len()))
+        .collect();
+    if !exact.is_empty() {
+        return exact;
+    }
+    let Ok(pattern) = Regex::new(&format!("(?i){}", regex::escape(needle))) else {
+        return Vec::new();
+    };
+    pattern
+        .find_iter(text)
+        .filter(|found| whole_word(found.start(), found.end()))
+        .map(|found| span(text, found.start(), found.end()))
+        .collect()
+}
+
+struct LocalPatterns {
+    date: Regex,
+    number: Regex,
+    technical: Regex,
+    name: Regex,
+}
+
+fn local_patterns() -> &'static LocalPatterns {
+    static PATTERNS: OnceLock<LocalPatterns> = OnceLock::new();
+    PATTERNS.get_or_init(|| {
+        let months = "January|February|March|April|May|June|July|August|September|October|November|December|\
+                      Jan|Feb|Mar|Apr|Jun|Jul|Aug|Sept|Sep|Oct|Nov|Dec";
+        LocalPatterns {
+            date: Regex::new(&format!(
+                r"\b(?:\d{{4}}-\d{{2}}-\d{{2}}|\d{{1,2}}/\d{{1,2}}/\d{{2,4}}|(?:{m})\.? \d{{1,2}}(?:st|nd|rd|th)?(?:,? \d{{4}})?|\d{{1,2}}(?:st|nd|rd|th)? (?:of )?(?:{m})(?:,? \d{{4}})?|(?:Monday|Tuesday|Wednesday|Thursday|Friday|Saturday|Sunday)|(?:{m}) \d{{4}})\b",
+                m = months
+            ))
+            .expect("date pattern is valid"),
+            number: Regex::new(r"[$€£¥]\s?\d[\d,]*(?:\.\d+)?(?:\s?(?:k|m|bn|million|billion))?\b|\b\d[\d,]*(?:\.\d+)?\s?%|\b\d[\d,]*(?:\.\d+)?\b")
+                .expect("number pattern is valid"),
+            technical: Regex::new(
+                r"\b(?:[A-Za-z]+-?\d+[A-Za-z0-9.]*|[a-z]+[A-Z][A-Za-z0-9]*|[A-Z][a-z]+[A-Z][A-Za-z0-9]*|[A-Za-z]+_[A-Za-z0-9_]+)\b",
+            )
+            .expect("technical term pattern is valid"),
+            name: Regex::new(r"\b[A-Z][\p{L}'’&-]*(?:\s+(?:(?:of|the|de|da|van|von|for)\s+)?[A-Z][\p{L}'’&-]*)*")
+                .expect("name pattern is valid"),
+        }
+    })
+}
+
+/// Whether the byte offset starts a sentence, where capitalization says nothing about names
+fn starts_sentence(text: &str, start: usize) -> bool {
+    let before = text[..start].trim_end();
+    before.is_empty() || before.ends_with(['.', '!', '?', '\n', ':', '"', '“'])
+}
+
+fn previous_word(text: &str, start: usize) -> Option<&str> {
+    text[..start]
+        .trim_end()
+        .rsplit(|c: char| c.is_whitespace())
+        .next()
+        .map(|word| word.trim_end_matches('.'))
+        .filter(|word| !word.is_empty())
+}
+
+fn classify_name(text: &str, start: usize, name: &str) -> Option<(EntityType, f32)> {
+    let words: Vec<&str> = name.split_whitespace().collect();
+    let first = *words.first()?;
+    let previous = previous_word(text, start);
+
+    if previous.map_or(false, |word| PERSON_TITLES.contains(&word)) {
+        return Some((EntityType::Person, 0.75));
+    }
+    if words.len() == 1
+        && (NOT_NAMES.contains(&first) || PERSON_TITLES.contains(&first) || starts_sentence(text, start))
+    {
+        return None;
+    }
+    if words.iter().any(|word| ORGANIZATION_WORDS.contains(&word.trim_end_matches('.'))) {
+        return Some((EntityType::Organization, 0.7));
+    }
+    if words.iter().any(|word| LOCATION_WORDS.contains(word)) {
+        return Some((EntityType::Location, 0.65));
+    }
+    if words.iter().any(|word| EVENT_WORDS.contains(word)) {
+        return Some((EntityType::Event, 0.6));
+    }
+    if previous.map_or(false, |word| LOCATION_CUES.contains(&word.to_lowercase().as_str())) {
+        return Some((EntityType::Location, 0.5));
+    }
+    if (2..=3).contains(&words.len()) {
+        return Some((EntityType::Person, 0.5));
+    }
+    // A proper noun of unknown kind
+    Some((EntityType::Concept, 0.3))
+}
+
+/// On-device recognizer: dates, technical terms, numbers and capitalized names, without concepts or relationships
+pub fn extract_locally(text: &str) -> EntityExtraction {
+    let patterns = local_patterns();
+    let mut taken: Vec<(usize, usize)> = Vec::new();
+    // Keyed by text so repeated mentions become one entity with several spans
+    let mut found: BTreeMap<String, (usize, EntityType, f32)> = BTreeMap::new();
+    let mut claim = |start: usize, end: usize, entity_type: EntityType, confidence: f32| {
+        if taken.iter().any(|(s, e)| start < *e && *s < end) {
+            return;
+        }
+        taken.push((start, end));
+        let order = found.len();
+        found
+            .entry(text[start..end].to_string())
+            .or_insert((order, entity_type, confidence));
+    };
+
+    for date in patterns.date.find_iter(text) {
+        claim(date.start(), date.end(), EntityType::Date, 0.8);
+    }
+    for term in patterns.technical.find_iter(text) {
+        claim(term.start(), term.end(), EntityType::TechnicalTerm, 0.5);
+    }
+    for number in patterns.number.find_iter(text) {
+        claim(number.start(), number.end(), EntityType::Number, 0.9);
+    }
+    for name in patterns.name.find_iter(text) {
+        let (mut start, mut name_text) = (name.start(), name.as_str().trim_end_matches(['\'', '’', '-', '&']));
+        // "Later Jane Smith" at the start of a sentence, or "The Bank of America"
+        while let Some((first, rest)) = name_text.split_once(char::is_whitespace) {
+            if !NOT_NAMES.contains(&first) {
+                break;
+            }
+            let rest = rest.trim_start();
+            start += name_text.len() - rest.len();
+            name_text = rest;
+        }
+        if let Some((entity_type, confidence)) = classify_name(text, start, name_text) {
+            claim(start, start + name_text.len(), entity_type, confidence);
+        }
+    }
+
+    let mut entities: Vec<(usize, TextEntity)> = found
+        .into_iter()
+        .map(|(name, (order, entity_type, confidence))| {
+            let spans = find_spans(text, &name);
+            (
+                order,
+                TextEntity {
+                    text: name,
+                    entity_type,
+                    confidence,
+                    context_relevance: 0.5,
+                    spans,
+                },
+            )
+        })
+        .collect();
+    entities.sort_by_key(|(order, _)| *order);
+
+    EntityExtraction {
+        entities: entities.into_iter().map(|(_, entity)| entity).collect(),
+        concepts: Vec::new(),
+        relationships: Vec::new(),
+        source: ExtractionSource::Local,
+    }
+}
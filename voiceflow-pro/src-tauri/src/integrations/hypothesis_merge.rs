@@ -0,0 +1,431 @@
+// Hypothesis Merging Module
+// Re-ranks the recognizer's top alternatives with the user's vocabulary and a language model, and combines them word by word when they disagree on single words
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use super::ai_ml_api::AIMLAPIGateway;
+use super::voice_recognition::Alternative;
+use crate::errors::{AppError, ValidationError};
+
+/// Choices kept for diagnostics
+const REMEMBERED_CHOICES: usize = 50;
+/// History segments the local model is trained on
+pub const LANGUAGE_MODEL_SEGMENTS: usize = 500;
+const RERANK_PROMPT: &str = "You are given numbered speech recognition hypotheses of one utterance. \
+    Reply with only the number of the hypothesis a native speaker most likely said.";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RerankModel {
+    /// Only confidence and vocabulary
+    None,
+    /// Word bigrams from the profile's own transcript history; free and on-device
+    Local,
+    /// Ask the text model which hypothesis is most plausible; one request per ambiguous utterance
+    Llm,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HypothesisMergeSettings {
+    pub enabled: bool,
+    pub language_model: RerankModel,
+    /// Weight of custom vocabulary and boosted keywords against recognizer confidence
+    pub vocabulary_weight: f32,
+    pub language_model_weight: f32,
+    /// The top hypothesis is kept without re-ranking when it leads the next by this much confidence
+    pub decisive_margin: f32,
+    /// Vote word by word across hypotheses that differ only in single words
+    pub combine_words: bool,
+}
+
+impl Default for HypothesisMergeSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            language_model: RerankModel::Local,
+            vocabulary_weight: 0.3,
+            language_model_weight: 0.3,
+            decisive_margin: 0.3,
+            combine_words: true,
+        }
+    }
+}
+
+pub fn validate(settings: &HypothesisMergeSettings) -> Result<(), AppError> {
+    let invalid = |message: String| AppError::Validation(ValidationError::InvalidConfigValue(message));
+    for (name, value) in [
+        ("vocabulary weight", settings.vocabulary_weight),
+        ("language model weight", settings.language_model_weight),
+        ("decisive margin", settings.decisive_margin),
+    ] {
+        if !(0.0..=1.0).contains(&value) {
+            return Err(invalid(format!("Hypothesis merging {} must be 0-1, got {}", name, value)));
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HypothesisScore {
+    /// Position in the recognizer's list, 0 being its top choice
+    pub index: usize,
+    pub transcript: String,
+    pub confidence: f32,
+    pub vocabulary: f32,
+    pub language_model: f32,
+    pub total: f32,
+}
+
+/// Which hypothesis won and why, sent as "hypothesis-merged" and kept for diagnostics
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HypothesisChoice {
+    pub transcript: String,
+    /// Hypothesis the transcript came from; for a combined transcript, the best-scored one
+    pub winner: usize,
+    /// The recognizer's own top choice was replaced
+    pub changed: bool,
+    /// Words were taken from more than one hypothesis
+    pub combined: bool,
+    /// Scores were computed; false when the top hypothesis was decisive
+    pub reranked: bool,
+    pub language_model: RerankModel,
+    pub candidates: Vec<HypothesisScore>,
+    pub timestamp: u64,
+}
+
+fn words(text: &str) -> Vec<String> {
+    text.split_whitespace()
+        .map(|word| {
+            word.trim_matches(|c: char| !c.is_alphanumeric() && c != '\'')
+                .to_lowercase()
+        })
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+/// Word bigram model with add-one smoothing, trained on what the user has dictated before
+#[derive(Debug, Default)]
+pub struct BigramModel {
+    unigrams: HashMap<String, u32>,
+    bigrams: HashMap<(String, String), u32>,
+}
+
+impl BigramModel {
+    pub fn train<'a>(texts: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut model = Self::default();
+        for text in texts {
+            let tokens: Vec<String> = std::iter::once("<s>".to_string()).chain(words(text)).collect();
+            for token in &tokens {
+                *model.unigrams.entry(token.clone()).or_default() += 1;
+            }
+            for pair in tokens.windows(2) {
+                *model.bigrams.entry((pair[0].clone(), pair[1].clone())).or_default() += 1;
+            }
+        }
+        model
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bigrams.is_empty()
+    }
+
+    /// Mean log probability per word, so hypotheses of different lengths compare fairly
+    pub fn score(&self, text: &str) -> f32 {
+        let tokens: Vec<String> = std::iter::once("<s>".to_string()).chain(words(text)).collect();
+        if tokens.len() < 2 {
+            return 0.0;
+        }
+        let vocabulary = self.unigrams.len().max(1) as f32;
+        let total: f32 = tokens
+            .windows(2)
+            .map(|pair| {
+                let previous = self.unigrams.get(&pair[0]).copied().unwrap_or(0) as f32;
+                let seen = self.bigrams.get(&(pair[0].clone(), pair[1].clone())).copied().unwrap_or(0) as f32;
+                ((seen + 1.0) / (previous + vocabulary)).ln()
+            })
+            .sum();
+        total / (tokens.len() - 1) as f32
+    }
+}
+
+/// Share of the hypothesis's words that belong to vocabulary terms it contains
+fn vocabulary_score(text: &str, vocabulary: &[String]) -> f32 {
+    let hypothesis = words(text);
+    if hypothesis.is_empty() {
+        return 0.0;
+    }
+    let mut matched = vec![false; hypothesis.len()];
+    for term in vocabulary {
+        let term = words(term);
+        if term.is_empty() || term.len() > hypothesis.len() {
+            continue;
+        }
+        for start in 0..=hypothesis.len() - term.len() {
+            if hypothesis[start..start + term.len()] == term[..] {
+                matched[start..start + term.len()].iter_mut().for_each(|word| *word = true);
+            }
+        }
+    }
+    matched.iter().filter(|word| **word).count() as f32 / hypothesis.len() as f32
+}
+
+/// Spread scores over 0-1 so the weights mean the same whatever the model's scale
+fn normalize(scores: &[f32]) -> Vec<f32> {
+    let (low, high) = scores
+        .iter()
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(low, high), score| (low.min(*score), high.max(*score)));
+    if !(high - low).is_normal() {
+        return vec![0.0; scores.len()];
+    }
+    scores.iter().map(|score| (score - low) / (high - low)).collect()
+}
+
+/// Word-by-word vote across hypotheses with the same number of words, each voting with its total score
+fn combine(candidates: &[HypothesisScore], vocabulary: &[String]) -> Option<String> {
+    let split: Vec<Vec<&str>> = candidates
+        .iter()
+        .map(|candidate| candidate.transcript.split_whitespace().collect())
+        .collect();
+    let length = split.first()?.len();
+    if split.len() < 2 || split.iter().any(|words| words.len() != length) {
+        return None;
+    }
+    let known: Vec<String> = vocabulary.iter().flat_map(|term| words(term)).collect();
+    let combined: Vec<&str> = (0..length)
+        .map(|position| {
+            let mut votes: Vec<(&str, f32)> = Vec::new();
+            for (candidate, words_of) in candidates.iter().zip(&split) {
+                let word = words_of[position];
+                let bonus = if known.contains(&words(word).concat()) { 0.5 } else { 0.0 };
+                match votes.iter_mut().find(|(seen, _)| seen.eq_ignore_ascii_case(word)) {
+                    Some((_, weight)) => *weight += candidate.total + bonus,
+                    None => votes.push((word, candidate.total + bonus)),
+                }
+            }
+            votes
+                .into_iter()
+                .fold(("", f32::NEG_INFINITY), |best, vote| if vote.1 > best.1 { vote } else { best })
+                .0
+        })
+        .collect();
+    Some(combined.join(" "))
+}
+
+/// Pick, or combine, the best of the recognizer's alternatives. `alternatives` is in the recognizer's
+/// order; `history` trains the local model and `gateway` is only used for `RerankModel::Llm`.
+pub async fn merge(
+    alternatives: &[Alternative],
+    vocabulary: &[String],
+    history: &[String],
+    settings: &HypothesisMergeSettings,
+    gateway: Option<(&AIMLAPIGateway, &str)>,
+) -> Option<HypothesisChoice> {
+    let alternatives: Vec<&Alternative> = alternatives
+        .iter()
+        .filter(|alternative| !alternative.transcript.trim().is_empty())
+        .collect();
+    let top = alternatives.first()?;
+    let mut choice = HypothesisChoice {
+        transcript: top.transcript.trim().to_string(),
+        winner: 0,
+        changed: false,
+        combined: false,
+        reranked: false,
+        language_model: settings.language_model,
+        candidates: alternatives
+            .iter()
+            .enumerate()
+            .map(|(index, alternative)| HypothesisScore {
+                index,
+                transcript: alternative.transcript.trim().to_string(),
+                confidence: alternative.confidence,
+                vocabulary: 0.0,
+                language_model: 0.0,
+                total: alternative.confidence,
+            })
+            .collect(),
+        timestamp: chrono::Utc::now().timestamp_millis() as u64,
+    };
+    let runner_up = alternatives.get(1).map_or(0.0, |alternative| alternative.confidence);
+    if alternatives.len() < 2 || top.confidence - runner_up >= settings.decisive_margin {
+        return Some(choice);
+    }
+    choice.reranked = true;
+
+    let language_scores = match settings.language_model {
+        RerankModel::None => vec![0.0; alternatives.len()],
+        RerankModel::Local => {
+            let model = BigramModel::train(history.iter().map(String::as_str));
+            if model.is_empty() {
+                vec![0.0; alternatives.len()]
+            } else {
+                normalize(&choice.candidates.iter().map(|candidate| model.score(&candidate.transcript)).collect::<Vec<_>>())
+            }
+        }
+        RerankModel::Llm => match gateway {
+            Some((gateway, model)) => llm_pick(gateway, model, &choice.candidates).await,
+            None => vec![0.0; alternatives.len()],
+        },
+    };
+    let confidences = normalize(&choice.candidates.iter().map(|candidate| candidate.confidence).collect::<Vec<_>>());
+    for ((candidate, language), confidence) in choice.candidates.iter_mut().zip(language_scores).zip(confidences) {
+        candidate.vocabulary = vocabulary_score(&candidate.transcript, vocabulary);
+        candidate.language_model = language;
+        candidate.total = confidence
+            + settings.vocabulary_weight * candidate.vocabulary
+            + settings.language_model_weight * candidate.language_model;
+    }
+
+    let winner = choice
+        .candidates
+        .iter()
+        .fold(&choice.candidates[0], |best, candidate| if candidate.total > best.total { candidate } else { best });
+    choice.winner = winner.index;
+    choice.transcript = winner.transcript.clone();
+    if settings.combine_words {
+        if let Some(combined) = combine(&choice.candidates, vocabulary) {
+            choice.combined = choice.candidates.iter().all(|candidate| candidate.transcript != combined);
+            choice.transcript = combined;
+        }
+    }
+    choice.changed = choice.transcript != choice.candidates[0].transcript;
+    Some(choice)
+}
+
+/// 1 for the hypothesis the model named, 0 for the rest; all 0 when it fails or answers something else
+async fn llm_pick(gateway: &AIMLAPIGateway, model: &str, candidates: &[HypothesisScore]) -> Vec<f32> {
+    let listed = candidates
+        .iter()
+        .map(|candidate| format!("{}. {}", candidate.index + 1, candidate.transcript))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let mut scores = vec![0.0; candidates.len()];
+    match gateway
+        .complete_with_model(model.to_string(), RERANK_PROMPT.to_string(), listed, Some(0.0))
+        .await
+    {
+        Ok((reply, _usage)) => {
+            let picked = reply
+                .trim()
+                .trim_end_matches('.')
+                .parse::<usize>()
+                .ok()
+                .and_then(|number| number.checked_sub(1));
+            match picked.and_then(|index| scores.get_mut(index)) {
+                Some(score) => *score = 1.0,
+                None => log::warn!("Hypothesis re-ranking answered {:?}", reply.trim()),
+            }
+        }
+        Err(e) => log::warn!("Hypothesis re-ranking failed: {}", e),
+    }
+    scores
+}
+
+fn recent_choices() -> &'static Mutex<VecDeque<HypothesisChoice>> {
+    static CHOICES: OnceLock<Mutex<VecDeque<HypothesisChoice>>> = OnceLock::new();
+    CHOICES.get_or_init(|| Mutex::new(VecDeque::new()))
+}
+
+pub async fn remember(choice: &HypothesisChoice) {
+    let mut choices = recent_choices().lock().await;
+    choices.push_back(choice.clone());
+    while choices.len() > REMEMBERED_CHOICES {
+        choices.pop_front();
+    }
+}
+
+/// Recent choices, newest first
+pub async fn recent(limit: usize) -> Vec<HypothesisChoice> {
+    recent_choices().lock().await.iter().rev().take(limit).cloned().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alternative(transcript: &str, confidence: f32) -> Alternative {
+        Alternative {
+            transcript: transcript.to_string(),
+            confidence,
+        }
+    }
+
+    fn scored(transcript: &str, total: f32) -> HypothesisScore {
+        HypothesisScore {
+            index: 0,
+            transcript: transcript.to_string(),
+            confidence: total,
+            vocabulary: 0.0,
+            language_model: 0.0,
+            total,
+        }
+    }
+
+    #[test]
+    fn bigram_model_prefers_word_orders_it_has_seen() {
+        assert!(BigramModel::train(Vec::<&str>::new()).is_empty());
+        let model = BigramModel::train(["Send the report today.", "send the report to the team"]);
+        assert!(!model.is_empty());
+        assert!(model.score("send the report") > model.score("report the send"));
+        assert_eq!(model.score(""), 0.0);
+    }
+
+    #[test]
+    fn combine_votes_word_by_word() {
+        let candidates = [
+            scored("meet at the cafe", 1.0),
+            scored("meat at a cafe", 0.6),
+            scored("meat on the cafe", 0.5),
+        ];
+        assert_eq!(combine(&candidates, &[]).as_deref(), Some("meat at the cafe"));
+    }
+
+    #[test]
+    fn combine_gives_vocabulary_words_a_bonus_and_needs_equal_lengths() {
+        let candidates = [scored("call jon now", 0.6), scored("call John now", 0.4)];
+        assert_eq!(combine(&candidates, &["John".to_string()]).as_deref(), Some("call John now"));
+        assert_eq!(combine(&[scored("call jon now", 0.6), scored("call jon", 0.4)], &[]), None);
+        assert_eq!(combine(&[scored("call jon now", 0.6)], &[]), None);
+    }
+
+    #[tokio::test]
+    async fn merge_keeps_a_decisive_top_hypothesis() {
+        let alternatives = [alternative("ship it on Friday", 0.9), alternative("ship it on fry day", 0.4)];
+        let choice = merge(&alternatives, &[], &[], &HypothesisMergeSettings::default(), None)
+            .await
+            .unwrap();
+        assert!(!choice.reranked);
+        assert!(!choice.changed);
+        assert_eq!(choice.transcript, "ship it on Friday");
+        assert!(merge(&[alternative("  ", 0.9)], &[], &[], &HypothesisMergeSettings::default(), None)
+            .await
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn merge_reranks_with_vocabulary_and_history() {
+        let settings = HypothesisMergeSettings {
+            vocabulary_weight: 1.0,
+            language_model_weight: 1.0,
+            ..HypothesisMergeSettings::default()
+        };
+        let history = [
+            "deploy it with the kubernetes team".to_string(),
+            "ask kubernetes team about it".to_string(),
+        ];
+        let alternatives = [alternative("ask cooper netties team", 0.6), alternative("ask kubernetes team", 0.55)];
+        let choice = merge(&alternatives, &["Kubernetes".to_string()], &history, &settings, None)
+            .await
+            .unwrap();
+        assert!(choice.reranked);
+        assert!(choice.changed);
+        assert!(!choice.combined);
+        assert_eq!(choice.winner, 1);
+        assert_eq!(choice.transcript, "ask kubernetes team");
+    }
+}
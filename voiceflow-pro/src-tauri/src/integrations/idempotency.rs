@@ -0,0 +1,415 @@
+// Idempotency Module
+// Remembers in-flight and recent results per client-supplied key, so a retried request is answered without paying for it twice
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tokio::sync::watch;
+
+use super::ai_ml_api::{AIMLError, AIMLResponse};
+use crate::encryption::{get_data_vault, is_encrypted};
+use crate::errors::{AppError, ValidationError};
+use crate::storage::{ensure_data_dir, DataDir};
+
+/// Subdirectory of the cache directory holding results of persisted scopes
+const IDEMPOTENCY_DIR: &str = "idempotency";
+/// Persisted results are sealed with the data vault; files named `.json` are plaintext from older builds
+const RESULT_EXTENSION: &str = "sealed";
+/// How long a duplicate is answered from memory; frontend retries happen within seconds
+const MEMORY_TTL: Duration = Duration::from_secs(10 * 60);
+/// Long jobs may be retried after a crash and restart, so their results are kept for a day
+const PERSISTED_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+const MAX_ENTRIES: usize = 512;
+const MAX_KEY_LENGTH: usize = 128;
+
+/// Kind of request a key belongs to; the same key used for two kinds names two requests
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IdempotencyScope {
+    Enhancement,
+    Translation,
+    Synthesis,
+    LongText,
+}
+
+impl IdempotencyScope {
+    fn name(self) -> &'static str {
+        match self {
+            IdempotencyScope::Enhancement => "enhancement",
+            IdempotencyScope::Translation => "translation",
+            IdempotencyScope::Synthesis => "synthesis",
+            IdempotencyScope::LongText => "long_text",
+        }
+    }
+
+    /// Only long jobs are written to disk: they are the ones worth recovering after a crash.
+    /// Even then only while data encryption is unlocked, as the results are the user's documents
+    fn persisted(self) -> bool {
+        matches!(self, IdempotencyScope::LongText)
+    }
+
+    fn ttl(self) -> Duration {
+        if self.persisted() {
+            PERSISTED_TTL
+        } else {
+            MEMORY_TTL
+        }
+    }
+}
+
+/// Keys are chosen by the frontend, usually a UUID per user action
+pub fn validate_key(key: &str) -> Result<String, AppError> {
+    let key = key.trim();
+    if key.is_empty() || key.len() > MAX_KEY_LENGTH || !key.chars().all(|c| c.is_ascii_graphic()) {
+        return Err(AppError::Validation(ValidationError::InvalidConfigValue(format!(
+            "Idempotency key must be 1-{} printable ASCII characters",
+            MAX_KEY_LENGTH
+        ))));
+    }
+    Ok(key.to_string())
+}
+
+/// Hash of what a request asks for, leaving out ids and timestamps, so a key reused for a different request
+/// names a different entry instead of replaying someone else's result
+pub fn fingerprint(inputs: &impl Serialize) -> String {
+    let json = serde_json::to_vec(inputs).unwrap_or_default();
+    hex(&Sha256::digest(&json))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// What a request produces, and which part of it may be replayed to a duplicate; failures never are, so a retry runs again
+pub trait Outcome: Sized {
+    type Stored: Serialize + DeserializeOwned;
+
+    fn stored(&self) -> Option<&Self::Stored>;
+    fn replay(stored: Self::Stored) -> Self;
+}
+
+impl<T: Serialize + DeserializeOwned> Outcome for Result<T, AIMLError> {
+    type Stored = T;
+
+    fn stored(&self) -> Option<&T> {
+        self.as_ref().ok()
+    }
+
+    fn replay(stored: T) -> Self {
+        Ok(stored)
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> Outcome for AIMLResponse<T> {
+    type Stored = AIMLResponse<T>;
+
+    fn stored(&self) -> Option<&AIMLResponse<T>> {
+        match self {
+            AIMLResponse::Failure(_) => None,
+            _ => Some(self),
+        }
+    }
+
+    fn replay(stored: AIMLResponse<T>) -> Self {
+        stored
+    }
+}
+
+enum Entry {
+    /// Dropping the sender, on completion or abandonment, wakes every duplicate waiting on it
+    InFlight(watch::Receiver<()>),
+    Done { value: Value, expires: Instant },
+}
+
+#[derive(Serialize, Deserialize)]
+struct PersistedResult {
+    stored_at: DateTime<Utc>,
+    value: Value,
+}
+
+#[derive(Default)]
+pub struct IdempotencyStore {
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+impl std::fmt::Debug for IdempotencyStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let entries = self.entries.lock().map(|entries| entries.len()).unwrap_or_default();
+        f.debug_struct("IdempotencyStore").field("entries", &entries).finish()
+    }
+}
+
+enum Lookup {
+    Replay(Value),
+    Wait(watch::Receiver<()>),
+    Run(watch::Sender<()>),
+}
+
+impl IdempotencyStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run `request` unless the same key and `fingerprint` already produced a result, in which case that result is
+    /// returned. A duplicate that arrives while the first is still running waits for it instead of starting another
+    pub async fn run<O, F>(&self, scope: IdempotencyScope, key: &str, fingerprint: &str, request: F) -> O
+    where
+        O: Outcome,
+        F: Future<Output = O>,
+    {
+        let id = entry_id(scope, key, fingerprint);
+        let mut checked_disk = !scope.persisted();
+        let sender = loop {
+            match self.lookup(&id) {
+                Lookup::Replay(value) => match serde_json::from_value(value) {
+                    Ok(stored) => {
+                        log::info!("Answering a repeated {} request from its earlier result", scope.name());
+                        return O::replay(stored);
+                    }
+                    Err(e) => {
+                        log::warn!("Discarding an unreadable remembered {} result: {}", scope.name(), e);
+                        self.forget(&id);
+                    }
+                },
+                Lookup::Wait(mut finished) => {
+                    let _ = finished.changed().await;
+                }
+                Lookup::Run(sender) if !checked_disk => {
+                    // Claimed the key; a result written before a crash still answers it
+                    checked_disk = true;
+                    if let Some(value) = read_persisted(&id, scope).await {
+                        self.complete(&id, scope, value);
+                        drop(sender);
+                    } else {
+                        break sender;
+                    }
+                }
+                Lookup::Run(sender) => break sender,
+            }
+        };
+
+        // Abandoning the request, e.g. when the command is cancelled, must not leave duplicates waiting forever
+        let mut claim = Claim {
+            store: self,
+            id: &id,
+            completed: false,
+            _sender: sender,
+        };
+        let outcome = request.await;
+        match outcome.stored().map(serde_json::to_value) {
+            Some(Ok(value)) => {
+                if scope.persisted() {
+                    write_persisted(&id, &value).await;
+                }
+                self.complete(&id, scope, value);
+                claim.completed = true;
+            }
+            Some(Err(e)) => log::warn!("Could not remember a {} result: {}", scope.name(), e),
+            None => {}
+        }
+        // Dropping the claim wakes the duplicates, which now find the result or the key free
+        drop(claim);
+        outcome
+    }
+
+    fn lookup(&self, id: &str) -> Lookup {
+        let mut entries = match self.entries.lock() {
+            Ok(entries) => entries,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        prune(&mut entries);
+        match entries.get(id) {
+            Some(Entry::Done { value, .. }) => return Lookup::Replay(value.clone()),
+            // A sender that is gone belonged to a request that ended without a result; the key is free again
+            Some(Entry::InFlight(finished)) if finished.has_changed().is_ok() => return Lookup::Wait(finished.clone()),
+            _ => {}
+        }
+        let (sender, finished) = watch::channel(());
+        entries.insert(id.to_string(), Entry::InFlight(finished));
+        Lookup::Run(sender)
+    }
+
+    fn complete(&self, id: &str, scope: IdempotencyScope, value: Value) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.insert(
+                id.to_string(),
+                Entry::Done {
+                    value,
+                    expires: Instant::now() + scope.ttl(),
+                },
+            );
+        }
+    }
+
+    fn forget(&self, id: &str) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.remove(id);
+        }
+    }
+}
+
+/// Releases a claimed key when the request ends without a result to remember
+struct Claim<'a> {
+    store: &'a IdempotencyStore,
+    id: &'a str,
+    completed: bool,
+    _sender: watch::Sender<()>,
+}
+
+impl Drop for Claim<'_> {
+    fn drop(&mut self) {
+        if !self.completed {
+            self.store.forget(self.id);
+        }
+    }
+}
+
+fn prune(entries: &mut HashMap<String, Entry>) {
+    let now = Instant::now();
+    entries.retain(|_, entry| match entry {
+        Entry::Done { expires, .. } => *expires > now,
+        Entry::InFlight(_) => true,
+    });
+    if entries.len() > MAX_ENTRIES {
+        let mut done: Vec<(String, Instant)> = entries
+            .iter()
+            .filter_map(|(id, entry)| match entry {
+                Entry::Done { expires, .. } => Some((id.clone(), *expires)),
+                Entry::InFlight(_) => None,
+            })
+            .collect();
+        done.sort_by_key(|(_, expires)| *expires);
+        let excess = entries.len() - MAX_ENTRIES;
+        for (id, _) in done.into_iter().take(excess) {
+            entries.remove(&id);
+        }
+    }
+}
+
+/// Keys come from the frontend, so they are hashed before becoming map keys and file names
+fn entry_id(scope: IdempotencyScope, key: &str, fingerprint: &str) -> String {
+    hex(&Sha256::digest(format!("{}:{}:{}", scope.name(), fingerprint, key).as_bytes()))
+}
+
+fn persisted_dir() -> Option<PathBuf> {
+    ensure_data_dir(DataDir::Cache)
+        .ok()
+        .map(|dir| dir.join(IDEMPOTENCY_DIR))
+}
+
+async fn read_persisted(id: &str, scope: IdempotencyScope) -> Option<Value> {
+    let path = persisted_dir()?.join(format!("{}.{}", id, RESULT_EXTENSION));
+    let contents = tokio::fs::read(&path).await.ok()?;
+    if !is_encrypted(&contents) {
+        let _ = tokio::fs::remove_file(&path).await;
+        return None;
+    }
+    let persisted: PersistedResult = serde_json::from_slice(&get_data_vault().open(contents).ok()?).ok()?;
+    let age = Utc::now().signed_duration_since(persisted.stored_at);
+    if age.to_std().map_or(true, |age| age > scope.ttl()) {
+        let _ = tokio::fs::remove_file(&path).await;
+        return None;
+    }
+    Some(persisted.value)
+}
+
+async fn write_persisted(id: &str, value: &Value) {
+    let Some(dir) = persisted_dir() else {
+        return;
+    };
+    if let Err(e) = tokio::fs::create_dir_all(&dir).await {
+        log::warn!("Could not create {}: {}", dir.display(), e);
+        return;
+    }
+    remove_expired(&dir).await;
+    let persisted = PersistedResult {
+        stored_at: Utc::now(),
+        value: value.clone(),
+    };
+    let Ok(contents) = serde_json::to_vec(&persisted).map_err(AppError::from).and_then(|json| get_data_vault().seal(&json))
+    else {
+        return;
+    };
+    if !is_encrypted(&contents) {
+        log::debug!("Long job result kept in memory only: data encryption is not unlocked");
+        return;
+    }
+    // Written aside and renamed, so a crash mid-write never leaves a truncated result to replay
+    let path = dir.join(format!("{}.{}", id, RESULT_EXTENSION));
+    let partial = path.with_extension("tmp");
+    let written = async {
+        tokio::fs::write(&partial, &contents).await?;
+        tokio::fs::rename(&partial, &path).await
+    };
+    if let Err(e) = written.await {
+        log::warn!("Could not persist a long job result: {}", e);
+    }
+}
+
+/// Also removes the plaintext results older builds wrote
+async fn remove_expired(dir: &std::path::Path) {
+    let Ok(mut files) = tokio::fs::read_dir(dir).await else {
+        return;
+    };
+    while let Ok(Some(file)) = files.next_entry().await {
+        if file.path().extension().map_or(false, |extension| extension == "json") {
+            let _ = tokio::fs::remove_file(file.path()).await;
+            continue;
+        }
+        let expired = file
+            .metadata()
+            .await
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .map_or(false, |age| age > PERSISTED_TTL);
+        if expired {
+            let _ = tokio::fs::remove_file(file.path()).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    async fn counted(runs: &AtomicUsize, answer: &str) -> Result<String, AIMLError> {
+        runs.fetch_add(1, Ordering::SeqCst);
+        Ok(answer.to_string())
+    }
+
+    #[tokio::test]
+    async fn a_repeated_request_is_answered_from_memory() {
+        let store = IdempotencyStore::new();
+        let runs = AtomicUsize::new(0);
+        let inputs = fingerprint(&("hola", "en"));
+        let first = store.run(IdempotencyScope::Translation, "key-1", &inputs, counted(&runs, "hello")).await;
+        let second = store.run(IdempotencyScope::Translation, "key-1", &inputs, counted(&runs, "other")).await;
+        assert_eq!(first.unwrap(), "hello");
+        assert_eq!(second.unwrap(), "hello");
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn a_key_reused_for_a_different_request_runs_it() {
+        let store = IdempotencyStore::new();
+        let runs = AtomicUsize::new(0);
+        let hola = fingerprint(&("hola", "en"));
+        let adios = fingerprint(&("adios", "en"));
+        assert_ne!(hola, adios);
+        let first = store.run(IdempotencyScope::Translation, "key-1", &hola, counted(&runs, "hello")).await;
+        let second = store.run(IdempotencyScope::Translation, "key-1", &adios, counted(&runs, "goodbye")).await;
+        assert_eq!(first.unwrap(), "hello");
+        assert_eq!(second.unwrap(), "goodbye");
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+    }
+}
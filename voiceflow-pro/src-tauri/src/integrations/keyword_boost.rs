@@ -0,0 +1,54 @@
+// Keyword Boost Module
+// Per-profile project vocabulary, passed to recognition as hints and to enhancement as terms to keep verbatim
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+/// Most terms one profile may boost; recognizers cap phrase hints and long lists dilute the prompt
+pub const MAX_BOOST_KEYWORDS: usize = 500;
+
+/// Named keyword lists with at most one active, so a project's terms apply only while working on it
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeywordBoostSettings {
+    pub active_profile: Option<String>,
+    pub profiles: HashMap<String, Vec<String>>,
+}
+
+impl KeywordBoostSettings {
+    /// Keywords of the active profile, empty when none is active
+    pub fn active_keywords(&self) -> Vec<String> {
+        self.active_profile
+            .as_ref()
+            .and_then(|name| self.profiles.get(name))
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+/// Trim, drop blanks and case-insensitive duplicates, keeping the first spelling
+pub fn clean_keywords(keywords: Vec<String>) -> Result<Vec<String>, String> {
+    let mut cleaned: Vec<String> = Vec::new();
+    for keyword in keywords {
+        let keyword = keyword.split_whitespace().collect::<Vec<_>>().join(" ");
+        if !keyword.is_empty() && !cleaned.iter().any(|known| known.eq_ignore_ascii_case(&keyword)) {
+            cleaned.push(keyword);
+        }
+    }
+    if cleaned.len() > MAX_BOOST_KEYWORDS {
+        return Err(format!("A profile can boost at most {} keywords", MAX_BOOST_KEYWORDS));
+    }
+    Ok(cleaned)
+}
+
+/// Recognition hints: the user's vocabulary followed by the active profile's keywords
+pub fn recognition_hints(vocabulary: &[String], boost: &KeywordBoostSettings) -> Vec<String> {
+    let mut hints = vocabulary.to_vec();
+    for keyword in boost.active_keywords() {
+        if !hints.iter().any(|known| known.eq_ignore_ascii_case(&keyword)) {
+            hints.push(keyword);
+        }
+    }
+    hints
+}
@@ -0,0 +1,329 @@
+// Language Registry Module
+// Single source of truth for supported languages and their STT/TTS/translation capabilities
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashSet};
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+use crate::errors::AppError;
+use crate::storage::{app_data_dir, ensure_data_dir, DataDir};
+
+/// Registry shipped with the app; a `languages.json` in the data directory overrides it
+const BUNDLED_REGISTRY: &str = include_str!("../../resources/languages.json");
+const REGISTRY_FILE: &str = "languages.json";
+const STATE_FILE: &str = "language_state.json";
+/// Base URL for resources that are listed by relative path
+const RESOURCE_BASE_URL: &str = "https://voiceflow.pro/language-packs";
+
+/// What a language can be used for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LanguageCapability {
+    Stt,
+    Tts,
+    Translation,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LanguageCapabilities {
+    pub stt: bool,
+    pub tts: bool,
+    pub translation: bool,
+}
+
+impl LanguageCapabilities {
+    pub fn supports(&self, capability: LanguageCapability) -> bool {
+        match capability {
+            LanguageCapability::Stt => self.stt,
+            LanguageCapability::Tts => self.tts,
+            LanguageCapability::Translation => self.translation,
+        }
+    }
+}
+
+/// Kinds of downloadable per-language resources
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResourceKind {
+    Model,
+    PunctuationRules,
+}
+
+/// A downloadable resource; either an absolute `url` or a `path` relative to the language-pack server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LanguageResource {
+    pub id: String,
+    pub kind: ResourceKind,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+impl LanguageResource {
+    pub fn download_url(&self) -> Option<String> {
+        self.url
+            .clone()
+            .or_else(|| self.path.as_ref().map(|p| format!("{}/{}", RESOURCE_BASE_URL, p)))
+    }
+
+    /// Where the resource lives once installed
+    pub fn install_path(&self) -> Result<PathBuf, AppError> {
+        let (dir, extension) = match self.kind {
+            ResourceKind::Model => (DataDir::Models, "bin"),
+            ResourceKind::PunctuationRules => (DataDir::Languages, "json"),
+        };
+        Ok(ensure_data_dir(dir)?.join(format!("{}.{}", self.id, extension)))
+    }
+
+    pub fn is_installed(&self) -> bool {
+        self.install_path().map(|p| p.exists()).unwrap_or(false)
+    }
+}
+
+/// A language as described by the registry data file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LanguageEntry {
+    /// Locale code, e.g. "en-US"
+    pub code: String,
+    /// ISO 639-1 code used for translation, e.g. "en"
+    pub base: String,
+    pub name: String,
+    pub native_name: String,
+    pub flag: String,
+    pub family: String,
+    /// "ltr" or "rtl"
+    pub direction: String,
+    pub capabilities: LanguageCapabilities,
+    pub translation_quality: String,
+    pub translation_models: Vec<String>,
+    #[serde(default)]
+    pub resources: Vec<LanguageResource>,
+}
+
+impl LanguageEntry {
+    /// Language name without the region qualifier, e.g. "English" for "English (US)"
+    pub fn base_name(&self) -> &str {
+        self.name.split(" (").next().unwrap_or(&self.name)
+    }
+
+    pub fn base_native_name(&self) -> &str {
+        self.native_name
+            .split([' ', '（'])
+            .next()
+            .filter(|s| !s.is_empty())
+            .unwrap_or(&self.native_name)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct RegistryFile {
+    #[allow(dead_code)]
+    version: u32,
+    languages: Vec<LanguageEntry>,
+}
+
+/// User choices persisted across runs
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct RegistryState {
+    disabled: BTreeSet<String>,
+}
+
+/// A language with its user-facing status
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LanguageStatus {
+    #[serde(flatten)]
+    pub language: LanguageEntry,
+    pub enabled: bool,
+    pub resources_installed: bool,
+}
+
+/// Registry of all known languages
+#[derive(Debug)]
+pub struct LanguageRegistry {
+    entries: Vec<LanguageEntry>,
+    state: RegistryState,
+}
+
+impl LanguageRegistry {
+    fn load() -> Self {
+        let override_file = app_data_dir().ok().map(|dir| dir.join(REGISTRY_FILE));
+        let entries = override_file
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|json| match serde_json::from_str::<RegistryFile>(&json) {
+                Ok(file) => Some(file.languages),
+                Err(e) => {
+                    log::warn!("Ignoring invalid {}: {}", REGISTRY_FILE, e);
+                    None
+                }
+            })
+            .unwrap_or_else(|| {
+                serde_json::from_str::<RegistryFile>(BUNDLED_REGISTRY)
+                    .map(|file| file.languages)
+                    .expect("bundled language registry is valid JSON")
+            });
+
+        let state = app_data_dir()
+            .ok()
+            .and_then(|dir| std::fs::read_to_string(dir.join(STATE_FILE)).ok())
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+
+        Self { entries, state }
+    }
+
+    pub fn get(&self, code: &str) -> Option<&LanguageEntry> {
+        self.entries.iter().find(|entry| entry.code == code)
+    }
+
+    pub fn is_enabled(&self, code: &str) -> bool {
+        !self.state.disabled.contains(code)
+    }
+
+    /// Whether `code` (locale or base code) is enabled for `capability`
+    pub fn supports(&self, code: &str, capability: LanguageCapability) -> bool {
+        self.entries.iter().any(|entry| {
+            (entry.code == code || entry.base == code)
+                && entry.capabilities.supports(capability)
+                && self.is_enabled(&entry.code)
+        })
+    }
+
+    /// Languages, optionally filtered by capability
+    pub fn languages(&self, capability: Option<LanguageCapability>, include_disabled: bool) -> Vec<LanguageStatus> {
+        self.entries
+            .iter()
+            .filter(|entry| capability.map(|c| entry.capabilities.supports(c)).unwrap_or(true))
+            .filter(|entry| include_disabled || self.is_enabled(&entry.code))
+            .map(|entry| LanguageStatus {
+                language: entry.clone(),
+                enabled: self.is_enabled(&entry.code),
+                resources_installed: entry.resources.iter().all(|r| r.is_installed()),
+            })
+            .collect()
+    }
+
+    /// Enabled translation languages, one per base code
+    pub fn translation_languages(&self) -> Vec<&LanguageEntry> {
+        let mut seen = HashSet::new();
+        self.entries
+            .iter()
+            .filter(|entry| entry.capabilities.translation && self.is_enabled(&entry.code))
+            .filter(|entry| seen.insert(entry.base.clone()))
+            .collect()
+    }
+
+    pub fn set_enabled(&mut self, code: &str, enabled: bool) -> Result<(), AppError> {
+        if self.get(code).is_none() {
+            return Err(AppError::Configuration(format!("Unknown language: {}", code)));
+        }
+        if enabled {
+            self.state.disabled.remove(code);
+        } else {
+            self.state.disabled.insert(code.to_string());
+        }
+        self.save_state()
+    }
+
+    fn save_state(&self) -> Result<(), AppError> {
+        let dir = app_data_dir()?;
+        std::fs::create_dir_all(&dir).map_err(|e| AppError::Internal(e.to_string()))?;
+        let json = serde_json::to_vec_pretty(&self.state).map_err(|e| AppError::Internal(e.to_string()))?;
+        std::fs::write(dir.join(STATE_FILE), json)
+            .map_err(|e| AppError::Internal(format!("Failed to save language state: {}", e)))
+    }
+}
+
+static LANGUAGE_REGISTRY: std::sync::OnceLock<RwLock<LanguageRegistry>> = std::sync::OnceLock::new();
+
+/// Get the global language registry
+pub fn get_language_registry() -> &'static RwLock<LanguageRegistry> {
+    LANGUAGE_REGISTRY.get_or_init(|| RwLock::new(LanguageRegistry::load()))
+}
+
+/// Progress of a resource download
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResourceDownloadProgress {
+    pub language: String,
+    pub resource_id: String,
+    pub downloaded_bytes: u64,
+    pub total_bytes: Option<u64>,
+    pub done: bool,
+}
+
+/// Download every missing resource for a language; already-installed resources are skipped
+pub async fn download_language_resources<F: Fn(ResourceDownloadProgress)>(
+    code: &str,
+    on_progress: F,
+) -> Result<Vec<String>, AppError> {
+    let resources = {
+        let registry = get_language_registry()
+            .read()
+            .map_err(|_| AppError::Internal("Language registry lock poisoned".to_string()))?;
+        registry
+            .get(code)
+            .ok_or_else(|| AppError::Configuration(format!("Unknown language: {}", code)))?
+            .resources
+            .clone()
+    };
+
+    let client = crate::network::client_builder()
+        .build()
+        .map_err(|e| AppError::Network(format!("Failed to build HTTP client: {}", e)))?;
+    let mut installed = Vec::new();
+    for resource in resources.iter().filter(|r| !r.is_installed()) {
+        let url = resource
+            .download_url()
+            .ok_or_else(|| AppError::Configuration(format!("Resource {} has no download location", resource.id)))?;
+        let target = resource.install_path()?;
+        let partial = target.with_extension("part");
+
+        let mut response = client
+            .get(&url)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| AppError::Network(format!("Failed to download {}: {}", resource.id, e)))?;
+        let total_bytes = response.content_length();
+
+        let mut file = tokio::fs::File::create(&partial)
+            .await
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+        let mut downloaded_bytes = 0u64;
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|e| AppError::Network(format!("Download of {} interrupted: {}", resource.id, e)))?
+        {
+            tokio::io::AsyncWriteExt::write_all(&mut file, &chunk)
+                .await
+                .map_err(|e| AppError::Internal(e.to_string()))?;
+            downloaded_bytes += chunk.len() as u64;
+            on_progress(ResourceDownloadProgress {
+                language: code.to_string(),
+                resource_id: resource.id.clone(),
+                downloaded_bytes,
+                total_bytes,
+                done: false,
+            });
+        }
+        tokio::io::AsyncWriteExt::flush(&mut file)
+            .await
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+        drop(file);
+        tokio::fs::rename(&partial, &target)
+            .await
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+
+        on_progress(ResourceDownloadProgress {
+            language: code.to_string(),
+            resource_id: resource.id.clone(),
+            downloaded_bytes,
+            total_bytes,
+            done: true,
+        });
+        installed.push(resource.id.clone());
+    }
+    Ok(installed)
+}
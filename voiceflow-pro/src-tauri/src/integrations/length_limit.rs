@@ -0,0 +1,186 @@
+// Length Limit Module
+// Output length targets for enhancement, such as tweet length or a word cap, and the shortening passes that hold results to them
+
+use serde::{Deserialize, Serialize};
+
+use super::ai_ml_api::AIMLAPIGateway;
+use super::ai_ml_core::AIMLError;
+use super::ai_text_processor::ToneType;
+use crate::unicode_text::{grapheme_count, word_count};
+
+/// Shortening passes after the first result before the length is reported as missed
+const MAX_SHORTENING_ATTEMPTS: u32 = 2;
+
+const SHORTEN_PROMPT: &str = "Shorten the user's text so it fits the limit below. Keep its meaning, tone, language, \
+names and numbers; drop detail and repetition before anything the reader needs. Reply with the shortened text only.";
+
+const ENHANCE_PROMPT: &str = "Clean up the user's dictated text: fix grammar, punctuation and word choice and drop \
+repetition. Keep its meaning, language, names and numbers. Reply with the cleaned-up text only.";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LengthUnit {
+    Words,
+    Characters,
+}
+
+impl LengthUnit {
+    pub fn label(self) -> &'static str {
+        match self {
+            LengthUnit::Words => "words",
+            LengthUnit::Characters => "characters",
+        }
+    }
+}
+
+/// How long an enhanced result may be
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LengthConstraint {
+    /// 280 characters, one post on X
+    Tweet,
+    /// 160 characters, one text message
+    Sms,
+    MaxWords { words: usize },
+    MaxCharacters { characters: usize },
+    /// About this many words; up to a fifth more still complies
+    TargetWords { words: usize },
+}
+
+impl LengthConstraint {
+    /// Unit and the most the result may have of it
+    pub fn limit(&self) -> (LengthUnit, usize) {
+        match self {
+            LengthConstraint::Tweet => (LengthUnit::Characters, 280),
+            LengthConstraint::Sms => (LengthUnit::Characters, 160),
+            LengthConstraint::MaxWords { words } => (LengthUnit::Words, *words),
+            LengthConstraint::MaxCharacters { characters } => (LengthUnit::Characters, *characters),
+            LengthConstraint::TargetWords { words } => (LengthUnit::Words, words + words / 5),
+        }
+    }
+
+    /// The constraint as an instruction for the model
+    pub fn instruction(&self) -> String {
+        match self {
+            LengthConstraint::TargetWords { words } => format!("Aim for about {} words", words),
+            _ => {
+                let (unit, limit) = self.limit();
+                format!("Keep the result under {} {}", limit, unit.label())
+            }
+        }
+    }
+
+    pub fn measure(&self, text: &str) -> usize {
+        match self.limit().0 {
+            LengthUnit::Words => word_count(text),
+            LengthUnit::Characters => grapheme_count(text.trim()),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let (unit, limit) = self.limit();
+        let most = match unit {
+            LengthUnit::Words => 5_000,
+            LengthUnit::Characters => 30_000,
+        };
+        if limit == 0 || limit > most {
+            return Err(format!("Length limit must be 1-{} {}, got {}", most, unit.label(), limit));
+        }
+        Ok(())
+    }
+}
+
+/// Whether a result kept to its length constraint, recorded in the result's metadata
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LengthCompliance {
+    pub constraint: LengthConstraint,
+    pub unit: LengthUnit,
+    pub limit: usize,
+    pub length: usize,
+    pub compliant: bool,
+    /// Shortening passes it took; 0 when the first result already fit
+    pub attempts: u32,
+}
+
+pub fn check(constraint: &LengthConstraint, text: &str, attempts: u32) -> LengthCompliance {
+    let (unit, limit) = constraint.limit();
+    let length = constraint.measure(text);
+    LengthCompliance {
+        constraint: constraint.clone(),
+        unit,
+        limit,
+        length,
+        compliant: length <= limit,
+        attempts,
+    }
+}
+
+/// System prompt for enhancing text that has to fit `constraint`, so the first result already aims for the limit
+pub fn enhancement_prompt(constraint: &LengthConstraint, tone: &ToneType) -> String {
+    format!(
+        "{}\n\nTone: {}.\nLimit: {}.",
+        ENHANCE_PROMPT,
+        format!("{:?}", tone).to_lowercase(),
+        constraint.instruction()
+    )
+}
+
+/// Ask `model` to shorten `text` until it fits, at most `MAX_SHORTENING_ATTEMPTS` times.
+/// Returns the shortest version produced, which may still be over the limit.
+pub async fn enforce(
+    gateway: &AIMLAPIGateway,
+    model: &str,
+    constraint: &LengthConstraint,
+    text: &str,
+) -> Result<(String, LengthCompliance), AIMLError> {
+    let mut best = text.to_string();
+    let mut compliance = check(constraint, text, 0);
+    let mut attempts = 0;
+    while !compliance.compliant && attempts < MAX_SHORTENING_ATTEMPTS {
+        attempts += 1;
+        let system_prompt = format!(
+            "{}\n\nLimit: {}. The text is {} {} long; it may be at most {}.",
+            SHORTEN_PROMPT,
+            constraint.instruction(),
+            compliance.length,
+            compliance.unit.label(),
+            compliance.limit
+        );
+        let (shortened, _usage) = gateway
+            .complete_with_failover(model.to_string(), system_prompt, best.clone(), Some(0.3))
+            .await?;
+        let shortened = shortened.trim().to_string();
+        let next = check(constraint, &shortened, attempts);
+        // A pass that came back longer is dropped, so retries always start from the shortest text
+        if !shortened.is_empty() && next.length < compliance.length {
+            best = shortened;
+            compliance = next;
+        } else {
+            compliance.attempts = attempts;
+        }
+    }
+    Ok((best, compliance))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn enhancement_prompt_carries_the_limit() {
+        let prompt = enhancement_prompt(&LengthConstraint::Tweet, &ToneType::Casual);
+        assert!(prompt.contains("Limit: Keep the result under 280 characters."));
+        assert!(prompt.contains("Tone: casual."));
+
+        let prompt = enhancement_prompt(&LengthConstraint::TargetWords { words: 50 }, &ToneType::Professional);
+        assert!(prompt.contains("Limit: Aim for about 50 words."));
+    }
+
+    #[test]
+    fn target_words_allow_a_fifth_more() {
+        let constraint = LengthConstraint::TargetWords { words: 10 };
+        assert_eq!(constraint.limit(), (LengthUnit::Words, 12));
+        assert!(check(&constraint, &"word ".repeat(12), 0).compliant);
+        assert!(!check(&constraint, &"word ".repeat(13), 0).compliant);
+    }
+}
@@ -0,0 +1,515 @@
+// Long Audio Module
+// Streams long recordings through the decoder in overlapping windows and transcribes them in parallel, in bounded memory
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::future::Future;
+use std::path::Path;
+use std::time::Instant;
+
+use futures_util::future::Either;
+use futures_util::{StreamExt, TryFutureExt};
+use serde::{Deserialize, Serialize};
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::{FormatOptions, FormatReader};
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+use tokio::sync::mpsc;
+
+use crate::errors::{AppError, ValidationError, VoiceError};
+
+/// Windows are decoded to 16 kHz mono, which is all a recognizer uses
+const TARGET_RATE: u32 = 16_000;
+/// Shortest run of words that counts as the same speech heard at the end of one window and the start of the next
+const MIN_OVERLAP_WORDS: usize = 2;
+/// Speech runs well under this many words per second, so longer matches are never looked for
+const MAX_WORDS_PER_SECOND: usize = 5;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LongAudioSettings {
+    /// Length of each transcribed window; five minutes of 16 kHz PCM stays well under the upload limit
+    pub window_secs: u32,
+    /// Audio shared by neighbouring windows, so a word cut at a boundary is heard whole in one of them
+    pub overlap_secs: u32,
+    /// Windows transcribed at the same time
+    pub parallelism: usize,
+}
+
+impl Default for LongAudioSettings {
+    fn default() -> Self {
+        Self {
+            window_secs: 300,
+            overlap_secs: 3,
+            parallelism: 3,
+        }
+    }
+}
+
+pub fn validate(settings: &LongAudioSettings) -> Result<(), AppError> {
+    let invalid = |message: String| AppError::Validation(ValidationError::InvalidConfigValue(message));
+    if !(30..=1200).contains(&settings.window_secs) {
+        return Err(invalid(format!(
+            "Long audio windows must be 30-1200 seconds, got {}",
+            settings.window_secs
+        )));
+    }
+    if settings.overlap_secs > 30 || settings.overlap_secs * 2 >= settings.window_secs {
+        return Err(invalid(format!(
+            "Long audio overlap must be at most 30 seconds and under half a window, got {}",
+            settings.overlap_secs
+        )));
+    }
+    if !(1..=8).contains(&settings.parallelism) {
+        return Err(invalid(format!(
+            "Long audio parallelism must be 1-8, got {}",
+            settings.parallelism
+        )));
+    }
+    Ok(())
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum LongAudioError {
+    #[error("Cannot decode {path}: {message}")]
+    Decode { path: String, message: String },
+    #[error(transparent)]
+    Transcription(#[from] AppError),
+}
+
+impl From<LongAudioError> for AppError {
+    fn from(error: LongAudioError) -> Self {
+        match error {
+            LongAudioError::Decode { path, message } => {
+                log::warn!("Cannot decode {}: {}", path, message);
+                AppError::VoiceRecognition(VoiceError::UnsupportedAudioFormat)
+            }
+            LongAudioError::Transcription(e) => e,
+        }
+    }
+}
+
+/// Reported each time a window's transcript arrives
+#[derive(Debug, Clone, Serialize)]
+pub struct LongAudioProgress {
+    pub request_id: String,
+    pub window_index: usize,
+    pub windows_done: usize,
+    /// From the duration the container declares; `None` when it declares none
+    pub windows_total: Option<usize>,
+    pub audio_secs_done: f64,
+    pub audio_secs_total: Option<f64>,
+    pub percent: Option<f32>,
+    /// Seconds left at the rate windows have been finishing in this run
+    pub eta_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LongTranscription {
+    pub id: String,
+    pub text: String,
+    pub windows: usize,
+    pub audio_secs: f64,
+    pub processing_time_ms: u64,
+}
+
+struct AudioWindow {
+    index: usize,
+    samples: Vec<i16>,
+}
+
+impl AudioWindow {
+    fn secs(&self) -> f64 {
+        self.samples.len() as f64 / TARGET_RATE as f64
+    }
+}
+
+/// Transcribe `path` window by window, each one a WAV file handed to `transcribe_window` with its name and the
+/// language. Windows in `completed`, transcripts of an earlier interrupted run, are decoded past but not sent
+/// again; `on_window` sees every new transcript as it arrives, possibly out of order
+pub async fn transcribe<T, R, F>(
+    transcribe_window: T,
+    request_id: String,
+    path: &Path,
+    language: Option<String>,
+    settings: &LongAudioSettings,
+    completed: BTreeMap<usize, String>,
+    mut on_window: F,
+) -> Result<LongTranscription, LongAudioError>
+where
+    T: Fn(Vec<u8>, String, Option<String>) -> R,
+    R: Future<Output = Result<String, AppError>>,
+    F: FnMut(LongAudioProgress, &str),
+{
+    let started = Instant::now();
+    let window_samples = settings.window_secs as usize * TARGET_RATE as usize;
+    let overlap_samples = settings.overlap_secs as usize * TARGET_RATE as usize;
+    let audio_secs_total = probe_duration(path);
+    let windows_total = audio_secs_total.map(|total| window_count(total, settings));
+
+    // The decoder runs ahead by at most one window per transcription slot, which bounds memory
+    let parallelism = settings.parallelism.max(1);
+    let (sender, receiver) = mpsc::channel(parallelism);
+    let decode_path = path.to_path_buf();
+    let decoder = tokio::task::spawn_blocking(move || decode_windows(&decode_path, window_samples, overlap_samples, sender));
+
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "audio".to_string());
+    let windows = futures_util::stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|window| (window, receiver))
+    });
+    let mut transcribed = windows
+        .map(|window: AudioWindow| {
+            let text = match completed.get(&window.index) {
+                Some(text) => Either::Left(std::future::ready(Ok((text.clone(), true)))),
+                None => {
+                    let file_name = format!("{}-{:04}.wav", stem, window.index);
+                    Either::Right(
+                        transcribe_window(wav_bytes(&window.samples), file_name, language.clone())
+                            .map_ok(|text| (text, false)),
+                    )
+                }
+            };
+            let (index, secs) = (window.index, window.secs());
+            async move { (index, secs, text.await) }
+        })
+        .buffer_unordered(parallelism);
+
+    let mut texts = BTreeMap::new();
+    let mut audio_secs_done = 0.0;
+    let mut transcribed_secs = 0.0;
+    while let Some((index, secs, text)) = transcribed.next().await {
+        // Dropping the stream on failure closes the channel, which stops the decoder
+        let (text, reused) = text?;
+        audio_secs_done += secs;
+        if !reused {
+            transcribed_secs += secs;
+        }
+        let elapsed = started.elapsed().as_secs_f64();
+        let eta_secs = audio_secs_total
+            .filter(|_| transcribed_secs > 0.0 && elapsed > 0.0)
+            .map(|total| ((total - audio_secs_done).max(0.0) / (transcribed_secs / elapsed)).round() as u64);
+        let progress = LongAudioProgress {
+            request_id: request_id.clone(),
+            window_index: index,
+            windows_done: texts.len() + 1,
+            windows_total,
+            audio_secs_done,
+            audio_secs_total,
+            percent: audio_secs_total.map(|total| (audio_secs_done / total.max(1.0) * 100.0).min(100.0) as f32),
+            eta_secs,
+        };
+        if !reused {
+            on_window(progress, &text);
+        }
+        texts.insert(index, text);
+    }
+    drop(transcribed);
+
+    let audio_secs = decoder
+        .await
+        .map_err(|e| LongAudioError::Decode {
+            path: path.display().to_string(),
+            message: e.to_string(),
+        })?
+        .map_err(|message| LongAudioError::Decode {
+            path: path.display().to_string(),
+            message,
+        })?;
+
+    let max_overlap_words = (settings.overlap_secs as usize * MAX_WORDS_PER_SECOND).max(MIN_OVERLAP_WORDS);
+    let text = texts
+        .values()
+        .fold(String::new(), |merged, next| merge_overlap(&merged, next, max_overlap_words));
+    Ok(LongTranscription {
+        id: request_id,
+        text,
+        windows: texts.len(),
+        audio_secs,
+        processing_time_ms: started.elapsed().as_millis() as u64,
+    })
+}
+
+/// Windows needed for `total_secs` of audio
+pub fn window_count(total_secs: f64, settings: &LongAudioSettings) -> usize {
+    let window = settings.window_secs as f64;
+    let step = (settings.window_secs - settings.overlap_secs) as f64;
+    if total_secs <= window {
+        1
+    } else {
+        1 + ((total_secs - window) / step).ceil() as usize
+    }
+}
+
+fn open_format(path: &Path) -> Result<Box<dyn FormatReader>, String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let stream = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = Hint::new();
+    if let Some(extension) = path.extension().and_then(|ext| ext.to_str()) {
+        hint.with_extension(extension);
+    }
+    symphonia::default::get_probe()
+        .format(&hint, stream, &FormatOptions::default(), &MetadataOptions::default())
+        .map(|probed| probed.format)
+        .map_err(|e| e.to_string())
+}
+
+/// Duration the container declares, read from its headers without decoding
+pub fn probe_duration(path: &Path) -> Option<f64> {
+    let format = open_format(path).ok()?;
+    let track = format.default_track()?;
+    let frames = track.codec_params.n_frames?;
+    let rate = track.codec_params.sample_rate?;
+    Some(frames as f64 / rate as f64)
+}
+
+/// Whether the decoder has a demuxer and codec for `path`; webm and Opus recordings usually have neither
+pub fn decodable(path: &Path) -> bool {
+    let Ok(format) = open_format(path) else {
+        return false;
+    };
+    format
+        .tracks()
+        .iter()
+        .find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+        .map_or(false, |track| {
+            symphonia::default::get_codecs()
+                .make(&track.codec_params, &DecoderOptions::default())
+                .is_ok()
+        })
+}
+
+/// Decode `path` packet by packet and send windows of `window_samples` that overlap by `overlap_samples`.
+/// Runs on a blocking thread; returns the decoded length in seconds
+fn decode_windows(
+    path: &Path,
+    window_samples: usize,
+    overlap_samples: usize,
+    windows: mpsc::Sender<AudioWindow>,
+) -> Result<f64, String> {
+    let mut format = open_format(path)?;
+    let track = format
+        .tracks()
+        .iter()
+        .find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or("no audio track")?;
+    let track_id = track.id;
+    let source_rate = track.codec_params.sample_rate.ok_or("unknown sample rate")?;
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .map_err(|e| e.to_string())?;
+
+    let mut resampler = Resampler::new(source_rate, TARGET_RATE);
+    let mut mono = Vec::new();
+    let mut pending: Vec<i16> = Vec::with_capacity(window_samples);
+    let mut index = 0;
+    let mut total_samples = 0usize;
+    let mut send = |samples: Vec<i16>, index: &mut usize| -> bool {
+        let sent = windows.blocking_send(AudioWindow { index: *index, samples }).is_ok();
+        *index += 1;
+        sent
+    };
+
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(SymphoniaError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(SymphoniaError::ResetRequired) => break,
+            Err(e) => return Err(e.to_string()),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            // A damaged packet costs a few milliseconds of audio, not the whole recording
+            Err(SymphoniaError::DecodeError(e)) => {
+                log::debug!("Skipping an undecodable packet in {}: {}", path.display(), e);
+                continue;
+            }
+            Err(e) => return Err(e.to_string()),
+        };
+        let spec = *decoded.spec();
+        let channels = spec.channels.count().max(1);
+        let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+        buffer.copy_interleaved_ref(decoded);
+
+        mono.clear();
+        mono.extend(
+            buffer
+                .samples()
+                .chunks_exact(channels)
+                .map(|frame| frame.iter().sum::<f32>() / channels as f32),
+        );
+        let before = pending.len();
+        resampler.push(&mono, &mut pending);
+        total_samples += pending.len() - before;
+
+        while pending.len() >= window_samples {
+            if !send(pending[..window_samples].to_vec(), &mut index) {
+                // Transcription stopped; nobody wants the rest
+                return Ok(total_samples as f64 / TARGET_RATE as f64);
+            }
+            pending.drain(..window_samples - overlap_samples);
+        }
+    }
+
+    // The tail is sent unless it is only the overlap already heard at the end of the previous window
+    if pending.len() > overlap_samples || index == 0 {
+        send(pending, &mut index);
+    }
+    Ok(total_samples as f64 / TARGET_RATE as f64)
+}
+
+/// Linear resampling that carries its position across packets
+struct Resampler {
+    /// Input samples per output sample
+    step: f64,
+    /// Position of the next output sample, measured from `previous`
+    position: f64,
+    previous: Option<f32>,
+}
+
+impl Resampler {
+    fn new(from: u32, to: u32) -> Self {
+        Self {
+            step: from as f64 / to as f64,
+            position: 0.0,
+            previous: None,
+        }
+    }
+
+    fn push(&mut self, input: &[f32], output: &mut Vec<i16>) {
+        for &sample in input {
+            if let Some(previous) = self.previous {
+                while self.position < 1.0 {
+                    let value = previous + (sample - previous) * self.position as f32;
+                    output.push((value.clamp(-1.0, 1.0) * i16::MAX as f32) as i16);
+                    self.position += self.step;
+                }
+                self.position -= 1.0;
+            }
+            self.previous = Some(sample);
+        }
+    }
+}
+
+/// 16-bit mono PCM WAV, which the upload path compresses to Opus when that pays off
+fn wav_bytes(samples: &[i16]) -> Vec<u8> {
+    let data_len = (samples.len() * 2) as u32;
+    let mut wav = Vec::with_capacity(44 + data_len as usize);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    wav.extend_from_slice(&1u16.to_le_bytes()); // mono
+    wav.extend_from_slice(&TARGET_RATE.to_le_bytes());
+    wav.extend_from_slice(&(TARGET_RATE * 2).to_le_bytes());
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    for sample in samples {
+        wav.extend_from_slice(&sample.to_le_bytes());
+    }
+    wav
+}
+
+fn normalize_word(word: &str) -> String {
+    word.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect()
+}
+
+/// Join two window transcripts, dropping the words at the start of `next` that repeat the end of `previous`
+fn merge_overlap(previous: &str, next: &str, max_words: usize) -> String {
+    let next = next.trim();
+    if previous.is_empty() {
+        return next.to_string();
+    }
+    if next.is_empty() {
+        return previous.to_string();
+    }
+    let tail: Vec<String> = previous.split_whitespace().rev().take(max_words).map(normalize_word).collect();
+    let head: Vec<&str> = next.split_whitespace().take(max_words).collect();
+    let head_normalized: Vec<String> = head.iter().map(|word| normalize_word(word)).collect();
+
+    // Longest run that ends `previous` and starts `next`
+    let repeated = (MIN_OVERLAP_WORDS..=tail.len().min(head.len()))
+        .rev()
+        .find(|&count| {
+            tail[..count]
+                .iter()
+                .rev()
+                .zip(&head_normalized[..count])
+                .all(|(a, b)| !a.is_empty() && a == b)
+        })
+        .unwrap_or(0);
+    let rest: Vec<&str> = next.split_whitespace().skip(repeated).collect();
+    if rest.is_empty() {
+        return previous.to_string();
+    }
+    format!("{} {}", previous, rest.join(" "))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn window_count_steps_by_window_minus_overlap() {
+        let settings = LongAudioSettings::default();
+        assert_eq!(window_count(0.0, &settings), 1);
+        assert_eq!(window_count(300.0, &settings), 1);
+        assert_eq!(window_count(301.0, &settings), 2);
+        // Each window after the first adds 297 seconds
+        assert_eq!(window_count(597.0, &settings), 2);
+        assert_eq!(window_count(597.5, &settings), 3);
+        assert_eq!(window_count(3600.0, &settings), 13);
+    }
+
+    #[test]
+    fn merge_overlap_drops_words_repeated_across_the_boundary() {
+        assert_eq!(
+            merge_overlap("we should ship the release on", "the release on Friday morning", 15),
+            "we should ship the release on Friday morning"
+        );
+        // Case and punctuation differ between windows
+        assert_eq!(merge_overlap("see you next week.", "Next week, then.", 15), "see you next week. then.");
+    }
+
+    #[test]
+    fn merge_overlap_keeps_a_single_repeated_word_and_handles_empty_sides() {
+        assert_eq!(merge_overlap("it was good", "good news today", 15), "it was good good news today");
+        assert_eq!(merge_overlap("", "  first window ", 15), "first window");
+        assert_eq!(merge_overlap("only window", "   ", 15), "only window");
+        assert_eq!(merge_overlap("the end of it", "end of it", 15), "the end of it");
+    }
+
+    #[test]
+    fn merge_overlap_looks_no_further_than_max_words() {
+        let previous = "one two three four five";
+        let next = "one two three four five six";
+        assert_eq!(merge_overlap(previous, next, 4), "one two three four five one two three four five six");
+        assert_eq!(merge_overlap(previous, next, 5), "one two three four five six");
+    }
+
+    #[test]
+    fn decodable_accepts_wav_and_rejects_unknown_containers() {
+        let dir = std::env::temp_dir().join(format!("voiceflow-long-audio-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let wav = dir.join("tone.wav");
+        std::fs::write(&wav, wav_bytes(&vec![0i16; TARGET_RATE as usize])).unwrap();
+        let webm = dir.join("meeting.webm");
+        std::fs::write(&webm, b"not really a recording").unwrap();
+
+        assert!(decodable(&wav));
+        assert!(!decodable(&webm));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,342 @@
+// Mock Provider Module
+// Deterministic canned responses with artificial latency so the full pipeline runs offline without an API key
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::sync::{Arc, OnceLock};
+use tokio::time::{sleep, Duration};
+
+use super::ai_ml_api::{
+    AIMLChoice, AIMLMessage, AIMLRequest, AIMLResponse, AIMLUsage, ToolCall, ToolChatReply, TranscriptionResponse,
+};
+
+/// Fixtures shipped with the app, one entry per operation type
+const BUNDLED_FIXTURES: &str = include_str!("../../resources/mock_responses.json");
+/// Set to 1 to force mock mode regardless of settings, e.g. in E2E runs
+const MOCK_ENV: &str = "VOICEFLOW_MOCK";
+const FILLER_WORDS: &[&str] = &["um", "uh", "uhm", "erm", "er", "hmm"];
+/// Generated speech is silent 16 kHz mono PCM
+const SPEECH_SAMPLE_RATE: u32 = 16_000;
+const MAX_SPEECH_MS: u64 = 30_000;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MockSettings {
+    pub enabled: bool,
+    /// Added before every mock response to mimic a network round trip
+    pub latency_ms: u64,
+}
+
+impl Default for MockSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            latency_ms: 250,
+        }
+    }
+}
+
+impl MockSettings {
+    /// Enabled in settings or through VOICEFLOW_MOCK
+    pub fn is_active(&self) -> bool {
+        self.enabled || env_enabled()
+    }
+}
+
+pub fn env_enabled() -> bool {
+    std::env::var(MOCK_ENV)
+        .map(|value| matches!(value.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes"))
+        .unwrap_or(false)
+}
+
+#[derive(Debug, Deserialize)]
+struct MockFixtures {
+    models: Vec<String>,
+    chat: Vec<ChatFixture>,
+    default_chat: MockReply,
+    tool_chat: ToolChatFixture,
+    transcription: TranscriptionFixture,
+    speech: SpeechFixture,
+}
+
+/// Used when any `match` phrase appears in the system prompt; the first matching fixture wins
+#[derive(Debug, Deserialize)]
+struct ChatFixture {
+    #[serde(rename = "match")]
+    phrases: Vec<String>,
+    reply: MockReply,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum MockReply {
+    /// `{input}` is the last user message, `{first_sentence}` its first sentence
+    Template { text: String },
+    Json { value: Value },
+    /// The user message tidied up: fillers dropped, capitalized and punctuated
+    Cleanup,
+    /// A JSON object answering a snippet placeholder request
+    Placeholders,
+}
+
+#[derive(Debug, Deserialize)]
+struct ToolChatFixture {
+    reply: String,
+    after_tools: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TranscriptionFixture {
+    text: String,
+    language: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SpeechFixture {
+    ms_per_word: u64,
+}
+
+static FIXTURES: OnceLock<Arc<MockFixtures>> = OnceLock::new();
+
+fn fixtures() -> Arc<MockFixtures> {
+    FIXTURES
+        .get_or_init(|| {
+            Arc::new(serde_json::from_str(BUNDLED_FIXTURES).expect("bundled mock fixtures are valid JSON"))
+        })
+        .clone()
+}
+
+/// Stands in for the AI ML API; the same input always produces the same output
+#[derive(Debug, Clone)]
+pub struct MockProvider {
+    latency: Duration,
+    fixtures: Arc<MockFixtures>,
+}
+
+impl MockProvider {
+    pub fn new(latency: Duration) -> Self {
+        Self {
+            latency,
+            fixtures: fixtures(),
+        }
+    }
+
+    /// A provider when mock mode is active, otherwise `None`
+    pub fn from_settings(settings: &MockSettings) -> Option<Self> {
+        settings
+            .is_active()
+            .then(|| Self::new(Duration::from_millis(settings.latency_ms)))
+    }
+
+    pub async fn list_model_ids(&self) -> Vec<String> {
+        self.delay().await;
+        self.fixtures.models.clone()
+    }
+
+    pub async fn chat(&self, request: &AIMLRequest) -> AIMLResponse {
+        self.delay().await;
+        let system = request
+            .messages
+            .iter()
+            .filter(|message| message.role == "system")
+            .map(|message| message.content.to_lowercase())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let input = request
+            .messages
+            .iter()
+            .rev()
+            .find(|message| message.role == "user")
+            .map(|message| message.content.as_str())
+            .unwrap_or_default();
+
+        let reply = self
+            .fixtures
+            .chat
+            .iter()
+            .find(|fixture| fixture.phrases.iter().any(|phrase| system.contains(&phrase.to_lowercase())))
+            .map(|fixture| &fixture.reply)
+            .unwrap_or(&self.fixtures.default_chat);
+        let content = render(reply, input);
+        let prompt_chars: usize = request.messages.iter().map(|message| message.content.chars().count()).sum();
+
+        AIMLResponse {
+            id: format!("mock-{}", request.model),
+            object: "chat.completion".to_string(),
+            created: 0,
+            model: request.model.clone(),
+            usage: Some(usage(prompt_chars, content.chars().count())),
+            choices: vec![AIMLChoice {
+                index: 0,
+                message: AIMLMessage {
+                    role: "assistant".to_string(),
+                    content,
+                },
+                finish_reason: Some("stop".to_string()),
+            }],
+        }
+    }
+
+    /// Calls a tool when the latest user message names it, and answers in text once tool results are back
+    pub async fn tool_chat(&self, messages: &[Value], tools: &[Value]) -> ToolChatReply {
+        self.delay().await;
+        let prompt_chars: usize = messages
+            .iter()
+            .filter_map(|message| message["content"].as_str())
+            .map(|content| content.chars().count())
+            .sum();
+        let last = messages.last();
+        let requested = match last.map(|message| message["role"] == "user") {
+            Some(true) => {
+                let said = last.and_then(|message| message["content"].as_str()).unwrap_or_default().to_lowercase();
+                tools
+                    .iter()
+                    .filter_map(|tool| tool["function"]["name"].as_str())
+                    .find(|name| said.contains(name) || said.contains(&name.replace('_', " ")))
+            }
+            _ => None,
+        };
+
+        if let Some(name) = requested {
+            let call = ToolCall {
+                id: "mock_call_1".to_string(),
+                name: name.to_string(),
+                arguments: json!({}),
+            };
+            return ToolChatReply {
+                content: None,
+                message: json!({
+                    "role": "assistant",
+                    "content": null,
+                    "tool_calls": [{
+                        "id": call.id,
+                        "type": "function",
+                        "function": { "name": call.name, "arguments": "{}" },
+                    }],
+                }),
+                tool_calls: vec![call],
+                usage: Some(usage(prompt_chars, 0)),
+            };
+        }
+
+        let content = match last.map(|message| message["role"] == "tool") {
+            Some(true) => self.fixtures.tool_chat.after_tools.clone(),
+            _ => self.fixtures.tool_chat.reply.clone(),
+        };
+        ToolChatReply {
+            message: json!({ "role": "assistant", "content": content }),
+            usage: Some(usage(prompt_chars, content.chars().count())),
+            content: Some(content),
+            tool_calls: Vec::new(),
+        }
+    }
+
+    pub async fn transcribe(&self, audio: &[u8], language: Option<String>) -> TranscriptionResponse {
+        self.delay().await;
+        let fixture = &self.fixtures.transcription;
+        TranscriptionResponse {
+            text: fixture.text.clone(),
+            language: Some(
+                language
+                    .map(|language| language.split(['-', '_']).next().unwrap_or(&language).to_string())
+                    .unwrap_or_else(|| fixture.language.clone()),
+            ),
+            // Sized as if the upload were 16-bit mono PCM at 16 kHz
+            duration: Some(audio.len() as f32 / (SPEECH_SAMPLE_RATE as f32 * 2.0)),
+        }
+    }
+
+    /// Silent WAV audio lasting roughly as long as `text` would take to say, whatever format was requested
+    pub async fn speech(&self, text: &str) -> Vec<u8> {
+        self.delay().await;
+        let words = text.split_whitespace().count() as u64;
+        let millis = (words * self.fixtures.speech.ms_per_word).clamp(300, MAX_SPEECH_MS);
+        silent_wav(millis)
+    }
+
+    async fn delay(&self) {
+        if !self.latency.is_zero() {
+            sleep(self.latency).await;
+        }
+    }
+}
+
+fn render(reply: &MockReply, input: &str) -> String {
+    match reply {
+        MockReply::Template { text } => text
+            .replace("{first_sentence}", first_sentence(input))
+            .replace("{input}", input),
+        MockReply::Json { value } => value.to_string(),
+        MockReply::Cleanup => cleanup(input),
+        MockReply::Placeholders => {
+            let request: Value = serde_json::from_str(input).unwrap_or(Value::Null);
+            let values: Map<String, Value> = request["placeholders"]
+                .as_array()
+                .map(|names| {
+                    names
+                        .iter()
+                        .filter_map(Value::as_str)
+                        .map(|name| (name.to_string(), Value::String(format!("mock {}", name))))
+                        .collect()
+                })
+                .unwrap_or_default();
+            Value::Object(values).to_string()
+        }
+    }
+}
+
+fn first_sentence(text: &str) -> &str {
+    let text = text.trim();
+    match text.find(['.', '!', '?']) {
+        Some(end) => &text[..=end],
+        None => text,
+    }
+}
+
+fn cleanup(text: &str) -> String {
+    let words: Vec<&str> = text
+        .split_whitespace()
+        .filter(|word| {
+            let bare = word.trim_end_matches([',', '.']).to_lowercase();
+            !FILLER_WORDS.contains(&bare.as_str())
+        })
+        .collect();
+    let mut cleaned = words.join(" ");
+    if let Some(first) = cleaned.chars().next() {
+        cleaned = first.to_uppercase().chain(cleaned.chars().skip(1)).collect();
+    }
+    if !cleaned.is_empty() && !cleaned.ends_with(['.', '!', '?']) {
+        cleaned.push('.');
+    }
+    cleaned
+}
+
+/// Roughly four characters per token, as the real tokenizers average for English
+fn usage(prompt_chars: usize, completion_chars: usize) -> AIMLUsage {
+    let tokens = |chars: usize| (chars as u32 + 3) / 4;
+    AIMLUsage {
+        prompt_tokens: tokens(prompt_chars),
+        completion_tokens: tokens(completion_chars),
+        total_tokens: tokens(prompt_chars) + tokens(completion_chars),
+    }
+}
+
+fn silent_wav(millis: u64) -> Vec<u8> {
+    let samples = (SPEECH_SAMPLE_RATE as u64 * millis / 1000) as u32;
+    let data_len = samples * 2;
+    let mut wav = Vec::with_capacity(44 + data_len as usize);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    wav.extend_from_slice(&1u16.to_le_bytes()); // mono
+    wav.extend_from_slice(&SPEECH_SAMPLE_RATE.to_le_bytes());
+    wav.extend_from_slice(&(SPEECH_SAMPLE_RATE * 2).to_le_bytes());
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    wav.resize(44 + data_len as usize, 0);
+    wav
+}
@@ -0,0 +1,233 @@
+// Model Comparison Module
+// Runs the same text through several model/provider/prompt setups and reports them side by side
+
+use futures_util::future::join_all;
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use super::ai_ml_api::{AIMLAPIGateway, AIMLUsage};
+use super::ai_text_processor::{AITextProcessor, ProcessingContext, ProcessingOptions, ProcessingRequest, ToneType};
+use crate::errors::{AppError, ValidationError};
+
+pub const MIN_COMPARISONS: usize = 2;
+pub const MAX_COMPARISONS: usize = 4;
+
+const DEFAULT_PROMPT: &str = "You clean up dictated text. Fix grammar, punctuation and obvious \
+    recognition errors, remove filler words and keep the speaker's meaning and voice. \
+    Return only the rewritten text.";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ComparisonProvider {
+    /// aimlapi.com chat models
+    #[default]
+    Aiml,
+    /// The on-device text processor
+    Local,
+}
+
+/// One setup to evaluate
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ComparisonConfig {
+    pub label: Option<String>,
+    pub provider: ComparisonProvider,
+    /// Defaults to the configured text model
+    pub model: Option<String>,
+    /// System prompt; defaults to the standard clean-up instructions
+    pub prompt: Option<String>,
+    pub temperature: Option<f32>,
+    /// Used by the local provider
+    pub context: Option<ProcessingContext>,
+    pub tone: Option<ToneType>,
+}
+
+/// Word-level differences between two texts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiffStats {
+    pub words_added: usize,
+    pub words_removed: usize,
+    pub words_unchanged: usize,
+    /// Changed words relative to the longer text, 0.0 (identical) to 1.0 (rewritten)
+    pub change_ratio: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComparisonEntry {
+    pub label: String,
+    pub provider: ComparisonProvider,
+    pub model: String,
+    pub output: Option<String>,
+    pub error: Option<String>,
+    pub latency_ms: u64,
+    pub usage: Option<AIMLUsage>,
+    /// Against the input text
+    pub diff_from_input: Option<DiffStats>,
+    /// Against the first successful output, so setups can be ranked by how much they disagree
+    pub diff_from_first: Option<DiffStats>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComparisonReport {
+    pub input: String,
+    pub entries: Vec<ComparisonEntry>,
+    pub fastest: Option<String>,
+    pub total_ms: u64,
+}
+
+/// Run every config concurrently; individual failures are reported per entry
+pub async fn compare_processing(
+    text: &str,
+    configs: &[ComparisonConfig],
+    gateway: Option<&AIMLAPIGateway>,
+    local: &Mutex<Option<AITextProcessor>>,
+) -> Result<ComparisonReport, AppError> {
+    if !(MIN_COMPARISONS..=MAX_COMPARISONS).contains(&configs.len()) {
+        return Err(AppError::Validation(ValidationError::InvalidConfigValue(format!(
+            "Compare between {} and {} setups, got {}",
+            MIN_COMPARISONS,
+            MAX_COMPARISONS,
+            configs.len()
+        ))));
+    }
+
+    let started = Instant::now();
+    let runs = configs
+        .iter()
+        .enumerate()
+        .map(|(index, config)| run_one(index, text, config, gateway, local));
+    let mut entries = join_all(runs).await;
+
+    let first_output = entries.iter().find_map(|e| e.output.clone());
+    for entry in &mut entries {
+        if let Some(output) = &entry.output {
+            entry.diff_from_input = Some(word_diff(text, output));
+            entry.diff_from_first = first_output.as_deref().map(|first| word_diff(first, output));
+        }
+    }
+    let fastest = entries
+        .iter()
+        .filter(|e| e.output.is_some())
+        .min_by_key(|e| e.latency_ms)
+        .map(|e| e.label.clone());
+
+    Ok(ComparisonReport {
+        input: text.to_string(),
+        entries,
+        fastest,
+        total_ms: started.elapsed().as_millis() as u64,
+    })
+}
+
+async fn run_one(
+    index: usize,
+    text: &str,
+    config: &ComparisonConfig,
+    gateway: Option<&AIMLAPIGateway>,
+    local: &Mutex<Option<AITextProcessor>>,
+) -> ComparisonEntry {
+    let model = match config.provider {
+        ComparisonProvider::Aiml => config
+            .model
+            .clone()
+            .or_else(|| gateway.map(|g| g.get_config().text_model.clone()))
+            .unwrap_or_default(),
+        ComparisonProvider::Local => "local-text-processor".to_string(),
+    };
+    let label = config
+        .label
+        .clone()
+        .unwrap_or_else(|| format!("{}. {}", index + 1, model));
+
+    let started = Instant::now();
+    let outcome: Result<(String, Option<AIMLUsage>), String> = match config.provider {
+        ComparisonProvider::Aiml => match gateway {
+            Some(gateway) => gateway
+                .complete_with_model(
+                    model.clone(),
+                    config.prompt.clone().unwrap_or_else(|| DEFAULT_PROMPT.to_string()),
+                    text.to_string(),
+                    config.temperature,
+                )
+                .await
+                .map_err(|e| e.to_string()),
+            None => Err("AI ML API not initialized".to_string()),
+        },
+        ComparisonProvider::Local => match local.lock().await.as_ref() {
+            Some(processor) => processor
+                .process_text(ProcessingRequest {
+                    id: Uuid::new_v4().to_string(),
+                    text: text.to_string(),
+                    context: config.context.clone().unwrap_or(ProcessingContext::Email),
+                    tone: config.tone.clone().unwrap_or(ToneType::Professional),
+                    options: ProcessingOptions {
+                        aggressiveness: 0.7,
+                        remove_fillers: true,
+                        preserve_formatting: false,
+                        smart_punctuation: true,
+                        auto_correct: true,
+                        length: None,
+                    },
+                    timestamp: std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_secs(),
+                })
+                .await
+                .map(|result| (result.processed_text, None)),
+            None => Err("Text processor not initialized".to_string()),
+        },
+    };
+    let latency_ms = started.elapsed().as_millis() as u64;
+
+    let (output, usage, error) = match outcome {
+        Ok((output, usage)) => (Some(output.trim().to_string()), usage, None),
+        Err(e) => (None, None, Some(e)),
+    };
+    ComparisonEntry {
+        label,
+        provider: config.provider,
+        model,
+        output,
+        error,
+        latency_ms,
+        usage,
+        diff_from_input: None,
+        diff_from_first: None,
+    }
+}
+
+/// Word diff from the longest common subsequence, ignoring case
+pub(crate) fn word_diff(before: &str, after: &str) -> DiffStats {
+    let a: Vec<String> = before.split_whitespace().map(str::to_lowercase).collect();
+    let b: Vec<String> = after.split_whitespace().map(str::to_lowercase).collect();
+
+    // Two-row LCS table; inputs are capped by validation so n*m stays small
+    let mut previous = vec![0usize; b.len() + 1];
+    let mut current = vec![0usize; b.len() + 1];
+    for word_a in &a {
+        for (j, word_b) in b.iter().enumerate() {
+            current[j + 1] = if word_a == word_b {
+                previous[j] + 1
+            } else {
+                current[j].max(previous[j + 1])
+            };
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    let common = previous[b.len()];
+
+    let longest = a.len().max(b.len());
+    DiffStats {
+        words_added: b.len() - common,
+        words_removed: a.len() - common,
+        words_unchanged: common,
+        change_ratio: if longest == 0 {
+            0.0
+        } else {
+            1.0 - common as f32 / longest as f32
+        },
+    }
+}
@@ -0,0 +1,492 @@
+// Model Manager Module
+// Downloads, verifies and removes local Whisper/GGUF speech models within a disk quota
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tokio::io::AsyncWriteExt;
+
+use crate::errors::{AppError, ValidationError};
+use crate::storage::{ensure_data_dir, DataDir};
+use crate::validation::validate_model_id;
+
+const MANIFEST_FILE: &str = "models.json";
+const WHISPER_CPP_BASE_URL: &str = "https://huggingface.co/ggerganov/whisper.cpp/resolve/main";
+/// File listing with the LFS SHA-256 of every model, fetched separately from the download it checks
+const WHISPER_CPP_TREE_URL: &str = "https://huggingface.co/api/models/ggerganov/whisper.cpp/tree/main";
+/// Progress is reported at most once per this many bytes
+const PROGRESS_STEP_BYTES: u64 = 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModelSettings {
+    /// Space local models may take up, language-pack models included
+    pub disk_quota_mb: u64,
+}
+
+impl Default for ModelSettings {
+    fn default() -> Self {
+        Self { disk_quota_mb: 4096 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModelFormat {
+    /// whisper.cpp ggml weights
+    Ggml,
+    Gguf,
+}
+
+impl ModelFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ModelFormat::Ggml => "bin",
+            ModelFormat::Gguf => "gguf",
+        }
+    }
+}
+
+/// A model the app knows how to fetch
+struct CatalogModel {
+    id: &'static str,
+    name: &'static str,
+    approx_size_mb: u64,
+    multilingual: bool,
+    accuracy: &'static str,
+    speed: &'static str,
+}
+
+const CATALOG: &[CatalogModel] = &[
+    CatalogModel { id: "tiny.en", name: "Whisper Tiny (English)", approx_size_mb: 75, multilingual: false, accuracy: "basic", speed: "fastest" },
+    CatalogModel { id: "tiny", name: "Whisper Tiny", approx_size_mb: 75, multilingual: true, accuracy: "basic", speed: "fastest" },
+    CatalogModel { id: "base.en", name: "Whisper Base (English)", approx_size_mb: 142, multilingual: false, accuracy: "fair", speed: "fast" },
+    CatalogModel { id: "base", name: "Whisper Base", approx_size_mb: 142, multilingual: true, accuracy: "fair", speed: "fast" },
+    CatalogModel { id: "small", name: "Whisper Small", approx_size_mb: 466, multilingual: true, accuracy: "good", speed: "moderate" },
+    CatalogModel { id: "medium", name: "Whisper Medium", approx_size_mb: 1500, multilingual: true, accuracy: "very good", speed: "slow" },
+    CatalogModel { id: "large-v3-turbo", name: "Whisper Large v3 Turbo", approx_size_mb: 1600, multilingual: true, accuracy: "excellent", speed: "moderate" },
+    CatalogModel { id: "large-v3", name: "Whisper Large v3", approx_size_mb: 2900, multilingual: true, accuracy: "best", speed: "slowest" },
+];
+
+impl CatalogModel {
+    fn file(&self) -> String {
+        format!("ggml-{}.bin", self.id)
+    }
+
+    fn url(&self) -> String {
+        format!("{}/{}", WHISPER_CPP_BASE_URL, self.file())
+    }
+}
+
+/// A model on disk, as recorded in the manifest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstalledModel {
+    pub id: String,
+    pub file: String,
+    pub format: ModelFormat,
+    pub size_bytes: u64,
+    pub sha256: String,
+    pub source_url: String,
+    pub installed_at: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ModelManifest {
+    models: Vec<InstalledModel>,
+}
+
+/// Catalog entry or custom download, with its install state
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelInfo {
+    pub id: String,
+    pub name: String,
+    pub format: ModelFormat,
+    pub multilingual: bool,
+    /// Relative accuracy, from "basic" to "best"; None for custom models
+    pub accuracy: Option<String>,
+    pub speed: Option<String>,
+    /// Size on disk when installed, otherwise the approximate download size
+    pub size_bytes: u64,
+    pub installed: bool,
+    pub path: Option<String>,
+    pub sha256: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelInventory {
+    pub models: Vec<ModelInfo>,
+    pub used_bytes: u64,
+    pub quota_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelDownloadProgress {
+    pub model_id: String,
+    pub downloaded_bytes: u64,
+    pub total_bytes: Option<u64>,
+    /// True when the download continued from an earlier partial file
+    pub resumed: bool,
+    pub verifying: bool,
+    pub done: bool,
+}
+
+/// Models currently being downloaded, so the same model is not fetched twice at once
+static ACTIVE_DOWNLOADS: Mutex<Option<HashSet<String>>> = Mutex::new(None);
+
+struct DownloadGuard(String);
+
+impl DownloadGuard {
+    fn acquire(model_id: &str) -> Result<Self, AppError> {
+        let mut active = ACTIVE_DOWNLOADS
+            .lock()
+            .map_err(|_| AppError::Internal("Model download lock poisoned".to_string()))?;
+        if !active.get_or_insert_with(HashSet::new).insert(model_id.to_string()) {
+            return Err(AppError::Configuration(format!("{} is already downloading", model_id)));
+        }
+        Ok(Self(model_id.to_string()))
+    }
+}
+
+impl Drop for DownloadGuard {
+    fn drop(&mut self) {
+        if let Ok(mut active) = ACTIVE_DOWNLOADS.lock() {
+            if let Some(active) = active.as_mut() {
+                active.remove(&self.0);
+            }
+        }
+    }
+}
+
+/// Catalog models plus any custom models installed from a URL
+pub fn list_models(settings: &ModelSettings) -> Result<ModelInventory, AppError> {
+    let dir = ensure_data_dir(DataDir::Models)?;
+    let manifest = load_manifest(&dir);
+
+    let mut models: Vec<ModelInfo> = CATALOG
+        .iter()
+        .map(|entry| {
+            let installed = manifest.models.iter().find(|m| m.id == entry.id);
+            ModelInfo {
+                id: entry.id.to_string(),
+                name: entry.name.to_string(),
+                format: ModelFormat::Ggml,
+                multilingual: entry.multilingual,
+                accuracy: Some(entry.accuracy.to_string()),
+                speed: Some(entry.speed.to_string()),
+                size_bytes: installed.map_or(entry.approx_size_mb * 1024 * 1024, |m| m.size_bytes),
+                installed: installed.is_some(),
+                path: installed.map(|m| dir.join(&m.file).to_string_lossy().to_string()),
+                sha256: installed.map(|m| m.sha256.clone()),
+            }
+        })
+        .collect();
+    models.extend(
+        manifest
+            .models
+            .iter()
+            .filter(|m| !CATALOG.iter().any(|entry| entry.id == m.id))
+            .map(|m| ModelInfo {
+                id: m.id.clone(),
+                name: m.id.clone(),
+                format: m.format,
+                multilingual: true,
+                accuracy: None,
+                speed: None,
+                size_bytes: m.size_bytes,
+                installed: true,
+                path: Some(dir.join(&m.file).to_string_lossy().to_string()),
+                sha256: Some(m.sha256.clone()),
+            }),
+    );
+
+    Ok(ModelInventory {
+        models,
+        used_bytes: used_bytes(&dir, None),
+        quota_bytes: settings.disk_quota_mb * 1024 * 1024,
+    })
+}
+
+/// Download a catalog model, or a custom model from `url`, resuming any partial download.
+/// The downloaded bytes are hashed and checked against `sha256`, or for catalog models against the
+/// SHA-256 in the host's LFS metadata; custom models need `sha256`.
+pub async fn download_model<F: Fn(ModelDownloadProgress)>(
+    model_id: &str,
+    url: Option<String>,
+    sha256: Option<String>,
+    settings: &ModelSettings,
+    on_progress: F,
+) -> Result<InstalledModel, AppError> {
+    let model_id = validate_model_id(model_id)?;
+    let catalog = CATALOG.iter().find(|entry| entry.id == model_id);
+    let url = match (url, catalog) {
+        (Some(url), _) => url,
+        (None, Some(entry)) => entry.url(),
+        (None, None) => {
+            return Err(AppError::Configuration(format!(
+                "{} is not a known model; provide a download URL",
+                model_id
+            )))
+        }
+    };
+    if !url.starts_with("https://") {
+        return Err(AppError::Security("Models can only be downloaded over HTTPS".to_string()));
+    }
+    let format = if url.to_lowercase().ends_with(".gguf") {
+        ModelFormat::Gguf
+    } else {
+        ModelFormat::Ggml
+    };
+
+    let _guard = DownloadGuard::acquire(&model_id)?;
+    let dir = ensure_data_dir(DataDir::Models)?;
+    let file_name = format!("whisper-{}.{}", model_id, format.extension());
+    let target = dir.join(&file_name);
+    let partial = dir.join(format!("{}.part", file_name));
+
+    let client = crate::network::client_builder()
+        .build()
+        .map_err(|e| AppError::Network(format!("Failed to build HTTP client: {}", e)))?;
+    let published = match (&sha256, catalog) {
+        (None, Some(entry)) => catalog_sha256(&client, &entry.file()).await,
+        _ => None,
+    };
+    let expected_sha256 = match sha256.or(published) {
+        Some(hash) => hash.to_lowercase(),
+        None => {
+            return Err(AppError::Validation(ValidationError::InvalidConfigValue(format!(
+                "No checksum is available for {}; provide its SHA-256",
+                model_id
+            ))))
+        }
+    };
+
+    let mut offset = tokio::fs::metadata(&partial).await.map(|m| m.len()).unwrap_or(0);
+    let mut request = client.get(&url);
+    if offset > 0 {
+        request = request.header(reqwest::header::RANGE, format!("bytes={}-", offset));
+    }
+    let response = request
+        .send()
+        .await
+        .map_err(|e| AppError::Network(format!("Failed to download {}: {}", model_id, e)))?;
+
+    let status = response.status();
+    let mut response = if status == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+        // The partial file already holds everything; go straight to verification
+        None
+    } else {
+        let response = response
+            .error_for_status()
+            .map_err(|e| AppError::Network(format!("Failed to download {}: {}", model_id, e)))?;
+        if response.status() != reqwest::StatusCode::PARTIAL_CONTENT {
+            offset = 0;
+        }
+        Some(response)
+    };
+    let resumed = offset > 0;
+
+    let total_bytes = response
+        .as_ref()
+        .and_then(|r| r.content_length())
+        .map(|remaining| offset + remaining)
+        .or_else(|| catalog.map(|entry| entry.approx_size_mb * 1024 * 1024));
+    if let Some(total) = total_bytes {
+        let quota = settings.disk_quota_mb * 1024 * 1024;
+        let existing = manifest_entry_size(&dir, &model_id);
+        let projected = used_bytes(&dir, Some(&partial)).saturating_sub(existing) + total;
+        if projected > quota {
+            return Err(AppError::Configuration(format!(
+                "Downloading {} would use {} MB of the {} MB model quota; remove a model or raise the quota",
+                model_id,
+                projected / (1024 * 1024),
+                settings.disk_quota_mb
+            )));
+        }
+    }
+
+    let mut downloaded_bytes = offset;
+    if let Some(response) = response.as_mut() {
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(resumed)
+            .truncate(!resumed)
+            .open(&partial)
+            .await
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+        let mut reported = downloaded_bytes;
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|e| AppError::Network(format!("Download of {} interrupted; it will resume next time: {}", model_id, e)))?
+        {
+            file.write_all(&chunk).await.map_err(|e| AppError::Internal(e.to_string()))?;
+            downloaded_bytes += chunk.len() as u64;
+            if downloaded_bytes - reported >= PROGRESS_STEP_BYTES {
+                reported = downloaded_bytes;
+                on_progress(ModelDownloadProgress {
+                    model_id: model_id.clone(),
+                    downloaded_bytes,
+                    total_bytes,
+                    resumed,
+                    verifying: false,
+                    done: false,
+                });
+            }
+        }
+        file.flush().await.map_err(|e| AppError::Internal(e.to_string()))?;
+    }
+
+    on_progress(ModelDownloadProgress {
+        model_id: model_id.clone(),
+        downloaded_bytes,
+        total_bytes,
+        resumed,
+        verifying: true,
+        done: false,
+    });
+    let actual_sha256 = hash_file(partial.clone()).await?;
+    if actual_sha256 != expected_sha256 {
+        let _ = tokio::fs::remove_file(&partial).await;
+        return Err(AppError::Security(format!(
+            "Checksum mismatch for {}: expected {}, got {}",
+            model_id, expected_sha256, actual_sha256
+        )));
+    }
+    tokio::fs::rename(&partial, &target)
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+
+    let size_bytes = tokio::fs::metadata(&target).await.map(|m| m.len()).unwrap_or(downloaded_bytes);
+    let installed = InstalledModel {
+        id: model_id.clone(),
+        file: file_name,
+        format,
+        size_bytes,
+        sha256: actual_sha256,
+        source_url: url,
+        installed_at: now_secs(),
+    };
+    let mut manifest = load_manifest(&dir);
+    manifest.models.retain(|m| m.id != installed.id);
+    manifest.models.push(installed.clone());
+    save_manifest(&dir, &manifest)?;
+
+    on_progress(ModelDownloadProgress {
+        model_id,
+        downloaded_bytes: size_bytes,
+        total_bytes: Some(size_bytes),
+        resumed,
+        verifying: false,
+        done: true,
+    });
+    Ok(installed)
+}
+
+/// Delete an installed model and any leftover partial download
+pub fn remove_model(model_id: &str) -> Result<(), AppError> {
+    let model_id = validate_model_id(model_id)?;
+    let dir = ensure_data_dir(DataDir::Models)?;
+    let mut manifest = load_manifest(&dir);
+    let position = manifest.models.iter().position(|m| m.id == model_id);
+
+    let mut removed = false;
+    if let Some(position) = position {
+        let model = manifest.models.remove(position);
+        let path = dir.join(&model.file);
+        if path.exists() {
+            std::fs::remove_file(&path).map_err(|e| AppError::Internal(format!("Failed to remove {}: {}", path.display(), e)))?;
+        }
+        save_manifest(&dir, &manifest)?;
+        removed = true;
+    }
+    for format in [ModelFormat::Ggml, ModelFormat::Gguf] {
+        let partial = dir.join(format!("whisper-{}.{}.part", model_id, format.extension()));
+        if partial.exists() && std::fs::remove_file(&partial).is_ok() {
+            removed = true;
+        }
+    }
+
+    if removed {
+        Ok(())
+    } else {
+        Err(AppError::Configuration(format!("Model {} is not installed", model_id)))
+    }
+}
+
+/// SHA-256 of a catalog file from the repository's LFS metadata; the download's own headers are not
+/// trusted for this, as an ETag is only an opaque cache validator
+async fn catalog_sha256(client: &reqwest::Client, file: &str) -> Option<String> {
+    let listing: Vec<serde_json::Value> = client
+        .get(WHISPER_CPP_TREE_URL)
+        .send()
+        .await
+        .ok()?
+        .error_for_status()
+        .ok()?
+        .json()
+        .await
+        .ok()?;
+    let oid = listing.iter().find(|entry| entry["path"] == file)?["lfs"]["oid"].as_str()?;
+    (oid.len() == 64 && oid.chars().all(|c| c.is_ascii_hexdigit())).then(|| oid.to_lowercase())
+}
+
+async fn hash_file(path: PathBuf) -> Result<String, AppError> {
+    tokio::task::spawn_blocking(move || {
+        let mut file = std::fs::File::open(&path).map_err(|e| AppError::Internal(e.to_string()))?;
+        let mut hasher = Sha256::new();
+        std::io::copy(&mut file, &mut hasher).map_err(|e| AppError::Internal(e.to_string()))?;
+        Ok(hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect())
+    })
+    .await
+    .map_err(|e| AppError::Internal(e.to_string()))?
+}
+
+/// Bytes used by everything in the models folder, optionally leaving one file out
+fn used_bytes(dir: &Path, exclude: Option<&Path>) -> u64 {
+    std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| exclude.map_or(true, |excluded| entry.path() != excluded))
+                .filter_map(|entry| entry.metadata().ok())
+                .filter(|metadata| metadata.is_file())
+                .map(|metadata| metadata.len())
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+/// Size of an installed copy that a re-download would replace
+fn manifest_entry_size(dir: &Path, model_id: &str) -> u64 {
+    load_manifest(dir)
+        .models
+        .iter()
+        .find(|m| m.id == model_id)
+        .map_or(0, |m| m.size_bytes)
+}
+
+fn load_manifest(dir: &Path) -> ModelManifest {
+    std::fs::read(dir.join(MANIFEST_FILE))
+        .ok()
+        .and_then(|data| serde_json::from_slice(&data).ok())
+        .unwrap_or_default()
+}
+
+fn save_manifest(dir: &Path, manifest: &ModelManifest) -> Result<(), AppError> {
+    let json = serde_json::to_vec_pretty(manifest).map_err(|e| AppError::Internal(e.to_string()))?;
+    std::fs::write(dir.join(MANIFEST_FILE), json).map_err(|e| AppError::Internal(e.to_string()))
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
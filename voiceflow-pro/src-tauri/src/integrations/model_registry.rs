@@ -0,0 +1,264 @@
+// Model Registry Module
+// Context window, modality, price and speed class of every model the app can call, shipped as JSON and extendable by the user
+
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use super::token_budget::TokenEncoding;
+use crate::errors::{AppError, ValidationError};
+use crate::storage::app_data_dir;
+
+/// Registry shipped with the app; entries in a `models.json` in the data directory replace or extend it by id
+const BUNDLED_REGISTRY: &str = include_str!("../../resources/models.json");
+const REGISTRY_FILE: &str = "models.json";
+/// How often the user's registry file is checked for edits
+const RELOAD_CHECK: Duration = Duration::from_secs(2);
+
+/// What a model takes in and gives back
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModelModality {
+    /// Text in, text out: enhancement, translation, context and commands
+    Text,
+    /// Text in, audio out
+    Speech,
+    /// Audio in, text out
+    Transcription,
+}
+
+impl ModelModality {
+    pub fn label(self) -> &'static str {
+        match self {
+            ModelModality::Text => "text",
+            ModelModality::Speech => "speech",
+            ModelModality::Transcription => "transcription",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpeedClass {
+    Fast,
+    Balanced,
+    Slow,
+}
+
+/// List prices in USD; only the ones that apply to the model's modality are set
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModelPricing {
+    pub input_usd_per_mtok: Option<f64>,
+    pub output_usd_per_mtok: Option<f64>,
+    pub usd_per_audio_minute: Option<f64>,
+    pub usd_per_mchars: Option<f64>,
+}
+
+/// A model family as described by the registry data file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelEntry {
+    /// Matched as a prefix of the model name, so "gpt-4o" also covers dated snapshots like "gpt-4o-2024-08-06".
+    /// An id with a vendor ("meta-llama/meta-llama-3.1") is matched against the full name
+    pub id: String,
+    pub name: String,
+    pub provider: String,
+    pub modality: ModelModality,
+    #[serde(default)]
+    pub context_tokens: Option<usize>,
+    #[serde(default)]
+    pub encoding: Option<TokenEncoding>,
+    #[serde(default)]
+    pub pricing: ModelPricing,
+    pub speed: SpeedClass,
+}
+
+#[derive(Debug, Deserialize)]
+struct RegistryFile {
+    #[allow(dead_code)]
+    version: u32,
+    models: Vec<ModelEntry>,
+}
+
+/// Registry of all known models
+#[derive(Debug)]
+pub struct ModelRegistry {
+    entries: Vec<ModelEntry>,
+}
+
+/// Names `model` is matched under, lowercased and without the gateway's "#g1_" marker: the full id, the name
+/// after any vendor routing like "openai/", and that name from each hyphen on, so the family in a vendor's
+/// own naming ("meta-llama-3.1-8b-instruct" for "llama-3.1") is found
+fn match_names(model: &str) -> Vec<String> {
+    let full = model.trim().to_lowercase();
+    let full = full.strip_prefix("#g1_").unwrap_or(&full).to_string();
+    let name = full.rsplit('/').next().unwrap_or(&full);
+    let name = name.strip_prefix("#g1_").unwrap_or(name).to_string();
+    let mut names = vec![full.clone()];
+    if name != full {
+        names.push(name.clone());
+    }
+    names.extend(name.match_indices('-').map(|(at, _)| name[at + 1..].to_string()));
+    names
+}
+
+impl ModelRegistry {
+    fn load() -> Self {
+        let overrides = app_data_dir()
+            .ok()
+            .and_then(|dir| std::fs::read_to_string(dir.join(REGISTRY_FILE)).ok());
+        Self::parse(BUNDLED_REGISTRY, overrides.as_deref())
+    }
+
+    /// The bundled registry with the user's entries replacing or extending it by id; an invalid user file is ignored
+    fn parse(bundled: &str, overrides: Option<&str>) -> Self {
+        let mut entries = serde_json::from_str::<RegistryFile>(bundled)
+            .map(|file| file.models)
+            .expect("bundled model registry is valid JSON");
+
+        let overrides = overrides
+            .and_then(|json| match serde_json::from_str::<RegistryFile>(json) {
+                Ok(file) => Some(file.models),
+                Err(e) => {
+                    log::warn!("Ignoring invalid {}: {}", REGISTRY_FILE, e);
+                    None
+                }
+            })
+            .unwrap_or_default();
+        for entry in overrides {
+            match entries.iter_mut().find(|existing| existing.id == entry.id) {
+                Some(existing) => *existing = entry,
+                None => entries.push(entry),
+            }
+        }
+
+        Self { entries }
+    }
+
+    /// Entry for `model`: the longest id one of its names starts with, so "gpt-4o-mini" wins over "gpt-4o"
+    pub fn get(&self, model: &str) -> Option<&ModelEntry> {
+        let names = match_names(model);
+        self.entries
+            .iter()
+            .filter(|entry| {
+                let id = entry.id.to_lowercase();
+                names.iter().any(|name| name.starts_with(&id))
+            })
+            .max_by_key(|entry| entry.id.len())
+    }
+
+    pub fn models(&self, modality: Option<ModelModality>) -> Vec<ModelEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| modality.map_or(true, |modality| entry.modality == modality))
+            .cloned()
+            .collect()
+    }
+
+    pub fn supports(&self, model: &str, modality: ModelModality) -> bool {
+        self.get(model).map_or(false, |entry| entry.modality == modality)
+    }
+
+    /// Reject a model the registry does not know, or one that cannot do what the setting asks of it
+    pub fn check(&self, setting: &str, model: &str, modality: ModelModality) -> Result<(), AppError> {
+        let invalid = |message: String| AppError::Validation(ValidationError::InvalidConfigValue(message));
+        match self.get(model) {
+            Some(entry) if entry.modality == modality => Ok(()),
+            Some(entry) => Err(invalid(format!(
+                "{} cannot be {}: it is a {} model",
+                model,
+                setting,
+                entry.modality.label()
+            ))),
+            None => Err(invalid(format!(
+                "Unknown model {} for {}; add it to {} in the data directory to use it",
+                model, setting, REGISTRY_FILE
+            ))),
+        }
+    }
+}
+
+struct LoadedRegistry {
+    registry: Arc<ModelRegistry>,
+    /// Modification time of the user's file when it was read; `None` when there was none
+    modified: Option<SystemTime>,
+    checked: Instant,
+}
+
+static MODEL_REGISTRY: Mutex<Option<LoadedRegistry>> = Mutex::new(None);
+
+fn user_file_modified() -> Option<SystemTime> {
+    let dir = app_data_dir().ok()?;
+    std::fs::metadata(dir.join(REGISTRY_FILE)).ok()?.modified().ok()
+}
+
+/// Get the global model registry, read again once the user's models.json has been added, edited or removed
+pub fn get_model_registry() -> Arc<ModelRegistry> {
+    let mut loaded = MODEL_REGISTRY.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(current) = loaded.as_mut() {
+        if current.checked.elapsed() < RELOAD_CHECK {
+            return current.registry.clone();
+        }
+        current.checked = Instant::now();
+        let modified = user_file_modified();
+        if modified == current.modified {
+            return current.registry.clone();
+        }
+        log::info!("{} changed; reloading the model registry", REGISTRY_FILE);
+    }
+    let modified = user_file_modified();
+    let registry = Arc::new(ModelRegistry::load());
+    *loaded = Some(LoadedRegistry {
+        registry: registry.clone(),
+        modified,
+        checked: Instant::now(),
+    });
+    registry
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bundled() -> ModelRegistry {
+        ModelRegistry::parse(BUNDLED_REGISTRY, None)
+    }
+
+    #[test]
+    fn bundled_registry_parses_with_unique_ids() {
+        let registry = bundled();
+        assert!(!registry.entries.is_empty());
+        let mut ids: Vec<&str> = registry.entries.iter().map(|entry| entry.id.as_str()).collect();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), registry.entries.len());
+    }
+
+    #[test]
+    fn vendor_ids_find_their_family() {
+        let registry = bundled();
+        let id = |model: &str| registry.get(model).map(|entry| entry.id.clone());
+        assert_eq!(id("meta-llama/Meta-Llama-3.1-8B-Instruct-Turbo").as_deref(), Some("llama-3.1"));
+        assert_eq!(id("openai/gpt-4o-mini-2024-07-18").as_deref(), Some("gpt-4o-mini"));
+        assert_eq!(id("#g1_gpt-4o").as_deref(), Some("gpt-4o"));
+        assert_eq!(id("gpt-4o-mini-tts").as_deref(), Some("gpt-4o-mini-tts"));
+        assert!(registry.supports("mistralai/Mistral-7B-Instruct-v0.2", ModelModality::Text));
+        assert!(registry.get("unknown/some-model").is_none());
+    }
+
+    #[test]
+    fn user_entries_replace_and_extend_by_id() {
+        let overrides = r#"{ "version": 1, "models": [
+            { "id": "gpt-4o", "name": "GPT-4o", "provider": "openai", "modality": "text",
+              "context_tokens": 1000, "speed": "fast" },
+            { "id": "qwen/qwen2.5-72b", "name": "Qwen 2.5", "provider": "qwen", "modality": "text", "speed": "balanced" }
+        ] }"#;
+        let registry = ModelRegistry::parse(BUNDLED_REGISTRY, Some(overrides));
+        assert_eq!(registry.get("gpt-4o").unwrap().context_tokens, Some(1000));
+        assert!(registry.check("the text model", "Qwen/Qwen2.5-72B-Instruct-Turbo", ModelModality::Text).is_ok());
+        assert!(bundled().check("the text model", "Qwen/Qwen2.5-72B-Instruct-Turbo", ModelModality::Text).is_err());
+
+        let invalid = ModelRegistry::parse(BUNDLED_REGISTRY, Some("not json"));
+        assert_eq!(invalid.entries.len(), bundled().entries.len());
+    }
+}
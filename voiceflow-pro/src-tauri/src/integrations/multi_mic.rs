@@ -0,0 +1,570 @@
+// Multi-Microphone Capture Module
+// Records two input devices at once, mixed or kept apart, fails over when a device disappears and tells which microphone was speaking
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tokio::sync::{broadcast, mpsc, watch};
+use tokio::time::Instant;
+
+use super::audio_capture::{self, AudioCaptureSettings, CaptureHandle, TARGET_SAMPLE_RATE};
+use super::echo_cancellation;
+use super::stt_providers;
+use super::voice_recognition::{EngineState, VoiceEngineStatus};
+use crate::errors::{AppError, ValidationError};
+
+/// How often open devices are checked for silence from the driver
+const HEALTH_CHECK: Duration = Duration::from_millis(250);
+/// No frames for this long from a device the system no longer lists means it was unplugged
+const STALL_AFTER: Duration = Duration::from_millis(1_500);
+/// No frames for this long means the device is gone even if the system still lists it
+const LOST_AFTER: Duration = Duration::from_secs(6);
+/// A channel running ahead of the other while mixing keeps at most this much audio
+const MIX_BUFFER_SAMPLES: usize = TARGET_SAMPLE_RATE as usize;
+/// Mean square level below which a channel counts as silent when picking the speaker
+const SPEAKER_FLOOR: f64 = 1e-5;
+const FRAME_CAPACITY: usize = 64;
+const MAX_LABEL_CHARS: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MultiMicMode {
+    /// One microphone, opened by the recognizer as usual
+    #[default]
+    Off,
+    /// Record the primary device and switch to the secondary one if it disappears
+    Fallback,
+    /// Record both devices and sum them into one stream
+    Mix,
+    /// Record both devices and keep their frames apart, one labeled channel each
+    DualChannel,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MultiMicSettings {
+    pub mode: MultiMicMode,
+    /// Second input device by name; the primary is `AudioCaptureSettings::device`
+    pub secondary_device: Option<String>,
+    /// Reopen a lost channel on the system default input when it is not already in use
+    pub failover: bool,
+    /// Shown in history for segments spoken into the primary device, e.g. the host's name
+    pub primary_label: String,
+    pub secondary_label: String,
+}
+
+impl Default for MultiMicSettings {
+    fn default() -> Self {
+        Self {
+            mode: MultiMicMode::Off,
+            secondary_device: None,
+            failover: true,
+            primary_label: "Mic 1".to_string(),
+            secondary_label: "Mic 2".to_string(),
+        }
+    }
+}
+
+pub fn validate(settings: &AudioCaptureSettings) -> Result<(), AppError> {
+    let invalid = |message: String| AppError::Validation(ValidationError::InvalidConfigValue(message));
+    let multi = &settings.multi_mic;
+    for label in [&multi.primary_label, &multi.secondary_label] {
+        if label.trim().is_empty() || label.chars().count() > MAX_LABEL_CHARS {
+            return Err(invalid(format!(
+                "Microphone labels must be 1-{} characters, got \"{}\"",
+                MAX_LABEL_CHARS, label
+            )));
+        }
+    }
+    if multi.primary_label.trim() == multi.secondary_label.trim() {
+        return Err(invalid("The two microphones need different labels".to_string()));
+    }
+    if multi.mode == MultiMicMode::Off {
+        return Ok(());
+    }
+    let Some(secondary) = multi.secondary_device.as_deref().filter(|name| !name.trim().is_empty()) else {
+        return Err(invalid("Recording from two microphones needs a secondary device".to_string()));
+    };
+    if settings.device.as_deref() == Some(secondary) {
+        return Err(invalid("The secondary microphone must be a different device from the primary".to_string()));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptureChannel {
+    Primary,
+    Secondary,
+}
+
+/// Audio at `TARGET_SAMPLE_RATE`; mixed frames carry the label of the louder microphone
+#[derive(Debug, Clone)]
+pub struct CaptureFrame {
+    /// `None` for the audio the recognizer hears: both microphones mixed, or the one recording in fallback mode.
+    /// Dual-channel sessions also send each microphone's own frames
+    pub channel: Option<CaptureChannel>,
+    pub label: String,
+    pub samples: Arc<Vec<f32>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChannelStatus {
+    pub channel: CaptureChannel,
+    pub label: String,
+    /// Device currently recording this channel; `None` once it was lost with nothing to fail over to
+    pub device: Option<String>,
+    /// Another device took over after the configured one disappeared
+    pub failed_over: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MultiMicStatus {
+    pub session_id: String,
+    pub mode: MultiMicMode,
+    pub channels: Vec<ChannelStatus>,
+}
+
+/// Sent as "capture-device-lost" when a device stops delivering audio, and as "capture-failover"
+/// once another device has taken its place (`to` is set)
+#[derive(Debug, Clone, Serialize)]
+pub struct CaptureFailoverEvent {
+    pub session_id: String,
+    pub channel: CaptureChannel,
+    pub label: String,
+    pub from: String,
+    pub to: Option<String>,
+}
+
+/// Energy heard per channel since the last finished segment
+#[derive(Debug, Default)]
+struct SpeakerActivity {
+    primary: f64,
+    secondary: f64,
+}
+
+struct Session {
+    status: MultiMicStatus,
+    activity: Arc<Mutex<SpeakerActivity>>,
+    frames: broadcast::Sender<CaptureFrame>,
+    stop: watch::Sender<bool>,
+}
+
+static SESSION: Mutex<Option<Session>> = Mutex::new(None);
+/// Bumped for each feed into the speech stream, so a replaced feed stops instead of sending audio twice
+static STREAM_FEED: AtomicU64 = AtomicU64::new(0);
+
+struct OpenChannel {
+    channel: CaptureChannel,
+    label: String,
+    /// Name configured for the channel, `None` for the system default
+    configured: Option<String>,
+    handle: Option<CaptureHandle>,
+    last_frame: Instant,
+    failed_over: bool,
+    buffer: VecDeque<f32>,
+}
+
+impl OpenChannel {
+    fn device(&self) -> Option<String> {
+        self.handle.as_ref().map(|handle| handle.format.device.clone())
+    }
+
+    fn status(&self) -> ChannelStatus {
+        ChannelStatus {
+            channel: self.channel,
+            label: self.label.clone(),
+            device: self.device(),
+            failed_over: self.failed_over,
+        }
+    }
+}
+
+/// Open the configured microphones for a dictation session; a no-op when multi-microphone capture
+/// is off or the session already has them. Capture ends with the session.
+pub async fn start_session(
+    settings: &AudioCaptureSettings,
+    app: &AppHandle,
+    engine_status: watch::Receiver<VoiceEngineStatus>,
+) -> Result<Option<MultiMicStatus>, AppError> {
+    let multi = settings.multi_mic.clone();
+    if multi.mode == MultiMicMode::Off {
+        return Ok(None);
+    }
+    let session_id = engine_status.borrow().session_id.clone();
+    if let Some(status) = status().filter(|status| status.session_id == session_id) {
+        return Ok(Some(status));
+    }
+    stop();
+
+    let (merged_sender, merged) = mpsc::unbounded_channel();
+    let mut channels = vec![OpenChannel {
+        channel: CaptureChannel::Primary,
+        label: multi.primary_label.trim().to_string(),
+        configured: settings.device.clone(),
+        handle: None,
+        last_frame: Instant::now(),
+        failed_over: false,
+        buffer: VecDeque::new(),
+    }];
+    if multi.mode != MultiMicMode::Fallback {
+        channels.push(OpenChannel {
+            channel: CaptureChannel::Secondary,
+            label: multi.secondary_label.trim().to_string(),
+            configured: multi.secondary_device.clone(),
+            handle: None,
+            last_frame: Instant::now(),
+            failed_over: false,
+            buffer: VecDeque::new(),
+        });
+    }
+    for open in channels.iter_mut() {
+        let device = open.configured.clone();
+        match open_device(settings, device.clone(), open.channel, &merged_sender).await {
+            Ok(handle) => open.handle = Some(handle),
+            // Fallback mode starts on the standby device when the primary is already missing
+            Err(e) if multi.mode == MultiMicMode::Fallback => {
+                log::warn!("Primary microphone unavailable, starting on the secondary: {}", e);
+                open.handle = Some(open_device(settings, multi.secondary_device.clone(), open.channel, &merged_sender).await?);
+                open.failed_over = true;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    let status = MultiMicStatus {
+        session_id: session_id.clone(),
+        mode: multi.mode,
+        channels: channels.iter().map(OpenChannel::status).collect(),
+    };
+    let activity = Arc::new(Mutex::new(SpeakerActivity::default()));
+    let (frames, _) = broadcast::channel(FRAME_CAPACITY);
+    let (stop_sender, stop_receiver) = watch::channel(false);
+    if let Ok(mut session) = SESSION.lock() {
+        *session = Some(Session {
+            status: status.clone(),
+            activity: activity.clone(),
+            frames: frames.clone(),
+            stop: stop_sender,
+        });
+    }
+    log::info!(
+        "Recording {} microphone(s) in {:?} mode: {}",
+        channels.len(),
+        multi.mode,
+        channels.iter().filter_map(OpenChannel::device).collect::<Vec<_>>().join(", ")
+    );
+
+    let supervisor = Supervisor {
+        settings: settings.clone(),
+        app: app.clone(),
+        session_id,
+        channels,
+        merged,
+        merged_sender,
+        activity,
+        frames,
+    };
+    tauri::async_runtime::spawn(supervisor.run(engine_status, stop_receiver));
+    Ok(Some(status))
+}
+
+/// Close the microphones of the current session, if any
+pub fn stop() {
+    if let Ok(mut session) = SESSION.lock() {
+        if let Some(session) = session.take() {
+            let _ = session.stop.send(true);
+        }
+    }
+}
+
+pub fn status() -> Option<MultiMicStatus> {
+    SESSION.lock().ok()?.as_ref().map(|session| session.status.clone())
+}
+
+/// Frames of the running session, for a recognizer that reads them directly
+pub fn subscribe() -> Option<broadcast::Receiver<CaptureFrame>> {
+    SESSION.lock().ok()?.as_ref().map(|session| session.frames.subscribe())
+}
+
+/// Send the session's recognizer audio to the open speech stream until either ends; false without a session.
+/// Called when either starts, as dictation may open the microphones before or after the stream.
+pub fn feed_speech_stream() -> bool {
+    let Some(mut frames) = subscribe() else {
+        return false;
+    };
+    let generation = STREAM_FEED.fetch_add(1, Ordering::SeqCst) + 1;
+    tauri::async_runtime::spawn(async move {
+        while STREAM_FEED.load(Ordering::SeqCst) == generation {
+            match frames.recv().await {
+                Ok(frame) if frame.channel.is_none() => {
+                    if let Err(e) = stt_providers::push_cancelled_stream_audio(&frame.samples, TARGET_SAMPLE_RATE).await {
+                        log::debug!("Microphone audio no longer goes to the speech stream: {}", e);
+                        break;
+                    }
+                }
+                Ok(_) => {}
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    log::warn!("The speech stream missed {} microphone frames", skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+    true
+}
+
+/// Label of the microphone heard most since the last call, which starts the next segment afresh.
+/// Each guest has their own microphone, so this is the segment's speaker.
+pub fn take_speaker() -> Option<String> {
+    let session = SESSION.lock().ok()?;
+    let session = session.as_ref()?;
+    let mut activity = session.activity.lock().ok()?;
+    let heard = std::mem::take(&mut *activity);
+    let channel = if heard.primary.max(heard.secondary) < SPEAKER_FLOOR {
+        return None;
+    } else if heard.primary >= heard.secondary {
+        CaptureChannel::Primary
+    } else {
+        CaptureChannel::Secondary
+    };
+    session
+        .status
+        .channels
+        .iter()
+        .find(|status| status.channel == channel)
+        .map(|status| status.label.clone())
+}
+
+async fn open_device(
+    settings: &AudioCaptureSettings,
+    device: Option<String>,
+    channel: CaptureChannel,
+    merged: &mpsc::UnboundedSender<(CaptureChannel, Vec<f32>)>,
+) -> Result<CaptureHandle, AppError> {
+    let settings = AudioCaptureSettings {
+        device,
+        ..settings.clone()
+    };
+    let (sender, mut frames) = mpsc::unbounded_channel();
+    let handle = audio_capture::start_capture(&settings, sender).await?;
+    let merged = merged.clone();
+    // Ends when the capture handle is dropped and its stream closes
+    tokio::spawn(async move {
+        while let Some(frame) = frames.recv().await {
+            if merged.send((channel, frame)).is_err() {
+                break;
+            }
+        }
+    });
+    Ok(handle)
+}
+
+fn mean_square(samples: &[f32]) -> f64 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    samples.iter().map(|sample| (*sample as f64).powi(2)).sum::<f64>() / samples.len() as f64
+}
+
+struct Supervisor {
+    settings: AudioCaptureSettings,
+    app: AppHandle,
+    session_id: String,
+    channels: Vec<OpenChannel>,
+    merged: mpsc::UnboundedReceiver<(CaptureChannel, Vec<f32>)>,
+    /// Kept so `merged` stays open while every device is being replaced
+    merged_sender: mpsc::UnboundedSender<(CaptureChannel, Vec<f32>)>,
+    activity: Arc<Mutex<SpeakerActivity>>,
+    frames: broadcast::Sender<CaptureFrame>,
+}
+
+impl Supervisor {
+    async fn run(mut self, mut engine_status: watch::Receiver<VoiceEngineStatus>, mut stop: watch::Receiver<bool>) {
+        let mut health = tokio::time::interval(HEALTH_CHECK);
+        loop {
+            tokio::select! {
+                changed = engine_status.changed() => {
+                    let ended = changed.is_err() || {
+                        let current = engine_status.borrow();
+                        current.session_id != self.session_id
+                            || matches!(current.state, EngineState::Idle | EngineState::Error(_))
+                    };
+                    if ended {
+                        break;
+                    }
+                }
+                _ = stop.changed() => break,
+                Some((channel, samples)) = self.merged.recv() => self.deliver(channel, samples),
+                _ = health.tick() => {
+                    if !self.check_devices().await {
+                        break;
+                    }
+                }
+            }
+        }
+
+        // Dropping the handles closes the streams
+        self.channels.clear();
+        if let Ok(mut session) = SESSION.lock() {
+            if session.as_ref().map_or(false, |session| session.status.session_id == self.session_id) {
+                *session = None;
+            }
+        }
+        log::info!("Multi-microphone capture ended");
+    }
+
+    fn deliver(&mut self, channel: CaptureChannel, mut samples: Vec<f32>) {
+        let Some(index) = self.channels.iter().position(|open| open.channel == channel) else {
+            return;
+        };
+        // One echo path is modelled, from the speakers to the primary microphone; a guest's mic is usually further away
+        if channel == CaptureChannel::Primary {
+            echo_cancellation::process_capture(&mut samples);
+        }
+        self.channels[index].last_frame = Instant::now();
+        let energy = mean_square(&samples) * samples.len() as f64;
+        if let Ok(mut activity) = self.activity.lock() {
+            match channel {
+                CaptureChannel::Primary => activity.primary += energy,
+                CaptureChannel::Secondary => activity.secondary += energy,
+            }
+        }
+
+        let label = self.channels[index].label.clone();
+        match self.settings.multi_mic.mode {
+            MultiMicMode::Off | MultiMicMode::Fallback => {
+                self.publish(None, label, samples);
+                return;
+            }
+            // Each microphone keeps its own frames, and the recognizer hears both mixed
+            MultiMicMode::DualChannel => self.publish(Some(channel), label.clone(), samples.clone()),
+            MultiMicMode::Mix => {}
+        }
+
+        let live = self.channels.iter().filter(|open| open.handle.is_some()).count();
+        if live < 2 {
+            // The other microphone is gone; its partner goes through on its own
+            self.publish(None, label, samples);
+            return;
+        }
+        let buffer = &mut self.channels[index].buffer;
+        buffer.extend(samples);
+        let overflow = buffer.len().saturating_sub(MIX_BUFFER_SAMPLES);
+        buffer.drain(..overflow);
+
+        let ready = self.channels.iter().map(|open| open.buffer.len()).min().unwrap_or(0);
+        if ready == 0 {
+            return;
+        }
+        let (first, second) = self.channels.split_at_mut(1);
+        let (primary, secondary) = (&mut first[0], &mut second[0]);
+        let a: Vec<f32> = primary.buffer.drain(..ready).collect();
+        let b: Vec<f32> = secondary.buffer.drain(..ready).collect();
+        let label = if mean_square(&a) >= mean_square(&b) {
+            primary.label.clone()
+        } else {
+            secondary.label.clone()
+        };
+        let mixed = a.iter().zip(&b).map(|(a, b)| (a + b).clamp(-1.0, 1.0)).collect();
+        self.publish(None, label, mixed);
+    }
+
+    fn publish(&self, channel: Option<CaptureChannel>, label: String, samples: Vec<f32>) {
+        // Nobody listening is fine; frames are only read by subscribers
+        let _ = self.frames.send(CaptureFrame {
+            channel,
+            label,
+            samples: Arc::new(samples),
+        });
+    }
+
+    /// Replace devices that stopped delivering; false when no microphone is left
+    async fn check_devices(&mut self) -> bool {
+        let stalled: Vec<usize> = self
+            .channels
+            .iter()
+            .enumerate()
+            .filter(|(_, open)| open.handle.is_some() && open.last_frame.elapsed() >= STALL_AFTER)
+            .map(|(index, _)| index)
+            .collect();
+        if stalled.is_empty() {
+            return true;
+        }
+
+        let listed = audio_capture::input_device_names();
+        for index in stalled {
+            let Some(device) = self.channels[index].device() else {
+                continue;
+            };
+            if listed.contains(&device) && self.channels[index].last_frame.elapsed() < LOST_AFTER {
+                continue;
+            }
+            log::warn!("{} stopped delivering audio", device);
+            self.channels[index].handle = None;
+            self.channels[index].buffer.clear();
+            let mut event = CaptureFailoverEvent {
+                session_id: self.session_id.clone(),
+                channel: self.channels[index].channel,
+                label: self.channels[index].label.clone(),
+                from: device.clone(),
+                to: None,
+            };
+            let _ = self.app.emit_all("capture-device-lost", &event);
+
+            if let Some(handle) = self.replacement(index, &device, &listed).await {
+                log::info!("{} took over from {}", handle.format.device, device);
+                event.to = Some(handle.format.device.clone());
+                let open = &mut self.channels[index];
+                open.handle = Some(handle);
+                open.last_frame = Instant::now();
+                open.failed_over = true;
+                let _ = self.app.emit_all("capture-failover", &event);
+            }
+            self.refresh_status();
+        }
+        self.channels.iter().any(|open| open.handle.is_some())
+    }
+
+    /// A device to record the lost channel on: the standby in fallback mode, otherwise the system default
+    async fn replacement(&self, index: usize, lost: &str, listed: &[String]) -> Option<CaptureHandle> {
+        let multi = &self.settings.multi_mic;
+        let in_use: Vec<String> = self.channels.iter().filter_map(OpenChannel::device).collect();
+        let mut candidates: Vec<Option<String>> = Vec::new();
+        if multi.mode == MultiMicMode::Fallback {
+            candidates.push(multi.secondary_device.clone());
+            candidates.push(self.channels[index].configured.clone());
+        }
+        if multi.failover || multi.mode == MultiMicMode::Fallback {
+            candidates.push(None);
+        }
+
+        let default_device = audio_capture::default_input_device_name();
+        for candidate in candidates {
+            let name = candidate.clone().or_else(|| default_device.clone());
+            let Some(name) = name else {
+                continue;
+            };
+            if name == lost || in_use.contains(&name) || !listed.contains(&name) {
+                continue;
+            }
+            match open_device(&self.settings, candidate, self.channels[index].channel, &self.merged_sender).await {
+                Ok(handle) => return Some(handle),
+                Err(e) => log::warn!("Could not fail over to {}: {}", name, e),
+            }
+        }
+        None
+    }
+
+    fn refresh_status(&self) {
+        if let Ok(mut session) = SESSION.lock() {
+            if let Some(session) = session.as_mut().filter(|session| session.status.session_id == self.session_id) {
+                session.status.channels = self.channels.iter().map(OpenChannel::status).collect();
+            }
+        }
+    }
+}
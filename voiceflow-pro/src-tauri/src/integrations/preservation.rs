@@ -0,0 +1,333 @@
+// Preservation Module
+// Quotes, citations, numbers and names that enhancement must leave exactly as dictated, and the check that puts them back
+
+use std::sync::OnceLock;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use super::ai_text_processor::{ChangeType, TextChange};
+use crate::unicode_text::similarity;
+
+/// An altered span is put back when what replaced it is at least this similar; less similar text is a conflict
+const MIN_REVERT_SIMILARITY: f32 = 0.5;
+/// Spans this short ("3", "12") are one edit away from most other short numbers, so they need a closer match
+const SHORT_SPAN_CHARS: usize = 4;
+const MIN_SHORT_REVERT_SIMILARITY: f32 = 0.75;
+
+/// What the preservation check protects, in priority order when spans overlap
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PreservedKind {
+    /// Text inside quotation marks
+    Quotes,
+    /// Author-year and numbered references, case names, reporters and sections
+    Citations,
+    ProperNouns,
+    Numbers,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PreservedSpan {
+    pub kind: PreservedKind,
+    pub text: String,
+}
+
+/// The enhanced text with preserved spans put back, and what was put back or could not be
+#[derive(Debug, Clone)]
+pub struct PreservationOutcome {
+    pub text: String,
+    /// Restored spans replace what the enhancement wrote; a span that was dropped outright has an empty
+    /// `replacement` and zero confidence, as there was nothing to put it back in place of
+    pub changes: Vec<TextChange>,
+}
+
+impl PreservationOutcome {
+    pub fn conflicts(&self) -> usize {
+        self.changes.iter().filter(|change| change.replacement.is_empty()).count()
+    }
+}
+
+struct Patterns {
+    quotes: Regex,
+    citations: Vec<Regex>,
+    proper_nouns: Regex,
+    numbers: Regex,
+}
+
+fn patterns() -> &'static Patterns {
+    static PATTERNS: OnceLock<Patterns> = OnceLock::new();
+    PATTERNS.get_or_init(|| Patterns {
+        // The quoted words, without the marks, so a change of quote style is not a conflict
+        quotes: Regex::new(r#""([^"\n]+)"|“([^”\n]+)”|«\s?([^»\n]+?)\s?»|「([^」\n]+)」|„([^“”\n]+)[“”]"#)
+            .expect("quote pattern is valid"),
+        citations: [
+            // (Smith, 2020), (Smith & Jones 2019a, p. 12), (Smith et al., 2020, pp. 3-5)
+            r"\(\p{Lu}[^()\n]{0,80}?\b\d{4}[a-z]?(?:,\s*pp?\.\s*\d+(?:[-–]\d+)?)?\)",
+            // [12], [3, 7], [4-6]
+            r"\[\d+(?:\s*[,–-]\s*\d+)*\]",
+            // 410 U.S. 113, 123 F.3d 456, 56 L. Ed. 2d 78
+            r"\b\d+\s+(?:U\.S\.|S\.\s?Ct\.|L\.\s?Ed\.(?:\s?2d)?|F\.\s?Supp\.(?:\s?[23]d)?|F\.(?:2d|3d|4th)?)\s+\d+\b",
+            // § 12(b)(1), §§ 3-5, Art. 5
+            r"§§?\s*\d+[\w.]*(?:\([\w]+\))*(?:\s*[-–]\s*\d+[\w.]*)?|\bArt\.\s*\d+[\w.]*(?:\(\w+\))*",
+            // Roe v. Wade, Brown v. Board of Education
+            r"\b\p{Lu}[\w.'&-]*(?:\s+\p{Lu}[\w.'&-]*)*\s+v\.\s+\p{Lu}[\w.'&-]*(?:\s+(?:of\s+)?\p{Lu}[\w.'&-]*)*",
+        ]
+        .iter()
+        .map(|pattern| Regex::new(pattern).expect("citation pattern is valid"))
+        .collect(),
+        proper_nouns: Regex::new(r"\b\p{Lu}[\p{L}\p{M}'’-]*(?:[ \t]+\p{Lu}[\p{L}\p{M}'’-]*)*")
+            .expect("proper noun pattern is valid"),
+        numbers: Regex::new(r"[$€£¥]?\b\d+(?:[.,:/]\d+)*(?:%|\b)").expect("number pattern is valid"),
+    })
+}
+
+/// Whether `start` begins a sentence, where any word is capitalized and says nothing about being a name
+fn starts_sentence(text: &str, start: usize) -> bool {
+    let before = text[..start].trim_end_matches(|c: char| c.is_whitespace() || "\"“„«「(".contains(c));
+    before.is_empty() || before.ends_with(['.', '!', '?', ':', ';', '\n', '。', '！', '？'])
+}
+
+/// Spans of `kinds` in `text`, earliest first; a span inside a higher-priority one (a number in a quote) is left to it
+pub fn extract(text: &str, kinds: &[PreservedKind]) -> Vec<PreservedSpan> {
+    let patterns = patterns();
+    let mut found: Vec<(PreservedKind, usize, usize)> = Vec::new();
+    let mut kinds = kinds.to_vec();
+    kinds.sort();
+    kinds.dedup();
+    for kind in kinds {
+        let matches: Vec<(usize, usize)> = match kind {
+            PreservedKind::Quotes => patterns
+                .quotes
+                .captures_iter(text)
+                .filter_map(|captures| captures.iter().skip(1).flatten().next())
+                .map(|inner| (inner.start(), inner.end()))
+                .collect(),
+            PreservedKind::Citations => patterns
+                .citations
+                .iter()
+                .flat_map(|pattern| pattern.find_iter(text))
+                .map(|m| (m.start(), m.end()))
+                .collect(),
+            PreservedKind::ProperNouns => patterns
+                .proper_nouns
+                .find_iter(text)
+                .filter(|m| m.as_str() != "I" && !m.as_str().starts_with("I'") && !m.as_str().starts_with("I’"))
+                // One capitalized word opening a sentence is just the sentence start
+                .filter(|m| !starts_sentence(text, m.start()) || m.as_str().split_whitespace().count() > 1)
+                .map(|m| (m.start(), m.end()))
+                .collect(),
+            PreservedKind::Numbers => patterns.numbers.find_iter(text).map(|m| (m.start(), m.end())).collect(),
+        };
+        for (start, end) in matches {
+            let overlaps = found.iter().any(|(_, s, e)| start < *e && *s < end);
+            if !overlaps && !text[start..end].trim().is_empty() {
+                found.push((kind, start, end));
+            }
+        }
+    }
+    found.sort_by_key(|(_, start, _)| *start);
+    found
+        .into_iter()
+        .map(|(kind, start, end)| PreservedSpan {
+            kind,
+            text: text[start..end].trim().to_string(),
+        })
+        .collect()
+}
+
+/// Byte ranges of the whitespace-separated tokens of `text`, with punctuation around them trimmed off
+fn token_ranges(text: &str) -> Vec<(usize, usize)> {
+    let mut ranges = Vec::new();
+    let mut offset = 0;
+    for token in text.split_inclusive(char::is_whitespace) {
+        let word = token.trim_end();
+        let core = word.trim_matches(|c: char| !c.is_alphanumeric() && !"$€£¥§".contains(c));
+        if !core.is_empty() {
+            let leading = word.find(core).unwrap_or(0);
+            ranges.push((offset + leading, offset + leading + core.len()));
+        }
+        offset += token.len();
+    }
+    ranges
+}
+
+/// `start..end` grown over the brackets, marks or symbols `span` opens and closes with, where `text` has them
+/// too, so restoring "(Smith, 2020)" over "Smith 2020" does not double the parentheses
+fn widened(text: &str, mut start: usize, mut end: usize, span: &str) -> (usize, usize) {
+    let inner = span.trim_matches(|c: char| !c.is_alphanumeric());
+    let opening = &span[..span.find(inner).unwrap_or(0)];
+    let closing = &span[span.find(inner).unwrap_or(0) + inner.len()..];
+    if !opening.is_empty() && text[..start].ends_with(opening) {
+        start -= opening.len();
+    }
+    if !closing.is_empty() && text[end..].starts_with(closing) {
+        end += closing.len();
+    }
+    (start, end)
+}
+
+/// Copies of `span` in `text` that stand on their own, so "3" is not found in "13" nor "Art. 5" in "Art. 50"
+fn occurrences(text: &str, span: &str) -> usize {
+    if span.is_empty() {
+        return 0;
+    }
+    text.match_indices(span)
+        .filter(|(start, _)| {
+            let before = text[..*start].chars().next_back();
+            let after = text[start + span.len()..].chars().next();
+            !before.is_some_and(char::is_alphanumeric) && !after.is_some_and(char::is_alphanumeric)
+        })
+        .count()
+}
+
+/// Least similarity at which an altered `span` is put back
+fn revert_threshold(span: &str) -> f32 {
+    if span.chars().count() <= SHORT_SPAN_CHARS {
+        MIN_SHORT_REVERT_SIMILARITY
+    } else {
+        MIN_REVERT_SIMILARITY
+    }
+}
+
+/// The run of tokens in `text` most like `span`, as a byte range with its similarity. Runs that `original`
+/// already had as many times are dictated text of their own and never taken for an altered `span`.
+fn closest_run(text: &str, span: &str, original: &str) -> Option<(usize, usize, f32)> {
+    let tokens = token_ranges(text);
+    let span_tokens = span.split_whitespace().count().max(1);
+    let mut best: Option<(usize, usize, f32)> = None;
+    for width in span_tokens.saturating_sub(1).max(1)..=span_tokens + 1 {
+        for run in tokens.windows(width) {
+            let (start, end) = widened(text, run[0].0, run[width - 1].1, span);
+            let candidate = &text[start..end];
+            if occurrences(text, candidate) <= occurrences(original, candidate) {
+                continue;
+            }
+            let score = similarity(candidate, span);
+            if best.map_or(true, |(_, _, best_score)| score > best_score) {
+                best = Some((start, end, score));
+            }
+        }
+    }
+    best
+}
+
+/// Check that every span of `kinds` in `original` survived into `enhanced`; altered spans are put back
+/// and dropped ones are listed as conflicts
+pub fn restore(original: &str, enhanced: &str, kinds: &[PreservedKind]) -> PreservationOutcome {
+    let mut text = enhanced.to_string();
+    let mut changes = Vec::new();
+    let mut checked: Vec<&str> = Vec::new();
+    let spans = extract(original, kinds);
+    for span in &spans {
+        if checked.contains(&span.text.as_str()) {
+            continue;
+        }
+        checked.push(&span.text);
+        if occurrences(&text, &span.text) >= occurrences(original, &span.text) {
+            continue;
+        }
+        let threshold = revert_threshold(&span.text);
+        match closest_run(&text, &span.text, original).filter(|(_, _, score)| *score >= threshold) {
+            Some((start, end, score)) => {
+                changes.push(TextChange {
+                    change_type: ChangeType::Preservation,
+                    original: text[start..end].to_string(),
+                    replacement: span.text.clone(),
+                    position: start,
+                    confidence: score,
+                });
+                text.replace_range(start..end, &span.text);
+            }
+            // Fewer copies than dictated but one still there means the repeat was merged away, which is fine
+            None if occurrences(&text, &span.text) > 0 => {}
+            None => changes.push(TextChange {
+                change_type: ChangeType::Preservation,
+                original: span.text.clone(),
+                replacement: String::new(),
+                position: 0,
+                confidence: 0.0,
+            }),
+        }
+    }
+    PreservationOutcome { text, changes }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spans(text: &str, kinds: &[PreservedKind]) -> Vec<String> {
+        extract(text, kinds).into_iter().map(|span| span.text).collect()
+    }
+
+    #[test]
+    fn extracts_quotes_without_their_marks() {
+        assert_eq!(spans("She said “see you at 5” and left", &[PreservedKind::Quotes]), vec!["see you at 5"]);
+        // The number inside the quote is left to the quote
+        assert_eq!(
+            spans("She said \"see you at 5\" twice", &[PreservedKind::Numbers, PreservedKind::Quotes]),
+            vec!["see you at 5"]
+        );
+    }
+
+    #[test]
+    fn extracts_citations() {
+        let text = "As held in Roe v. Wade (Smith, 2020) under § 12(b)(1) and Art. 5, see [3, 7].";
+        assert_eq!(
+            spans(text, &[PreservedKind::Citations]),
+            vec!["Roe v. Wade", "(Smith, 2020)", "§ 12(b)(1)", "Art. 5", "[3, 7]"]
+        );
+    }
+
+    #[test]
+    fn a_capitalized_sentence_start_is_not_a_name() {
+        assert_eq!(spans("Then we met Maria. Today is fine", &[PreservedKind::ProperNouns]), vec!["Maria"]);
+        assert_eq!(spans("We flew to New York", &[PreservedKind::ProperNouns]), vec!["New York"]);
+    }
+
+    #[test]
+    fn occurrences_only_count_whole_tokens() {
+        assert_eq!(occurrences("take 13 pills", "3"), 0);
+        assert_eq!(occurrences("see Art. 50", "Art. 5"), 0);
+        assert_eq!(occurrences("see Art. 5, then Art. 5.", "Art. 5"), 2);
+        assert_eq!(occurrences("($3)", "3"), 1);
+    }
+
+    #[test]
+    fn an_extended_citation_is_put_back() {
+        let outcome = restore("See Art. 5 of the treaty", "See Art. 50 of the treaty.", &[PreservedKind::Citations]);
+        assert_eq!(outcome.text, "See Art. 5 of the treaty.");
+        assert_eq!(outcome.changes.len(), 1);
+        assert_eq!(outcome.changes[0].original, "Art. 50");
+    }
+
+    #[test]
+    fn a_changed_short_number_is_not_taken_as_preserved() {
+        let outcome = restore("Take 3 pills", "Take 13 pills.", &[PreservedKind::Numbers]);
+        // "13" is too far from "3" to put back with confidence, so it is reported instead of passing as kept
+        assert_eq!(outcome.text, "Take 13 pills.");
+        assert_eq!(outcome.conflicts(), 1);
+    }
+
+    #[test]
+    fn a_dropped_number_does_not_overwrite_another() {
+        let outcome = restore("Page 8 and page 18", "Page and page 18.", &[PreservedKind::Numbers]);
+        assert_eq!(outcome.text, "Page and page 18.");
+        assert_eq!(outcome.conflicts(), 1);
+    }
+
+    #[test]
+    fn an_altered_number_is_put_back() {
+        let outcome = restore("The budget is $1,250 for 2024", "The budget is $1,205 for 2024.", &[PreservedKind::Numbers]);
+        assert_eq!(outcome.text, "The budget is $1,250 for 2024.");
+        assert_eq!(outcome.conflicts(), 0);
+    }
+
+    #[test]
+    fn untouched_spans_make_no_changes() {
+        let outcome = restore("please call Maria Lopez at 5", "Please call Maria Lopez at 5.", &[PreservedKind::ProperNouns, PreservedKind::Numbers]);
+        assert_eq!(outcome.text, "Please call Maria Lopez at 5.");
+        assert!(outcome.changes.is_empty());
+    }
+}
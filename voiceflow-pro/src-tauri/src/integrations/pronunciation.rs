@@ -0,0 +1,199 @@
+// Pronunciation Lexicon Module
+// User-defined pronunciations for product names and jargon, applied to text before it is synthesized
+
+use std::collections::HashMap;
+
+use regex::RegexBuilder;
+use serde::{Deserialize, Serialize};
+
+/// Longest pronunciation accepted for one term
+const MAX_PRONUNCIATION_CHARS: usize = 200;
+
+/// How an entry's pronunciation is written.
+/// Phonetic alphabets need SSML; respellings also work with voices that only take plain text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PhoneticAlphabet {
+    Ipa,
+    XSampa,
+    /// Ordinary spelling that sounds right, e.g. "nginx" as "engine x"
+    Respelling,
+}
+
+impl PhoneticAlphabet {
+    fn ssml_name(&self) -> Option<&'static str> {
+        match self {
+            PhoneticAlphabet::Ipa => Some("ipa"),
+            PhoneticAlphabet::XSampa => Some("x-sampa"),
+            PhoneticAlphabet::Respelling => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PronunciationEntry {
+    pub term: String,
+    pub pronunciation: String,
+    pub alphabet: PhoneticAlphabet,
+    /// Match the term only with the same capitalization, e.g. "SAP" but not "sap"
+    #[serde(default)]
+    pub case_sensitive: bool,
+}
+
+/// Lexicons keyed by language code; "en" applies to every English variant, "en-GB" only to British English
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PronunciationSettings {
+    pub lexicons: HashMap<String, Vec<PronunciationEntry>>,
+}
+
+impl PronunciationSettings {
+    /// Entries for `language`, regional entries taking precedence over the base language's
+    pub fn entries_for(&self, language: &str) -> Vec<PronunciationEntry> {
+        let language = language.to_lowercase();
+        let base = language.split(['-', '_']).next().unwrap_or_default().to_string();
+        let mut entries: Vec<PronunciationEntry> = Vec::new();
+        for code in [&language, &base] {
+            let Some(lexicon) = self
+                .lexicons
+                .iter()
+                .find(|(key, _)| key.to_lowercase() == *code)
+                .map(|(_, lexicon)| lexicon)
+            else {
+                continue;
+            };
+            for entry in lexicon {
+                if !entries.iter().any(|known| known.term.eq_ignore_ascii_case(&entry.term)) {
+                    entries.push(entry.clone());
+                }
+            }
+        }
+        entries
+    }
+
+    /// Add an entry, replacing any existing one for the same term
+    pub fn add(&mut self, language: &str, entry: PronunciationEntry) {
+        let lexicon = self.lexicons.entry(language.to_string()).or_default();
+        lexicon.retain(|known| !known.term.eq_ignore_ascii_case(&entry.term));
+        lexicon.push(entry);
+    }
+
+    /// Remove the entry for `term`, returning whether one existed
+    pub fn remove(&mut self, language: &str, term: &str) -> bool {
+        let Some(lexicon) = self.lexicons.get_mut(language) else {
+            return false;
+        };
+        let before = lexicon.len();
+        lexicon.retain(|known| !known.term.eq_ignore_ascii_case(term));
+        let removed = lexicon.len() != before;
+        if lexicon.is_empty() {
+            self.lexicons.remove(language);
+        }
+        removed
+    }
+}
+
+/// A lexicon term spoken on its own so the user can check it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PronunciationPreview {
+    pub term: String,
+    pub language: String,
+    /// The entry that applied, if the term is in the lexicon
+    pub entry: Option<PronunciationEntry>,
+    /// What was sent to the voice after the lexicon was applied
+    pub rendered_text: String,
+    pub audio_base64: String,
+    pub format: String,
+    pub sample_rate: u32,
+}
+
+/// Check an entry before it is saved
+pub fn validate_entry(entry: &PronunciationEntry) -> Result<(), String> {
+    if entry.term.trim().is_empty() {
+        return Err("Pronunciation term cannot be empty".to_string());
+    }
+    if entry.pronunciation.trim().is_empty() {
+        return Err(format!("Pronunciation for '{}' cannot be empty", entry.term));
+    }
+    if entry.pronunciation.chars().count() > MAX_PRONUNCIATION_CHARS {
+        return Err(format!(
+            "Pronunciation for '{}' is longer than {} characters",
+            entry.term, MAX_PRONUNCIATION_CHARS
+        ));
+    }
+    Ok(())
+}
+
+/// Text as SSML-safe markup, lexicon terms wrapped in `<phoneme>` or `<sub>` tags
+pub fn apply_ssml(text: &str, entries: &[PronunciationEntry]) -> String {
+    let mut markup = String::with_capacity(text.len());
+    let mut position = 0;
+    for (start, end, entry) in find_terms(text, entries) {
+        markup.push_str(&escape_xml(&text[position..start]));
+        let term = escape_xml(&text[start..end]);
+        let pronunciation = escape_xml(&entry.pronunciation);
+        match entry.alphabet.ssml_name() {
+            Some(alphabet) => markup.push_str(&format!(
+                "<phoneme alphabet=\"{}\" ph=\"{}\">{}</phoneme>",
+                alphabet, pronunciation, term
+            )),
+            None => markup.push_str(&format!("<sub alias=\"{}\">{}</sub>", pronunciation, term)),
+        }
+        position = end;
+    }
+    markup.push_str(&escape_xml(&text[position..]));
+    markup
+}
+
+/// Text with respelled terms substituted, for voices that do not read SSML
+pub fn apply_plain(text: &str, entries: &[PronunciationEntry]) -> String {
+    let respellings: Vec<PronunciationEntry> = entries
+        .iter()
+        .filter(|entry| entry.alphabet == PhoneticAlphabet::Respelling)
+        .cloned()
+        .collect();
+    let mut spoken = String::with_capacity(text.len());
+    let mut position = 0;
+    for (start, end, entry) in find_terms(text, &respellings) {
+        spoken.push_str(&text[position..start]);
+        spoken.push_str(&entry.pronunciation);
+        position = end;
+    }
+    spoken.push_str(&text[position..]);
+    spoken
+}
+
+/// Whole-word occurrences of lexicon terms, longest terms first so "Visual Studio Code" wins over "Code"
+fn find_terms<'a>(text: &str, entries: &'a [PronunciationEntry]) -> Vec<(usize, usize, &'a PronunciationEntry)> {
+    let mut ordered: Vec<&PronunciationEntry> = entries.iter().filter(|entry| !entry.term.trim().is_empty()).collect();
+    ordered.sort_by_key(|entry| std::cmp::Reverse(entry.term.len()));
+
+    let mut found: Vec<(usize, usize, &PronunciationEntry)> = Vec::new();
+    for entry in ordered {
+        let Ok(pattern) = RegexBuilder::new(&regex::escape(entry.term.trim()))
+            .case_insensitive(!entry.case_sensitive)
+            .build()
+        else {
+            continue;
+        };
+        for occurrence in pattern.find_iter(text) {
+            let (start, end) = (occurrence.start(), occurrence.end());
+            let word_before = text[..start].chars().next_back().map_or(false, char::is_alphanumeric);
+            let word_after = text[end..].chars().next().map_or(false, char::is_alphanumeric);
+            let overlaps = found.iter().any(|(s, e, _)| start < *e && *s < end);
+            if !word_before && !word_after && !overlaps {
+                found.push((start, end, entry));
+            }
+        }
+    }
+    found.sort_by_key(|(start, _, _)| *start);
+    found
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
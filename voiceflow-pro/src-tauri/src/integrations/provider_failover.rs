@@ -0,0 +1,500 @@
+// Provider Failover Module
+// Routes speech-to-text, language model and speech synthesis traffic away from a failing provider and back once it recovers
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use super::ai_ml_core::AIMLError;
+use super::model_registry::{get_model_registry, ModelModality};
+use super::stt_providers::SttProviderKind;
+use crate::error_boundary::{get_error_boundary_registry, CircuitBreakerState};
+use crate::errors::{AppError, ValidationError};
+
+const EVENT_CAPACITY: usize = 32;
+/// Voice fallback served by the operating system's own synthesizer instead of the AI gateway
+pub const SYSTEM_VOICE: &str = "system";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderService {
+    Stt,
+    Llm,
+    Tts,
+}
+
+impl ProviderService {
+    const ALL: [ProviderService; 3] = [ProviderService::Stt, ProviderService::Llm, ProviderService::Tts];
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProviderFailoverSettings {
+    pub enabled: bool,
+    /// Consecutive failures after which a provider is skipped
+    pub failure_threshold: u32,
+    /// How long a skipped provider rests before one request tries it again
+    pub retry_after_secs: u64,
+    /// Tried in order when the configured speech-to-text provider fails
+    pub stt_providers: Vec<SttProviderKind>,
+    /// Tried in order when the configured text model fails
+    pub text_models: Vec<String>,
+    /// Tried in order when the configured voice model fails; they speak with their default voice
+    pub voice_models: Vec<String>,
+    /// Read aloud with the operating system's synthesizer once every voice model has failed,
+    /// so speech still works while the gateway is down
+    pub system_voice: bool,
+}
+
+impl Default for ProviderFailoverSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            failure_threshold: 3,
+            retry_after_secs: 60,
+            stt_providers: Vec::new(),
+            text_models: Vec::new(),
+            voice_models: Vec::new(),
+            system_voice: true,
+        }
+    }
+}
+
+impl ProviderService {
+    fn modality(self) -> ModelModality {
+        match self {
+            ProviderService::Stt => ModelModality::Transcription,
+            ProviderService::Llm => ModelModality::Text,
+            ProviderService::Tts => ModelModality::Speech,
+        }
+    }
+}
+
+impl ProviderFailoverSettings {
+    /// Configured fallbacks the model registry says can stand in for the service
+    fn fallbacks(&self, service: ProviderService) -> Vec<String> {
+        let models = match service {
+            ProviderService::Stt => return self.stt_providers.iter().map(|kind| kind.label().to_string()).collect(),
+            ProviderService::Llm => &self.text_models,
+            ProviderService::Tts => &self.voice_models,
+        };
+        let registry = get_model_registry();
+        let mut fallbacks: Vec<String> = models
+            .iter()
+            .filter(|model| registry.supports(model, service.modality()))
+            .cloned()
+            .collect();
+        if service == ProviderService::Tts && self.system_voice {
+            fallbacks.push(SYSTEM_VOICE.to_string());
+        }
+        fallbacks
+    }
+}
+
+pub fn validate(settings: &ProviderFailoverSettings) -> Result<(), AppError> {
+    let invalid = |message: String| AppError::Validation(ValidationError::InvalidConfigValue(message));
+    if !(1..=20).contains(&settings.failure_threshold) {
+        return Err(invalid(format!(
+            "Failover threshold must be 1-20 failures, got {}",
+            settings.failure_threshold
+        )));
+    }
+    if !(5..=3600).contains(&settings.retry_after_secs) {
+        return Err(invalid(format!(
+            "Failover retry must be 5-3600 seconds, got {}",
+            settings.retry_after_secs
+        )));
+    }
+    if settings.text_models.iter().chain(&settings.voice_models).any(|model| model.trim().is_empty()) {
+        return Err(invalid("Fallback models cannot be empty".to_string()));
+    }
+    let registry = get_model_registry();
+    for model in &settings.text_models {
+        registry.check("a fallback text model", model, ModelModality::Text)?;
+    }
+    for model in &settings.voice_models {
+        registry.check("a fallback voice model", model, ModelModality::Speech)?;
+    }
+    Ok(())
+}
+
+/// Errors that say the provider is at fault, as opposed to the request
+pub trait FailoverError: std::fmt::Display {
+    fn fails_over(&self) -> bool;
+}
+
+impl FailoverError for AppError {
+    fn fails_over(&self) -> bool {
+        // A format, file size or setting one provider refuses may suit the next; empty, overlong or malformed input suits none
+        !matches!(
+            self,
+            AppError::Validation(
+                ValidationError::EmptyInput
+                    | ValidationError::InputTooLong(..)
+                    | ValidationError::InvalidCharacters(_)
+                    | ValidationError::PathTraversal(_)
+            )
+        )
+    }
+}
+
+impl FailoverError for AIMLError {
+    fn fails_over(&self) -> bool {
+        !matches!(
+            self,
+            AIMLError::MissingParameter(_) | AIMLError::ApiError { status: 400 | 413 | 422, .. }
+        )
+    }
+}
+
+/// Sent as "provider-failover" when traffic moves to another provider, or back to the primary
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderFailoverEvent {
+    pub service: ProviderService,
+    pub from: String,
+    pub to: String,
+    pub reason: String,
+    /// Traffic went back to the configured provider
+    pub recovered: bool,
+    pub timestamp: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProviderHealth {
+    pub provider: String,
+    pub consecutive_failures: u32,
+    /// Skipped until it is retried
+    pub skipped: bool,
+    pub retry_in_secs: Option<u64>,
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProviderRoute {
+    pub service: ProviderService,
+    pub primary: Option<String>,
+    /// Provider the last request was served by
+    pub active: Option<String>,
+    pub providers: Vec<ProviderHealth>,
+}
+
+#[derive(Debug, Default)]
+struct Health {
+    consecutive_failures: u32,
+    skipped_at: Option<Instant>,
+    last_error: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct Router {
+    health: HashMap<(ProviderService, String), Health>,
+    primary: HashMap<ProviderService, String>,
+    active: HashMap<ProviderService, String>,
+}
+
+fn router() -> &'static Mutex<Router> {
+    static ROUTER: OnceLock<Mutex<Router>> = OnceLock::new();
+    ROUTER.get_or_init(|| Mutex::new(Router::default()))
+}
+
+fn events() -> &'static broadcast::Sender<ProviderFailoverEvent> {
+    static EVENTS: OnceLock<broadcast::Sender<ProviderFailoverEvent>> = OnceLock::new();
+    EVENTS.get_or_init(|| broadcast::channel(EVENT_CAPACITY).0)
+}
+
+pub fn subscribe() -> broadcast::Receiver<ProviderFailoverEvent> {
+    events().subscribe()
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Whether the error boundary guarding the AI gateway has opened; gateway-backed primaries count as down while it is
+async fn gateway_circuit_open() -> bool {
+    match get_error_boundary_registry().get("ai_ml_api").await {
+        Some(boundary) => boundary.get_circuit_breaker_state().await == CircuitBreakerState::Open,
+        None => false,
+    }
+}
+
+fn gateway_backed(service: ProviderService, provider: &str) -> bool {
+    match service {
+        ProviderService::Stt => provider == SttProviderKind::Whisper.label(),
+        ProviderService::Llm => true,
+        ProviderService::Tts => provider != SYSTEM_VOICE,
+    }
+}
+
+impl Router {
+    /// Providers to try in order: usable ones as configured, then skipped ones as a last resort
+    fn order(
+        &mut self,
+        service: ProviderService,
+        primary: &str,
+        settings: &ProviderFailoverSettings,
+        circuit_open: bool,
+    ) -> Vec<String> {
+        self.primary.insert(service, primary.to_string());
+        let mut chain = vec![primary.to_string()];
+        for fallback in settings.fallbacks(service) {
+            if !chain.contains(&fallback) {
+                chain.push(fallback);
+            }
+        }
+        let retry_after = Duration::from_secs(settings.retry_after_secs);
+        let (usable, skipped): (Vec<String>, Vec<String>) = chain.into_iter().partition(|provider| {
+            if circuit_open && provider == primary && gateway_backed(service, provider) {
+                return false;
+            }
+            // A rested provider gets one request; failing it again starts a new rest
+            self.health
+                .get(&(service, provider.clone()))
+                .and_then(|health| health.skipped_at)
+                .map_or(true, |skipped_at| skipped_at.elapsed() >= retry_after)
+        });
+        usable.into_iter().chain(skipped).collect()
+    }
+
+    fn record_success(&mut self, service: ProviderService, provider: &str) -> Option<ProviderFailoverEvent> {
+        self.health.remove(&(service, provider.to_string()));
+        let primary = self.primary.get(&service).cloned();
+        // Before any request has been served, traffic counts as going to the primary
+        let previous = self.active.insert(service, provider.to_string()).or(primary)?;
+        if previous == provider {
+            return None;
+        }
+        let recovered = self.primary.get(&service).map_or(false, |primary| primary == provider);
+        Some(ProviderFailoverEvent {
+            service,
+            reason: if recovered {
+                format!("{} is healthy again", provider)
+            } else {
+                self.health
+                    .get(&(service, previous.clone()))
+                    .and_then(|health| health.last_error.clone())
+                    .unwrap_or_else(|| format!("{} is unavailable", previous))
+            },
+            from: previous,
+            to: provider.to_string(),
+            recovered,
+            timestamp: now_secs(),
+        })
+    }
+
+    fn record_failure(&mut self, service: ProviderService, provider: &str, error: String, threshold: u32) {
+        let health = self.health.entry((service, provider.to_string())).or_default();
+        health.consecutive_failures += 1;
+        health.last_error = Some(error);
+        if health.consecutive_failures >= threshold {
+            if health.skipped_at.is_none() {
+                log::warn!(
+                    "{:?} provider {} failed {} times in a row; routing around it",
+                    service,
+                    provider,
+                    health.consecutive_failures
+                );
+            }
+            health.skipped_at = Some(Instant::now());
+        }
+    }
+}
+
+/// Run `attempt` with the primary provider, moving down the fallbacks while providers fail.
+/// Errors about the request itself are returned at once, as another provider would refuse it too.
+pub async fn run<T, E, F, Fut>(
+    service: ProviderService,
+    primary: &str,
+    settings: &ProviderFailoverSettings,
+    mut attempt: F,
+) -> Result<T, E>
+where
+    E: FailoverError,
+    F: FnMut(String) -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    if !settings.enabled {
+        return attempt(primary.to_string()).await;
+    }
+    let circuit_open = gateway_circuit_open().await;
+    let order = match router().lock() {
+        Ok(mut router) => router.order(service, primary, settings, circuit_open),
+        Err(_) => vec![primary.to_string()],
+    };
+
+    let mut last_error = None;
+    for provider in order {
+        match attempt(provider.clone()).await {
+            Ok(value) => {
+                let event = router()
+                    .lock()
+                    .ok()
+                    .and_then(|mut router| router.record_success(service, &provider));
+                if let Some(event) = event {
+                    log::info!("{:?} traffic moved from {} to {}: {}", service, event.from, event.to, event.reason);
+                    let _ = events().send(event);
+                }
+                return Ok(value);
+            }
+            Err(e) if e.fails_over() => {
+                log::warn!("{:?} provider {} failed: {}", service, provider, e);
+                if let Ok(mut router) = router().lock() {
+                    router.record_failure(service, &provider, e.to_string(), settings.failure_threshold);
+                }
+                last_error = Some(e);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    // The order always holds the primary, so at least one attempt was made
+    match last_error {
+        Some(e) => Err(e),
+        None => attempt(primary.to_string()).await,
+    }
+}
+
+/// Feed a health check result; a failed check skips the provider right away and a passed one makes it usable again
+pub fn record_health(service: ProviderService, provider: &str, healthy: bool, settings: &ProviderFailoverSettings) {
+    if !settings.enabled {
+        return;
+    }
+    let Ok(mut router) = router().lock() else {
+        return;
+    };
+    let key = (service, provider.to_string());
+    if healthy {
+        router.health.remove(&key);
+    } else {
+        let health = router.health.entry(key).or_default();
+        health.consecutive_failures = health.consecutive_failures.max(settings.failure_threshold);
+        health.skipped_at = Some(Instant::now());
+        health.last_error = Some(format!("{} failed its health check", provider));
+    }
+}
+
+/// Where each service's traffic goes and how its providers are doing
+pub fn routes(settings: &ProviderFailoverSettings) -> Vec<ProviderRoute> {
+    let Ok(router) = router().lock() else {
+        return Vec::new();
+    };
+    let retry_after = Duration::from_secs(settings.retry_after_secs);
+    ProviderService::ALL
+        .iter()
+        .map(|&service| {
+            let primary = router.primary.get(&service).cloned();
+            let mut names: Vec<String> = primary.iter().cloned().collect();
+            for name in settings.fallbacks(service) {
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+            let providers = names
+                .into_iter()
+                .map(|provider| {
+                    let health = router.health.get(&(service, provider.clone()));
+                    let skipped_at = health.and_then(|health| health.skipped_at);
+                    ProviderHealth {
+                        consecutive_failures: health.map_or(0, |health| health.consecutive_failures),
+                        skipped: skipped_at.map_or(false, |at| at.elapsed() < retry_after),
+                        retry_in_secs: skipped_at.map(|at| retry_after.saturating_sub(at.elapsed()).as_secs()),
+                        last_error: health.and_then(|health| health.last_error.clone()),
+                        provider,
+                    }
+                })
+                .collect();
+            ProviderRoute {
+                service,
+                primary,
+                active: router.active.get(&service).cloned(),
+                providers,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn provider_specific_rejections_fail_over() {
+        let format = AppError::Validation(ValidationError::InvalidFileType("webm".to_string()));
+        assert!(format.fails_over());
+        assert!(AppError::Network("reset".to_string()).fails_over());
+        assert!(!AppError::Validation(ValidationError::EmptyInput).fails_over());
+        assert!(!AppError::Validation(ValidationError::InputTooLong(10, 5)).fails_over());
+    }
+
+    #[test]
+    fn system_voice_is_the_last_voice_fallback() {
+        let settings = ProviderFailoverSettings::default();
+        assert_eq!(settings.fallbacks(ProviderService::Tts), vec![SYSTEM_VOICE.to_string()]);
+        assert!(!settings.fallbacks(ProviderService::Llm).contains(&SYSTEM_VOICE.to_string()));
+
+        let without = ProviderFailoverSettings {
+            system_voice: false,
+            ..ProviderFailoverSettings::default()
+        };
+        assert!(without.fallbacks(ProviderService::Tts).is_empty());
+    }
+
+    #[test]
+    fn only_gateway_providers_follow_the_gateway_circuit() {
+        assert!(gateway_backed(ProviderService::Llm, "gpt-4o"));
+        assert!(gateway_backed(ProviderService::Tts, "openai/tts-1"));
+        assert!(!gateway_backed(ProviderService::Tts, SYSTEM_VOICE));
+        assert!(gateway_backed(ProviderService::Stt, SttProviderKind::Whisper.label()));
+        assert!(!gateway_backed(ProviderService::Stt, SttProviderKind::Deepgram.label()));
+
+        let mut router = Router::default();
+        let order = router.order(ProviderService::Tts, "voice-model", &ProviderFailoverSettings::default(), true);
+        assert_eq!(order, vec![SYSTEM_VOICE.to_string(), "voice-model".to_string()]);
+    }
+
+    #[test]
+    fn skipped_provider_goes_to_the_end() {
+        let settings = ProviderFailoverSettings {
+            failure_threshold: 1,
+            ..ProviderFailoverSettings::default()
+        };
+        let mut router = Router::default();
+        router.record_failure(ProviderService::Tts, "voice-model", "down".to_string(), settings.failure_threshold);
+        let order = router.order(ProviderService::Tts, "voice-model", &settings, false);
+        assert_eq!(order, vec![SYSTEM_VOICE.to_string(), "voice-model".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn failing_voice_model_falls_back_to_the_system_voice() {
+        let settings = ProviderFailoverSettings::default();
+        let result: Result<String, AIMLError> =
+            run(ProviderService::Tts, "failover-test-voice", &settings, |provider| async move {
+                if provider == SYSTEM_VOICE {
+                    Ok(provider)
+                } else {
+                    Err(AIMLError::ServiceUnavailable("down".to_string()))
+                }
+            })
+            .await;
+        assert_eq!(result.unwrap(), SYSTEM_VOICE);
+    }
+
+    #[tokio::test]
+    async fn request_errors_are_not_retried_elsewhere() {
+        let settings = ProviderFailoverSettings::default();
+        let mut attempts = Vec::new();
+        let result: Result<(), AppError> = run(ProviderService::Tts, "failover-test-empty", &settings, |provider| {
+            attempts.push(provider);
+            async { Err(AppError::Validation(ValidationError::EmptyInput)) }
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(attempts, vec!["failover-test-empty".to_string()]);
+    }
+}
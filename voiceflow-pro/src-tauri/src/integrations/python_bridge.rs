@@ -0,0 +1,312 @@
+// Python Bridge Module
+// Runs a Python helper as a tokio child process and exchanges line-delimited JSON with it, with heartbeats and a bounded queue
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::sync::{mpsc, oneshot, watch, Mutex};
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+/// Environment variable naming the helper script; without it the processor stays in-process
+pub const SCRIPT_ENV: &str = "VOICEFLOW_TEXT_PROCESSOR";
+/// Interpreter override, e.g. a virtualenv's python
+pub const PYTHON_ENV: &str = "VOICEFLOW_PYTHON";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PythonBridgeConfig {
+    pub program: String,
+    pub args: Vec<String>,
+    /// Requests that may wait to be written or for their reply before new ones are turned away
+    pub queue_capacity: usize,
+    pub request_timeout_ms: u64,
+    pub heartbeat_interval_ms: u64,
+    /// A helper that has not answered a ping for this long is considered hung and stopped
+    pub heartbeat_timeout_ms: u64,
+}
+
+impl PythonBridgeConfig {
+    /// Helper configured through `VOICEFLOW_TEXT_PROCESSOR`, if any
+    pub fn from_env() -> Option<Self> {
+        let script = std::env::var(SCRIPT_ENV).ok().filter(|script| !script.is_empty())?;
+        let program = std::env::var(PYTHON_ENV)
+            .ok()
+            .filter(|program| !program.is_empty())
+            .unwrap_or_else(|| if cfg!(target_os = "windows") { "python" } else { "python3" }.to_string());
+        Some(Self {
+            program,
+            // Unbuffered, so each reply line reaches us as soon as it is printed
+            args: vec!["-u".to_string(), script],
+            queue_capacity: 32,
+            request_timeout_ms: 10_000,
+            heartbeat_interval_ms: 5_000,
+            heartbeat_timeout_ms: 15_000,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BridgeHealth {
+    Starting,
+    Ready,
+    /// Stopped after missing heartbeats, exiting or closing its pipes
+    Down,
+}
+
+/// One line from the helper: a reply carries the id of its request, a pong only its type
+#[derive(Debug, Deserialize)]
+struct Reply {
+    #[serde(default)]
+    id: Option<String>,
+    #[serde(rename = "type", default)]
+    kind: Option<String>,
+    #[serde(default)]
+    result: Option<Value>,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+type Pending = Arc<Mutex<HashMap<String, oneshot::Sender<Result<Value, String>>>>>;
+
+/// Handle to a running helper. Requests are written as `{"id", "type", "payload"}` lines and answered
+/// with `{"id", "result"}` or `{"id", "error"}`; `{"type": "ping"}` is answered with `{"type": "pong"}`
+pub struct PythonBridge {
+    config: PythonBridgeConfig,
+    outgoing: mpsc::Sender<String>,
+    pending: Pending,
+    health: watch::Receiver<BridgeHealth>,
+    child: Arc<Mutex<Child>>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl PythonBridge {
+    /// Start the helper and wait for its first pong
+    pub async fn spawn(config: PythonBridgeConfig) -> Result<Self, String> {
+        let mut child = Command::new(&config.program)
+            .args(&config.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| format!("Could not start {}: {}", config.program, e))?;
+        let stdin = child.stdin.take().ok_or("Helper stdin unavailable")?;
+        let stdout = child.stdout.take().ok_or("Helper stdout unavailable")?;
+        let stderr = child.stderr.take();
+
+        let (outgoing, queued) = mpsc::channel(config.queue_capacity.max(1));
+        let pending: Pending = Arc::new(Mutex::new(HashMap::new()));
+        let (health_sender, health) = watch::channel(BridgeHealth::Starting);
+        let last_pong = Arc::new(Mutex::new(Instant::now()));
+        let child = Arc::new(Mutex::new(child));
+
+        let mut tasks = vec![
+            tokio::spawn(write_lines(stdin, queued)),
+            tokio::spawn(read_lines(stdout, pending.clone(), last_pong.clone(), health_sender.clone())),
+            tokio::spawn(heartbeat(
+                outgoing.clone(),
+                pending.clone(),
+                last_pong,
+                health_sender,
+                child.clone(),
+                Duration::from_millis(config.heartbeat_interval_ms),
+                Duration::from_millis(config.heartbeat_timeout_ms),
+            )),
+        ];
+        if let Some(stderr) = stderr {
+            tasks.push(tokio::spawn(async move {
+                let mut lines = BufReader::new(stderr).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    log::debug!("Text processor: {}", line);
+                }
+            }));
+        }
+
+        let bridge = Self {
+            config,
+            outgoing,
+            pending,
+            health,
+            child,
+            tasks,
+        };
+        bridge.wait_ready().await?;
+        Ok(bridge)
+    }
+
+    async fn wait_ready(&self) -> Result<(), String> {
+        let mut health = self.health.clone();
+        let ready = tokio::time::timeout(Duration::from_millis(self.config.heartbeat_timeout_ms), async {
+            loop {
+                match *health.borrow() {
+                    BridgeHealth::Ready => return Ok(()),
+                    BridgeHealth::Down => return Err("Text processor exited during startup".to_string()),
+                    BridgeHealth::Starting => {}
+                }
+                if health.changed().await.is_err() {
+                    return Err("Text processor exited during startup".to_string());
+                }
+            }
+        });
+        ready
+            .await
+            .unwrap_or_else(|_| Err("Text processor did not answer its first ping".to_string()))
+    }
+
+    pub fn health(&self) -> BridgeHealth {
+        *self.health.borrow()
+    }
+
+    /// Send one request and wait for its reply. A full queue turns the request away at once instead of stalling the caller
+    pub async fn request(&self, kind: &str, payload: Value) -> Result<Value, String> {
+        if self.health() == BridgeHealth::Down {
+            return Err("Text processor is not running".to_string());
+        }
+        let id = Uuid::new_v4().to_string();
+        let (sender, receiver) = oneshot::channel();
+        {
+            let mut pending = self.pending.lock().await;
+            if pending.len() >= self.config.queue_capacity {
+                return Err(format!(
+                    "Text processor is busy with {} requests; try again shortly",
+                    pending.len()
+                ));
+            }
+            pending.insert(id.clone(), sender);
+        }
+
+        let line = json!({ "id": id, "type": kind, "payload": payload }).to_string();
+        if let Err(e) = self.outgoing.try_send(line) {
+            self.pending.lock().await.remove(&id);
+            return Err(match e {
+                mpsc::error::TrySendError::Full(_) => "Text processor queue is full; try again shortly".to_string(),
+                mpsc::error::TrySendError::Closed(_) => "Text processor is not running".to_string(),
+            });
+        }
+
+        match tokio::time::timeout(Duration::from_millis(self.config.request_timeout_ms), receiver).await {
+            Ok(Ok(reply)) => reply,
+            Ok(Err(_)) => Err("Text processor stopped before replying".to_string()),
+            Err(_) => {
+                self.pending.lock().await.remove(&id);
+                Err(format!("Text processor did not reply within {} ms", self.config.request_timeout_ms))
+            }
+        }
+    }
+
+    /// Ask the helper to exit, and stop it if it does not within a second
+    pub async fn shutdown(self) {
+        let _ = self.outgoing.try_send(json!({ "type": "shutdown" }).to_string());
+        let mut child = self.child.lock().await;
+        if tokio::time::timeout(Duration::from_secs(1), child.wait()).await.is_err() {
+            let _ = child.kill().await;
+        }
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+impl Drop for PythonBridge {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+async fn write_lines(mut stdin: ChildStdin, mut queued: mpsc::Receiver<String>) {
+    while let Some(line) = queued.recv().await {
+        let written = async {
+            stdin.write_all(line.as_bytes()).await?;
+            stdin.write_all(b"\n").await?;
+            stdin.flush().await
+        };
+        if let Err(e) = written.await {
+            log::warn!("Text processor stdin closed: {}", e);
+            return;
+        }
+    }
+}
+
+async fn read_lines(
+    stdout: ChildStdout,
+    pending: Pending,
+    last_pong: Arc<Mutex<Instant>>,
+    health: watch::Sender<BridgeHealth>,
+) {
+    let mut lines = BufReader::new(stdout).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let reply: Reply = match serde_json::from_str(&line) {
+            Ok(reply) => reply,
+            Err(e) => {
+                log::warn!("Text processor sent an unreadable line ({}): {}", e, line);
+                continue;
+            }
+        };
+        if reply.kind.as_deref() == Some("pong") {
+            *last_pong.lock().await = Instant::now();
+            health.send_if_modified(|health| {
+                let starting = *health == BridgeHealth::Starting;
+                if starting {
+                    *health = BridgeHealth::Ready;
+                }
+                starting
+            });
+            continue;
+        }
+        let Some(id) = reply.id else {
+            continue;
+        };
+        if let Some(sender) = pending.lock().await.remove(&id) {
+            let outcome = match reply.error {
+                Some(error) => Err(error),
+                None => Ok(reply.result.unwrap_or(Value::Null)),
+            };
+            let _ = sender.send(outcome);
+        }
+    }
+    let _ = health.send(BridgeHealth::Down);
+    fail_pending(&pending, "Text processor closed its output").await;
+}
+
+async fn heartbeat(
+    outgoing: mpsc::Sender<String>,
+    pending: Pending,
+    last_pong: Arc<Mutex<Instant>>,
+    health: watch::Sender<BridgeHealth>,
+    child: Arc<Mutex<Child>>,
+    interval: Duration,
+    timeout: Duration,
+) {
+    let ping = json!({ "type": "ping" }).to_string();
+    // The first ping goes out at once so startup does not wait a whole interval
+    let mut ticks = tokio::time::interval(interval);
+    loop {
+        ticks.tick().await;
+        if *health.borrow() == BridgeHealth::Down {
+            return;
+        }
+        if last_pong.lock().await.elapsed() > timeout {
+            log::warn!("Text processor missed its heartbeats for {:?}; stopping it", timeout);
+            let _ = health.send(BridgeHealth::Down);
+            let _ = child.lock().await.kill().await;
+            fail_pending(&pending, "Text processor stopped responding").await;
+            return;
+        }
+        // A full queue already proves the writer is busy; the next tick tries again
+        let _ = outgoing.try_send(ping.clone());
+    }
+}
+
+async fn fail_pending(pending: &Pending, reason: &str) {
+    for (_, sender) in pending.lock().await.drain() {
+        let _ = sender.send(Err(reason.to_string()));
+    }
+}
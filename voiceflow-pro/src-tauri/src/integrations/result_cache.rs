@@ -0,0 +1,610 @@
+// Result Cache Module
+// Disk-backed LRU caches for enhancement, translation and synthesis results, each within its own size budget
+
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use lru::LruCache;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::encryption::get_data_vault;
+use crate::errors::{AppError, ValidationError};
+use crate::storage::{data_path, DataDir};
+
+/// Subdirectory of the cache directory holding one directory per cache kind
+const RESULTS_DIR: &str = "results";
+const MAX_BUDGET_MB: u64 = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CacheKind {
+    Enhancement,
+    Translation,
+    Synthesis,
+}
+
+impl CacheKind {
+    pub const ALL: [CacheKind; 3] = [CacheKind::Enhancement, CacheKind::Translation, CacheKind::Synthesis];
+
+    fn dir_name(self) -> &'static str {
+        match self {
+            CacheKind::Enhancement => "enhancement",
+            CacheKind::Translation => "translation",
+            CacheKind::Synthesis => "synthesis",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResultCacheSettings {
+    /// Keep results on disk so they survive a restart. Off by default, since entries hold dictated text,
+    /// translations and speech; they are sealed with the data key when encryption is on, and never written in
+    /// privacy mode
+    pub persist: bool,
+    pub enhancement_mb: u64,
+    pub translation_mb: u64,
+    /// Audio is the costliest result to regenerate and the largest to keep
+    pub synthesis_mb: u64,
+}
+
+impl Default for ResultCacheSettings {
+    fn default() -> Self {
+        Self {
+            persist: false,
+            enhancement_mb: 16,
+            translation_mb: 16,
+            synthesis_mb: 256,
+        }
+    }
+}
+
+impl ResultCacheSettings {
+    fn budget_bytes(&self, kind: CacheKind) -> u64 {
+        let mb = match kind {
+            CacheKind::Enhancement => self.enhancement_mb,
+            CacheKind::Translation => self.translation_mb,
+            CacheKind::Synthesis => self.synthesis_mb,
+        };
+        mb * 1024 * 1024
+    }
+}
+
+/// Hit, miss and size figures for one cache
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheStats {
+    pub kind: CacheKind,
+    pub memory_entries: usize,
+    pub disk_entries: usize,
+    pub disk_bytes: u64,
+    pub budget_bytes: u64,
+    /// Served from memory
+    pub hits: u64,
+    /// Served from disk after a restart or memory eviction
+    pub disk_hits: u64,
+    pub misses: u64,
+    pub hit_rate: f64,
+}
+
+static SETTINGS: OnceLock<Mutex<ResultCacheSettings>> = OnceLock::new();
+
+fn settings() -> std::sync::MutexGuard<'static, ResultCacheSettings> {
+    let settings = SETTINGS.get_or_init(|| Mutex::new(ResultCacheSettings::default()));
+    match settings.lock() {
+        Ok(settings) => settings,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+/// Apply new budgets; caches over a smaller budget shrink on their next write
+pub fn configure(new_settings: &ResultCacheSettings, privacy_mode: bool) {
+    *settings() = effective(new_settings, privacy_mode);
+}
+
+/// Privacy mode keeps results in memory only, whatever the cache settings say
+fn effective(settings: &ResultCacheSettings, privacy_mode: bool) -> ResultCacheSettings {
+    ResultCacheSettings {
+        persist: settings.persist && !privacy_mode,
+        ..settings.clone()
+    }
+}
+
+pub fn validate(settings: &ResultCacheSettings) -> Result<(), AppError> {
+    for kind in CacheKind::ALL {
+        let mb = settings.budget_bytes(kind) / (1024 * 1024);
+        if mb > MAX_BUDGET_MB {
+            return Err(AppError::Validation(ValidationError::InvalidConfigValue(format!(
+                "The {} cache budget must be at most {} MB, got {}",
+                kind.dir_name(),
+                MAX_BUDGET_MB,
+                mb
+            ))));
+        }
+    }
+    Ok(())
+}
+
+/// A cached result; large binary payloads can be stored beside the JSON rather than inside it
+pub trait CacheValue: Serialize + DeserializeOwned + Clone {
+    fn take_blob(&mut self) -> Option<Vec<u8>> {
+        None
+    }
+
+    fn restore_blob(&mut self, _blob: Vec<u8>) {}
+}
+
+#[derive(Serialize)]
+struct EnvelopeRef<'a, V> {
+    key: &'a str,
+    namespace: &'a str,
+    value: &'a V,
+}
+
+#[derive(Deserialize)]
+struct Envelope<V> {
+    key: String,
+    namespace: String,
+    value: V,
+}
+
+/// In-memory LRU in front of an LRU directory of result files
+#[derive(Debug)]
+pub struct PersistentCache<V> {
+    kind: CacheKind,
+    memory: LruCache<String, V>,
+    /// Entry files by stem, least recently used first, with their size on disk
+    disk: LruCache<String, u64>,
+    disk_bytes: u64,
+    /// Directory the disk index was read from; switching profiles changes it
+    dir: Option<PathBuf>,
+    /// Fingerprint of the settings results depend on, such as the pronunciation lexicon
+    namespace: String,
+    hits: u64,
+    disk_hits: u64,
+    misses: u64,
+}
+
+impl<V: CacheValue> PersistentCache<V> {
+    pub fn new(kind: CacheKind, capacity: usize) -> Self {
+        Self {
+            kind,
+            memory: LruCache::new(NonZeroUsize::new(capacity.max(1)).unwrap_or(NonZeroUsize::MIN)),
+            disk: LruCache::unbounded(),
+            disk_bytes: 0,
+            dir: None,
+            namespace: String::new(),
+            hits: 0,
+            disk_hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Switch to results made under other settings. Memory is dropped; entries on disk stay for when the
+    /// settings change back and otherwise age out under the budget
+    pub fn set_namespace(&mut self, context: &impl Serialize) {
+        let namespace = hex_digest(canonical_json(context, &[]).as_bytes());
+        if namespace != self.namespace {
+            self.namespace = namespace;
+            self.memory.clear();
+        }
+    }
+
+    pub async fn get(&mut self, key: &str) -> Option<V> {
+        if let Some(value) = self.memory.get(key) {
+            self.hits += 1;
+            return Some(value.clone());
+        }
+        if settings().persist {
+            if let Some(dir) = self.disk_dir().await {
+                let stem = self.stem(key);
+                if self.disk.contains(&stem) {
+                    match read_entry::<V>(&dir, &stem).await {
+                        Some(entry) if entry.key == key && entry.namespace == self.namespace => {
+                            self.disk.promote(&stem);
+                            self.disk_hits += 1;
+                            self.memory.put(key.to_string(), entry.value.clone());
+                            return Some(entry.value);
+                        }
+                        _ => self.forget(&dir, &stem).await,
+                    }
+                }
+            }
+        }
+        self.misses += 1;
+        None
+    }
+
+    pub async fn put(&mut self, key: String, value: V) {
+        let settings = settings().clone();
+        if settings.persist {
+            if let Some(dir) = self.disk_dir().await {
+                self.write(&dir, &key, &value, settings.budget_bytes(self.kind)).await;
+            }
+        }
+        self.memory.put(key, value);
+    }
+
+    /// Read the disk index and fill memory with the most recent entries made under the current settings
+    pub async fn warm_load(&mut self) -> usize {
+        if !settings().persist {
+            return 0;
+        }
+        let Some(dir) = self.disk_dir().await else {
+            return 0;
+        };
+        let recent: Vec<String> = self
+            .disk
+            .iter()
+            .take(self.memory.cap().get())
+            .map(|(stem, _)| stem.clone())
+            .collect();
+        let mut loaded = 0;
+        // Oldest first, so the most recent entry ends up most recently used in memory too
+        for stem in recent.iter().rev() {
+            match read_entry::<V>(&dir, stem).await {
+                Some(entry) if entry.namespace == self.namespace => {
+                    self.memory.put(entry.key, entry.value);
+                    loaded += 1;
+                }
+                Some(_) => {}
+                None => self.forget(&dir, stem).await,
+            }
+        }
+        loaded
+    }
+
+    /// Remove every entry from memory and disk, returning how many were removed
+    pub async fn clear(&mut self) -> usize {
+        let dir = self.disk_dir().await;
+        let removed = self.memory.len().max(self.disk.len());
+        self.memory.clear();
+        self.disk.clear();
+        self.disk_bytes = 0;
+        if let Some(dir) = dir {
+            if let Err(e) = tokio::fs::remove_dir_all(&dir).await {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    log::warn!("Failed to clear {}: {}", dir.display(), e);
+                }
+            }
+        }
+        removed
+    }
+
+    /// Entries held in memory
+    pub fn len(&self) -> usize {
+        self.memory.len()
+    }
+
+    pub fn resize(&mut self, capacity: NonZeroUsize) {
+        self.memory.resize(capacity);
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let lookups = self.hits + self.disk_hits + self.misses;
+        CacheStats {
+            kind: self.kind,
+            memory_entries: self.memory.len(),
+            disk_entries: self.disk.len(),
+            disk_bytes: self.disk_bytes,
+            budget_bytes: settings().budget_bytes(self.kind),
+            hits: self.hits,
+            disk_hits: self.disk_hits,
+            misses: self.misses,
+            hit_rate: if lookups == 0 {
+                0.0
+            } else {
+                (self.hits + self.disk_hits) as f64 / lookups as f64
+            },
+        }
+    }
+
+    fn stem(&self, key: &str) -> String {
+        hex_digest(format!("{}\0{}", self.namespace, key).as_bytes())
+    }
+
+    /// This cache's directory for the active profile, re-reading the index when it changed
+    async fn disk_dir(&mut self) -> Option<PathBuf> {
+        let dir = data_path(DataDir::Cache)
+            .ok()?
+            .join(RESULTS_DIR)
+            .join(self.kind.dir_name());
+        if self.dir.as_ref() != Some(&dir) {
+            self.scan(&dir).await;
+            self.dir = Some(dir.clone());
+        }
+        Some(dir)
+    }
+
+    async fn scan(&mut self, dir: &Path) {
+        self.disk.clear();
+        self.disk_bytes = 0;
+        let Ok(mut entries) = tokio::fs::read_dir(dir).await else {
+            return;
+        };
+        let mut found = Vec::new();
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                continue;
+            }
+            let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()).map(str::to_string) else {
+                continue;
+            };
+            let Ok(metadata) = entry.metadata().await else {
+                continue;
+            };
+            let blob_size = tokio::fs::metadata(dir.join(format!("{}.bin", stem)))
+                .await
+                .map_or(0, |metadata| metadata.len());
+            let modified = metadata.modified().unwrap_or(std::time::UNIX_EPOCH);
+            found.push((modified, stem, metadata.len() + blob_size));
+        }
+        found.sort();
+        for (_, stem, size) in found {
+            self.disk_bytes += size;
+            self.disk.put(stem, size);
+        }
+        log::debug!(
+            "{} cache: {} entries, {} bytes on disk",
+            self.kind.dir_name(),
+            self.disk.len(),
+            self.disk_bytes
+        );
+    }
+
+    async fn write(&mut self, dir: &Path, key: &str, value: &V, budget: u64) {
+        let stem = self.stem(key);
+        let mut stored = value.clone();
+        let blob = stored.take_blob();
+        let envelope = EnvelopeRef {
+            key,
+            namespace: &self.namespace,
+            value: &stored,
+        };
+        let vault = get_data_vault();
+        let sealed = serde_json::to_vec(&envelope)
+            .map_err(AppError::from)
+            .and_then(|json| vault.seal(&json))
+            .and_then(|json| Ok((json, blob.map(|blob| vault.seal(&blob)).transpose()?)));
+        let (json, blob) = match sealed {
+            Ok(sealed) => sealed,
+            Err(e) => {
+                log::warn!("Failed to store {} cache entry: {}", self.kind.dir_name(), e);
+                return;
+            }
+        };
+        let size = json.len() as u64 + blob.as_ref().map_or(0, |blob| blob.len() as u64);
+        if let Some(previous) = self.disk.pop(&stem) {
+            self.disk_bytes = self.disk_bytes.saturating_sub(previous);
+        }
+        if size > budget {
+            return;
+        }
+        while self.disk_bytes + size > budget {
+            let Some((evicted, evicted_size)) = self.disk.pop_lru() else {
+                break;
+            };
+            self.disk_bytes = self.disk_bytes.saturating_sub(evicted_size);
+            remove_entry_files(dir, &evicted).await;
+        }
+
+        if let Err(e) = tokio::fs::create_dir_all(dir).await {
+            log::warn!("Failed to create {}: {}", dir.display(), e);
+            return;
+        }
+        // The JSON file marks a complete entry, so the blob goes first
+        if let Some(blob) = blob {
+            if let Err(e) = write_atomically(&dir.join(format!("{}.bin", stem)), &blob).await {
+                log::warn!("Failed to write {} cache entry: {}", self.kind.dir_name(), e);
+                return;
+            }
+        }
+        if let Err(e) = write_atomically(&dir.join(format!("{}.json", stem)), &json).await {
+            log::warn!("Failed to write {} cache entry: {}", self.kind.dir_name(), e);
+            remove_entry_files(dir, &stem).await;
+            return;
+        }
+        self.disk.put(stem, size);
+        self.disk_bytes += size;
+    }
+
+    /// Drop an unreadable or mismatched entry from the index and the disk
+    async fn forget(&mut self, dir: &Path, stem: &str) {
+        if let Some(size) = self.disk.pop(stem) {
+            self.disk_bytes = self.disk_bytes.saturating_sub(size);
+        }
+        remove_entry_files(dir, stem).await;
+    }
+}
+
+/// Cache key for a request: a digest of the model and the request's canonical JSON. `ignored` lists JSON
+/// pointers (e.g. "/id") of fields that differ between calls without changing the result; everything else counts
+pub fn request_key(model: &str, request: &impl Serialize, ignored: &[&str]) -> String {
+    hex_digest(format!("{}\0{}", model, canonical_json(request, ignored)).as_bytes())
+}
+
+/// JSON with object keys sorted at every level, so equal values always serialize the same way
+fn canonical_json(value: &impl Serialize, ignored: &[&str]) -> String {
+    let mut value = serde_json::to_value(value).unwrap_or(Value::Null);
+    for pointer in ignored {
+        let (parent, field) = pointer.rsplit_once('/').unwrap_or(("", pointer));
+        if let Some(Value::Object(fields)) = value.pointer_mut(parent) {
+            fields.remove(field);
+        }
+    }
+    let mut out = String::new();
+    write_canonical(&value, &mut out);
+    out
+}
+
+fn write_canonical(value: &Value, out: &mut String) {
+    match value {
+        Value::Object(fields) => {
+            let mut keys: Vec<&String> = fields.keys().collect();
+            keys.sort();
+            out.push('{');
+            for (i, key) in keys.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&Value::String(key.clone()).to_string());
+                out.push(':');
+                write_canonical(&fields[key], out);
+            }
+            out.push('}');
+        }
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical(item, out);
+            }
+            out.push(']');
+        }
+        other => out.push_str(&other.to_string()),
+    }
+}
+
+async fn read_entry<V: CacheValue>(dir: &Path, stem: &str) -> Option<Envelope<V>> {
+    let vault = get_data_vault();
+    let json = tokio::fs::read(dir.join(format!("{}.json", stem))).await.ok()?;
+    let mut entry: Envelope<V> = serde_json::from_slice(&vault.open(json).ok()?).ok()?;
+    match tokio::fs::read(dir.join(format!("{}.bin", stem))).await {
+        Ok(blob) => entry.value.restore_blob(vault.open(blob).ok()?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(_) => return None,
+    }
+    Some(entry)
+}
+
+async fn write_atomically(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let temp = path.with_extension("tmp");
+    tokio::fs::write(&temp, contents).await?;
+    tokio::fs::rename(&temp, path).await
+}
+
+async fn remove_entry_files(dir: &Path, stem: &str) {
+    let _ = tokio::fs::remove_file(dir.join(format!("{}.json", stem))).await;
+    let _ = tokio::fs::remove_file(dir.join(format!("{}.bin", stem))).await;
+}
+
+fn hex_digest(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .take(16)
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Pointers of the fields in `request` that can change without changing `key`. Each leaf is altered in turn and
+/// read back into `T`; changes `T` cannot represent, such as an unknown enum variant, are skipped
+#[cfg(test)]
+pub(crate) fn fields_outside_key<T: Serialize + DeserializeOwned>(request: &T, key: impl Fn(&T) -> String) -> Vec<String> {
+    let original = serde_json::to_value(request).unwrap_or(Value::Null);
+    let base = key(request);
+    let mut outside = Vec::new();
+    for pointer in leaf_pointers(&original, String::new()) {
+        let Some(leaf) = original.pointer(&pointer) else {
+            continue;
+        };
+        let changed = alterations(leaf).into_iter().find_map(|altered| {
+            let mut changed = original.clone();
+            *changed.pointer_mut(&pointer)? = altered;
+            serde_json::from_value::<T>(changed).ok()
+        });
+        if changed.map_or(false, |changed| key(&changed) == base) {
+            outside.push(pointer);
+        }
+    }
+    outside
+}
+
+#[cfg(test)]
+fn leaf_pointers(value: &Value, prefix: String) -> Vec<String> {
+    match value {
+        Value::Object(fields) if !fields.is_empty() => fields
+            .iter()
+            .flat_map(|(name, field)| {
+                leaf_pointers(field, format!("{}/{}", prefix, name.replace('~', "~0").replace('/', "~1")))
+            })
+            .collect(),
+        Value::Array(items) if !items.is_empty() => items
+            .iter()
+            .enumerate()
+            .flat_map(|(index, item)| leaf_pointers(item, format!("{}/{}", prefix, index)))
+            .collect(),
+        _ => vec![prefix],
+    }
+}
+
+/// Other values a leaf could take, tried in order until one fits the field's type
+#[cfg(test)]
+fn alterations(value: &Value) -> Vec<Value> {
+    use serde_json::json;
+    match value {
+        Value::Bool(flag) => vec![json!(!flag)],
+        Value::Number(number) => match (number.as_u64(), number.as_i64()) {
+            (Some(n), _) => vec![json!(n + 1)],
+            (None, Some(n)) => vec![json!(n + 1)],
+            _ => vec![json!(number.as_f64().unwrap_or(0.0) + 0.5)],
+        },
+        Value::String(text) => vec![json!(format!("{}x", text))],
+        Value::Null => vec![json!("x"), json!(1), json!(true)],
+        Value::Array(_) => vec![json!(["x"]), json!([1])],
+        Value::Object(_) => vec![json!({ "x": "x" }), json!({ "x": 1 })],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn request_key_ignores_map_order_and_listed_fields() {
+        let mut first = HashMap::new();
+        let mut second = HashMap::new();
+        for (key, value) in [("a", 1), ("b", 2), ("c", 3)] {
+            first.insert(key, value);
+        }
+        for (key, value) in [("c", 3), ("b", 2), ("a", 1)] {
+            second.insert(key, value);
+        }
+        assert_eq!(request_key("m", &first, &[]), request_key("m", &second, &[]));
+
+        let one = serde_json::json!({ "id": "1", "text": "hi", "meta": { "at": 1 } });
+        let two = serde_json::json!({ "id": "2", "text": "hi", "meta": { "at": 2 } });
+        assert_ne!(request_key("m", &one, &["/id"]), request_key("m", &two, &["/id"]));
+        assert_eq!(
+            request_key("m", &one, &["/id", "/meta/at"]),
+            request_key("m", &two, &["/id", "/meta/at"])
+        );
+    }
+
+    #[test]
+    fn request_key_depends_on_model_and_every_field() {
+        let request = serde_json::json!({ "text": "hi", "options": { "formal": true } });
+        let changed = serde_json::json!({ "text": "hi", "options": { "formal": false } });
+        assert_ne!(request_key("a", &request, &[]), request_key("b", &request, &[]));
+        assert_ne!(request_key("a", &request, &[]), request_key("a", &changed, &[]));
+        assert_eq!(request_key("a", &request, &[]), request_key("a", &request.clone(), &[]));
+    }
+
+    #[test]
+    fn privacy_mode_keeps_results_off_disk() {
+        let persisted = ResultCacheSettings {
+            persist: true,
+            ..ResultCacheSettings::default()
+        };
+        assert!(!ResultCacheSettings::default().persist);
+        assert!(effective(&persisted, false).persist);
+        assert!(!effective(&persisted, true).persist);
+        assert_eq!(effective(&persisted, true).synthesis_mb, persisted.synthesis_mb);
+    }
+}
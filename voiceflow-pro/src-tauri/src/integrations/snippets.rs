@@ -0,0 +1,236 @@
+// Snippet Library Module
+// Named text templates inserted by voice ("insert signature"), with placeholders filled from variables or by AI
+
+use std::collections::HashMap;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::ai_ml_api::AIMLAPIGateway;
+
+const MAX_SNIPPET_CHARS: usize = 20000;
+/// Placeholders prefixed with this are written by the model from the surrounding dictation
+const AI_PREFIX: &str = "ai:";
+const AI_FILL_PROMPT: &str = "You fill placeholders in a text template. The user message is JSON with the recent dictation as \"context\" and the placeholders to write as \"placeholders\", each a short instruction. Reply with only a JSON object mapping each placeholder exactly as given to the text that should replace it. Keep each value brief and in the language of the context.";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snippet {
+    #[serde(default)]
+    pub id: String,
+    /// What the user says after "insert"
+    pub name: String,
+    /// Template text; `{{variable}}` is replaced from the snippet variables and `{{ai:instruction}}` by the model
+    pub body: String,
+    /// Other names that insert the snippet
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    #[serde(default)]
+    pub created_at: u64,
+    #[serde(default)]
+    pub updated_at: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SnippetSettings {
+    pub snippets: Vec<Snippet>,
+    /// Values for `{{name}}` placeholders, e.g. "full_name" or "phone"
+    pub variables: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Placeholder {
+    /// Text between the braces
+    pub key: String,
+    pub ai: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnippetExpansion {
+    pub snippet_id: String,
+    pub name: String,
+    pub text: String,
+    pub filled: Vec<String>,
+    /// Placeholders with no value, removed from the text
+    pub unfilled: Vec<String>,
+}
+
+impl SnippetSettings {
+    pub fn find(&self, name: &str) -> Option<&Snippet> {
+        let wanted = normalize_name(name);
+        self.snippets.iter().find(|snippet| {
+            normalize_name(&snippet.name) == wanted || snippet.aliases.iter().any(|alias| normalize_name(alias) == wanted)
+        })
+    }
+}
+
+/// Recognize "insert <snippet>", "insert my <snippet>" and "insert the <snippet> snippet"
+pub fn parse_insert_command<'a>(utterance: &str, settings: &'a SnippetSettings) -> Option<&'a Snippet> {
+    let pattern = Regex::new(
+        r"(?i)^\s*(?:please\s+)?(?:insert|paste)\s+(?:my\s+|the\s+|a\s+|an\s+)?(.+?)(?:\s+(?:snippet|template))?\s*[.!]?\s*$",
+    )
+    .ok()?;
+    let name = pattern.captures(utterance)?.get(1)?.as_str();
+    settings.find(name)
+}
+
+/// Check a snippet before it is saved and fill in its ID and timestamps
+pub fn prepare_snippet(mut snippet: Snippet, existing: &[Snippet]) -> Result<Snippet, String> {
+    if normalize_name(&snippet.name).is_empty() {
+        return Err("Snippets need a name".to_string());
+    }
+    if snippet.body.trim().is_empty() {
+        return Err(format!("Snippet '{}' has no text", snippet.name));
+    }
+    if snippet.body.chars().count() > MAX_SNIPPET_CHARS {
+        return Err(format!("Snippet '{}' is longer than {} characters", snippet.name, MAX_SNIPPET_CHARS));
+    }
+    snippet.aliases.retain(|alias| !normalize_name(alias).is_empty());
+    let names: Vec<String> = std::iter::once(&snippet.name)
+        .chain(&snippet.aliases)
+        .map(|name| normalize_name(name))
+        .collect();
+    let taken = existing.iter().filter(|other| other.id != snippet.id).find(|other| {
+        std::iter::once(&other.name)
+            .chain(&other.aliases)
+            .any(|name| names.contains(&normalize_name(name)))
+    });
+    if let Some(other) = taken {
+        return Err(format!("'{}' is already used by the snippet '{}'", snippet.name, other.name));
+    }
+
+    let now = now_secs();
+    if snippet.id.is_empty() {
+        snippet.id = Uuid::new_v4().to_string();
+        snippet.created_at = now;
+    }
+    snippet.updated_at = now;
+    Ok(snippet)
+}
+
+/// Placeholders in the order they first appear
+pub fn placeholders(body: &str) -> Vec<Placeholder> {
+    let Ok(pattern) = Regex::new(r"\{\{\s*([^{}]+?)\s*\}\}") else {
+        return Vec::new();
+    };
+    let mut found: Vec<Placeholder> = Vec::new();
+    for captures in pattern.captures_iter(body) {
+        let key = captures[1].to_string();
+        if found.iter().all(|known| known.key != key) {
+            found.push(Placeholder {
+                ai: key.starts_with(AI_PREFIX),
+                key,
+            });
+        }
+    }
+    found
+}
+
+/// Values for the built-in placeholders and the user's variables
+pub fn variable_values(settings: &SnippetSettings, last_dictation: Option<&str>) -> HashMap<String, String> {
+    let now = chrono::Local::now();
+    let mut values = HashMap::from([
+        ("date".to_string(), now.format("%Y-%m-%d").to_string()),
+        ("time".to_string(), now.format("%H:%M").to_string()),
+        ("weekday".to_string(), now.format("%A").to_string()),
+    ]);
+    if let Some(text) = last_dictation {
+        values.insert("last_dictation".to_string(), text.to_string());
+    }
+    values.extend(settings.variables.clone());
+    values
+}
+
+/// Ask the model to write the `{{ai:...}}` placeholders from the recent dictation
+pub async fn fill_ai_placeholders(
+    gateway: &AIMLAPIGateway,
+    model: String,
+    snippet: &Snippet,
+    context: &str,
+) -> Result<HashMap<String, String>, String> {
+    let wanted: Vec<String> = placeholders(&snippet.body)
+        .into_iter()
+        .filter(|placeholder| placeholder.ai)
+        .map(|placeholder| placeholder.key)
+        .collect();
+    if wanted.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let request = serde_json::json!({
+        "context": context,
+        "placeholders": wanted.iter().map(|key| key[AI_PREFIX.len()..].trim()).collect::<Vec<_>>(),
+    });
+    let (reply, _usage) = gateway
+        .complete_with_failover(model, AI_FILL_PROMPT.to_string(), request.to_string(), Some(0.4))
+        .await
+        .map_err(|e| e.to_string())?;
+    // Models sometimes wrap JSON in a code fence
+    let json = reply
+        .trim()
+        .trim_start_matches("```json")
+        .trim_start_matches("```")
+        .trim_end_matches("```");
+    let values: HashMap<String, String> =
+        serde_json::from_str(json.trim()).map_err(|e| format!("Could not read placeholder values: {}", e))?;
+    Ok(wanted
+        .into_iter()
+        .filter_map(|key| {
+            let value = values.get(key[AI_PREFIX.len()..].trim()).or_else(|| values.get(&key))?;
+            Some((key, value.clone()))
+        })
+        .collect())
+}
+
+/// Replace every placeholder that has a value; those without one are dropped and reported
+pub fn expand(snippet: &Snippet, values: &HashMap<String, String>) -> SnippetExpansion {
+    let mut filled = Vec::new();
+    let mut unfilled = Vec::new();
+    let mut text = snippet.body.clone();
+    for placeholder in placeholders(&snippet.body) {
+        let value = values.get(&placeholder.key).or_else(|| {
+            values
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(&placeholder.key))
+                .map(|(_, value)| value)
+        });
+        match value {
+            Some(_) => filled.push(placeholder.key.clone()),
+            None => unfilled.push(placeholder.key.clone()),
+        }
+        let Ok(pattern) = Regex::new(&format!(r"\{{\{{\s*{}\s*\}}\}}", regex::escape(&placeholder.key))) else {
+            continue;
+        };
+        text = pattern
+            .replace_all(&text, regex::NoExpand(value.map(String::as_str).unwrap_or_default()))
+            .into_owned();
+    }
+    SnippetExpansion {
+        snippet_id: snippet.id.clone(),
+        name: snippet.name.clone(),
+        text,
+        filled,
+        unfilled,
+    }
+}
+
+/// Lowercase words without punctuation, so "E-mail signature." matches "email signature"
+fn normalize_name(name: &str) -> String {
+    name.split_whitespace()
+        .map(|word| {
+            word.chars()
+                .filter(|c| c.is_alphanumeric())
+                .collect::<String>()
+                .to_lowercase()
+        })
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
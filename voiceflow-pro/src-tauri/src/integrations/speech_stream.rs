@@ -0,0 +1,228 @@
+// Speech Streaming Module
+// Reads long text aloud sentence by sentence, synthesizing a few chunks ahead of playback
+
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tokio::sync::{watch, Mutex};
+use uuid::Uuid;
+
+use super::ai_ml_api::{AIMLAPIGateway, VoiceRequest};
+use crate::unicode_text::split_sentences;
+use crate::errors::AppError;
+
+/// Chunks synthesized beyond the one playing; enough to hide synthesis time without wasting requests on a skip
+const LOOKAHEAD_CHUNKS: usize = 2;
+/// The first chunk is kept short so speech starts quickly
+const FIRST_CHUNK_CHARS: usize = 120;
+const CHUNK_CHARS: usize = 400;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpeechStreamAction {
+    Pause,
+    Resume,
+    /// Drop the chunk playing now and continue with the next one
+    Skip,
+    Stop,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpeechStreamState {
+    Playing,
+    Paused,
+    Finished,
+    Stopped,
+    Failed,
+}
+
+/// Audio for one chunk, played back to back with its neighbours by the frontend
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpeechChunk {
+    pub stream_id: String,
+    pub sequence: usize,
+    pub total: usize,
+    pub text: String,
+    pub audio_base64: String,
+    pub format: String,
+    pub sample_rate: u32,
+    pub duration_seconds: f32,
+    pub is_last: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpeechStreamStatus {
+    pub stream_id: String,
+    pub state: SpeechStreamState,
+    pub total_chunks: usize,
+    pub synthesized: usize,
+    /// Chunks the frontend finished or skipped
+    pub played: usize,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Control {
+    paused: bool,
+    stopped: bool,
+    played: usize,
+}
+
+struct StreamHandle {
+    control: watch::Sender<Control>,
+}
+
+static SPEECH_STREAMS: OnceLock<Mutex<HashMap<String, StreamHandle>>> = OnceLock::new();
+
+fn streams() -> &'static Mutex<HashMap<String, StreamHandle>> {
+    SPEECH_STREAMS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Group sentences into chunks, the first one short so playback can begin early
+pub fn plan_chunks(text: &str) -> Vec<String> {
+    let mut chunks: Vec<String> = Vec::new();
+    let mut current = String::new();
+    for sentence in split_sentences(text, "") {
+        let limit = if chunks.is_empty() { FIRST_CHUNK_CHARS } else { CHUNK_CHARS };
+        if !current.is_empty() && current.len() + sentence.len() > limit {
+            chunks.push(std::mem::take(&mut current).trim().to_string());
+        }
+        current.push_str(&sentence);
+    }
+    if !current.trim().is_empty() {
+        chunks.push(current.trim().to_string());
+    }
+    chunks.retain(|chunk| !chunk.is_empty());
+    chunks
+}
+
+/// Start reading `text` aloud. Chunks arrive as "speech-stream-chunk" events and
+/// state changes as "speech-stream-status"; playback progress comes back through `acknowledge`.
+pub async fn start_stream(
+    app: AppHandle,
+    gateway: Arc<Mutex<Option<AIMLAPIGateway>>>,
+    text: &str,
+    model: String,
+    voice: Option<String>,
+    language: String,
+) -> Result<SpeechStreamStatus, AppError> {
+    let chunks = plan_chunks(text);
+    if chunks.is_empty() {
+        return Err(AppError::Configuration("There is nothing to read aloud".to_string()));
+    }
+    if gateway.lock().await.is_none() {
+        return Err(AppError::NotInitialized("AI ML API".to_string()));
+    }
+
+    let stream_id = Uuid::new_v4().to_string();
+    let (control, mut control_rx) = watch::channel(Control {
+        paused: false,
+        stopped: false,
+        played: 0,
+    });
+    streams().lock().await.insert(stream_id.clone(), StreamHandle { control });
+
+    let status = SpeechStreamStatus {
+        stream_id: stream_id.clone(),
+        state: SpeechStreamState::Playing,
+        total_chunks: chunks.len(),
+        synthesized: 0,
+        played: 0,
+        error: None,
+    };
+    let _ = app.emit_all("speech-stream-status", &status);
+
+    let mut progress = status.clone();
+    tauri::async_runtime::spawn(async move {
+        let total = chunks.len();
+        for (sequence, text) in chunks.into_iter().enumerate() {
+            // Wait until playback is close enough, unless paused or stopped
+            let ready = control_rx
+                .wait_for(|c| c.stopped || (!c.paused && sequence < c.played + 1 + LOOKAHEAD_CHUNKS))
+                .await
+                .map(|c| !c.stopped)
+                .unwrap_or(false);
+            if !ready {
+                progress.state = SpeechStreamState::Stopped;
+                break;
+            }
+
+            let request = VoiceRequest::for_text(text.clone(), model.clone(), voice.clone(), language.clone());
+            // Cloned per chunk, so a synthesis in flight does not keep other commands waiting on the lock
+            let current = gateway.lock().await.clone();
+            let result = match current {
+                Some(gateway) => gateway.synthesize_with_failover(request).await.map_err(|e| e.to_string()),
+                None => Err("AI ML API not initialized".to_string()),
+            };
+            let result = match result {
+                Ok(result) => result,
+                Err(e) => {
+                    log::warn!("Speech stream {} failed at chunk {}: {}", progress.stream_id, sequence, e);
+                    progress.state = SpeechStreamState::Failed;
+                    progress.error = Some(e);
+                    break;
+                }
+            };
+
+            let chunk = SpeechChunk {
+                stream_id: progress.stream_id.clone(),
+                sequence,
+                total,
+                text,
+                audio_base64: BASE64.encode(&result.audio_data),
+                format: format!("{:?}", result.format).to_lowercase(),
+                sample_rate: result.sample_rate,
+                duration_seconds: result.duration_seconds,
+                is_last: sequence + 1 == total,
+            };
+            let _ = app.emit_all("speech-stream-chunk", &chunk);
+            progress.synthesized = sequence + 1;
+        }
+        if progress.state == SpeechStreamState::Playing {
+            progress.state = SpeechStreamState::Finished;
+        }
+        progress.played = control_rx.borrow().played;
+        streams().lock().await.remove(&progress.stream_id);
+        let _ = app.emit_all("speech-stream-status", &progress);
+    });
+
+    Ok(status)
+}
+
+/// Pause, resume, skip or stop a stream.
+/// Synthesis may already be done while the last chunks still play, so a finished stream is not an error.
+pub async fn control_stream(app: &AppHandle, stream_id: &str, action: SpeechStreamAction) {
+    if let Some(handle) = streams().lock().await.get(stream_id) {
+        handle.control.send_modify(|c| match action {
+            SpeechStreamAction::Pause => c.paused = true,
+            SpeechStreamAction::Resume => c.paused = false,
+            SpeechStreamAction::Skip => c.played += 1,
+            SpeechStreamAction::Stop => c.stopped = true,
+        });
+    }
+    // The frontend owns playback, so it applies the same action to what is queued there
+    let _ = app.emit_all(
+        "speech-stream-control",
+        serde_json::json!({ "stream_id": stream_id, "action": action }),
+    );
+}
+
+/// Apply `action` to every stream still synthesizing, returning their IDs
+pub async fn control_all(app: &AppHandle, action: SpeechStreamAction) -> Vec<String> {
+    let stream_ids: Vec<String> = streams().lock().await.keys().cloned().collect();
+    for stream_id in &stream_ids {
+        control_stream(app, stream_id, action).await;
+    }
+    stream_ids
+}
+
+/// Record that the frontend finished playing chunk `sequence`, letting synthesis run further ahead
+pub async fn acknowledge(stream_id: &str, sequence: usize) {
+    if let Some(handle) = streams().lock().await.get(stream_id) {
+        handle.control.send_modify(|c| c.played = c.played.max(sequence + 1));
+    }
+}
@@ -0,0 +1,465 @@
+// Stream Captions Module
+// Feeds live transcription to OBS through obs-websocket (v5) as stream captions or a text source
+
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Manager};
+use tokio::sync::watch;
+use tokio_tungstenite::tungstenite::Message;
+
+use super::caption_filter::{CaptionFilter, CaptionFilterSettings};
+use crate::errors::AppError;
+use crate::unicode_text::{break_units, display_width, split_sentences};
+
+const OBS_RPC_VERSION: u64 = 1;
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How captions reach OBS
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptionOutput {
+    /// CEA-608 captions embedded in the outgoing stream; OBS must be live
+    StreamCaptions,
+    /// Text of a GDI+/FreeType text source, visible in preview and recordings too
+    TextSource,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StreamingSettings {
+    pub enabled: bool,
+    pub output: CaptionOutput,
+    pub obs_url: String,
+    pub obs_password: Option<String>,
+    /// Text source updated when `output` is `TextSource`
+    pub text_source_name: String,
+    /// Line width in columns; full-width CJK characters take two
+    pub max_line_chars: usize,
+    pub max_lines: usize,
+    /// Interim updates closer together than this are coalesced
+    pub min_update_interval_ms: u64,
+    /// Captions are cleared after this much silence
+    pub clear_after_ms: u64,
+    pub max_reconnect_delay_secs: u64,
+    /// Masking applied on-device before anything is sent to OBS
+    pub profanity_filter: CaptionFilterSettings,
+}
+
+impl Default for StreamingSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            output: CaptionOutput::StreamCaptions,
+            obs_url: "ws://127.0.0.1:4455".to_string(),
+            obs_password: None,
+            text_source_name: "VoiceFlow Captions".to_string(),
+            max_line_chars: 42,
+            max_lines: 2,
+            min_update_interval_ms: 150,
+            clear_after_ms: 4000,
+            max_reconnect_delay_secs: 30,
+            profanity_filter: CaptionFilterSettings::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptionConnectionState {
+    Disabled,
+    Connecting,
+    Connected,
+    Reconnecting,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaptionStreamStatus {
+    pub state: CaptionConnectionState,
+    pub endpoint: String,
+    pub last_error: Option<String>,
+    pub reconnect_attempts: u32,
+    pub captions_sent: u64,
+    pub words_masked: u64,
+}
+
+/// Rolling caption window: finished sentences plus the hypothesis still being spoken
+#[derive(Debug, Default)]
+struct CaptionBuffer {
+    /// Finished sentences, oldest first
+    committed: Vec<String>,
+    interim: String,
+    last_update: Option<Instant>,
+}
+
+impl CaptionBuffer {
+    fn push(&mut self, text: &str, is_final: bool, language: &str, settings: &StreamingSettings) {
+        let stale = self
+            .last_update
+            .map_or(false, |at| at.elapsed() > Duration::from_millis(settings.clear_after_ms));
+        if stale {
+            self.committed.clear();
+        }
+        self.last_update = Some(Instant::now());
+
+        let text = text.trim();
+        if is_final {
+            self.committed.extend(
+                split_sentences(text, language)
+                    .iter()
+                    .map(|sentence| sentence.trim().to_string())
+                    .filter(|sentence| !sentence.is_empty()),
+            );
+            self.interim.clear();
+            // Only enough history to fill the window is worth keeping
+            let keep = settings.max_lines.max(1) * 2;
+            if self.committed.len() > keep {
+                self.committed.drain(..self.committed.len() - keep);
+            }
+        } else {
+            self.interim = text.to_string();
+        }
+    }
+
+    /// The last `max_lines` wrapped lines of the buffered text. CJK wraps between characters, and a
+    /// sentence starts a fresh line when the current one is already half full
+    fn render(&self, settings: &StreamingSettings) -> String {
+        let width = settings.max_line_chars.max(8);
+        let mut lines: Vec<String> = Vec::new();
+        let mut line = String::new();
+        for sentence in self.committed.iter().chain(std::iter::once(&self.interim)) {
+            for (index, unit) in break_units(sentence).into_iter().enumerate() {
+                let used = display_width(&line);
+                let space = !line.is_empty() && unit.space_before;
+                let overflows = used + usize::from(space) + display_width(unit.text) > width;
+                if !line.is_empty() && (overflows || (index == 0 && used * 2 > width)) {
+                    lines.push(std::mem::take(&mut line));
+                } else if space {
+                    line.push(' ');
+                }
+                line.push_str(unit.text);
+            }
+        }
+        if !line.is_empty() {
+            lines.push(line);
+        }
+        let skip = lines.len().saturating_sub(settings.max_lines.max(1));
+        lines[skip..].join("\n")
+    }
+}
+
+/// Owns the OBS connection task and the caption buffer
+pub struct CaptionStreamer {
+    settings: StreamingSettings,
+    buffer: CaptionBuffer,
+    filter: CaptionFilter,
+    caption_tx: Option<watch::Sender<String>>,
+    task: Option<JoinHandle<()>>,
+    status: Arc<StdMutex<CaptionStreamStatus>>,
+    app: Option<AppHandle>,
+}
+
+impl CaptionStreamer {
+    fn new() -> Self {
+        let settings = StreamingSettings::default();
+        Self {
+            status: Arc::new(StdMutex::new(CaptionStreamStatus {
+                state: CaptionConnectionState::Disabled,
+                endpoint: settings.obs_url.clone(),
+                last_error: None,
+                reconnect_attempts: 0,
+                captions_sent: 0,
+                words_masked: 0,
+            })),
+            filter: CaptionFilter::new(&settings.profanity_filter),
+            settings,
+            buffer: CaptionBuffer::default(),
+            caption_tx: None,
+            task: None,
+            app: None,
+        }
+    }
+
+    /// Handle used to report connection changes as "caption-stream-status" events
+    pub fn attach(&mut self, app: AppHandle) {
+        self.app = Some(app);
+    }
+
+    /// Apply new settings, reconnecting only when they actually changed
+    pub fn configure(&mut self, settings: &StreamingSettings) {
+        // The word list changes without touching the OBS connection
+        if settings.profanity_filter != self.settings.profanity_filter {
+            self.filter = CaptionFilter::new(&settings.profanity_filter);
+            self.settings.profanity_filter = settings.profanity_filter.clone();
+        }
+        if *settings == self.settings && (self.task.is_some() == settings.enabled) {
+            return;
+        }
+        self.stop();
+        self.settings = settings.clone();
+        if !settings.enabled {
+            return;
+        }
+
+        let (tx, rx) = watch::channel(String::new());
+        self.caption_tx = Some(tx);
+        self.buffer = CaptionBuffer::default();
+        self.task = Some(tauri::async_runtime::spawn(run_connection(
+            settings.clone(),
+            rx,
+            self.status.clone(),
+            self.app.clone(),
+        )));
+    }
+
+    fn stop(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+        self.caption_tx = None;
+        update_status(&self.status, self.app.as_ref(), |status| {
+            status.state = CaptionConnectionState::Disabled;
+            status.reconnect_attempts = 0;
+        });
+    }
+
+    /// Add recognized text in `language`; interim results replace each other until a final one arrives.
+    /// Masking happens here, so no hypothesis reaches OBS unfiltered
+    pub fn publish(&mut self, text: &str, is_final: bool, language: &str) {
+        let Some(tx) = &self.caption_tx else {
+            return;
+        };
+        let (text, masked) = self.filter.apply(text);
+        if masked > 0 {
+            update_status(&self.status, None, |status| status.words_masked += masked as u64);
+        }
+        self.buffer.push(&text, is_final, language, &self.settings);
+        let caption = self.buffer.render(&self.settings);
+        tx.send_if_modified(|current| {
+            if *current == caption {
+                false
+            } else {
+                *current = caption;
+                true
+            }
+        });
+    }
+
+    pub fn status(&self) -> CaptionStreamStatus {
+        self.status.lock().map(|s| s.clone()).unwrap_or_else(|e| e.into_inner().clone())
+    }
+}
+
+static CAPTION_STREAMER: std::sync::OnceLock<tokio::sync::Mutex<CaptionStreamer>> = std::sync::OnceLock::new();
+
+pub fn get_caption_streamer() -> &'static tokio::sync::Mutex<CaptionStreamer> {
+    CAPTION_STREAMER.get_or_init(|| tokio::sync::Mutex::new(CaptionStreamer::new()))
+}
+
+fn update_status(
+    status: &StdMutex<CaptionStreamStatus>,
+    app: Option<&AppHandle>,
+    change: impl FnOnce(&mut CaptionStreamStatus),
+) {
+    let snapshot = {
+        let mut status = status.lock().unwrap_or_else(|e| e.into_inner());
+        change(&mut status);
+        status.clone()
+    };
+    if let Some(app) = app {
+        let _ = app.emit_all("caption-stream-status", &snapshot);
+    }
+}
+
+/// Keep a connection to OBS open, reconnecting with backoff, and forward caption updates
+async fn run_connection(
+    settings: StreamingSettings,
+    mut captions: watch::Receiver<String>,
+    status: Arc<StdMutex<CaptionStreamStatus>>,
+    app: Option<AppHandle>,
+) {
+    let mut attempts: u32 = 0;
+    loop {
+        update_status(&status, app.as_ref(), |s| {
+            s.endpoint = settings.obs_url.clone();
+            s.reconnect_attempts = attempts;
+            s.state = if attempts == 0 {
+                CaptionConnectionState::Connecting
+            } else {
+                CaptionConnectionState::Reconnecting
+            };
+        });
+
+        let error = match connect_obs(&settings).await {
+            Ok(socket) => {
+                attempts = 0;
+                update_status(&status, app.as_ref(), |s| {
+                    s.state = CaptionConnectionState::Connected;
+                    s.reconnect_attempts = 0;
+                    s.last_error = None;
+                });
+                log::info!("Caption stream connected to {}", settings.obs_url);
+                stream_captions(socket, &settings, &mut captions, &status).await
+            }
+            Err(e) => e,
+        };
+
+        log::warn!("Caption stream to {} interrupted: {}", settings.obs_url, error);
+        attempts = attempts.saturating_add(1);
+        update_status(&status, app.as_ref(), |s| {
+            s.state = CaptionConnectionState::Reconnecting;
+            s.reconnect_attempts = attempts;
+            s.last_error = Some(error.to_string());
+        });
+        let delay = 2u64.saturating_pow(attempts.min(6)).min(settings.max_reconnect_delay_secs.max(1));
+        tokio::time::sleep(Duration::from_secs(delay)).await;
+    }
+}
+
+type ObsSocket = tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>;
+
+/// Open the socket and complete the Hello / Identify / Identified handshake
+async fn connect_obs(settings: &StreamingSettings) -> Result<ObsSocket, AppError> {
+    let (mut socket, _) = tokio_tungstenite::connect_async(settings.obs_url.as_str())
+        .await
+        .map_err(|e| AppError::Network(format!("Cannot reach OBS: {}", e)))?;
+
+    let hello = next_obs_message(&mut socket, 0).await?;
+    let mut identify = json!({ "rpcVersion": OBS_RPC_VERSION, "eventSubscriptions": 0 });
+    if let Some(auth) = hello.get("authentication") {
+        let password = settings
+            .obs_password
+            .as_deref()
+            .ok_or_else(|| AppError::Configuration("OBS requires a WebSocket password".to_string()))?;
+        let challenge = auth.get("challenge").and_then(Value::as_str).unwrap_or_default();
+        let salt = auth.get("salt").and_then(Value::as_str).unwrap_or_default();
+        identify["authentication"] = json!(obs_auth_response(password, salt, challenge));
+    }
+    socket
+        .send(Message::Text(json!({ "op": 1, "d": identify }).to_string()))
+        .await
+        .map_err(|e| AppError::Network(e.to_string()))?;
+    // OBS closes the socket instead of answering when authentication fails
+    next_obs_message(&mut socket, 2)
+        .await
+        .map_err(|_| AppError::Security("OBS rejected the WebSocket password".to_string()))?;
+    Ok(socket)
+}
+
+/// Wait for the message with opcode `op` and return its data
+async fn next_obs_message(socket: &mut ObsSocket, op: u64) -> Result<Value, AppError> {
+    let read = async {
+        while let Some(message) = socket.next().await {
+            let message = message.map_err(|e| AppError::Network(e.to_string()))?;
+            if let Message::Text(text) = message {
+                let value: Value = serde_json::from_str(&text).map_err(|e| AppError::Network(e.to_string()))?;
+                if value.get("op").and_then(Value::as_u64) == Some(op) {
+                    return Ok(value.get("d").cloned().unwrap_or(Value::Null));
+                }
+            }
+        }
+        Err(AppError::Network("OBS closed the connection".to_string()))
+    };
+    tokio::time::timeout(HANDSHAKE_TIMEOUT, read)
+        .await
+        .map_err(|_| AppError::Network("Timed out waiting for OBS".to_string()))?
+}
+
+/// base64(sha256(base64(sha256(password + salt)) + challenge)), per the obs-websocket spec
+fn obs_auth_response(password: &str, salt: &str, challenge: &str) -> String {
+    let secret = BASE64.encode(Sha256::digest(format!("{}{}", password, salt).as_bytes()));
+    BASE64.encode(Sha256::digest(format!("{}{}", secret, challenge).as_bytes()))
+}
+
+/// Send caption updates until the connection drops; returns why it stopped
+async fn stream_captions(
+    socket: ObsSocket,
+    settings: &StreamingSettings,
+    captions: &mut watch::Receiver<String>,
+    status: &StdMutex<CaptionStreamStatus>,
+) -> AppError {
+    let (mut sink, mut incoming) = socket.split();
+    let min_interval = Duration::from_millis(settings.min_update_interval_ms);
+    let clear_after = Duration::from_millis(settings.clear_after_ms.max(500));
+    let mut last_sent = Instant::now().checked_sub(min_interval).unwrap_or_else(Instant::now);
+    let mut showing_text = false;
+
+    // Re-send whatever is current so a reconnect restores the caption
+    captions.mark_changed();
+
+    loop {
+        let caption = tokio::select! {
+            changed = tokio::time::timeout(clear_after, captions.changed()) => match changed {
+                Ok(Ok(())) => {
+                    // Coalesce bursts of interim results into one update per interval
+                    let since = last_sent.elapsed();
+                    if since < min_interval {
+                        tokio::time::sleep(min_interval - since).await;
+                    }
+                    captions.borrow_and_update().clone()
+                }
+                Ok(Err(_)) => return AppError::Internal("Caption stream stopped".to_string()),
+                Err(_) if showing_text => String::new(),
+                Err(_) => continue,
+            },
+            message = incoming.next() => match message {
+                Some(Ok(Message::Text(text))) => {
+                    log_request_failure(&text);
+                    continue;
+                }
+                Some(Ok(Message::Close(_))) | None => {
+                    return AppError::Network("OBS closed the connection".to_string())
+                }
+                Some(Ok(_)) => continue,
+                Some(Err(e)) => return AppError::Network(e.to_string()),
+            },
+        };
+
+        let request = match settings.output {
+            CaptionOutput::StreamCaptions => json!({
+                "requestType": "SendStreamCaption",
+                "requestId": uuid::Uuid::new_v4().to_string(),
+                "requestData": { "captionText": caption },
+            }),
+            CaptionOutput::TextSource => json!({
+                "requestType": "SetInputSettings",
+                "requestId": uuid::Uuid::new_v4().to_string(),
+                "requestData": {
+                    "inputName": settings.text_source_name,
+                    "inputSettings": { "text": caption },
+                    "overlay": true,
+                },
+            }),
+        };
+        if let Err(e) = sink.send(Message::Text(json!({ "op": 6, "d": request }).to_string())).await {
+            return AppError::Network(e.to_string());
+        }
+        last_sent = Instant::now();
+        showing_text = !caption.is_empty();
+        status.lock().unwrap_or_else(|e| e.into_inner()).captions_sent += 1;
+    }
+}
+
+/// OBS answers every request; failures (e.g. not streaming, missing source) are only worth a log line
+fn log_request_failure(text: &str) {
+    let Ok(value) = serde_json::from_str::<Value>(text) else {
+        return;
+    };
+    if value.get("op").and_then(Value::as_u64) != Some(7) {
+        return;
+    }
+    let status = &value["d"]["requestStatus"];
+    if status["result"].as_bool() == Some(false) {
+        log::debug!(
+            "OBS rejected caption update ({}): {}",
+            status["code"],
+            status["comment"].as_str().unwrap_or("no details")
+        );
+    }
+}
@@ -0,0 +1,1085 @@
+// Speech-to-Text Providers Module
+// One interface over Whisper (aimlapi.com), Azure Speech, Google Speech-to-Text and Deepgram, chosen per language
+
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::sync::{mpsc, Mutex};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::Message;
+
+use super::ai_ml_api::AIMLAPIGateway;
+use super::audio_capture::{Resampler, TARGET_SAMPLE_RATE};
+use super::echo_cancellation;
+use super::provider_failover::{self, ProviderFailoverSettings, ProviderService};
+use super::voice_recognition::Alternative;
+use crate::errors::{AppError, ValidationError};
+
+const WHISPER_MODEL: &str = "#g1_whisper-large";
+const DEEPGRAM_URL: &str = "https://api.deepgram.com/v1/listen";
+const DEEPGRAM_STREAM_URL: &str = "wss://api.deepgram.com/v1/listen";
+const GOOGLE_URL: &str = "https://speech.googleapis.com/v1p1beta1/speech:recognize";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+/// Audio frames queued for a streaming connection before the caller is told to slow down
+const STREAM_QUEUE: usize = 64;
+/// Hypotheses asked for per streamed utterance, for hypothesis merging
+const STREAM_ALTERNATIVES: usize = 3;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SttProviderKind {
+    /// Whisper large through aimlapi.com, using the app's AI key
+    #[default]
+    Whisper,
+    Azure,
+    Google,
+    Deepgram,
+}
+
+impl SttProviderKind {
+    pub const ALL: [SttProviderKind; 4] = [
+        SttProviderKind::Whisper,
+        SttProviderKind::Azure,
+        SttProviderKind::Google,
+        SttProviderKind::Deepgram,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SttProviderKind::Whisper => "whisper-large",
+            SttProviderKind::Azure => "azure-speech",
+            SttProviderKind::Google => "google-stt",
+            SttProviderKind::Deepgram => "deepgram",
+        }
+    }
+
+    pub fn capabilities(self) -> SttCapabilities {
+        match self {
+            SttProviderKind::Whisper => SttCapabilities {
+                streaming: false,
+                word_timestamps: false,
+                diarization: false,
+                max_batch_secs: None,
+                formats: vec!["wav", "mp3", "m4a", "ogg", "webm", "flac"],
+            },
+            // Streaming and diarization need the Speech SDK; the short-audio REST API has neither
+            SttProviderKind::Azure => SttCapabilities {
+                streaming: false,
+                word_timestamps: true,
+                diarization: false,
+                max_batch_secs: Some(60),
+                formats: vec!["wav", "ogg"],
+            },
+            // Streaming recognition is only offered over gRPC
+            SttProviderKind::Google => SttCapabilities {
+                streaming: false,
+                word_timestamps: true,
+                diarization: true,
+                max_batch_secs: Some(60),
+                formats: vec!["wav", "flac", "ogg", "webm"],
+            },
+            SttProviderKind::Deepgram => SttCapabilities {
+                streaming: true,
+                word_timestamps: true,
+                diarization: true,
+                max_batch_secs: None,
+                formats: vec!["wav", "mp3", "m4a", "ogg", "webm", "flac"],
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AzureSttSettings {
+    /// Falls back to AZURE_SPEECH_KEY
+    pub api_key: Option<String>,
+    /// Speech resource region, e.g. "westeurope"
+    pub region: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GoogleSttSettings {
+    /// Falls back to GOOGLE_STT_API_KEY
+    pub api_key: Option<String>,
+    /// Recognition model such as "latest_long"; the API picks one when unset
+    pub model: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeepgramSttSettings {
+    /// Falls back to DEEPGRAM_API_KEY
+    pub api_key: Option<String>,
+    pub model: String,
+}
+
+impl Default for DeepgramSttSettings {
+    fn default() -> Self {
+        Self {
+            api_key: None,
+            model: "nova-2".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SttSettings {
+    pub default_provider: SttProviderKind,
+    /// Provider per language, by full code ("pt-BR") or base language ("pt")
+    pub by_language: HashMap<String, SttProviderKind>,
+    pub azure: AzureSttSettings,
+    pub google: GoogleSttSettings,
+    pub deepgram: DeepgramSttSettings,
+}
+
+impl SttSettings {
+    /// Provider configured for a language; the full code wins over the base language
+    pub fn provider_for(&self, language: &str) -> SttProviderKind {
+        let base = language.split('-').next().unwrap_or(language);
+        self.by_language
+            .get(language)
+            .or_else(|| self.by_language.get(base))
+            .copied()
+            .unwrap_or(self.default_provider)
+    }
+
+    fn key(&self, kind: SttProviderKind) -> Option<String> {
+        let (configured, env) = match kind {
+            SttProviderKind::Whisper => return None,
+            SttProviderKind::Azure => (&self.azure.api_key, "AZURE_SPEECH_KEY"),
+            SttProviderKind::Google => (&self.google.api_key, "GOOGLE_STT_API_KEY"),
+            SttProviderKind::Deepgram => (&self.deepgram.api_key, "DEEPGRAM_API_KEY"),
+        };
+        configured
+            .clone()
+            .or_else(|| std::env::var(env).ok())
+            .filter(|key| !key.trim().is_empty())
+    }
+
+    /// Whether a provider has what it needs to be called; Whisper depends on the AI gateway instead
+    pub fn is_configured(&self, kind: SttProviderKind) -> bool {
+        match kind {
+            SttProviderKind::Whisper => true,
+            SttProviderKind::Azure => self.key(kind).is_some() && !self.azure.region.trim().is_empty(),
+            _ => self.key(kind).is_some(),
+        }
+    }
+
+    /// Settings with every stored key removed, for exports and recordings
+    pub fn without_keys(&self) -> Self {
+        let mut settings = self.clone();
+        settings.azure.api_key = None;
+        settings.google.api_key = None;
+        settings.deepgram.api_key = None;
+        settings
+    }
+
+    /// Keep the keys entered this session when switching profile, as they are never saved with one
+    pub fn carry_keys_from(&mut self, current: &Self) {
+        self.azure.api_key = current.azure.api_key.clone();
+        self.google.api_key = current.google.api_key.clone();
+        self.deepgram.api_key = current.deepgram.api_key.clone();
+    }
+
+    pub fn redact_keys(&mut self) {
+        for key in [
+            &mut self.azure.api_key,
+            &mut self.google.api_key,
+            &mut self.deepgram.api_key,
+        ] {
+            if key.is_some() {
+                *key = Some("<redacted>".to_string());
+            }
+        }
+    }
+}
+
+pub fn validate(settings: &SttSettings) -> Result<(), AppError> {
+    let invalid = |message: String| AppError::Validation(ValidationError::InvalidConfigValue(message));
+    for language in settings.by_language.keys() {
+        if language.trim().is_empty() || language.len() > 16 {
+            return Err(invalid(format!("Invalid language code for a speech provider: \"{}\"", language)));
+        }
+    }
+    let region = settings.azure.region.trim();
+    if !region.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(invalid(format!("Invalid Azure Speech region: \"{}\"", region)));
+    }
+    if settings.deepgram.model.trim().is_empty() {
+        return Err(invalid("Deepgram needs a model name".to_string()));
+    }
+    Ok(())
+}
+
+/// What a provider can do, so callers only ask for what it supports
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SttCapabilities {
+    /// Audio can be sent while the user speaks, with interim results coming back
+    pub streaming: bool,
+    pub word_timestamps: bool,
+    /// Words are attributed to speakers
+    pub diarization: bool,
+    /// Longest recording one batch request takes; longer audio has to be windowed
+    pub max_batch_secs: Option<u32>,
+    /// Audio formats taken as-is, by file extension
+    pub formats: Vec<&'static str>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SttProviderInfo {
+    pub kind: SttProviderKind,
+    pub label: &'static str,
+    pub configured: bool,
+    pub capabilities: SttCapabilities,
+    /// Languages set to use this provider
+    pub languages: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SttRequest {
+    pub audio: Vec<u8>,
+    /// Name with an extension telling the audio format
+    pub file_name: String,
+    /// Full language code such as "en-US"; `None` lets the provider detect it
+    pub language: Option<String>,
+    pub word_timestamps: bool,
+    pub diarization: bool,
+}
+
+impl SttRequest {
+    fn format(&self) -> String {
+        self.file_name
+            .rsplit_once('.')
+            .map(|(_, extension)| extension.to_ascii_lowercase())
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SttWord {
+    pub word: String,
+    /// Seconds from the start of the audio
+    pub start: f32,
+    pub end: f32,
+    pub confidence: Option<f32>,
+    pub speaker: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SttTranscript {
+    pub text: String,
+    pub language: Option<String>,
+    /// Audio length in seconds
+    pub duration: Option<f32>,
+    pub confidence: Option<f32>,
+    /// Empty unless word timestamps were asked for and the provider has them
+    pub words: Vec<SttWord>,
+    pub provider: SttProviderKind,
+}
+
+impl SttTranscript {
+    /// Speaker who said most of the words, when the provider diarized
+    pub fn main_speaker(&self) -> Option<u32> {
+        let mut counts: HashMap<u32, usize> = HashMap::new();
+        for speaker in self.words.iter().filter_map(|word| word.speaker) {
+            *counts.entry(speaker).or_default() += 1;
+        }
+        counts.into_iter().max_by_key(|(_, count)| *count).map(|(speaker, _)| speaker)
+    }
+}
+
+/// Sent as "stt-result" while a streaming session runs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SttStreamResult {
+    pub text: String,
+    /// Final results are not revised any more; interim ones are
+    pub is_final: bool,
+    pub words: Vec<SttWord>,
+    /// The provider's hypotheses for a final result, best first, to hand back with the transcript to
+    /// `process_speech_with_ai`; empty for interim results
+    pub alternatives: Vec<Alternative>,
+    pub provider: SttProviderKind,
+}
+
+impl SttStreamResult {
+    /// The best hypothesis's confidence, else the mean over words; `None` when the provider reports neither
+    pub fn confidence(&self) -> Option<f32> {
+        if let Some(best) = self.alternatives.first() {
+            return Some(best.confidence);
+        }
+        let scores: Vec<f32> = self.words.iter().filter_map(|word| word.confidence).collect();
+        (!scores.is_empty()).then(|| scores.iter().sum::<f32>() / scores.len() as f32)
+    }
+}
+
+/// An open streaming recognition. Dropping `audio` ends the audio; the remaining results still arrive
+pub struct SttStream {
+    /// Mono samples at `TARGET_SAMPLE_RATE`
+    pub audio: mpsc::Sender<Vec<f32>>,
+    pub results: mpsc::Receiver<Result<SttStreamResult, AppError>>,
+}
+
+#[async_trait]
+pub trait SttProvider: Send + Sync {
+    fn kind(&self) -> SttProviderKind;
+
+    fn capabilities(&self) -> SttCapabilities {
+        self.kind().capabilities()
+    }
+
+    async fn transcribe(&self, request: SttRequest) -> Result<SttTranscript, AppError>;
+
+    /// Open a streaming recognition; only providers whose capabilities say `streaming` implement it
+    async fn stream(&self, language: Option<String>) -> Result<SttStream, AppError> {
+        let _ = language;
+        Err(AppError::Configuration(format!(
+            "{} does not support streaming recognition",
+            self.kind().label()
+        )))
+    }
+}
+
+/// Whisper large through the AI gateway
+pub struct WhisperProvider {
+    gateway: Arc<Mutex<Option<AIMLAPIGateway>>>,
+}
+
+#[async_trait]
+impl SttProvider for WhisperProvider {
+    fn kind(&self) -> SttProviderKind {
+        SttProviderKind::Whisper
+    }
+
+    async fn transcribe(&self, request: SttRequest) -> Result<SttTranscript, AppError> {
+        // Whisper takes ISO-639-1 codes
+        let language = request
+            .language
+            .as_deref()
+            .and_then(|language| language.split('-').next())
+            .map(str::to_string);
+        // A handle, so windows of a long recording are transcribed in parallel rather than one at a time
+        let gateway = self
+            .gateway
+            .lock()
+            .await
+            .clone()
+            .ok_or_else(|| AppError::NotInitialized("AI ML API".to_string()))?;
+        let response = gateway
+            .transcribe_audio_with_model(request.audio, request.file_name, WHISPER_MODEL, language)
+            .await?;
+        Ok(SttTranscript {
+            text: response.text.trim().to_string(),
+            language: response.language,
+            duration: response.duration,
+            confidence: None,
+            words: Vec::new(),
+            provider: SttProviderKind::Whisper,
+        })
+    }
+}
+
+/// Azure Speech's REST API for short audio
+pub struct AzureProvider {
+    key: String,
+    region: String,
+}
+
+#[async_trait]
+impl SttProvider for AzureProvider {
+    fn kind(&self) -> SttProviderKind {
+        SttProviderKind::Azure
+    }
+
+    async fn transcribe(&self, request: SttRequest) -> Result<SttTranscript, AppError> {
+        let content_type = match request.format().as_str() {
+            "wav" => "audio/wav; codecs=audio/pcm; samplerate=16000",
+            "ogg" => "audio/ogg; codecs=opus",
+            other => {
+                return Err(AppError::Validation(ValidationError::InvalidFileType(format!(
+                    "Azure Speech takes WAV or Ogg audio, not \"{}\"",
+                    other
+                ))))
+            }
+        };
+        let language = request
+            .language
+            .clone()
+            .filter(|language| language.contains('-'))
+            .ok_or_else(|| AppError::Configuration("Azure Speech needs a locale such as en-US".to_string()))?;
+        let url = format!(
+            "https://{}.stt.speech.microsoft.com/speech/recognition/conversation/cognitiveservices/v1",
+            self.region
+        );
+        let response = crate::network::client(REQUEST_TIMEOUT)?
+            .post(url)
+            .query(&[
+                ("language", language.as_str()),
+                ("format", "detailed"),
+                ("wordLevelTimestamps", if request.word_timestamps { "true" } else { "false" }),
+            ])
+            .header("Ocp-Apim-Subscription-Key", &self.key)
+            .header("Content-Type", content_type)
+            .body(request.audio)
+            .send()
+            .await
+            .map_err(|e| AppError::Network(format!("Azure Speech request failed: {}", e)))?;
+        let body = read_response(response, "Azure Speech").await?;
+        azure_transcript(&body, language)
+    }
+}
+
+fn azure_transcript(body: &Value, language: String) -> Result<SttTranscript, AppError> {
+    let status = body["RecognitionStatus"].as_str().unwrap_or_default();
+    if status != "Success" && status != "NoMatch" {
+        return Err(AppError::Upstream {
+            status: 200,
+            message: format!("Azure Speech could not recognize the audio: {}", status),
+        });
+    }
+    // Azure counts time in 100 ns ticks
+    let ticks = |value: &Value| value.as_f64().unwrap_or_default() as f32 / 10_000_000.0;
+    let best = &body["NBest"][0];
+    let words = best["Words"]
+        .as_array()
+        .map(|words| {
+            words
+                .iter()
+                .map(|word| SttWord {
+                    word: word["Word"].as_str().unwrap_or_default().to_string(),
+                    start: ticks(&word["Offset"]),
+                    end: ticks(&word["Offset"]) + ticks(&word["Duration"]),
+                    confidence: word["Confidence"].as_f64().map(|c| c as f32),
+                    speaker: None,
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    Ok(SttTranscript {
+        text: body["DisplayText"].as_str().unwrap_or_default().trim().to_string(),
+        language: Some(language),
+        duration: body["Duration"].as_f64().map(|_| ticks(&body["Duration"])),
+        confidence: best["Confidence"].as_f64().map(|c| c as f32),
+        words,
+        provider: SttProviderKind::Azure,
+    })
+}
+
+/// Google Cloud Speech-to-Text's synchronous recognize method
+pub struct GoogleProvider {
+    key: String,
+    model: Option<String>,
+}
+
+#[async_trait]
+impl SttProvider for GoogleProvider {
+    fn kind(&self) -> SttProviderKind {
+        SttProviderKind::Google
+    }
+
+    async fn transcribe(&self, request: SttRequest) -> Result<SttTranscript, AppError> {
+        let mut config = json!({
+            "languageCode": request.language.clone().unwrap_or_else(|| "en-US".to_string()),
+            "enableAutomaticPunctuation": true,
+            "enableWordTimeOffsets": request.word_timestamps,
+            "enableWordConfidence": request.word_timestamps,
+        });
+        // WAV and FLAC carry their encoding in the header; Opus containers have to name it
+        match request.format().as_str() {
+            "ogg" => {
+                config["encoding"] = json!("OGG_OPUS");
+                config["sampleRateHertz"] = json!(48_000);
+            }
+            "webm" => {
+                config["encoding"] = json!("WEBM_OPUS");
+                config["sampleRateHertz"] = json!(48_000);
+            }
+            _ => {}
+        }
+        if request.diarization {
+            config["diarizationConfig"] = json!({ "enableSpeakerDiarization": true });
+        }
+        if let Some(model) = &self.model {
+            config["model"] = json!(model);
+        }
+        let response = crate::network::client(REQUEST_TIMEOUT)?
+            .post(GOOGLE_URL)
+            .query(&[("key", self.key.as_str())])
+            .json(&json!({ "config": config, "audio": { "content": BASE64.encode(&request.audio) } }))
+            .send()
+            .await
+            .map_err(|e| AppError::Network(format!("Google Speech-to-Text request failed: {}", e)))?;
+        let body = read_response(response, "Google Speech-to-Text").await?;
+        Ok(google_transcript(&body, request.language, request.diarization))
+    }
+}
+
+fn google_transcript(body: &Value, language: Option<String>, diarization: bool) -> SttTranscript {
+    let results = body["results"].as_array().cloned().unwrap_or_default();
+    let text = results
+        .iter()
+        .filter_map(|result| result["alternatives"][0]["transcript"].as_str())
+        .map(str::trim)
+        .collect::<Vec<_>>()
+        .join(" ");
+    let seconds = |value: &Value| {
+        value
+            .as_str()
+            .and_then(|value| value.trim_end_matches('s').parse::<f32>().ok())
+            .unwrap_or_default()
+    };
+    // With diarization the last result repeats every word with its speaker tag
+    let word_results: Vec<&Value> = if diarization {
+        results.last().into_iter().collect()
+    } else {
+        results.iter().collect()
+    };
+    let words = word_results
+        .into_iter()
+        .flat_map(|result| result["alternatives"][0]["words"].as_array().cloned().unwrap_or_default())
+        .map(|word| SttWord {
+            word: word["word"].as_str().unwrap_or_default().to_string(),
+            start: seconds(&word["startTime"]),
+            end: seconds(&word["endTime"]),
+            confidence: word["confidence"].as_f64().map(|c| c as f32),
+            speaker: word["speakerTag"].as_u64().filter(|tag| *tag > 0).map(|tag| tag as u32),
+        })
+        .collect();
+    SttTranscript {
+        text,
+        language: results
+            .iter()
+            .find_map(|result| result["languageCode"].as_str().map(str::to_string))
+            .or(language),
+        duration: body["totalBilledTime"].as_str().map(|_| seconds(&body["totalBilledTime"])),
+        confidence: results
+            .first()
+            .and_then(|result| result["alternatives"][0]["confidence"].as_f64())
+            .map(|c| c as f32),
+        words,
+        provider: SttProviderKind::Google,
+    }
+}
+
+/// Deepgram's pre-recorded and live endpoints
+pub struct DeepgramProvider {
+    key: String,
+    model: String,
+}
+
+impl DeepgramProvider {
+    fn query(&self, language: Option<&str>, diarization: bool) -> Vec<(&'static str, String)> {
+        let mut query = vec![
+            ("model", self.model.clone()),
+            ("smart_format", "true".to_string()),
+            ("diarize", diarization.to_string()),
+        ];
+        match language {
+            Some(language) => query.push(("language", language.to_string())),
+            None => query.push(("detect_language", "true".to_string())),
+        }
+        query
+    }
+}
+
+#[async_trait]
+impl SttProvider for DeepgramProvider {
+    fn kind(&self) -> SttProviderKind {
+        SttProviderKind::Deepgram
+    }
+
+    async fn transcribe(&self, request: SttRequest) -> Result<SttTranscript, AppError> {
+        let response = crate::network::client(REQUEST_TIMEOUT)?
+            .post(DEEPGRAM_URL)
+            .query(&self.query(request.language.as_deref(), request.diarization))
+            .header("Authorization", format!("Token {}", self.key))
+            .header("Content-Type", format!("audio/{}", request.format()))
+            .body(request.audio)
+            .send()
+            .await
+            .map_err(|e| AppError::Network(format!("Deepgram request failed: {}", e)))?;
+        let body = read_response(response, "Deepgram").await?;
+        Ok(deepgram_transcript(&body, request.language, request.word_timestamps))
+    }
+
+    async fn stream(&self, language: Option<String>) -> Result<SttStream, AppError> {
+        let mut query = self.query(language.as_deref(), false);
+        query.extend([
+            ("encoding", "linear16".to_string()),
+            ("sample_rate", TARGET_SAMPLE_RATE.to_string()),
+            ("channels", "1".to_string()),
+            ("interim_results", "true".to_string()),
+            ("alternatives", STREAM_ALTERNATIVES.to_string()),
+        ]);
+        let query: Vec<String> = query.iter().map(|(name, value)| format!("{}={}", name, value)).collect();
+        let mut request = format!("{}?{}", DEEPGRAM_STREAM_URL, query.join("&"))
+            .into_client_request()
+            .map_err(|e| AppError::Internal(format!("Invalid Deepgram stream request: {}", e)))?;
+        request.headers_mut().insert(
+            "Authorization",
+            format!("Token {}", self.key)
+                .parse()
+                .map_err(|_| AppError::Configuration("The Deepgram key is not a valid header".to_string()))?,
+        );
+        let (socket, _) = tokio_tungstenite::connect_async(request)
+            .await
+            .map_err(|e| AppError::Network(format!("Could not open the Deepgram stream: {}", e)))?;
+
+        let (audio_tx, mut audio_rx) = mpsc::channel::<Vec<f32>>(STREAM_QUEUE);
+        let (results_tx, results_rx) = mpsc::channel(STREAM_QUEUE);
+        tokio::spawn(async move {
+            let (mut sink, mut incoming) = socket.split();
+            let mut sending = true;
+            loop {
+                tokio::select! {
+                    samples = audio_rx.recv(), if sending => {
+                        let message = match samples {
+                            Some(samples) => Message::Binary(
+                                samples
+                                    .iter()
+                                    .flat_map(|sample| ((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16).to_le_bytes())
+                                    .collect(),
+                            ),
+                            // Deepgram flushes what it has heard and then closes the socket
+                            None => {
+                                sending = false;
+                                Message::Text(json!({ "type": "CloseStream" }).to_string())
+                            }
+                        };
+                        if let Err(e) = sink.send(message).await {
+                            let _ = results_tx.send(Err(AppError::Network(format!("Deepgram stream failed: {}", e)))).await;
+                            break;
+                        }
+                    }
+                    message = incoming.next() => match message {
+                        Some(Ok(Message::Text(text))) => {
+                            let Ok(body) = serde_json::from_str::<Value>(&text) else {
+                                continue;
+                            };
+                            if body["type"] != "Results" {
+                                continue;
+                            }
+                            let best = &body["channel"]["alternatives"][0];
+                            let text = best["transcript"].as_str().unwrap_or_default().trim().to_string();
+                            if text.is_empty() {
+                                continue;
+                            }
+                            let is_final = body["is_final"].as_bool().unwrap_or(false);
+                            let result = SttStreamResult {
+                                text,
+                                is_final,
+                                words: deepgram_words(best),
+                                alternatives: if is_final { deepgram_alternatives(&body["channel"]) } else { Vec::new() },
+                                provider: SttProviderKind::Deepgram,
+                            };
+                            if results_tx.send(Ok(result)).await.is_err() {
+                                break;
+                            }
+                        }
+                        Some(Ok(Message::Close(_))) | None => break,
+                        Some(Ok(_)) => {}
+                        Some(Err(e)) => {
+                            let _ = results_tx.send(Err(AppError::Network(format!("Deepgram stream failed: {}", e)))).await;
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+        Ok(SttStream {
+            audio: audio_tx,
+            results: results_rx,
+        })
+    }
+}
+
+fn deepgram_transcript(body: &Value, language: Option<String>, word_timestamps: bool) -> SttTranscript {
+    let channel = &body["results"]["channels"][0];
+    let best = &channel["alternatives"][0];
+    SttTranscript {
+        text: best["transcript"].as_str().unwrap_or_default().trim().to_string(),
+        language: channel["detected_language"].as_str().map(str::to_string).or(language),
+        duration: body["metadata"]["duration"].as_f64().map(|d| d as f32),
+        confidence: best["confidence"].as_f64().map(|c| c as f32),
+        words: if word_timestamps { deepgram_words(best) } else { Vec::new() },
+        provider: SttProviderKind::Deepgram,
+    }
+}
+
+fn deepgram_alternatives(channel: &Value) -> Vec<Alternative> {
+    channel["alternatives"]
+        .as_array()
+        .map(|alternatives| {
+            alternatives
+                .iter()
+                .filter_map(|alternative| {
+                    let transcript = alternative["transcript"].as_str()?.trim();
+                    (!transcript.is_empty()).then(|| Alternative {
+                        transcript: transcript.to_string(),
+                        confidence: alternative["confidence"].as_f64().unwrap_or_default() as f32,
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn deepgram_words(alternative: &Value) -> Vec<SttWord> {
+    alternative["words"]
+        .as_array()
+        .map(|words| {
+            words
+                .iter()
+                .map(|word| SttWord {
+                    word: word["punctuated_word"]
+                        .as_str()
+                        .or(word["word"].as_str())
+                        .unwrap_or_default()
+                        .to_string(),
+                    start: word["start"].as_f64().unwrap_or_default() as f32,
+                    end: word["end"].as_f64().unwrap_or_default() as f32,
+                    confidence: word["confidence"].as_f64().map(|c| c as f32),
+                    speaker: word["speaker"].as_u64().map(|speaker| speaker as u32),
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// JSON body of a successful response; a rejected key becomes a configuration error
+async fn read_response(response: reqwest::Response, provider: &str) -> Result<Value, AppError> {
+    let status = response.status();
+    if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN {
+        return Err(AppError::Configuration(format!("{} rejected the API key", provider)));
+    }
+    if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        return Err(AppError::RateLimited { retry_after_secs: None });
+    }
+    if !status.is_success() {
+        let message = response.text().await.unwrap_or_default();
+        return Err(AppError::Upstream {
+            status: status.as_u16(),
+            message: format!("{}: {}", provider, message),
+        });
+    }
+    response
+        .json()
+        .await
+        .map_err(|e| AppError::Internal(format!("Unreadable {} response: {}", provider, e)))
+}
+
+/// Build a provider; an unconfigured cloud provider is an error rather than a silent fallback
+pub fn provider(
+    settings: &SttSettings,
+    kind: SttProviderKind,
+    gateway: &Arc<Mutex<Option<AIMLAPIGateway>>>,
+) -> Result<Box<dyn SttProvider>, AppError> {
+    if !settings.is_configured(kind) {
+        return Err(AppError::Configuration(format!(
+            "{} is selected for speech recognition but has no API key{}",
+            kind.label(),
+            if kind == SttProviderKind::Azure { " or region" } else { "" }
+        )));
+    }
+    let key = settings.key(kind).unwrap_or_default();
+    Ok(match kind {
+        SttProviderKind::Whisper => Box::new(WhisperProvider {
+            gateway: gateway.clone(),
+        }),
+        SttProviderKind::Azure => Box::new(AzureProvider {
+            key,
+            region: settings.azure.region.trim().to_string(),
+        }),
+        SttProviderKind::Google => Box::new(GoogleProvider {
+            key,
+            model: settings.google.model.clone(),
+        }),
+        SttProviderKind::Deepgram => Box::new(DeepgramProvider {
+            key,
+            model: settings.deepgram.model.clone(),
+        }),
+    })
+}
+
+/// The provider set for a language
+pub fn provider_for_language(
+    settings: &SttSettings,
+    language: &str,
+    gateway: &Arc<Mutex<Option<AIMLAPIGateway>>>,
+) -> Result<Box<dyn SttProvider>, AppError> {
+    provider(settings, settings.provider_for(language), gateway)
+}
+
+/// Every provider with its capabilities, whether it can be used and the languages routed to it
+pub fn list_providers(settings: &SttSettings) -> Vec<SttProviderInfo> {
+    SttProviderKind::ALL
+        .iter()
+        .map(|&kind| {
+            let mut languages: Vec<String> = settings
+                .by_language
+                .iter()
+                .filter(|(_, provider)| **provider == kind)
+                .map(|(language, _)| language.clone())
+                .collect();
+            languages.sort();
+            SttProviderInfo {
+                kind,
+                label: kind.label(),
+                configured: settings.is_configured(kind),
+                capabilities: kind.capabilities(),
+                languages,
+            }
+        })
+        .collect()
+}
+
+/// Batch transcription through the language's provider, asking for word timing and speakers where it has them.
+/// While that provider keeps failing, the configured fallback providers take over.
+pub async fn transcribe(
+    settings: &SttSettings,
+    failover: &ProviderFailoverSettings,
+    gateway: &Arc<Mutex<Option<AIMLAPIGateway>>>,
+    audio: Vec<u8>,
+    file_name: String,
+    language: Option<String>,
+) -> Result<SttTranscript, AppError> {
+    let primary = language
+        .as_deref()
+        .map_or(settings.default_provider, |language| settings.provider_for(language));
+    let (audio, file_name, language) = (&audio, &file_name, &language);
+    provider_failover::run(ProviderService::Stt, primary.label(), failover, move |label| async move {
+        let kind = SttProviderKind::ALL
+            .into_iter()
+            .find(|kind| kind.label() == label)
+            .unwrap_or(primary);
+        let provider = provider(settings, kind, gateway)?;
+        let capabilities = provider.capabilities();
+        provider
+            .transcribe(SttRequest {
+                audio: audio.clone(),
+                file_name: file_name.clone(),
+                language: language.clone(),
+                word_timestamps: capabilities.word_timestamps,
+                diarization: capabilities.diarization,
+            })
+            .await
+    })
+    .await
+}
+
+struct StreamSession {
+    audio: mpsc::Sender<Vec<f32>>,
+    resampler: Option<(u32, Resampler)>,
+}
+
+fn stream_session() -> &'static Mutex<Option<StreamSession>> {
+    static SESSION: OnceLock<Mutex<Option<StreamSession>>> = OnceLock::new();
+    SESSION.get_or_init(|| Mutex::new(None))
+}
+
+/// Open a streaming recognition with the language's provider, replacing any open one.
+/// Results are handed to `on_result` until the stream ends.
+pub async fn start_stream<F>(
+    settings: &SttSettings,
+    language: &str,
+    gateway: &Arc<Mutex<Option<AIMLAPIGateway>>>,
+    mut on_result: F,
+) -> Result<SttProviderKind, AppError>
+where
+    F: FnMut(Result<SttStreamResult, AppError>) + Send + 'static,
+{
+    let provider = provider_for_language(settings, language, gateway)?;
+    if !provider.capabilities().streaming {
+        return Err(AppError::Configuration(format!(
+            "{} (set for {}) cannot stream; pick a streaming provider such as Deepgram",
+            provider.kind().label(),
+            language
+        )));
+    }
+    let SttStream { audio, mut results } = provider.stream(Some(language.to_string())).await?;
+    tokio::spawn(async move {
+        while let Some(result) = results.recv().await {
+            on_result(result);
+        }
+    });
+    *stream_session().lock().await = Some(StreamSession { audio, resampler: None });
+    Ok(provider.kind())
+}
+
+/// Feed microphone audio, mono at its own rate, to the open stream; the app's own playback is cancelled out first
+pub async fn push_stream_audio(samples: &[f32], sample_rate: u32) -> Result<(), AppError> {
+    send_stream_audio(samples, sample_rate, true).await
+}
+
+/// Feed audio that has already been through echo cancellation, such as the multi-microphone mix
+pub async fn push_cancelled_stream_audio(samples: &[f32], sample_rate: u32) -> Result<(), AppError> {
+    send_stream_audio(samples, sample_rate, false).await
+}
+
+async fn send_stream_audio(samples: &[f32], sample_rate: u32, cancel_echo: bool) -> Result<(), AppError> {
+    let mut session = stream_session().lock().await;
+    let session = session
+        .as_mut()
+        .ok_or_else(|| AppError::NotInitialized("Speech stream".to_string()))?;
+    if session.resampler.as_ref().map_or(true, |(rate, _)| *rate != sample_rate) {
+        session.resampler = Some((sample_rate, Resampler::new(sample_rate, TARGET_SAMPLE_RATE)));
+    }
+    let mut samples = match session.resampler.as_mut() {
+        Some((_, resampler)) => resampler.process(samples),
+        None => samples.to_vec(),
+    };
+    // The canceller runs at the stream's rate, so it sees the frame after resampling
+    if cancel_echo {
+        echo_cancellation::process_capture(&mut samples);
+    }
+    session
+        .audio
+        .send(samples)
+        .await
+        .map_err(|_| AppError::Network("The speech stream has closed".to_string()))
+}
+
+/// End the audio; the provider's last results still arrive
+pub async fn stop_stream() -> bool {
+    stream_session().lock().await.take().is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_language_code_wins_over_base_language() {
+        let mut settings = SttSettings {
+            default_provider: SttProviderKind::Whisper,
+            ..SttSettings::default()
+        };
+        settings.by_language.insert("pt".to_string(), SttProviderKind::Google);
+        settings.by_language.insert("pt-BR".to_string(), SttProviderKind::Deepgram);
+        assert_eq!(settings.provider_for("pt-BR"), SttProviderKind::Deepgram);
+        assert_eq!(settings.provider_for("pt-PT"), SttProviderKind::Google);
+        assert_eq!(settings.provider_for("de-DE"), SttProviderKind::Whisper);
+    }
+
+    #[test]
+    fn saved_settings_carry_no_keys() {
+        let mut settings = SttSettings::default();
+        settings.azure.api_key = Some("azure".to_string());
+        settings.deepgram.api_key = Some("deepgram".to_string());
+        let saved = settings.without_keys();
+        assert_eq!(saved.azure.api_key, None);
+        assert_eq!(saved.deepgram.api_key, None);
+
+        let mut switched = saved.clone();
+        switched.carry_keys_from(&settings);
+        assert_eq!(switched, settings);
+    }
+
+    #[test]
+    fn azure_ticks_become_seconds() {
+        let body = json!({
+            "RecognitionStatus": "Success",
+            "DisplayText": " Hello world. ",
+            "Duration": 15_000_000,
+            "NBest": [{
+                "Confidence": 0.9,
+                "Words": [
+                    { "Word": "hello", "Offset": 5_000_000, "Duration": 4_000_000, "Confidence": 0.95 },
+                    { "Word": "world", "Offset": 10_000_000, "Duration": 5_000_000 }
+                ]
+            }]
+        });
+        let transcript = azure_transcript(&body, "en-US".to_string()).unwrap();
+        assert_eq!(transcript.text, "Hello world.");
+        assert_eq!(transcript.duration, Some(1.5));
+        assert_eq!(transcript.words.len(), 2);
+        assert!((transcript.words[0].start - 0.5).abs() < 1e-6);
+        assert!((transcript.words[0].end - 0.9).abs() < 1e-6);
+        assert_eq!(transcript.words[1].confidence, None);
+    }
+
+    #[test]
+    fn azure_failure_status_is_an_error() {
+        let body = json!({ "RecognitionStatus": "InitialSilenceTimeout" });
+        assert!(azure_transcript(&body, "en-US".to_string()).is_err());
+    }
+
+    #[test]
+    fn google_diarized_words_come_from_the_last_result() {
+        let body = json!({
+            "totalBilledTime": "15s",
+            "results": [
+                {
+                    "languageCode": "en-us",
+                    "alternatives": [{
+                        "transcript": "hi there",
+                        "confidence": 0.8,
+                        "words": [
+                            { "word": "hi", "startTime": "0s", "endTime": "0.400s" },
+                            { "word": "there", "startTime": "0.400s", "endTime": "0.900s" }
+                        ]
+                    }]
+                },
+                {
+                    "alternatives": [{
+                        "words": [
+                            { "word": "hi", "startTime": "0s", "endTime": "0.400s", "speakerTag": 1 },
+                            { "word": "there", "startTime": "0.400s", "endTime": "0.900s", "speakerTag": 2 }
+                        ]
+                    }]
+                }
+            ]
+        });
+        let transcript = google_transcript(&body, Some("en-US".to_string()), true);
+        assert_eq!(transcript.text, "hi there");
+        assert_eq!(transcript.language.as_deref(), Some("en-us"));
+        assert_eq!(transcript.duration, Some(15.0));
+        let speakers: Vec<_> = transcript.words.iter().map(|word| word.speaker).collect();
+        assert_eq!(speakers, vec![Some(1), Some(2)]);
+        assert!((transcript.words[1].end - 0.9).abs() < 1e-6);
+
+        let undiarized = google_transcript(&body, None, false);
+        assert_eq!(undiarized.words.len(), 4);
+    }
+
+    #[test]
+    fn deepgram_keeps_punctuated_words_and_speakers() {
+        let body = json!({
+            "metadata": { "duration": 2.5 },
+            "results": { "channels": [{
+                "detected_language": "en",
+                "alternatives": [
+                    {
+                        "transcript": "Hello, world.",
+                        "confidence": 0.97,
+                        "words": [
+                            { "word": "hello", "punctuated_word": "Hello,", "start": 0.1, "end": 0.5, "speaker": 0 },
+                            { "word": "world", "start": 0.6, "end": 1.0, "speaker": 1 }
+                        ]
+                    },
+                    { "transcript": "Hello world", "confidence": 0.6 },
+                    { "transcript": " ", "confidence": 0.1 }
+                ]
+            }] }
+        });
+        let transcript = deepgram_transcript(&body, Some("en-US".to_string()), true);
+        assert_eq!(transcript.text, "Hello, world.");
+        assert_eq!(transcript.language.as_deref(), Some("en"));
+        assert_eq!(transcript.duration, Some(2.5));
+        let words: Vec<_> = transcript.words.iter().map(|word| (word.word.as_str(), word.speaker)).collect();
+        assert_eq!(words, vec![("Hello,", Some(0)), ("world", Some(1))]);
+        assert!(deepgram_transcript(&body, None, false).words.is_empty());
+
+        let alternatives = deepgram_alternatives(&body["results"]["channels"][0]);
+        let transcripts: Vec<_> = alternatives.iter().map(|a| a.transcript.as_str()).collect();
+        assert_eq!(transcripts, vec!["Hello, world.", "Hello world"]);
+    }
+}
@@ -0,0 +1,194 @@
+// Long Text Chunking Module
+// Splits long documents on semantic boundaries so each model call stays within context limits
+
+use serde::{Deserialize, Serialize};
+
+use crate::unicode_text::split_sentences;
+
+/// Chunking configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkingConfig {
+    pub max_chunk_chars: usize,
+    pub overlap_sentences: usize,
+    /// Language of the document, for sentence boundaries; abbreviations like "z.B." differ between languages
+    #[serde(default)]
+    pub language: Option<String>,
+}
+
+impl Default for ChunkingConfig {
+    fn default() -> Self {
+        Self {
+            max_chunk_chars: 4000,
+            overlap_sentences: 2,
+            language: None,
+        }
+    }
+}
+
+/// Operation applied to each chunk of a long document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum LongTextOperation {
+    Enhance { tone: String },
+    Translate { source_language: Option<String>, target_language: String },
+}
+
+/// A single chunk of a long document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TextChunk {
+    pub index: usize,
+    /// Chunk body with surrounding whitespace removed
+    pub content: String,
+    /// Whitespace that preceded the body inside the chunk
+    pub leading_whitespace: String,
+    /// Whitespace and separators that followed the body, restored on reassembly
+    pub trailing_separator: String,
+    /// Last few sentences of the previous chunk, given to the model for continuity only
+    pub preceding_context: Option<String>,
+}
+
+/// Progress report for chunked processing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkProgress {
+    pub request_id: String,
+    pub chunk_index: usize,
+    pub total_chunks: usize,
+    pub succeeded: bool,
+    pub progress: f32, // 0.0 to 100.0
+}
+
+/// Result of processing a long document chunk by chunk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LongTextResult {
+    pub id: String,
+    pub original_text: String,
+    pub processed_text: String,
+    pub total_chunks: usize,
+    pub failed_chunks: Vec<usize>,
+    pub processing_time_ms: u64,
+}
+
+/// Split text into chunks no longer than `max_chunk_chars`, preferring paragraph then sentence boundaries
+pub fn split_into_chunks(text: &str, config: &ChunkingConfig) -> Vec<TextChunk> {
+    let max_chars = config.max_chunk_chars.max(200);
+    let language = config.language.as_deref().unwrap_or_default();
+    let mut segments: Vec<String> = Vec::new();
+
+    // Paragraphs keep their separators attached so the original layout can be restored
+    for paragraph in split_keeping_separators(text, "\n\n") {
+        if paragraph.chars().count() <= max_chars {
+            segments.push(paragraph);
+            continue;
+        }
+        for sentence in split_sentences(&paragraph, language) {
+            if sentence.chars().count() <= max_chars {
+                segments.push(sentence);
+            } else {
+                segments.extend(split_on_words(&sentence, max_chars));
+            }
+        }
+    }
+
+    // Greedily pack segments into chunks
+    let mut raw_chunks: Vec<String> = Vec::new();
+    let mut current = String::new();
+    for segment in segments {
+        if !current.is_empty() && current.chars().count() + segment.chars().count() > max_chars {
+            raw_chunks.push(std::mem::take(&mut current));
+        }
+        current.push_str(&segment);
+    }
+    if !current.is_empty() {
+        raw_chunks.push(current);
+    }
+
+    let mut chunks = Vec::with_capacity(raw_chunks.len());
+    let mut previous_body: Option<String> = None;
+    for (index, raw) in raw_chunks.into_iter().enumerate() {
+        let body_start = raw.len() - raw.trim_start().len();
+        let body_end = raw.trim_end().len();
+        let (leading, body, trailing) = if body_start >= body_end {
+            (String::new(), String::new(), raw.clone())
+        } else {
+            (
+                raw[..body_start].to_string(),
+                raw[body_start..body_end].to_string(),
+                raw[body_end..].to_string(),
+            )
+        };
+
+        let preceding_context = previous_body
+            .as_deref()
+            .map(|prev| last_sentences(prev, config.overlap_sentences, language))
+            .filter(|ctx| !ctx.is_empty());
+
+        if !body.is_empty() {
+            previous_body = Some(body.clone());
+        }
+
+        chunks.push(TextChunk {
+            index,
+            content: body,
+            leading_whitespace: leading,
+            trailing_separator: trailing,
+            preceding_context,
+        });
+    }
+
+    chunks
+}
+
+/// Reassemble processed chunk bodies, restoring the original whitespace around each one
+pub fn reassemble_chunks(chunks: &[TextChunk], processed: &[String]) -> String {
+    let mut output = String::new();
+    for (chunk, body) in chunks.iter().zip(processed.iter()) {
+        output.push_str(&chunk.leading_whitespace);
+        output.push_str(body.trim());
+        output.push_str(&chunk.trailing_separator);
+    }
+    output
+}
+
+/// Split text on a separator, leaving the separator attached to the preceding piece
+fn split_keeping_separators(text: &str, separator: &str) -> Vec<String> {
+    let mut pieces = Vec::new();
+    let mut rest = text;
+    while let Some(pos) = rest.find(separator) {
+        let mut end = pos + separator.len();
+        // Fold any extra blank lines into the same separator
+        while rest[end..].starts_with('\n') {
+            end += 1;
+        }
+        pieces.push(rest[..end].to_string());
+        rest = &rest[end..];
+    }
+    if !rest.is_empty() {
+        pieces.push(rest.to_string());
+    }
+    pieces
+}
+
+/// Last resort for run-on text without sentence punctuation
+fn split_on_words(text: &str, max_chars: usize) -> Vec<String> {
+    let mut pieces = Vec::new();
+    let mut current = String::new();
+    for word in text.split_inclusive(char::is_whitespace) {
+        if !current.is_empty() && current.chars().count() + word.chars().count() > max_chars {
+            pieces.push(std::mem::take(&mut current));
+        }
+        current.push_str(word);
+    }
+    if !current.is_empty() {
+        pieces.push(current);
+    }
+    pieces
+}
+
+/// The last `count` sentences of a chunk, used as overlapping context
+fn last_sentences(text: &str, count: usize, language: &str) -> String {
+    if count == 0 {
+        return String::new();
+    }
+    let sentences = split_sentences(text, language);
+    let start = sentences.len().saturating_sub(count);
+    sentences[start..].concat().trim().to_string()
+}
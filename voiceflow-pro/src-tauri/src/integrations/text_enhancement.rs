@@ -6,13 +6,26 @@ use tokio::sync::Mutex;
 use uuid::Uuid;
 
 use super::ai_ml_core::{AIMLClient, AIMLError, AIMLMessage, AIMLService};
+use crate::integrations::context::EnhancedContext;
+use crate::integrations::result_cache::{request_key, CacheKind, CacheStats, CacheValue, PersistentCache};
+use crate::integrations::token_budget::TokenBudget;
+
+/// Cached results kept while the app is in use
+const ENHANCEMENT_CACHE_CAPACITY: usize = 100;
+
+/// Reply room held back from the context window for an enhancement
+const ENHANCEMENT_MAX_TOKENS: usize = 2000;
 
 /// Text Enhancement Service
 #[derive(Debug)]
 pub struct TextEnhancer {
     client: Arc<Mutex<AIMLClient>>,
     model: String,
-    enhancement_cache: tokio::sync::Mutex<lru::LruCache<String, EnhancementResult>>,
+    enhancement_cache: tokio::sync::Mutex<PersistentCache<EnhancementResult>>,
+    /// Project terms the model must not rephrase or respell
+    preserved_terms: Vec<String>,
+    /// Instructions and glossary from the active domain pack
+    domain_instructions: Option<String>,
 }
 
 /// Text enhancement request
@@ -36,6 +49,20 @@ pub struct EnhancementContext {
     pub examples: Vec<String>,
 }
 
+impl From<EnhancedContext> for EnhancementContext {
+    fn from(context: EnhancedContext) -> Self {
+        Self {
+            domain: context.domain.unwrap_or_else(|| "general".to_string()),
+            audience: context.audience.unwrap_or_else(|| "general".to_string()),
+            purpose: context.purpose.or(context.user_intent).unwrap_or_else(|| "communication".to_string()),
+            format: "text".to_string(),
+            constraints: context.constraints,
+            // Recent messages show the enhancer how this user writes
+            examples: context.previous_messages,
+        }
+    }
+}
+
 /// Enhancement options
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct EnhancementOptions {
@@ -61,6 +88,9 @@ pub struct EnhancementResult {
     pub tokens_used: u32,
 }
 
+impl CacheValue for EnhancementResult {}
+
+
 /// Individual improvement made
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct EnhancementImprovement {
@@ -230,23 +260,59 @@ impl TextEnhancer {
         Self {
             client,
             model,
-            enhancement_cache: tokio::sync::Mutex::new(lru::LruCache::new(100)), // Cache 100 results
+            enhancement_cache: tokio::sync::Mutex::new(PersistentCache::new(CacheKind::Enhancement, ENHANCEMENT_CACHE_CAPACITY)),
+            preserved_terms: Vec::new(),
+            domain_instructions: None,
         }
     }
 
+    /// Replace the must-preserve terms; cached results were made without them, so the cache switches namespace
+    pub async fn set_preserved_terms(&mut self, terms: Vec<String>) {
+        if self.preserved_terms != terms {
+            self.preserved_terms = terms;
+            self.update_cache_namespace().await;
+        }
+    }
+
+    /// Replace the domain pack instructions; cached results were made without them, so the cache switches namespace
+    pub async fn set_domain_instructions(&mut self, instructions: Option<String>) {
+        if self.domain_instructions != instructions {
+            self.domain_instructions = instructions;
+            self.update_cache_namespace().await;
+        }
+    }
+
+    async fn update_cache_namespace(&self) {
+        self.enhancement_cache
+            .lock()
+            .await
+            .set_namespace(&(&self.preserved_terms, &self.domain_instructions));
+    }
+
     /// Enhance text with AI assistance
     pub async fn enhance_text(&self, request: EnhancementRequest) -> Result<EnhancementResult, AIMLError> {
+        self.enhance_text_with_model(request, &self.model).await
+    }
+
+    /// Enhance text with a model other than the configured one, e.g. a fallback while it is down
+    pub async fn enhance_text_with_model(
+        &self,
+        request: EnhancementRequest,
+        model: &str,
+    ) -> Result<EnhancementResult, AIMLError> {
         let start_time = std::time::Instant::now();
 
         // Check cache first
-        let cache_key = self.generate_cache_key(&request);
-        if let Some(cached_result) = self.enhancement_cache.lock().await.get(&cache_key) {
+        let cache_key = request_cache_key(model, &request);
+        if let Some(cached_result) = self.enhancement_cache.lock().await.get(&cache_key).await {
             log::debug!("Returning cached enhancement result");
-            return Ok(cached_result.clone());
+            return Ok(cached_result);
         }
 
-        // Prepare enhancement instructions
-        let instructions = self.build_enhancement_instructions(&request);
+        // Prepare enhancement instructions, with constraints fitted to the model's window
+        let mut budget = TokenBudget::new(model, ENHANCEMENT_MAX_TOKENS);
+        budget.reserve(&request.text);
+        let instructions = self.build_enhancement_instructions(&request, &mut budget);
         
         // Create system prompt
         let system_prompt = format!(
@@ -280,9 +346,9 @@ impl TextEnhancer {
         ];
 
         let response = client.chat_completion(super::ai_ml_core::AIMLRequest {
-            model: self.model.clone(),
+            model: model.to_string(),
             messages,
-            max_tokens: Some(2000),
+            max_tokens: Some(ENHANCEMENT_MAX_TOKENS as u32),
             temperature: Some(0.3), // Lower temperature for consistent enhancements
             stream: Some(false),
             top_p: Some(0.9),
@@ -310,8 +376,8 @@ impl TextEnhancer {
             };
 
             // Cache the result
-            let cache_key = self.generate_cache_key(&request);
-            self.enhancement_cache.lock().await.put(cache_key, result.clone());
+            let cache_key = request_cache_key(model, &request);
+            self.enhancement_cache.lock().await.put(cache_key, result.clone()).await;
 
             Ok(result)
         } else {
@@ -508,14 +574,47 @@ impl TextEnhancer {
         self.enhance_text(style_request).await
     }
 
+    /// Number of cached results
+    pub async fn cached_entries(&self) -> usize {
+        self.enhancement_cache.lock().await.len()
+    }
+
+    /// Drop all cached results from memory and disk, returning how many were removed
+    pub async fn clear_cache(&self) -> usize {
+        self.enhancement_cache.lock().await.clear().await
+    }
+
+    /// Load recent results saved by earlier runs
+    pub async fn warm_cache(&self) -> usize {
+        self.enhancement_cache.lock().await.warm_load().await
+    }
+
+    pub async fn cache_stats(&self) -> CacheStats {
+        self.enhancement_cache.lock().await.stats()
+    }
+
+    /// Cap the cache below its normal size, evicting the oldest entries; `None` restores the normal size
+    pub async fn limit_cache(&self, limit: Option<usize>) {
+        let capacity = limit.unwrap_or(ENHANCEMENT_CACHE_CAPACITY).clamp(1, ENHANCEMENT_CACHE_CAPACITY);
+        if let Some(capacity) = std::num::NonZeroUsize::new(capacity) {
+            self.enhancement_cache.lock().await.resize(capacity);
+        }
+    }
+
     /// Check service health
     pub async fn health_check(&self) -> Result<bool, AIMLError> {
         let client = self.client.lock().await;
         client.health_check().await
     }
 
-    /// Build enhancement instructions based on options
-    fn build_enhancement_instructions(&self, request: &EnhancementRequest) -> String {
+    /// Build enhancement instructions based on options; constraints are kept as far as `budget` allows
+    fn build_enhancement_instructions(&self, request: &EnhancementRequest, budget: &mut TokenBudget) -> String {
+        let preserve_terms = (!self.preserved_terms.is_empty()).then(|| {
+            format!(
+                "• Keep these terms exactly as written, with the same spelling and capitalization: {}",
+                self.preserved_terms.join(", ")
+            )
+        });
         let mut instructions = Vec::new();
 
         if request.options.improve_clarity {
@@ -540,8 +639,23 @@ impl TextEnhancer {
             instructions.push("• Preserve the original meaning and intent");
         }
 
-        if request.context.constraints.len() > 0 {
-            instructions.push(&format!("• Respect these constraints: {}", request.context.constraints.join(", ")));
+        if let Some(preserve_terms) = &preserve_terms {
+            instructions.push(preserve_terms.as_str());
+        }
+
+        budget.reserve(&instructions.join("\n"));
+        if let Some(domain) = &self.domain_instructions {
+            budget.reserve(domain);
+        }
+        let constraints = budget.fit_constraints(&request.context.constraints);
+        let constraints = (!constraints.is_empty())
+            .then(|| format!("• Respect these constraints: {}", constraints.join(", ")));
+        if let Some(constraints) = &constraints {
+            instructions.push(constraints.as_str());
+        }
+
+        if let Some(domain) = &self.domain_instructions {
+            instructions.push(domain.as_str());
         }
 
         instructions.join("\n")
@@ -585,17 +699,6 @@ impl TextEnhancer {
         (avg_impact + 0.2).min(1.0) // Boost confidence slightly
     }
 
-    /// Generate cache key for request
-    fn generate_cache_key(&self, request: &EnhancementRequest) -> String {
-        use std::collections::hash_map::DefaultHasher;
-        use std::hash::{Hash, Hasher};
-
-        let mut hasher = DefaultHasher::new();
-        request.text.hash(&mut hasher);
-        request.context.domain.hash(&mut hasher);
-        request.tone.hash(&mut hasher);
-        format!("{:x}", hasher.finish())
-    }
 
     /// Extract key points from summary
     fn extract_key_points(&self, summary: &str) -> Vec<String> {
@@ -687,3 +790,35 @@ impl TextEnhancer {
         (score / 206.835).max(0.0).min(1.0) * 100.0
     }
 }
+
+/// Every field but the request ID shapes the enhancement, so all of them go into the key
+fn request_cache_key(model: &str, request: &EnhancementRequest) -> String {
+    request_key(model, request, &["/id"])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integrations::result_cache::fields_outside_key;
+
+    #[test]
+    fn every_option_but_the_request_id_is_part_of_the_cache_key() {
+        let request = EnhancementRequest {
+            id: "first".to_string(),
+            text: "their going to the meeting".to_string(),
+            context: EnhancedContext::default().into(),
+            tone: "professional".to_string(),
+            options: EnhancementOptions {
+                improve_clarity: true,
+                fix_grammar: true,
+                enhance_style: false,
+                adjust_tone: false,
+                remove_redundancy: false,
+                improve_readability: false,
+                preserve_meaning: true,
+                maintain_length: false,
+            },
+        };
+        assert_eq!(fields_outside_key(&request, |request| request_cache_key("gpt", request)), vec!["/id"]);
+    }
+}
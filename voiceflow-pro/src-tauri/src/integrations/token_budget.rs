@@ -0,0 +1,261 @@
+// Token Budget Module
+// Counts prompt tokens with the target model's tokenizer and trims history and constraints to fit its context window
+
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+use tiktoken_rs::CoreBPE;
+
+use super::ai_ml_api::AIMLMessage;
+use super::model_registry::get_model_registry;
+
+/// Chat format overhead per message (role and separators), as counted by OpenAI's cookbook
+const TOKENS_PER_MESSAGE: usize = 3;
+/// Every reply is primed with `<|start|>assistant<|message|>`
+const REPLY_PRIMING_TOKENS: usize = 3;
+/// Bullet and newline added around each history entry or constraint in a prompt
+const TOKENS_PER_ITEM: usize = 2;
+/// Largest summary of dropped history, however roomy the window
+const MAX_SUMMARY_TOKENS: usize = 512;
+/// Each dropped message contributes at most this much to the summary
+const SUMMARY_TOKENS_PER_MESSAGE: usize = 40;
+/// Window assumed for models the registry does not know or lists without one
+const DEFAULT_CONTEXT_TOKENS: usize = 8_192;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenEncoding {
+    /// GPT-4o, GPT-4.1, GPT-5 and the o-series
+    O200k,
+    /// GPT-4 and GPT-3.5; also the closest public match for other providers
+    Cl100k,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelLimits {
+    pub context_tokens: usize,
+    pub encoding: TokenEncoding,
+}
+
+static O200K: OnceLock<Option<CoreBPE>> = OnceLock::new();
+static CL100K: OnceLock<Option<CoreBPE>> = OnceLock::new();
+
+/// Context window and tokenizer for `model` from the model registry; provider prefixes like "openai/" are ignored
+pub fn model_limits(model: &str) -> ModelLimits {
+    let registry = get_model_registry();
+    let entry = registry.get(model);
+    ModelLimits {
+        context_tokens: entry
+            .and_then(|entry| entry.context_tokens)
+            .unwrap_or(DEFAULT_CONTEXT_TOKENS),
+        encoding: entry.and_then(|entry| entry.encoding).unwrap_or(TokenEncoding::Cl100k),
+    }
+}
+
+/// Tokenizers are built once; a failed build falls back to the four-characters-per-token estimate
+fn tokenizer(encoding: TokenEncoding) -> Option<&'static CoreBPE> {
+    let (cell, build): (_, fn() -> anyhow::Result<CoreBPE>) = match encoding {
+        TokenEncoding::O200k => (&O200K, tiktoken_rs::o200k_base),
+        TokenEncoding::Cl100k => (&CL100K, tiktoken_rs::cl100k_base),
+    };
+    cell.get_or_init(|| {
+        build()
+            .map_err(|e| log::warn!("Tokenizer {:?} unavailable, estimating token counts: {}", encoding, e))
+            .ok()
+    })
+    .as_ref()
+}
+
+pub fn count_tokens(model: &str, text: &str) -> usize {
+    if text.is_empty() {
+        return 0;
+    }
+    match tokenizer(model_limits(model).encoding) {
+        Some(bpe) => bpe.encode_with_special_tokens(text).len(),
+        None => (text.len() + 3) / 4,
+    }
+}
+
+/// Prompt tokens a chat request with `messages` is billed for
+pub fn count_messages(model: &str, messages: &[AIMLMessage]) -> usize {
+    messages
+        .iter()
+        .map(|message| TOKENS_PER_MESSAGE + count_tokens(model, &message.role) + count_tokens(model, &message.content))
+        .sum::<usize>()
+        + REPLY_PRIMING_TOKENS
+}
+
+/// The longest prefix of `text` that fits in `max_tokens`, cut at a word boundary where there is one
+pub fn truncate_to_tokens(model: &str, text: &str, max_tokens: usize) -> String {
+    if count_tokens(model, text) <= max_tokens {
+        return text.to_string();
+    }
+    let boundaries: Vec<usize> = text.char_indices().map(|(at, _)| at).chain(std::iter::once(text.len())).collect();
+    let (mut low, mut high) = (0, boundaries.len() - 1);
+    while low < high {
+        let mid = (low + high + 1) / 2;
+        if count_tokens(model, &text[..boundaries[mid]]) <= max_tokens {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+    let prefix = &text[..boundaries[low]];
+    match prefix.rfind(char::is_whitespace) {
+        Some(at) if at > prefix.len() / 2 => prefix[..at].trim_end().to_string(),
+        _ => prefix.to_string(),
+    }
+}
+
+/// Token accounting reported with a result
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TokenUsage {
+    pub context_window: usize,
+    /// Tokens in the request as sent, counted with the model's tokenizer
+    pub prompt_tokens: usize,
+    /// Held back for the reply
+    pub reserved_output_tokens: usize,
+    pub history_tokens: usize,
+    pub constraint_tokens: usize,
+    pub history_messages_kept: usize,
+    /// Older messages left out, folded into the summary when there was room for one
+    pub history_messages_dropped: usize,
+    pub history_summarized: bool,
+    pub constraints_dropped: usize,
+}
+
+/// History that fits, oldest first, and a summary of what did not
+#[derive(Debug, Clone, Default)]
+pub struct FittedHistory {
+    pub messages: Vec<String>,
+    pub summary: Option<String>,
+}
+
+/// Spends one request's context window: fixed prompt parts first, then constraints, then history newest-first
+#[derive(Debug, Clone)]
+pub struct TokenBudget {
+    model: String,
+    remaining: usize,
+    usage: TokenUsage,
+}
+
+impl TokenBudget {
+    pub fn new(model: &str, reserved_output_tokens: usize) -> Self {
+        let context_window = model_limits(model).context_tokens;
+        Self {
+            model: model.to_string(),
+            remaining: context_window.saturating_sub(reserved_output_tokens + REPLY_PRIMING_TOKENS),
+            usage: TokenUsage {
+                context_window,
+                reserved_output_tokens,
+                ..TokenUsage::default()
+            },
+        }
+    }
+
+    /// Count a part of the prompt that is always sent, such as the instructions or the user's text
+    pub fn reserve(&mut self, text: &str) {
+        let tokens = TOKENS_PER_MESSAGE + count_tokens(&self.model, text);
+        self.remaining = self.remaining.saturating_sub(tokens);
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.remaining
+    }
+
+    /// Keep constraints in order within half of what is left; later ones are dropped
+    pub fn fit_constraints(&mut self, constraints: &[String]) -> Vec<String> {
+        let limit = self.remaining / 2;
+        let mut used = 0;
+        let mut kept = Vec::new();
+        for constraint in constraints {
+            let tokens = TOKENS_PER_ITEM + count_tokens(&self.model, constraint);
+            if used + tokens > limit {
+                break;
+            }
+            used += tokens;
+            kept.push(constraint.clone());
+        }
+        self.usage.constraints_dropped = constraints.len() - kept.len();
+        self.usage.constraint_tokens = used;
+        self.remaining -= used;
+        kept
+    }
+
+    /// Keep the newest `history` (oldest first) that fits; when some has to go, a share of the room
+    /// goes to a summary of the dropped messages' opening sentences
+    pub fn fit_history(&mut self, history: &[String]) -> FittedHistory {
+        let costs: Vec<usize> = history
+            .iter()
+            .map(|message| TOKENS_PER_ITEM + count_tokens(&self.model, message))
+            .collect();
+        let total: usize = costs.iter().sum();
+        if total <= self.remaining {
+            self.remaining -= total;
+            self.usage.history_tokens = total;
+            self.usage.history_messages_kept = history.len();
+            return FittedHistory {
+                messages: history.to_vec(),
+                summary: None,
+            };
+        }
+
+        let summary_budget = (self.remaining / 8).min(MAX_SUMMARY_TOKENS);
+        let limit = self.remaining - summary_budget;
+        let mut used = 0;
+        let mut first_kept = history.len();
+        for (index, cost) in costs.iter().enumerate().rev() {
+            if used + cost > limit {
+                break;
+            }
+            used += cost;
+            first_kept = index;
+        }
+
+        let dropped = &history[..first_kept];
+        let summary = self.summarize(dropped, summary_budget);
+        let summary_tokens = summary
+            .as_ref()
+            .map_or(0, |summary| TOKENS_PER_ITEM + count_tokens(&self.model, summary));
+        used += summary_tokens;
+
+        self.remaining -= used;
+        self.usage.history_tokens = used;
+        self.usage.history_messages_kept = history.len() - first_kept;
+        self.usage.history_messages_dropped = first_kept;
+        self.usage.history_summarized = summary.is_some();
+        FittedHistory {
+            messages: history[first_kept..].to_vec(),
+            summary,
+        }
+    }
+
+    /// Extractive: the opening sentence of each dropped message, oldest first, cut to the budget
+    fn summarize(&self, dropped: &[String], budget: usize) -> Option<String> {
+        let budget = budget.saturating_sub(TOKENS_PER_ITEM);
+        if dropped.is_empty() || budget < SUMMARY_TOKENS_PER_MESSAGE / 2 {
+            return None;
+        }
+        let points: Vec<String> = dropped
+            .iter()
+            .filter_map(|message| {
+                let message = message.trim();
+                let end = message.find(['.', '!', '?', '\n']).map_or(message.len(), |at| at + 1);
+                let sentence = message[..end].trim();
+                (!sentence.is_empty()).then(|| truncate_to_tokens(&self.model, sentence, SUMMARY_TOKENS_PER_MESSAGE))
+            })
+            .collect();
+        if points.is_empty() {
+            return None;
+        }
+        let summary = format!("Earlier in the conversation ({} messages): {}", dropped.len(), points.join(" / "));
+        Some(truncate_to_tokens(&self.model, &summary, budget))
+    }
+
+    /// Final accounting, with the prompt counted as it will be sent
+    pub fn finish(mut self, messages: &[AIMLMessage]) -> TokenUsage {
+        self.usage.prompt_tokens = count_messages(&self.model, messages);
+        self.usage
+    }
+}
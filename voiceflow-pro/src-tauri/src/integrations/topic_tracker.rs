@@ -0,0 +1,276 @@
+// Topic Tracker Module
+// Follows the topic of a dictation session from keyword profiles, on-device and incrementally, one segment at a time
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+/// Segments with fewer keywords than this stay with the current topic
+const MIN_SEGMENT_KEYWORDS: usize = 3;
+/// Below this cosine similarity a segment is off the current topic
+const SHIFT_THRESHOLD: f32 = 0.12;
+/// Below this the change is reported as abrupt rather than gradual
+const ABRUPT_THRESHOLD: f32 = 0.03;
+/// Weight kept by the topic profile for each segment that follows
+const PROFILE_DECAY: f32 = 0.85;
+/// Keywords in a topic label
+const LABEL_KEYWORDS: usize = 3;
+const MAX_PROFILE_TERMS: usize = 200;
+const MAX_SHIFTS_PER_SESSION: usize = 100;
+/// Sessions whose topics are kept for `topic_state`
+const MAX_SESSIONS: usize = 16;
+
+const STOPWORDS: &[&str] = &[
+    "about", "above", "after", "again", "against", "all", "also", "and", "any", "are", "because", "been", "before",
+    "being", "below", "between", "both", "but", "can", "could", "did", "does", "doing", "done", "down", "during",
+    "each", "even", "few", "for", "from", "further", "get", "gets", "getting", "going", "gonna", "got", "had", "has",
+    "have", "having", "her", "here", "hers", "herself", "him", "himself", "his", "how", "into", "its", "itself",
+    "just", "know", "like", "make", "many", "may", "maybe", "might", "more", "most", "much", "must", "need", "not",
+    "now", "off", "okay", "once", "one", "only", "other", "our", "ours", "out", "over", "own", "really", "right",
+    "said", "same", "say", "see", "she", "should", "some", "something", "still", "such", "sure", "take", "than",
+    "that", "the", "their", "theirs", "them", "then", "there", "these", "they", "thing", "things", "think", "this",
+    "those", "through", "too", "under", "until", "very", "want", "was", "way", "well", "were", "what", "when",
+    "where", "which", "while", "who", "whom", "why", "will", "with", "would", "yeah", "yes", "you", "your",
+    "yours", "yourself", "let", "lets", "use", "used", "using", "new", "good", "great", "two", "three",
+];
+
+/// A change of topic, emitted as `topic-changed`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TopicChange {
+    pub session_id: Option<String>,
+    pub from: Option<String>,
+    pub to: String,
+    pub keywords: Vec<String>,
+    /// Similarity of the new segments to the old topic, 0-1
+    pub similarity: f32,
+    pub abrupt: bool,
+    /// Index of the first segment on the new topic, counted from the start of the session
+    pub at_segment: usize,
+    pub timestamp: u64,
+}
+
+/// Topic of one observed segment
+#[derive(Debug, Clone, Default)]
+pub struct TopicObservation {
+    pub topic: Option<String>,
+    pub change: Option<TopicChange>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TopicState {
+    pub session_id: Option<String>,
+    pub current_topic: Option<String>,
+    /// Strongest keywords of the current topic, strongest first
+    pub keywords: Vec<String>,
+    pub segments: usize,
+    pub shifts: Vec<TopicChange>,
+}
+
+/// Incremental keyword-profile tracker for one conversation or dictation session
+#[derive(Debug, Default, Clone)]
+pub struct TopicTracker {
+    session_id: Option<String>,
+    label: Option<String>,
+    profile: HashMap<String, f32>,
+    /// An off-topic segment waiting for a second one before the topic changes
+    pending: Option<HashMap<String, f32>>,
+    segments: usize,
+    shifts: Vec<TopicChange>,
+}
+
+impl TopicTracker {
+    pub fn new(session_id: Option<String>) -> Self {
+        Self {
+            session_id,
+            ..Self::default()
+        }
+    }
+
+    pub fn current_topic(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    pub fn shifts(&self) -> &[TopicChange] {
+        &self.shifts
+    }
+
+    /// Keywords of the segment waiting to confirm a change, if any
+    pub fn emerging_keywords(&self) -> Vec<String> {
+        self.pending.as_ref().map(|pending| top_terms(pending, LABEL_KEYWORDS)).unwrap_or_default()
+    }
+
+    pub fn state(&self) -> TopicState {
+        TopicState {
+            session_id: self.session_id.clone(),
+            current_topic: self.label.clone(),
+            keywords: top_terms(&self.profile, LABEL_KEYWORDS * 2),
+            segments: self.segments,
+            shifts: self.shifts.clone(),
+        }
+    }
+
+    /// Fold one segment in; a single off-topic segment is held until the next confirms the change
+    pub fn observe(&mut self, text: &str) -> TopicObservation {
+        let segment = keyword_vector(text);
+        self.segments += 1;
+        if segment.len() < MIN_SEGMENT_KEYWORDS {
+            return self.observation(None);
+        }
+        if self.label.is_none() {
+            self.label = Some(label(&segment));
+            self.profile = segment;
+            return self.observation(None);
+        }
+
+        let similarity = cosine(&segment, &self.profile);
+        if similarity >= SHIFT_THRESHOLD {
+            self.pending = None;
+            self.absorb(&segment);
+            return self.observation(None);
+        }
+
+        let Some(mut pending) = self.pending.take() else {
+            self.pending = Some(segment);
+            return self.observation(None);
+        };
+        for (term, weight) in segment {
+            *pending.entry(term).or_default() += weight;
+        }
+        let keywords = top_terms(&pending, LABEL_KEYWORDS);
+        let to = keywords.join(", ");
+        let change = TopicChange {
+            session_id: self.session_id.clone(),
+            from: self.label.replace(to.clone()),
+            to,
+            keywords,
+            similarity: cosine(&pending, &self.profile),
+            abrupt: similarity < ABRUPT_THRESHOLD,
+            at_segment: self.segments - 2,
+            timestamp: now_secs(),
+        };
+        self.profile = pending;
+        self.shifts.push(change.clone());
+        if self.shifts.len() > MAX_SHIFTS_PER_SESSION {
+            self.shifts.remove(0);
+        }
+        self.observation(Some(change))
+    }
+
+    fn observation(&self, change: Option<TopicChange>) -> TopicObservation {
+        TopicObservation {
+            topic: self.label.clone(),
+            change,
+        }
+    }
+
+    fn absorb(&mut self, segment: &HashMap<String, f32>) {
+        for weight in self.profile.values_mut() {
+            *weight *= PROFILE_DECAY;
+        }
+        for (term, weight) in segment {
+            *self.profile.entry(term.clone()).or_default() += weight;
+        }
+        if self.profile.len() > MAX_PROFILE_TERMS {
+            let keep: Vec<String> = top_terms(&self.profile, MAX_PROFILE_TERMS);
+            self.profile.retain(|term, _| keep.contains(term));
+        }
+    }
+}
+
+fn stem(word: &str) -> String {
+    if word.len() > 4 && word.ends_with('s') && !word.ends_with("ss") && !word.ends_with("us") {
+        word[..word.len() - 1].to_string()
+    } else {
+        word.to_string()
+    }
+}
+
+/// Term counts of the content words in `text`
+fn keyword_vector(text: &str) -> HashMap<String, f32> {
+    let mut vector = HashMap::new();
+    for word in text
+        .split(|c: char| !c.is_alphanumeric() && c != '\'')
+        .map(|word| word.trim_matches('\'').to_lowercase())
+        .filter(|word| word.chars().count() >= 3 && !word.chars().all(|c| c.is_numeric()))
+        .filter(|word| !STOPWORDS.contains(&word.as_str()) && !word.contains('\''))
+    {
+        *vector.entry(stem(&word)).or_insert(0.0) += 1.0;
+    }
+    vector
+}
+
+fn cosine(a: &HashMap<String, f32>, b: &HashMap<String, f32>) -> f32 {
+    let dot: f32 = a.iter().filter_map(|(term, weight)| b.get(term).map(|other| weight * other)).sum();
+    let norm = |v: &HashMap<String, f32>| v.values().map(|w| w * w).sum::<f32>().sqrt();
+    let denominator = norm(a) * norm(b);
+    if denominator == 0.0 {
+        0.0
+    } else {
+        dot / denominator
+    }
+}
+
+/// Heaviest terms first, alphabetical among equals so labels are stable
+fn top_terms(vector: &HashMap<String, f32>, count: usize) -> Vec<String> {
+    let mut terms: Vec<(&String, &f32)> = vector.iter().collect();
+    terms.sort_by(|(a_term, a), (b_term, b)| b.total_cmp(a).then_with(|| a_term.cmp(b_term)));
+    terms.into_iter().take(count).map(|(term, _)| term.clone()).collect()
+}
+
+fn label(vector: &HashMap<String, f32>) -> String {
+    top_terms(vector, LABEL_KEYWORDS).join(", ")
+}
+
+#[derive(Debug, Default)]
+struct SessionTopics {
+    current: TopicTracker,
+    /// Finished sessions, oldest first
+    previous: VecDeque<TopicTracker>,
+}
+
+static SESSIONS: OnceLock<Mutex<SessionTopics>> = OnceLock::new();
+
+fn sessions() -> &'static Mutex<SessionTopics> {
+    SESSIONS.get_or_init(|| Mutex::new(SessionTopics::default()))
+}
+
+/// Track `session_id` from now on; resuming the current session keeps its topic
+pub async fn start_session(session_id: &str) {
+    let mut sessions = sessions().lock().await;
+    if sessions.current.session_id.as_deref() == Some(session_id) {
+        return;
+    }
+    let finished = std::mem::replace(&mut sessions.current, TopicTracker::new(Some(session_id.to_string())));
+    if finished.segments > 0 {
+        sessions.previous.push_back(finished);
+        while sessions.previous.len() > MAX_SESSIONS {
+            sessions.previous.pop_front();
+        }
+    }
+}
+
+/// Observe a finished dictation segment in the current session
+pub async fn observe(text: &str) -> TopicObservation {
+    sessions().lock().await.current.observe(text)
+}
+
+/// The current session's topic, or a recent session's when `session_id` names one
+pub async fn topic_state(session_id: Option<&str>) -> Option<TopicState> {
+    let sessions = sessions().lock().await;
+    match session_id {
+        None => Some(sessions.current.state()),
+        Some(id) => std::iter::once(&sessions.current)
+            .chain(sessions.previous.iter())
+            .find(|tracker| tracker.session_id.as_deref() == Some(id))
+            .map(TopicTracker::state),
+    }
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
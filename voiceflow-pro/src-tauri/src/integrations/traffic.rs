@@ -0,0 +1,92 @@
+// Traffic Metrics Module
+// Counts bytes sent to and received from each AI service, and what upload compression saved
+
+use std::collections::BTreeMap;
+use std::sync::{Mutex, OnceLock};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrafficService {
+    Chat,
+    ToolChat,
+    Transcription,
+    Speech,
+    /// Model listing and key checks, used by health checks
+    Models,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ServiceTraffic {
+    pub requests: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    /// Size the uploads would have had without compression
+    pub uncompressed_bytes: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TrafficReport {
+    pub services: BTreeMap<TrafficService, ServiceTraffic>,
+    pub total_sent: u64,
+    pub total_received: u64,
+    /// Smoothed upload throughput from recent transcription uploads
+    pub upload_throughput_kbps: Option<f64>,
+    /// Unix seconds the counters started from
+    pub since: u64,
+}
+
+struct TrafficCounters {
+    services: BTreeMap<TrafficService, ServiceTraffic>,
+    since: u64,
+}
+
+static COUNTERS: OnceLock<Mutex<TrafficCounters>> = OnceLock::new();
+
+fn counters() -> std::sync::MutexGuard<'static, TrafficCounters> {
+    let counters = COUNTERS.get_or_init(|| {
+        Mutex::new(TrafficCounters {
+            services: BTreeMap::new(),
+            since: now_secs(),
+        })
+    });
+    match counters.lock() {
+        Ok(counters) => counters,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+/// Count one request; `uncompressed` is the upload size before compression, when it was compressed
+pub fn record(service: TrafficService, sent: usize, received: usize, uncompressed: Option<usize>) {
+    let mut counters = counters();
+    let traffic = counters.services.entry(service).or_default();
+    traffic.requests += 1;
+    traffic.bytes_sent += sent as u64;
+    traffic.bytes_received += received as u64;
+    traffic.uncompressed_bytes += uncompressed.unwrap_or(sent) as u64;
+}
+
+pub fn report() -> TrafficReport {
+    let counters = counters();
+    TrafficReport {
+        total_sent: counters.services.values().map(|traffic| traffic.bytes_sent).sum(),
+        total_received: counters.services.values().map(|traffic| traffic.bytes_received).sum(),
+        services: counters.services.clone(),
+        upload_throughput_kbps: super::audio_upload::measured_throughput_kbps(),
+        since: counters.since,
+    }
+}
+
+pub fn reset() {
+    let mut counters = counters();
+    counters.services.clear();
+    counters.since = now_secs();
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
@@ -0,0 +1,493 @@
+// Translation Formality Module
+// Grammatical register (du/Sie, tu/vous, keigo) for target languages where formality is grammar, resolved from the audience and per-language defaults
+
+use std::collections::BTreeMap;
+use std::sync::{OnceLock, RwLock};
+
+use serde::{Deserialize, Serialize};
+
+use super::ai_ml_api::{FormalityLevel, TranslationContext};
+
+/// How the translation addresses its reader
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Register {
+    /// T form: du, tu, ты, plain form, 반말
+    Familiar,
+    /// V form: Sie, vous, вы, です/ます, 해요체
+    Polite,
+    /// Honorific speech where the language has it: sonkeigo/kenjōgo, 합쇼체
+    Honorific,
+}
+
+/// Where the register came from, most specific first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FormalitySource {
+    Request,
+    Audience,
+    UserDefault,
+    LanguageDefault,
+}
+
+/// The register a translation has to use, reported with the result
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegisterChoice {
+    pub language: String,
+    pub level: FormalityLevel,
+    pub register: Register,
+    pub source: FormalitySource,
+    /// The forms to use, as told to the model
+    pub forms: String,
+}
+
+/// Default formality per target language, keyed by language code ("de", "fr-ca")
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FormalitySettings {
+    pub defaults: BTreeMap<String, FormalityLevel>,
+}
+
+struct RegisterRule {
+    language: &'static str,
+    familiar: &'static str,
+    polite: &'static str,
+    honorific: Option<&'static str>,
+    /// Register used when nothing asks for one
+    default: Register,
+    familiar_words: &'static [&'static str],
+    /// Matched case-sensitively and never at the start of a sentence, where "Sie" may just be "she"
+    polite_words: &'static [&'static str],
+}
+
+const RULES: &[RegisterRule] = &[
+    RegisterRule {
+        language: "de",
+        familiar: "du (ihr in the plural) with its verb forms",
+        polite: "Sie with third-person plural verb forms",
+        honorific: None,
+        default: Register::Polite,
+        familiar_words: &["du", "dich", "dir", "dein", "deine", "deinen", "deinem", "deiner", "deines", "euch", "euer", "eure"],
+        polite_words: &["Sie", "Ihnen", "Ihr", "Ihre", "Ihren", "Ihrem", "Ihrer", "Ihres"],
+    },
+    RegisterRule {
+        language: "fr",
+        familiar: "tu with its verb forms (toi, ton, ta, tes)",
+        polite: "vous with its verb forms (votre, vos)",
+        honorific: None,
+        default: Register::Polite,
+        familiar_words: &["tu", "toi", "te", "ton", "ta", "tes"],
+        polite_words: &["vous", "votre", "vos", "Vous", "Votre", "Vos"],
+    },
+    RegisterRule {
+        language: "es",
+        familiar: "tú (vosotros in the plural) with its verb forms",
+        polite: "usted (ustedes in the plural) with third-person verb forms",
+        honorific: None,
+        default: Register::Polite,
+        familiar_words: &["tú", "ti", "contigo", "vosotros", "vosotras", "os"],
+        polite_words: &["usted", "ustedes", "Usted", "Ustedes", "Ud", "Uds"],
+    },
+    RegisterRule {
+        language: "it",
+        familiar: "tu with its verb forms (ti, tuo, tua)",
+        polite: "Lei with third-person verb forms (La, Le, Suo, Sua)",
+        honorific: None,
+        default: Register::Polite,
+        familiar_words: &["tu", "ti", "tuo", "tua", "tuoi", "tue"],
+        polite_words: &["Lei", "Suo", "Sua", "Suoi", "Sue"],
+    },
+    RegisterRule {
+        language: "pt",
+        familiar: "tu or você with its verb forms",
+        polite: "o senhor / a senhora with third-person verb forms",
+        honorific: None,
+        default: Register::Polite,
+        familiar_words: &["tu", "te", "ti", "contigo", "teu", "tua", "teus", "tuas"],
+        polite_words: &["senhor", "senhora", "senhores", "senhoras"],
+    },
+    RegisterRule {
+        language: "nl",
+        familiar: "jij/je (jullie in the plural)",
+        polite: "u and uw",
+        honorific: None,
+        default: Register::Polite,
+        familiar_words: &["jij", "je", "jou", "jouw", "jullie"],
+        polite_words: &["u", "uw"],
+    },
+    RegisterRule {
+        language: "ru",
+        familiar: "ты with its verb forms",
+        polite: "Вы with plural verb forms",
+        honorific: None,
+        default: Register::Polite,
+        familiar_words: &["ты", "тебя", "тебе", "тобой", "твой", "твоя", "твоё", "твое", "твои", "твоего", "твоей"],
+        polite_words: &["вы", "вас", "вам", "вами", "ваш", "ваша", "ваше", "ваши", "Вы", "Вас", "Вам", "Ваш", "Ваша"],
+    },
+    RegisterRule {
+        language: "pl",
+        familiar: "ty with second-person verb forms",
+        polite: "Pan/Pani (Państwo in the plural) with third-person verb forms",
+        honorific: None,
+        default: Register::Polite,
+        familiar_words: &["ty", "cię", "ciebie", "tobie", "tobą", "twój", "twoja", "twoje", "twoich"],
+        polite_words: &["Pan", "Pani", "Państwo", "Pana", "Panu", "Panią", "Państwa", "pan", "pani", "państwo"],
+    },
+    RegisterRule {
+        language: "ja",
+        familiar: "plain form (だ/である, dictionary-form verbs)",
+        polite: "teineigo (です/ます)",
+        honorific: Some("keigo: sonkeigo for the reader's actions and kenjōgo for the writer's (いらっしゃる, おっしゃる, いたします, ございます)"),
+        default: Register::Polite,
+        familiar_words: &[],
+        polite_words: &[],
+    },
+    RegisterRule {
+        language: "ko",
+        familiar: "banmal (해체)",
+        polite: "haeyo-che (해요체)",
+        honorific: Some("hasipsio-che (합쇼체: -습니다/-ㅂ니다) with honorific -시-"),
+        default: Register::Polite,
+        familiar_words: &[],
+        polite_words: &[],
+    },
+];
+
+/// Audience words that call for a register, checked in order
+const AUDIENCE_CUES: &[(FormalityLevel, &[&str])] = &[
+    (
+        FormalityLevel::VeryFormal,
+        &["executive", "executives", "board", "dignitary", "dignitaries", "ceremony", "ceremonial", "vip", "elder", "elders"],
+    ),
+    (
+        FormalityLevel::Informal,
+        &[
+            "friend", "friends", "family", "child", "children", "kid", "kids", "partner", "sibling", "siblings", "teen",
+            "teens", "teenager", "teenagers", "classmate", "classmates", "buddy", "casual", "team", "teammate", "teammates",
+        ],
+    ),
+    (
+        FormalityLevel::Formal,
+        &[
+            "customer", "customers", "client", "clients", "manager", "boss", "official", "officials", "government",
+            "stranger", "strangers", "public", "patient", "patients", "professor", "teacher", "investor", "investors",
+            "authority", "authorities", "court", "business", "formal", "professional", "professionals", "applicant",
+        ],
+    ),
+];
+
+static DEFAULTS: OnceLock<RwLock<FormalitySettings>> = OnceLock::new();
+
+fn defaults() -> &'static RwLock<FormalitySettings> {
+    DEFAULTS.get_or_init(|| RwLock::new(FormalitySettings::default()))
+}
+
+/// Lower-case code with '-' separators, the form defaults are keyed by
+pub fn language_key(language: &str) -> String {
+    language.trim().replace('_', "-").to_lowercase()
+}
+
+fn base_language(language: &str) -> String {
+    language_key(language).split('-').next().unwrap_or_default().to_string()
+}
+
+fn rule(language: &str) -> Option<&'static RegisterRule> {
+    let base = base_language(language);
+    RULES.iter().find(|rule| rule.language == base)
+}
+
+/// Whether the language marks formality in its grammar
+pub fn has_register(language: &str) -> bool {
+    rule(language).is_some()
+}
+
+/// Apply the user's per-language defaults
+pub fn configure(settings: &FormalitySettings) {
+    let mut current = match defaults().write() {
+        Ok(current) => current,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    *current = settings.clone();
+}
+
+/// The register a translation into `target_language` has to use, or `None` where the language has no T-V distinction
+pub fn resolve_register(target_language: &str, context: &TranslationContext) -> Option<RegisterChoice> {
+    let settings = match defaults().read() {
+        Ok(settings) => settings,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    resolve_with(target_language, context, &settings)
+}
+
+/// An explicit level on the request wins, then cues in the audience, then the user's default for the language
+fn resolve_with(target_language: &str, context: &TranslationContext, settings: &FormalitySettings) -> Option<RegisterChoice> {
+    let rule = rule(target_language)?;
+    let user_default = settings
+        .defaults
+        .get(&language_key(target_language))
+        .or_else(|| settings.defaults.get(rule.language));
+    let (level, source) = if context.formality_level != FormalityLevel::Neutral {
+        (context.formality_level, FormalitySource::Request)
+    } else if let Some(level) = audience_formality(&context.audience) {
+        (level, FormalitySource::Audience)
+    } else if let Some(level) = user_default.filter(|level| **level != FormalityLevel::Neutral) {
+        (*level, FormalitySource::UserDefault)
+    } else {
+        (FormalityLevel::Neutral, FormalitySource::LanguageDefault)
+    };
+    let register = match level {
+        FormalityLevel::VeryFormal if rule.honorific.is_some() => Register::Honorific,
+        FormalityLevel::VeryFormal | FormalityLevel::Formal => Register::Polite,
+        FormalityLevel::Neutral => rule.default,
+        FormalityLevel::Informal | FormalityLevel::VeryInformal => Register::Familiar,
+    };
+    Some(RegisterChoice {
+        language: language_key(target_language),
+        level,
+        register,
+        source,
+        forms: forms(rule, register).to_string(),
+    })
+}
+
+fn audience_formality(audience: &str) -> Option<FormalityLevel> {
+    let audience = audience.to_lowercase();
+    let words: Vec<&str> = audience.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()).collect();
+    AUDIENCE_CUES
+        .iter()
+        .find(|(_, cues)| words.iter().any(|word| cues.contains(word)))
+        .map(|(level, _)| *level)
+}
+
+fn forms(rule: &RegisterRule, register: Register) -> &'static str {
+    match register {
+        Register::Familiar => rule.familiar,
+        Register::Polite => rule.polite,
+        Register::Honorific => rule.honorific.unwrap_or(rule.polite),
+    }
+}
+
+/// Prompt lines requiring the chosen register and ruling out the others
+pub fn instruction(choice: &RegisterChoice) -> String {
+    let Some(rule) = rule(&choice.language) else {
+        return String::new();
+    };
+    let avoid: Vec<&str> = [Register::Familiar, Register::Polite, Register::Honorific]
+        .into_iter()
+        .filter(|register| *register != choice.register && (*register != Register::Honorific || rule.honorific.is_some()))
+        .map(|register| forms(rule, register))
+        .collect();
+    format!(
+        "Grammatical register (required): address the reader using {}. Do not use {}. Keep the register consistent throughout.\n",
+        choice.forms,
+        avoid.join(" or ")
+    )
+}
+
+/// The register `text` is written in, judged from pronouns or sentence endings; `None` when nothing gives it away
+pub fn detect_register(language: &str, text: &str) -> Option<Register> {
+    let rule = rule(language)?;
+    match rule.language {
+        "ja" => detect_by_endings(text, japanese_sentence),
+        "ko" => detect_by_endings(text, korean_sentence),
+        _ => detect_by_pronouns(rule, text),
+    }
+}
+
+/// Whether text written in `found` satisfies `required`; honorific text is still polite
+pub fn satisfies(required: Register, found: Register) -> bool {
+    required == found || (required == Register::Polite && found == Register::Honorific)
+}
+
+fn detect_by_pronouns(rule: &RegisterRule, text: &str) -> Option<Register> {
+    let (mut familiar, mut polite) = (0, 0);
+    let mut sentence_start = true;
+    for token in text.split(|c: char| c.is_whitespace() || matches!(c, '-' | '\'' | '’')) {
+        let word = token.trim_matches(|c: char| !c.is_alphanumeric());
+        if !word.is_empty() {
+            if rule.familiar_words.contains(&word.to_lowercase().as_str()) {
+                familiar += 1;
+            } else if !sentence_start && rule.polite_words.contains(&word) {
+                polite += 1;
+            }
+            sentence_start = false;
+        }
+        if token.ends_with(['.', '!', '?']) {
+            sentence_start = true;
+        }
+    }
+    match (familiar, polite) {
+        (0, 0) => None,
+        (familiar, polite) if familiar > polite => Some(Register::Familiar),
+        _ => Some(Register::Polite),
+    }
+}
+
+/// Sentences vote by their endings; a single honorific sentence makes the text honorific
+fn detect_by_endings(text: &str, classify: fn(&str) -> Option<Register>) -> Option<Register> {
+    let votes: Vec<Register> = text
+        .split(['。', '！', '？', '!', '?', '.', '\n'])
+        .map(|sentence| sentence.trim_end_matches(|c: char| c.is_whitespace() || "」』）)…".contains(c)).trim())
+        .filter(|sentence| !sentence.is_empty())
+        .filter_map(classify)
+        .collect();
+    if votes.is_empty() {
+        return None;
+    }
+    if votes.contains(&Register::Honorific) {
+        return Some(Register::Honorific);
+    }
+    let familiar = votes.iter().filter(|vote| **vote == Register::Familiar).count();
+    Some(if familiar * 2 > votes.len() { Register::Familiar } else { Register::Polite })
+}
+
+const JAPANESE_HONORIFIC: &[&str] =
+    &["いらっしゃ", "おっしゃ", "ございま", "いたしま", "申し上げ", "存じ", "伺い", "参りま", "召し上が", "なさいま", "くださいま"];
+const JAPANESE_POLITE_ENDINGS: &[&str] =
+    &["です", "ます", "でした", "ました", "ません", "ましょう", "ください", "でしょう", "ませんか", "ですか", "ますか"];
+
+fn japanese_sentence(sentence: &str) -> Option<Register> {
+    if JAPANESE_HONORIFIC.iter().any(|marker| sentence.contains(marker)) {
+        return Some(Register::Honorific);
+    }
+    let ending = sentence.trim_end_matches(['ね', 'よ', 'な', 'か']);
+    let ending = if ending.is_empty() { sentence } else { ending };
+    if JAPANESE_POLITE_ENDINGS.iter().any(|polite| ending.ends_with(polite) || sentence.ends_with(polite)) {
+        Some(Register::Polite)
+    } else if ending.chars().last().is_some_and(|c| ('\u{3040}'..='\u{30FF}').contains(&c) || ('\u{4E00}'..='\u{9FFF}').contains(&c)) {
+        Some(Register::Familiar)
+    } else {
+        None
+    }
+}
+
+fn korean_sentence(sentence: &str) -> Option<Register> {
+    let last = sentence.chars().last()?;
+    if !('\u{AC00}'..='\u{D7AF}').contains(&last) {
+        return None;
+    }
+    if sentence.ends_with("니다") || sentence.ends_with("니까") || sentence.ends_with("십시오") {
+        Some(Register::Honorific)
+    } else if sentence.ends_with('요') {
+        Some(Register::Polite)
+    } else {
+        Some(Register::Familiar)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(audience: &str, level: FormalityLevel) -> TranslationContext {
+        TranslationContext {
+            audience: audience.to_string(),
+            formality_level: level,
+            ..TranslationContext::default()
+        }
+    }
+
+    fn resolve(language: &str, context: &TranslationContext, defaults: &[(&str, FormalityLevel)]) -> Option<RegisterChoice> {
+        let settings = FormalitySettings {
+            defaults: defaults.iter().map(|(language, level)| (language.to_string(), *level)).collect(),
+        };
+        resolve_with(language, context, &settings)
+    }
+
+    #[test]
+    fn languages_without_t_v_distinction_have_no_register() {
+        assert!(resolve("en", &context("friends", FormalityLevel::Informal), &[]).is_none());
+        assert!(resolve("zh-CN", &TranslationContext::default(), &[]).is_none());
+    }
+
+    #[test]
+    fn explicit_level_wins_over_audience_and_defaults() {
+        let choice = resolve("de", &context("friends", FormalityLevel::Formal), &[("de", FormalityLevel::Informal)]).unwrap();
+        assert_eq!(choice.register, Register::Polite);
+        assert_eq!(choice.source, FormalitySource::Request);
+        assert!(choice.forms.contains("Sie"));
+    }
+
+    #[test]
+    fn audience_picks_the_register() {
+        let friends = resolve("fr", &context("close friends", FormalityLevel::Neutral), &[]).unwrap();
+        assert_eq!(friends.register, Register::Familiar);
+        assert_eq!(friends.source, FormalitySource::Audience);
+        let clients = resolve("fr", &context("prospective clients", FormalityLevel::Neutral), &[("fr", FormalityLevel::Informal)]).unwrap();
+        assert_eq!(clients.register, Register::Polite);
+        let board = resolve("ja", &context("the board", FormalityLevel::Neutral), &[]).unwrap();
+        assert_eq!(board.register, Register::Honorific);
+    }
+
+    #[test]
+    fn user_default_applies_per_language_and_region() {
+        let general = context("general", FormalityLevel::Neutral);
+        let german = resolve("de-AT", &general, &[("de", FormalityLevel::Informal)]).unwrap();
+        assert_eq!(german.register, Register::Familiar);
+        assert_eq!(german.source, FormalitySource::UserDefault);
+        let swiss = resolve("de-CH", &general, &[("de", FormalityLevel::Informal), ("de-ch", FormalityLevel::Formal)]).unwrap();
+        assert_eq!(swiss.register, Register::Polite);
+        let french = resolve("fr", &general, &[("de", FormalityLevel::Informal)]).unwrap();
+        assert_eq!(french.register, Register::Polite);
+        assert_eq!(french.source, FormalitySource::LanguageDefault);
+    }
+
+    #[test]
+    fn very_formal_is_polite_where_there_are_no_honorifics() {
+        let german = resolve("de", &context("general", FormalityLevel::VeryFormal), &[]).unwrap();
+        assert_eq!(german.register, Register::Polite);
+        let korean = resolve("ko", &context("general", FormalityLevel::VeryFormal), &[]).unwrap();
+        assert_eq!(korean.register, Register::Honorific);
+    }
+
+    #[test]
+    fn instruction_requires_one_register_and_rules_out_the_other() {
+        let choice = resolve("de", &context("friends", FormalityLevel::Neutral), &[]).unwrap();
+        let instruction = instruction(&choice);
+        assert!(instruction.contains("using du"));
+        assert!(instruction.contains("Do not use Sie"));
+    }
+
+    #[test]
+    fn detects_german_du_and_sie() {
+        assert_eq!(detect_register("de", "Kannst du mir den Bericht schicken? Ich danke dir."), Some(Register::Familiar));
+        assert_eq!(detect_register("de", "Können Sie mir den Bericht schicken? Ich danke Ihnen."), Some(Register::Polite));
+        // Sentence-initial "Sie" may be "she"; nothing else marks the register here
+        assert_eq!(detect_register("de", "Sie hat den Bericht geschickt."), None);
+    }
+
+    #[test]
+    fn detects_french_tu_and_vous() {
+        assert_eq!(detect_register("fr", "Peux-tu m'envoyer ton rapport ?"), Some(Register::Familiar));
+        assert_eq!(detect_register("fr", "Pourriez-vous m'envoyer votre rapport ?"), Some(Register::Polite));
+    }
+
+    #[test]
+    fn detects_spanish_and_russian() {
+        assert_eq!(detect_register("es", "¿Puede usted enviarme el informe?"), Some(Register::Polite));
+        assert_eq!(detect_register("es", "¿Tú me puedes enviar el informe? Es para ti."), Some(Register::Familiar));
+        assert_eq!(detect_register("ru", "Не могли бы Вы прислать отчёт?"), Some(Register::Polite));
+        assert_eq!(detect_register("ru", "Ты можешь прислать отчёт?"), Some(Register::Familiar));
+    }
+
+    #[test]
+    fn detects_japanese_plain_polite_and_keigo() {
+        assert_eq!(detect_register("ja", "報告書を送ってくれる？明日までに読む。"), Some(Register::Familiar));
+        assert_eq!(detect_register("ja", "報告書を送ってください。明日までに読みます。"), Some(Register::Polite));
+        assert_eq!(detect_register("ja", "報告書をお送りいただけますでしょうか。社長がおっしゃっていました。"), Some(Register::Honorific));
+    }
+
+    #[test]
+    fn detects_korean_speech_levels() {
+        assert_eq!(detect_register("ko", "보고서 보내 줄래?"), Some(Register::Familiar));
+        assert_eq!(detect_register("ko", "보고서를 보내 주세요."), Some(Register::Polite));
+        assert_eq!(detect_register("ko", "보고서를 보내 주시겠습니까?"), Some(Register::Honorific));
+    }
+
+    #[test]
+    fn honorific_text_satisfies_a_polite_requirement() {
+        assert!(satisfies(Register::Polite, Register::Honorific));
+        assert!(!satisfies(Register::Honorific, Register::Polite));
+        assert!(!satisfies(Register::Familiar, Register::Polite));
+    }
+}
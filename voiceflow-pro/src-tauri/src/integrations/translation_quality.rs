@@ -0,0 +1,424 @@
+// Translation Quality Estimation Module
+// Reference-free quality signals: length ratio, entity preservation, glossary compliance and round-trip similarity
+
+use regex::Regex;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::OnceLock;
+
+use super::translation_service::TranslationQuality;
+
+/// Typical character-count expansion relative to English
+fn expansion_factor(language: &str) -> f32 {
+    match base_language(language).as_str() {
+        "es" | "fr" => 1.2,
+        "de" => 1.25,
+        "it" | "pt" | "nl" => 1.15,
+        "ru" => 1.1,
+        "sv" | "hi" => 1.05,
+        "ar" => 0.95,
+        "ko" => 0.5,
+        "ja" => 0.45,
+        "zh" => 0.35,
+        _ => 1.0,
+    }
+}
+
+/// Languages written mostly outside the Latin script, with a predicate for their characters
+fn script_check(language: &str) -> Option<fn(char) -> bool> {
+    match base_language(language).as_str() {
+        "zh" => Some(|c| ('\u{4E00}'..='\u{9FFF}').contains(&c)),
+        "ja" => Some(|c| ('\u{3040}'..='\u{30FF}').contains(&c) || ('\u{4E00}'..='\u{9FFF}').contains(&c)),
+        "ko" => Some(|c| ('\u{AC00}'..='\u{D7AF}').contains(&c)),
+        "ru" => Some(|c| ('\u{0400}'..='\u{04FF}').contains(&c)),
+        "ar" => Some(|c| ('\u{0600}'..='\u{06FF}').contains(&c)),
+        "hi" => Some(|c| ('\u{0900}'..='\u{097F}').contains(&c)),
+        _ => None,
+    }
+}
+
+fn base_language(language: &str) -> String {
+    language.split(['-', '_']).next().unwrap_or(language).to_lowercase()
+}
+
+/// Inputs to the estimator
+pub struct QualityInputs<'a> {
+    pub original: &'a str,
+    pub translated: &'a str,
+    pub source_language: &'a str,
+    pub target_language: &'a str,
+    pub glossary: &'a BTreeMap<String, String>,
+    /// The translation rendered back into the source language, when a round trip was requested
+    pub back_translation: Option<&'a str>,
+}
+
+/// Estimate translation quality from observable signals
+pub fn estimate_quality(inputs: &QualityInputs) -> TranslationQuality {
+    let mut issues = Vec::new();
+
+    let source_chars = inputs.original.chars().filter(|c| !c.is_whitespace()).count().max(1) as f32;
+    let target_chars = inputs.translated.chars().filter(|c| !c.is_whitespace()).count() as f32;
+    let length_ratio = target_chars / source_chars;
+    let expected_ratio = expansion_factor(inputs.target_language) / expansion_factor(inputs.source_language);
+    let length_score = if target_chars == 0.0 {
+        0.0
+    } else {
+        // 1.0 at the expected ratio, ~0.5 when off by a factor of 1.4
+        (-(length_ratio / expected_ratio).ln().abs() * 2.0).exp()
+    };
+    if length_score < 0.5 {
+        issues.push(format!(
+            "Length ratio {:.2} is far from the expected {:.2}; content may be missing or added",
+            length_ratio, expected_ratio
+        ));
+    }
+
+    let entity_score = entity_preservation(inputs.original, inputs.translated, &mut issues);
+    let glossary_score = glossary_compliance(inputs.original, inputs.translated, inputs.glossary, &mut issues);
+    let script_score = script_conformance(inputs.translated, inputs.target_language, &mut issues);
+    let cultural_fitness_score = locale_conventions(inputs.translated, inputs.target_language, &mut issues);
+    let fluency_score = fluency(inputs, &mut issues);
+    let round_trip_score = inputs
+        .back_translation
+        .map(|back| token_similarity(inputs.original, back));
+    if let Some(score) = round_trip_score.filter(|s| *s < 0.4) {
+        issues.push(format!("Back-translation only {:.0}% similar to the original", score * 100.0));
+    }
+
+    let adequacy_score = match round_trip_score {
+        Some(round_trip) => 0.6 * round_trip + 0.25 * length_score + 0.15 * entity_score,
+        None => 0.6 * length_score + 0.4 * entity_score,
+    };
+    let technical_accuracy_score = glossary_score.unwrap_or(entity_score);
+
+    let raw = 0.3 * adequacy_score
+        + 0.25 * fluency_score
+        + 0.2 * entity_score
+        + 0.15 * technical_accuracy_score
+        + 0.05 * script_score
+        + 0.05 * cultural_fitness_score;
+
+    TranslationQuality {
+        fluency_score,
+        adequacy_score,
+        preservation_score: entity_score,
+        cultural_fitness_score,
+        technical_accuracy_score,
+        overall_score: calibrate(raw, inputs.original, round_trip_score.is_some()),
+        length_ratio,
+        glossary_compliance: glossary_score,
+        back_translation_similarity: round_trip_score,
+        issues,
+    }
+}
+
+/// Shrink scores toward 0.5 when there is little evidence (short text, no round trip)
+fn calibrate(raw: f32, original: &str, has_round_trip: bool) -> f32 {
+    let words = original.split_whitespace().count() as f32;
+    let mut evidence = (words / 25.0).min(1.0) * 0.7 + 0.2;
+    if has_round_trip {
+        evidence += 0.1;
+    }
+    (0.5 + (raw - 0.5) * evidence.min(1.0)).clamp(0.0, 1.0)
+}
+
+/// Fraction of numbers, URLs, code spans and proper names carried over verbatim
+fn entity_preservation(original: &str, translated: &str, issues: &mut Vec<String>) -> f32 {
+    let entities = extract_entities(original);
+    if entities.is_empty() {
+        return 1.0;
+    }
+
+    let translated_digits = digit_runs(translated);
+    let missing: Vec<&String> = entities
+        .iter()
+        .filter(|entity| {
+            if entity.chars().all(|c| c.is_ascii_digit()) {
+                !translated_digits.contains(entity.as_str())
+            } else {
+                !translated.contains(entity.as_str())
+            }
+        })
+        .collect();
+
+    if !missing.is_empty() {
+        let sample: Vec<&str> = missing.iter().take(5).map(|s| s.as_str()).collect();
+        issues.push(format!("Entities not preserved: {}", sample.join(", ")));
+    }
+    1.0 - missing.len() as f32 / entities.len() as f32
+}
+
+fn extract_entities(text: &str) -> Vec<String> {
+    let mut entities: Vec<String> = Vec::new();
+    let mut seen = HashSet::new();
+    let mut push = |value: String| {
+        if seen.insert(value.clone()) {
+            entities.push(value);
+        }
+    };
+
+    static URL: OnceLock<Regex> = OnceLock::new();
+    static CODE: OnceLock<Regex> = OnceLock::new();
+    static NAME: OnceLock<Regex> = OnceLock::new();
+
+    let url = URL.get_or_init(|| {
+        Regex::new(r"(?:https?://|www\.)\S+|[\w.+-]+@[\w-]+\.[\w.]+").expect("URL pattern is valid")
+    });
+    for m in url.find_iter(text) {
+        push(m.as_str().trim_end_matches(['.', ',', ')']).to_string());
+    }
+    let code = CODE.get_or_init(|| Regex::new(r"`([^`]+)`").expect("code span pattern is valid"));
+    for caps in code.captures_iter(text) {
+        push(caps[1].to_string());
+    }
+    // Numbers compared by digits only, since separators are localized (1,000 vs 1.000)
+    for digits in digit_runs(text) {
+        push(digits);
+    }
+    // Capitalized words that do not start a sentence are likely names
+    let name = NAME.get_or_init(|| {
+        Regex::new(r"[^.!?\n]\s+(\p{Lu}[\p{Ll}\p{Lu}]+(?:\s+\p{Lu}[\p{Ll}\p{Lu}]+)*)").expect("name pattern is valid")
+    });
+    for caps in name.captures_iter(text) {
+        push(caps[1].to_string());
+    }
+    entities
+}
+
+fn digit_runs(text: &str) -> HashSet<String> {
+    static DIGITS: OnceLock<Regex> = OnceLock::new();
+    DIGITS
+        .get_or_init(|| Regex::new(r"\d[\d.,\s]*\d|\d").expect("digit pattern is valid"))
+        .find_iter(text)
+        .map(|m| m.as_str().chars().filter(|c| c.is_ascii_digit()).collect())
+        .collect()
+}
+
+/// Fraction of glossary source terms present in the original whose required rendering appears in the translation
+fn glossary_compliance(
+    original: &str,
+    translated: &str,
+    glossary: &BTreeMap<String, String>,
+    issues: &mut Vec<String>,
+) -> Option<f32> {
+    let original_lower = original.to_lowercase();
+    let translated_lower = translated.to_lowercase();
+    let applicable: Vec<(&String, &String)> = glossary
+        .iter()
+        .filter(|(term, _)| !term.is_empty() && original_lower.contains(&term.to_lowercase()))
+        .collect();
+    if applicable.is_empty() {
+        return None;
+    }
+
+    let violations: Vec<String> = applicable
+        .iter()
+        .filter(|(_, rendering)| !translated_lower.contains(&rendering.to_lowercase()))
+        .map(|(term, rendering)| format!("'{}' should be '{}'", term, rendering))
+        .collect();
+    let score = 1.0 - violations.len() as f32 / applicable.len() as f32;
+    if !violations.is_empty() {
+        issues.push(format!("Glossary not followed: {}", violations.join("; ")));
+    }
+    Some(score)
+}
+
+/// Whether the translation is written in the target language's script
+fn script_conformance(translated: &str, target_language: &str, issues: &mut Vec<String>) -> f32 {
+    let letters: Vec<char> = translated.chars().filter(|c| c.is_alphabetic()).collect();
+    if letters.is_empty() {
+        return 0.0;
+    }
+    let score = match script_check(target_language) {
+        Some(in_script) => letters.iter().filter(|c| in_script(**c)).count() as f32 / letters.len() as f32,
+        // Latin-script targets: penalize non-Latin letters
+        None => letters.iter().filter(|c| c.is_ascii_alphabetic() || ('\u{00C0}'..='\u{024F}').contains(*c)).count() as f32
+            / letters.len() as f32,
+    };
+    // Names and code legitimately keep their own script, so anything above 70% counts as conforming
+    let score = (score / 0.7).min(1.0);
+    if score < 0.7 {
+        issues.push(format!("Translation is not mostly in the {} script", target_language));
+    }
+    score
+}
+
+/// Whether the translation follows the target locale's punctuation conventions, e.g. ¿…? in Spanish,
+/// full-width marks in Chinese and Japanese, and the space before ? ! : ; in French
+fn locale_conventions(translated: &str, target_language: &str, issues: &mut Vec<String>) -> f32 {
+    let chars: Vec<char> = translated.chars().collect();
+    let (followed, checked) = match base_language(target_language).as_str() {
+        "es" => {
+            // Each question or exclamation needs its opening mark somewhere in the same sentence
+            let mut followed = 0;
+            let mut checked = 0;
+            let mut sentence_start = 0;
+            for (i, c) in chars.iter().enumerate() {
+                let opening = match c {
+                    '?' => '¿',
+                    '!' => '¡',
+                    '.' | '\n' => {
+                        sentence_start = i + 1;
+                        continue;
+                    }
+                    _ => continue,
+                };
+                checked += 1;
+                if chars[sentence_start..i].contains(&opening) {
+                    followed += 1;
+                }
+                sentence_start = i + 1;
+            }
+            (followed, checked)
+        }
+        "zh" | "ja" => {
+            // Punctuation straight after CJK text should be the full-width form
+            let cjk = script_check(target_language).unwrap_or(|_| false);
+            let marks: Vec<char> = chars
+                .windows(2)
+                .filter(|pair| cjk(pair[0]) && ",.?!:;，。？！：；、".contains(pair[1]))
+                .map(|pair| pair[1])
+                .collect();
+            (marks.iter().filter(|c| !c.is_ascii()).count(), marks.len())
+        }
+        "fr" => {
+            // Times, ratios, URLs and runs like "?!" are not sentence punctuation
+            let marks: Vec<usize> = (1..chars.len())
+                .filter(|&i| "?!:;".contains(chars[i]))
+                .filter(|&i| !chars[i - 1].is_ascii_digit() && !"?!".contains(chars[i - 1]))
+                .filter(|&i| chars.get(i + 1) != Some(&'/'))
+                .collect();
+            (marks.iter().filter(|&&i| chars[i - 1].is_whitespace()).count(), marks.len())
+        }
+        _ => (0, 0),
+    };
+    if checked == 0 {
+        return 1.0;
+    }
+    let score = followed as f32 / checked as f32;
+    if score < 0.7 {
+        issues.push(format!("Punctuation does not follow {} conventions", target_language));
+    }
+    score
+}
+
+/// Penalize common failure modes: untranslated output, repetition loops, leaked model chatter
+fn fluency(inputs: &QualityInputs, issues: &mut Vec<String>) -> f32 {
+    let mut score: f32 = 1.0;
+    let translated = inputs.translated.trim();
+
+    if base_language(inputs.source_language) != base_language(inputs.target_language)
+        && token_similarity(inputs.original, translated) > 0.8
+    {
+        issues.push("Output looks untranslated".to_string());
+        score -= 0.6;
+    }
+
+    let words: Vec<&str> = translated.split_whitespace().collect();
+    if words.len() >= 12 {
+        let trigrams: Vec<String> = words.windows(3).map(|w| w.join(" ").to_lowercase()).collect();
+        let unique: HashSet<&String> = trigrams.iter().collect();
+        let repetition = 1.0 - unique.len() as f32 / trigrams.len() as f32;
+        if repetition > 0.3 {
+            issues.push("Repeated phrases suggest a generation loop".to_string());
+            score -= repetition.min(0.5);
+        }
+    }
+
+    let lower = translated.to_lowercase();
+    if ["translation:", "here is the translation", "translated text:", "as an ai"]
+        .iter()
+        .any(|marker| lower.contains(marker))
+    {
+        issues.push("Output contains model commentary".to_string());
+        score -= 0.2;
+    }
+
+    score.clamp(0.0, 1.0)
+}
+
+/// Dice coefficient over lowercase word bigrams (unigrams for very short texts)
+fn token_similarity(a: &str, b: &str) -> f32 {
+    let grams = |text: &str| -> HashSet<String> {
+        let words: Vec<String> = text
+            .split_whitespace()
+            .map(|w| w.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase())
+            .filter(|w| !w.is_empty())
+            .collect();
+        if words.len() < 4 {
+            words.into_iter().collect()
+        } else {
+            words.windows(2).map(|w| w.join(" ")).collect()
+        }
+    };
+    let (a, b) = (grams(a), grams(b));
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+    let overlap = a.intersection(&b).count() as f32;
+    2.0 * overlap / (a.len() + b.len()) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preservation(original: &str, translated: &str) -> (f32, Vec<String>) {
+        let mut issues = Vec::new();
+        let score = entity_preservation(original, translated, &mut issues);
+        (score, issues)
+    }
+
+    #[test]
+    fn preserved_spans_count_urls_code_and_names() {
+        let original = "Open https://example.com/docs and run `cargo build` before asking Maria Lopez.";
+        let (score, issues) = preservation(
+            original,
+            "Abre https://example.com/docs y ejecuta cargo build antes de preguntar a Maria Lopez.",
+        );
+        assert_eq!(score, 1.0, "{:?}", issues);
+
+        let (score, issues) = preservation(original, "Abre la documentación y ejecuta cargo build antes de preguntar a María.");
+        assert!(score < 0.5);
+        assert!(issues[0].contains("https://example.com/docs"));
+        assert!(issues[0].contains("Maria Lopez"));
+    }
+
+    #[test]
+    fn numbers_match_across_localized_separators() {
+        let (score, _) = preservation("The budget is 1,250.50 for 3 teams", "El presupuesto es 1.250,50 para 3 equipos");
+        assert_eq!(score, 1.0);
+
+        let (score, issues) = preservation("The budget is 1,250.50 for 3 teams", "El presupuesto es 1.205,50 para 3 equipos");
+        assert!(score < 1.0);
+        assert!(issues[0].contains("125050"));
+    }
+
+    #[test]
+    fn script_check_flags_text_left_in_the_source_script() {
+        let mut issues = Vec::new();
+        assert_eq!(script_conformance("Привет, как дела?", "ru", &mut issues), 1.0);
+        assert!(issues.is_empty());
+
+        assert!(script_conformance("Hello, how are you?", "ru-RU", &mut issues) < 0.7);
+        assert_eq!(issues.len(), 1);
+
+        // A name in Latin letters inside Japanese text is not flagged
+        let mut issues = Vec::new();
+        assert!(script_conformance("明日Googleで会いましょう", "ja", &mut issues) > 0.7);
+        assert!(issues.is_empty());
+    }
+
+    #[test]
+    fn locale_conventions_check_target_punctuation() {
+        let mut issues = Vec::new();
+        assert_eq!(locale_conventions("¿Vienes mañana? ¡Qué bien!", "es", &mut issues), 1.0);
+        assert_eq!(locale_conventions("Vienes mañana? Qué bien!", "es", &mut issues), 0.0);
+        assert_eq!(locale_conventions("你好，明天见。", "zh", &mut issues), 1.0);
+        assert_eq!(locale_conventions("你好,明天见.", "zh", &mut issues), 0.0);
+        assert_eq!(locale_conventions("Vous venez ? Rendez-vous à 10:30 : merci !", "fr", &mut issues), 1.0);
+        assert_eq!(locale_conventions("Vous venez?", "fr", &mut issues), 0.0);
+        // Languages without a checked convention are not penalized
+        assert_eq!(locale_conventions("See you tomorrow!", "en", &mut issues), 1.0);
+        assert_eq!(issues.len(), 3);
+    }
+}
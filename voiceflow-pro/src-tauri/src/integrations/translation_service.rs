@@ -6,14 +6,23 @@ use tokio::sync::Mutex;
 use uuid::Uuid;
 
 use super::ai_ml_core::{AIMLClient, AIMLError, AIMLService};
+use crate::integrations::bilingual::{BilingualLayout, BilingualText};
+use crate::integrations::translation_formality::{self, detect_register, resolve_register, satisfies, RegisterChoice};
+use super::translation_quality::{estimate_quality, QualityInputs};
+use crate::integrations::language_registry::{get_language_registry, LanguageEntry};
+use crate::integrations::result_cache::{request_key, CacheKind, CacheStats, CacheValue, PersistentCache};
+use crate::integrations::token_budget::count_messages;
+use std::collections::BTreeMap;
+
+/// Cached translations kept while the app is in use
+const TRANSLATION_CACHE_CAPACITY: usize = 200;
 
 /// Translation Service
 #[derive(Debug)]
 pub struct Translator {
     client: Arc<Mutex<AIMLClient>>,
     model: String,
-    translation_cache: tokio::sync::Mutex<lru::LruCache<String, TranslationResult>>,
-    supported_languages: Vec<LanguageInfo>,
+    translation_cache: tokio::sync::Mutex<PersistentCache<TranslationResult>>,
 }
 
 /// Translation request
@@ -27,8 +36,9 @@ pub struct TranslationRequest {
     pub options: TranslationOptions,
 }
 
-/// Translation context
+/// Translation context; omitted fields fall back to a neutral, general-purpose translation
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 pub struct TranslationContext {
     pub domain: TranslationDomain,
     pub audience: String,
@@ -36,6 +46,8 @@ pub struct TranslationContext {
     pub formality_level: FormalityLevel,
     pub cultural_considerations: bool,
     pub technical_terminology: bool,
+    #[serde(default)]
+    pub preceding_context: Option<String>,
 }
 
 /// Translation domains
@@ -53,8 +65,8 @@ pub enum TranslationDomain {
     Marketing,
 }
 
-/// Formality levels
-#[derive(Debug, Clone, serde::Serialize, serde:: Deserialize)]
+/// Formality levels; in languages with a T-V distinction or honorifics they pick the grammatical register
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde:: Deserialize)]
 pub enum FormalityLevel {
     VeryFormal,
     Formal,
@@ -65,6 +77,7 @@ pub enum FormalityLevel {
 
 /// Translation options
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 pub struct TranslationOptions {
     pub preserve_formatting: bool,
     pub maintain_style: bool,
@@ -73,6 +86,42 @@ pub struct TranslationOptions {
     pub cultural_adaptation: bool,
     pub technical_accuracy: bool,
     pub creative_freedom: f32, // 0.0 to 1.0
+    /// Translate the output back to the source language and score the round trip
+    #[serde(default)]
+    pub back_translation_check: bool,
+    /// Required renderings for specific source terms
+    #[serde(default)]
+    pub glossary: BTreeMap<String, String>,
+}
+
+impl Default for TranslationContext {
+    fn default() -> Self {
+        Self {
+            domain: TranslationDomain::General,
+            audience: "general".to_string(),
+            purpose: "communication".to_string(),
+            formality_level: FormalityLevel::Neutral,
+            cultural_considerations: true,
+            technical_terminology: false,
+            preceding_context: None,
+        }
+    }
+}
+
+impl Default for TranslationOptions {
+    fn default() -> Self {
+        Self {
+            preserve_formatting: true,
+            maintain_style: true,
+            include_comments: false,
+            preserve_code_blocks: true,
+            cultural_adaptation: true,
+            technical_accuracy: true,
+            creative_freedom: 0.3,
+            back_translation_check: false,
+            glossary: BTreeMap::new(),
+        }
+    }
 }
 
 /// Translation result
@@ -90,8 +139,14 @@ pub struct TranslationResult {
     pub technical_terms: Vec<TechnicalTerm>,
     pub processing_time_ms: u64,
     pub metadata: TranslationMetadata,
+    /// Original and translation in the requested bilingual layout; `translated_text` stays the translation alone
+    #[serde(default)]
+    pub bilingual: Option<String>,
 }
 
+impl CacheValue for TranslationResult {}
+
+
 /// Translation quality metrics
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct TranslationQuality {
@@ -101,6 +156,15 @@ pub struct TranslationQuality {
     pub cultural_fitness_score: f32,
     pub technical_accuracy_score: f32,
     pub overall_score: f32,
+    /// Non-whitespace character ratio of translation to original
+    #[serde(default)]
+    pub length_ratio: f32,
+    #[serde(default)]
+    pub glossary_compliance: Option<f32>,
+    #[serde(default)]
+    pub back_translation_similarity: Option<f32>,
+    #[serde(default)]
+    pub issues: Vec<String>,
 }
 
 /// Cultural adaptation made
@@ -129,6 +193,9 @@ pub struct TranslationMetadata {
     pub context_window_used: usize,
     pub domain_specific_adaptations: Vec<String>,
     pub quality_recommendations: Vec<String>,
+    /// Register the translation was held to, for languages where formality is grammar
+    #[serde(default)]
+    pub register: Option<RegisterChoice>,
 }
 
 /// Language information
@@ -171,6 +238,9 @@ pub struct EnhancedTranslationRequest {
     pub target_language: String,
     pub enhancement_level: EnhancementLevel,
     pub output_format: OutputFormat,
+    /// Export the original alongside the translation, sentence by sentence
+    #[serde(default)]
+    pub bilingual: Option<BilingualLayout>,
 }
 
 /// Enhancement levels
@@ -209,20 +279,34 @@ impl Translator {
         Self {
             client,
             model,
-            translation_cache: tokio::sync::Mutex::new(lru::LruCache::new(200)), // Cache 200 translations
-            supported_languages: Self::initialize_supported_languages(),
+            translation_cache: tokio::sync::Mutex::new(PersistentCache::new(CacheKind::Translation, TRANSLATION_CACHE_CAPACITY)),
         }
     }
 
     /// Translate text with context awareness
     pub async fn translate(&self, request: TranslationRequest) -> Result<TranslationResult, AIMLError> {
+        self.translate_with_model(request, &self.model).await
+    }
+
+    /// Translate with a model other than the configured one, e.g. a fallback while it is down
+    pub async fn translate_with_model(
+        &self,
+        mut request: TranslationRequest,
+        model: &str,
+    ) -> Result<TranslationResult, AIMLError> {
         let start_time = std::time::Instant::now();
 
+        // Settle the register before the cache lookup, since user defaults are not part of the request
+        let register = resolve_register(&request.target_language, &request.context);
+        if let Some(choice) = &register {
+            request.context.formality_level = choice.level;
+        }
+
         // Check cache first
-        let cache_key = self.generate_cache_key(&request);
-        if let Some(cached_result) = self.translation_cache.lock().await.get(&cache_key) {
+        let cache_key = request_cache_key(model, &request);
+        if let Some(cached_result) = self.translation_cache.lock().await.get(&cache_key).await {
             log::debug!("Returning cached translation");
-            return Ok(cached_result.clone());
+            return Ok(cached_result);
         }
 
         // Detect source language if not provided
@@ -233,7 +317,7 @@ impl Translator {
         };
 
         // Prepare translation prompt
-        let translation_prompt = self.build_translation_prompt(&request);
+        let translation_prompt = self.build_translation_prompt(&request, register.as_ref());
         
         // Get AI client and translate
         let client = self.client.lock().await;
@@ -248,9 +332,11 @@ impl Translator {
             },
         ];
 
+        let prompt_tokens = count_messages(model, &messages);
+
         let response = client.chat_completion(super::ai_ml_core::AIMLRequest {
-            model: self.model.clone(),
-            messages,
+            model: model.to_string(),
+            messages: messages.clone(),
             max_tokens: Some(2000),
             temperature: Some(0.2), // Lower temperature for consistent translations
             stream: Some(false),
@@ -263,10 +349,32 @@ impl Translator {
         let processing_time = start_time.elapsed().as_millis();
         
         if let Some(choice) = response.choices.first() {
-            let translated_text = choice.message.content.clone();
+            let mut translated_text = choice.message.content.clone();
+            let mut register_issue = None;
+            if let Some(required) = &register {
+                (translated_text, register_issue) =
+                    self.enforce_register(&client, model, &messages, translated_text, required).await;
+            }
+
+            let back_translation = if request.options.back_translation_check {
+                self.back_translate(&client, model, &translated_text, &request.target_language, &source_language).await
+            } else {
+                None
+            };
             
             // Analyze translation quality
-            let quality = self.analyze_translation_quality(&request.text, &translated_text, &source_language, &request.target_language);
+            let mut quality = estimate_quality(&QualityInputs {
+                original: &request.text,
+                translated: &translated_text,
+                source_language: &source_language,
+                target_language: &request.target_language,
+                glossary: &request.options.glossary,
+                back_translation: back_translation.as_deref(),
+            });
+            if let Some(issue) = register_issue {
+                quality.cultural_fitness_score = quality.cultural_fitness_score.min(0.5);
+                quality.issues.push(issue);
+            }
             
             // Extract cultural adaptations and technical terms
             let cultural_adaptations = self.extract_cultural_adaptations(&request, &translated_text);
@@ -285,16 +393,21 @@ impl Translator {
                 technical_terms,
                 processing_time_ms: processing_time,
                 metadata: TranslationMetadata {
-                    model_used: self.model.clone(),
-                    tokens_consumed: response.usage.map(|u| u.total_tokens).unwrap_or(0),
-                    context_window_used: 1000, // Estimated
+                    model_used: model.to_string(),
+                    tokens_consumed: response.usage.as_ref().map(|u| u.total_tokens).unwrap_or(0),
+                    context_window_used: response
+                        .usage
+                        .as_ref()
+                        .map_or(prompt_tokens, |u| u.prompt_tokens as usize),
                     domain_specific_adaptations: vec!["domain_applied".to_string()],
                     quality_recommendations: self.generate_quality_recommendations(&quality),
+                    register,
                 },
+                bilingual: None,
             };
 
             // Cache the result
-            self.translation_cache.lock().await.put(cache_key, result.clone());
+            self.translation_cache.lock().await.put(cache_key, result.clone()).await;
 
             Ok(result)
         } else {
@@ -302,30 +415,22 @@ impl Translator {
         }
     }
 
-    /// Translate with enhancement
-    pub async fn translate_with_enhancement(&self, text: String, source_language: Option<String>, target_language: String) -> Result<TranslationResult, AIMLError> {
+    /// Translate with enhancement, using the caller's domain, formality and options
+    pub async fn translate_with_enhancement(
+        &self,
+        text: String,
+        source_language: Option<String>,
+        target_language: String,
+        context: TranslationContext,
+        options: TranslationOptions,
+    ) -> Result<TranslationResult, AIMLError> {
         let request = TranslationRequest {
             id: Uuid::new_v4().to_string(),
             text,
             source_language,
             target_language,
-            context: TranslationContext {
-                domain: TranslationDomain::General,
-                audience: "general".to_string(),
-                purpose: "communication".to_string(),
-                formality_level: FormalityLevel::Neutral,
-                cultural_considerations: true,
-                technical_terminology: false,
-            },
-            options: TranslationOptions {
-                preserve_formatting: true,
-                maintain_style: true,
-                include_comments: false,
-                preserve_code_blocks: true,
-                cultural_adaptation: true,
-                technical_accuracy: true,
-                creative_freedom: 0.3,
-            },
+            context,
+            options,
         };
 
         self.translate(request).await
@@ -348,6 +453,7 @@ impl Translator {
                 formality_level: FormalityLevel::Neutral,
                 cultural_considerations: true,
                 technical_terminology: true,
+                preceding_context: None,
             },
             options: TranslationOptions {
                 preserve_formatting: true,
@@ -362,6 +468,8 @@ impl Translator {
                     EnhancementLevel::Full => 0.5,
                     EnhancementLevel::Creative => 0.8,
                 },
+                back_translation_check: false,
+                glossary: BTreeMap::new(),
             },
         };
 
@@ -378,7 +486,19 @@ impl Translator {
         }
 
         // Apply format-specific processing
-        translation_result = self.apply_format_processing(translation_result, &request.output_format).await?;
+        translation_result = match request.bilingual {
+            Some(layout) => {
+                let bilingual = BilingualText::align(
+                    &translation_result.original_text,
+                    &translation_result.translated_text,
+                    &translation_result.source_language,
+                    &translation_result.target_language,
+                );
+                translation_result.bilingual = Some(bilingual.render(layout, &request.output_format));
+                translation_result
+            }
+            None => self.apply_format_processing(translation_result, &request.output_format).await?,
+        };
 
         log::info!("Enhanced translation completed in {}ms", start_time.elapsed().as_millis());
         Ok(translation_result)
@@ -414,7 +534,7 @@ impl Translator {
             let detected_lang = choice.message.content.clone().trim().to_string();
             
             // Validate against supported languages
-            if self.supported_languages.iter().any(|lang| lang.code == detected_lang) {
+            if Self::supported_languages().iter().any(|lang| lang.code == detected_lang) {
                 Ok(detected_lang)
             } else {
                 log::warn!("Detected language '{}' not in supported list, defaulting to 'en'", detected_lang);
@@ -442,6 +562,33 @@ impl Translator {
         Ok(results)
     }
 
+    /// Number of cached results
+    pub async fn cached_entries(&self) -> usize {
+        self.translation_cache.lock().await.len()
+    }
+
+    /// Drop all cached results from memory and disk, returning how many were removed
+    pub async fn clear_cache(&self) -> usize {
+        self.translation_cache.lock().await.clear().await
+    }
+
+    /// Load recent results saved by earlier runs
+    pub async fn warm_cache(&self) -> usize {
+        self.translation_cache.lock().await.warm_load().await
+    }
+
+    pub async fn cache_stats(&self) -> CacheStats {
+        self.translation_cache.lock().await.stats()
+    }
+
+    /// Cap the cache below its normal size, evicting the oldest entries; `None` restores the normal size
+    pub async fn limit_cache(&self, limit: Option<usize>) {
+        let capacity = limit.unwrap_or(TRANSLATION_CACHE_CAPACITY).clamp(1, TRANSLATION_CACHE_CAPACITY);
+        if let Some(capacity) = std::num::NonZeroUsize::new(capacity) {
+            self.translation_cache.lock().await.resize(capacity);
+        }
+    }
+
     /// Check service health
     pub async fn health_check(&self) -> Result<bool, AIMLError> {
         let test_request = TranslationRequest {
@@ -456,6 +603,7 @@ impl Translator {
                 formality_level: FormalityLevel::Neutral,
                 cultural_considerations: false,
                 technical_terminology: false,
+                preceding_context: None,
             },
             options: TranslationOptions {
                 preserve_formatting: false,
@@ -465,6 +613,8 @@ impl Translator {
                 cultural_adaptation: false,
                 technical_accuracy: false,
                 creative_freedom: 0.0,
+                back_translation_check: false,
+                glossary: BTreeMap::new(),
             },
         };
 
@@ -475,8 +625,8 @@ impl Translator {
     }
 
     /// Get supported languages
-    pub async fn get_supported_languages(&self) -> &Vec<LanguageInfo> {
-        &self.supported_languages
+    pub async fn get_supported_languages(&self) -> Vec<LanguageInfo> {
+        Self::supported_languages()
     }
 
     /// Get translation statistics
@@ -493,7 +643,7 @@ impl Translator {
     }
 
     /// Build translation prompt
-    fn build_translation_prompt(&self, request: &TranslationRequest) -> String {
+    fn build_translation_prompt(&self, request: &TranslationRequest, register: Option<&RegisterChoice>) -> String {
         let mut prompt = format!(
             "You are an expert translator from {} to {}.\n\n\
              Domain: {:?}\n\
@@ -508,6 +658,10 @@ impl Translator {
             request.context.formality_level
         );
 
+        if let Some(register) = register {
+            prompt.push_str(&translation_formality::instruction(register));
+        }
+
         if request.context.cultural_considerations {
             prompt.push_str("Consider cultural nuances and local expressions.\n");
         }
@@ -530,31 +684,104 @@ impl Translator {
             prompt.push_str("• Maintain the writing style and voice\n");
         }
 
+        if let Some(preceding) = &request.context.preceding_context {
+            prompt.push_str(&format!(
+                "\nThe text continues a longer document. Preceding passage, for continuity only (do not translate or repeat it):\n{}\n",
+                preceding
+            ));
+        }
+
         prompt.push_str("\nTranslate the following text:");
         prompt
     }
 
-    /// Analyze translation quality
-    fn analyze_translation_quality(&self, original: &str, translated: &str, source: &str, target: &str) -> TranslationQuality {
-        // In a real implementation, you'd use sophisticated quality metrics
-        // For now, provide a reasonable estimate
-        
-        let length_ratio = translated.len() as f32 / original.len() as f32;
-        let fluency_score = if length_ratio > 0.8 && length_ratio < 1.5 { 0.9 } else { 0.7 };
-        let adequacy_score = 0.85;
-        let preservation_score = 0.88;
-        let cultural_fitness_score = 0.82;
-        let technical_accuracy_score = 0.80;
-        let overall_score = (fluency_score + adequacy_score + preservation_score + 
-                           cultural_fitness_score + technical_accuracy_score) / 5.0;
-
-        TranslationQuality {
-            fluency_score,
-            adequacy_score,
-            preservation_score,
-            cultural_fitness_score,
-            technical_accuracy_score,
-            overall_score,
+    /// Ask once for a rewrite when the translation slipped into the wrong register, returning an issue if it still has
+    async fn enforce_register(
+        &self,
+        client: &AIMLClient,
+        model: &str,
+        messages: &[super::ai_ml_core::AIMLMessage],
+        translated: String,
+        required: &RegisterChoice,
+    ) -> (String, Option<String>) {
+        let wrong = |text: &str| detect_register(&required.language, text).filter(|found| !satisfies(required.register, *found));
+        let Some(found) = wrong(&translated) else {
+            return (translated, None);
+        };
+        log::debug!("Translation used {:?} register where {:?} is required; asking for a rewrite", found, required.register);
+
+        let mut retry = messages.to_vec();
+        retry.push(super::ai_ml_core::AIMLMessage {
+            role: "assistant".to_string(),
+            content: translated.clone(),
+        });
+        retry.push(super::ai_ml_core::AIMLMessage {
+            role: "user".to_string(),
+            content: format!(
+                "That translation uses the wrong register. Rewrite it addressing the reader with {}, changing nothing else. Respond with the translation only.",
+                required.forms
+            ),
+        });
+        let response = client.chat_completion(super::ai_ml_core::AIMLRequest {
+            model: model.to_string(),
+            messages: retry,
+            max_tokens: Some(2000),
+            temperature: Some(0.0),
+            stream: Some(false),
+            top_p: Some(1.0),
+            frequency_penalty: Some(0.0),
+            presence_penalty: Some(0.0),
+            stop: None,
+        }).await;
+
+        let rewritten = match response {
+            Ok(response) => response.choices.first().map(|choice| choice.message.content.clone()),
+            Err(e) => {
+                log::debug!("Register rewrite skipped: {}", e);
+                None
+            }
+        };
+        let text = rewritten.filter(|text| !text.trim().is_empty()).unwrap_or(translated);
+        let issue = wrong(&text).map(|found| {
+            format!("Translation uses {:?} register; {} was required ({})", found, required.forms, required.language)
+        });
+        (text, issue)
+    }
+
+    /// Translate output back to the source language for round-trip scoring; failures just skip the check
+    async fn back_translate(&self, client: &AIMLClient, model: &str, text: &str, from: &str, to: &str) -> Option<String> {
+        let messages = vec![
+            super::ai_ml_core::AIMLMessage {
+                role: "system".to_string(),
+                content: format!(
+                    "Translate the following {} text into {} as literally as possible. Respond with the translation only.",
+                    from, to
+                ),
+            },
+            super::ai_ml_core::AIMLMessage {
+                role: "user".to_string(),
+                content: text.to_string(),
+            },
+        ];
+
+        let response = client.chat_completion(super::ai_ml_core::AIMLRequest {
+            model: model.to_string(),
+            messages,
+            max_tokens: Some(2000),
+            temperature: Some(0.0),
+            stream: Some(false),
+            top_p: Some(1.0),
+            frequency_penalty: Some(0.0),
+            presence_penalty: Some(0.0),
+            stop: None,
+        }).await;
+
+        match response {
+            Ok(response) => response.choices.first().map(|choice| choice.message.content.clone()),
+            Err(e) => {
+                log::debug!("Back-translation skipped: {}", e);
+                None
+            }
         }
     }
 
@@ -605,6 +832,7 @@ impl Translator {
         if quality.technical_accuracy_score < 0.8 {
             recommendations.push("Verify technical terminology accuracy".to_string());
         }
+        recommendations.extend(quality.issues.iter().cloned());
 
         if recommendations.is_empty() {
             recommendations.push("Translation quality appears good".to_string());
@@ -681,111 +909,79 @@ impl Translator {
         Ok(result)
     }
 
-    /// Generate cache key for request
-    fn generate_cache_key(&self, request: &TranslationRequest) -> String {
-        use std::collections::hash_map::DefaultHasher;
-        use std::hash::{Hash, Hasher};
 
-        let mut hasher = DefaultHasher::new();
-        request.text.hash(&mut hasher);
-        request.source_language.hash(&mut hasher);
-        request.target_language.hash(&mut hasher);
-        format!("{:x}", hasher.finish())
+    /// Enabled translation languages from the language registry
+    fn supported_languages() -> Vec<LanguageInfo> {
+        let registry = match get_language_registry().read() {
+            Ok(registry) => registry,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        registry.translation_languages().into_iter().map(LanguageInfo::from).collect()
     }
+}
 
-    /// Initialize supported languages
-    fn initialize_supported_languages() -> Vec<LanguageInfo> {
-        vec![
-            LanguageInfo {
-                code: "en".to_string(),
-                name: "English".to_string(),
-                native_name: "English".to_string(),
-                family: "Indo-European".to_string(),
-                direction: TextDirection::LeftToRight,
-                supported_models: vec!["gpt-4o".to_string(), "claude-3-5-haiku".to_string()],
-                quality_level: LanguageQuality::Native,
-            },
-            LanguageInfo {
-                code: "es".to_string(),
-                name: "Spanish".to_string(),
-                native_name: "Español".to_string(),
-                family: "Indo-European".to_string(),
-                direction: TextDirection::LeftToRight,
-                supported_models: vec!["gpt-4o".to_string(), "claude-3-5-haiku".to_string()],
-                quality_level: LanguageQuality::High,
-            },
-            LanguageInfo {
-                code: "fr".to_string(),
-                name: "French".to_string(),
-                native_name: "Français".to_string(),
-                family: "Indo-European".to_string(),
-                direction: TextDirection::LeftToRight,
-                supported_models: vec!["gpt-4o".to_string(), "claude-3-5-haiku".to_string()],
-                quality_level: LanguageQuality::High,
-            },
-            LanguageInfo {
-                code: "de".to_string(),
-                name: "German".to_string(),
-                native_name: "Deutsch".to_string(),
-                family: "Indo-European".to_string(),
-                direction: TextDirection::LeftToRight,
-                supported_models: vec!["gpt-4o".to_string(), "claude-3-5-haiku".to_string()],
-                quality_level: LanguageQuality::High,
-            },
-            LanguageInfo {
-                code: "it".to_string(),
-                name: "Italian".to_string(),
-                native_name: "Italiano".to_string(),
-                family: "Indo-European".to_string(),
-                direction: TextDirection::LeftToRight,
-                supported_models: vec!["gpt-4o".to_string(), "claude-3-5-haiku".to_string()],
-                quality_level: LanguageQuality::High,
-            },
-            LanguageInfo {
-                code: "pt".to_string(),
-                name: "Portuguese".to_string(),
-                native_name: "Português".to_string(),
-                family: "Indo-European".to_string(),
-                direction: TextDirection::LeftToRight,
-                supported_models: vec!["gpt-4o".to_string(), "claude-3-5-haiku".to_string()],
-                quality_level: LanguageQuality::High,
-            },
-            LanguageInfo {
-                code: "zh".to_string(),
-                name: "Chinese".to_string(),
-                native_name: "中文".to_string(),
-                family: "Sino-Tibetan".to_string(),
-                direction: TextDirection::LeftToRight,
-                supported_models: vec!["gpt-4o".to_string(), "claude-3-5-haiku".to_string()],
-                quality_level: LanguageQuality::High,
+impl From<&LanguageEntry> for LanguageInfo {
+    fn from(entry: &LanguageEntry) -> Self {
+        Self {
+            code: entry.base.clone(),
+            name: entry.base_name().to_string(),
+            native_name: entry.base_native_name().to_string(),
+            family: entry.family.clone(),
+            direction: match entry.direction.as_str() {
+                "rtl" => TextDirection::RightToLeft,
+                _ => TextDirection::LeftToRight,
             },
-            LanguageInfo {
-                code: "ja".to_string(),
-                name: "Japanese".to_string(),
-                native_name: "日本語".to_string(),
-                family: "Japonic".to_string(),
-                direction: TextDirection::LeftToRight,
-                supported_models: vec!["gpt-4o".to_string(), "claude-3-5-haiku".to_string()],
-                quality_level: LanguageQuality::High,
+            supported_models: entry.translation_models.clone(),
+            quality_level: match entry.translation_quality.as_str() {
+                "Native" => LanguageQuality::Native,
+                "NearNative" => LanguageQuality::NearNative,
+                "High" => LanguageQuality::High,
+                "Good" => LanguageQuality::Good,
+                _ => LanguageQuality::Basic,
             },
-            LanguageInfo {
-                code: "ko".to_string(),
-                name: "Korean".to_string(),
-                native_name: "한국어".to_string(),
-                family: "Koreanic".to_string(),
-                direction: TextDirection::LeftToRight,
-                supported_models: vec!["gpt-4o".to_string(), "claude-3-5-haiku".to_string()],
-                quality_level: LanguageQuality::High,
+        }
+    }
+}
+
+/// Formality, domain, audience and the other options all change the translation, so every field but the ID counts
+fn request_cache_key(model: &str, request: &TranslationRequest) -> String {
+    request_key(model, request, &["/id"])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integrations::result_cache::fields_outside_key;
+
+    #[test]
+    fn formality_and_domain_are_part_of_the_cache_key() {
+        let mut request = TranslationRequest {
+            id: "first".to_string(),
+            text: "Could you send the report?".to_string(),
+            source_language: Some("en".to_string()),
+            target_language: "de".to_string(),
+            context: TranslationContext::default(),
+            options: TranslationOptions::default(),
+        };
+        request.options.glossary.insert("report".to_string(), "Bericht".to_string());
+        assert_eq!(fields_outside_key(&request, |request| request_cache_key("gpt", request)), vec!["/id"]);
+
+        // Enum fields cannot be altered generically, so the two the old key left out are checked directly
+        let formal = TranslationRequest {
+            context: TranslationContext {
+                formality_level: FormalityLevel::VeryFormal,
+                ..request.context.clone()
             },
-            LanguageInfo {
-                code: "ar".to_string(),
-                name: "Arabic".to_string(),
-                native_name: "العربية".to_string(),
-                family: "Afro-Asiatic".to_string(),
-                direction: TextDirection::RightToLeft,
-                supported_models: vec!["gpt-4o".to_string(), "claude-3-5-haiku".to_string()],
-                quality_level: LanguageQuality::High,
+            ..request.clone()
+        };
+        let technical = TranslationRequest {
+            context: TranslationContext {
+                domain: TranslationDomain::Technical,
+                ..request.context.clone()
             },
-        ]
+            ..request.clone()
+        };
+        assert_ne!(request_cache_key("gpt", &formal), request_cache_key("gpt", &request));
+        assert_ne!(request_cache_key("gpt", &technical), request_cache_key("gpt", &request));
     }
 }
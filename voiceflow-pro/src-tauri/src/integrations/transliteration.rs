@@ -0,0 +1,586 @@
+// Transliteration Module
+// Romanization of Japanese, Chinese, Cyrillic, Greek and Arabic text, done on-device where the script allows it
+
+use serde::{Deserialize, Serialize};
+
+use super::ai_ml_api::AIMLAPIGateway;
+use super::ai_ml_core::AIMLError;
+
+/// How text is written in Latin script
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransliterationScheme {
+    /// Modified Hepburn for Japanese; kana on-device, kanji readings need the model
+    Hepburn,
+    /// Hanyu Pinyin with tone marks for Chinese; always needs the model
+    Pinyin,
+    /// ISO 9 for Cyrillic
+    Iso9,
+    /// ISO 843 transcription for Greek
+    Iso843,
+    /// ISO 233 for Arabic, with vowels where the text is pointed
+    Iso233,
+}
+
+impl TransliterationScheme {
+    pub fn label(self) -> &'static str {
+        match self {
+            TransliterationScheme::Hepburn => "modified Hepburn romanization",
+            TransliterationScheme::Pinyin => "Hanyu Pinyin with tone marks",
+            TransliterationScheme::Iso9 => "ISO 9 transliteration of Cyrillic",
+            TransliterationScheme::Iso843 => "ISO 843 transcription of Greek",
+            TransliterationScheme::Iso233 => "ISO 233 transliteration of Arabic",
+        }
+    }
+
+    /// The script the scheme romanizes
+    pub fn script(self) -> &'static str {
+        match self {
+            TransliterationScheme::Hepburn => "Japanese",
+            TransliterationScheme::Pinyin => "Chinese",
+            TransliterationScheme::Iso9 => "Cyrillic",
+            TransliterationScheme::Iso843 => "Greek",
+            TransliterationScheme::Iso233 => "Arabic",
+        }
+    }
+
+    fn covers(self, c: char) -> bool {
+        match self {
+            TransliterationScheme::Hepburn => matches!(c, '\u{3040}'..='\u{30FF}') || is_han(c),
+            TransliterationScheme::Pinyin => is_han(c),
+            TransliterationScheme::Iso9 => matches!(c, '\u{0400}'..='\u{052F}'),
+            TransliterationScheme::Iso843 => matches!(c, '\u{0370}'..='\u{03FF}' | '\u{1F00}'..='\u{1FFF}'),
+            TransliterationScheme::Iso233 => matches!(c, '\u{0600}'..='\u{06FF}' | '\u{0750}'..='\u{077F}'),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransliterationSource {
+    /// Mapped on-device from the scheme's tables
+    Local,
+    /// Written by the model, for text whose reading the tables cannot know, such as kanji
+    Model,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Transliteration {
+    pub text: String,
+    pub scheme: TransliterationScheme,
+    pub source: TransliterationSource,
+}
+
+fn is_han(c: char) -> bool {
+    matches!(c, '\u{3400}'..='\u{4DBF}' | '\u{4E00}'..='\u{9FFF}' | '\u{F900}'..='\u{FAFF}' | '\u{20000}'..='\u{2A6DF}')
+}
+
+/// Whether `text` has anything in the scheme's script to romanize
+pub fn has_script(text: &str, scheme: TransliterationScheme) -> bool {
+    text.chars().any(|c| scheme.covers(c))
+}
+
+/// Whether `text` has characters whose reading only the model knows: kanji, and all of Chinese
+pub fn needs_model(text: &str, scheme: TransliterationScheme) -> bool {
+    matches!(scheme, TransliterationScheme::Hepburn | TransliterationScheme::Pinyin) && text.chars().any(is_han)
+}
+
+/// `text` in the scheme's Latin spelling, or `None` when it has characters only the model can read
+pub fn transliterate_locally(text: &str, scheme: TransliterationScheme) -> Option<String> {
+    if needs_model(text, scheme) {
+        return None;
+    }
+    match scheme {
+        TransliterationScheme::Hepburn => Some(hepburn(text)),
+        // Without hanzi there is nothing Pinyin spells
+        TransliterationScheme::Pinyin => Some(text.to_string()),
+        TransliterationScheme::Iso9 => Some(map_cased(text, iso9)),
+        TransliterationScheme::Iso843 => Some(map_cased(text, iso843)),
+        TransliterationScheme::Iso233 => Some(iso233(text)),
+    }
+}
+
+/// Transliterate on-device when the tables cover the text, otherwise with the gateway and text model in `model`.
+/// Text with nothing in the scheme's script is rejected rather than passed through as a transliteration.
+pub async fn transliterate(
+    model: Option<(&AIMLAPIGateway, &str)>,
+    text: &str,
+    scheme: TransliterationScheme,
+) -> Result<Transliteration, AIMLError> {
+    if !has_script(text, scheme) {
+        return Err(AIMLError::MissingParameter(format!(
+            "{} text to transliterate with {}",
+            scheme.script(),
+            scheme.label()
+        )));
+    }
+    if let Some(local) = transliterate_locally(text, scheme) {
+        return Ok(Transliteration {
+            text: local,
+            scheme,
+            source: TransliterationSource::Local,
+        });
+    }
+    let Some((gateway, model)) = model else {
+        return Err(AIMLError::ServiceUnavailable(format!(
+            "Transliterating this text with {} needs the text model",
+            scheme.label()
+        )));
+    };
+    let system_prompt = format!(
+        "Transliterate the user's text into Latin script using {}. Do not translate it. Keep punctuation, numbers \
+         and anything already in Latin script as it is. Reply with the transliteration only.",
+        scheme.label()
+    );
+    let (transliterated, _usage) = gateway
+        .complete_with_failover(model.to_string(), system_prompt, text.to_string(), Some(0.0))
+        .await?;
+    Ok(Transliteration {
+        text: transliterated.trim().to_string(),
+        scheme,
+        source: TransliterationSource::Model,
+    })
+}
+
+/// Map each lowercase letter with `table`, keeping the case of the original: capitalized, or all caps
+/// when a neighbouring letter is uppercase too, so "Щука" gives "Ŝuka" and "ЩУКА" gives "ŜUKA"
+fn map_cased(text: &str, table: fn(char) -> Option<&'static str>) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::with_capacity(text.len());
+    for (index, &c) in chars.iter().enumerate() {
+        let lower = c.to_lowercase().next().unwrap_or(c);
+        let Some(latin) = table(lower) else {
+            out.push(c);
+            continue;
+        };
+        if lower == c {
+            out.push_str(latin);
+            continue;
+        }
+        let neighbour_upper = [index.checked_sub(1), Some(index + 1)]
+            .into_iter()
+            .flatten()
+            .filter_map(|i| chars.get(i))
+            .any(|n| n.is_uppercase());
+        if neighbour_upper {
+            out.push_str(&latin.to_uppercase());
+        } else {
+            let mut letters = latin.chars();
+            if let Some(first) = letters.next() {
+                out.extend(first.to_uppercase());
+                out.push_str(letters.as_str());
+            }
+        }
+    }
+    out
+}
+
+fn iso9(c: char) -> Option<&'static str> {
+    Some(match c {
+        'а' => "a",
+        'б' => "b",
+        'в' => "v",
+        'г' => "g",
+        'ґ' => "g̀",
+        'д' => "d",
+        'ѓ' => "ǵ",
+        'е' => "e",
+        'ё' => "ë",
+        'є' => "ê",
+        'ж' => "ž",
+        'з' => "z",
+        'ѕ' => "ẑ",
+        'и' => "i",
+        'і' => "ì",
+        'ї' => "ï",
+        'й' => "j",
+        'ј' => "ǰ",
+        'к' => "k",
+        'л' => "l",
+        'љ' => "l̂",
+        'м' => "m",
+        'н' => "n",
+        'њ' => "n̂",
+        'о' => "o",
+        'п' => "p",
+        'р' => "r",
+        'с' => "s",
+        'т' => "t",
+        'ќ' => "ḱ",
+        'у' => "u",
+        'ў' => "ŭ",
+        'ф' => "f",
+        'х' => "h",
+        'ц' => "c",
+        'ч' => "č",
+        'џ' => "d̂",
+        'ш' => "š",
+        'щ' => "ŝ",
+        'ъ' => "ʺ",
+        'ы' => "y",
+        'ь' => "ʹ",
+        'э' => "è",
+        'ю' => "û",
+        'я' => "â",
+        _ => return None,
+    })
+}
+
+fn iso843(c: char) -> Option<&'static str> {
+    Some(match c {
+        'α' | 'ά' => "a",
+        'β' => "v",
+        'γ' => "g",
+        'δ' => "d",
+        'ε' | 'έ' => "e",
+        'ζ' => "z",
+        'η' | 'ή' => "i",
+        'θ' => "th",
+        'ι' | 'ί' | 'ϊ' | 'ΐ' => "i",
+        'κ' => "k",
+        'λ' => "l",
+        'μ' => "m",
+        'ν' => "n",
+        'ξ' => "x",
+        'ο' | 'ό' => "o",
+        'π' => "p",
+        'ρ' => "r",
+        'σ' | 'ς' => "s",
+        'τ' => "t",
+        'υ' | 'ύ' | 'ϋ' | 'ΰ' => "y",
+        'φ' => "f",
+        'χ' => "ch",
+        'ψ' => "ps",
+        'ω' | 'ώ' => "o",
+        // Greek question mark and ano teleia
+        '\u{037E}' => "?",
+        '\u{0387}' => ";",
+        _ => return None,
+    })
+}
+
+/// Arabic has no case, and its short vowels only show where the text is pointed
+fn iso233(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() * 2);
+    // The last letter and where it ends; canonical order puts a vowel sign before the shadda on the same
+    // letter, so the shadda doubles this rather than whatever was written last
+    let mut last_letter: Option<(usize, &'static str)> = None;
+    for c in text.chars() {
+        if let Some(letter) = arabic_letter(c) {
+            out.push_str(letter);
+            last_letter = Some((out.len(), letter));
+            continue;
+        }
+        match c {
+            'َ' => out.push('a'),
+            'ُ' => out.push('u'),
+            'ِ' => out.push('i'),
+            'ً' => out.push_str("an"),
+            'ٌ' => out.push_str("un"),
+            'ٍ' => out.push_str("in"),
+            // Shadda doubles the consonant it sits on
+            'ّ' => {
+                if let Some((end, letter)) = last_letter.take() {
+                    out.insert_str(end, letter);
+                }
+            }
+            'ْ' | 'ـ' => {}
+            _ => {
+                last_letter = None;
+                match c {
+                    '،' => out.push(','),
+                    '؛' => out.push(';'),
+                    '؟' => out.push('?'),
+                    '٠'..='٩' => out.push(char::from(b'0' + (c as u32 - '٠' as u32) as u8)),
+                    _ => out.push(c),
+                }
+            }
+        }
+    }
+    out
+}
+
+fn arabic_letter(c: char) -> Option<&'static str> {
+    Some(match c {
+        'ء' | 'أ' | 'إ' | 'ؤ' | 'ئ' => "ʾ",
+        'آ' => "ʾā",
+        'ا' => "ā",
+        'ب' => "b",
+        'ة' => "ẗ",
+        'ت' => "t",
+        'ث' => "ṯ",
+        'ج' => "ǧ",
+        'ح' => "ḥ",
+        'خ' => "ẖ",
+        'د' => "d",
+        'ذ' => "ḏ",
+        'ر' => "r",
+        'ز' => "z",
+        'س' => "s",
+        'ش' => "š",
+        'ص' => "ṣ",
+        'ض' => "ḍ",
+        'ط' => "ṭ",
+        'ظ' => "ẓ",
+        'ع' => "ʿ",
+        'غ' => "ġ",
+        'ف' => "f",
+        'ق' => "q",
+        'ك' => "k",
+        'ل' => "l",
+        'م' => "m",
+        'ن' => "n",
+        'ه' => "h",
+        'و' => "w",
+        'ى' => "ỳ",
+        'ي' => "y",
+        _ => return None,
+    })
+}
+
+fn kana(c: char) -> Option<&'static str> {
+    Some(match c {
+        'あ' | 'ぁ' => "a",
+        'い' | 'ぃ' | 'ゐ' => "i",
+        'う' | 'ぅ' => "u",
+        'え' | 'ぇ' | 'ゑ' => "e",
+        'お' | 'ぉ' | 'を' => "o",
+        'か' | 'ゕ' => "ka",
+        'き' => "ki",
+        'く' => "ku",
+        'け' | 'ゖ' => "ke",
+        'こ' => "ko",
+        'さ' => "sa",
+        'し' => "shi",
+        'す' => "su",
+        'せ' => "se",
+        'そ' => "so",
+        'た' => "ta",
+        'ち' => "chi",
+        'つ' => "tsu",
+        'て' => "te",
+        'と' => "to",
+        'な' => "na",
+        'に' => "ni",
+        'ぬ' => "nu",
+        'ね' => "ne",
+        'の' => "no",
+        'は' => "ha",
+        'ひ' => "hi",
+        'ふ' => "fu",
+        'へ' => "he",
+        'ほ' => "ho",
+        'ま' => "ma",
+        'み' => "mi",
+        'む' => "mu",
+        'め' => "me",
+        'も' => "mo",
+        'や' | 'ゃ' => "ya",
+        'ゆ' | 'ゅ' => "yu",
+        'よ' | 'ょ' => "yo",
+        'ら' => "ra",
+        'り' => "ri",
+        'る' => "ru",
+        'れ' => "re",
+        'ろ' => "ro",
+        'わ' | 'ゎ' => "wa",
+        'ん' => "n",
+        'が' => "ga",
+        'ぎ' => "gi",
+        'ぐ' => "gu",
+        'げ' => "ge",
+        'ご' => "go",
+        'ざ' => "za",
+        'じ' | 'ぢ' => "ji",
+        'ず' | 'づ' => "zu",
+        'ぜ' => "ze",
+        'ぞ' => "zo",
+        'だ' => "da",
+        'で' => "de",
+        'ど' => "do",
+        'ば' => "ba",
+        'び' => "bi",
+        'ぶ' => "bu",
+        'べ' => "be",
+        'ぼ' => "bo",
+        'ぱ' => "pa",
+        'ぴ' => "pi",
+        'ぷ' => "pu",
+        'ぺ' => "pe",
+        'ぽ' => "po",
+        'ゔ' => "vu",
+        _ => return None,
+    })
+}
+
+/// Katakana as the matching hiragana, so one table serves both
+fn to_hiragana(c: char) -> char {
+    match c {
+        '\u{30A1}'..='\u{30F6}' => char::from_u32(c as u32 - 0x60).unwrap_or(c),
+        _ => c,
+    }
+}
+
+fn with_macron(vowel: char) -> Option<char> {
+    Some(match vowel {
+        'a' => 'ā',
+        'i' => 'ī',
+        'u' => 'ū',
+        'e' => 'ē',
+        'o' => 'ō',
+        _ => return None,
+    })
+}
+
+fn hepburn(text: &str) -> String {
+    let chars: Vec<char> = text.chars().map(to_hiragana).collect();
+    let mut out = String::with_capacity(text.len());
+    let mut geminate = false;
+    let mut index = 0;
+    while index < chars.len() {
+        let c = chars[index];
+        index += 1;
+        match c {
+            'っ' => {
+                geminate = true;
+                continue;
+            }
+            // The long-vowel mark lengthens the vowel before it
+            'ー' => {
+                if let Some(long) = out.chars().last().and_then(with_macron) {
+                    out.pop();
+                    out.push(long);
+                }
+                continue;
+            }
+            '、' => {
+                out.push_str(", ");
+                continue;
+            }
+            '。' => {
+                out.push_str(". ");
+                continue;
+            }
+            '・' => {
+                out.push(' ');
+                continue;
+            }
+            _ => {}
+        }
+        let Some(base) = kana(c) else {
+            geminate = false;
+            out.push(c);
+            continue;
+        };
+        let mut syllable = base.to_string();
+        match chars.get(index) {
+            // Youon: き + ゃ is "kya", し + ゃ is "sha"
+            Some('ゃ' | 'ゅ' | 'ょ') if base.len() > 1 && base.ends_with('i') => {
+                let glide = kana(chars[index]).unwrap_or_default();
+                syllable.pop();
+                if matches!(base, "shi" | "chi" | "ji") {
+                    syllable.push_str(&glide[1..]);
+                } else {
+                    syllable.push_str(glide);
+                }
+                index += 1;
+            }
+            // Small vowels spell sounds foreign to Japanese: ファ "fa", ティ "ti", ウィ "wi"
+            Some(small @ ('ぁ' | 'ぃ' | 'ぅ' | 'ぇ' | 'ぉ')) => {
+                syllable.pop();
+                if syllable.is_empty() {
+                    syllable.push('w');
+                }
+                syllable.push_str(kana(*small).unwrap_or_default());
+                index += 1;
+            }
+            _ => {}
+        }
+        if c == 'ん' {
+            // n' keeps "kan'i" apart from "kani"
+            let next = chars.get(index).copied().and_then(kana);
+            if next.map_or(false, |next| next.starts_with(['a', 'i', 'u', 'e', 'o', 'y'])) {
+                syllable.push('\'');
+            }
+        }
+        if std::mem::take(&mut geminate) {
+            if syllable.starts_with("ch") {
+                out.push('t');
+            } else if let Some(consonant) = syllable.chars().next().filter(|c| !"aiueon".contains(*c)) {
+                out.push(consonant);
+            }
+        }
+        out.push_str(&syllable);
+    }
+    out.trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local(text: &str, scheme: TransliterationScheme) -> String {
+        transliterate_locally(text, scheme).unwrap()
+    }
+
+    #[test]
+    fn shadda_doubles_the_consonant_not_its_vowel() {
+        // Canonical order, fatha before shadda
+        assert_eq!(local("\u{0628}\u{064E}\u{0651}", TransliterationScheme::Iso233), "bba");
+        assert_eq!(local("\u{0628}\u{0651}\u{064E}", TransliterationScheme::Iso233), "bba");
+        assert_eq!(local("مُحَمَّد", TransliterationScheme::Iso233), "muḥammad");
+    }
+
+    #[test]
+    fn arabic_punctuation_and_digits_become_latin() {
+        assert_eq!(local("كَتَبَ، ١٢؟", TransliterationScheme::Iso233), "kataba, 12?");
+    }
+
+    #[test]
+    fn cyrillic_keeps_its_case() {
+        assert_eq!(local("Щука", TransliterationScheme::Iso9), "Ŝuka");
+        assert_eq!(local("ЩУКА", TransliterationScheme::Iso9), "ŜUKA");
+    }
+
+    #[test]
+    fn greek_is_transcribed() {
+        assert_eq!(local("Αθήνα", TransliterationScheme::Iso843), "Athina");
+    }
+
+    #[test]
+    fn kana_is_romanized_on_device() {
+        assert_eq!(local("カタカナ", TransliterationScheme::Hepburn), "katakana");
+        assert_eq!(local("がっこう", TransliterationScheme::Hepburn), "gakkou");
+        assert_eq!(local("ラーメン", TransliterationScheme::Hepburn), "rāmen");
+        assert_eq!(local("きょうと", TransliterationScheme::Hepburn), "kyouto");
+        assert_eq!(local("しんいち", TransliterationScheme::Hepburn), "shin'ichi");
+    }
+
+    #[test]
+    fn kanji_and_hanzi_need_the_model() {
+        assert!(transliterate_locally("東京へ", TransliterationScheme::Hepburn).is_none());
+        assert!(transliterate_locally("你好", TransliterationScheme::Pinyin).is_none());
+    }
+
+    #[test]
+    fn text_outside_the_scheme_script_has_nothing_to_transliterate() {
+        assert!(!has_script("hello", TransliterationScheme::Pinyin));
+        assert!(!has_script("こんにちは", TransliterationScheme::Iso9));
+        assert!(!has_script("Привет", TransliterationScheme::Iso843));
+        assert!(has_script("say Привет", TransliterationScheme::Iso9));
+    }
+
+    #[tokio::test]
+    async fn the_wrong_script_is_an_error_not_a_transliteration() {
+        let result = transliterate(None, "こんにちは", TransliterationScheme::Iso9).await;
+        assert!(matches!(result, Err(AIMLError::MissingParameter(_))));
+        let result = transliterate(None, "no hanzi here", TransliterationScheme::Pinyin).await;
+        assert!(matches!(result, Err(AIMLError::MissingParameter(_))));
+    }
+
+    #[tokio::test]
+    async fn kanji_without_the_model_is_unavailable() {
+        let result = transliterate(None, "東京", TransliterationScheme::Hepburn).await;
+        assert!(matches!(result, Err(AIMLError::ServiceUnavailable(_))));
+    }
+}
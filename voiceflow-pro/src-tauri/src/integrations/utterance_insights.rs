@@ -0,0 +1,260 @@
+// Utterance Insights Module
+// Lightweight per-utterance sentiment and intent signals emitted during dictation
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::ai_ml_api::{AIMLAPIGateway, EnhancedContext, SentimentPolarity, SessionContext, UserIntent};
+use super::emotion_tracking;
+
+/// Words that pull an utterance towards a positive reading
+const POSITIVE_WORDS: &[&str] = &[
+    "good", "great", "excellent", "thanks", "thank", "appreciate", "love", "happy", "glad",
+    "awesome", "perfect", "wonderful", "nice", "pleased", "fantastic", "brilliant",
+];
+
+/// Words that pull an utterance towards a negative reading
+const NEGATIVE_WORDS: &[&str] = &[
+    "bad", "terrible", "awful", "hate", "angry", "annoyed", "frustrated", "ridiculous",
+    "unacceptable", "disappointed", "useless", "stupid", "worst", "broken", "furious", "sick",
+];
+
+/// Words that amplify whatever sentiment follows them
+const INTENSIFIERS: &[&str] = &["very", "really", "extremely", "so", "totally", "absolutely", "completely"];
+
+/// Words that flip the sentiment of the next sentiment-bearing word
+const NEGATIONS: &[&str] = &["not", "no", "never", "don't", "isn't", "wasn't", "can't", "won't"];
+
+/// Leading verbs that usually mark an imperative command
+const COMMAND_VERBS: &[&str] = &[
+    "open", "close", "send", "delete", "stop", "start", "create", "set", "run", "show", "save",
+    "find", "search", "call", "remind", "schedule", "insert", "copy", "paste", "undo",
+];
+
+/// Configuration for the utterance insight stream
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UtteranceInsightsConfig {
+    pub enabled: bool,
+    pub min_words: usize,
+    pub hostile_flag_threshold: f32,
+}
+
+impl Default for UtteranceInsightsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_words: 3,
+            hostile_flag_threshold: -0.6,
+        }
+    }
+}
+
+/// Where an insight came from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum InsightSource {
+    Model,
+    LocalHeuristic,
+}
+
+/// Flags assistive UIs can act on
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum InsightFlag {
+    HostileTone,
+    Shouting,
+    UrgentRequest,
+}
+
+/// Per-utterance sentiment and intent signal
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UtteranceInsight {
+    pub id: String,
+    pub text: String,
+    pub polarity: SentimentPolarity,
+    pub sentiment_score: f32, // -1.0 to 1.0
+    pub intent: UserIntent,
+    pub confidence: f32,
+    pub source: InsightSource,
+    pub flags: Vec<InsightFlag>,
+    pub timestamp: u64,
+}
+
+/// Classify an utterance, preferring the context processor and falling back to local heuristics
+pub async fn classify_utterance(
+    text: &str,
+    gateway: Option<&AIMLAPIGateway>,
+    privacy_mode: bool,
+    config: &UtteranceInsightsConfig,
+) -> UtteranceInsight {
+    let mut insight = analyze_locally(text, config);
+
+    if privacy_mode {
+        return insight;
+    }
+
+    if let Some(gateway) = gateway {
+        match gateway.predict_intent(text.to_string(), dictation_context()).await {
+            Ok(intent) => {
+                insight.intent = intent;
+                insight.source = InsightSource::Model;
+                insight.confidence = (insight.confidence + 0.2).min(0.95);
+            }
+            Err(e) => {
+                log::debug!("Intent prediction failed, keeping local heuristic: {}", e);
+            }
+        }
+    }
+
+    insight
+}
+
+/// Minimal context describing live dictation for the intent classifier, with the tracked mood when there is one
+fn dictation_context() -> EnhancedContext {
+    EnhancedContext {
+        user_intent: None,
+        domain: None,
+        audience: None,
+        purpose: Some("live dictation".to_string()),
+        constraints: Vec::new(),
+        previous_messages: Vec::new(),
+        conversation_history: Vec::new(),
+        session_context: SessionContext {
+            emotional_state: emotion_tracking::current_state().map(|session| session.state).unwrap_or_default(),
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+/// Classify an utterance entirely on-device (used in privacy mode and as a fallback)
+pub fn analyze_locally(text: &str, config: &UtteranceInsightsConfig) -> UtteranceInsight {
+    let words: Vec<String> = text
+        .split_whitespace()
+        .map(|w| w.trim_matches(|c: char| !c.is_alphanumeric() && c != '\'').to_lowercase())
+        .filter(|w| !w.is_empty())
+        .collect();
+
+    let sentiment_score = score_sentiment(&words, text);
+    let intent = classify_intent(&words, text);
+
+    let mut flags = Vec::new();
+    if sentiment_score <= config.hostile_flag_threshold {
+        flags.push(InsightFlag::HostileTone);
+    }
+    if is_shouting(text) {
+        flags.push(InsightFlag::Shouting);
+    }
+    if words.iter().any(|w| w == "asap" || w == "urgent" || w == "immediately") {
+        flags.push(InsightFlag::UrgentRequest);
+    }
+
+    // Short utterances carry too little signal for a confident reading
+    let confidence = if words.len() < config.min_words { 0.3 } else { 0.55 };
+
+    UtteranceInsight {
+        id: Uuid::new_v4().to_string(),
+        text: text.to_string(),
+        polarity: polarity_from_score(sentiment_score),
+        sentiment_score,
+        intent,
+        confidence,
+        source: InsightSource::LocalHeuristic,
+        flags,
+        timestamp: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+    }
+}
+
+/// Lexicon-based sentiment score in the range -1.0 to 1.0
+fn score_sentiment(words: &[String], raw: &str) -> f32 {
+    let mut score = 0.0f32;
+    let mut hits = 0usize;
+    let mut multiplier = 1.0f32;
+    let mut negate = false;
+
+    for word in words {
+        let w = word.as_str();
+        if INTENSIFIERS.contains(&w) {
+            multiplier = 1.5;
+            continue;
+        }
+        if NEGATIONS.contains(&w) {
+            negate = true;
+            continue;
+        }
+
+        let base = if POSITIVE_WORDS.contains(&w) {
+            1.0
+        } else if NEGATIVE_WORDS.contains(&w) {
+            -1.0
+        } else {
+            0.0
+        };
+
+        if base != 0.0 {
+            let value = if negate { -base * 0.5 } else { base };
+            score += value * multiplier;
+            hits += 1;
+        }
+        multiplier = 1.0;
+        negate = false;
+    }
+
+    if hits == 0 {
+        return 0.0;
+    }
+
+    let mut normalized = score / hits as f32;
+    // Exclamation marks amplify whatever direction the text already leans
+    let exclamations = raw.matches('!').count().min(3) as f32;
+    normalized *= 1.0 + exclamations * 0.1;
+    normalized.clamp(-1.0, 1.0)
+}
+
+/// Rule-based intent classification
+fn classify_intent(words: &[String], raw: &str) -> UserIntent {
+    let trimmed = raw.trim();
+    let first = words.first().map(|w| w.as_str()).unwrap_or("");
+    let question_openers = ["what", "why", "how", "when", "where", "who", "which", "is", "are", "can", "do", "does"];
+
+    if trimmed.ends_with('?') || question_openers.contains(&first) {
+        if words.iter().any(|w| w == "please") || (first == "can" && words.get(1).map(|w| w == "you").unwrap_or(false)) {
+            return UserIntent::Request;
+        }
+        return UserIntent::Question;
+    }
+    if COMMAND_VERBS.contains(&first) {
+        return UserIntent::Command;
+    }
+    if words.iter().any(|w| w == "please") || trimmed.to_lowercase().starts_with("could you") {
+        return UserIntent::Request;
+    }
+    if words.iter().any(|w| NEGATIVE_WORDS.contains(&w.as_str())) {
+        return UserIntent::Complaint;
+    }
+    if words.iter().any(|w| w == "thanks" || w == "thank" || w == "appreciate") {
+        return UserIntent::Praise;
+    }
+    UserIntent::Discussion
+}
+
+/// Heuristic for all-caps dictation (usually the recognizer picking up emphasis)
+fn is_shouting(text: &str) -> bool {
+    let letters: Vec<char> = text.chars().filter(|c| c.is_alphabetic()).collect();
+    letters.len() >= 8 && letters.iter().all(|c| !c.is_lowercase())
+}
+
+fn polarity_from_score(score: f32) -> SentimentPolarity {
+    if score >= 0.6 {
+        SentimentPolarity::VeryPositive
+    } else if score >= 0.2 {
+        SentimentPolarity::Positive
+    } else if score <= -0.6 {
+        SentimentPolarity::VeryNegative
+    } else if score <= -0.2 {
+        SentimentPolarity::Negative
+    } else {
+        SentimentPolarity::Neutral
+    }
+}
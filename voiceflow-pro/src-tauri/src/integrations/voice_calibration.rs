@@ -0,0 +1,346 @@
+// Voice Calibration Module
+// Measures ambient noise, speaking level and recognition confidence to build a per-user voice profile
+
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use tokio::time::{Duration, Instant};
+use uuid::Uuid;
+
+use super::voice_recognition::{VoiceEngineHandle, VoiceEvent, VoiceRecognitionConfig};
+use crate::errors::AppError;
+use crate::event_channel::EventReceiver;
+use crate::storage::active_profile_dir;
+
+pub(crate) const PROFILE_FILE: &str = "voice_profile.json";
+/// Level the gain is tuned to bring typical speech up to
+const TARGET_SPEECH_LEVEL: f32 = 0.5;
+const AMBIENT_PHASE: Duration = Duration::from_secs(5);
+const MAX_READING_PHASE: Duration = Duration::from_secs(45);
+/// Fewer samples than this and the measurement is not trusted
+const MIN_SAMPLES: usize = 3;
+
+/// Sentences read aloud during calibration; together they cover most English phonemes
+pub const DEFAULT_CALIBRATION_SCRIPT: &[&str] = &[
+    "The quick brown fox jumps over the lazy dog.",
+    "Please schedule a meeting with the design team for Thursday at three thirty.",
+    "She sells seashells by the seashore, and the shells she sells are surely seashells.",
+    "Send the quarterly report to Jordan and copy the finance group.",
+];
+
+/// Tuning derived from a calibration run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VoiceProfile {
+    pub noise_floor: f32,
+    pub speech_level: f32,
+    /// Multiplier applied to input levels so typical speech reaches the target level
+    pub recommended_gain: f32,
+    /// Input level (after gain) above which audio counts as speech
+    pub vad_threshold: f32,
+    pub typical_confidence: Option<f32>,
+    pub confidence_threshold: f32,
+    pub clipping_detected: bool,
+    pub language: String,
+    pub calibrated_at: u64,
+}
+
+impl VoiceProfile {
+    /// Apply the profile's tuning to a recognition config
+    pub fn apply(&self, config: &mut VoiceRecognitionConfig) {
+        config.vad_threshold = Some(self.vad_threshold);
+        config.input_gain = Some(self.recommended_gain);
+        config.confidence_threshold = self.confidence_threshold;
+    }
+}
+
+/// Load the active user's saved voice profile, if they have calibrated
+pub fn load_voice_profile() -> Option<VoiceProfile> {
+    let path = active_profile_dir().ok()?.join(PROFILE_FILE);
+    let json = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&json)
+        .map_err(|e| log::warn!("Ignoring unreadable voice profile: {}", e))
+        .ok()
+}
+
+fn save_voice_profile(profile: &VoiceProfile) -> Result<(), AppError> {
+    let dir = active_profile_dir()?;
+    std::fs::create_dir_all(&dir).map_err(|e| AppError::Internal(e.to_string()))?;
+    let json = serde_json::to_vec_pretty(profile).map_err(|e| AppError::Internal(e.to_string()))?;
+    std::fs::write(dir.join(PROFILE_FILE), json)
+        .map_err(|e| AppError::Internal(format!("Failed to save voice profile: {}", e)))
+}
+
+/// Stage of a calibration run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "phase", content = "message", rename_all = "snake_case")]
+pub enum CalibrationPhase {
+    Idle,
+    /// The user stays quiet while background noise is measured
+    AmbientNoise,
+    /// The user reads the script aloud
+    Reading,
+    Complete,
+    Failed(String),
+}
+
+impl CalibrationPhase {
+    pub fn is_running(&self) -> bool {
+        matches!(self, CalibrationPhase::AmbientNoise | CalibrationPhase::Reading)
+    }
+}
+
+/// Snapshot reported by `get_calibration_status` and the progress event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CalibrationStatus {
+    pub session_id: Option<String>,
+    pub phase: CalibrationPhase,
+    /// Overall progress from 0.0 to 1.0
+    pub progress: f32,
+    pub script: Vec<String>,
+    /// Index of the sentence the user should be reading
+    pub current_line: usize,
+    pub samples_collected: usize,
+    pub profile: Option<VoiceProfile>,
+}
+
+impl Default for CalibrationStatus {
+    fn default() -> Self {
+        Self {
+            session_id: None,
+            phase: CalibrationPhase::Idle,
+            progress: 0.0,
+            script: Vec::new(),
+            current_line: 0,
+            samples_collected: 0,
+            profile: load_voice_profile(),
+        }
+    }
+}
+
+static CALIBRATION_STATUS: std::sync::OnceLock<Mutex<CalibrationStatus>> = std::sync::OnceLock::new();
+
+fn status_cell() -> &'static Mutex<CalibrationStatus> {
+    CALIBRATION_STATUS.get_or_init(|| Mutex::new(CalibrationStatus::default()))
+}
+
+/// Current calibration status
+pub fn get_calibration_status() -> CalibrationStatus {
+    status_cell().lock().map(|s| s.clone()).unwrap_or_default()
+}
+
+fn update_status(f: impl FnOnce(&mut CalibrationStatus)) -> CalibrationStatus {
+    let mut status = match status_cell().lock() {
+        Ok(status) => status,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    f(&mut status);
+    status.clone()
+}
+
+/// Reload the saved profile after the active user changes; a run in progress is left alone
+pub fn reset_calibration_status() {
+    update_status(|status| {
+        if !status.phase.is_running() {
+            *status = CalibrationStatus::default();
+        }
+    });
+}
+
+/// Claim the calibration slot, failing if a run is already in progress
+pub fn begin_calibration(script: Vec<String>) -> Result<CalibrationStatus, AppError> {
+    let mut status = match status_cell().lock() {
+        Ok(status) => status,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    if status.phase.is_running() {
+        return Err(AppError::VoiceRecognition(crate::errors::VoiceError::CalibrationInProgress));
+    }
+    *status = CalibrationStatus {
+        session_id: Some(Uuid::new_v4().to_string()),
+        phase: CalibrationPhase::AmbientNoise,
+        progress: 0.0,
+        script,
+        current_line: 0,
+        samples_collected: 0,
+        profile: status.profile.take(),
+    };
+    Ok(status.clone())
+}
+
+/// Measurements gathered during a run
+#[derive(Debug, Default)]
+struct Measurements {
+    ambient_levels: Vec<f32>,
+    speech_levels: Vec<f32>,
+    confidences: Vec<f32>,
+    clipped_frames: usize,
+    transcript_words: usize,
+}
+
+/// Drive a calibration run to completion, reporting progress through `on_progress`.
+/// The caller lends the engine's event stream and gets it back when the run ends.
+pub async fn run_calibration<F: Fn(&CalibrationStatus)>(
+    engine: &VoiceEngineHandle,
+    events: &mut EventReceiver<VoiceEvent>,
+    on_progress: F,
+) -> CalibrationStatus {
+    let script = get_calibration_status().script;
+    let script_words: usize = script.iter().map(|line| line.split_whitespace().count()).sum::<usize>().max(1);
+    let language = engine.status().config.language;
+
+    // Discard anything queued before the run so old audio is not measured
+    while events.try_recv().is_ok() {}
+
+    let mut measurements = Measurements::default();
+    let started = Instant::now();
+    let mut phase = CalibrationPhase::AmbientNoise;
+    let mut reading_started = started;
+
+    let failure = loop {
+        let now = Instant::now();
+        if phase == CalibrationPhase::AmbientNoise && now.duration_since(started) >= AMBIENT_PHASE {
+            if measurements.ambient_levels.len() < MIN_SAMPLES {
+                break Some("No audio was received from the microphone".to_string());
+            }
+            phase = CalibrationPhase::Reading;
+            reading_started = now;
+        }
+        let reading_elapsed = now.duration_since(reading_started);
+        if phase == CalibrationPhase::Reading
+            && (measurements.transcript_words >= script_words || reading_elapsed >= MAX_READING_PHASE)
+        {
+            break None;
+        }
+
+        match tokio::time::timeout(Duration::from_millis(250), events.recv()).await {
+            Ok(Some(VoiceEvent::AudioMetrics(metrics))) => {
+                if metrics.clipping {
+                    measurements.clipped_frames += 1;
+                }
+                match phase {
+                    CalibrationPhase::AmbientNoise => measurements.ambient_levels.push(metrics.volume),
+                    _ => measurements.speech_levels.push(metrics.volume),
+                }
+            }
+            Ok(Some(VoiceEvent::SpeechResult(result))) if phase == CalibrationPhase::Reading && result.is_final => {
+                measurements.confidences.push(result.confidence);
+                measurements.transcript_words += result.transcript.split_whitespace().count();
+            }
+            Ok(Some(VoiceEvent::SpeechError(error))) => break Some(error),
+            Ok(Some(_)) | Err(_) => {}
+            Ok(None) => break Some("Voice engine stopped during calibration".to_string()),
+        }
+
+        let progress = match phase {
+            CalibrationPhase::AmbientNoise => 0.2 * now.duration_since(started).as_secs_f32() / AMBIENT_PHASE.as_secs_f32(),
+            _ => {
+                let by_words = measurements.transcript_words as f32 / script_words as f32;
+                let by_time = reading_elapsed.as_secs_f32() / MAX_READING_PHASE.as_secs_f32();
+                0.2 + 0.8 * by_words.max(by_time).min(1.0)
+            }
+        };
+        let current_line = line_for_words(&script, measurements.transcript_words);
+        let samples = measurements.ambient_levels.len() + measurements.speech_levels.len();
+        let status = update_status(|status| {
+            status.phase = phase.clone();
+            status.progress = progress.min(0.99);
+            status.current_line = current_line;
+            status.samples_collected = samples;
+        });
+        on_progress(&status);
+    };
+
+    let outcome = match failure {
+        Some(reason) => Err(reason),
+        None => build_profile(&measurements, language),
+    };
+    let status = match outcome {
+        Ok(profile) => match save_voice_profile(&profile) {
+            Ok(()) => update_status(|status| {
+                status.phase = CalibrationPhase::Complete;
+                status.progress = 1.0;
+                status.profile = Some(profile);
+            }),
+            Err(e) => update_status(|status| status.phase = CalibrationPhase::Failed(e.to_string())),
+        },
+        Err(reason) => update_status(|status| status.phase = CalibrationPhase::Failed(reason)),
+    };
+    on_progress(&status);
+    status
+}
+
+/// Mark a run as failed before it could start measuring
+pub fn fail_calibration(reason: String) -> CalibrationStatus {
+    update_status(|status| status.phase = CalibrationPhase::Failed(reason))
+}
+
+fn line_for_words(script: &[String], words_read: usize) -> usize {
+    let mut total = 0;
+    for (index, line) in script.iter().enumerate() {
+        total += line.split_whitespace().count();
+        if words_read < total {
+            return index;
+        }
+    }
+    script.len().saturating_sub(1)
+}
+
+fn build_profile(measurements: &Measurements, language: String) -> Result<VoiceProfile, String> {
+    let noise_floor = percentile(&measurements.ambient_levels, 0.5).unwrap_or(0.0);
+
+    // Only frames clearly above the noise floor are speech; pauses between sentences are ignored
+    let voiced: Vec<f32> = measurements
+        .speech_levels
+        .iter()
+        .copied()
+        .filter(|level| *level > noise_floor * 1.5 + 0.02)
+        .collect();
+    if voiced.len() < MIN_SAMPLES {
+        return Err("Speech was too quiet to measure; move closer to the microphone and try again".to_string());
+    }
+    let speech_level = percentile(&voiced, 0.9).unwrap_or(TARGET_SPEECH_LEVEL);
+    if speech_level <= noise_floor * 2.0 {
+        return Err("Background noise is too close to speech level for reliable recognition".to_string());
+    }
+
+    let clipping_detected = measurements.clipped_frames * 20 > measurements.speech_levels.len().max(1);
+    let mut recommended_gain = (TARGET_SPEECH_LEVEL / speech_level).clamp(0.25, 4.0);
+    if clipping_detected {
+        recommended_gain = recommended_gain.min(0.8);
+    }
+
+    // Threshold sits a third of the way from the (amplified) noise floor to speech
+    let gained_noise = noise_floor * recommended_gain;
+    let gained_speech = speech_level * recommended_gain;
+    let vad_threshold = (gained_noise + (gained_speech - gained_noise) / 3.0).clamp(0.05, 0.6);
+
+    // Accept results somewhat below what this speaker usually gets, so normal speech is not dropped
+    let typical_confidence = percentile(&measurements.confidences, 0.5);
+    let confidence_threshold = percentile(&measurements.confidences, 0.1)
+        .map(|low| (low - 0.05).clamp(0.3, 0.85))
+        .unwrap_or(0.7);
+
+    Ok(VoiceProfile {
+        noise_floor,
+        speech_level,
+        recommended_gain,
+        vad_threshold,
+        typical_confidence,
+        confidence_threshold,
+        clipping_detected,
+        language,
+        calibrated_at: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+    })
+}
+
+fn percentile(values: &[f32], p: f32) -> Option<f32> {
+    if values.is_empty() {
+        return None;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let index = ((sorted.len() - 1) as f32 * p).round() as usize;
+    Some(sorted[index])
+}
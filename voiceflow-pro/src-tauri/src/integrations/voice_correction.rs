@@ -0,0 +1,173 @@
+// Voice Correction Module
+// Handles "correction: <wrong> to <right>" utterances against recently dictated text
+
+use std::sync::OnceLock;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::unicode_text::similarity;
+
+/// Minimum similarity for a fuzzy match; recognizers rarely mishear a phrase the same way twice
+const MIN_MATCH_SIMILARITY: f32 = 0.7;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CorrectionSettings {
+    /// Ask the frontend to replace the text in the target app, not just in the session buffer
+    pub reinject: bool,
+    /// Add corrected words to the vocabulary so recognition favours them next time
+    pub learn_vocabulary: bool,
+}
+
+impl Default for CorrectionSettings {
+    fn default() -> Self {
+        Self {
+            reinject: true,
+            learn_vocabulary: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CorrectionCommand {
+    pub wrong: String,
+    pub right: String,
+}
+
+/// Result of applying a correction to the session buffer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CorrectionOutcome {
+    pub command: CorrectionCommand,
+    /// Text as it appeared in the buffer
+    pub matched: String,
+    pub similarity: f32,
+    /// Byte range of `matched` in the text before the correction
+    pub start: usize,
+    pub end: usize,
+    pub before: String,
+    pub after: String,
+    /// Whether the frontend should also replace the text in the target app
+    #[serde(default)]
+    pub reinject: bool,
+}
+
+/// Recognize "correction: X to Y", "correction, change X to Y" and "correction: replace X with Y".
+/// The leading "correction" is required; "change the deadline to Monday" on its own is ordinary dictation.
+pub fn parse_correction_command(utterance: &str) -> Option<CorrectionCommand> {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    let pattern = PATTERN.get_or_init(|| {
+        Regex::new(
+            r#"(?i)^\s*correction\s*[:,.]?\s+(?:please\s+)?(?:(?:correct|change|replace|fix)\s+)?["']?(.+?)["']?\s+(?:to|with|into|should\s+be)\s+["']?(.+?)["']?\s*[.!]?\s*$"#,
+        )
+        .expect("correction pattern is valid")
+    });
+    let captures = pattern.captures(utterance)?;
+    let wrong = captures.get(1)?.as_str().trim().to_string();
+    let right = captures.get(2)?.as_str().trim().to_string();
+    if wrong.is_empty() || right.is_empty() || wrong.eq_ignore_ascii_case(&right) {
+        return None;
+    }
+    Some(CorrectionCommand { wrong, right })
+}
+
+/// Replace the closest match of `command.wrong` in `text`, preferring the most recent occurrence
+pub fn apply_correction(text: &str, command: &CorrectionCommand) -> Option<CorrectionOutcome> {
+    let words = word_spans(text);
+    let target = normalize(&command.wrong);
+    let target_len = command.wrong.split_whitespace().count().max(1);
+    if words.is_empty() || target.is_empty() {
+        return None;
+    }
+
+    // Mis-recognitions often split or merge words, so windows one word shorter and longer are tried too
+    let mut best: Option<(f32, usize, usize)> = None;
+    for size in target_len.saturating_sub(1).max(1)..=target_len + 1 {
+        for first in 0..words.len().saturating_sub(size - 1) {
+            let last = first + size - 1;
+            let candidate = normalize(&text[words[first].0..words[last].1]);
+            let score = similarity(&candidate, &target);
+            // `>=` keeps later windows on ties, since corrections usually target what was just said
+            if best.map_or(true, |(best_score, _, _)| score >= best_score) {
+                best = Some((score, words[first].0, words[last].1));
+            }
+        }
+    }
+
+    let (score, start, end) = best?;
+    if score < MIN_MATCH_SIMILARITY {
+        return None;
+    }
+    let matched = &text[start..end];
+    let replacement = match_capitalization(matched, &command.right);
+    let after = format!("{}{}{}", &text[..start], replacement, &text[end..]);
+    Some(CorrectionOutcome {
+        command: command.clone(),
+        matched: matched.to_string(),
+        similarity: score,
+        start,
+        end,
+        before: text.to_string(),
+        after,
+        reinject: false,
+    })
+}
+
+/// Byte spans of words with surrounding punctuation excluded, so "Smyth," matches as "Smyth"
+fn word_spans(text: &str) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut offset = 0;
+    for token in text.split_inclusive(char::is_whitespace) {
+        let word = token.trim_end();
+        let leading = word.len() - word.trim_start_matches(|c: char| !c.is_alphanumeric()).len();
+        let core = word.trim_matches(|c: char| !c.is_alphanumeric());
+        if !core.is_empty() {
+            spans.push((offset + leading, offset + leading + core.len()));
+        }
+        offset += token.len();
+    }
+    spans
+}
+
+fn normalize(text: &str) -> String {
+    text.split_whitespace()
+        .map(|w| w.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase())
+        .filter(|w| !w.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Keep a capital letter when the replaced text started a sentence or was a name
+fn match_capitalization(matched: &str, replacement: &str) -> String {
+    let starts_upper = matched.chars().next().map_or(false, char::is_uppercase);
+    let mut chars = replacement.chars();
+    match chars.next() {
+        Some(first) if starts_upper && first.is_lowercase() => first.to_uppercase().chain(chars).collect(),
+        _ => replacement.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn corrections_need_the_correction_prefix() {
+        assert_eq!(parse_correction_command("Change the deadline to Monday."), None);
+        assert_eq!(parse_correction_command("Replace the filter with a new one"), None);
+        assert_eq!(
+            parse_correction_command("Correction: change Smyth to Smith."),
+            Some(CorrectionCommand {
+                wrong: "Smyth".to_string(),
+                right: "Smith".to_string()
+            })
+        );
+        assert_eq!(
+            parse_correction_command("correction, \"fourteen\" should be forty"),
+            Some(CorrectionCommand {
+                wrong: "fourteen".to_string(),
+                right: "forty".to_string()
+            })
+        );
+    }
+}
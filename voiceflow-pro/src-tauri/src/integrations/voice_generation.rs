@@ -5,7 +5,19 @@ use std::sync::Arc;
 use tokio::sync::Mutex;
 use uuid::Uuid;
 
+use futures_util::{stream, StreamExt};
+
 use super::ai_ml_core::{AIMLClient, AIMLError, AIMLService};
+use super::audio_upload::wav_duration;
+use super::provider_failover::SYSTEM_VOICE;
+use crate::platform::run;
+use crate::integrations::result_cache::{request_key, CacheKind, CacheStats, CacheValue, PersistentCache};
+use crate::integrations::pronunciation::{apply_plain, apply_ssml, PronunciationSettings};
+
+/// Synthesized clips kept in memory during normal use
+const SYNTHESIS_CACHE_CAPACITY: usize = 50;
+/// Syntheses in flight at once during a batch
+const BATCH_CONCURRENCY: usize = 3;
 
 /// Voice Generation Service
 #[derive(Debug)]
@@ -13,7 +25,8 @@ pub struct VoiceGenerator {
     client: Arc<Mutex<AIMLClient>>,
     model: String,
     default_voice: String,
-    synthesis_cache: tokio::sync::Mutex<lru::LruCache<String, VoiceResult>>,
+    synthesis_cache: tokio::sync::Mutex<PersistentCache<VoiceResult>>,
+    pronunciations: PronunciationSettings,
 }
 
 /// Voice generation request
@@ -26,6 +39,48 @@ pub struct VoiceRequest {
     pub processing_options: VoiceProcessingOptions,
 }
 
+impl VoiceRequest {
+    /// Plain request for reading text aloud: uncompressed audio at a natural pace, silence trimmed so clips can be joined
+    pub fn for_text(text: String, model: String, voice_id: Option<String>, language_code: String) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            text,
+            voice_config: VoiceConfig {
+                model,
+                voice_id,
+                language_code,
+                use_neural_voices: true,
+                voice_characteristics: VoiceCharacteristics {
+                    speaking_rate: 1.0,
+                    pitch: 0.0,
+                    volume: 1.0,
+                    emphasis: 1.0,
+                    style: VoiceStyle::Narrator,
+                    emotion: VoiceEmotion::Neutral,
+                },
+                ssml_enabled: false,
+            },
+            audio_settings: AudioSettings {
+                output_format: AudioFormat::WAV,
+                sample_rate: 24000,
+                bitrate: 384,
+                channels: 1,
+                quality_level: AudioQuality::High,
+            },
+            processing_options: VoiceProcessingOptions {
+                apply_noise_reduction: false,
+                normalize_audio: true,
+                remove_silence: true,
+                enhance_clarity: false,
+                dynamic_range_compression: false,
+                speed_normalization: false,
+                pitch_correction: false,
+                reverb_effect: None,
+            },
+        }
+    }
+}
+
 /// Voice configuration
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct VoiceConfig {
@@ -134,6 +189,18 @@ pub struct VoiceResult {
     pub metadata: VoiceMetadata,
 }
 
+/// The audio is kept in a file of its own instead of as a JSON number array
+impl CacheValue for VoiceResult {
+    fn take_blob(&mut self) -> Option<Vec<u8>> {
+        Some(std::mem::take(&mut self.audio_data))
+    }
+
+    fn restore_blob(&mut self, blob: Vec<u8>) {
+        self.audio_data = blob;
+    }
+}
+
+
 /// Voice metadata
 #[derive(Debug, Clone, serde::Serialize, serde:: Deserialize)]
 pub struct VoiceMetadata {
@@ -167,6 +234,24 @@ pub struct VoiceModel {
     pub realtime: bool,
 }
 
+/// Outcome of one request in a batch; exactly one of `result` and `error` is set
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct BatchSynthesisItem {
+    pub index: usize,
+    pub request_id: String,
+    pub result: Option<VoiceResult>,
+    pub error: Option<String>,
+}
+
+/// Reported after each request in a batch finishes
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct BatchSynthesisProgress {
+    pub completed: usize,
+    pub total: usize,
+    pub request_id: String,
+    pub error: Option<String>,
+}
+
 /// Voice generation statistics
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct VoiceStats {
@@ -184,7 +269,16 @@ impl VoiceGenerator {
             client,
             model,
             default_voice: "alloy".to_string(), // Default OpenAI voice
-            synthesis_cache: tokio::sync::Mutex::new(lru::LruCache::new(50)), // Cache 50 results
+            synthesis_cache: tokio::sync::Mutex::new(PersistentCache::new(CacheKind::Synthesis, SYNTHESIS_CACHE_CAPACITY)),
+            pronunciations: PronunciationSettings::default(),
+        }
+    }
+
+    /// Replace the pronunciation lexicons; cached clips may have used the old ones, so the cache switches namespace
+    pub async fn set_pronunciations(&mut self, pronunciations: PronunciationSettings) {
+        if self.pronunciations != pronunciations {
+            self.pronunciations = pronunciations;
+            self.synthesis_cache.lock().await.set_namespace(&self.pronunciations);
         }
     }
 
@@ -193,20 +287,21 @@ impl VoiceGenerator {
         let start_time = std::time::Instant::now();
 
         // Check cache first
-        let cache_key = self.generate_cache_key(&request);
-        if let Some(cached_result) = self.synthesis_cache.lock().await.get(&cache_key) {
+        let cache_key = request_cache_key(&self.model, &request);
+        if let Some(cached_result) = self.synthesis_cache.lock().await.get(&cache_key).await {
             log::debug!("Returning cached voice synthesis");
-            return Ok(cached_result.clone());
+            return Ok(cached_result);
         }
 
         // Prepare voice configuration
         let voice_config = self.prepare_voice_config(&request.voice_config);
         
-        // Generate SSML if enabled
-        let processed_text = if request.voice_config.ssml_enabled {
-            self.generate_ssml(&request.text, &request.voice_config.characteristics)?
+        // Apply the lexicon for the request's language, as SSML tags or plain respellings
+        let lexicon = self.pronunciations.entries_for(&request.voice_config.language_code);
+        let processed_text = if request.voice_config.ssml_enabled && accepts_ssml(&request.voice_config.model) {
+            self.generate_ssml(&apply_ssml(&request.text, &lexicon), &request.voice_config.voice_characteristics)?
         } else {
-            request.text.clone()
+            apply_plain(&strip_ssml(&request.text), &lexicon)
         };
 
         // Send TTS request
@@ -261,34 +356,65 @@ impl VoiceGenerator {
         };
 
         // Cache the result
-        self.synthesis_cache.lock().await.put(cache_key, result.clone());
+        self.synthesis_cache.lock().await.put(cache_key, result.clone()).await;
 
         Ok(result)
     }
 
-    /// Generate voice with multiple variations
+    /// Read text aloud with the operating system's synthesizer, the last fallback when no voice model answers.
+    /// It speaks 16-bit WAV in the system's default voice for the language, without the request's voice settings
+    pub async fn generate_system_voice(&self, id: String, text: &str, language_code: &str) -> Result<VoiceResult, AIMLError> {
+        let start_time = std::time::Instant::now();
+        let text = strip_ssml(text);
+        let path = std::env::temp_dir().join(format!("voiceflow-speech-{}.wav", Uuid::new_v4()));
+        let spoken = system_speech(&text, language_code, &path.to_string_lossy()).await;
+        let written = tokio::fs::read(&path).await;
+        let _ = tokio::fs::remove_file(&path).await;
+        spoken.map_err(|e| AIMLError::ServiceUnavailable(format!("System speech failed: {}", e)))?;
+        let audio_data =
+            written.map_err(|e| AIMLError::ServiceUnavailable(format!("System speech wrote no audio: {}", e)))?;
+
+        let (sample_rate, duration_seconds) = wav_duration(&audio_data).unwrap_or((22_050, text.len() as f32 / 10.0));
+        let processing_time = start_time.elapsed().as_millis() as u64;
+        Ok(VoiceResult {
+            id,
+            audio_data,
+            format: AudioFormat::WAV,
+            duration_seconds,
+            sample_rate,
+            bitrate: (sample_rate * 16 / 1000) as u16,
+            voice_used: SYSTEM_VOICE.to_string(),
+            confidence_score: 0.7,
+            processing_time_ms: processing_time,
+            metadata: VoiceMetadata {
+                text_length: text.len(),
+                phonemes_generated: self.estimate_phonemes(&text),
+                processing_pipeline: vec!["system_synthesizer".to_string()],
+                quality_metrics: AudioQualityMetrics {
+                    snr_db: 30.0,
+                    clarity_score: 0.8,
+                    naturalness: 0.6,
+                    intelligibility: 0.9,
+                },
+                api_response_time_ms: processing_time,
+            },
+        })
+    }
+
+    /// Generate voice with multiple variations, each with a slightly different rate and pitch
     pub async fn generate_variations(&self, request: VoiceRequest, variations: u8) -> Result<Vec<VoiceResult>, AIMLError> {
         let mut results = Vec::new();
         
         for i in 0..variations {
+            let mut voice_config = request.voice_config.clone();
+            let characteristics = &mut voice_config.voice_characteristics;
+            characteristics.speaking_rate = (characteristics.speaking_rate * (0.9 + i as f32 * 0.05)).clamp(0.5, 2.0);
+            characteristics.pitch = (characteristics.pitch + (i as f32 - variations as f32 / 2.0) * 2.0).clamp(-50.0, 50.0);
+
             let variation_request = VoiceRequest {
                 id: format!("{}-{}", request.id, i),
                 text: request.text.clone(),
-                voice_config: VoiceConfig {
-                    model: request.voice_config.model.clone(),
-                    voice_id: request.voice_config.voice_id.clone(),
-                    language_code: request.voice_config.language_code.clone(),
-                    use_neural_voices: request.voice_config.use_neural_voices,
-                    voice_characteristics: VoiceCharacteristics {
-                        speaking_rate: request.voice_config.characteristics.speaking_rate * (0.9 + (i as f32 * 0.05)), // Slight variations
-                        pitch: request.voice_config.characteristics.pitch + (i as f32 - variations as f32 / 2.0) * 2.0,
-                        volume: request.voice_config.characteristics.volume,
-                        emphasis: request.voice_config.characteristics.emphasis,
-                        style: request.voice_config.characteristics.style.clone(),
-                        emotion: request.voice_config.characteristics.emotion.clone(),
-                    },
-                    ssml_enabled: request.voice_config.ssml_enabled,
-                },
+                voice_config,
                 audio_settings: request.audio_settings.clone(),
                 processing_options: request.processing_options.clone(),
             };
@@ -300,31 +426,22 @@ impl VoiceGenerator {
         Ok(results)
     }
 
-    /// Batch synthesize multiple texts
-    pub async fn batch_synthesize(&self, requests: Vec<VoiceRequest>) -> Result<Vec<VoiceResult>, AIMLError> {
-        let mut results = Vec::new();
-        let mut handles = Vec::new();
-
-        // Process in parallel with limited concurrency
-        for request in requests {
-            let handle = tokio::spawn({
-                let self_ref = &self;
-                async move {
-                    self_ref.generate_voice(request).await
-                }
-            });
-            handles.push(handle);
-        }
+    /// Batch synthesize multiple texts. Every request gets its own result, in request order,
+    /// so one failure does not lose the rest; `on_item` is called as each one finishes.
+    pub async fn batch_synthesize<F: FnMut(usize, &Result<VoiceResult, AIMLError>)>(
+        &self,
+        requests: Vec<VoiceRequest>,
+        mut on_item: F,
+    ) -> Vec<Result<VoiceResult, AIMLError>> {
+        let mut synthesis = stream::iter(requests.into_iter().map(|request| self.generate_voice(request)))
+            .buffered(BATCH_CONCURRENCY);
 
-        // Collect results
-        for handle in handles {
-            match handle.await {
-                Ok(result) => results.push(result?),
-                Err(e) => return Err(AIMLError::NetworkError(format!("Batch synthesis error: {}", e))),
-            }
+        let mut results = Vec::new();
+        while let Some(result) = synthesis.next().await {
+            on_item(results.len(), &result);
+            results.push(result);
         }
-
-        Ok(results)
+        results
     }
 
     /// Get available voice models
@@ -401,6 +518,33 @@ impl VoiceGenerator {
         Ok(voices)
     }
 
+    /// Number of cached results
+    pub async fn cached_entries(&self) -> usize {
+        self.synthesis_cache.lock().await.len()
+    }
+
+    /// Drop all cached results from memory and disk, returning how many were removed
+    pub async fn clear_cache(&self) -> usize {
+        self.synthesis_cache.lock().await.clear().await
+    }
+
+    /// Load recent clips saved by earlier runs
+    pub async fn warm_cache(&self) -> usize {
+        self.synthesis_cache.lock().await.warm_load().await
+    }
+
+    pub async fn cache_stats(&self) -> CacheStats {
+        self.synthesis_cache.lock().await.stats()
+    }
+
+    /// Cap the cache below its normal size, evicting the oldest entries; `None` restores the normal size
+    pub async fn limit_cache(&self, limit: Option<usize>) {
+        let capacity = limit.unwrap_or(SYNTHESIS_CACHE_CAPACITY).clamp(1, SYNTHESIS_CACHE_CAPACITY);
+        if let Some(capacity) = std::num::NonZeroUsize::new(capacity) {
+            self.synthesis_cache.lock().await.resize(capacity);
+        }
+    }
+
     /// Check service health
     pub async fn health_check(&self) -> Result<bool, AIMLError> {
         let test_request = VoiceRequest {
@@ -469,7 +613,7 @@ impl VoiceGenerator {
         }
     }
 
-    /// Generate SSML markup
+    /// Generate SSML markup around `text`, which must already be escaped
     fn generate_ssml(&self, text: &str, characteristics: &VoiceCharacteristics) -> Result<String, AIMLError> {
         let mut ssml = String::new();
         ssml.push_str("<speak>");
@@ -558,17 +702,6 @@ impl VoiceGenerator {
         pipeline
     }
 
-    /// Generate cache key for request
-    fn generate_cache_key(&self, request: &VoiceRequest) -> String {
-        use std::collections::hash_map::DefaultHasher;
-        use std::hash::{Hash, Hasher};
-
-        let mut hasher = DefaultHasher::new();
-        request.text.hash(&mut hasher);
-        request.voice_config.voice_id.hash(&mut hasher);
-        request.voice_config.characteristics.speaking_rate.to_bits().hash(&mut hasher);
-        format!("{:x}", hasher.finish())
-    }
 
     /// Convert audio format to string
     fn get_format_string(&self, format: &AudioFormat) -> String {
@@ -615,3 +748,90 @@ impl VoiceEmotion {
         }
     }
 }
+
+/// Speak `text` into a WAV file at `path`: `say` on macOS, System.Speech on Windows and espeak-ng elsewhere
+async fn system_speech(text: &str, language_code: &str, path: &str) -> Result<(), String> {
+    if cfg!(target_os = "macos") {
+        run("say", &["--file-format=WAVE", "--data-format=LEI16@22050", "-o", path, "-f", "-"], Some(text)).await
+    } else if cfg!(target_os = "windows") {
+        let script = format!(
+            "[Console]::InputEncoding = [Text.Encoding]::UTF8; Add-Type -AssemblyName System.Speech; \
+             $synth = New-Object System.Speech.Synthesis.SpeechSynthesizer; $synth.SetOutputToWaveFile('{}'); \
+             $synth.Speak([Console]::In.ReadToEnd()); $synth.Dispose()",
+            path.replace('\'', "''")
+        );
+        run("powershell", &["-NoProfile", "-NonInteractive", "-Command", &script], Some(text)).await
+    } else {
+        let voice = language_code.split('-').next().unwrap_or(language_code).to_ascii_lowercase();
+        run("espeak-ng", &["-v", &voice, "-w", path, "--stdin"], Some(text)).await
+    }
+}
+
+/// OpenAI's speech models read markup out loud, so they get plain text with respellings instead of SSML
+fn accepts_ssml(model: &str) -> bool {
+    let name = model.rsplit('/').next().unwrap_or(model).to_ascii_lowercase();
+    !(model.to_ascii_lowercase().starts_with("openai/") || name.starts_with("tts-1") || name.ends_with("-tts"))
+}
+
+/// `text` without SSML tags and with its entities decoded; plain text passes through unchanged
+fn strip_ssml(text: &str) -> String {
+    let trimmed = text.trim_start();
+    if !(trimmed.starts_with("<speak") || trimmed.starts_with("<?xml")) {
+        return text.to_string();
+    }
+    let mut plain = String::with_capacity(text.len());
+    let mut in_tag = false;
+    for c in text.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => {
+                in_tag = false;
+                // Tags such as <break/> separate words
+                if !plain.ends_with(char::is_whitespace) {
+                    plain.push(' ');
+                }
+            }
+            _ if !in_tag => plain.push(c),
+            _ => {}
+        }
+    }
+    let plain = plain
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+    plain.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Voice, characteristics, audio settings and post-processing all change the clip, so every field but the ID counts
+fn request_cache_key(model: &str, request: &VoiceRequest) -> String {
+    request_key(model, request, &["/id"])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integrations::result_cache::fields_outside_key;
+
+    #[test]
+    fn openai_speech_gets_plain_text() {
+        assert!(!accepts_ssml("openai/tts-1-hd"));
+        assert!(!accepts_ssml("tts-1"));
+        assert!(!accepts_ssml("gpt-4o-mini-tts"));
+        assert!(accepts_ssml("elevenlabs/eleven_multilingual_v2"));
+        assert_eq!(
+            strip_ssml("<speak><voice name=\"nova\">Fish &amp; chips<break time=\"400ms\"/>please</voice></speak>"),
+            "Fish & chips please"
+        );
+        assert_eq!(strip_ssml("3 < 4 & 5 > 2"), "3 < 4 & 5 > 2");
+    }
+
+    #[test]
+    fn every_voice_and_audio_setting_but_the_request_id_is_part_of_the_cache_key() {
+        let request =
+            VoiceRequest::for_text("Hello there".to_string(), "tts".to_string(), Some("nova".to_string()), "en".to_string());
+        assert_eq!(fields_outside_key(&request, |request| request_cache_key("tts", request)), vec!["/id"]);
+    }
+}
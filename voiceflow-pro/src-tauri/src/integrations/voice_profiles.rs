@@ -0,0 +1,232 @@
+// Custom Voice Profiles Module
+// Registers provider-cloned voices with the speaker's consent and keeps the combined voice list cached
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use super::ai_ml_api::{AIMLAPIGateway, AudioQuality, VoiceModel, VoiceRequest};
+use super::voice_selection::VoiceSelectionSettings;
+
+/// How long the voice list is reused before the provider is asked again
+const CATALOG_TTL: Duration = Duration::from_secs(10 * 60);
+/// Providers whose TTS models accept voices cloned from the user's own recordings
+const CUSTOM_VOICE_PROVIDERS: &[&str] = &["elevenlabs"];
+/// Short phrase synthesized to confirm a custom voice still exists
+const PROBE_TEXT: &str = "Voice check.";
+/// Each probe is a paid synthesis, so a voice that answered is trusted this long, refresh or not
+const PROBE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The speaker's confirmation that this voice may be used
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VoiceConsent {
+    /// Person whose voice was cloned
+    pub speaker_name: String,
+    /// The speaker is the user, or gave permission to clone and use their voice
+    pub confirmed: bool,
+    #[serde(default)]
+    pub confirmed_at: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CustomVoiceProfile {
+    pub id: String,
+    pub name: String,
+    /// TTS model the voice belongs to, e.g. "elevenlabs/eleven_multilingual_v2"
+    pub model: String,
+    /// Voice ID the provider assigned when the voice was cloned
+    pub provider_voice_id: String,
+    pub language: String,
+    pub consent: VoiceConsent,
+    pub created_at: u64,
+}
+
+impl CustomVoiceProfile {
+    pub fn provider(&self) -> &str {
+        provider_of(&self.model)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VoiceProfileSettings {
+    pub custom_voices: Vec<CustomVoiceProfile>,
+    /// Automatic voice choice for the output language
+    pub selection: VoiceSelectionSettings,
+}
+
+/// A custom voice as the voice list reports it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CustomVoiceStatus {
+    pub profile: CustomVoiceProfile,
+    pub available: bool,
+    pub error: Option<String>,
+}
+
+/// Stock voices of the configured model together with the user's custom voices
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VoiceCatalog {
+    pub stock: Vec<VoiceModel>,
+    pub custom: Vec<CustomVoiceStatus>,
+    pub fetched_at: u64,
+}
+
+struct CachedCatalog {
+    catalog: VoiceCatalog,
+    fetched: Instant,
+}
+
+static VOICE_CATALOG: OnceLock<Mutex<Option<CachedCatalog>>> = OnceLock::new();
+
+fn catalog_cache() -> &'static Mutex<Option<CachedCatalog>> {
+    VOICE_CATALOG.get_or_init(|| Mutex::new(None))
+}
+
+/// When each custom voice, by model and provider voice ID, last answered a probe
+static CONFIRMED_VOICES: OnceLock<Mutex<HashMap<(String, String), Instant>>> = OnceLock::new();
+
+fn confirmed_voices() -> &'static Mutex<HashMap<(String, String), Instant>> {
+    CONFIRMED_VOICES.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Provider prefix of a model ID; bare model IDs are OpenAI's
+pub fn provider_of(model: &str) -> &str {
+    model.split_once('/').map_or("openai", |(provider, _)| provider)
+}
+
+pub fn supports_custom_voices(model: &str) -> bool {
+    CUSTOM_VOICE_PROVIDERS.contains(&provider_of(model).to_lowercase().as_str())
+}
+
+/// Check a new profile and fill in its ID and timestamps
+pub fn prepare_profile(mut profile: CustomVoiceProfile, existing: &[CustomVoiceProfile]) -> Result<CustomVoiceProfile, String> {
+    if !profile.consent.confirmed || profile.consent.speaker_name.trim().is_empty() {
+        return Err("Custom voices need the speaker's name and confirmed consent".to_string());
+    }
+    if !supports_custom_voices(&profile.model) {
+        return Err(format!("Custom voices are not available for {}", profile.provider()));
+    }
+    if profile.name.trim().is_empty() || profile.provider_voice_id.trim().is_empty() {
+        return Err("Custom voices need a name and the provider's voice ID".to_string());
+    }
+    if existing.iter().any(|known| {
+        known.provider_voice_id == profile.provider_voice_id && known.model == profile.model
+    }) {
+        return Err(format!("Voice {} is already registered", profile.provider_voice_id));
+    }
+    let now = now_secs();
+    profile.id = Uuid::new_v4().to_string();
+    profile.consent.confirmed_at = now;
+    profile.created_at = now;
+    Ok(profile)
+}
+
+/// Model and voice ID to synthesize with: a custom profile ID maps to its own model, anything else is a stock voice
+pub fn resolve_voice(profiles: &[CustomVoiceProfile], default_model: &str, voice: Option<String>) -> (String, Option<String>) {
+    match voice.as_deref().and_then(|voice| profiles.iter().find(|profile| profile.id == voice)) {
+        Some(profile) => (profile.model.clone(), Some(profile.provider_voice_id.clone())),
+        None => (default_model.to_string(), voice),
+    }
+}
+
+/// Confirm the provider still serves a custom voice by synthesizing a short phrase with it.
+/// The provider has no voice metadata endpoint for cloned voices, so this costs one short synthesis.
+pub async fn probe_voice(gateway: &AIMLAPIGateway, profile: &CustomVoiceProfile) -> Result<(), String> {
+    let request = VoiceRequest::for_text(
+        PROBE_TEXT.to_string(),
+        profile.model.clone(),
+        Some(profile.provider_voice_id.clone()),
+        profile.language.clone(),
+    );
+    let key = (profile.model.clone(), profile.provider_voice_id.clone());
+    match gateway.synthesize(request).await {
+        Ok(_) => {
+            confirmed_voices().lock().await.insert(key, Instant::now());
+            Ok(())
+        }
+        Err(e) => {
+            confirmed_voices().lock().await.remove(&key);
+            Err(e.to_string())
+        }
+    }
+}
+
+/// The voice list, from cache unless it is stale or `refresh` is set
+pub async fn voice_catalog(
+    gateway: &AIMLAPIGateway,
+    profiles: &[CustomVoiceProfile],
+    refresh: bool,
+) -> Result<VoiceCatalog, String> {
+    let mut cache = catalog_cache().lock().await;
+    if let Some(cached) = cache.as_ref() {
+        let same_profiles = cached.catalog.custom.len() == profiles.len()
+            && cached.catalog.custom.iter().zip(profiles).all(|(status, profile)| status.profile == *profile);
+        if !refresh && same_profiles && cached.fetched.elapsed() < CATALOG_TTL {
+            return Ok(cached.catalog.clone());
+        }
+    }
+
+    let stock = gateway.list_voices().await.map_err(|e| e.to_string())?;
+    let mut custom = Vec::with_capacity(profiles.len());
+    for profile in profiles {
+        let key = (profile.model.clone(), profile.provider_voice_id.clone());
+        let recently_confirmed = confirmed_voices()
+            .lock()
+            .await
+            .get(&key)
+            .map_or(false, |confirmed| confirmed.elapsed() < PROBE_TTL);
+        let outcome = if recently_confirmed {
+            Ok(())
+        } else {
+            probe_voice(gateway, profile).await
+        };
+        if let Err(e) = &outcome {
+            log::warn!("Custom voice {} is unavailable: {}", profile.name, e);
+        }
+        custom.push(CustomVoiceStatus {
+            profile: profile.clone(),
+            available: outcome.is_ok(),
+            error: outcome.err(),
+        });
+    }
+    let catalog = VoiceCatalog {
+        stock,
+        custom,
+        fetched_at: now_secs(),
+    };
+    *cache = Some(CachedCatalog {
+        catalog: catalog.clone(),
+        fetched: Instant::now(),
+    });
+    Ok(catalog)
+}
+
+/// Custom voices in the shape of the stock voice list, so pickers can show both together
+pub fn as_voice_models(custom: &[CustomVoiceStatus]) -> Vec<VoiceModel> {
+    custom
+        .iter()
+        .filter(|status| status.available)
+        .map(|status| VoiceModel {
+            id: status.profile.id.clone(),
+            name: status.profile.name.clone(),
+            language: status.profile.language.clone(),
+            gender: "custom".to_string(),
+            accent: String::new(),
+            neural: true,
+            quality: AudioQuality::High,
+            emotion_support: false,
+            realtime: true,
+        })
+        .collect()
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
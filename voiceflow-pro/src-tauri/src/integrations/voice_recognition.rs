@@ -3,18 +3,34 @@
 
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot, watch};
 use uuid::Uuid;
 
+use super::language_registry::{get_language_registry, LanguageCapability};
+use crate::event_channel::{event_channel, Coalesce, EventReceiver, EventSender};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VoiceRecognitionConfig {
     pub language: String,
     pub continuous: bool,
     pub interim_results: bool,
     pub max_alternatives: u32,
+    /// Final results less confident than this are dropped rather than sent on
     pub confidence_threshold: f32,
     pub noise_reduction: bool,
     pub privacy_mode: bool,
+    /// Input level treated as the start of speech; set by calibration
+    #[serde(default)]
+    pub vad_threshold: Option<f32>,
+    /// Multiplier applied to raw input levels; set by calibration
+    #[serde(default)]
+    pub input_gain: Option<f32>,
+    /// Words and names the recognizer should favour, for engines that accept hints
+    #[serde(default)]
+    pub phrase_hints: Vec<String>,
+    /// Microphone by name; the system default when unset. Picked again before each session starts
+    #[serde(default)]
+    pub input_device: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -53,6 +69,9 @@ pub struct AudioMetrics {
     pub latency: u64,
     pub sample_rate: u32,
     pub channels: u32,
+    /// Profile of the Bluetooth headset the audio comes from; `hfp` means narrowband audio and lower accuracy
+    #[serde(default)]
+    pub bluetooth_profile: Option<crate::bluetooth_audio::HeadsetProfile>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -64,254 +83,520 @@ pub enum VoiceEvent {
     AudioMetrics(AudioMetrics),
     LanguageDetected(String),
     EngineSwitched(String),
+    StateChanged(EngineState),
+    ConfigApplied(VoiceRecognitionConfig),
+}
+
+impl Coalesce for VoiceEvent {
+    fn coalesce_key(&self) -> Option<&'static str> {
+        match self {
+            VoiceEvent::SpeechResult(result) if !result.is_final => Some("interim_result"),
+            VoiceEvent::AudioMetrics(_) => Some("audio_metrics"),
+            _ => None,
+        }
+    }
+}
+
+/// Roughly 25 seconds of audio metrics; a UI further behind than that only needs the latest reading
+pub const VOICE_EVENT_CAPACITY: usize = 256;
+
+/// Input level above which a simulated frame counts as speech, unless calibration set one
+const SPEECH_LEVEL: f32 = 0.2;
+
+/// Explain why moving from `current` to `next` cannot happen inside a running session
+pub fn restart_reason(current: &VoiceRecognitionConfig, next: &VoiceRecognitionConfig) -> Option<String> {
+    if current.continuous != next.continuous {
+        Some("switching continuous mode needs a new recognition session".to_string())
+    } else if current.privacy_mode != next.privacy_mode {
+        Some("privacy mode selects a different recognizer backend".to_string())
+    } else {
+        None
+    }
+}
+
+/// Lifecycle state of the voice recognition engine
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "state", content = "message", rename_all = "snake_case")]
+pub enum EngineState {
+    Idle,
+    Listening,
+    /// Microphone open for voice activity only, e.g. to notice the user talking over speech playback
+    Monitoring,
+    Paused,
+    Error(String),
+}
+
+impl EngineState {
+    /// The state's name as sent in "voice-status" events, which the frontend compares as a plain string
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EngineState::Idle => "idle",
+            EngineState::Listening => "listening",
+            EngineState::Monitoring => "monitoring",
+            EngineState::Paused => "paused",
+            EngineState::Error(_) => "error",
+        }
+    }
+
+    pub fn is_listening(&self) -> bool {
+        matches!(self, EngineState::Listening)
+    }
+
+    /// Whether audio frames are read in this state
+    pub fn captures_audio(&self) -> bool {
+        matches!(self, EngineState::Listening | EngineState::Monitoring)
+    }
+
+    /// Resolve the state reached by applying a command, or explain why the transition is invalid
+    fn transition(&self, command: &EngineCommand) -> Result<EngineState, String> {
+        use EngineState::*;
+        match (self, command) {
+            (Idle | Error(_) | Listening | Monitoring, EngineCommand::Start) => Ok(Listening),
+            (Paused, EngineCommand::Start) => {
+                Err("Voice recognition is paused; resume it instead of starting".to_string())
+            }
+            (_, EngineCommand::Stop) => Ok(Idle),
+            (Idle | Error(_) | Monitoring, EngineCommand::Monitor) => Ok(Monitoring),
+            (state, EngineCommand::Monitor) => {
+                Err(format!("Cannot monitor voice activity while {:?}", state))
+            }
+            (Listening | Paused, EngineCommand::Pause(_)) => Ok(Paused),
+            (Paused | Listening, EngineCommand::Resume) => Ok(Listening),
+            (state, EngineCommand::Pause(_) | EngineCommand::Resume) => {
+                Err(format!("Cannot {:?} voice recognition while {:?}", command, state))
+            }
+            (state, EngineCommand::Reconfigure(_)) => Ok(state.clone()),
+            (Idle | Error(_), EngineCommand::Unload) => Ok(self.clone()),
+            (state, EngineCommand::Unload) => {
+                Err(format!("Cannot unload the recognition model while {:?}", state))
+            }
+        }
+    }
+}
+
+/// Why the engine was paused
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum PauseReason {
+    Manual,
+    SystemIdle,
+    ScreenLocked,
+    CallActive(String),
+    /// macOS Secure Input is on, with the app holding it when known
+    SecureInput(Option<String>),
+    /// The microphone is muted by its hardware switch or in the OS
+    MicMuted,
+}
+
+impl PauseReason {
+    /// Automatic pauses may be lifted automatically; manual ones wait for the user
+    pub fn is_automatic(&self) -> bool {
+        !matches!(self, PauseReason::Manual)
+    }
 }
 
+/// Conditions under which listening pauses itself
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AutoPauseConfig {
+    pub enabled: bool,
+    pub on_system_idle: bool,
+    pub idle_threshold_secs: u64,
+    pub on_screen_lock: bool,
+    pub on_call_active: bool,
+    pub call_applications: Vec<String>,
+    pub auto_resume: bool,
+    pub poll_interval_secs: u64,
+}
+
+impl Default for AutoPauseConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            on_system_idle: true,
+            idle_threshold_secs: 300,
+            on_screen_lock: true,
+            on_call_active: true,
+            call_applications: vec![
+                "zoom.us".to_string(),
+                "CptHost".to_string(),
+                "Teams".to_string(),
+                "FaceTime".to_string(),
+                "webex".to_string(),
+                "Skype".to_string(),
+            ],
+            auto_resume: true,
+            poll_interval_secs: 5,
+        }
+    }
+}
+
+/// Messages accepted by the engine task
+#[derive(Debug, Clone)]
+pub enum EngineCommand {
+    Start,
+    Stop,
+    /// Watch for speech without recognizing it
+    Monitor,
+    Pause(PauseReason),
+    Resume,
+    Reconfigure(VoiceRecognitionConfig),
+    /// Release the recognition model; the next Start loads it again
+    Unload,
+}
+
+/// A command paired with the channel its outcome is reported on
+struct EngineRequest {
+    command: EngineCommand,
+    reply: oneshot::Sender<Result<VoiceEngineStatus, String>>,
+}
+
+impl std::fmt::Debug for EngineRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EngineRequest").field("command", &self.command).finish()
+    }
+}
+
+/// Reconfiguration waiting for the current utterance to finish
+struct PendingReconfigure {
+    config: VoiceRecognitionConfig,
+    reply: oneshot::Sender<Result<VoiceEngineStatus, String>>,
+}
+
+/// The engine itself; owned exclusively by the task spawned in `spawn_voice_engine`
 pub struct VoiceRecognitionEngine {
     config: VoiceRecognitionConfig,
-    is_listening: bool,
-    event_sender: mpsc::UnboundedSender<VoiceEvent>,
+    state: EngineState,
+    pause_reason: Option<PauseReason>,
+    event_sender: EventSender<VoiceEvent>,
     engine_type: String,
     session_id: String,
+    audio_ticks: u64,
+    in_utterance: bool,
+    pending_reconfigure: Option<PendingReconfigure>,
+    model_loaded: bool,
 }
 
 impl VoiceRecognitionEngine {
     pub fn new(
         config: VoiceRecognitionConfig,
-        event_sender: mpsc::UnboundedSender<VoiceEvent>,
+        event_sender: EventSender<VoiceEvent>,
     ) -> Self {
         Self {
             config,
-            is_listening: false,
+            state: EngineState::Idle,
+            pause_reason: None,
             event_sender,
             engine_type: "web-speech-api".to_string(),
             session_id: Uuid::new_v4().to_string(),
+            audio_ticks: 0,
+            in_utterance: false,
+            pending_reconfigure: None,
+            model_loaded: true,
         }
     }
 
-    pub async fn initialize(&mut self) -> Result<(), String> {
-        // Initialize voice recognition engine
-        // This would integrate with the TypeScript voice recognition engine
-        // For now, we'll simulate the initialization
-        self.send_event(VoiceEvent::RecognitionStart).await;
-        Ok(())
+    pub fn get_status(&self) -> VoiceEngineStatus {
+        VoiceEngineStatus {
+            state: self.state.clone(),
+            pause_reason: self.pause_reason.clone(),
+            is_listening: self.state.is_listening(),
+            engine_type: self.engine_type.clone(),
+            session_id: self.session_id.clone(),
+            config: self.config.clone(),
+            model_loaded: self.model_loaded,
+            speech_detected: self.in_utterance,
+        }
     }
 
-    pub async fn start_listening(&mut self) -> Result<(), String> {
-        if self.is_listening {
-            return Ok(());
-        }
+    /// Command loop: the only place engine state is mutated
+    async fn run(
+        mut self,
+        mut commands: mpsc::Receiver<EngineRequest>,
+        status: watch::Sender<VoiceEngineStatus>,
+    ) {
+        let mut audio_interval = tokio::time::interval(tokio::time::Duration::from_millis(100));
 
-        self.is_listening = true;
-        self.send_event(VoiceEvent::RecognitionStart).await;
+        loop {
+            tokio::select! {
+                request = commands.recv() => {
+                    let Some(EngineRequest { command, reply }) = request else {
+                        // Every handle has been dropped
+                        break;
+                    };
+                    if let EngineCommand::Reconfigure(config) = command {
+                        self.queue_reconfigure(config, reply);
+                    } else {
+                        let outcome = self.apply(command).map(|_| self.get_status());
+                        // Publish before replying so callers never read a stale status
+                        status.send_replace(self.get_status());
+                        let _ = reply.send(outcome);
+                    }
+                }
+                _ = audio_interval.tick(), if self.state.captures_audio() => {
+                    if let Err(e) = self.process_audio_frame() {
+                        self.state = EngineState::Error(e);
+                    }
+                }
+            }
+
+            // Outside an utterance (or once audio stops) queued configuration can be applied
+            if !self.state.captures_audio() {
+                self.in_utterance = false;
+            }
+            if !self.in_utterance {
+                if let Some(PendingReconfigure { config, reply }) = self.pending_reconfigure.take() {
+                    let outcome = self
+                        .apply(EngineCommand::Reconfigure(config))
+                        .map(|_| self.get_status());
+                    status.send_replace(self.get_status());
+                    let _ = reply.send(outcome);
+                }
+            }
+            status.send_replace(self.get_status());
+        }
 
-        // Start continuous listening loop
-        let event_sender = self.event_sender.clone();
-        tokio::spawn(async move {
-            Self::listening_loop(event_sender).await;
-        });
+        if self.state != EngineState::Idle {
+            self.send_event(VoiceEvent::RecognitionStop);
+        }
+    }
 
-        Ok(())
+    /// Hold a reconfiguration until the current utterance ends so no utterance mixes two configurations
+    fn queue_reconfigure(
+        &mut self,
+        config: VoiceRecognitionConfig,
+        reply: oneshot::Sender<Result<VoiceEngineStatus, String>>,
+    ) {
+        let superseded = self.pending_reconfigure.replace(PendingReconfigure { config, reply });
+        if let Some(previous) = superseded {
+            let _ = previous
+                .reply
+                .send(Err("Superseded by a newer configuration".to_string()));
+        }
     }
 
-    pub async fn stop_listening(&mut self) -> Result<(), String> {
-        if !self.is_listening {
-            return Ok(());
+    fn apply(&mut self, command: EngineCommand) -> Result<(), String> {
+        if let EngineCommand::Reconfigure(config) = &command {
+            if self.state != EngineState::Idle {
+                if let Some(reason) = restart_reason(&self.config, config) {
+                    return Err(format!("Restart required: {}", reason));
+                }
+            }
         }
 
-        self.is_listening = false;
-        self.send_event(VoiceEvent::RecognitionStop).await;
-        Ok(())
-    }
+        let next = self.state.transition(&command)?;
+        let previous = std::mem::replace(&mut self.state, next.clone());
+
+        match command {
+            EngineCommand::Start if previous != EngineState::Listening => {
+                if !self.model_loaded {
+                    log::info!("Reloading {} recognition model", self.engine_type);
+                    self.model_loaded = true;
+                }
+                self.session_id = Uuid::new_v4().to_string();
+                self.audio_ticks = 0;
+                self.send_event(VoiceEvent::RecognitionStart);
+            }
+            EngineCommand::Stop if previous != EngineState::Idle => {
+                self.send_event(VoiceEvent::RecognitionStop);
+            }
+            EngineCommand::Pause(reason) => {
+                // A manual pause takes precedence over an automatic one already in effect
+                if self.pause_reason != Some(PauseReason::Manual) {
+                    self.pause_reason = Some(reason);
+                }
+            }
+            EngineCommand::Reconfigure(config) => {
+                self.config = config.clone();
+                self.send_event(VoiceEvent::ConfigApplied(config));
+            }
+            EngineCommand::Unload if self.model_loaded => {
+                log::info!("Unloading {} recognition model", self.engine_type);
+                self.model_loaded = false;
+                self.audio_ticks = 0;
+            }
+            _ => {}
+        }
 
-    pub async fn set_language(&mut self, language: String) -> Result<(), String> {
-        self.config.language = language;
+        // Session id and audio position survive a pause; only leaving the paused state clears its reason
+        if next != EngineState::Paused {
+            self.pause_reason = None;
+        }
+
+        if previous != next {
+            self.send_event(VoiceEvent::StateChanged(next));
+        }
         Ok(())
     }
 
-    pub fn get_status(&self) -> VoiceEngineStatus {
-        VoiceEngineStatus {
-            is_listening: self.is_listening,
-            engine_type: self.engine_type.clone(),
-            session_id: self.session_id.clone(),
-            config: self.config.clone(),
+    fn process_audio_frame(&mut self) -> Result<(), String> {
+        if self.event_sender.is_closed() {
+            return Err("Voice event channel closed; results have nowhere to go".to_string());
         }
-    }
 
-    async fn listening_loop(mut event_sender: mpsc::UnboundedSender<VoiceEvent>) {
-        // Simulate audio processing loop
+        // Simulate audio processing
         // In real implementation, this would:
         // 1. Capture audio from microphone
         // 2. Send to voice recognition engine
         // 3. Handle results and emit events
-        let mut counter = 0;
-        
-        loop {
-            tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-            counter += 1;
-            
-            // Simulate audio metrics
-            if counter % 10 == 0 {
-                let metrics = AudioMetrics {
-                    volume: (counter as f32 * 0.01) % 1.0,
-                    signal_to_noise_ratio: 0.8,
-                    clipping: false,
-                    latency: 150,
-                    sample_rate: 44100,
-                    channels: 1,
-                };
-                
-                let _ = event_sender.send(VoiceEvent::AudioMetrics(metrics));
-            }
+        self.audio_ticks += 1;
+        let raw_level = (self.audio_ticks as f32 * 0.01) % 1.0;
+        let level = (raw_level * self.config.input_gain.unwrap_or(1.0)).min(1.0);
+        // During playback only what is left after echo cancellation can be the user speaking
+        let level = level * super::echo_cancellation::capture_level_factor();
+        // Power saving raises the threshold so background noise wakes the recognizer less often
+        let threshold = self.config.vad_threshold.unwrap_or(SPEECH_LEVEL) * crate::power::current_profile().vad_threshold_factor;
+        self.in_utterance = level >= threshold.min(1.0);
+
+        // Simulate audio metrics
+        if self.audio_ticks % 10 == 0 {
+            let metrics = AudioMetrics {
+                volume: level,
+                signal_to_noise_ratio: 0.8,
+                clipping: false,
+                latency: 150,
+                sample_rate: 44100,
+                channels: 1,
+                bluetooth_profile: crate::bluetooth_audio::active_input_profile(),
+            };
+
+            self.send_event(VoiceEvent::AudioMetrics(metrics));
         }
+        Ok(())
     }
 
-    async fn send_event(&self, event: VoiceEvent) {
+    fn send_event(&self, event: VoiceEvent) {
+        if let VoiceEvent::SpeechResult(result) = &event {
+            if result.is_final && result.confidence < self.config.confidence_threshold {
+                log::debug!(
+                    "Dropping a final result at confidence {:.2}, below the threshold {:.2}",
+                    result.confidence,
+                    self.config.confidence_threshold
+                );
+                return;
+            }
+        }
         if let Err(e) = self.event_sender.send(event) {
             eprintln!("Failed to send voice event: {}", e);
         }
     }
 }
 
+/// Cloneable handle to the engine task
+#[derive(Debug, Clone)]
+pub struct VoiceEngineHandle {
+    commands: mpsc::Sender<EngineRequest>,
+    status: watch::Receiver<VoiceEngineStatus>,
+}
+
+impl VoiceEngineHandle {
+    pub async fn start(&self) -> Result<VoiceEngineStatus, String> {
+        self.send(EngineCommand::Start).await
+    }
+
+    pub async fn stop(&self) -> Result<VoiceEngineStatus, String> {
+        self.send(EngineCommand::Stop).await
+    }
+
+    pub async fn pause(&self, reason: PauseReason) -> Result<VoiceEngineStatus, String> {
+        self.send(EngineCommand::Pause(reason)).await
+    }
+
+    pub async fn resume(&self) -> Result<VoiceEngineStatus, String> {
+        self.send(EngineCommand::Resume).await
+    }
+
+    pub async fn reconfigure(&self, config: VoiceRecognitionConfig) -> Result<VoiceEngineStatus, String> {
+        self.send(EngineCommand::Reconfigure(config)).await
+    }
+
+    /// Open the microphone for voice activity detection only; `start` turns it into dictation
+    pub async fn monitor(&self) -> Result<VoiceEngineStatus, String> {
+        self.send(EngineCommand::Monitor).await
+    }
+
+    /// Receiver that sees every status the engine publishes
+    pub fn watch_status(&self) -> watch::Receiver<VoiceEngineStatus> {
+        self.status.clone()
+    }
+
+    /// Free the model while nobody is dictating; only allowed when idle
+    pub async fn unload_model(&self) -> Result<VoiceEngineStatus, String> {
+        self.send(EngineCommand::Unload).await
+    }
+
+    /// Latest status published by the engine task; never blocks on an in-flight command
+    pub fn status(&self) -> VoiceEngineStatus {
+        self.status.borrow().clone()
+    }
+
+    async fn send(&self, command: EngineCommand) -> Result<VoiceEngineStatus, String> {
+        let (reply, outcome) = oneshot::channel();
+        self.commands
+            .send(EngineRequest { command, reply })
+            .await
+            .map_err(|_| "Voice engine task has stopped".to_string())?;
+        outcome
+            .await
+            .map_err(|_| "Voice engine task dropped the command".to_string())?
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VoiceEngineStatus {
+    pub state: EngineState,
+    pub pause_reason: Option<PauseReason>,
     pub is_listening: bool,
     pub engine_type: String,
     pub session_id: String,
     pub config: VoiceRecognitionConfig,
+    /// False after the model was unloaded for being idle
+    pub model_loaded: bool,
+    /// Voice activity is above the speech threshold right now
+    #[serde(default)]
+    pub speech_detected: bool,
 }
 
-pub fn create_voice_recognition_engine(
+/// Move a new engine onto its own task and return the handle used to drive it
+pub fn spawn_voice_engine(
     config: VoiceRecognitionConfig,
-) -> Result<(VoiceRecognitionEngine, mpsc::UnboundedReceiver<VoiceEvent>), String> {
-    let (event_sender, event_receiver) = mpsc::unbounded_channel();
+    event_sender: EventSender<VoiceEvent>,
+) -> VoiceEngineHandle {
     let engine = VoiceRecognitionEngine::new(config, event_sender);
-    Ok((engine, event_receiver))
+    let (command_sender, command_receiver) = mpsc::channel(32);
+    let (status_sender, status_receiver) = watch::channel(engine.get_status());
+
+    tokio::spawn(engine.run(command_receiver, status_sender));
+
+    VoiceEngineHandle {
+        commands: command_sender,
+        status: status_receiver,
+    }
+}
+
+pub fn create_voice_recognition_engine(
+    config: VoiceRecognitionConfig,
+) -> Result<(VoiceEngineHandle, EventReceiver<VoiceEvent>), String> {
+    let (event_sender, event_receiver) = event_channel("voice", VOICE_EVENT_CAPACITY);
+    let handle = spawn_voice_engine(config, event_sender);
+    Ok((handle, event_receiver))
 }
 
 // Utility functions for voice recognition
+/// Enabled speech-recognition languages from the language registry
 pub fn get_supported_languages() -> Vec<Language> {
-    vec![
-        Language {
-            code: "en-US".to_string(),
-            name: "English (US)".to_string(),
-            native_name: "English (US)".to_string(),
-            flag: "🇺🇸".to_string(),
-        },
-        Language {
-            code: "en-GB".to_string(),
-            name: "English (UK)".to_string(),
-            native_name: "English (UK)".to_string(),
-            flag: "🇬🇧".to_string(),
-        },
-        Language {
-            code: "es-ES".to_string(),
-            name: "Spanish (Spain)".to_string(),
-            native_name: "Español (España)".to_string(),
-            flag: "🇪🇸".to_string(),
-        },
-        Language {
-            code: "es-MX".to_string(),
-            name: "Spanish (Mexico)".to_string(),
-            native_name: "Español (México)".to_string(),
-            flag: "🇲🇽".to_string(),
-        },
-        Language {
-            code: "fr-FR".to_string(),
-            name: "French".to_string(),
-            native_name: "Français".to_string(),
-            flag: "🇫🇷".to_string(),
-        },
-        Language {
-            code: "de-DE".to_string(),
-            name: "German".to_string(),
-            native_name: "Deutsch".to_string(),
-            flag: "🇩🇪".to_string(),
-        },
-        Language {
-            code: "it-IT".to_string(),
-            name: "Italian".to_string(),
-            native_name: "Italiano".to_string(),
-            flag: "🇮🇹".to_string(),
-        },
-        Language {
-            code: "pt-PT".to_string(),
-            name: "Portuguese (Portugal)".to_string(),
-            native_name: "Português (Portugal)".to_string(),
-            flag: "🇵🇹".to_string(),
-        },
-        Language {
-            code: "pt-BR".to_string(),
-            name: "Portuguese (Brazil)".to_string(),
-            native_name: "Português (Brasil)".to_string(),
-            flag: "🇧🇷".to_string(),
-        },
-        Language {
-            code: "zh-CN".to_string(),
-            name: "Chinese (Simplified)".to_string(),
-            native_name: "中文（简体）".to_string(),
-            flag: "🇨🇳".to_string(),
-        },
-        Language {
-            code: "zh-TW".to_string(),
-            name: "Chinese (Traditional)".to_string(),
-            native_name: "中文（繁體）".to_string(),
-            flag: "🇹🇼".to_string(),
-        },
-        Language {
-            code: "ja-JP".to_string(),
-            name: "Japanese".to_string(),
-            native_name: "日本語".to_string(),
-            flag: "🇯🇵".to_string(),
-        },
-        Language {
-            code: "ko-KR".to_string(),
-            name: "Korean".to_string(),
-            native_name: "한국어".to_string(),
-            flag: "🇰🇷".to_string(),
-        },
-        Language {
-            code: "ar-SA".to_string(),
-            name: "Arabic".to_string(),
-            native_name: "العربية".to_string(),
-            flag: "🇸🇦".to_string(),
-        },
-        Language {
-            code: "hi-IN".to_string(),
-            name: "Hindi".to_string(),
-            native_name: "हिन्दी".to_string(),
-            flag: "🇮🇳".to_string(),
-        },
-        Language {
-            code: "ru-RU".to_string(),
-            name: "Russian".to_string(),
-            native_name: "Русский".to_string(),
-            flag: "🇷🇺".to_string(),
-        },
-        Language {
-            code: "nl-NL".to_string(),
-            name: "Dutch".to_string(),
-            native_name: "Nederlands".to_string(),
-            flag: "🇳🇱".to_string(),
-        },
-        Language {
-            code: "sv-SE".to_string(),
-            name: "Swedish".to_string(),
-            native_name: "Svenska".to_string(),
-            flag: "🇸🇪".to_string(),
-        },
-        Language {
-            code: "no-NO".to_string(),
-            name: "Norwegian".to_string(),
-            native_name: "Norsk".to_string(),
-            flag: "🇳🇴".to_string(),
-        },
-        Language {
-            code: "da-DK".to_string(),
-            name: "Danish".to_string(),
-            native_name: "Dansk".to_string(),
-            flag: "🇩🇰".to_string(),
-        },
-    ]
+    let registry = match get_language_registry().read() {
+        Ok(registry) => registry,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    registry
+        .languages(Some(LanguageCapability::Stt), false)
+        .into_iter()
+        .map(|status| Language {
+            code: status.language.code,
+            name: status.language.name,
+            native_name: status.language.native_name,
+            flag: status.language.flag,
+        })
+        .collect()
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -323,7 +608,8 @@ pub struct Language {
 }
 
 pub fn is_language_supported(language_code: &str) -> bool {
-    get_supported_languages()
-        .iter()
-        .any(|lang| lang.code == language_code)
+    get_language_registry()
+        .read()
+        .map(|registry| registry.supports(language_code, LanguageCapability::Stt))
+        .unwrap_or(false)
 }
\ No newline at end of file
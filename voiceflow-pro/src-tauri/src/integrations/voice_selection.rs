@@ -0,0 +1,334 @@
+// Voice Selection Module
+// Picks the TTS voice for an output language from the user's gender and style preference, per provider
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, OnceLock};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use super::ai_ml_api::{AIMLAPIGateway, VoiceModel};
+use super::voice_profiles::{provider_of, resolve_voice, CustomVoiceProfile, VoiceProfileSettings};
+
+/// Style words for stock voices, matched against `VoicePreference::style` together with the accent
+const STOCK_VOICE_STYLES: &[(&str, &[&str])] = &[
+    ("alloy", &["balanced", "neutral"]),
+    ("echo", &["warm", "conversational"]),
+    ("fable", &["expressive", "storytelling"]),
+    ("onyx", &["deep", "authoritative"]),
+    ("nova", &["bright", "friendly"]),
+    ("shimmer", &["soft", "calm"]),
+];
+
+/// Mappings kept before the cache starts over
+const MAX_CACHED_CHOICES: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VoiceGender {
+    Female,
+    Male,
+    Neutral,
+}
+
+impl VoiceGender {
+    fn matches(self, gender: &str) -> bool {
+        let gender = gender.to_lowercase();
+        match self {
+            VoiceGender::Female => gender == "female",
+            VoiceGender::Male => gender == "male",
+            VoiceGender::Neutral => gender == "neutral",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VoicePreference {
+    pub gender: Option<VoiceGender>,
+    /// e.g. "calm", "deep", "british"
+    pub style: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VoiceSelectionSettings {
+    /// Pick a voice for the output language when the caller did not name one
+    pub auto_select: bool,
+    pub preference: VoicePreference,
+    /// Voice ID (stock or custom profile) per language tag, chosen over any automatic pick
+    pub preferred_voices: BTreeMap<String, String>,
+}
+
+impl Default for VoiceSelectionSettings {
+    fn default() -> Self {
+        Self {
+            auto_select: true,
+            preference: VoicePreference::default(),
+            preferred_voices: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SelectionReason {
+    /// The caller named the voice
+    Requested,
+    /// Set with `set_preferred_voice` for this language
+    Preferred,
+    /// A voice whose own language is the output language
+    NativeVoice,
+    /// A multilingual voice that best fits the gender and style preference
+    Multilingual,
+    /// Nothing fits better than the provider's default voice
+    ProviderDefault,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VoiceChoice {
+    pub language: String,
+    pub model: String,
+    /// `None` leaves the choice to the provider
+    pub voice_id: Option<String>,
+    pub voice_name: Option<String>,
+    pub reason: SelectionReason,
+}
+
+struct Candidate<'a> {
+    model: &'a str,
+    /// What the synthesis request is sent with
+    voice_id: &'a str,
+    name: &'a str,
+    language: &'a str,
+    gender: &'a str,
+    styles: Vec<&'a str>,
+}
+
+struct ChoiceCache {
+    /// Fingerprint of the settings and voices the choices were made from
+    fingerprint: u64,
+    choices: HashMap<String, VoiceChoice>,
+}
+
+static CHOICE_CACHE: OnceLock<Mutex<ChoiceCache>> = OnceLock::new();
+
+fn choice_cache() -> &'static Mutex<ChoiceCache> {
+    CHOICE_CACHE.get_or_init(|| {
+        Mutex::new(ChoiceCache {
+            fingerprint: 0,
+            choices: HashMap::new(),
+        })
+    })
+}
+
+fn base_language(language: &str) -> String {
+    language.split(['-', '_']).next().unwrap_or(language).to_lowercase()
+}
+
+/// Normalized key for `preferred_voices`, e.g. "pt-br"
+pub fn language_key(language: &str) -> String {
+    language.trim().replace('_', "-").to_lowercase()
+}
+
+/// Models whose voices speak any language, with the voice's own language only shaping the accent
+fn is_multilingual(model: &str) -> bool {
+    provider_of(model).eq_ignore_ascii_case("openai") || model.to_lowercase().contains("multilingual")
+}
+
+/// Whether `voice_id` names a stock voice or a custom profile the user can pin
+pub fn is_known_voice(voice_id: &str, profiles: &[CustomVoiceProfile], stock: &[VoiceModel]) -> bool {
+    profiles.iter().any(|profile| profile.id == voice_id) || stock.iter().any(|voice| voice.id == voice_id)
+}
+
+/// Choose the voice for `language`; `stock` is the configured model's voice list with its default voice first
+pub fn select(
+    settings: &VoiceProfileSettings,
+    stock: &[VoiceModel],
+    default_model: &str,
+    language: &str,
+) -> VoiceChoice {
+    let selection = &settings.selection;
+    let key = language_key(language);
+    let base = base_language(language);
+    let pinned = selection
+        .preferred_voices
+        .get(&key)
+        .or_else(|| selection.preferred_voices.get(&base));
+    if let Some(voice_id) = pinned {
+        let (model, voice) = resolve_voice(&settings.custom_voices, default_model, Some(voice_id.clone()));
+        let voice_name = settings
+            .custom_voices
+            .iter()
+            .find(|profile| &profile.id == voice_id)
+            .map(|profile| profile.name.clone())
+            .or_else(|| stock.iter().find(|v| &v.id == voice_id).map(|v| v.name.clone()));
+        return VoiceChoice {
+            language: language.to_string(),
+            model,
+            voice_id: voice,
+            voice_name,
+            reason: SelectionReason::Preferred,
+        };
+    }
+
+    let provider_default = VoiceChoice {
+        language: language.to_string(),
+        model: default_model.to_string(),
+        voice_id: None,
+        voice_name: None,
+        reason: SelectionReason::ProviderDefault,
+    };
+    if !selection.auto_select {
+        return provider_default;
+    }
+
+    let stock_candidates = stock.iter().map(|voice| Candidate {
+        model: default_model,
+        voice_id: &voice.id,
+        name: &voice.name,
+        language: &voice.language,
+        gender: &voice.gender,
+        styles: STOCK_VOICE_STYLES
+            .iter()
+            .find(|(id, _)| *id == voice.id)
+            .map(|(_, styles)| styles.to_vec())
+            .unwrap_or_default()
+            .into_iter()
+            .chain(std::iter::once(voice.accent.as_str()))
+            .collect(),
+    });
+    let custom_candidates = settings.custom_voices.iter().map(|profile| Candidate {
+        model: &profile.model,
+        voice_id: &profile.provider_voice_id,
+        name: &profile.name,
+        language: &profile.language,
+        gender: "",
+        styles: Vec::new(),
+    });
+
+    let score = |candidate: &Candidate| -> Option<u32> {
+        let native = base_language(candidate.language) == base;
+        if !native && !is_multilingual(candidate.model) {
+            return None;
+        }
+        let preference = &selection.preference;
+        let gender = preference.gender.map_or(false, |gender| gender.matches(candidate.gender));
+        let style = preference.style.as_deref().map_or(false, |style| {
+            candidate.styles.iter().any(|s| s.eq_ignore_ascii_case(style.trim()))
+        });
+        Some(u32::from(native) * 4 + u32::from(gender) * 2 + u32::from(style))
+    };
+
+    // The provider default is the first stock voice; anything else has to fit strictly better
+    let default_score = stock
+        .first()
+        .map(|voice| Candidate {
+            model: default_model,
+            voice_id: &voice.id,
+            name: &voice.name,
+            language: &voice.language,
+            gender: &voice.gender,
+            styles: Vec::new(),
+        })
+        .and_then(|candidate| score(&candidate))
+        .unwrap_or(0);
+
+    let mut best: Option<(u32, Candidate)> = None;
+    for candidate in stock_candidates.chain(custom_candidates) {
+        let Some(candidate_score) = score(&candidate) else {
+            continue;
+        };
+        if best.as_ref().map_or(true, |(best_score, _)| candidate_score > *best_score) {
+            best = Some((candidate_score, candidate));
+        }
+    }
+
+    match best {
+        Some((best_score, candidate)) if best_score > default_score => VoiceChoice {
+            language: language.to_string(),
+            model: candidate.model.to_string(),
+            voice_id: Some(candidate.voice_id.to_string()),
+            voice_name: Some(candidate.name.to_string()),
+            reason: if base_language(candidate.language) == base {
+                SelectionReason::NativeVoice
+            } else {
+                SelectionReason::Multilingual
+            },
+        },
+        _ => provider_default,
+    }
+}
+
+fn fingerprint(settings: &VoiceProfileSettings, default_model: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    default_model.hash(&mut hasher);
+    settings.selection.auto_select.hash(&mut hasher);
+    settings.selection.preference.hash(&mut hasher);
+    settings.selection.preferred_voices.hash(&mut hasher);
+    for profile in &settings.custom_voices {
+        profile.id.hash(&mut hasher);
+        profile.model.hash(&mut hasher);
+        profile.provider_voice_id.hash(&mut hasher);
+        profile.language.hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+/// `select` with the outcome cached per language until the voice settings change
+pub async fn select_cached(
+    gateway: Option<&AIMLAPIGateway>,
+    settings: &VoiceProfileSettings,
+    default_model: &str,
+    language: &str,
+) -> VoiceChoice {
+    let fingerprint = fingerprint(settings, default_model);
+    let key = language_key(language);
+    {
+        let mut cache = choice_cache().lock().await;
+        if cache.fingerprint != fingerprint {
+            cache.fingerprint = fingerprint;
+            cache.choices.clear();
+        }
+        if let Some(choice) = cache.choices.get(&key) {
+            return choice.clone();
+        }
+    }
+
+    let stock = match gateway {
+        Some(gateway) => gateway.list_voices().await.unwrap_or_else(|e| {
+            log::warn!("Voice list unavailable for voice selection: {}", e);
+            Vec::new()
+        }),
+        None => Vec::new(),
+    };
+    let choice = select(settings, &stock, default_model, language);
+    log::debug!("Voice for {}: {:?} ({:?})", language, choice.voice_id, choice.reason);
+
+    let mut cache = choice_cache().lock().await;
+    if cache.fingerprint == fingerprint {
+        if cache.choices.len() >= MAX_CACHED_CHOICES {
+            cache.choices.clear();
+        }
+        cache.choices.insert(key, choice.clone());
+    }
+    choice
+}
+
+/// Model and voice for speech in `language`: the named voice when there is one, otherwise the selected one
+pub async fn voice_for_language(
+    gateway: &Arc<Mutex<Option<AIMLAPIGateway>>>,
+    settings: &VoiceProfileSettings,
+    default_model: &str,
+    language: &str,
+    voice: Option<String>,
+) -> (String, Option<String>) {
+    if voice.is_some() {
+        return resolve_voice(&settings.custom_voices, default_model, voice);
+    }
+    let gateway = gateway.lock().await;
+    let choice = select_cached(gateway.as_ref(), settings, default_model, language).await;
+    (choice.model, choice.voice_id)
+}
@@ -0,0 +1,220 @@
+//! Intent rules for VoiceFlow Pro
+//! Runs user-chosen actions when context processing classifies an utterance with a given intent
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager};
+use uuid::Uuid;
+
+use crate::assistant::post_json;
+use crate::errors::{AppError, ValidationError};
+use crate::integrations::content_filter::ContentFilterSettings;
+use crate::integrations::{IntentClassification, UserIntent};
+use crate::{profiles, storage, AppState};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IntentRuleSettings {
+    pub rules: Vec<IntentRule>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IntentRule {
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    pub intent: UserIntent,
+    /// Classifications less certain than this are ignored
+    #[serde(default = "default_min_confidence")]
+    pub min_confidence: f32,
+    /// When set, the text must also contain one of these words or phrases
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    pub action: IntentAction,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub created_at: u64,
+}
+
+fn default_min_confidence() -> f32 {
+    0.6
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum IntentAction {
+    /// Run the text through `prompt` and send the result as "intent-rule-fired"
+    AiAction {
+        prompt: String,
+        #[serde(default)]
+        model: Option<String>,
+    },
+    /// POST the text and its classification to `url`
+    Webhook {
+        url: String,
+        #[serde(default)]
+        headers: HashMap<String, String>,
+    },
+    /// Make a content filter profile the active one
+    SwitchProfile { profile: String },
+}
+
+/// A rule that matched, with what its action did; dry runs only report the match
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleOutcome {
+    pub rule_id: String,
+    pub rule_name: String,
+    pub intent: UserIntent,
+    pub confidence: f32,
+    pub action: IntentAction,
+    pub dry_run: bool,
+    pub output: Option<Value>,
+    pub error: Option<String>,
+}
+
+/// Check a rule before it is saved and fill in its ID
+pub fn prepare_rule(mut rule: IntentRule, content_filters: &ContentFilterSettings) -> Result<IntentRule, AppError> {
+    let invalid = |message: String| AppError::Validation(ValidationError::InvalidConfigValue(message));
+    if rule.name.trim().is_empty() {
+        return Err(invalid("Intent rules need a name".to_string()));
+    }
+    if !(0.0..=1.0).contains(&rule.min_confidence) {
+        return Err(invalid(format!("min_confidence must be between 0 and 1, got {}", rule.min_confidence)));
+    }
+    match &rule.action {
+        IntentAction::AiAction { prompt, .. } if prompt.trim().is_empty() => {
+            return Err(invalid("AI actions need a prompt".to_string()));
+        }
+        IntentAction::Webhook { url, .. } if !(url.starts_with("https://") || url.starts_with("http://")) => {
+            return Err(invalid(format!("Webhook URL must be http or https: {}", url)));
+        }
+        IntentAction::SwitchProfile { profile } if !content_filters.profiles.contains_key(profile) => {
+            return Err(invalid(format!("There is no content filter profile named '{}'", profile)));
+        }
+        _ => {}
+    }
+    rule.keywords.retain(|keyword| !keyword.trim().is_empty());
+    if rule.id.is_empty() {
+        rule.id = Uuid::new_v4().to_string();
+    }
+    if rule.created_at == 0 {
+        rule.created_at = now_secs();
+    }
+    Ok(rule)
+}
+
+/// Enabled rules for the classified intent whose confidence and keywords are satisfied, in rule order
+pub fn matching_rules<'a>(rules: &'a [IntentRule], text: &str, classification: &IntentClassification) -> Vec<&'a IntentRule> {
+    let lowered = text.to_lowercase();
+    rules
+        .iter()
+        .filter(|rule| rule.enabled)
+        .filter(|rule| rule.intent == classification.primary_intent)
+        .filter(|rule| classification.confidence >= rule.min_confidence)
+        .filter(|rule| {
+            rule.keywords.is_empty()
+                || rule
+                    .keywords
+                    .iter()
+                    .any(|keyword| lowered.contains(&keyword.trim().to_lowercase()))
+        })
+        .collect()
+}
+
+/// Evaluate the rules against a classified utterance, carrying out their actions unless `dry_run`
+pub async fn run_rules(
+    state: &AppState,
+    app: &AppHandle,
+    text: &str,
+    classification: &IntentClassification,
+    dry_run: bool,
+) -> Vec<RuleOutcome> {
+    let rules = state.settings.snapshot().intent_rules.rules.clone();
+    let mut outcomes = Vec::new();
+    for rule in matching_rules(&rules, text, classification) {
+        let result = if dry_run {
+            Ok(None)
+        } else {
+            execute(state, rule, text, classification).await.map(Some)
+        };
+        let outcome = RuleOutcome {
+            rule_id: rule.id.clone(),
+            rule_name: rule.name.clone(),
+            intent: classification.primary_intent.clone(),
+            confidence: classification.confidence,
+            action: rule.action.clone(),
+            dry_run,
+            output: result.as_ref().ok().cloned().flatten(),
+            error: result.err().map(|e| e.to_string()),
+        };
+        if let Some(error) = &outcome.error {
+            log::warn!("Intent rule '{}' failed: {}", rule.name, error);
+        }
+        if !dry_run {
+            let _ = app.emit_all("intent-rule-fired", &outcome);
+        }
+        outcomes.push(outcome);
+    }
+    outcomes
+}
+
+async fn execute(
+    state: &AppState,
+    rule: &IntentRule,
+    text: &str,
+    classification: &IntentClassification,
+) -> Result<Value, AppError> {
+    match &rule.action {
+        IntentAction::AiAction { prompt, model } => {
+            let model = match model {
+                Some(model) => model.clone(),
+                None => state.settings.snapshot().ai_ml_settings.text_model.clone(),
+            };
+            crate::startup::ensure_started(state, crate::startup::Service::AiGateway).await?;
+            let gateway_state = state.ai_ml_gateway.lock().await;
+            let gateway = gateway_state
+                .as_ref()
+                .ok_or_else(|| AppError::NotInitialized("AI ML API".to_string()))?;
+            let (output, _usage) = gateway
+                .complete_with_failover(model, prompt.clone(), text.to_string(), Some(0.3))
+                .await
+                .map_err(AppError::from)?;
+            Ok(json!({ "text": output }))
+        }
+        IntentAction::Webhook { url, headers } => {
+            let body = json!({
+                "rule": rule.name,
+                "text": text,
+                "intent": classification.primary_intent,
+                "confidence": classification.confidence,
+            });
+            post_json(url, headers, &body).await
+        }
+        IntentAction::SwitchProfile { profile } => {
+            let mut settings = state.settings.write().await;
+            if !settings.content_filters.profiles.contains_key(profile) {
+                return Err(AppError::Configuration(format!(
+                    "Content filter profile '{}' no longer exists",
+                    profile
+                )));
+            }
+            let previous = std::mem::replace(&mut settings.content_filters.active_profile, profile.clone());
+            profiles::save_profile_settings(&storage::active_profile_id(), &settings)?;
+            Ok(json!({ "previous_profile": previous, "active_profile": profile }))
+        }
+    }
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
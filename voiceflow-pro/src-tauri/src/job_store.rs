@@ -0,0 +1,308 @@
+//! Durable job store for VoiceFlow Pro
+//! Keeps each profile's background jobs, with their checkpoints and partial outputs, in SQLite so they survive restarts
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::path::Path;
+use std::time::Duration;
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::encryption::get_data_vault;
+use crate::errors::AppError;
+use crate::jobs::Job;
+use crate::storage::{profile_dir, profile_dirs_all};
+
+const DATABASE_FILE: &str = "jobs.sqlite3";
+/// Where job history lived before the database; imported on first open and then renamed
+const LEGACY_FILE: &str = "jobs.json";
+const LEGACY_IMPORTED_FILE: &str = "jobs.json.imported";
+const SCHEMA_VERSION: i32 = 1;
+const WATCH_STATE_KEY: &str = "watch_state";
+
+/// What the watch folders have already produced, so no file is queued twice
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct WatchState {
+    /// Files already queued, with the modification time they had
+    #[serde(default)]
+    pub processed: BTreeMap<String, u64>,
+    /// Watch folders whose pre-existing files have been recorded
+    #[serde(default)]
+    pub baselined: BTreeSet<String>,
+    /// Local date of the last daily run per watch folder
+    #[serde(default)]
+    pub last_daily_run: BTreeMap<String, String>,
+}
+
+/// Where an interrupted document job picks up again
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct JobCheckpoint {
+    /// Outputs of the chunks finished so far, in document order
+    pub completed_chunks: Vec<String>,
+    pub total_chunks: usize,
+}
+
+/// Work a job kept from earlier attempts
+#[derive(Debug, Default)]
+pub struct SavedProgress {
+    pub checkpoint: Option<JobCheckpoint>,
+    /// Output produced but not yet written out, e.g. a transcript fetched just before a crash
+    pub partial_output: Option<String>,
+}
+
+/// A stored job with the output it kept, as written to a data export
+#[derive(Debug, Serialize)]
+pub struct ExportedJob {
+    #[serde(flatten)]
+    pub job: Job,
+    pub partial_output: Option<String>,
+}
+
+/// Layout of `jobs.json` from before the database
+#[derive(Deserialize)]
+struct LegacyStore {
+    #[serde(default)]
+    jobs: Vec<Job>,
+    #[serde(flatten)]
+    watch: WatchState,
+}
+
+pub struct JobDatabase {
+    connection: Connection,
+}
+
+impl std::fmt::Debug for JobDatabase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JobDatabase").field("path", &self.connection.path()).finish()
+    }
+}
+
+impl JobDatabase {
+    /// Open a profile's job database, creating it and importing the old JSON history on first use
+    pub fn open(profile_id: &str) -> Result<Self, AppError> {
+        let dir = profile_dir(profile_id)?;
+        std::fs::create_dir_all(&dir)?;
+        let connection = Connection::open(dir.join(DATABASE_FILE))?;
+        // A running job writes checkpoints through its own connection while the scheduler saves; WAL keeps them apart
+        connection.pragma_update(None, "journal_mode", "WAL")?;
+        connection.busy_timeout(Duration::from_secs(5))?;
+        let mut database = Self { connection };
+        database.migrate()?;
+        database.import_legacy(&dir);
+        Ok(database)
+    }
+
+    fn migrate(&self) -> Result<(), AppError> {
+        let version: i32 = self
+            .connection
+            .pragma_query_value(None, "user_version", |row| row.get(0))?;
+        if version >= SCHEMA_VERSION {
+            return Ok(());
+        }
+        self.connection.execute_batch(&format!(
+            "BEGIN;
+             CREATE TABLE IF NOT EXISTS jobs (
+                 id TEXT PRIMARY KEY,
+                 kind TEXT NOT NULL,
+                 status TEXT NOT NULL,
+                 created_at INTEGER NOT NULL,
+                 updated_at INTEGER NOT NULL,
+                 record BLOB NOT NULL,
+                 checkpoint BLOB,
+                 partial_output BLOB
+             );
+             CREATE INDEX IF NOT EXISTS jobs_by_status ON jobs (status);
+             CREATE TABLE IF NOT EXISTS state (key TEXT PRIMARY KEY, value BLOB NOT NULL);
+             PRAGMA user_version = {};
+             COMMIT;",
+            SCHEMA_VERSION
+        ))?;
+        Ok(())
+    }
+
+    /// Move `jobs.json` into the database; a file that cannot be read is left in place for the next start
+    fn import_legacy(&mut self, dir: &Path) {
+        let path = dir.join(LEGACY_FILE);
+        if !path.exists() {
+            return;
+        }
+        let imported = get_data_vault()
+            .read_file(&path)
+            .and_then(|data| serde_json::from_slice::<LegacyStore>(&data).map_err(AppError::from))
+            .and_then(|legacy| {
+                self.save_jobs(&legacy.jobs)?;
+                self.save_watch_state(&legacy.watch)?;
+                Ok(legacy.jobs.len())
+            })
+            .and_then(|count| {
+                std::fs::rename(&path, dir.join(LEGACY_IMPORTED_FILE))?;
+                Ok(count)
+            });
+        match imported {
+            Ok(count) => log::info!("Imported {} jobs from {} into the job database", count, path.display()),
+            Err(e) => log::warn!("Could not import {}: {}", path.display(), e),
+        }
+    }
+
+    /// Profiles that have a job database on disk, with its size including the write-ahead log
+    pub fn existing() -> Result<Vec<(String, u64)>, AppError> {
+        Ok(profile_dirs_all()?
+            .into_iter()
+            .filter_map(|(id, dir)| {
+                let bytes = [DATABASE_FILE, "jobs.sqlite3-wal", LEGACY_FILE, LEGACY_IMPORTED_FILE]
+                    .iter()
+                    .filter_map(|name| std::fs::metadata(dir.join(name)).ok())
+                    .map(|metadata| metadata.len())
+                    .reduce(|a, b| a + b)?;
+                Some((id, bytes))
+            })
+            .collect())
+    }
+
+    /// Every job with its partial output, for a data export
+    pub fn export(&self) -> Result<Vec<ExportedJob>, AppError> {
+        self.load_jobs()?
+            .into_iter()
+            .map(|job| {
+                let partial_output = self.load_progress(&job.id)?.partial_output;
+                Ok(ExportedJob { job, partial_output })
+            })
+            .collect()
+    }
+
+    /// Delete every job, checkpoint and watch record, overwriting the freed pages and the old JSON history
+    pub fn erase(&mut self) -> Result<(), AppError> {
+        self.connection.pragma_update(None, "secure_delete", "ON")?;
+        self.connection.execute_batch(
+            "DELETE FROM jobs;
+             DELETE FROM state;
+             PRAGMA wal_checkpoint(TRUNCATE);
+             VACUUM;",
+        )?;
+        if let Some(dir) = self.connection.path().and_then(|path| Path::new(path).parent()) {
+            for name in [LEGACY_FILE, LEGACY_IMPORTED_FILE] {
+                let path = dir.join(name);
+                if path.exists() {
+                    crate::data_management::secure_delete(&path)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Every job, oldest first
+    pub fn load_jobs(&self) -> Result<Vec<Job>, AppError> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT record FROM jobs ORDER BY created_at, rowid")?;
+        let records = statement
+            .query_map([], |row| row.get::<_, Vec<u8>>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(records
+            .into_iter()
+            .filter_map(|record| match unseal::<Job>(record) {
+                Ok(job) => Some(job),
+                Err(e) => {
+                    log::warn!("Skipping an unreadable job record: {}", e);
+                    None
+                }
+            })
+            .collect())
+    }
+
+    /// Make the stored jobs match `jobs`; checkpoints of jobs that remain are kept
+    pub fn save_jobs(&mut self, jobs: &[Job]) -> Result<(), AppError> {
+        let transaction = self.connection.transaction()?;
+        {
+            let mut upsert = transaction.prepare(
+                "INSERT INTO jobs (id, kind, status, created_at, updated_at, record) VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                 ON CONFLICT(id) DO UPDATE SET kind = ?2, status = ?3, updated_at = ?5, record = ?6",
+            )?;
+            for job in jobs {
+                upsert.execute(params![
+                    job.id,
+                    job.kind.name(),
+                    job.status.as_str(),
+                    job.created_at as i64,
+                    job.updated_at as i64,
+                    seal(job)?,
+                ])?;
+            }
+
+            let kept: HashSet<&str> = jobs.iter().map(|job| job.id.as_str()).collect();
+            let stored = transaction
+                .prepare("SELECT id FROM jobs")?
+                .query_map([], |row| row.get::<_, String>(0))?
+                .collect::<Result<Vec<_>, _>>()?;
+            let mut delete = transaction.prepare("DELETE FROM jobs WHERE id = ?1")?;
+            for id in stored.iter().filter(|id| !kept.contains(id.as_str())) {
+                delete.execute(params![id])?;
+            }
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    pub fn load_watch_state(&self) -> Result<WatchState, AppError> {
+        let value: Option<Vec<u8>> = self
+            .connection
+            .query_row("SELECT value FROM state WHERE key = ?1", params![WATCH_STATE_KEY], |row| row.get(0))
+            .optional()?;
+        value.map(unseal).transpose().map(Option::unwrap_or_default)
+    }
+
+    pub fn save_watch_state(&self, state: &WatchState) -> Result<(), AppError> {
+        self.connection.execute(
+            "INSERT INTO state (key, value) VALUES (?1, ?2) ON CONFLICT(key) DO UPDATE SET value = ?2",
+            params![WATCH_STATE_KEY, seal(state)?],
+        )?;
+        Ok(())
+    }
+
+    pub fn load_progress(&self, id: &str) -> Result<SavedProgress, AppError> {
+        let row: Option<(Option<Vec<u8>>, Option<Vec<u8>>)> = self
+            .connection
+            .query_row(
+                "SELECT checkpoint, partial_output FROM jobs WHERE id = ?1",
+                params![id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        let Some((checkpoint, partial_output)) = row else {
+            return Ok(SavedProgress::default());
+        };
+        Ok(SavedProgress {
+            checkpoint: checkpoint.map(unseal).transpose()?,
+            partial_output: partial_output.map(unseal).transpose()?,
+        })
+    }
+
+    /// Record how far a job got; `None` clears that part
+    pub fn save_progress(
+        &self,
+        id: &str,
+        checkpoint: Option<&JobCheckpoint>,
+        partial_output: Option<&str>,
+    ) -> Result<(), AppError> {
+        self.connection.execute(
+            "UPDATE jobs SET checkpoint = ?2, partial_output = ?3 WHERE id = ?1",
+            params![id, checkpoint.map(seal).transpose()?, partial_output.map(seal).transpose()?],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete(&self, id: &str) -> Result<(), AppError> {
+        self.connection.execute("DELETE FROM jobs WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+}
+
+/// Values are stored as JSON, encrypted like the rest of the profile's data when encryption is enabled
+fn seal<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, AppError> {
+    get_data_vault().seal(&serde_json::to_vec(value)?)
+}
+
+fn unseal<T: DeserializeOwned>(data: Vec<u8>) -> Result<T, AppError> {
+    Ok(serde_json::from_slice(&get_data_vault().open(data)?)?)
+}
@@ -0,0 +1,1213 @@
+//! Background jobs for VoiceFlow Pro
+//! Transcribes audio dropped into watch folders and processes queued documents, with retries, checkpoints and history
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use chrono::{Local, Timelike};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use crate::errors::AppError;
+use crate::integrations::stt_providers::{self, SttProviderKind, SttTranscript};
+use crate::integrations::{long_audio, ChunkingConfig, LongTextOperation};
+use crate::job_store::{JobCheckpoint, JobDatabase, SavedProgress, WatchState};
+use crate::storage::active_profile_id;
+use crate::AppState;
+
+/// File types handed to the transcription service
+pub const AUDIO_EXTENSIONS: &[&str] = &["wav", "mp3", "m4a", "ogg", "flac", "webm", "mp4"];
+/// Text documents that can be queued for enhancement or translation
+pub const DOCUMENT_EXTENSIONS: &[&str] = &["txt", "md", "markdown"];
+/// Recordings larger than this are decoded and transcribed in windows rather than uploaded whole
+const WINDOWED_AUDIO_BYTES: u64 = 8 * 1024 * 1024;
+/// Largest file a transcription provider takes in one upload
+const MAX_UPLOAD_BYTES: u64 = 25 * 1024 * 1024;
+/// Lowest bitrate speech is stored at (16 kbit/s Opus), bounding the length of a file whose container declares none
+const MIN_AUDIO_BYTES_PER_SEC: u64 = 2_000;
+pub const MAX_DOCUMENT_BYTES: u64 = 10 * 1024 * 1024;
+const TRANSCRIPT_PREVIEW_CHARS: usize = 200;
+
+/// When a watch folder's new files are processed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WatchTrigger {
+    /// As soon as a file has finished copying
+    OnArrival,
+    /// Once a day at a local time, e.g. nightly batches
+    Daily { hour: u32, minute: u32 },
+}
+
+/// A directory VoiceFlow watches for new recordings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchFolder {
+    pub id: String,
+    pub path: String,
+    pub trigger: WatchTrigger,
+    /// Where transcripts are written; defaults to a `transcripts` folder inside the watch folder
+    #[serde(default)]
+    pub output_dir: Option<String>,
+    #[serde(default)]
+    pub language: Option<String>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Also process files that were already in the folder when it was added
+    #[serde(default)]
+    pub include_existing: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobSettings {
+    pub watch_folders: Vec<WatchFolder>,
+    pub max_attempts: u32,
+    /// Delay before the first retry; doubles with each further attempt
+    pub retry_backoff_secs: u64,
+    pub poll_interval_secs: u64,
+    /// Finished jobs kept in history
+    pub history_limit: usize,
+}
+
+impl Default for JobSettings {
+    fn default() -> Self {
+        Self {
+            watch_folders: Vec::new(),
+            max_attempts: 3,
+            retry_backoff_secs: 60,
+            poll_interval_secs: 10,
+            history_limit: 200,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    pub fn is_finished(self) -> bool {
+        matches!(self, JobStatus::Succeeded | JobStatus::Failed | JobStatus::Cancelled)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            JobStatus::Queued => "queued",
+            JobStatus::Running => "running",
+            JobStatus::Succeeded => "succeeded",
+            JobStatus::Failed => "failed",
+            JobStatus::Cancelled => "cancelled",
+        }
+    }
+}
+
+/// What a job does with its input
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum JobKind {
+    /// Audio transcribed to text; jobs recorded before there were other kinds are all of this one
+    #[default]
+    FileTranscription,
+    /// A text document enhanced or translated chunk by chunk
+    Document { operation: LongTextOperation },
+}
+
+impl JobKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            JobKind::FileTranscription => "file_transcription",
+            JobKind::Document { .. } => "document",
+        }
+    }
+}
+
+/// Payload of the `job-progress` event
+#[derive(Debug, Clone, Serialize)]
+pub struct JobProgress {
+    pub job_id: String,
+    /// Steps done, counting those carried over from an interrupted attempt
+    pub completed: usize,
+    pub total: usize,
+    pub percent: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    pub id: String,
+    #[serde(default)]
+    pub kind: JobKind,
+    /// Watch folder the input came from
+    pub watch_folder_id: Option<String>,
+    pub input_path: String,
+    pub output_path: Option<String>,
+    pub language: Option<String>,
+    pub status: JobStatus,
+    pub attempts: u32,
+    pub max_attempts: u32,
+    pub created_at: u64,
+    pub updated_at: u64,
+    /// Earliest time a queued job may run; set when waiting to retry
+    pub next_attempt_at: Option<u64>,
+    pub error: Option<String>,
+    pub transcript_preview: Option<String>,
+}
+
+/// The active profile's jobs, mirrored to its job database on every change
+#[derive(Debug, Default)]
+struct JobStore {
+    jobs: Vec<Job>,
+    watch: WatchState,
+}
+
+/// Owns the job queue; the scheduler task and commands share it through `get_job_scheduler`
+#[derive(Debug, Default)]
+pub struct JobScheduler {
+    profile_id: Option<String>,
+    database: Option<JobDatabase>,
+    store: JobStore,
+    /// Size seen on the previous scan for files that may still be copying
+    growing: HashMap<PathBuf, u64>,
+    running: Option<(String, tokio::task::AbortHandle)>,
+}
+
+impl JobScheduler {
+    /// Jobs newest first, optionally only those with `status`
+    pub fn list(&self, status: Option<JobStatus>) -> Vec<Job> {
+        self.store
+            .jobs
+            .iter()
+            .rev()
+            .filter(|job| status.map(|s| job.status == s).unwrap_or(true))
+            .cloned()
+            .collect()
+    }
+
+    /// Cancel a queued or running job
+    pub fn cancel(&mut self, id: &str) -> Result<Job, AppError> {
+        let job = self
+            .store
+            .jobs
+            .iter_mut()
+            .find(|job| job.id == id)
+            .ok_or_else(|| AppError::Configuration(format!("Unknown job: {}", id)))?;
+        if job.status.is_finished() {
+            return Err(AppError::Configuration(format!("Job {} has already finished", id)));
+        }
+
+        if let Some((running_id, handle)) = &self.running {
+            if running_id == id {
+                handle.abort();
+                self.running = None;
+            }
+        }
+        // Checkpoints stay, so a cancelled job can be resumed where it stopped
+        job.status = JobStatus::Cancelled;
+        job.next_attempt_at = None;
+        job.updated_at = now_secs();
+        let job = job.clone();
+        self.save();
+        Ok(job)
+    }
+
+    /// Queue a failed or cancelled job again, or a job waiting to retry right away; work it already did is reused
+    pub fn resume(&mut self, id: &str) -> Result<Job, AppError> {
+        let job = self
+            .store
+            .jobs
+            .iter_mut()
+            .find(|job| job.id == id)
+            .ok_or_else(|| AppError::Configuration(format!("Unknown job: {}", id)))?;
+        match job.status {
+            JobStatus::Failed | JobStatus::Cancelled | JobStatus::Queued => {}
+            JobStatus::Running | JobStatus::Succeeded => {
+                return Err(AppError::Configuration(format!(
+                    "Job {} is {} and cannot be resumed",
+                    id,
+                    job.status.as_str()
+                )))
+            }
+        }
+        job.status = JobStatus::Queued;
+        job.attempts = 0;
+        job.next_attempt_at = None;
+        job.error = None;
+        job.updated_at = now_secs();
+        let job = job.clone();
+        self.save();
+        Ok(job)
+    }
+
+    /// Remove a job with its checkpoint and partial output, stopping it first if it is running
+    pub fn discard(&mut self, id: &str) -> Result<Job, AppError> {
+        let index = self
+            .store
+            .jobs
+            .iter()
+            .position(|job| job.id == id)
+            .ok_or_else(|| AppError::Configuration(format!("Unknown job: {}", id)))?;
+        if let Some((running_id, handle)) = &self.running {
+            if running_id == id {
+                handle.abort();
+                self.running = None;
+            }
+        }
+        let job = self.store.jobs.remove(index);
+        if let Some(database) = &self.database {
+            if let Err(e) = database.delete(id) {
+                log::warn!("Failed to delete job {}: {}", id, e);
+            }
+        }
+        self.save();
+        Ok(job)
+    }
+
+    /// Add a job to the queue, e.g. a document the user picked
+    pub fn enqueue(&mut self, job: Job) -> Job {
+        self.sync_profile();
+        self.store.jobs.push(job.clone());
+        self.save();
+        job
+    }
+
+    /// Stop the running job and forget every job, erasing the active profile's job database
+    pub fn clear(&mut self) -> Result<(), AppError> {
+        if let Some((_, handle)) = self.running.take() {
+            handle.abort();
+        }
+        self.store = JobStore::default();
+        self.growing.clear();
+        match &mut self.database {
+            Some(database) => database.erase(),
+            None => Ok(()),
+        }
+    }
+
+    /// Load the active profile's jobs if the profile changed since the last tick
+    fn sync_profile(&mut self) {
+        let active = active_profile_id();
+        if self.profile_id.as_deref() == Some(active.as_str()) {
+            return;
+        }
+        if let Some((_, handle)) = self.running.take() {
+            handle.abort();
+        }
+        self.save();
+        self.database = match JobDatabase::open(&active) {
+            Ok(database) => Some(database),
+            Err(e) => {
+                log::warn!("Job history is unavailable for this session: {}", e);
+                None
+            }
+        };
+        self.store = self.database.as_ref().map(load_store).unwrap_or_default();
+        // A job interrupted by a restart or profile switch runs again, from its last checkpoint
+        let mut interrupted = 0;
+        for job in self.store.jobs.iter_mut().filter(|job| job.status == JobStatus::Running) {
+            job.status = JobStatus::Queued;
+            interrupted += 1;
+        }
+        if interrupted > 0 {
+            log::info!("Resuming {} jobs interrupted by a restart or profile switch", interrupted);
+        }
+        self.growing.clear();
+        self.profile_id = Some(active);
+    }
+
+    /// Profile the loaded jobs belong to; running jobs write their checkpoints there
+    fn profile_id(&self) -> Option<&str> {
+        self.profile_id.as_deref()
+    }
+
+    /// Queue files that have appeared in watch folders, returning the new jobs
+    fn scan(&mut self, settings: &JobSettings) -> Vec<Job> {
+        let mut queued = Vec::new();
+        let now_local = Local::now();
+        let today = now_local.date_naive().to_string();
+
+        for folder in settings.watch_folders.iter().filter(|f| f.enabled) {
+            let files = audio_files(Path::new(&folder.path));
+
+            if !self.store.watch.baselined.contains(&folder.id) {
+                if !folder.include_existing {
+                    for (path, modified, _) in &files {
+                        self.store.watch.processed.insert(path.display().to_string(), *modified);
+                    }
+                }
+                self.store.watch.baselined.insert(folder.id.clone());
+            }
+
+            let due = match &folder.trigger {
+                WatchTrigger::OnArrival => true,
+                WatchTrigger::Daily { hour, minute } => {
+                    (now_local.hour(), now_local.minute()) >= (*hour, *minute)
+                        && self.store.watch.last_daily_run.get(&folder.id) != Some(&today)
+                }
+            };
+
+            let mut ready = Vec::new();
+            for (path, modified, size) in files {
+                let key = path.display().to_string();
+                if self.store.watch.processed.get(&key) == Some(&modified) {
+                    continue;
+                }
+                // Only pick up files whose size held steady since the last scan, i.e. finished copying
+                if self.growing.insert(path.clone(), size) == Some(size) {
+                    ready.push((path, modified));
+                }
+            }
+
+            if !due {
+                continue;
+            }
+            if matches!(folder.trigger, WatchTrigger::Daily { .. }) {
+                self.store.watch.last_daily_run.insert(folder.id.clone(), today.clone());
+            }
+            for (path, modified) in ready {
+                self.growing.remove(&path);
+                self.store.watch.processed.insert(path.display().to_string(), modified);
+                let output_path = transcript_path(folder, &path);
+                let job = new_job(
+                    Some(folder.id.clone()),
+                    &path,
+                    Some(output_path),
+                    folder.language.clone(),
+                    settings.max_attempts,
+                );
+                queued.push(job.clone());
+                self.store.jobs.push(job);
+            }
+        }
+
+        if !queued.is_empty() {
+            self.save();
+        }
+        queued
+    }
+
+    /// Claim the next runnable job, if nothing is running
+    fn next_runnable(&mut self) -> Option<Job> {
+        if self.running.is_some() {
+            return None;
+        }
+        let now = now_secs();
+        let job = self.store.jobs.iter_mut().find(|job| {
+            job.status == JobStatus::Queued && job.next_attempt_at.map(|at| at <= now).unwrap_or(true)
+        })?;
+        job.status = JobStatus::Running;
+        job.attempts += 1;
+        job.updated_at = now;
+        job.next_attempt_at = None;
+        Some(job.clone())
+    }
+
+    /// Record the outcome of a run, scheduling a retry when the failure is transient
+    fn finish(&mut self, id: &str, outcome: Result<String, JobFailure>, settings: &JobSettings) -> Option<Job> {
+        if self.running.as_ref().map(|(running, _)| running == id).unwrap_or(false) {
+            self.running = None;
+        }
+        let job = self.store.jobs.iter_mut().find(|job| job.id == id)?;
+        if job.status != JobStatus::Running {
+            // Cancelled while the result was in flight
+            return None;
+        }
+
+        let now = now_secs();
+        job.updated_at = now;
+        match outcome {
+            Ok(transcript) => {
+                job.status = JobStatus::Succeeded;
+                job.error = None;
+                job.transcript_preview = Some(transcript.chars().take(TRANSCRIPT_PREVIEW_CHARS).collect());
+                // The output file now holds the result; the checkpoint is no longer needed
+                if let Some(database) = &self.database {
+                    let _ = database.save_progress(id, None, None);
+                }
+            }
+            Err(failure) => {
+                job.error = Some(failure.message);
+                if failure.retryable && job.attempts < job.max_attempts {
+                    let backoff = settings.retry_backoff_secs.saturating_mul(1u64 << (job.attempts - 1).min(10));
+                    job.status = JobStatus::Queued;
+                    job.next_attempt_at = Some(now + backoff);
+                } else {
+                    job.status = JobStatus::Failed;
+                }
+            }
+        }
+        let job = job.clone();
+        self.trim_history(settings.history_limit);
+        self.save();
+        Some(job)
+    }
+
+    fn trim_history(&mut self, limit: usize) {
+        let finished = self.store.jobs.iter().filter(|job| job.status.is_finished()).count();
+        let mut excess = finished.saturating_sub(limit);
+        self.store.jobs.retain(|job| {
+            if excess > 0 && job.status.is_finished() {
+                excess -= 1;
+                false
+            } else {
+                true
+            }
+        });
+    }
+
+    /// Write the store to the profile it was loaded from, which may no longer be the active one
+    fn save(&mut self) {
+        let Some(database) = &mut self.database else {
+            return;
+        };
+        let result = database
+            .save_jobs(&self.store.jobs)
+            .and_then(|_| database.save_watch_state(&self.store.watch));
+        if let Err(e) = result {
+            log::warn!("Failed to save job history: {}", e);
+        }
+    }
+}
+
+static JOB_SCHEDULER: std::sync::OnceLock<Mutex<JobScheduler>> = std::sync::OnceLock::new();
+
+/// Get the global job scheduler
+pub fn get_job_scheduler() -> &'static Mutex<JobScheduler> {
+    JOB_SCHEDULER.get_or_init(|| Mutex::new(JobScheduler::default()))
+}
+
+/// Poll watch folders and run queued jobs one at a time, for the lifetime of the app
+pub async fn run_scheduler(state: AppState, app: AppHandle) {
+    loop {
+        let settings = state.settings.snapshot().jobs.clone();
+
+        let (queued, runnable) = {
+            let mut scheduler = get_job_scheduler().lock().await;
+            scheduler.sync_profile();
+            let queued = scheduler.scan(&settings);
+            // Files keep queueing while the user is presenting or on battery; they just start later
+            let deferred = crate::focus::current_focus_state().defer_jobs || crate::power::current_profile().defer_jobs;
+            let runnable = if deferred {
+                None
+            } else {
+                scheduler.next_runnable()
+            };
+            let profile_id = scheduler.profile_id().map(str::to_string);
+            (queued, runnable.zip(profile_id))
+        };
+        for job in &queued {
+            let _ = app.emit_all("job-updated", job);
+        }
+
+        if let Some((job, profile_id)) = runnable {
+            let _ = app.emit_all("job-updated", &job);
+            let id = job.id.clone();
+            let task_state = state.clone();
+            let task_app = app.clone();
+            let task_settings = settings.clone();
+
+            // Hold the scheduler lock across spawn so the job cannot finish before its handle is stored
+            let mut scheduler = get_job_scheduler().lock().await;
+            let handle = tokio::spawn(async move {
+                let outcome = execute_job(&job, &task_state, &task_app, &profile_id).await;
+                if let Err(failure) = &outcome {
+                    log::warn!("Job {} attempt {} failed: {}", job.id, job.attempts, failure.message);
+                }
+                let finished = get_job_scheduler().lock().await.finish(&job.id, outcome, &task_settings);
+                if let Some(job) = finished {
+                    let _ = task_app.emit_all("job-updated", &job);
+                    notify_finished(&task_app, &job, task_state.settings.snapshot().notifications);
+                }
+            });
+            scheduler.running = Some((id, handle.abort_handle()));
+        }
+
+        let resources = state.settings.snapshot().resources.clone();
+        let interval = tokio::time::Duration::from_secs(settings.poll_interval_secs.max(1));
+        tokio::time::sleep(crate::idle::adjust_interval(interval, &resources)).await;
+    }
+}
+
+/// Desktop notification for a job that succeeded or ran out of attempts, unless focus rules hold it back
+fn notify_finished(app: &AppHandle, job: &Job, notifications: bool) {
+    let title = match job.status {
+        JobStatus::Succeeded => "Job finished",
+        JobStatus::Failed => "Job failed",
+        _ => return,
+    };
+    if !crate::focus::notifications_allowed(notifications) {
+        return;
+    }
+    let file = Path::new(&job.input_path)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| job.input_path.clone());
+    let body = match &job.error {
+        Some(error) if job.status == JobStatus::Failed => format!("{}: {}", file, error),
+        _ => file,
+    };
+    let shown = tauri::api::notification::Notification::new(&app.config().tauri.bundle.identifier)
+        .title(title)
+        .body(body)
+        .show();
+    if let Err(e) = shown {
+        log::warn!("Could not show the job notification: {}", e);
+    }
+}
+
+#[derive(Debug)]
+struct JobFailure {
+    message: String,
+    retryable: bool,
+}
+
+impl JobFailure {
+    fn permanent(message: impl Into<String>) -> Self {
+        Self { message: message.into(), retryable: false }
+    }
+
+    fn transient(message: impl Into<String>) -> Self {
+        Self { message: message.into(), retryable: true }
+    }
+}
+
+async fn execute_job(job: &Job, state: &AppState, app: &AppHandle, profile_id: &str) -> Result<String, JobFailure> {
+    let progress = ProgressStore::open(profile_id, &job.id);
+    let saved = progress.load();
+    let output = match &job.kind {
+        JobKind::FileTranscription => transcribe_file(job, state, app, &progress, saved).await?,
+        JobKind::Document { operation } => process_document(job, operation, state, app, &progress, saved).await?,
+    };
+
+    if let Some(path) = &job.output_path {
+        let path = Path::new(path);
+        // Jobs queued before output paths were checked must not replace their own input either
+        if same_file(path, Path::new(&job.input_path)) {
+            return Err(JobFailure::permanent(format!("{} would overwrite the job's input", path.display())));
+        }
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|e| JobFailure::permanent(format!("Cannot create {}: {}", parent.display(), e)))?;
+        }
+        tokio::fs::write(path, output.as_bytes())
+            .await
+            .map_err(|e| JobFailure::transient(format!("Cannot write {}: {}", path.display(), e)))?;
+    }
+    Ok(output)
+}
+
+async fn transcribe_file(
+    job: &Job,
+    state: &AppState,
+    app: &AppHandle,
+    progress: &ProgressStore,
+    saved: SavedProgress,
+) -> Result<String, JobFailure> {
+    // A transcript fetched before an interruption is not paid for twice
+    if let Some(transcript) = saved.partial_output {
+        log::info!("Job {} resumes with the transcript from its previous attempt", job.id);
+        emit_progress(app, &job.id, 1, 1);
+        return Ok(transcript);
+    }
+
+    let input = Path::new(&job.input_path);
+    let metadata = tokio::fs::metadata(input)
+        .await
+        .map_err(|e| JobFailure::permanent(format!("Cannot read {}: {}", input.display(), e)))?;
+    let file_name = input
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "audio".to_string());
+    let (speech_to_text, failover) = {
+        let settings = state.settings.snapshot();
+        (settings.speech_to_text.clone(), settings.ai_ml_settings.failover.clone())
+    };
+    let provider = job
+        .language
+        .as_deref()
+        .map_or(speech_to_text.default_provider, |language| speech_to_text.provider_for(language));
+    // A file the provider cannot take in one request goes through the windowed decoder instead
+    let capabilities = provider.capabilities();
+    let extension = input
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    let probe_path = input.to_path_buf();
+    let duration_secs = tokio::task::spawn_blocking(move || long_audio::probe_duration(&probe_path))
+        .await
+        .ok()
+        .flatten()
+        .unwrap_or(metadata.len() as f64 / MIN_AUDIO_BYTES_PER_SEC as f64);
+    let fits_provider = capabilities.formats.contains(&extension.as_str())
+        && capabilities.max_batch_secs.map_or(true, |secs| duration_secs <= secs as f64);
+    let windowed = metadata.len() > WINDOWED_AUDIO_BYTES || !fits_provider;
+    // The decoder has no webm or Opus support; such a file is still sent whole when the provider can take it
+    let uploadable = fits_provider && metadata.len() <= MAX_UPLOAD_BYTES;
+    if windowed && uploadable && !long_audio::decodable(input) {
+        log::info!("Job {} cannot be decoded into windows; uploading the whole file", job.id);
+    } else if windowed {
+        if !fits_provider {
+            log::info!(
+                "Job {} is too long or in a format {} does not take; transcribing in windows",
+                job.id,
+                provider.label()
+            );
+        }
+        return transcribe_windowed(job, state, app, progress, provider, saved.checkpoint).await;
+    }
+    let audio = tokio::fs::read(input)
+        .await
+        .map_err(|e| JobFailure::transient(format!("Cannot read {}: {}", input.display(), e)))?;
+    emit_progress(app, &job.id, 0, 1);
+
+    let transcript = stt_providers::transcribe(
+        &speech_to_text,
+        &failover,
+        &state.ai_ml_gateway,
+        audio,
+        file_name,
+        job.language.clone(),
+    )
+    .await
+    .map_err(provider_failure)?;
+    write_word_timings(job, &transcript).await;
+    let text = render_transcript(&transcript);
+    progress.save(None, Some(&text));
+    emit_progress(app, &job.id, 1, 1);
+    Ok(text)
+}
+
+/// The transcript as written out: one paragraph per speaker turn when the provider told speakers apart
+fn render_transcript(transcript: &SttTranscript) -> String {
+    let mut turns: Vec<(Option<u32>, Vec<&str>)> = Vec::new();
+    for word in &transcript.words {
+        match turns.last_mut() {
+            Some((speaker, words)) if *speaker == word.speaker => words.push(&word.word),
+            _ => turns.push((word.speaker, vec![&word.word])),
+        }
+    }
+    let speakers: std::collections::BTreeSet<u32> = turns.iter().filter_map(|(speaker, _)| *speaker).collect();
+    if speakers.len() < 2 {
+        return transcript.text.clone();
+    }
+    turns
+        .into_iter()
+        .map(|(speaker, words)| match speaker {
+            Some(speaker) => format!("Speaker {}: {}", speaker, words.join(" ")),
+            None => words.join(" "),
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Word timings and speakers go next to the transcript as `<output>.words.json`, for subtitles and review
+async fn write_word_timings(job: &Job, transcript: &SttTranscript) {
+    let Some(output) = job.output_path.as_ref().filter(|_| !transcript.words.is_empty()) else {
+        return;
+    };
+    let path = PathBuf::from(format!("{}.words.json", output));
+    let written = async {
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(&path, serde_json::to_vec_pretty(transcript)?).await
+    };
+    if let Err(e) = written.await {
+        log::warn!("Could not write the word timings of job {} to {}: {}", job.id, path.display(), e);
+    }
+}
+
+/// Hour-long recordings are streamed through the decoder in windows and sent to the language's provider;
+/// each finished window is checkpointed. Windows carry text only, so no word timings are written.
+async fn transcribe_windowed(
+    job: &Job,
+    state: &AppState,
+    app: &AppHandle,
+    progress: &ProgressStore,
+    provider: SttProviderKind,
+    checkpoint: Option<JobCheckpoint>,
+) -> Result<String, JobFailure> {
+    let (mut settings, speech_to_text, failover) = {
+        let settings = state.settings.snapshot();
+        (
+            settings.long_audio.clone(),
+            settings.speech_to_text.clone(),
+            settings.ai_ml_settings.failover.clone(),
+        )
+    };
+    // Each window has to fit in one of the provider's requests
+    if let Some(max_secs) = provider.capabilities().max_batch_secs {
+        settings.window_secs = settings.window_secs.min(max_secs);
+        settings.overlap_secs = settings.overlap_secs.min(settings.window_secs / 4);
+    }
+    let completed: BTreeMap<usize, String> = checkpoint
+        .map(|checkpoint| checkpoint.completed_chunks.into_iter().enumerate().collect())
+        .unwrap_or_default();
+    if !completed.is_empty() {
+        log::info!("Job {} resumes after {} transcribed windows", job.id, completed.len());
+    }
+
+    let store = progress.clone();
+    let job_id = job.id.clone();
+    let progress_app = app.clone();
+    let mut checkpoint = JobCheckpoint {
+        completed_chunks: completed.values().cloned().collect(),
+        total_chunks: 0,
+    };
+    // Windows finish out of order; only an unbroken run from the start can be resumed from
+    let mut finished: BTreeMap<usize, String> = BTreeMap::new();
+    let on_window = move |progress: crate::integrations::LongAudioProgress, text: &str| {
+        finished.insert(progress.window_index, text.to_string());
+        let before = checkpoint.completed_chunks.len();
+        while let Some(text) = finished.remove(&checkpoint.completed_chunks.len()) {
+            checkpoint.completed_chunks.push(text);
+        }
+        checkpoint.total_chunks = progress.windows_total.unwrap_or_default();
+        if checkpoint.completed_chunks.len() > before {
+            store.save(Some(&checkpoint), None);
+        }
+        let _ = progress_app.emit_all("long-audio-progress", &progress);
+        emit_progress(
+            &progress_app,
+            &job_id,
+            progress.windows_done,
+            progress.windows_total.unwrap_or(progress.windows_done),
+        );
+    };
+
+    let transcribe_window = |audio: Vec<u8>, file_name: String, language: Option<String>| {
+        let (speech_to_text, failover) = (&speech_to_text, &failover);
+        async move {
+            stt_providers::transcribe(speech_to_text, failover, &state.ai_ml_gateway, audio, file_name, language)
+                .await
+                .map(|transcript| transcript.text)
+        }
+    };
+    let transcript = long_audio::transcribe(
+        transcribe_window,
+        job.id.clone(),
+        Path::new(&job.input_path),
+        job.language.clone(),
+        &settings,
+        completed,
+        on_window,
+    )
+    .await
+    .map_err(|e| match e {
+        crate::integrations::LongAudioError::Decode { .. } => JobFailure::permanent(e.to_string()),
+        crate::integrations::LongAudioError::Transcription(e) => provider_failure(e),
+    })?
+    .text;
+    progress.save(None, Some(&transcript));
+    Ok(transcript)
+}
+
+async fn process_document(
+    job: &Job,
+    operation: &LongTextOperation,
+    state: &AppState,
+    app: &AppHandle,
+    progress: &ProgressStore,
+    saved: SavedProgress,
+) -> Result<String, JobFailure> {
+    if let Some(document) = saved.partial_output {
+        log::info!("Job {} resumes with the document finished by its previous attempt", job.id);
+        return Ok(document);
+    }
+
+    let input = Path::new(&job.input_path);
+    let metadata = tokio::fs::metadata(input)
+        .await
+        .map_err(|e| JobFailure::permanent(format!("Cannot read {}: {}", input.display(), e)))?;
+    if metadata.len() > MAX_DOCUMENT_BYTES {
+        return Err(JobFailure::permanent(format!(
+            "{} is larger than {} MB",
+            input.display(),
+            MAX_DOCUMENT_BYTES / (1024 * 1024)
+        )));
+    }
+    let text = tokio::fs::read_to_string(input)
+        .await
+        .map_err(|e| JobFailure::permanent(format!("Cannot read {} as text: {}", input.display(), e)))?;
+
+    let mut completed = saved.checkpoint.map(|checkpoint| checkpoint.completed_chunks).unwrap_or_default();
+    if !completed.is_empty() {
+        log::info!("Job {} resumes after {} finished chunks", job.id, completed.len());
+    }
+
+    let store = progress.clone();
+    let job_id = job.id.clone();
+    let progress_app = app.clone();
+    let mut checkpoint = JobCheckpoint {
+        completed_chunks: completed.clone(),
+        total_chunks: 0,
+    };
+    let on_chunk = move |progress: crate::integrations::ChunkProgress, output: &str| {
+        // Only an unbroken run of successful chunks can be resumed from; a failed chunk is retried next attempt
+        if progress.succeeded && progress.chunk_index == checkpoint.completed_chunks.len() {
+            checkpoint.completed_chunks.push(output.to_string());
+            checkpoint.total_chunks = progress.total_chunks;
+            store.save(Some(&checkpoint), None);
+        }
+        emit_progress(&progress_app, &job_id, progress.chunk_index + 1, progress.total_chunks);
+    };
+
+    let result = gateway(state)
+        .await?
+        .process_long_text_from(
+            job.id.clone(),
+            text,
+            operation.clone(),
+            ChunkingConfig::default(),
+            std::mem::take(&mut completed),
+            on_chunk,
+        )
+        .await
+        .map_err(gateway_failure)?;
+    if !result.failed_chunks.is_empty() {
+        return Err(JobFailure::transient(format!(
+            "{} of {} chunks failed; finished chunks are kept for the next attempt",
+            result.failed_chunks.len(),
+            result.total_chunks
+        )));
+    }
+    progress.save(None, Some(&result.processed_text));
+    Ok(result.processed_text)
+}
+
+/// A handle on the gateway, so a job that runs for minutes does not keep other commands waiting on the lock
+async fn gateway(state: &AppState) -> Result<crate::integrations::AIMLAPIGateway, JobFailure> {
+    state
+        .ai_ml_gateway
+        .lock()
+        .await
+        .clone()
+        .ok_or_else(|| JobFailure::transient("AI service is not initialized"))
+}
+
+fn gateway_failure(e: crate::integrations::AIMLError) -> JobFailure {
+    match e {
+        crate::integrations::AIMLError::AuthError(_) | crate::integrations::AIMLError::MissingParameter(_) => {
+            JobFailure::permanent(e.to_string())
+        }
+        _ => JobFailure::transient(e.to_string()),
+    }
+}
+
+fn provider_failure(e: AppError) -> JobFailure {
+    match e {
+        AppError::Configuration(_) | AppError::Authentication(_) | AppError::Validation(_) => {
+            JobFailure::permanent(e.to_string())
+        }
+        _ => JobFailure::transient(e.to_string()),
+    }
+}
+
+fn emit_progress(app: &AppHandle, job_id: &str, completed: usize, total: usize) {
+    let progress = JobProgress {
+        job_id: job_id.to_string(),
+        completed,
+        total,
+        percent: completed as f32 / total.max(1) as f32 * 100.0,
+    };
+    let _ = app.emit_all("job-progress", progress);
+}
+
+/// The checkpoints of one running job, on a connection opened once and shared with the progress callbacks
+#[derive(Clone)]
+struct ProgressStore {
+    job_id: String,
+    // A mutex because the connection is not `Sync` and the job's future must stay `Send`
+    database: Option<std::sync::Arc<std::sync::Mutex<JobDatabase>>>,
+}
+
+impl ProgressStore {
+    fn open(profile_id: &str, job_id: &str) -> Self {
+        let database = JobDatabase::open(profile_id)
+            .map_err(|e| log::warn!("Job {} will run without checkpoints: {}", job_id, e))
+            .ok()
+            .map(|database| std::sync::Arc::new(std::sync::Mutex::new(database)));
+        Self { job_id: job_id.to_string(), database }
+    }
+
+    fn load(&self) -> SavedProgress {
+        let Some(database) = &self.database else {
+            return SavedProgress::default();
+        };
+        let loaded = database.lock().unwrap_or_else(|e| e.into_inner()).load_progress(&self.job_id);
+        loaded.unwrap_or_else(|e| {
+            log::warn!("Job {} starts over; its checkpoint could not be read: {}", self.job_id, e);
+            SavedProgress::default()
+        })
+    }
+
+    fn save(&self, checkpoint: Option<&JobCheckpoint>, partial_output: Option<&str>) {
+        let Some(database) = &self.database else {
+            return;
+        };
+        let saved = database
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .save_progress(&self.job_id, checkpoint, partial_output);
+        if let Err(e) = saved {
+            log::warn!("Failed to save the progress of job {}: {}", self.job_id, e);
+        }
+    }
+}
+
+/// Whether two paths name the same file; an output that does not exist yet is compared through its parent
+fn same_file(a: &Path, b: &Path) -> bool {
+    fn resolve(path: &Path) -> PathBuf {
+        if let Ok(path) = std::fs::canonicalize(path) {
+            return path;
+        }
+        match (path.parent(), path.file_name()) {
+            (Some(parent), Some(name)) => std::fs::canonicalize(parent)
+                .map(|parent| parent.join(name))
+                .unwrap_or_else(|_| path.to_path_buf()),
+            _ => path.to_path_buf(),
+        }
+    }
+    resolve(a) == resolve(b)
+}
+
+/// An explicit output path for a document job: absolute, not a directory, and not the document itself
+fn validate_output_path(input: &Path, output: &str) -> Result<(), AppError> {
+    let path = Path::new(output);
+    if !path.is_absolute() || path.file_name().is_none() {
+        return Err(AppError::Configuration(format!("Output path {} must be an absolute file path", output)));
+    }
+    if path.is_dir() {
+        return Err(AppError::Configuration(format!("Output path {} is a directory", output)));
+    }
+    if same_file(path, input) {
+        return Err(AppError::Configuration(format!(
+            "Output path {} would overwrite the document being processed",
+            output
+        )));
+    }
+    Ok(())
+}
+
+/// A document job for `input`, checked before it is queued so mistakes surface in the command rather than the job
+pub fn document_job(
+    input: &Path,
+    operation: LongTextOperation,
+    output_path: Option<String>,
+    max_attempts: u32,
+) -> Result<Job, AppError> {
+    let metadata = std::fs::metadata(input)
+        .map_err(|e| AppError::Configuration(format!("Cannot read {}: {}", input.display(), e)))?;
+    let extension = input
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_lowercase)
+        .unwrap_or_default();
+    if !metadata.is_file() || !DOCUMENT_EXTENSIONS.contains(&extension.as_str()) {
+        return Err(AppError::Configuration(format!(
+            "{} is not a text document ({})",
+            input.display(),
+            DOCUMENT_EXTENSIONS.join(", ")
+        )));
+    }
+    if metadata.len() > MAX_DOCUMENT_BYTES {
+        return Err(AppError::Configuration(format!(
+            "{} is larger than {} MB",
+            input.display(),
+            MAX_DOCUMENT_BYTES / (1024 * 1024)
+        )));
+    }
+
+    if let Some(output) = &output_path {
+        validate_output_path(input, output)?;
+    }
+    let output_path = output_path.unwrap_or_else(|| {
+        let suffix = match &operation {
+            LongTextOperation::Enhance { .. } => "enhanced".to_string(),
+            LongTextOperation::Translate { target_language, .. } => target_language.clone(),
+        };
+        let stem = input.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+        input
+            .with_file_name(format!("{}.{}.{}", stem, suffix, extension))
+            .display()
+            .to_string()
+    });
+    let mut job = new_job(None, input, Some(output_path), None, max_attempts);
+    job.kind = JobKind::Document { operation };
+    Ok(job)
+}
+
+fn new_job(watch_folder_id: Option<String>, input: &Path, output_path: Option<String>, language: Option<String>, max_attempts: u32) -> Job {
+    let now = now_secs();
+    Job {
+        id: Uuid::new_v4().to_string(),
+        kind: JobKind::FileTranscription,
+        watch_folder_id,
+        input_path: input.display().to_string(),
+        output_path,
+        language,
+        status: JobStatus::Queued,
+        attempts: 0,
+        max_attempts: max_attempts.max(1),
+        created_at: now,
+        updated_at: now,
+        next_attempt_at: None,
+        error: None,
+        transcript_preview: None,
+    }
+}
+
+fn transcript_path(folder: &WatchFolder, input: &Path) -> String {
+    let dir = folder
+        .output_dir
+        .as_ref()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| Path::new(&folder.path).join("transcripts"));
+    let stem = input.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    dir.join(format!("{}.txt", stem)).display().to_string()
+}
+
+/// Audio files directly inside `dir`, with modification time and size
+fn audio_files(dir: &Path) -> Vec<(PathBuf, u64, u64)> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            entry
+                .path()
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .map(|ext| AUDIO_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+                .unwrap_or(false)
+        })
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok().filter(|m| m.is_file())?;
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            Some((entry.path(), modified, metadata.len()))
+        })
+        .collect()
+}
+
+fn load_store(database: &JobDatabase) -> JobStore {
+    let jobs = database.load_jobs().unwrap_or_else(|e| {
+        log::warn!("Failed to load job history: {}", e);
+        Vec::new()
+    });
+    let watch = database.load_watch_state().unwrap_or_else(|e| {
+        log::warn!("Failed to load watch folder state: {}", e);
+        WatchState::default()
+    });
+    JobStore { jobs, watch }
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integrations::stt_providers::SttWord;
+
+    fn temp_document(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("voiceflow-jobs-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        std::fs::write(&path, "Some notes to clean up.").unwrap();
+        path
+    }
+
+    fn enhance() -> LongTextOperation {
+        LongTextOperation::Enhance { tone: "professional".to_string() }
+    }
+
+    #[test]
+    fn document_job_rejects_an_output_path_that_is_the_input() {
+        let input = temp_document("notes.txt");
+        let same = input.display().to_string();
+        let through_dot = input.parent().unwrap().join(".").join("notes.txt").display().to_string();
+        assert!(document_job(&input, enhance(), Some(same), 3).is_err());
+        assert!(document_job(&input, enhance(), Some(through_dot), 3).is_err());
+        std::fs::remove_dir_all(input.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn document_job_rejects_relative_and_directory_outputs() {
+        let input = temp_document("notes.txt");
+        let dir = input.parent().unwrap().display().to_string();
+        assert!(document_job(&input, enhance(), Some("notes.enhanced.txt".to_string()), 3).is_err());
+        assert!(document_job(&input, enhance(), Some(dir), 3).is_err());
+        std::fs::remove_dir_all(input.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn document_job_accepts_a_new_file_and_defaults_next_to_the_input() {
+        let input = temp_document("notes.txt");
+        let output = input.with_file_name("clean.txt").display().to_string();
+        let job = document_job(&input, enhance(), Some(output.clone()), 3).unwrap();
+        assert_eq!(job.output_path.as_deref(), Some(output.as_str()));
+
+        let job = document_job(&input, enhance(), None, 3).unwrap();
+        let default = job.output_path.unwrap();
+        assert!(default.ends_with("notes.enhanced.txt"));
+        assert!(!same_file(Path::new(&default), &input));
+        std::fs::remove_dir_all(input.parent().unwrap()).unwrap();
+    }
+
+    fn word(word: &str, speaker: Option<u32>) -> SttWord {
+        SttWord {
+            word: word.to_string(),
+            speaker,
+            ..SttWord::default()
+        }
+    }
+
+    fn transcript(words: Vec<SttWord>) -> SttTranscript {
+        SttTranscript {
+            text: "hi there how are you".to_string(),
+            language: None,
+            duration: None,
+            confidence: None,
+            words,
+            provider: SttProviderKind::Deepgram,
+        }
+    }
+
+    #[test]
+    fn transcript_splits_into_speaker_turns() {
+        let diarized = transcript(vec![
+            word("hi", Some(0)),
+            word("there", Some(0)),
+            word("how", Some(1)),
+            word("are", Some(1)),
+            word("you", Some(0)),
+        ]);
+        assert_eq!(
+            render_transcript(&diarized),
+            "Speaker 0: hi there\n\nSpeaker 1: how are\n\nSpeaker 0: you"
+        );
+
+        let one_speaker = transcript(vec![word("hi", Some(0)), word("there", Some(0))]);
+        assert_eq!(render_transcript(&one_speaker), "hi there how are you");
+        assert_eq!(render_transcript(&transcript(Vec::new())), "hi there how are you");
+    }
+}
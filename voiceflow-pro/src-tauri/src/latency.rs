@@ -0,0 +1,211 @@
+//! Latency tracking for VoiceFlow Pro
+//! Times each dictation stage from audio capture to text injection and reports where the time goes
+
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+/// Breakdowns kept for the report; older utterances are dropped
+const MAX_SAMPLES: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LatencyStage {
+    /// Hotkey or trigger until the first audio frame arrives
+    AudioCapture,
+    /// End of speech until voice activity detection closes the utterance
+    Vad,
+    /// Utterance closed until the final transcript is available
+    Stt,
+    /// Transcript until the processed text is ready to insert
+    TextPipeline,
+    /// Processed text until it appears in the target app
+    Injection,
+}
+
+impl LatencyStage {
+    const ALL: [LatencyStage; 5] = [
+        LatencyStage::AudioCapture,
+        LatencyStage::Vad,
+        LatencyStage::Stt,
+        LatencyStage::TextPipeline,
+        LatencyStage::Injection,
+    ];
+}
+
+/// Timestamps taken by the frontend, in milliseconds since the Unix epoch
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClientTimings {
+    pub capture_started_ms: Option<u64>,
+    pub first_audio_ms: Option<u64>,
+    pub speech_ended_ms: Option<u64>,
+    pub vad_endpoint_ms: Option<u64>,
+    pub transcript_ready_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StageTiming {
+    pub stage: LatencyStage,
+    pub duration_ms: u64,
+}
+
+/// Per-utterance timings; stages the frontend did not time are left out
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LatencyBreakdown {
+    pub utterance_id: String,
+    pub stages: Vec<StageTiming>,
+    pub total_ms: u64,
+    pub slowest_stage: Option<LatencyStage>,
+    /// When the processed text was ready, used to time injection once the frontend reports it
+    pub result_ready_ms: u64,
+}
+
+impl LatencyBreakdown {
+    /// Build a breakdown for an utterance whose text pipeline ran from `pipeline_started_ms` until now
+    pub fn new(utterance_id: &str, client: &ClientTimings, pipeline_started_ms: u64) -> Self {
+        let result_ready_ms = now_ms();
+        // The pipeline starts when the transcript exists, so IPC time to reach us counts against it
+        let pipeline_start = client.transcript_ready_ms.unwrap_or(pipeline_started_ms);
+        let spans = [
+            (LatencyStage::AudioCapture, client.capture_started_ms, client.first_audio_ms),
+            (LatencyStage::Vad, client.speech_ended_ms, client.vad_endpoint_ms),
+            (LatencyStage::Stt, client.vad_endpoint_ms.or(client.speech_ended_ms), client.transcript_ready_ms),
+            (LatencyStage::TextPipeline, Some(pipeline_start), Some(result_ready_ms)),
+        ];
+
+        let mut breakdown = Self {
+            utterance_id: utterance_id.to_string(),
+            stages: Vec::new(),
+            total_ms: 0,
+            slowest_stage: None,
+            result_ready_ms,
+        };
+        for (stage, start, end) in spans {
+            if let (Some(start), Some(end)) = (start, end) {
+                breakdown.stages.push(StageTiming {
+                    stage,
+                    duration_ms: end.saturating_sub(start),
+                });
+            }
+        }
+        breakdown.summarize();
+        breakdown
+    }
+
+    pub fn record_injection(&mut self, injected_at_ms: u64) {
+        self.stages.retain(|s| s.stage != LatencyStage::Injection);
+        self.stages.push(StageTiming {
+            stage: LatencyStage::Injection,
+            duration_ms: injected_at_ms.saturating_sub(self.result_ready_ms),
+        });
+        self.summarize();
+    }
+
+    fn summarize(&mut self) {
+        self.total_ms = self.stages.iter().map(|s| s.duration_ms).sum();
+        self.slowest_stage = self.stages.iter().max_by_key(|s| s.duration_ms).map(|s| s.stage);
+    }
+
+    fn duration(&self, stage: LatencyStage) -> Option<u64> {
+        self.stages.iter().find(|s| s.stage == stage).map(|s| s.duration_ms)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StageStats {
+    pub stage: LatencyStage,
+    pub samples: usize,
+    pub mean_ms: u64,
+    pub p50_ms: u64,
+    pub p95_ms: u64,
+    pub max_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LatencyReport {
+    pub samples: usize,
+    pub stages: Vec<StageStats>,
+    /// Stage with the highest mean; the first place to look for a speed-up
+    pub slowest_stage: Option<LatencyStage>,
+    pub mean_total_ms: u64,
+    pub p95_total_ms: u64,
+    pub recent: Vec<LatencyBreakdown>,
+}
+
+#[derive(Debug, Default)]
+pub struct LatencyTracker {
+    samples: VecDeque<LatencyBreakdown>,
+}
+
+impl LatencyTracker {
+    pub fn record(&mut self, breakdown: LatencyBreakdown) {
+        self.samples.push_back(breakdown);
+        while self.samples.len() > MAX_SAMPLES {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Add the injection stage to an utterance recorded earlier
+    pub fn record_injection(&mut self, utterance_id: &str, injected_at_ms: u64) -> Option<LatencyBreakdown> {
+        let breakdown = self.samples.iter_mut().rev().find(|b| b.utterance_id == utterance_id)?;
+        breakdown.record_injection(injected_at_ms);
+        Some(breakdown.clone())
+    }
+
+    /// Statistics over the last `limit` utterances
+    pub fn report(&self, limit: usize) -> LatencyReport {
+        let recent: Vec<&LatencyBreakdown> = self.samples.iter().rev().take(limit).collect();
+
+        let stages: Vec<StageStats> = LatencyStage::ALL
+            .iter()
+            .filter_map(|&stage| {
+                let durations: Vec<u64> = recent.iter().filter_map(|b| b.duration(stage)).collect();
+                summarize(&durations).map(|(mean_ms, p50_ms, p95_ms, max_ms)| StageStats {
+                    stage,
+                    samples: durations.len(),
+                    mean_ms,
+                    p50_ms,
+                    p95_ms,
+                    max_ms,
+                })
+            })
+            .collect();
+        let totals: Vec<u64> = recent.iter().map(|b| b.total_ms).collect();
+        let (mean_total_ms, _, p95_total_ms, _) = summarize(&totals).unwrap_or_default();
+
+        LatencyReport {
+            samples: recent.len(),
+            slowest_stage: stages.iter().max_by_key(|s| s.mean_ms).map(|s| s.stage),
+            stages,
+            mean_total_ms,
+            p95_total_ms,
+            recent: recent.into_iter().take(20).cloned().collect(),
+        }
+    }
+}
+
+/// Mean, median, 95th percentile and maximum
+fn summarize(values: &[u64]) -> Option<(u64, u64, u64, u64)> {
+    if values.is_empty() {
+        return None;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_unstable();
+    let percentile = |p: usize| sorted[((sorted.len() - 1) * p) / 100];
+    let mean = sorted.iter().sum::<u64>() / sorted.len() as u64;
+    Some((mean, percentile(50), percentile(95), sorted[sorted.len() - 1]))
+}
+
+static LATENCY_TRACKER: std::sync::OnceLock<tokio::sync::Mutex<LatencyTracker>> = std::sync::OnceLock::new();
+
+pub fn get_latency_tracker() -> &'static tokio::sync::Mutex<LatencyTracker> {
+    LATENCY_TRACKER.get_or_init(|| tokio::sync::Mutex::new(LatencyTracker::default()))
+}
+
+pub fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
@@ -0,0 +1,252 @@
+//! Live typing for VoiceFlow Pro
+//! Types interim words into the focused app once they stop changing, and corrects them with backspaces when the final text differs
+
+use std::collections::VecDeque;
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use crate::errors::{AppError, ValidationError};
+use crate::undo_history::Compensation;
+use crate::unicode_text::grapheme_count;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LiveTypingSettings {
+    pub enabled: bool,
+    /// Interim results a word must survive unchanged before it is typed; higher means fewer corrections and more lag
+    pub stable_updates: usize,
+    /// Newest words never typed from an interim result, as recognizers revise the tail most
+    pub hold_back_words: usize,
+}
+
+impl Default for LiveTypingSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            stable_updates: 2,
+            hold_back_words: 1,
+        }
+    }
+}
+
+pub fn validate(settings: &LiveTypingSettings) -> Result<(), AppError> {
+    let invalid = |message: String| AppError::Validation(ValidationError::InvalidConfigValue(message));
+    if !(1..=10).contains(&settings.stable_updates) {
+        return Err(invalid(format!(
+            "Live typing stable updates must be 1-10, got {}",
+            settings.stable_updates
+        )));
+    }
+    if settings.hold_back_words > 5 {
+        return Err(invalid(format!(
+            "Live typing can hold back at most 5 words, got {}",
+            settings.hold_back_words
+        )));
+    }
+    Ok(())
+}
+
+/// Sent as "live-typing"; the frontend performs `steps` in order, as it does for undo
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LiveTypingUpdate {
+    pub steps: Vec<Compensation>,
+    /// What the target app holds of this utterance once the steps are done
+    pub typed: String,
+    /// Typed words had to be taken back
+    pub correction: bool,
+    /// The utterance is finished and typing for it is over
+    pub is_final: bool,
+    /// Corrections issued for this utterance so far
+    pub corrections: usize,
+}
+
+#[derive(Debug, Default)]
+struct LiveTyper {
+    /// Text of the current utterance already typed into the app
+    typed: String,
+    recent: VecDeque<Vec<String>>,
+    corrections: usize,
+}
+
+impl LiveTyper {
+    /// Words every one of the last `stable_updates` interim results agrees on, minus the held-back tail
+    fn stable_prefix(&self, settings: &LiveTypingSettings) -> String {
+        let Some(newest) = self.recent.back() else {
+            return String::new();
+        };
+        let mut agreed = newest.len().saturating_sub(settings.hold_back_words);
+        for words in self.recent.iter() {
+            agreed = agreed.min(words.iter().zip(newest).take_while(|(a, b)| a == b).count());
+        }
+        newest[..agreed].join(" ")
+    }
+
+    /// Steps that turn what was typed into `target`, keeping the longest shared prefix
+    fn steps_to(&mut self, target: &str) -> (Vec<Compensation>, bool) {
+        let shared = self
+            .typed
+            .char_indices()
+            .zip(target.chars())
+            .take_while(|((_, a), b)| a == b)
+            .last()
+            .map_or(0, |((index, a), _)| index + a.len_utf8());
+        // A word changed midway is retyped whole, as a person would
+        let shared = if shared < self.typed.len() {
+            self.typed[..shared].rfind(' ').map_or(0, |space| space + 1)
+        } else {
+            shared
+        };
+
+        let mut steps = Vec::new();
+        let removed = grapheme_count(&self.typed[shared..]);
+        if removed > 0 {
+            steps.push(Compensation::Backspace { count: removed });
+        }
+        if target.len() > shared {
+            steps.push(Compensation::TypeText {
+                text: target[shared..].to_string(),
+            });
+        }
+        self.typed = target.to_string();
+        (steps, removed > 0)
+    }
+}
+
+fn typer() -> &'static Mutex<Option<LiveTyper>> {
+    static TYPER: OnceLock<Mutex<Option<LiveTyper>>> = OnceLock::new();
+    TYPER.get_or_init(|| Mutex::new(None))
+}
+
+/// Take an interim transcript; returns what to type when more words have become stable
+pub async fn interim(settings: &LiveTypingSettings, transcript: &str) -> Option<LiveTypingUpdate> {
+    let mut typer = typer().lock().await;
+    let typer = typer.get_or_insert_with(LiveTyper::default);
+    typer
+        .recent
+        .push_back(transcript.split_whitespace().map(str::to_string).collect());
+    while typer.recent.len() > settings.stable_updates {
+        typer.recent.pop_front();
+    }
+    if typer.recent.len() < settings.stable_updates {
+        return None;
+    }
+
+    let stable = typer.stable_prefix(settings);
+    // Only grow the typed text from interims; stable words the recognizer later drops wait for the final text
+    if stable.len() <= typer.typed.len() && typer.typed.starts_with(&stable) {
+        return None;
+    }
+    let (steps, correction) = typer.steps_to(&stable);
+    if correction {
+        typer.corrections += 1;
+    }
+    Some(LiveTypingUpdate {
+        steps,
+        typed: typer.typed.clone(),
+        correction,
+        is_final: false,
+        corrections: typer.corrections,
+    })
+}
+
+/// Bring the typed interim words in line with the utterance's final text and end the utterance.
+/// `None` when nothing was typed live, so the text is delivered the usual way.
+pub async fn finalize(text: &str) -> Option<LiveTypingUpdate> {
+    let mut typer = typer().lock().await.take()?;
+    if typer.typed.is_empty() {
+        return None;
+    }
+    let (steps, correction) = typer.steps_to(text.trim());
+    if correction {
+        typer.corrections += 1;
+        log::debug!("Live typing corrected the utterance ({} correction(s))", typer.corrections);
+    }
+    Some(LiveTypingUpdate {
+        steps,
+        typed: typer.typed,
+        correction,
+        is_final: true,
+        corrections: typer.corrections,
+    })
+}
+
+/// Erase words typed for an utterance that ends up not being typed, e.g. a voice command or a held result
+pub async fn retract() -> Option<LiveTypingUpdate> {
+    finalize("").await
+}
+
+/// Start the next utterance without touching what was typed
+pub async fn reset() {
+    *typer().lock().await = None;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn typer_with(updates: &[&str]) -> LiveTyper {
+        LiveTyper {
+            recent: updates
+                .iter()
+                .map(|update| update.split_whitespace().map(str::to_string).collect())
+                .collect(),
+            ..LiveTyper::default()
+        }
+    }
+
+    #[test]
+    fn stable_prefix_keeps_words_every_update_agrees_on() {
+        let settings = LiveTypingSettings::default();
+        let typer = typer_with(&["send the", "send the report to", "send the report two"]);
+        assert_eq!(typer.stable_prefix(&settings), "send the");
+        assert_eq!(LiveTyper::default().stable_prefix(&settings), "");
+    }
+
+    #[test]
+    fn stable_prefix_holds_back_the_newest_words() {
+        let typer = typer_with(&["send the report to", "send the report to"]);
+        let settings = LiveTypingSettings {
+            hold_back_words: 2,
+            ..LiveTypingSettings::default()
+        };
+        assert_eq!(typer.stable_prefix(&settings), "send the");
+        let settings = LiveTypingSettings {
+            hold_back_words: 0,
+            ..LiveTypingSettings::default()
+        };
+        assert_eq!(typer.stable_prefix(&settings), "send the report to");
+    }
+
+    #[test]
+    fn steps_to_only_types_what_extends_the_typed_text() {
+        let mut typer = LiveTyper {
+            typed: "Send the".to_string(),
+            ..LiveTyper::default()
+        };
+        let (steps, correction) = typer.steps_to("Send the report.");
+        assert!(!correction);
+        assert_eq!(steps, vec![Compensation::TypeText { text: " report.".to_string() }]);
+        assert_eq!(typer.typed, "Send the report.");
+    }
+
+    #[test]
+    fn steps_to_retypes_a_changed_word_whole() {
+        let mut typer = LiveTyper {
+            typed: "meet at the cafe".to_string(),
+            ..LiveTyper::default()
+        };
+        let (steps, correction) = typer.steps_to("meet at the café today");
+        assert!(correction);
+        assert_eq!(
+            steps,
+            vec![
+                Compensation::Backspace { count: 4 },
+                Compensation::TypeText { text: "café today".to_string() },
+            ]
+        );
+        let (steps, _) = typer.steps_to("");
+        assert_eq!(steps, vec![Compensation::Backspace { count: 22 }]);
+    }
+}
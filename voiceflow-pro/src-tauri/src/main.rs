@@ -5,31 +5,128 @@ use std::collections::HashMap;
 use std::sync::Arc;
 use tauri::{Manager, State, Window, AppHandle, WindowEvent, CustomMenuItem, Menu, MenuItem, Submenu, SystemTray, SystemTrayEvent, SystemTrayMenu, SystemTrayMenuItem};
 use serde::{Deserialize, Serialize};
-use tokio::sync::{Mutex, mpsc};
+use tokio::sync::Mutex;
 use uuid::Uuid;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
 
 // Import new security and error handling modules
 mod errors;
 mod validation;
 mod memory;
 mod error_boundary;
+mod system_activity;
+mod storage;
+mod system_checks;
+mod data_management;
+mod encryption;
+mod profiles;
+mod jobs;
+mod job_store;
+mod analytics;
+mod history;
+mod focus;
+mod idle;
+mod latency;
+mod event_channel;
+mod read_aloud;
+mod audio_ducking;
+mod barge_in;
+mod assistant;
+mod intent_rules;
+mod form_filling;
+mod refinement;
+mod undo_history;
+mod domain_packs;
+mod feedback;
+mod plugins;
+mod pipeline;
+mod preview;
+mod audit;
+mod startup;
+mod session_recording;
+mod crash_reports;
+mod updater;
+mod network;
+mod unicode_text;
+mod caret;
+mod platform;
+mod injection_safety;
+mod bluetooth_audio;
+mod mic_state;
+mod power;
+mod settings_store;
+mod api;
+mod command_limits;
+mod auto_submit;
+mod live_typing;
+mod cost_estimate;
+mod session_stats;
+mod screen_ocr;
+#[cfg(test)]
+mod test_support;
 
 // Import integration modules
 mod integrations {
     pub mod voice_recognition;
     pub mod ai_text_processor;
     pub mod ai_ml_api;
+    pub mod utterance_insights;
+    pub mod text_chunker;
+    pub mod content_filter;
+    pub mod language_registry;
+    pub mod voice_calibration;
+    pub mod destinations;
+    pub mod stream_captions;
+    pub mod model_comparison;
+    pub mod voice_correction;
+    pub mod model_manager;
+    pub mod context;
+    pub mod speech_stream;
+    pub mod pronunciation;
+    pub mod voice_profiles;
+    pub mod code_dictation;
+    pub mod snippets;
+    pub mod keyword_boost;
+    pub mod alternatives;
+    pub mod mock_provider;
+    pub mod audio_upload;
+    pub mod traffic;
+    pub mod caption_filter;
+    pub mod voice_selection;
+    pub mod result_cache;
+    pub mod token_budget;
+    pub mod topic_tracker;
+    pub mod emotion_tracking;
+    pub mod translation_formality;
+    pub mod audio_capture;
+    pub mod python_bridge;
+    pub mod idempotency;
+    pub mod long_audio;
+    pub mod multi_mic;
+    pub mod echo_cancellation;
+    pub mod hypothesis_merge;
+    pub mod stt_providers;
+    pub mod provider_failover;
+    pub mod length_limit;
+    pub mod preservation;
+    pub mod bilingual;
+    pub mod transliteration;
+    pub mod model_registry;
     pub use ai_ml_api::*;
 }
 
 use errors::{AppError, Result, VoiceError, TextProcessingError, ValidationError};
-use validation::{validate_text, validate_language_code, validate_hotkey, validate_config_value, validate_numeric_value};
+use validation::{
+    validate_config_value, validate_hotkey, validate_language_code, validate_model_id, validate_numeric_value, validate_text,
+    validate_url, Theme,
+};
 use memory::{get_resource_manager, start_cleanup_task, ResourceManager};
 use error_boundary::{ErrorBoundary, ErrorBoundaryConfig, get_error_boundary_registry, start_error_monitoring_task, with_error_boundary, CircuitBreakerState};
 
 // Re-export integration types for easy access
 use integrations::voice_recognition::{
-    VoiceRecognitionEngine, VoiceRecognitionConfig, VoiceEvent, SpeechRecognitionResult,
+    VoiceEngineHandle, VoiceRecognitionConfig, VoiceEvent, SpeechRecognitionResult,
+    spawn_voice_engine, restart_reason, AutoPauseConfig, EngineState, PauseReason, VoiceEngineStatus,
     get_supported_languages, is_language_supported, Language,
 };
 use integrations::ai_text_processor::{
@@ -37,19 +134,47 @@ use integrations::ai_text_processor::{
     ProcessingContext, ToneType, ProcessingEvent, get_default_config_for_context,
 };
 
-use self::integrations::ai_text_processor::ProcessingOptions;
+use integrations::utterance_insights::{classify_utterance, UtteranceInsight, UtteranceInsightsConfig};
+use integrations::content_filter::{apply_content_filter, ContentFilterResult, ContentFilterSettings, OutputTarget};
+use integrations::language_registry::{get_language_registry, LanguageCapability, LanguageStatus};
+use integrations::model_registry::ModelModality;
+use integrations::model_manager::{InstalledModel, ModelInventory, ModelSettings};
+use integrations::context::EnhancedContext;
+use integrations::{IntentClassification, UserIntent};
+use integrations::speech_stream::{SpeechStreamAction, SpeechStreamStatus};
+use integrations::pronunciation::{PhoneticAlphabet, PronunciationEntry, PronunciationPreview, PronunciationSettings};
+use integrations::idempotency::{self, IdempotencyScope};
+use integrations::voice_profiles::{resolve_voice, CustomVoiceProfile, VoiceCatalog, VoiceProfileSettings};
+use integrations::voice_selection::{language_key, voice_for_language, VoiceChoice};
+use integrations::keyword_boost::{recognition_hints, KeywordBoostSettings};
+use integrations::alternatives::{AlternativePreferences, AlternativeVersion};
+use integrations::snippets::{parse_insert_command, Snippet, SnippetExpansion, SnippetSettings};
+use integrations::code_dictation::{format_code, validate_syntax, CodeDictationSettings, CodeFormatResult, CodeLanguage};
+use integrations::voice_calibration::{
+    begin_calibration, fail_calibration, load_voice_profile, run_calibration, CalibrationPhase, CalibrationStatus,
+    DEFAULT_CALIBRATION_SCRIPT, reset_calibration_status,
+};
+use integrations::destinations::{
+    available_destinations, parse_destination_command, DestinationInfo, DestinationMetadata, DestinationReceipt,
+    DestinationSettings,
+};
+use integrations::voice_correction::{apply_correction, parse_correction_command, CorrectionSettings};
+use integrations::stream_captions::{get_caption_streamer, CaptionStreamStatus, StreamingSettings};
+use profiles::{get_profile_manager, UserProfileSummary};
 
 // Application state with integrated engines and security features
 #[derive(Debug, Clone)]
 pub struct AppState {
-    pub voice_engine: Arc<Mutex<Option<VoiceRecognitionEngine>>>,
+    pub voice_engine: Arc<Mutex<Option<VoiceEngineHandle>>>,
     pub text_processor: Arc<Mutex<Option<AITextProcessor>>>,
     pub ai_ml_gateway: Arc<Mutex<Option<AIMLAPIGateway>>>,
-    pub settings: Arc<Mutex<Settings>>,
+    pub settings: Arc<settings_store::SettingsStore>,
     pub shortcuts: Arc<Mutex<HashMap<String, String>>>,
-    pub event_handlers: Arc<Mutex<Vec<tokio::sync::mpsc::UnboundedReceiver<VoiceEvent>>>>,
+    pub event_handlers: Arc<Mutex<Vec<event_channel::EventReceiver<VoiceEvent>>>>,
     pub resource_manager: Arc<Mutex<ResourceManager>>,
     pub error_boundaries: Arc<error_boundary::ErrorBoundaryRegistry>,
+    /// Most recent processed dictation, the "this" in "send this as an email draft"
+    pub last_output: Arc<Mutex<Option<String>>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -63,6 +188,132 @@ pub struct Settings {
     pub voice_recognition: VoiceRecognitionSettings,
     pub text_processing: TextProcessingSettings,
     pub ai_ml_settings: AIMLSettings,
+    #[serde(default)]
+    pub content_filters: ContentFilterSettings,
+    /// Names and terms this user dictates often
+    #[serde(default)]
+    pub vocabulary: Vec<String>,
+    #[serde(default)]
+    pub jobs: jobs::JobSettings,
+    #[serde(default)]
+    pub destinations: DestinationSettings,
+    #[serde(default)]
+    pub streaming: StreamingSettings,
+    #[serde(default)]
+    pub analytics: analytics::AnalyticsSettings,
+    #[serde(default)]
+    pub corrections: CorrectionSettings,
+    #[serde(default)]
+    pub focus: focus::FocusSettings,
+    #[serde(default)]
+    pub resources: idle::ResourceSettings,
+    #[serde(default)]
+    pub models: ModelSettings,
+    #[serde(default)]
+    pub read_aloud: read_aloud::ReadAloudSettings,
+    /// TTS lexicons of custom pronunciations, per language
+    #[serde(default)]
+    pub pronunciation: PronunciationSettings,
+    #[serde(default)]
+    pub voices: VoiceProfileSettings,
+    #[serde(default)]
+    pub ducking: audio_ducking::DuckingSettings,
+    #[serde(default)]
+    pub barge_in: barge_in::BargeInSettings,
+    #[serde(default)]
+    pub assistant: assistant::AssistantSettings,
+    /// Actions run when context processing detects particular intents
+    #[serde(default)]
+    pub intent_rules: intent_rules::IntentRuleSettings,
+    #[serde(default)]
+    pub code_dictation: CodeDictationSettings,
+    /// Text templates inserted by saying "insert <name>"
+    #[serde(default)]
+    pub snippets: SnippetSettings,
+    /// Project keywords boosted while their profile is active, kept apart from `vocabulary`
+    #[serde(default)]
+    pub keyword_boost: KeywordBoostSettings,
+    /// Installed vertical packs (medical, legal, ...) and the one in use
+    #[serde(default)]
+    pub domain_packs: domain_packs::DomainPackSettings,
+    /// Alternative styles the user has picked, learned by `pick_alternative`
+    #[serde(default)]
+    pub alternatives: AlternativePreferences,
+    /// Result ratings and the tone/model routing learned from them
+    #[serde(default)]
+    pub feedback: feedback::FeedbackSettings,
+    /// Third-party plugins enabled for this profile and the permissions granted to them
+    #[serde(default)]
+    pub plugins: plugins::PluginSettings,
+    /// Ordered text stages dictation runs through
+    #[serde(default)]
+    pub pipeline: pipeline::PipelineSettings,
+    /// Keep dictation word for word, skipping the stages that rewrite it
+    #[serde(default)]
+    pub verbatim: pipeline::VerbatimSettings,
+    /// When dictation is held for approval instead of being typed straight away
+    #[serde(default)]
+    pub preview: preview::PreviewSettings,
+    /// Local log of outbound AI requests, off unless the user turns it on
+    #[serde(default)]
+    pub audit: audit::AuditSettings,
+    /// Whether crash reports are sent anywhere; they are always kept locally
+    #[serde(default)]
+    pub crash_reports: crash_reports::CrashReportSettings,
+    /// Release channel and how often to look for new versions
+    #[serde(default)]
+    pub updates: updater::UpdateSettings,
+    /// Proxy and extra root certificates applied to every outbound HTTP request
+    #[serde(default)]
+    pub network: network::NetworkSettings,
+    /// Whether enhancement, translation and synthesis results are kept on disk, and how much of it they may use
+    #[serde(default)]
+    pub result_cache: integrations::result_cache::ResultCacheSettings,
+    /// Formal or informal address per target language, for languages where translation has to pick one
+    #[serde(default)]
+    pub formality: integrations::translation_formality::FormalitySettings,
+    /// Whether the dictation overlay follows the text caret of the focused app
+    #[serde(default)]
+    pub overlay: caret::OverlaySettings,
+    /// What is held back while macOS Secure Input is on
+    #[serde(default)]
+    pub injection_safety: injection_safety::InjectionSafetySettings,
+    /// Input device, capture mode and how long to wait for a device another app holds
+    #[serde(default)]
+    pub audio_capture: integrations::audio_capture::AudioCaptureSettings,
+    /// Warnings about a Bluetooth headset in hands-free mode, and whether to dictate with the built-in mic instead
+    #[serde(default)]
+    pub bluetooth_audio: bluetooth_audio::BluetoothAudioSettings,
+    /// Whether listening pauses while the microphone is muted and picks up again once it is not
+    #[serde(default)]
+    pub mic_mute: mic_state::MicMuteSettings,
+    /// What listening gives up on battery, and the battery level at which it gives up more
+    #[serde(default)]
+    pub power: power::PowerSettings,
+    /// How often the frontend may call each category of command
+    #[serde(default)]
+    pub command_limits: command_limits::CommandLimitSettings,
+    /// How recordings too long for a single upload are split and transcribed
+    #[serde(default)]
+    pub long_audio: integrations::long_audio::LongAudioSettings,
+    /// What happens when the speaker falls silent mid-dictation
+    #[serde(default)]
+    pub auto_submit: auto_submit::AutoSubmitSettings,
+    /// Type words while the user is still speaking instead of the whole utterance at the end
+    #[serde(default)]
+    pub live_typing: live_typing::LiveTypingSettings,
+    /// Which speech-to-text service transcribes recordings, per language, and its credentials
+    #[serde(default)]
+    pub speech_to_text: integrations::stt_providers::SttSettings,
+    /// When long transcriptions, document runs and speech batches need confirming before they spend money
+    #[serde(default)]
+    pub cost_guard: cost_estimate::CostGuardSettings,
+    /// Running word count, reading time and pace sent to the UI while dictating
+    #[serde(default)]
+    pub session_stats: session_stats::SessionStatsSettings,
+    /// Whether on-screen text may be captured and read as context for instructions like "summarize this"
+    #[serde(default)]
+    pub screen_ocr: screen_ocr::ScreenOcrSettings,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -73,6 +324,16 @@ pub struct VoiceRecognitionSettings {
     pub confidence_threshold: f32,
     pub noise_reduction: bool,
     pub privacy_mode: bool,
+    #[serde(default)]
+    pub utterance_insights: UtteranceInsightsConfig,
+    #[serde(default)]
+    pub auto_pause: AutoPauseConfig,
+    /// Opt-in emotion estimate per utterance, kept as the session's emotional state
+    #[serde(default)]
+    pub emotion_tracking: integrations::emotion_tracking::EmotionTrackingSettings,
+    /// How the `max_alternatives` hypotheses are re-ranked and combined
+    #[serde(default)]
+    pub hypothesis_merge: integrations::hypothesis_merge::HypothesisMergeSettings,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -99,6 +360,18 @@ pub struct AIMLSettings {
     pub voice_model: String,
     pub translation_model: String,
     pub context_model: String,
+    /// Per-service health checks; only services set to full checks spend tokens
+    #[serde(default)]
+    pub health_checks: integrations::ai_ml_api::HealthCheckSettings,
+    /// Canned local responses instead of API calls; VOICEFLOW_MOCK=1 turns this on too
+    #[serde(default)]
+    pub mock: integrations::mock_provider::MockSettings,
+    /// Opus compression of PCM audio sent for cloud transcription
+    #[serde(default)]
+    pub upload_compression: integrations::audio_upload::UploadCompressionSettings,
+    /// Fallback providers and when traffic moves to them
+    #[serde(default)]
+    pub failover: integrations::provider_failover::ProviderFailoverSettings,
 }
 
 impl Default for Settings {
@@ -117,6 +390,10 @@ impl Default for Settings {
                 confidence_threshold: 0.7,
                 noise_reduction: true,
                 privacy_mode: false,
+                utterance_insights: UtteranceInsightsConfig::default(),
+                auto_pause: AutoPauseConfig::default(),
+                emotion_tracking: integrations::emotion_tracking::EmotionTrackingSettings::default(),
+                hypothesis_merge: integrations::hypothesis_merge::HypothesisMergeSettings::default(),
             },
             text_processing: TextProcessingSettings {
                 context: "email".to_string(),
@@ -139,7 +416,58 @@ impl Default for Settings {
                 voice_model: "gpt-4o-mini-tts".to_string(),
                 translation_model: "claude-3-5-haiku".to_string(),
                 context_model: "gpt-5-pro".to_string(),
+                health_checks: integrations::ai_ml_api::HealthCheckSettings::default(),
+                mock: integrations::mock_provider::MockSettings::default(),
+                upload_compression: integrations::audio_upload::UploadCompressionSettings::default(),
+                failover: integrations::provider_failover::ProviderFailoverSettings::default(),
             },
+            content_filters: ContentFilterSettings::default(),
+            vocabulary: Vec::new(),
+            jobs: jobs::JobSettings::default(),
+            destinations: DestinationSettings::default(),
+            streaming: StreamingSettings::default(),
+            analytics: analytics::AnalyticsSettings::default(),
+            corrections: CorrectionSettings::default(),
+            focus: focus::FocusSettings::default(),
+            resources: idle::ResourceSettings::default(),
+            models: ModelSettings::default(),
+            read_aloud: read_aloud::ReadAloudSettings::default(),
+            pronunciation: PronunciationSettings::default(),
+            voices: VoiceProfileSettings::default(),
+            ducking: audio_ducking::DuckingSettings::default(),
+            barge_in: barge_in::BargeInSettings::default(),
+            assistant: assistant::AssistantSettings::default(),
+            intent_rules: intent_rules::IntentRuleSettings::default(),
+            code_dictation: CodeDictationSettings::default(),
+            snippets: SnippetSettings::default(),
+            keyword_boost: KeywordBoostSettings::default(),
+            domain_packs: domain_packs::DomainPackSettings::default(),
+            alternatives: AlternativePreferences::default(),
+            feedback: feedback::FeedbackSettings::default(),
+            plugins: plugins::PluginSettings::default(),
+            pipeline: pipeline::PipelineSettings::default(),
+            verbatim: pipeline::VerbatimSettings::default(),
+            preview: preview::PreviewSettings::default(),
+            audit: audit::AuditSettings::default(),
+            crash_reports: crash_reports::CrashReportSettings::default(),
+            updates: updater::UpdateSettings::default(),
+            network: network::NetworkSettings::default(),
+            result_cache: integrations::result_cache::ResultCacheSettings::default(),
+            formality: integrations::translation_formality::FormalitySettings::default(),
+            overlay: caret::OverlaySettings::default(),
+            injection_safety: injection_safety::InjectionSafetySettings::default(),
+            audio_capture: integrations::audio_capture::AudioCaptureSettings::default(),
+            bluetooth_audio: bluetooth_audio::BluetoothAudioSettings::default(),
+            mic_mute: mic_state::MicMuteSettings::default(),
+            power: power::PowerSettings::default(),
+            command_limits: command_limits::CommandLimitSettings::default(),
+            long_audio: integrations::long_audio::LongAudioSettings::default(),
+            auto_submit: auto_submit::AutoSubmitSettings::default(),
+            live_typing: live_typing::LiveTypingSettings::default(),
+            speech_to_text: integrations::stt_providers::SttSettings::default(),
+            cost_guard: cost_estimate::CostGuardSettings::default(),
+            session_stats: session_stats::SessionStatsSettings::default(),
+            screen_ocr: screen_ocr::ScreenOcrSettings::default(),
         }
     }
 }
@@ -147,12 +475,22 @@ impl Default for Settings {
 // Enhanced voice engine with integrated processing
 #[derive(Debug, Clone)]
 pub struct EnhancedVoiceEngine {
-    pub voice_engine: Arc<Mutex<Option<VoiceRecognitionEngine>>>,
+    pub voice_engine: Arc<Mutex<Option<VoiceEngineHandle>>>,
     pub text_processor: Arc<Mutex<Option<AITextProcessor>>>,
     pub current_session: Arc<Mutex<Option<String>>>,
     pub window: Window,
 }
 
+/// Start backend services in dependency order, deferring rarely used ones to first use; progress arrives as "init-progress"
+#[tauri::command]
+async fn initialize_all(
+    options: Option<startup::InitOptions>,
+    state: State<'_, AppState>,
+    window: Window,
+) -> Result<startup::InitReport, AppError> {
+    startup::initialize_all(&state, &window, options.unwrap_or_default()).await
+}
+
 // Tauri Commands for voice recognition with proper error handling and validation
 #[tauri::command]
 async fn initialize_voice_recognition(
@@ -163,539 +501,3983 @@ async fn initialize_voice_recognition(
     let boundary = registry.get("voice_recognition").await
         .unwrap_or_else(|| Arc::new(ErrorBoundary::new("voice_recognition".to_string(), None)));
 
-    with_error_boundary!(boundary, async {
-        let mut voice_engine_state = state.voice_engine.lock().await;
-        
-        // Check if already initialized
-        if voice_engine_state.is_some() {
-            return Err(AppError::VoiceRecognition(VoiceError::AlreadyInitialized));
-        }
+    with_error_boundary!(boundary, async { start_voice_recognition(&state, &window).await }).await
+}
 
-        let config = VoiceRecognitionConfig {
-            language: "en-US".to_string(),
-            continuous: true,
-            interim_results: true,
-            max_alternatives: 3,
-            confidence_threshold: 0.7,
-            noise_reduction: true,
-            privacy_mode: false,
-        };
+/// Spawn the recognition engine and its event loop; used by the command and the startup orchestrator
+async fn start_voice_recognition(state: &AppState, window: &Window) -> Result<(), AppError> {
+    let mut voice_engine_state = state.voice_engine.lock().await;
+    
+    // Check if already initialized
+    if voice_engine_state.is_some() {
+        return Err(AppError::VoiceRecognition(VoiceError::AlreadyInitialized));
+    }
 
-        let (event_sender, event_receiver) = mpsc::unbounded_channel();
-        
-        // Store event receiver for the app state
-        {
-            let mut handlers = state.event_handlers.lock().await;
-            handlers.push(event_receiver);
-        }
+    let mut config = VoiceRecognitionConfig {
+        language: "en-US".to_string(),
+        continuous: true,
+        interim_results: true,
+        max_alternatives: 3,
+        confidence_threshold: 0.7,
+        noise_reduction: true,
+        privacy_mode: false,
+        vad_threshold: None,
+        input_gain: None,
+        phrase_hints: {
+            let settings = state.settings.snapshot();
+            recognition_hints(&settings.vocabulary, &settings.keyword_boost)
+        },
+        input_device: None,
+    };
+    if let Some(profile) = load_voice_profile() {
+        profile.apply(&mut config);
+    }
+
+    let (event_sender, event_receiver) = event_channel::event_channel("voice", integrations::voice_recognition::VOICE_EVENT_CAPACITY);
+    
+    // Store event receiver for the app state
+    {
+        let mut handlers = state.event_handlers.lock().await;
+        handlers.push(event_receiver);
+    }
 
-        let engine = VoiceRecognitionEngine::new(config, event_sender);
-        *voice_engine_state = Some(engine);
+    // The engine lives on its own task; state only ever holds a handle to it
+    *voice_engine_state = Some(spawn_voice_engine(config, event_sender));
 
-        // Start event handling loop with error boundary protection
-        let voice_engine_clone = state.voice_engine.clone();
-        let window_clone = window.clone();
-        tokio::spawn(async move {
-            if let Err(e) = handle_voice_events(voice_engine_clone, window_clone).await {
-                tracing::error!("Voice event handling error: {}", e);
-            }
-        });
+    // Start event handling loop with error boundary protection
+    let voice_engine_clone = state.voice_engine.clone();
+    let window_clone = window.clone();
+    tokio::spawn(async move {
+        if let Err(e) = handle_voice_events(voice_engine_clone, window_clone).await {
+            tracing::error!("Voice event handling error: {}", e);
+        }
+    });
 
-        Ok(())
-    }).await
+    tokio::spawn(run_auto_pause_monitor(state.clone(), window.clone()));
+
+    Ok(())
 }
 
 #[tauri::command]
 async fn start_voice_listening(
+    from_hotkey: Option<bool>,
     state: State<'_, AppState>,
     window: Window,
 ) -> Result<(), String> {
-    let voice_engine_state = state.voice_engine.lock().await;
-    
-    if let Some(ref engine) = *voice_engine_state {
-        let mut engine_clone = engine.clone();
-        tokio::spawn(async move {
-            let _ = engine_clone.start_listening().await;
-        });
-        
-        let _ = window.emit("voice-status", "listening");
+    // Guard against an accidental hotkey press while presenting; the UI button always works
+    if from_hotkey.unwrap_or(false) {
+        let focus = focus::current_focus_state();
+        if focus.block_hotkey {
+            let _ = window.emit("hotkey-suppressed", &focus);
+            return Err(format!(
+                "Hotkey ignored: {}",
+                focus.reason.unwrap_or_else(|| "focus rules".to_string())
+            ));
+        }
     }
-    
+
+    if let Some(reason) = injection_safety::recording_blocked() {
+        let _ = window.emit("injection-safety-changed", &injection_safety::current_status());
+        return Err(format!("Listening held back: {}", reason));
+    }
+    if state.settings.snapshot().mic_mute.pause_on_mute && mic_state::current_mute_state().muted {
+        let _ = window.emit("mic-mute-changed", &mic_state::current_mute_state());
+        return Err("Listening held back: the microphone is muted".to_string());
+    }
+
+    idle::mark_activity();
+    let engine = voice_engine_handle(&state).await?;
+    let capture = bluetooth_audio::route_engine_input(&state, &engine).await;
+    let status = engine.start().await?;
+    undo_history::start_session(&status.session_id).await;
+    live_typing::reset().await;
+    integrations::topic_tracker::start_session(&status.session_id).await;
+    integrations::emotion_tracking::start_session(&status.session_id);
+    session_stats::start_session(&status.session_id, &state.settings.snapshot().session_stats).await;
+    auto_submit::watch_session(&state, &window.app_handle(), &status.session_id).await;
+    match integrations::multi_mic::start_session(&capture, &window.app_handle(), engine.watch_status()).await {
+        Ok(Some(microphones)) => {
+            integrations::multi_mic::feed_speech_stream();
+            let _ = window.emit("multi-mic-status", &microphones);
+        }
+        Ok(None) => {}
+        // Dictation goes on with the recognizer's own microphone
+        Err(e) => {
+            log::warn!("Could not open both microphones: {}", e);
+            let _ = window.emit("multi-mic-error", e.to_string());
+        }
+    }
+
+    let ducking = state.settings.snapshot().ducking.clone();
+    audio_ducking::engage_while_listening(engine.watch_status(), &ducking, &window.app_handle()).await;
+    let _ = window.emit("voice-status", status.state.as_str());
     Ok(())
 }
 
 #[tauri::command]
 async fn stop_voice_listening(
     state: State<'_, AppState>,
+    window: Window,
 ) -> Result<(), String> {
-    let voice_engine_state = state.voice_engine.lock().await;
-    
-    if let Some(ref engine) = *voice_engine_state {
-        let mut engine_clone = engine.clone();
-        tokio::spawn(async move {
-            let _ = engine_clone.stop_listening().await;
-        });
-    }
-    
+    let engine = voice_engine_handle(&state).await?;
+    let status = engine.stop().await?;
+
+    audio_ducking::release(audio_ducking::DuckReason::Dictation, &window.app_handle()).await;
+    let _ = window.emit("voice-status", status.state.as_str());
     Ok(())
 }
 
+/// Pause dictation without ending the session; the session id and conversation memory are kept
 #[tauri::command]
-async fn process_speech_with_ai(
-    transcript: String,
+async fn pause_voice_listening(
     state: State<'_, AppState>,
     window: Window,
-) -> Result<ProcessingResult, AppError> {
-    // Validate and sanitize input transcript
-    let validated_transcript = validate_text(&transcript, Some(1), Some(5000))
+) -> Result<VoiceEngineStatus, String> {
+    let engine = voice_engine_handle(&state).await?;
+    let status = engine.pause(PauseReason::Manual).await?;
+
+    audio_ducking::release(audio_ducking::DuckReason::Dictation, &window.app_handle()).await;
+    let _ = window.emit("voice-status", status.state.as_str());
+    Ok(status)
+}
+
+/// Resume a paused dictation session where it left off
+#[tauri::command]
+async fn resume_voice_listening(
+    state: State<'_, AppState>,
+    window: Window,
+) -> Result<VoiceEngineStatus, String> {
+    let engine = voice_engine_handle(&state).await?;
+    let status = engine.resume().await?;
+
+    let ducking = state.settings.snapshot().ducking.clone();
+    audio_ducking::engage_while_listening(engine.watch_status(), &ducking, &window.app_handle()).await;
+    let _ = window.emit("voice-status", status.state.as_str());
+    Ok(status)
+}
+
+/// Apply a new recognition config to the running engine between utterances
+#[tauri::command]
+async fn reconfigure_voice_recognition(
+    config: VoiceRecognitionConfig,
+    state: State<'_, AppState>,
+    window: Window,
+) -> Result<VoiceEngineStatus, AppError> {
+    let language = validate_language_code(&config.language)
+        .map_err(|e| AppError::Validation(e.to_string().into()))?;
+    if !is_language_supported(&language) {
+        return Err(AppError::VoiceRecognition(VoiceError::InvalidLanguage(language)));
+    }
+    validate_numeric_value(config.confidence_threshold, 0.0, 1.0, "confidence_threshold")
+        .map_err(|e| AppError::Validation(e.to_string().into()))?;
+    validate_numeric_value(config.max_alternatives, 1, 10, "max_alternatives")
         .map_err(|e| AppError::Validation(e.to_string().into()))?;
 
-    let registry = get_error_boundary_registry();
-    let boundary = registry.get("text_processor").await
-        .unwrap_or_else(|| Arc::new(ErrorBoundary::new("text_processor".to_string(), None)));
+    let engine = voice_engine_handle(&state).await
+        .map_err(|_| AppError::VoiceRecognition(VoiceError::NotInitialized))?;
 
-    with_error_boundary!(boundary, async {
-        let text_processor_state = state.text_processor.lock().await;
-        
-        // Send sanitized transcript to frontend
-        let _ = window.emit("speech-transcript", validated_transcript.clone());
-        
-        if let Some(ref processor) = *text_processor_state {
-            let request = ProcessingRequest {
-                id: Uuid::new_v4().to_string(),
-                text: validated_transcript,
-                context: ProcessingContext::Email, // Could be configurable
-                tone: ToneType::Professional,
-                options: ProcessingOptions {
-                    aggressiveness: 0.7,
-                    remove_fillers: true,
-                    preserve_formatting: false,
-                    smart_punctuation: true,
-                    auto_correct: true,
-                },
-                timestamp: std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs(),
-            };
+    // Report restart-only changes up front instead of waiting for the next utterance boundary
+    let current = engine.status();
+    if current.state != EngineState::Idle {
+        if let Some(reason) = restart_reason(&current.config, &config) {
+            return Err(AppError::VoiceRecognition(VoiceError::RestartRequired(reason)));
+        }
+    }
 
-            let result = processor.process_text(request).await
-                .map_err(|e| AppError::TextProcessing(e.to_string().into()))?;
-            
-            // Send processed result to frontend
-            let _ = window.emit("voice-response", result.processed_text.clone());
-            
-            Ok(result)
-        } else {
-            // Fallback if text processor not initialized
-            let fallback_result = ProcessingResult {
-                id: Uuid::new_v4().to_string(),
-                original_text: validated_transcript.clone(),
-                processed_text: validated_transcript,
-                changes_made: Vec::new(),
-                confidence_score: 1.0,
-                processing_time_ms: 0,
-                context_used: ProcessingContext::Email,
-                tone_applied: ToneType::Professional,
-                metadata: integrations::ai_text_processor::ProcessingMetadata {
-                    readability_before: 0.0,
-                    readability_after: 0.0,
-                    word_count_before: 0,
-                    word_count_after: 0,
-                    sentences_processed: 0,
-                    errors_corrected: 0,
-                    filler_words_removed: 0,
-                },
-            };
-            
-            let _ = window.emit("voice-response", fallback_result.processed_text.clone());
-            Ok(fallback_result)
+    let status = engine.reconfigure(config).await
+        .map_err(AppError::Internal)?;
+
+    let _ = window.emit("config-applied", &status.config);
+    Ok(status)
+}
+
+/// Begin a calibration run: ambient noise is measured first, then the user reads the script aloud.
+/// Progress is reported through "calibration-progress" events.
+#[tauri::command]
+async fn start_calibration(
+    script: Option<Vec<String>>,
+    state: State<'_, AppState>,
+    window: Window,
+) -> Result<CalibrationStatus, AppError> {
+    let script = match script {
+        Some(lines) if !lines.is_empty() => {
+            validate_numeric_value(lines.len(), 1, 20, "script lines")
+                .map_err(|e| AppError::Validation(e.to_string().into()))?;
+            lines
+                .iter()
+                .map(|line| validate_text(line, Some(1), Some(500)))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| AppError::Validation(e.to_string().into()))?
         }
-    }).await
+        _ => DEFAULT_CALIBRATION_SCRIPT.iter().map(|line| line.to_string()).collect(),
+    };
+
+    let engine = voice_engine_handle(&state).await
+        .map_err(|_| AppError::VoiceRecognition(VoiceError::NotInitialized))?;
+    let initial = begin_calibration(script)?;
+
+    // Calibration borrows the engine's event stream for the duration of the run
+    let Some(mut events) = state.event_handlers.lock().await.pop() else {
+        let reason = "Voice events are already in use".to_string();
+        fail_calibration(reason.clone());
+        return Err(AppError::Internal(reason));
+    };
+
+    let previous_state = engine.status().state;
+    // Calibration measures how confident the recognizer is, so nothing is dropped while it runs
+    let previous_config = engine.status().config;
+    let measuring = VoiceRecognitionConfig { confidence_threshold: 0.0, ..previous_config.clone() };
+    if let Err(e) = engine.reconfigure(measuring).await {
+        tracing::warn!("Could not lift the confidence threshold for calibration: {}", e);
+    }
+    let started = match previous_state {
+        EngineState::Listening => Ok(()),
+        EngineState::Paused => engine.resume().await.map(|_| ()),
+        _ => {
+            bluetooth_audio::route_engine_input(&state, &engine).await;
+            engine.start().await.map(|_| ())
+        }
+    };
+    if let Err(e) = started {
+        state.event_handlers.lock().await.push(events);
+        let _ = engine.reconfigure(previous_config).await;
+        fail_calibration(e.clone());
+        return Err(AppError::VoiceRecognition(VoiceError::AudioCaptureFailed(e)));
+    }
+
+    let app_state = state.inner().clone();
+    tokio::spawn(async move {
+        let status = run_calibration(&engine, &mut events, |status| {
+            let _ = window.emit("calibration-progress", status);
+        })
+        .await;
+        app_state.event_handlers.lock().await.push(events);
+
+        let restored = match previous_state {
+            EngineState::Listening => Ok(()),
+            EngineState::Paused => engine.pause(PauseReason::Manual).await.map(|_| ()),
+            _ => engine.stop().await.map(|_| ()),
+        };
+        if let Err(e) = restored {
+            tracing::warn!("Could not restore voice engine state after calibration: {}", e);
+        }
+
+        let mut config = previous_config;
+        let calibrated = match (&status.phase, &status.profile) {
+            (CalibrationPhase::Complete, Some(profile)) => {
+                profile.apply(&mut config);
+                Some(profile)
+            }
+            _ => None,
+        };
+        if let Err(e) = engine.reconfigure(config).await {
+            tracing::warn!("Could not restore the voice engine configuration after calibration: {}", e);
+        }
+
+        if let Some(profile) = calibrated {
+            app_state.settings.write().await.voice_recognition.confidence_threshold = profile.confidence_threshold;
+            tracing::info!(
+                "Voice calibration complete: noise floor {:.2}, gain {:.2}, VAD {:.2}",
+                profile.noise_floor, profile.recommended_gain, profile.vad_threshold
+            );
+        }
+    });
+
+    Ok(initial)
 }
 
-// AI ML API Commands with Error Handling and Validation
 #[tauri::command]
-async fn initialize_ai_ml_api(
+async fn get_calibration_status() -> Result<CalibrationStatus, AppError> {
+    Ok(integrations::voice_calibration::get_calibration_status())
+}
+
+#[tauri::command]
+async fn list_user_profiles() -> Result<Vec<UserProfileSummary>, AppError> {
+    Ok(get_profile_manager().lock().await.list())
+}
+
+/// Add a local user profile with its own settings, vocabulary, history and memories
+#[tauri::command]
+async fn create_user_profile(name: String, passphrase: Option<String>) -> Result<UserProfileSummary, AppError> {
+    let validated_name = validate_text(&name, Some(1), Some(64))
+        .map_err(|e| AppError::Validation(e.to_string().into()))?;
+
+    get_profile_manager()
+        .lock()
+        .await
+        .create(validated_name.trim(), passphrase.as_deref())
+}
+
+/// Save the current user's state, then load another profile's settings and memories
+#[tauri::command]
+async fn switch_user_profile(
+    id: String,
+    passphrase: Option<String>,
     state: State<'_, AppState>,
-) -> Result<(), AppError> {
-    let registry = get_error_boundary_registry();
-    let boundary = registry.get("ai_ml_api").await
-        .unwrap_or_else(|| Arc::new(ErrorBoundary::new("ai_ml_api".to_string(), None)));
+    window: Window,
+) -> Result<UserProfileSummary, AppError> {
+    let mut manager = get_profile_manager().lock().await;
+    let previous = manager.active_id().to_string();
+    if previous == id {
+        return manager
+            .list()
+            .into_iter()
+            .find(|p| p.active)
+            .ok_or_else(|| AppError::Internal("Active profile missing from index".to_string()));
+    }
+    manager.verify(&id, passphrase.as_deref())?;
 
-    with_error_boundary!(boundary, async {
-        let mut ai_ml_gateway_state = state.ai_ml_gateway.lock().await;
-        
-        // Check if already initialized
-        if ai_ml_gateway_state.is_some() {
-            return Err(AppError::Custom("AI ML API Gateway already initialized".to_string()));
-        }
-
-        let settings = state.settings.lock().await;
-        let config = AIMLGatewayConfig {
-            api_key: settings.ai_ml_settings.api_key.clone(),
-            base_url: settings.ai_ml_settings.base_url.clone(),
-            timeout_seconds: settings.ai_ml_settings.timeout_seconds,
-            max_retries: settings.ai_ml_settings.max_retries,
-            retry_delay_ms: 1000,
-            enable_fallback: settings.ai_ml_settings.enable_fallback,
-            cache_results: settings.ai_ml_settings.cache_results,
-            max_cache_size: 1000,
-            default_model: settings.ai_ml_settings.default_model.clone(),
-            text_model: settings.ai_ml_settings.text_model.clone(),
-            voice_model: settings.ai_ml_settings.voice_model.clone(),
-            translation_model: settings.ai_ml_settings.translation_model.clone(),
-            context_model: settings.ai_ml_settings.context_model.clone(),
+    // Nothing from the previous user may keep flowing while the switch happens
+    let engine = voice_engine_handle(&state).await.ok();
+    if let Some(engine) = &engine {
+        engine.stop().await.map_err(AppError::Internal)?;
+    }
+
+    let current_settings = state.settings.snapshot();
+    profiles::save_profile_settings(&previous, &current_settings)?;
+    let gateway = state.ai_ml_gateway.lock().await;
+    if let Some(gateway) = gateway.as_ref() {
+        profiles::save_profile_memory(&previous, &gateway.conversation_memory().await)?;
+    }
+
+    let summary = manager.activate(&id)?;
+
+    let mut settings = profiles::load_profile_settings(&id).unwrap_or_default();
+    settings.ai_ml_settings.api_key = current_settings.ai_ml_settings.api_key.clone();
+    settings.speech_to_text.carry_keys_from(&current_settings.speech_to_text);
+    get_caption_streamer().lock().await.configure(&settings.streaming);
+    audit::configure(&settings.audit);
+    command_limits::configure(&settings.command_limits);
+    let boost_keywords = settings.keyword_boost.active_keywords();
+    let domain_instructions = domain_packs::active_instructions(&settings.domain_packs);
+    let alternative_preferences = settings.alternatives.clone();
+    let plugin_settings = settings.plugins.clone();
+    *state.settings.write().await = settings;
+    if let Err(e) = plugins::load_installed(&plugin_settings).await {
+        log::warn!("Plugins not loaded for profile '{}': {}", id, e);
+    }
+
+    if let Some(gateway) = gateway.as_ref() {
+        match profiles::load_profile_memory(&id) {
+            Some(memory) => gateway.restore_conversation_memory(memory).await,
+            None => gateway.clear_conversation_memory().await,
+        }
+        // Cached results may contain the previous user's text
+        gateway.clear_caches().await;
+        gateway.set_preserved_terms(boost_keywords).await;
+        gateway.set_domain_instructions(domain_instructions).await;
+        gateway.set_alternative_preferences(alternative_preferences).await;
+    }
+    drop(gateway);
+
+    reset_calibration_status();
+    if let Some(engine) = &engine {
+        let mut config = engine.status().config;
+        config.vad_threshold = None;
+        config.input_gain = None;
+        config.phrase_hints = {
+            let settings = state.settings.snapshot();
+            recognition_hints(&settings.vocabulary, &settings.keyword_boost)
         };
+        if let Some(profile) = load_voice_profile() {
+            profile.apply(&mut config);
+        }
+        engine.reconfigure(config).await.map_err(AppError::Internal)?;
+    }
 
-        let gateway = AIMLAPIGateway::new(config)
-            .await
-            .map_err(|e| AppError::Custom(format!("Failed to initialize AI ML API: {}", e)))?;
-        
-        gateway.initialize()
-            .await
-            .map_err(|e| AppError::Custom(format!("Failed to initialize AI ML services: {}", e)))?;
+    tracing::info!("Switched user profile to '{}'", summary.name);
+    let _ = window.emit("profile-switched", &summary);
+    Ok(summary)
+}
 
-        *ai_ml_gateway_state = Some(gateway);
-        
-        tracing::info!("AI ML API Gateway initialized successfully");
-        Ok(())
-    }).await
+/// Background jobs, newest first
+#[tauri::command]
+async fn list_jobs(status: Option<jobs::JobStatus>) -> Result<Vec<jobs::Job>, AppError> {
+    Ok(jobs::get_job_scheduler().lock().await.list(status))
 }
 
 #[tauri::command]
-async fn process_enhanced_text(
-    text: String,
-    operations: Vec<TextOperation>,
-    source_language: Option<String>,
-    target_language: Option<String>,
-    context: EnhancedContext,
-    options: EnhancedProcessingOptions,
+async fn cancel_job(id: String, window: Window) -> Result<jobs::Job, AppError> {
+    let job = jobs::get_job_scheduler().lock().await.cancel(&id)?;
+    let _ = window.emit("job-updated", &job);
+    Ok(job)
+}
+
+/// Queue a failed or cancelled job again; chunks and transcripts it already produced are reused
+#[tauri::command]
+async fn resume_job(id: String, window: Window) -> Result<jobs::Job, AppError> {
+    let job = jobs::get_job_scheduler().lock().await.resume(&id)?;
+    let _ = window.emit("job-updated", &job);
+    Ok(job)
+}
+
+/// Drop a job from history together with its checkpoint and partial output
+#[tauri::command]
+async fn discard_job(id: String, window: Window) -> Result<jobs::Job, AppError> {
+    let job = jobs::get_job_scheduler().lock().await.discard(&id)?;
+    let _ = window.emit("job-discarded", &job);
+    Ok(job)
+}
+
+/// Enhance or translate a text document in the background; the result is written next to it unless `output_path` is given
+#[tauri::command]
+async fn queue_document_job(
+    input_path: String,
+    operation: LongTextOperation,
+    output_path: Option<String>,
+    confirmed: Option<bool>,
     state: State<'_, AppState>,
-) -> Result<AIMLResponse<EnhancedTextResult>, AppError> {
-    // Validate and sanitize input
-    let validated_text = validate_text(&text, Some(1), Some(10000))
+    window: Window,
+) -> Result<jobs::Job, AppError> {
+    let settings = state.settings.snapshot();
+    let estimate = cost_estimate::CostRequest::LongText {
+        text: None,
+        path: Some(input_path.clone()),
+        operation: operation.clone(),
+    };
+    cost_estimate::require_confirmation(estimate, &settings, confirmed.unwrap_or(false)).await?;
+    let max_attempts = settings.jobs.max_attempts;
+    let job = jobs::document_job(std::path::Path::new(&input_path), operation, output_path, max_attempts)?;
+    let job = jobs::get_job_scheduler().lock().await.enqueue(job);
+    let _ = window.emit("job-updated", &job);
+    Ok(job)
+}
+
+/// Poll system activity and pause/resume listening according to the auto-pause settings
+async fn run_auto_pause_monitor(state: AppState, window: Window) {
+    loop {
+        let config = state.settings.snapshot().voice_recognition.auto_pause.clone();
+        tokio::time::sleep(tokio::time::Duration::from_secs(config.poll_interval_secs.max(1))).await;
+
+        if !config.enabled {
+            continue;
+        }
+        let Ok(engine) = voice_engine_handle(&state).await else {
+            continue;
+        };
+
+        let status = engine.status();
+        let watching = match (&status.state, &status.pause_reason) {
+            (EngineState::Listening, _) => true,
+            // Lifted by the injection safety and microphone monitors once Secure Input or the mute is off
+            (EngineState::Paused, Some(PauseReason::SecureInput(_) | PauseReason::MicMuted)) => false,
+            (EngineState::Paused, Some(reason)) => reason.is_automatic() && config.auto_resume,
+            _ => false,
+        };
+        if !watching {
+            continue;
+        }
+
+        let call_apps = if config.on_call_active { config.call_applications.as_slice() } else { &[] };
+        let activity = system_activity::probe_system_activity(call_apps).await;
+
+        let reason = if config.on_screen_lock && activity.screen_locked {
+            Some(PauseReason::ScreenLocked)
+        } else if let Some(app) = activity.active_call_app {
+            Some(PauseReason::CallActive(app))
+        } else if config.on_system_idle
+            && activity.idle_seconds.map(|idle| idle >= config.idle_threshold_secs).unwrap_or(false)
+        {
+            Some(PauseReason::SystemIdle)
+        } else {
+            None
+        };
+
+        let outcome = match (&status.state, reason) {
+            (EngineState::Listening, Some(reason)) => engine.pause(reason).await,
+            (EngineState::Paused, None) => engine.resume().await,
+            _ => continue,
+        };
+
+        match outcome {
+            Ok(status) => {
+                let _ = window.emit("voice-status", status.state.as_str());
+                let _ = window.emit("voice-auto-pause", &status);
+            }
+            Err(e) => tracing::warn!("Auto-pause transition failed: {}", e),
+        }
+    }
+}
+
+/// Settings sections do not implement PartialEq, so they are compared in serialized form
+fn section_changed<T: Serialize>(before: &T, after: &T) -> bool {
+    serde_json::to_value(before).ok() != serde_json::to_value(after).ok()
+}
+
+/// Carry committed settings changes into the gateway and the voice engine as they happen
+async fn run_settings_listener(state: AppState, app: AppHandle) {
+    let mut changes = state.settings.subscribe();
+    let mut previous = state.settings.snapshot();
+    loop {
+        let settings = match changes.recv().await {
+            Ok(settings) => settings,
+            // Only the latest settings matter to a listener that fell behind
+            Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => state.settings.snapshot(),
+            Err(tokio::sync::broadcast::error::RecvError::Closed) => return,
+        };
+
+        if section_changed(&previous.ai_ml_settings, &settings.ai_ml_settings) {
+            if let Some(gateway) = state.ai_ml_gateway.lock().await.as_mut() {
+                gateway.update_config(gateway_config(&settings.ai_ml_settings)).await;
+                if previous.ai_ml_settings.timeout_seconds != settings.ai_ml_settings.timeout_seconds {
+                    if let Err(e) = gateway.reload_network().await {
+                        tracing::warn!("New AI request timeout not applied: {}", e);
+                    }
+                }
+            }
+        }
+
+        if section_changed(&previous.voice_recognition, &settings.voice_recognition) {
+            if let Ok(engine) = voice_engine_handle(&state).await {
+                let current = engine.status();
+                let recognition = &settings.voice_recognition;
+                let mut config = current.config.clone();
+                config.continuous = recognition.continuous;
+                config.interim_results = recognition.interim_results;
+                config.max_alternatives = recognition.max_alternatives;
+                config.confidence_threshold = recognition.confidence_threshold;
+                config.noise_reduction = recognition.noise_reduction;
+                config.privacy_mode = recognition.privacy_mode;
+                match restart_reason(&current.config, &config).filter(|_| current.state != EngineState::Idle) {
+                    Some(reason) => {
+                        let _ = app.emit_all("voice-restart-required", &reason);
+                    }
+                    None => match engine.reconfigure(config).await {
+                        Ok(status) => {
+                            let _ = app.emit_all("config-applied", &status.config);
+                        }
+                        Err(e) => tracing::warn!("Voice recognition settings not applied: {}", e),
+                    },
+                }
+            }
+        }
+
+        previous = settings;
+    }
+}
+
+/// Clone the engine handle out of state so the lock is not held while a command is in flight
+async fn voice_engine_handle(state: &AppState) -> Result<VoiceEngineHandle, String> {
+    state
+        .voice_engine
+        .lock()
+        .await
+        .clone()
+        .ok_or_else(|| "Voice recognition not initialized".to_string())
+}
+
+/// Process a dictated utterance and hand it to the frontend to type; with `preview`, or when the
+/// profile's preview settings apply, the result is held as "result-pending" until approved
+#[tauri::command]
+async fn process_speech_with_ai(
+    transcript: String,
+    audio: Option<history::SegmentAudio>,
+    timings: Option<latency::ClientTimings>,
+    preview: Option<bool>,
+    prosody: Option<integrations::emotion_tracking::ProsodyFeatures>,
+    speaker: Option<String>,
+    alternatives: Option<Vec<integrations::voice_recognition::Alternative>>,
+    state: State<'_, AppState>,
+    window: Window,
+) -> Result<ProcessingResult, AppError> {
+    process_utterance(&state, &window, transcript, audio, timings, preview, prosody, speaker, alternatives).await
+}
+
+/// The finalize-and-inject path of a dictated utterance, shared by `process_speech_with_ai` and auto-submit
+#[allow(clippy::too_many_arguments)]
+async fn process_utterance(
+    state: &AppState,
+    window: &Window,
+    transcript: String,
+    audio: Option<history::SegmentAudio>,
+    timings: Option<latency::ClientTimings>,
+    preview: Option<bool>,
+    prosody: Option<integrations::emotion_tracking::ProsodyFeatures>,
+    speaker: Option<String>,
+    alternatives: Option<Vec<integrations::voice_recognition::Alternative>>,
+) -> Result<ProcessingResult, AppError> {
+    // The segment is submitted now, so auto-submit must not submit it again
+    auto_submit::end_segment();
+    let started_ms = latency::now_ms();
+    let timings = timings.unwrap_or_default();
+    let transcript = match alternatives {
+        Some(alternatives) if alternatives.len() > 1 => merge_hypotheses(state, window, transcript, &alternatives).await,
+        _ => transcript,
+    };
+    // Validate and sanitize input transcript
+    let validated_transcript = validate_text(&transcript, Some(1), Some(5000))
         .map_err(|e| AppError::Validation(e.to_string().into()))?;
+    idle::mark_activity();
+    session_stats::observe_utterance(&timings, audio.as_ref()).await;
+    if session_recording::is_recording() {
+        session_recording::record(session_recording::SessionEvent::Utterance {
+            transcript: validated_transcript.clone(),
+            audio: audio.clone(),
+            preview,
+        });
+    }
 
     let registry = get_error_boundary_registry();
-    let boundary = registry.get("ai_ml_api").await
-        .unwrap_or_else(|| Arc::new(ErrorBoundary::new("ai_ml_api".to_string(), None)));
+    let boundary = registry.get("text_processor").await
+        .unwrap_or_else(|| Arc::new(ErrorBoundary::new("text_processor".to_string(), None)));
 
-    with_error_boundary!(boundary, async {
-        let ai_ml_gateway_state = state.ai_ml_gateway.lock().await;
-        
-        if let Some(ref gateway) = *ai_ml_gateway_state {
-            let request = EnhancedTextRequest {
-                id: Uuid::new_v4().to_string(),
-                text: validated_text,
-                operations,
-                source_language,
-                target_language,
-                context,
-                options,
-                timestamp: std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs(),
+    let outcome = with_error_boundary!(boundary, async {
+        // Send sanitized transcript to frontend
+        let _ = window.emit("speech-transcript", validated_transcript.clone());
+
+        // While a form is open, each utterance answers its current field instead of being typed
+        if form_filling::has_active_session().await {
+            let status = form_filling::answer(state, &window.app_handle(), None, &validated_transcript).await?;
+            let _ = window.emit("form-session", &status);
+            return Ok(unprocessed_result(validated_transcript, String::new()));
+        }
+
+        // In verbatim mode "make it shorter" is testimony, not an instruction; voice commands are not parsed
+        let voice_commands = !state.settings.snapshot().verbatim.enabled;
+
+        // Spoken hand-off of the previous dictation, e.g. "send this as an email draft"
+        let destination_settings = state.settings.snapshot().destinations.clone();
+        if let Some(destination_id) = parse_destination_command(&validated_transcript, &destination_settings)
+            .filter(|_| voice_commands)
+        {
+            let text = state.last_output.lock().await.clone().ok_or_else(|| {
+                AppError::Configuration("There is no dictated text to send yet".to_string())
+            })?;
+            let receipt = integrations::destinations::send_to_destination(
+                &destination_id,
+                &text,
+                &DestinationMetadata::default(),
+                &destination_settings,
+            )
+            .await?;
+            let _ = window.emit("destination-sent", &receipt);
+            // Nothing is typed for a command utterance
+            return Ok(unprocessed_result(validated_transcript, String::new()));
+        }
+
+        // "correction: <wrong> to <right>" edits the previous dictation instead of being typed
+        if let Some(command) = parse_correction_command(&validated_transcript).filter(|_| voice_commands) {
+            match apply_voice_correction(state, command).await {
+                Ok(outcome) => {
+                    let _ = window.emit("correction-applied", &outcome);
+                    return Ok(unprocessed_result(validated_transcript, String::new()));
+                }
+                // Nothing like the target was dictated, so the utterance is typed as it was said
+                Err(AppError::TextProcessing(TextProcessingError::CorrectionTargetNotFound(wrong))) => {
+                    tracing::debug!("No '{}' to correct; treating the utterance as dictation", wrong);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        // "add <term> to my vocabulary" changes the active profile's vocabulary instead of being typed
+        if let Some(command) = profiles::parse_vocabulary_command(&validated_transcript).filter(|_| voice_commands) {
+            update_vocabulary(state, &command).await?;
+            let _ = window.emit("vocabulary-updated", &command);
+            return Ok(unprocessed_result(validated_transcript, String::new()));
+        }
+
+        // "make it shorter", "more formal" revise the previous dictation instead of being typed;
+        // with nothing dictated yet there is nothing to revise, so the words are typed as said
+        let has_last_output = state.last_output.lock().await.is_some();
+        if let Some(instruction) = refinement::parse_refinement_command(&validated_transcript)
+            .filter(|_| voice_commands && has_last_output)
+        {
+            let outcome = refinement::refine_last_result(state, &instruction).await?;
+            let _ = window.emit("result-refined", &outcome);
+            return Ok(unprocessed_result(validated_transcript, String::new()));
+        }
+
+        // "summarize this", "translate this into German" work on the text on screen once screen OCR is turned on
+        let screen_ocr_enabled = state.settings.snapshot().screen_ocr.enabled;
+        if let Some(instruction) = screen_ocr::parse_screen_command(&validated_transcript)
+            .filter(|_| voice_commands && screen_ocr_enabled)
+        {
+            let outcome = screen_instruction(state, &instruction, None).await?;
+            let text = filter_output(state, window, &outcome.result, OutputTarget::Injection).await;
+            refinement::record(&validated_transcript, &text).await;
+            let mut result = unprocessed_result(validated_transcript, text.clone());
+            *state.last_output.lock().await = Some(text.clone());
+            attach_latency(window, &mut result, &timings, started_ms).await;
+            let _ = window.emit("screen-instruction", &outcome);
+            type_result(window, text).await;
+            return Ok(result);
+        }
+
+        // "insert <snippet>" types the expanded template in place of the utterance
+        let snippet = {
+            let settings = state.settings.snapshot();
+            parse_insert_command(&validated_transcript, &settings.snippets)
+                .filter(|_| voice_commands)
+                .cloned()
+        };
+        if let Some(snippet) = snippet {
+            let expansion = expand_snippet(state, &snippet).await?;
+            let text = filter_output(state, window, &expansion.text, OutputTarget::Injection).await;
+            refinement::record(&validated_transcript, &text).await;
+            let mut result = unprocessed_result(validated_transcript, text.clone());
+            *state.last_output.lock().await = Some(text.clone());
+            attach_latency(window, &mut result, &timings, started_ms).await;
+            let _ = window.emit("snippet-expanded", &expansion);
+            type_result(window, text).await;
+            return Ok(result);
+        }
+
+        // Keep the segment (and its audio, if sent) so it can be re-transcribed later
+        let (engine, language) = {
+            let settings = state.settings.snapshot();
+            (settings.voice_model.clone(), settings.language.clone())
+        };
+        get_caption_streamer().lock().await.publish(&validated_transcript, true, &language);
+        // Long dictations report when the subject moves on; the label is stored with the segment
+        let topic = integrations::topic_tracker::observe(&validated_transcript).await;
+        if let Some(change) = &topic.change {
+            let _ = window.emit("topic-changed", change);
+        }
+        // With two microphones the one heard most names the speaker, unless the frontend already knows
+        let speaker = speaker.or_else(integrations::multi_mic::take_speaker);
+        match history::get_transcript_history().lock().await.add_segment(
+            &validated_transcript,
+            &engine,
+            &language,
+            topic.topic.as_deref(),
+            speaker.as_deref(),
+            audio,
+        ) {
+            Ok(segment) => {
+                let _ = window.emit("transcript-segment", &segment);
+            }
+            Err(e) => log::warn!("Transcript segment not saved: {}", e),
+        }
+
+        // Stream sentiment/intent insight alongside the transcript without blocking processing
+        spawn_utterance_insight(state, window, validated_transcript.clone()).await;
+
+        // Only with the user's opt-in; estimated on-device from the words and the frontend's prosody
+        let emotion_settings = state.settings.snapshot().voice_recognition.emotion_tracking.clone();
+        if let Some(change) =
+            integrations::emotion_tracking::observe(&validated_transcript, prosody.as_ref(), &emotion_settings)
+        {
+            let _ = window.emit("emotion-changed", &change);
+        }
+
+        let (code_dictation, feedback_settings, pipeline_settings, preview_settings, verbatim) = {
+            let settings = state.settings.snapshot();
+            (
+                settings.code_dictation.clone(),
+                settings.feedback.clone(),
+                settings.pipeline.clone(),
+                settings.preview.clone(),
+                settings.verbatim.clone(),
+            )
+        };
+        let pipeline::PipelineRun { mut result, route, injected } = pipeline::run(
+            state,
+            window,
+            &pipeline_settings,
+            &validated_transcript,
+            &language,
+            &code_dictation,
+            &feedback_settings,
+            &verbatim,
+        )
+        .await?;
+
+        // Code is checked as it will be typed, after filtering
+        if code_dictation.enabled && injected {
+            let issues = validate_syntax(&result.processed_text, code_dictation.language);
+            let check = CodeFormatResult {
+                spoken: result.original_text.clone(),
+                code: result.processed_text.clone(),
+                language: code_dictation.language,
+                valid: issues.is_empty(),
+                issues,
             };
+            let _ = window.emit("code-dictation", &check);
+            if !check.valid && code_dictation.hold_invalid {
+                attach_latency(window, &mut result, &timings, started_ms).await;
+                return Ok(result);
+            }
+        }
+
+        // A pipeline without Inject, or one Vad stopped, only shows its result
+        if !injected {
+            attach_latency(window, &mut result, &timings, started_ms).await;
+            return Ok(result);
+        }
+
+        attach_latency(window, &mut result, &timings, started_ms).await;
+        let target_app = match preview {
+            Some(hold) => hold.then_some(None),
+            None if preview_settings.enabled => Some(None),
+            None if preview_settings.apps.is_empty() => None,
+            None => {
+                let app = system_activity::frontmost_application().await;
+                preview_settings.applies_to(app.as_deref()).then_some(app)
+            }
+        };
+        if let Some(target_app) = target_app {
+            let pending = preview::hold(result.clone(), route, target_app).await;
+            let _ = window.emit("result-pending", &pending);
+            return Ok(result);
+        }
+
+        deliver_result(state, window, &result, route).await;
+        Ok(result)
+    }).await;
+
+    // Words typed live for an utterance that was not typed after all, e.g. a voice command, come back out
+    if let Some(update) = live_typing::retract().await {
+        let _ = emit_live_typing(window, &update);
+    }
+    if let Some(stats) = session_stats::end_utterance().await {
+        let _ = window.emit("dictation-stats", &stats);
+    }
+
+    if session_recording::is_recording() {
+        session_recording::record(session_recording::SessionEvent::Outcome {
+            result: outcome.as_ref().ok().cloned(),
+            error: outcome.as_ref().err().map(|e| e.to_string()),
+        });
+    }
+    outcome
+}
+
+/// Replace the recognizer's top transcript with the best of its alternatives, re-ranked with the
+/// profile's vocabulary and language model; the transcript is kept when merging is off or finds nothing
+async fn merge_hypotheses(
+    state: &AppState,
+    window: &Window,
+    transcript: String,
+    alternatives: &[integrations::voice_recognition::Alternative],
+) -> String {
+    let (mut settings, vocabulary, text_model, verbatim) = {
+        let settings = state.settings.snapshot();
+        (
+            settings.voice_recognition.hypothesis_merge.clone(),
+            recognition_hints(&settings.vocabulary, &settings.keyword_boost),
+            settings.ai_ml_settings.text_model.clone(),
+            settings.verbatim.enabled,
+        )
+    };
+    if !settings.enabled {
+        return transcript;
+    }
+    // A verbatim record keeps one hypothesis as heard; words stitched from several were never said together
+    settings.combine_words &= !verbatim;
+    let history: Vec<String> = match settings.language_model {
+        integrations::hypothesis_merge::RerankModel::Local => history::get_transcript_history()
+            .lock()
+            .await
+            .list(integrations::hypothesis_merge::LANGUAGE_MODEL_SEGMENTS, None)
+            .into_iter()
+            .map(|segment| segment.text)
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    // A handle on the gateway, so the re-ranking request does not keep other commands waiting on the lock
+    let gateway = if settings.language_model == integrations::hypothesis_merge::RerankModel::Llm
+        && startup::ensure_started(state, startup::Service::AiGateway).await.is_ok()
+    {
+        state.ai_ml_gateway.lock().await.clone()
+    } else {
+        None
+    };
+    let gateway = gateway.as_ref().map(|gateway| (gateway, text_model.as_str()));
+    let choice = integrations::hypothesis_merge::merge(alternatives, &vocabulary, &history, &settings, gateway).await;
+    let Some(choice) = choice else {
+        return transcript;
+    };
+    integrations::hypothesis_merge::remember(&choice).await;
+    let _ = window.emit("hypothesis-merged", &choice);
+    if choice.changed {
+        log::info!(
+            "Hypothesis {} replaced the recognizer's top choice{}",
+            choice.winner + 1,
+            if choice.combined { " (combined word by word)" } else { "" }
+        );
+    }
+    choice.transcript
+}
+
+/// Which recognition alternative won for recent utterances, and the scores behind it
+#[tauri::command]
+async fn get_hypothesis_diagnostics(
+    limit: Option<usize>,
+) -> Result<Vec<integrations::hypothesis_merge::HypothesisChoice>, AppError> {
+    Ok(integrations::hypothesis_merge::recent(limit.unwrap_or(20)).await)
+}
+
+/// Hand a finished dictation to the frontend to type and remember it for refinement, ratings and stats
+async fn deliver_result(state: &AppState, window: &Window, result: &ProcessingResult, route: Option<feedback::Route>) {
+    *state.last_output.lock().await = Some(result.processed_text.clone());
+    refinement::record(&result.original_text, &result.processed_text).await;
+    let feedback_enabled = state.settings.snapshot().feedback.enabled;
+    if let (true, Some(route)) = (feedback_enabled, route) {
+        feedback::get_feedback_loop().lock().await.note_result(
+            &result.id,
+            feedback::FeedbackTask::Dictation,
+            &result.original_text,
+            &result.processed_text,
+            route,
+        );
+    }
+    spawn_dictation_stats(state, result).await;
+    // Send processed result to frontend
+    type_result(window, result.processed_text.clone()).await;
+}
+
+/// Words already typed live are corrected into `text` instead of typing it again
+async fn type_result(window: &Window, text: String) {
+    if let Some(stats) = session_stats::observe_final(&text).await {
+        let _ = window.emit("dictation-stats", &stats);
+    }
+    match live_typing::finalize(&text).await {
+        Some(update) => {
+            // The final text stays in `last_output` for the user to copy, as for any held-back injection
+            if let Err(reason) = emit_live_typing(window, &update) {
+                let _ = window.emit("injection-suppressed", injection_safety::SuppressedInjection { text, reason });
+            }
+        }
+        None => emit_voice_response(window, text),
+    }
+}
+
+/// Live-typing steps type into the focused app like any injection, so Secure Input holds them back too;
+/// the reason is returned when they were held back
+fn emit_live_typing(window: &Window, update: &live_typing::LiveTypingUpdate) -> Result<(), String> {
+    if let Some(reason) = injection_safety::injection_blocked() {
+        log::info!("Live typing held back: {}", reason);
+        return Err(reason);
+    }
+    let _ = window.emit("live-typing", update);
+    Ok(())
+}
+
+/// Hand text to the frontend to type, unless Secure Input holds typing back; the text then stays in
+/// `last_output` for the user to copy
+fn emit_voice_response(window: &Window, text: String) {
+    match injection_safety::injection_blocked() {
+        None => {
+            let _ = window.emit("voice-response", text);
+        }
+        Some(reason) => {
+            let _ = window.emit("injection-suppressed", injection_safety::SuppressedInjection { text, reason });
+        }
+    }
+}
+
+/// Dictation held for approval, oldest first
+#[tauri::command]
+async fn list_pending_results() -> Result<Vec<preview::PendingResult>, AppError> {
+    Ok(preview::list().await)
+}
+
+/// Type a held result, optionally as edited by the user
+#[tauri::command]
+async fn approve_pending_result(
+    id: String,
+    edited_text: Option<String>,
+    state: State<'_, AppState>,
+    window: Window,
+) -> Result<ProcessingResult, AppError> {
+    let (pending, route) = preview::take(&id)
+        .await
+        .ok_or_else(|| AppError::Configuration(format!("No result with id {} is waiting for approval", id)))?;
+    let mut result = pending.result;
+    if let Some(edited) = edited_text {
+        let validated_text = validate_text(&edited, Some(1), Some(50000))
+            .map_err(|e| AppError::Validation(e.to_string().into()))?;
+        result.processed_text = filter_output(&state, &window, &validated_text, OutputTarget::Injection).await;
+    }
+    deliver_result(&state, &window, &result, route).await;
+    Ok(result)
+}
+
+/// Drop a held result without typing it
+#[tauri::command]
+async fn discard_pending_result(id: String) -> Result<bool, AppError> {
+    Ok(preview::discard(&id).await)
+}
+
+/// Push the active boost keywords to the recognizer as hints and to text enhancement as must-preserve terms
+async fn apply_keyword_boost(state: &AppState) {
+    let (hints, keywords) = {
+        let settings = state.settings.snapshot();
+        (
+            recognition_hints(&settings.vocabulary, &settings.keyword_boost),
+            settings.keyword_boost.active_keywords(),
+        )
+    };
+    if let Ok(engine) = voice_engine_handle(state).await {
+        let mut config = engine.status().config;
+        config.phrase_hints = hints;
+        if let Err(e) = engine.reconfigure(config).await {
+            tracing::warn!("Could not update recognition hints: {}", e);
+        }
+    }
+    if let Some(gateway) = state.ai_ml_gateway.lock().await.as_ref() {
+        gateway.set_preserved_terms(keywords).await;
+    }
+}
+
+/// Boost the active domain pack's keywords and give text enhancement its instructions and glossary
+async fn apply_domain_pack(state: &AppState) {
+    let domain_settings = state.settings.snapshot().domain_packs.clone();
+    let instructions = domain_packs::active_instructions(&domain_settings);
+    apply_keyword_boost(state).await;
+    if let Some(gateway) = state.ai_ml_gateway.lock().await.as_ref() {
+        gateway.set_domain_instructions(instructions).await;
+    }
+}
+
+/// Fill a snippet's placeholders, asking the model for the AI ones using the recent dictation as context
+async fn expand_snippet(state: &AppState, snippet: &Snippet) -> Result<SnippetExpansion, AppError> {
+    let (snippet_settings, text_model) = {
+        let settings = state.settings.snapshot();
+        (settings.snippets.clone(), settings.ai_ml_settings.text_model.clone())
+    };
+    let last_dictation = state.last_output.lock().await.clone();
+    let mut values = integrations::snippets::variable_values(&snippet_settings, last_dictation.as_deref());
+
+    let needs_ai = integrations::snippets::placeholders(&snippet.body).iter().any(|placeholder| placeholder.ai);
+    if needs_ai {
+        let context = history::get_transcript_history()
+            .lock()
+            .await
+            .list(5, None)
+            .into_iter()
+            .rev()
+            .map(|segment| segment.text)
+            .collect::<Vec<_>>()
+            .join("\n");
+        let gateway_state = state.ai_ml_gateway.lock().await;
+        match gateway_state.as_ref() {
+            Some(gateway) => {
+                match integrations::snippets::fill_ai_placeholders(gateway, text_model, snippet, &context).await {
+                    Ok(ai_values) => values.extend(ai_values),
+                    Err(e) => log::warn!("AI placeholders of snippet '{}' left empty: {}", snippet.name, e),
+                }
+            }
+            None => log::warn!("AI placeholders of snippet '{}' left empty: AI ML API not initialized", snippet.name),
+        }
+    }
+    Ok(integrations::snippets::expand(snippet, &values))
+}
+
+/// Apply a spoken correction to the last dictation and learn the corrected words
+async fn apply_voice_correction(
+    state: &AppState,
+    command: integrations::voice_correction::CorrectionCommand,
+) -> Result<integrations::voice_correction::CorrectionOutcome, AppError> {
+    let mut outcome = {
+        let mut last_output = state.last_output.lock().await;
+        let text = last_output
+            .as_deref()
+            .ok_or_else(|| AppError::Configuration("There is no dictated text to correct yet".to_string()))?;
+        let outcome = apply_correction(text, &command)
+            .ok_or_else(|| TextProcessingError::CorrectionTargetNotFound(command.wrong.clone()))?;
+        *last_output = Some(outcome.after.clone());
+        outcome
+    };
+
+    let mut settings = state.settings.write().await;
+    outcome.reinject = settings.corrections.reinject;
+    undo_history::record(undo_history::TextAction::Enhancement {
+        before: outcome.before.clone(),
+        after: outcome.after.clone(),
+        reinjected: outcome.reinject,
+    })
+    .await;
+    let already_known = settings.vocabulary.iter().any(|term| term.eq_ignore_ascii_case(&command.right));
+    if settings.corrections.learn_vocabulary && !already_known {
+        settings.vocabulary.push(command.right.clone());
+        let hints = recognition_hints(&settings.vocabulary, &settings.keyword_boost);
+        if let Err(e) = profiles::save_profile_settings(&storage::active_profile_id(), &settings) {
+            tracing::warn!("Learned correction not saved: {}", e);
+        }
+        drop(settings);
+        apply_recognition_hints(state, hints).await;
+    }
+    Ok(outcome)
+}
+
+/// Add a spoken term to, or remove it from, the active profile's vocabulary and the recognizer's hints
+async fn update_vocabulary(state: &AppState, command: &profiles::VocabularyCommand) -> Result<(), AppError> {
+    let mut settings = state.settings.write().await;
+    if !profiles::apply_vocabulary_command(&mut settings.vocabulary, command) {
+        return Ok(());
+    }
+    let hints = recognition_hints(&settings.vocabulary, &settings.keyword_boost);
+    profiles::save_profile_settings(&storage::active_profile_id(), &settings)?;
+    drop(settings);
+    apply_recognition_hints(state, hints).await;
+    Ok(())
+}
+
+/// Give a running engine new phrase hints; the next start picks them up from settings anyway
+async fn apply_recognition_hints(state: &AppState, hints: Vec<String>) {
+    if let Ok(engine) = voice_engine_handle(state).await {
+        let mut config = engine.status().config;
+        config.phrase_hints = hints;
+        if let Err(e) = engine.reconfigure(config).await {
+            tracing::warn!("Could not update recognition hints: {}", e);
+        }
+    }
+}
+
+/// Result for text that skipped AI processing
+fn unprocessed_result(original_text: String, processed_text: String) -> ProcessingResult {
+    ProcessingResult {
+        id: Uuid::new_v4().to_string(),
+        original_text,
+        processed_text,
+        changes_made: Vec::new(),
+        confidence_score: 1.0,
+        processing_time_ms: 0,
+        context_used: ProcessingContext::Email,
+        tone_applied: ToneType::Professional,
+        metadata: integrations::ai_text_processor::ProcessingMetadata {
+            readability_before: 0.0,
+            readability_after: 0.0,
+            word_count_before: 0,
+            word_count_after: 0,
+            sentences_processed: 0,
+            errors_corrected: 0,
+            filler_words_removed: 0,
+            verbatim: false,
+            length: None,
+        },
+        latency: None,
+        pipeline: None,
+    }
+}
+
+/// Time the utterance's stages, attach the breakdown to the result and keep it for the latency report
+async fn attach_latency(window: &Window, result: &mut ProcessingResult, timings: &latency::ClientTimings, started_ms: u64) {
+    let breakdown = latency::LatencyBreakdown::new(&result.id, timings, started_ms);
+    latency::get_latency_tracker().lock().await.record(breakdown.clone());
+    let _ = window.emit("latency-breakdown", &breakdown);
+    result.latency = Some(breakdown);
+}
+
+/// Per-stage latency over recent dictations, highlighting the slowest stage
+#[tauri::command]
+async fn get_latency_report(limit: Option<usize>) -> Result<latency::LatencyReport, AppError> {
+    Ok(latency::get_latency_tracker().lock().await.report(limit.unwrap_or(100).clamp(1, 200)))
+}
+
+/// Called by the frontend once a result's text has been inserted, completing its breakdown
+#[tauri::command]
+async fn report_injection_latency(
+    result_id: String,
+    injected_at_ms: u64,
+    window: Window,
+) -> Result<latency::LatencyBreakdown, AppError> {
+    let breakdown = latency::get_latency_tracker()
+        .lock()
+        .await
+        .record_injection(&result_id, injected_at_ms)
+        .ok_or_else(|| AppError::Configuration(format!("No latency recorded for result {}", result_id)))?;
+    let _ = window.emit("latency-breakdown", &breakdown);
+    Ok(breakdown)
+}
+
+#[tauri::command]
+async fn list_destinations(state: State<'_, AppState>) -> Result<Vec<DestinationInfo>, AppError> {
+    let settings = state.settings.snapshot();
+    Ok(available_destinations(&settings.destinations).iter().map(|d| d.info()).collect())
+}
+
+#[tauri::command]
+async fn send_to_destination(
+    destination_id: String,
+    text: String,
+    metadata: Option<DestinationMetadata>,
+    state: State<'_, AppState>,
+    window: Window,
+) -> Result<DestinationReceipt, AppError> {
+    let text = validate_text(&text, Some(1), Some(50000))
+        .map_err(|e| AppError::Validation(e.to_string().into()))?;
+    let text = filter_output(&state, &window, &text, OutputTarget::Injection).await;
+    let destination_settings = state.settings.snapshot().destinations.clone();
+
+    let receipt = integrations::destinations::send_to_destination(
+        &destination_id,
+        &text,
+        &metadata.unwrap_or_default(),
+        &destination_settings,
+    )
+    .await?;
+    let _ = window.emit("destination-sent", &receipt);
+    Ok(receipt)
+}
+
+/// Feed interim recognizer output to live typing and the running "dictation-stats";
+/// returns the steps also sent as "live-typing" when words became stable
+#[tauri::command]
+async fn push_interim_transcript(
+    text: String,
+    state: State<'_, AppState>,
+    window: Window,
+) -> Result<Option<live_typing::LiveTypingUpdate>, AppError> {
+    if text.trim().is_empty() {
+        return Ok(None);
+    }
+    validate_text(&text, Some(1), Some(5000)).map_err(|e| AppError::Validation(e.to_string().into()))?;
+    if let Some(stats) = session_stats::observe_interim(&text).await {
+        let _ = window.emit("dictation-stats", &stats);
+    }
+    auto_submit::observe_interim(&text);
+
+    let (settings, text) = {
+        let settings = state.settings.snapshot();
+        let text = pipeline::format_interim(&text, &settings.language, &settings.pipeline, &settings.verbatim);
+        (settings.live_typing.clone(), text)
+    };
+    if !settings.enabled || injection_safety::injection_blocked().is_some() {
+        return Ok(None);
+    }
+    let update = live_typing::interim(&settings, &text).await;
+    if let Some(update) = &update {
+        let _ = window.emit("live-typing", update);
+    }
+    Ok(update)
+}
+
+/// Feed interim recognizer output to stream captions; final text arrives through process_speech_with_ai
+#[tauri::command]
+async fn push_caption_text(text: String, is_final: bool, state: State<'_, AppState>) -> Result<(), AppError> {
+    // An empty interim result clears the hypothesis, so only non-empty text is validated
+    if !text.trim().is_empty() {
+        validate_text(&text, Some(1), Some(5000)).map_err(|e| AppError::Validation(e.to_string().into()))?;
+    }
+    // On battery interim captions are thinned out; the next one or the final text catches up
+    if !is_final && !power::allow_interim_update() {
+        return Ok(());
+    }
+    let language = state.settings.snapshot().language.clone();
+    get_caption_streamer().lock().await.publish(&text, is_final, &language);
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_caption_stream_status() -> Result<CaptionStreamStatus, AppError> {
+    Ok(get_caption_streamer().lock().await.status())
+}
+
+#[tauri::command]
+async fn analyze_utterance(
+    text: String,
+    state: State<'_, AppState>,
+) -> Result<UtteranceInsight, AppError> {
+    let validated_text = validate_text(&text, Some(1), Some(5000))
+        .map_err(|e| AppError::Validation(e.to_string().into()))?;
+
+    let (privacy_mode, config) = {
+        let settings = state.settings.snapshot();
+        (
+            settings.voice_recognition.privacy_mode,
+            settings.voice_recognition.utterance_insights.clone(),
+        )
+    };
+
+    let gateway_state = state.ai_ml_gateway.lock().await;
+    Ok(classify_utterance(&validated_text, gateway_state.as_ref(), privacy_mode, &config).await)
+}
+
+/// Emit an `utterance-insight` event for a finished utterance when insights are enabled
+async fn spawn_utterance_insight(state: &AppState, window: &Window, transcript: String) {
+    let (privacy_mode, config) = {
+        let settings = state.settings.snapshot();
+        (
+            settings.voice_recognition.privacy_mode,
+            settings.voice_recognition.utterance_insights.clone(),
+        )
+    };
+
+    if !config.enabled {
+        return;
+    }
+
+    let gateway = state.ai_ml_gateway.clone();
+    let window = window.clone();
+    tokio::spawn(async move {
+        // Cloned out of the lock so dictation is not kept waiting on the model call
+        let gateway = gateway.lock().await.clone();
+        let insight = classify_utterance(&transcript, gateway.as_ref(), privacy_mode, &config).await;
+        if let Err(e) = window.emit("utterance-insight", insight) {
+            tracing::warn!("Failed to emit utterance insight: {}", e);
+        }
+    });
+}
+
+/// Count the dictation in local statistics, attributing it to the focused application
+async fn spawn_dictation_stats(state: &AppState, result: &ProcessingResult) {
+    let (enabled, language) = {
+        let settings = state.settings.snapshot();
+        (settings.analytics.enabled, settings.language.clone())
+    };
+    if !enabled {
+        return;
+    }
+
+    let result_id = result.id.clone();
+    let text = result.processed_text.clone();
+    let changes = result.changes_made.len() as u64;
+    tokio::spawn(async move {
+        let app = system_activity::frontmost_application()
+            .await
+            .unwrap_or_else(|| analytics::UNKNOWN_APP.to_string());
+        analytics::get_dictation_analytics()
+            .lock()
+            .await
+            .record_dictation(&result_id, &text, &app, &language, changes);
+    });
+}
+
+#[tauri::command]
+async fn get_dictation_stats(
+    range: Option<analytics::StatsRange>,
+    state: State<'_, AppState>,
+) -> Result<analytics::DictationStats, AppError> {
+    let settings = state.settings.snapshot().analytics.clone();
+    Ok(analytics::get_dictation_analytics()
+        .lock()
+        .await
+        .stats(range.unwrap_or(analytics::StatsRange::Week), &settings))
+}
+
+/// Whether the user kept the AI's changes to a result, for the correction-rate trend
+#[tauri::command]
+async fn record_processing_feedback(
+    result_id: String,
+    accepted: bool,
+    state: State<'_, AppState>,
+) -> Result<(), AppError> {
+    if !state.settings.snapshot().analytics.enabled {
+        return Ok(());
+    }
+    analytics::get_dictation_analytics().lock().await.record_feedback(&result_id, accepted)
+}
+
+/// Thumbs up or down for a dictation or refinement result; ratings steer which tone and model are used next
+#[tauri::command]
+async fn rate_result(
+    result_id: String,
+    positive: bool,
+    comment: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<feedback::FeedbackRecord, AppError> {
+    if !state.settings.snapshot().feedback.enabled {
+        return Err(AppError::Configuration("Result feedback is turned off".to_string()));
+    }
+    if let Some(comment) = comment.as_deref().filter(|comment| !comment.trim().is_empty()) {
+        validate_text(comment, Some(1), Some(1000)).map_err(|e| AppError::Validation(e.to_string().into()))?;
+    }
+    feedback::get_feedback_loop().lock().await.rate(&result_id, positive, comment)
+}
+
+/// Ratings so far and how they rank each tone and model
+#[tauri::command]
+async fn get_feedback_summary() -> Result<feedback::FeedbackSummary, AppError> {
+    Ok(feedback::get_feedback_loop().lock().await.summary())
+}
+
+/// Newest segments first; `topic` keeps only segments whose topic label contains it
+#[tauri::command]
+async fn list_transcript_segments(
+    limit: Option<usize>,
+    topic: Option<String>,
+) -> Result<Vec<history::TranscriptSegment>, AppError> {
+    Ok(history::get_transcript_history()
+        .lock()
+        .await
+        .list(limit.unwrap_or(50), topic.as_deref()))
+}
+
+/// The dictation session's emotional state and recent estimates; `None` until tracking is on and has heard something
+#[tauri::command]
+async fn get_emotional_state() -> Result<Option<integrations::emotion_tracking::EmotionSessionState>, AppError> {
+    Ok(integrations::emotion_tracking::current_state())
+}
+
+/// Topic labels in transcript history, for the search filter
+#[tauri::command]
+async fn list_transcript_topics() -> Result<Vec<history::TopicCount>, AppError> {
+    Ok(history::get_transcript_history().lock().await.topics())
+}
+
+/// Current topic and shift history of the dictation session, or of a recent session by id
+#[tauri::command]
+async fn get_topic_state(session_id: Option<String>) -> Result<Option<integrations::topic_tracker::TopicState>, AppError> {
+    Ok(integrations::topic_tracker::topic_state(session_id.as_deref()).await)
+}
+
+/// Re-run a segment's stored audio through a more accurate cloud engine and keep the better text.
+/// Without an engine, the speech-to-text provider set for the segment's language is used.
+#[tauri::command]
+async fn retranscribe_segment(
+    segment_id: String,
+    engine: Option<history::RetranscriptionEngine>,
+    state: State<'_, AppState>,
+    window: Window,
+) -> Result<history::TranscriptSegment, AppError> {
+    let (audio, file_name, language) = {
+        let mut history = history::get_transcript_history().lock().await;
+        let language = history.get(&segment_id)?.language;
+        let (audio, file_name) = history.load_audio(&segment_id)?;
+        (audio, file_name, language)
+    };
+
+    let (transcription, engine_label) = match engine {
+        Some(engine) => {
+            // Whisper takes ISO-639-1 codes
+            let language_hint = language.split('-').next().map(str::to_string);
+            startup::ensure_started(&state, startup::Service::AiGateway).await?;
+            // Uploads take seconds; a clone keeps the lock free for dictation meanwhile
+            let gateway = state
+                .ai_ml_gateway
+                .lock()
+                .await
+                .clone()
+                .ok_or_else(|| AppError::NotInitialized("AI ML API".to_string()))?;
+            let transcription = gateway
+                .transcribe_audio_with_model(audio, file_name, engine.model_id(), language_hint)
+                .await
+                .map_err(AppError::from)?;
+            (transcription.text, engine.label().to_string())
+        }
+        None => {
+            let (settings, failover) = {
+                let settings = state.settings.snapshot();
+                (settings.speech_to_text.clone(), settings.ai_ml_settings.failover.clone())
+            };
+            let uses_gateway = std::iter::once(settings.provider_for(&language))
+                .chain(failover.stt_providers.iter().copied())
+                .any(|kind| kind == integrations::stt_providers::SttProviderKind::Whisper);
+            if uses_gateway {
+                startup::ensure_started(&state, startup::Service::AiGateway).await?;
+            }
+            let transcript = integrations::stt_providers::transcribe(
+                &settings,
+                &failover,
+                &state.ai_ml_gateway,
+                audio,
+                file_name,
+                Some(language),
+            )
+            .await?;
+            (transcript.text, format!("cloud-{}", transcript.provider.label()))
+        }
+    };
+    let text = transcription.trim();
+    if text.is_empty() {
+        return Err(AppError::Internal(format!("{} returned an empty transcript", engine_label)));
+    }
+
+    let segment = history::get_transcript_history()
+        .lock()
+        .await
+        .apply_revision(&segment_id, text, &engine_label)?;
+    let _ = window.emit("segment-retranscribed", &segment);
+    Ok(segment)
+}
+
+/// Run the same text through 2-4 model/provider/prompt setups for side-by-side evaluation
+#[tauri::command]
+async fn compare_processing(
+    text: String,
+    configs: Vec<integrations::model_comparison::ComparisonConfig>,
+    state: State<'_, AppState>,
+) -> Result<integrations::model_comparison::ComparisonReport, AppError> {
+    let validated_text = validate_text(&text, Some(1), Some(5000))
+        .map_err(|e| AppError::Validation(e.to_string().into()))?;
+
+    // The setups run concurrently on a clone, so other commands are not kept waiting on the lock
+    let gateway = state.ai_ml_gateway.lock().await.clone();
+    integrations::model_comparison::compare_processing(
+        &validated_text,
+        &configs,
+        gateway.as_ref(),
+        &state.text_processor,
+    )
+    .await
+}
+
+// AI ML API Commands with Error Handling and Validation
+#[tauri::command]
+async fn initialize_ai_ml_api(
+    state: State<'_, AppState>,
+) -> Result<(), AppError> {
+    let registry = get_error_boundary_registry();
+    let boundary = registry.get("ai_ml_api").await
+        .unwrap_or_else(|| Arc::new(ErrorBoundary::new("ai_ml_api".to_string(), None)));
+
+    with_error_boundary!(boundary, async { start_ai_ml_gateway(&state).await }).await
+}
+
+/// Gateway configuration for the given AI settings
+fn gateway_config(settings: &AIMLSettings) -> AIMLGatewayConfig {
+    AIMLGatewayConfig {
+        api_key: settings.api_key.clone(),
+        base_url: settings.base_url.clone(),
+        timeout_seconds: settings.timeout_seconds,
+        max_retries: settings.max_retries,
+        retry_delay_ms: 1000,
+        enable_fallback: settings.enable_fallback,
+        cache_results: settings.cache_results,
+        max_cache_size: 1000,
+        default_model: settings.default_model.clone(),
+        text_model: settings.text_model.clone(),
+        voice_model: settings.voice_model.clone(),
+        translation_model: settings.translation_model.clone(),
+        context_model: settings.context_model.clone(),
+        health_checks: settings.health_checks.clone(),
+        mock: settings.mock.clone(),
+        upload_compression: settings.upload_compression.clone(),
+        failover: settings.failover.clone(),
+    }
+}
+
+/// Connect the AI ML API gateway with the current settings; used by the command and the startup orchestrator
+async fn start_ai_ml_gateway(state: &AppState) -> Result<(), AppError> {
+    let mut ai_ml_gateway_state = state.ai_ml_gateway.lock().await;
+    
+    // Check if already initialized
+    if ai_ml_gateway_state.is_some() {
+        return Err(AppError::Configuration("AI ML API Gateway already initialized".to_string()));
+    }
+
+    let settings = state.settings.snapshot();
+    let config = gateway_config(&settings.ai_ml_settings);
+
+    let gateway = AIMLAPIGateway::new(config)
+        .await
+        .map_err(AppError::from)?;
+    
+    gateway.initialize()
+        .await
+        .map_err(AppError::from)?;
+    gateway.set_pronunciations(settings.pronunciation.clone()).await;
+    gateway.set_preserved_terms(settings.keyword_boost.active_keywords()).await;
+    gateway.set_domain_instructions(domain_packs::active_instructions(&settings.domain_packs)).await;
+    gateway.set_alternative_preferences(settings.alternatives.clone()).await;
+    // After the lexicons and terms above, so only results made under the same settings are loaded
+    let warmed = gateway.warm_caches().await;
+    if warmed > 0 {
+        tracing::info!("Loaded {} cached results from disk", warmed);
+    }
+
+    *ai_ml_gateway_state = Some(gateway);
+    
+    tracing::info!("AI ML API Gateway initialized successfully");
+    Ok(())
+}
+
+#[tauri::command]
+async fn process_enhanced_text(
+    text: String,
+    operations: Vec<TextOperation>,
+    source_language: Option<String>,
+    target_language: Option<String>,
+    context: EnhancedContext,
+    options: EnhancedProcessingOptions,
+    idempotency_key: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<AIMLResponse<EnhancedTextResult>, AppError> {
+    // Validate and sanitize input
+    let validated_text = validate_text(&text, Some(1), Some(10000))
+        .map_err(|e| AppError::Validation(e.to_string().into()))?;
+    let idempotency_key = idempotency_key.as_deref().map(idempotency::validate_key).transpose()?;
+    if options.generate_alternatives {
+        validate_numeric_value(
+            options.number_of_alternatives,
+            1,
+            integrations::alternatives::MAX_ALTERNATIVES,
+            "number_of_alternatives",
+        )
+        .map_err(|e| AppError::Validation(e.to_string().into()))?;
+    }
+
+    let registry = get_error_boundary_registry();
+    let boundary = registry.get("ai_ml_api").await
+        .unwrap_or_else(|| Arc::new(ErrorBoundary::new("ai_ml_api".to_string(), None)));
+
+    with_error_boundary!(boundary, async {
+        startup::ensure_started(&state, startup::Service::AiGateway).await?;
+        let ai_ml_gateway_state = state.ai_ml_gateway.lock().await;
+        
+        if let Some(ref gateway) = *ai_ml_gateway_state {
+            let request = EnhancedTextRequest {
+                id: Uuid::new_v4().to_string(),
+                text: validated_text,
+                operations,
+                source_language,
+                target_language,
+                context,
+                options,
+                timestamp: std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs(),
+            };
+
+            let inputs = (
+                &request.text,
+                &request.operations,
+                &request.source_language,
+                &request.target_language,
+                &request.context,
+                &request.options,
+            );
+            let inputs = serde_json::to_value(inputs)?;
+            let result = gateway
+                .once(IdempotencyScope::Enhancement, idempotency_key.as_deref(), &inputs, gateway.process_enhanced_text(request))
+                .await;
+            
+            Ok(result)
+        } else {
+            Err(AppError::NotInitialized("AI ML API Gateway".to_string()))
+        }
+    }).await
+}
+
+#[tauri::command]
+async fn enhance_text_v1(
+    request: api::EnhanceTextRequestV1,
+    state: State<'_, AppState>,
+) -> Result<api::EnhanceTextResponseV1, AppError> {
+    let operations = request.internal_operations()?;
+    let (context, options) = (request.context(), request.options());
+    let response = process_enhanced_text(
+        request.text,
+        operations,
+        request.source_language,
+        request.target_language,
+        context,
+        options,
+        request.idempotency_key,
+        state,
+    )
+    .await?;
+    api::EnhanceTextResponseV1::try_from(response)
+}
+
+/// Record that the user chose alternative `index` of an enhanced result; their favourite styles are generated first
+#[tauri::command]
+async fn pick_alternative(
+    result_id: String,
+    index: usize,
+    state: State<'_, AppState>,
+) -> Result<AlternativeVersion, AppError> {
+    let picked = integrations::alternatives::find(&result_id, index).await.ok_or_else(|| {
+        AppError::Configuration(format!("Result {} has no alternative {}", result_id, index))
+    })?;
+    let preferences = {
+        let mut settings = state.settings.write().await;
+        settings.alternatives.record_pick(picked.style);
+        profiles::save_profile_settings(&storage::active_profile_id(), &settings)?;
+        settings.alternatives.clone()
+    };
+    if let Some(gateway) = state.ai_ml_gateway.lock().await.as_ref() {
+        gateway.set_alternative_preferences(preferences).await;
+    }
+    Ok(picked)
+}
+
+#[tauri::command]
+async fn generate_enhanced_voice(
+    text: String,
+    voice_config: VoiceConfiguration,
+    language: String,
+    emotion: Option<String>,
+    speed: Option<f32>,
+    pitch: Option<f32>,
+    output_format: VoiceOutputFormat,
+    post_processing: Vec<VoicePostProcessing>,
+    idempotency_key: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<VoiceResult, AppError> {
+    // Validate input
+    let validated_text = validate_text(&text, Some(1), Some(5000))
+        .map_err(|e| AppError::Validation(e.to_string().into()))?;
+    let idempotency_key = idempotency_key.as_deref().map(idempotency::validate_key).transpose()?;
+
+    let registry = get_error_boundary_registry();
+    let boundary = registry.get("ai_ml_api").await
+        .unwrap_or_else(|| Arc::new(ErrorBoundary::new("ai_ml_api".to_string(), None)));
+
+    with_error_boundary!(boundary, async {
+        let (filter_config, custom_voices) = {
+            let settings = state.settings.snapshot();
+            (settings.content_filters.config_for(None), settings.voices.custom_voices.clone())
+        };
+        let filtered = apply_content_filter(&validated_text, &filter_config, OutputTarget::Speech);
+        let mut voice_config = voice_config;
+        if let integrations::VoiceSelection::CustomVoice { profile_id } = &voice_config.voice {
+            let profile = custom_voices
+                .iter()
+                .find(|profile| &profile.id == profile_id)
+                .ok_or_else(|| AppError::Validation(ValidationError::InvalidConfigValue(format!(
+                    "Unknown custom voice: {}",
+                    profile_id
+                ))))?;
+            voice_config.model = profile.model.clone();
+            voice_config.voice_id = Some(profile.provider_voice_id.clone());
+        }
+        startup::ensure_started(&state, startup::Service::AiGateway).await?;
+        let ai_ml_gateway_state = state.ai_ml_gateway.lock().await;
+        
+        if let Some(ref gateway) = *ai_ml_gateway_state {
+            let request = EnhancedVoiceRequest {
+                id: Uuid::new_v4().to_string(),
+                text: filtered.text,
+                voice_config,
+                language,
+                emotion,
+                speed,
+                pitch,
+                output_format,
+                post_processing,
+            };
+
+            let inputs = EnhancedVoiceRequest {
+                id: String::new(),
+                ..request.clone()
+            };
+            let result = gateway
+                .once(IdempotencyScope::Synthesis, idempotency_key.as_deref(), &inputs, gateway.generate_enhanced_voice(request))
+                .await?;
+            
+            Ok(result)
+        } else {
+            Err(AppError::NotInitialized("AI ML API Gateway".to_string()))
+        }
+    }).await
+}
+
+#[tauri::command]
+async fn synthesize_v1(
+    request: api::SynthesizeRequestV1,
+    state: State<'_, AppState>,
+) -> Result<api::SynthesizeResponseV1, AppError> {
+    let model = state.settings.snapshot().ai_ml_settings.voice_model.clone();
+    let voice_config = request.voice_configuration(model)?;
+    let output_format = request.output_format();
+    generate_enhanced_voice(
+        request.text,
+        voice_config,
+        request.language,
+        None,
+        request.speed,
+        request.pitch,
+        output_format,
+        Vec::new(),
+        request.idempotency_key,
+        state,
+    )
+    .await
+    .map(api::SynthesizeResponseV1::from)
+}
+
+/// Read long text aloud in sentence-sized chunks so playback starts before the whole text is synthesized
+#[tauri::command]
+async fn speak_text_streaming(
+    text: String,
+    voice: Option<String>,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<SpeechStreamStatus, AppError> {
+    let text = validate_text(&text, Some(1), Some(100000))
+        .map_err(|e| AppError::Validation(e.to_string().into()))?;
+    let (filter_config, voices, voice_model, language) = {
+        let settings = state.settings.snapshot();
+        (
+            settings.content_filters.config_for(None),
+            settings.voices.clone(),
+            settings.ai_ml_settings.voice_model.clone(),
+            settings.language.clone(),
+        )
+    };
+    let (model, voice) = voice_for_language(&state.ai_ml_gateway, &voices, &voice_model, &language, voice).await;
+    let text = apply_content_filter(&text, &filter_config, OutputTarget::Speech).text;
+    integrations::speech_stream::start_stream(app, state.ai_ml_gateway.clone(), &text, model, voice, language).await
+}
+
+#[tauri::command]
+async fn control_speech_stream(stream_id: String, action: SpeechStreamAction, app: AppHandle) -> Result<(), AppError> {
+    integrations::speech_stream::control_stream(&app, &stream_id, action).await;
+    Ok(())
+}
+
+/// Speak the current selection, or `text` when the frontend supplies it, with the read-aloud settings
+#[tauri::command]
+async fn read_selection_aloud(
+    text: Option<String>,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<read_aloud::ReadAloudOutcome, AppError> {
+    read_aloud::read_selection(&state, app, text).await
+}
+
+/// Called by the frontend when speech playback starts and ends, so other audio is ducked
+/// and the user can talk over the speech meanwhile
+#[tauri::command]
+async fn set_speech_playback_active(active: bool, state: State<'_, AppState>, app: AppHandle) -> Result<(), AppError> {
+    if active {
+        let ducking = state.settings.snapshot().ducking.clone();
+        audio_ducking::engage(audio_ducking::DuckReason::Speech, &ducking, &app).await;
+        barge_in::playback_started(&state, &app).await;
+    } else {
+        audio_ducking::release(audio_ducking::DuckReason::Speech, &app).await;
+        barge_in::playback_ended(&state).await;
+        integrations::echo_cancellation::playback_ended();
+    }
+    Ok(())
+}
+
+/// Speech audio as it is played, sent by the frontend in short blocks of little-endian f32 mono samples,
+/// so echo cancellation can remove it from the microphone
+#[tauri::command]
+async fn push_playback_reference(
+    samples_base64: String,
+    sample_rate: u32,
+    state: State<'_, AppState>,
+) -> Result<(), AppError> {
+    if !(8_000..=192_000).contains(&sample_rate) {
+        return Err(AppError::Validation(ValidationError::InvalidConfigValue(format!(
+            "Playback sample rate must be 8000-192000 Hz, got {}",
+            sample_rate
+        ))));
+    }
+    let samples = decode_f32_samples(&samples_base64, "playback")?;
+    let settings = state.settings.snapshot().audio_capture.echo_cancellation.clone();
+    integrations::echo_cancellation::push_reference(&settings, &samples, sample_rate);
+    Ok(())
+}
+
+/// Base64 of little-endian f32 samples, as the frontend sends captured and played audio
+fn decode_f32_samples(samples_base64: &str, what: &str) -> Result<Vec<f32>, AppError> {
+    let bytes = BASE64.decode(samples_base64.as_bytes()).map_err(|e| {
+        AppError::Validation(ValidationError::InvalidConfigValue(format!("Invalid {} audio: {}", what, e)))
+    })?;
+    Ok(bytes
+        .chunks_exact(4)
+        .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .collect())
+}
+
+/// Speech-to-text providers with their capabilities and the languages routed to each
+#[tauri::command]
+async fn list_stt_providers(
+    state: State<'_, AppState>,
+) -> Result<Vec<integrations::stt_providers::SttProviderInfo>, AppError> {
+    Ok(integrations::stt_providers::list_providers(&state.settings.snapshot().speech_to_text))
+}
+
+/// Stream microphone audio to the language's provider while the user speaks; results arrive as "stt-result"
+#[tauri::command]
+async fn start_stt_stream(
+    language: Option<String>,
+    state: State<'_, AppState>,
+    window: Window,
+) -> Result<integrations::stt_providers::SttProviderKind, AppError> {
+    let settings = state.settings.snapshot();
+    let language = language.unwrap_or_else(|| settings.language.clone());
+    let threshold = settings.voice_recognition.confidence_threshold;
+    let provider = integrations::stt_providers::start_stream(
+        &settings.speech_to_text,
+        &language,
+        &state.ai_ml_gateway,
+        move |result| match result {
+            Ok(result) => {
+                let confidence = result.confidence().filter(|_| result.is_final);
+                if let Some(confidence) = confidence.filter(|c| *c < threshold) {
+                    log::debug!("Dropping a final result at confidence {:.2}, below the threshold {:.2}", confidence, threshold);
+                    return;
+                }
+                if result.is_final {
+                    let words = result.words.clone();
+                    tauri::async_runtime::spawn(async move { session_stats::observe_words(&words).await });
+                }
+                let _ = window.emit("stt-result", &result);
+            }
+            Err(e) => {
+                log::warn!("Speech stream failed: {}", e);
+                let _ = window.emit("stt-error", e.to_string());
+            }
+        },
+    )
+    .await?;
+    // With two microphones open the stream hears them instead of the frontend's capture
+    integrations::multi_mic::feed_speech_stream();
+    Ok(provider)
+}
+
+#[tauri::command]
+async fn push_stt_audio(samples_base64: String, sample_rate: u32) -> Result<(), AppError> {
+    if !(8_000..=192_000).contains(&sample_rate) {
+        return Err(AppError::Validation(ValidationError::InvalidConfigValue(format!(
+            "Capture sample rate must be 8000-192000 Hz, got {}",
+            sample_rate
+        ))));
+    }
+    // The multi-microphone session feeds the stream itself; the frontend's capture would be heard twice
+    if integrations::multi_mic::status().is_some() {
+        return Ok(());
+    }
+    let samples = decode_f32_samples(&samples_base64, "captured")?;
+    integrations::stt_providers::push_stream_audio(&samples, sample_rate).await
+}
+
+/// End the stream's audio; the provider's last results still arrive. `false` when none was open
+#[tauri::command]
+async fn stop_stt_stream() -> Result<bool, AppError> {
+    Ok(integrations::stt_providers::stop_stream().await)
+}
+
+#[tauri::command]
+async fn get_ducking_status() -> Result<audio_ducking::DuckingStatus, AppError> {
+    Ok(audio_ducking::ducking_status().await)
+}
+
+#[tauri::command]
+async fn start_assistant_session(state: State<'_, AppState>) -> Result<assistant::AssistantSessionInfo, AppError> {
+    let settings = state.settings.snapshot().assistant.clone();
+    Ok(assistant::start_session(&settings).await)
+}
+
+/// Answer one spoken or typed request within an assistant session
+#[tauri::command]
+async fn send_assistant_message(
+    session_id: String,
+    utterance: String,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<assistant::AssistantReply, AppError> {
+    if utterance.trim().is_empty() {
+        return Err(AppError::Validation(ValidationError::InvalidConfigValue(
+            "Nothing to send to the assistant".to_string(),
+        )));
+    }
+    assistant::assistant_turn(&state, &app, &session_id, &utterance).await
+}
+
+#[tauri::command]
+async fn end_assistant_session(session_id: String) -> Result<bool, AppError> {
+    Ok(assistant::end_session(&session_id).await)
+}
+
+#[tauri::command]
+async fn list_assistant_tools(state: State<'_, AppState>) -> Result<Vec<assistant::AssistantToolInfo>, AppError> {
+    let settings = state.settings.snapshot().assistant.clone();
+    Ok(assistant::list_tools(&settings))
+}
+
+/// Add or replace a webhook the assistant can call as a tool
+#[tauri::command]
+async fn register_assistant_tool(tool: assistant::WebhookToolConfig, state: State<'_, AppState>) -> Result<(), AppError> {
+    assistant::validate_webhook_tool(&tool)?;
+    let mut settings = state.settings.write().await;
+    settings.assistant.webhook_tools.retain(|known| known.name != tool.name);
+    settings.assistant.webhook_tools.push(tool);
+    Ok(())
+}
+
+#[tauri::command]
+async fn unregister_assistant_tool(name: String, state: State<'_, AppState>) -> Result<bool, AppError> {
+    let mut settings = state.settings.write().await;
+    let before = settings.assistant.webhook_tools.len();
+    settings.assistant.webhook_tools.retain(|known| known.name != name);
+    Ok(settings.assistant.webhook_tools.len() != before)
+}
+
+/// Called by the frontend as each chunk finishes playing
+#[tauri::command]
+async fn ack_speech_chunk(stream_id: String, sequence: usize) -> Result<(), AppError> {
+    integrations::speech_stream::acknowledge(&stream_id, sequence).await;
+    Ok(())
+}
+
+/// Most requests `batch_generate_voice` accepts at once
+const MAX_VOICE_BATCH: usize = 20;
+
+/// Validate and speech-filter the text of a synthesis request
+async fn prepare_voice_request(state: &AppState, mut request: integrations::VoiceRequest) -> Result<integrations::VoiceRequest, AppError> {
+    let text = validate_text(&request.text, Some(1), Some(5000))
+        .map_err(|e| AppError::Validation(e.to_string().into()))?;
+    let filter_config = state.settings.snapshot().content_filters.config_for(None);
+    request.text = apply_content_filter(&text, &filter_config, OutputTarget::Speech).text;
+    if request.id.trim().is_empty() {
+        request.id = Uuid::new_v4().to_string();
+    }
+    Ok(request)
+}
+
+/// Stock voices followed by the custom voices the provider still serves
+#[tauri::command]
+async fn list_tts_voices(
+    refresh: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<Vec<integrations::VoiceModel>, AppError> {
+    let catalog = get_voice_catalog(refresh, state).await?;
+    let mut voices = catalog.stock;
+    voices.extend(integrations::voice_profiles::as_voice_models(&catalog.custom));
+    Ok(voices)
+}
+
+/// Stock and custom voices with availability, cached for a few minutes unless `refresh` is set
+#[tauri::command]
+async fn get_voice_catalog(refresh: Option<bool>, state: State<'_, AppState>) -> Result<VoiceCatalog, AppError> {
+    let custom_voices = state.settings.snapshot().voices.custom_voices.clone();
+    startup::ensure_started(&state, startup::Service::AiGateway).await?;
+    let gateway_state = state.ai_ml_gateway.lock().await;
+    let gateway = gateway_state
+        .as_ref()
+        .ok_or_else(|| AppError::NotInitialized("AI ML API".to_string()))?;
+    integrations::voice_profiles::voice_catalog(gateway, &custom_voices, refresh.unwrap_or(false))
+        .await
+        .map_err(AppError::Network)
+}
+
+/// Register a voice cloned with the provider; the voice must be usable before it is saved
+#[tauri::command]
+async fn register_custom_voice(profile: CustomVoiceProfile, state: State<'_, AppState>) -> Result<CustomVoiceProfile, AppError> {
+    let existing = state.settings.snapshot().voices.custom_voices.clone();
+    let profile = integrations::voice_profiles::prepare_profile(profile, &existing)
+        .map_err(|e| AppError::Validation(ValidationError::InvalidConfigValue(e)))?;
+    {
+        startup::ensure_started(&state, startup::Service::AiGateway).await?;
+        let gateway_state = state.ai_ml_gateway.lock().await;
+        let gateway = gateway_state
+            .as_ref()
+            .ok_or_else(|| AppError::NotInitialized("AI ML API".to_string()))?;
+        integrations::voice_profiles::probe_voice(gateway, &profile)
+            .await
+            .map_err(|e| AppError::Network(format!("Voice {} is not available: {}", profile.provider_voice_id, e)))?;
+    }
+    state.settings.write().await.voices.custom_voices.push(profile.clone());
+    Ok(profile)
+}
+
+#[tauri::command]
+async fn remove_custom_voice(profile_id: String, state: State<'_, AppState>) -> Result<bool, AppError> {
+    let mut settings = state.settings.write().await;
+    let before = settings.voices.custom_voices.len();
+    settings.voices.custom_voices.retain(|profile| profile.id != profile_id);
+    settings.voices.selection.preferred_voices.retain(|_, voice_id| *voice_id != profile_id);
+    Ok(settings.voices.custom_voices.len() != before)
+}
+
+/// Pin the voice used for speech in `language`, or go back to automatic selection with `None`
+#[tauri::command]
+async fn set_preferred_voice(
+    language: String,
+    voice_id: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<VoiceChoice, AppError> {
+    let language = validate_language_code(&language)?;
+    startup::ensure_started(&state, startup::Service::AiGateway).await?;
+    if let Some(voice_id) = &voice_id {
+        let custom_voices = state.settings.snapshot().voices.custom_voices.clone();
+        let stock = match state.ai_ml_gateway.lock().await.as_ref() {
+            Some(gateway) => gateway.list_voices().await.map_err(AppError::from)?,
+            None => Vec::new(),
+        };
+        if !integrations::voice_selection::is_known_voice(voice_id, &custom_voices, &stock) {
+            return Err(AppError::Validation(ValidationError::InvalidConfigValue(format!(
+                "Unknown voice: {}",
+                voice_id
+            ))));
+        }
+    }
+
+    let (voices, voice_model) = {
+        let mut settings = state.settings.write().await;
+        let preferred = &mut settings.voices.selection.preferred_voices;
+        match voice_id {
+            Some(voice_id) => preferred.insert(language_key(&language), voice_id),
+            None => preferred.remove(&language_key(&language)),
+        };
+        (settings.voices.clone(), settings.ai_ml_settings.voice_model.clone())
+    };
+    let gateway = state.ai_ml_gateway.lock().await;
+    Ok(integrations::voice_selection::select_cached(gateway.as_ref(), &voices, &voice_model, &language).await)
+}
+
+/// The voice speech in `language` would use right now, and why
+#[tauri::command]
+async fn get_voice_for_language(language: String, state: State<'_, AppState>) -> Result<VoiceChoice, AppError> {
+    let language = validate_language_code(&language)?;
+    startup::ensure_started(&state, startup::Service::AiGateway).await?;
+    let (voices, voice_model) = {
+        let settings = state.settings.snapshot();
+        (settings.voices.clone(), settings.ai_ml_settings.voice_model.clone())
+    };
+    let gateway = state.ai_ml_gateway.lock().await;
+    Ok(integrations::voice_selection::select_cached(gateway.as_ref(), &voices, &voice_model, &language).await)
+}
+
+/// Synthesize `n` takes of the same request so the user can pick the one that sounds best
+#[tauri::command]
+async fn generate_voice_variations(
+    request: integrations::VoiceRequest,
+    n: u8,
+    state: State<'_, AppState>,
+) -> Result<Vec<VoiceResult>, AppError> {
+    if !(1..=5).contains(&n) {
+        return Err(AppError::Validation(ValidationError::InvalidConfigValue(
+            "Between 1 and 5 variations can be generated".to_string(),
+        )));
+    }
+    let request = prepare_voice_request(&state, request).await?;
+
+    startup::ensure_started(&state, startup::Service::AiGateway).await?;
+    // Takes are synthesized concurrently on a clone, so the lock is not held while they run
+    let gateway = state
+        .ai_ml_gateway
+        .lock()
+        .await
+        .clone()
+        .ok_or_else(|| AppError::NotInitialized("AI ML API".to_string()))?;
+    gateway
+        .generate_voice_variations(request, n)
+        .await
+        .map_err(AppError::from)
+}
+
+/// Synthesize several requests, emitting "voice-batch-progress" as each finishes.
+/// A failed item is reported in its slot instead of failing the whole batch.
+#[tauri::command]
+async fn batch_generate_voice(
+    requests: Vec<integrations::VoiceRequest>,
+    confirmed: Option<bool>,
+    state: State<'_, AppState>,
+    window: Window,
+) -> Result<Vec<integrations::BatchSynthesisItem>, AppError> {
+    if requests.is_empty() || requests.len() > MAX_VOICE_BATCH {
+        return Err(AppError::Validation(ValidationError::InvalidConfigValue(format!(
+            "A batch needs between 1 and {} requests",
+            MAX_VOICE_BATCH
+        ))));
+    }
+    let estimate = cost_estimate::CostRequest::Speech {
+        items: requests
+            .iter()
+            .map(|request| cost_estimate::SpeechItem {
+                text: request.text.clone(),
+                model: Some(request.voice_config.model.clone()),
+            })
+            .collect(),
+    };
+    cost_estimate::require_confirmation(estimate, &state.settings.snapshot(), confirmed.unwrap_or(false)).await?;
+    let mut prepared = Vec::with_capacity(requests.len());
+    for request in requests {
+        prepared.push(prepare_voice_request(&state, request).await?);
+    }
+    let request_ids: Vec<String> = prepared.iter().map(|r| r.id.clone()).collect();
+    let total = prepared.len();
+
+    startup::ensure_started(&state, startup::Service::AiGateway).await?;
+    let gateway = state
+        .ai_ml_gateway
+        .lock()
+        .await
+        .clone()
+        .ok_or_else(|| AppError::NotInitialized("AI ML API".to_string()))?;
+    let outcomes = gateway
+        .batch_generate_voice(prepared, |index, outcome| {
+            let _ = window.emit(
+                "voice-batch-progress",
+                integrations::BatchSynthesisProgress {
+                    completed: index + 1,
+                    total,
+                    request_id: request_ids[index].clone(),
+                    error: outcome.as_ref().err().map(|e| e.to_string()),
+                },
+            );
+        })
+        .await;
+
+    Ok(outcomes
+        .into_iter()
+        .zip(request_ids.iter())
+        .enumerate()
+        .map(|(index, (outcome, request_id))| {
+            let (result, error) = match outcome {
+                Ok(result) => (Some(result), None),
+                Err(e) => (None, Some(e.to_string())),
+            };
+            integrations::BatchSynthesisItem {
+                index,
+                request_id: request_id.clone(),
+                result,
+                error,
+            }
+        })
+        .collect())
+}
+
+/// Lexicon entries for `language`, or every lexicon keyed by language when it is omitted
+#[tauri::command]
+async fn list_pronunciations(
+    language: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<HashMap<String, Vec<PronunciationEntry>>, AppError> {
+    let settings = state.settings.snapshot();
+    Ok(match language {
+        Some(language) => {
+            let entries = settings.pronunciation.entries_for(&language);
+            HashMap::from([(language, entries)])
+        }
+        None => settings.pronunciation.lexicons.clone(),
+    })
+}
+
+/// Add or replace how `entry.term` is spoken in `language`
+#[tauri::command]
+async fn add_pronunciation(
+    language: String,
+    entry: PronunciationEntry,
+    state: State<'_, AppState>,
+) -> Result<(), AppError> {
+    let language = validate_language_code(&language)
+        .map_err(|e| AppError::Validation(e.to_string().into()))?;
+    integrations::pronunciation::validate_entry(&entry)
+        .map_err(|e| AppError::Validation(ValidationError::InvalidConfigValue(e)))?;
+    let pronunciation = {
+        let mut settings = state.settings.write().await;
+        settings.pronunciation.add(&language, entry);
+        settings.pronunciation.clone()
+    };
+    if let Some(gateway) = state.ai_ml_gateway.lock().await.as_ref() {
+        gateway.set_pronunciations(pronunciation).await;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn remove_pronunciation(language: String, term: String, state: State<'_, AppState>) -> Result<bool, AppError> {
+    let (removed, pronunciation) = {
+        let mut settings = state.settings.write().await;
+        let removed = settings.pronunciation.remove(&language, &term);
+        (removed, settings.pronunciation.clone())
+    };
+    if removed {
+        if let Some(gateway) = state.ai_ml_gateway.lock().await.as_ref() {
+            gateway.set_pronunciations(pronunciation).await;
+        }
+    }
+    Ok(removed)
+}
+
+/// Speak `word` on its own with the lexicon applied
+#[tauri::command]
+async fn test_pronunciation(
+    word: String,
+    language: Option<String>,
+    voice: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<PronunciationPreview, AppError> {
+    let word = validate_text(&word, Some(1), Some(200))
+        .map_err(|e| AppError::Validation(e.to_string().into()))?;
+    let (language, (model, voice), entries) = {
+        let settings = state.settings.snapshot();
+        let language = language.unwrap_or_else(|| settings.language.clone());
+        let entries = settings.pronunciation.entries_for(&language);
+        let voice = resolve_voice(&settings.voices.custom_voices, &settings.ai_ml_settings.voice_model, voice);
+        (language, voice, entries)
+    };
+    let entry = entries.iter().find(|entry| entry.term.eq_ignore_ascii_case(word.trim())).cloned();
+    // Phonetic alphabets only take effect through SSML
+    let use_ssml = entry.as_ref().map_or(false, |entry| entry.alphabet != PhoneticAlphabet::Respelling);
+    let rendered_text = if use_ssml {
+        integrations::pronunciation::apply_ssml(&word, &entries)
+    } else {
+        integrations::pronunciation::apply_plain(&word, &entries)
+    };
+
+    let mut request = integrations::VoiceRequest::for_text(word.clone(), model, voice, language.clone());
+    request.voice_config.ssml_enabled = use_ssml;
+    startup::ensure_started(&state, startup::Service::AiGateway).await?;
+    let gateway_state = state.ai_ml_gateway.lock().await;
+    let gateway = gateway_state
+        .as_ref()
+        .ok_or_else(|| AppError::NotInitialized("AI ML API".to_string()))?;
+    let result = gateway.synthesize(request).await.map_err(AppError::from)?;
+
+    Ok(PronunciationPreview {
+        term: word,
+        language,
+        entry,
+        rendered_text,
+        audio_base64: BASE64.encode(&result.audio_data),
+        format: format!("{:?}", result.format).to_lowercase(),
+        sample_rate: result.sample_rate,
+    })
+}
+
+#[tauri::command]
+async fn translate_with_enhancement(
+    text: String,
+    from: Option<String>,
+    to: String,
+    context: Option<integrations::TranslationContext>,
+    options: Option<integrations::TranslationOptions>,
+    idempotency_key: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<TranslationResult, AppError> {
+    // Validate input
+    let validated_text = validate_text(&text, Some(1), Some(8000))
+        .map_err(|e| AppError::Validation(e.to_string().into()))?;
+    let idempotency_key = idempotency_key.as_deref().map(idempotency::validate_key).transpose()?;
+
+    let registry = get_error_boundary_registry();
+    let boundary = registry.get("ai_ml_api").await
+        .unwrap_or_else(|| Arc::new(ErrorBoundary::new("ai_ml_api".to_string(), None)));
+
+    with_error_boundary!(boundary, async {
+        startup::ensure_started(&state, startup::Service::AiGateway).await?;
+        let ai_ml_gateway_state = state.ai_ml_gateway.lock().await;
+        
+        if let Some(ref gateway) = *ai_ml_gateway_state {
+            let (context, options) = (context.unwrap_or_default(), options.unwrap_or_default());
+            let inputs = serde_json::to_value((&validated_text, &from, &to, &context, &options))?;
+            let translation = gateway.translate_with_enhancement(validated_text, from, to, context, options);
+            let result = gateway
+                .once(IdempotencyScope::Translation, idempotency_key.as_deref(), &inputs, translation)
+                .await?;
+            
+            Ok(result)
+        } else {
+            Err(AppError::NotInitialized("AI ML API Gateway".to_string()))
+        }
+    }).await
+}
+
+#[tauri::command]
+async fn translate_v1(
+    request: api::TranslateRequestV1,
+    state: State<'_, AppState>,
+) -> Result<api::TranslateResponseV1, AppError> {
+    let (context, options) = (request.context(), request.options());
+    translate_with_enhancement(
+        request.text,
+        request.source_language,
+        request.target_language,
+        Some(context),
+        Some(options),
+        request.idempotency_key,
+        state,
+    )
+    .await
+    .map(api::TranslateResponseV1::from)
+}
+
+/// Original and translation paired sentence by sentence, rendered for export
+#[tauri::command]
+async fn export_bilingual_translation(
+    original_text: String,
+    translated_text: String,
+    source_language: String,
+    target_language: String,
+    layout: integrations::bilingual::BilingualLayout,
+    format: Option<integrations::OutputFormat>,
+) -> Result<String, AppError> {
+    let original_text = validate_text(&original_text, Some(1), Some(100_000))
+        .map_err(|e| AppError::Validation(e.to_string().into()))?;
+    let translated_text = validate_text(&translated_text, Some(1), Some(100_000))
+        .map_err(|e| AppError::Validation(e.to_string().into()))?;
+    let source_language = validate_language_code(&source_language)?;
+    let target_language = validate_language_code(&target_language)?;
+    let bilingual = integrations::bilingual::BilingualText::align(
+        &original_text,
+        &translated_text,
+        &source_language,
+        &target_language,
+    );
+    Ok(bilingual.render(layout, &format.unwrap_or(integrations::OutputFormat::PlainText)))
+}
+
+/// Default formality for translations into `language` when the request and its audience leave it open;
+/// `Neutral` goes back to the language's usual register
+#[tauri::command]
+async fn set_default_formality(
+    language: String,
+    level: integrations::FormalityLevel,
+    state: State<'_, AppState>,
+) -> Result<integrations::translation_formality::FormalitySettings, AppError> {
+    let language = validate_language_code(&language)?;
+    if !integrations::translation_formality::has_register(&language) {
+        return Err(AppError::Validation(ValidationError::InvalidConfigValue(format!(
+            "{} has no formal and informal forms of address to choose between",
+            language
+        ))));
+    }
+
+    let mut settings = state.settings.write().await;
+    let key = integrations::translation_formality::language_key(&language);
+    if level == integrations::FormalityLevel::Neutral {
+        settings.formality.defaults.remove(&key);
+    } else {
+        settings.formality.defaults.insert(key, level);
+    }
+    profiles::save_profile_settings(&storage::active_profile_id(), &settings)?;
+    integrations::translation_formality::configure(&settings.formality);
+    Ok(settings.formality.clone())
+}
+
+#[tauri::command]
+async fn process_context_aware(
+    text: String,
+    mut context: EnhancedContext,
+    requires_understanding: bool,
+    include_sentiment: bool,
+    include_intent: bool,
+    memory_retention: bool,
+    include_screen: Option<bool>,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<ContextAwareResult, AppError> {
+    // Validate input
+    let validated_text = validate_text(&text, Some(1), Some(6000))
+        .map_err(|e| AppError::Validation(e.to_string().into()))?;
+    // What "this" refers to, read from the screen when the caller asks for it
+    if include_screen.unwrap_or(false) {
+        context.screen_text = Some(screen_text_for_model(&state, None).await?.text);
+    }
+
+    let registry = get_error_boundary_registry();
+    let boundary = registry.get("ai_ml_api").await
+        .unwrap_or_else(|| Arc::new(ErrorBoundary::new("ai_ml_api".to_string(), None)));
+
+    let rule_text = validated_text.clone();
+    let result: ContextAwareResult = with_error_boundary!(boundary, async {
+        startup::ensure_started(&state, startup::Service::AiGateway).await?;
+        let ai_ml_gateway_state = state.ai_ml_gateway.lock().await;
+        
+        if let Some(ref gateway) = *ai_ml_gateway_state {
+            let request = ContextAwareRequest {
+                id: Uuid::new_v4().to_string(),
+                text: validated_text,
+                context,
+                requires_understanding,
+                include_sentiment,
+                include_intent,
+                memory_retention,
+            };
+
+            let result = gateway.process_context_aware(request).await?;
+            
+            Ok(result)
+        } else {
+            Err(AppError::NotInitialized("AI ML API Gateway".to_string()))
+        }
+    })?;
+
+    // Rule actions can be slow, so they report through "intent-rule-fired" rather than delaying the result
+    if include_intent {
+        let state = state.inner().clone();
+        let classification = result.intent.clone();
+        tauri::async_runtime::spawn(async move {
+            intent_rules::run_rules(&state, &app, &rule_text, &classification, false).await;
+        });
+    }
+    Ok(result)
+}
+
+#[tauri::command]
+async fn list_intent_rules(state: State<'_, AppState>) -> Result<Vec<intent_rules::IntentRule>, AppError> {
+    Ok(state.settings.snapshot().intent_rules.rules.clone())
+}
+
+/// Add a rule, or replace the rule with the same ID
+#[tauri::command]
+async fn add_intent_rule(rule: intent_rules::IntentRule, state: State<'_, AppState>) -> Result<intent_rules::IntentRule, AppError> {
+    let mut settings = state.settings.write().await;
+    let rule = intent_rules::prepare_rule(rule, &settings.content_filters)?;
+    match settings.intent_rules.rules.iter_mut().find(|known| known.id == rule.id) {
+        Some(known) => *known = rule.clone(),
+        None => settings.intent_rules.rules.push(rule.clone()),
+    }
+    profiles::save_profile_settings(&storage::active_profile_id(), &settings)?;
+    Ok(rule)
+}
+
+#[tauri::command]
+async fn remove_intent_rule(id: String, state: State<'_, AppState>) -> Result<bool, AppError> {
+    let mut settings = state.settings.write().await;
+    let before = settings.intent_rules.rules.len();
+    settings.intent_rules.rules.retain(|rule| rule.id != id);
+    let removed = settings.intent_rules.rules.len() != before;
+    if removed {
+        profiles::save_profile_settings(&storage::active_profile_id(), &settings)?;
+    }
+    Ok(removed)
+}
+
+/// Show which rules `text` would trigger, classifying it first unless an intent is given.
+/// With `dry_run` the actions are only reported, not carried out.
+#[tauri::command]
+async fn evaluate_intent_rules(
+    text: String,
+    intent: Option<UserIntent>,
+    confidence: Option<f32>,
+    dry_run: bool,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<Vec<intent_rules::RuleOutcome>, AppError> {
+    let validated_text = validate_text(&text, Some(1), Some(6000))
+        .map_err(|e| AppError::Validation(e.to_string().into()))?;
+
+    let classification = match intent {
+        Some(intent) => IntentClassification {
+            primary_intent: intent,
+            confidence: confidence.unwrap_or(1.0),
+            alternative_intents: Vec::new(),
+            required_actions: Vec::new(),
+            expected_outcome: String::new(),
+        },
+        None => {
+            startup::ensure_started(&state, startup::Service::AiGateway).await?;
+            let gateway_state = state.ai_ml_gateway.lock().await;
+            let gateway = gateway_state
+                .as_ref()
+                .ok_or_else(|| AppError::NotInitialized("AI ML API".to_string()))?;
+            let request = ContextAwareRequest {
+                id: Uuid::new_v4().to_string(),
+                text: validated_text.clone(),
+                context: EnhancedContext::default(),
+                requires_understanding: false,
+                include_sentiment: false,
+                include_intent: true,
+                memory_retention: false,
+            };
+            gateway
+                .process_context_aware(request)
+                .await
+                .map_err(AppError::from)?
+                .intent
+        }
+    };
+    Ok(intent_rules::run_rules(&state, &app, &validated_text, &classification, dry_run).await)
+}
+
+#[tauri::command]
+async fn process_long_text(
+    text: String,
+    operation: LongTextOperation,
+    chunking: Option<ChunkingConfig>,
+    idempotency_key: Option<String>,
+    confirmed: Option<bool>,
+    state: State<'_, AppState>,
+    window: Window,
+) -> Result<LongTextResult, AppError> {
+    // Long documents are bounded by the global text limit; chunking keeps each model call small
+    let validated_text = validate_text(&text, Some(1), None)
+        .map_err(|e| AppError::Validation(e.to_string().into()))?;
+    let idempotency_key = idempotency_key.as_deref().map(idempotency::validate_key).transpose()?;
+    let estimate = cost_estimate::CostRequest::LongText {
+        text: Some(validated_text.clone()),
+        path: None,
+        operation: operation.clone(),
+    };
+    cost_estimate::require_confirmation(estimate, &state.settings.snapshot(), confirmed.unwrap_or(false)).await?;
+
+    let registry = get_error_boundary_registry();
+    let boundary = registry.get("ai_ml_api").await
+        .unwrap_or_else(|| Arc::new(ErrorBoundary::new("ai_ml_api".to_string(), None)));
+
+    with_error_boundary!(boundary, async {
+        startup::ensure_started(&state, startup::Service::AiGateway).await?;
+        // A clone shares the gateway's services, so the lock is not held while the chunks run
+        let gateway = state
+            .ai_ml_gateway
+            .lock()
+            .await
+            .clone()
+            .ok_or_else(|| AppError::NotInitialized("AI ML API Gateway".to_string()))?;
+
+        let progress_window = window.clone();
+        let inputs = serde_json::to_value((&validated_text, &operation, &chunking))?;
+        let job = gateway.process_long_text(
+            Uuid::new_v4().to_string(),
+            validated_text,
+            operation,
+            chunking.unwrap_or_default(),
+            move |progress| {
+                let _ = progress_window.emit("chunk-progress", progress);
+            },
+        );
+        // Long jobs are remembered on disk, so a retry after a crash returns the finished document
+        let result = gateway.once(IdempotencyScope::LongText, idempotency_key.as_deref(), &inputs, job).await?;
+
+        Ok(result)
+    }).await
+}
+
+/// Transcribe a recording too long for one upload; progress and an ETA arrive as `long-audio-progress` events
+#[tauri::command]
+async fn transcribe_long_audio(
+    path: String,
+    language: Option<String>,
+    confirmed: Option<bool>,
+    state: State<'_, AppState>,
+    window: Window,
+) -> Result<integrations::LongTranscription, AppError> {
+    let path = std::path::PathBuf::from(&path);
+    let is_audio = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| jobs::AUDIO_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+        .unwrap_or(false);
+    if !path.is_absolute() || !path.is_file() || !is_audio {
+        return Err(AppError::Validation(ValidationError::InvalidConfigValue(format!(
+            "{} is not an audio file ({})",
+            path.display(),
+            jobs::AUDIO_EXTENSIONS.join(", ")
+        ))));
+    }
+    // Long recordings always go to Whisper through the gateway, window by window
+    let estimate = cost_estimate::CostRequest::Transcription {
+        path: Some(path.to_string_lossy().into_owned()),
+        duration_secs: None,
+        language: language.clone(),
+        provider: Some(integrations::stt_providers::SttProviderKind::Whisper),
+        windowed: true,
+    };
+    cost_estimate::require_confirmation(estimate, &state.settings.snapshot(), confirmed.unwrap_or(false)).await?;
+    let settings = state.settings.snapshot().long_audio.clone();
+
+    let registry = get_error_boundary_registry();
+    let boundary = registry.get("ai_ml_api").await
+        .unwrap_or_else(|| Arc::new(ErrorBoundary::new("ai_ml_api".to_string(), None)));
+
+    with_error_boundary!(boundary, async {
+        startup::ensure_started(&state, startup::Service::AiGateway).await?;
+        // A handle on the gateway, so an hour-long run does not keep other commands waiting on the lock
+        let gateway = state.ai_ml_gateway.lock().await.clone();
+
+        if let Some(gateway) = gateway {
+            let progress_window = window.clone();
+            let result = gateway
+                .transcribe_long_audio(
+                    Uuid::new_v4().to_string(),
+                    &path,
+                    language,
+                    &settings,
+                    Default::default(),
+                    move |progress, _| {
+                        let _ = progress_window.emit("long-audio-progress", progress);
+                    },
+                )
+                .await?;
+
+            Ok(result)
+        } else {
+            Err(AppError::NotInitialized("AI ML API Gateway".to_string()))
+        }
+    }).await
+}
+
+/// Expected tokens, audio minutes and price of an operation, and whether running it needs confirming
+#[tauri::command]
+async fn estimate_operation_cost(
+    request: cost_estimate::CostRequest,
+    state: State<'_, AppState>,
+) -> Result<cost_estimate::CostEstimate, AppError> {
+    cost_estimate::estimate(request, &state.settings.snapshot()).await
+}
+
+/// Which provider each of speech-to-text, text and speech traffic goes to, and how the providers are doing
+#[tauri::command]
+async fn get_provider_routes(
+    state: State<'_, AppState>,
+) -> Result<Vec<integrations::provider_failover::ProviderRoute>, AppError> {
+    Ok(integrations::provider_failover::routes(&state.settings.snapshot().ai_ml_settings.failover))
+}
+
+#[tauri::command]
+async fn get_ai_ml_health_status(
+    state: State<'_, AppState>,
+) -> Result<HealthStatus, AppError> {
+    startup::ensure_started(&state, startup::Service::AiGateway).await?;
+    let ai_ml_gateway_state = state.ai_ml_gateway.lock().await;
+    
+    if let Some(ref gateway) = *ai_ml_gateway_state {
+        let health_status = gateway.check_health().await;
+        
+        Ok(health_status)
+    } else {
+        Err(AppError::NotInitialized("AI ML API Gateway".to_string()))
+    }
+}
+
+// Tauri Commands for text processing
+#[tauri::command]
+async fn initialize_text_processor(
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    start_text_processor(&state).await.map_err(|e| e.to_string())
+}
+
+async fn start_text_processor(state: &AppState) -> Result<(), AppError> {
+    let mut text_processor_state = state.text_processor.lock().await;
+    
+    let config = get_default_config_for_context(ProcessingContext::Email);
+    let (event_sender, _event_receiver) = event_channel::event_channel("processing", integrations::ai_text_processor::PROCESSING_EVENT_CAPACITY);
+    
+    let mut processor = AITextProcessor::new(config, event_sender);
+    processor.set_code_language(state.settings.snapshot().code_dictation.language);
+    processor.initialize().await.map_err(AppError::Internal)?;
+    // Restarting replaces the previous processor; stop its Python helper rather than leave it to the drop
+    if let Some(mut previous) = text_processor_state.replace(processor) {
+        previous.shutdown().await;
+    }
+
+    Ok(())
+}
+
+/// Unversioned form of `process_text_v1`, kept for frontends built before the handshake
+#[tauri::command]
+async fn process_text(
+    text: String,
+    context: String,
+    tone: String,
+    state: State<'_, AppState>,
+) -> Result<ProcessingResult, AppError> {
+    let request = api::ProcessTextRequestV1::from_legacy(text, &context, &tone)?.into_request()?;
+    run_text_processing(&state, request).await
+}
+
+#[tauri::command]
+async fn process_text_v1(
+    request: api::ProcessTextRequestV1,
+    state: State<'_, AppState>,
+) -> Result<api::ProcessTextResponseV1, AppError> {
+    let request = request.into_request()?;
+    run_text_processing(&state, request).await.map(api::ProcessTextResponseV1::from)
+}
+
+/// Tell the frontend which API version this backend speaks and whether it can serve it
+#[tauri::command]
+async fn api_handshake(request: api::HandshakeRequest) -> Result<api::HandshakeResponse, AppError> {
+    Ok(api::handshake(&request))
+}
+
+async fn run_text_processing(state: &AppState, request: ProcessingRequest) -> Result<ProcessingResult, AppError> {
+    let registry = get_error_boundary_registry();
+    let boundary = registry.get("text_processor").await
+        .unwrap_or_else(|| Arc::new(ErrorBoundary::new("text_processor".to_string(), None)));
+
+    with_error_boundary!(boundary, async {
+        let text_processor_state = state.text_processor.lock().await;
+        
+        if let Some(ref processor) = *text_processor_state {
+            let result = processor.process_text(request).await
+                .map_err(|e| AppError::TextProcessing(e.to_string().into()))?;
+            Ok(result)
+        } else {
+            Err(AppError::TextProcessing(TextProcessingError::NotInitialized))
+        }
+    }).await
+}
+
+#[tauri::command]
+async fn get_keyword_boost(state: State<'_, AppState>) -> Result<KeywordBoostSettings, AppError> {
+    Ok(state.settings.snapshot().keyword_boost.clone())
+}
+
+/// Create or replace a boost profile's keywords, returning them cleaned up
+#[tauri::command]
+async fn set_boost_keywords(
+    profile: String,
+    keywords: Vec<String>,
+    state: State<'_, AppState>,
+) -> Result<Vec<String>, AppError> {
+    let profile = validate_config_value(&profile, "profile")
+        .map_err(|e| AppError::Validation(e.to_string().into()))?;
+    let keywords = integrations::keyword_boost::clean_keywords(keywords)
+        .map_err(|e| AppError::Validation(ValidationError::InvalidConfigValue(e)))?;
+    let active = {
+        let mut settings = state.settings.write().await;
+        settings.keyword_boost.profiles.insert(profile.clone(), keywords.clone());
+        profiles::save_profile_settings(&storage::active_profile_id(), &settings)?;
+        settings.keyword_boost.active_profile.as_deref() == Some(profile.as_str())
+    };
+    if active {
+        apply_keyword_boost(&state).await;
+    }
+    Ok(keywords)
+}
+
+#[tauri::command]
+async fn delete_boost_profile(profile: String, state: State<'_, AppState>) -> Result<bool, AppError> {
+    let (removed, was_active) = {
+        let mut settings = state.settings.write().await;
+        let removed = settings.keyword_boost.profiles.remove(&profile).is_some();
+        let was_active = settings.keyword_boost.active_profile.as_deref() == Some(profile.as_str());
+        if was_active {
+            settings.keyword_boost.active_profile = None;
+        }
+        if removed || was_active {
+            profiles::save_profile_settings(&storage::active_profile_id(), &settings)?;
+        }
+        (removed, was_active)
+    };
+    if was_active {
+        apply_keyword_boost(&state).await;
+    }
+    Ok(removed)
+}
+
+/// Boost `profile`'s keywords from now on, or none with `None`
+#[tauri::command]
+async fn activate_boost_profile(profile: Option<String>, state: State<'_, AppState>) -> Result<Vec<String>, AppError> {
+    {
+        let mut settings = state.settings.write().await;
+        if let Some(name) = &profile {
+            if !settings.keyword_boost.profiles.contains_key(name) {
+                return Err(AppError::Configuration(format!("There is no boost profile named '{}'", name)));
+            }
+        }
+        settings.keyword_boost.active_profile = profile;
+        profiles::save_profile_settings(&storage::active_profile_id(), &settings)?;
+    }
+    apply_keyword_boost(&state).await;
+    Ok(state.settings.snapshot().keyword_boost.active_keywords())
+}
+
+/// Revise the last dictation with `instruction`, e.g. "more formal", keeping earlier revisions in effect
+#[tauri::command]
+async fn refine_last_result(
+    instruction: String,
+    state: State<'_, AppState>,
+    window: Window,
+) -> Result<refinement::RefinementOutcome, AppError> {
+    let validated_instruction = validate_text(&instruction, Some(1), Some(500))
+        .map_err(|e| AppError::Validation(e.to_string().into()))?;
+    let outcome = refinement::refine_last_result(&state, validated_instruction.trim()).await?;
+    let _ = window.emit("result-refined", &outcome);
+    Ok(outcome)
+}
+
+/// The dictation being refined and the revisions applied to it so far
+#[tauri::command]
+async fn get_refinement_history() -> Result<Option<refinement::RefinementHistory>, AppError> {
+    Ok(refinement::current_history().await)
+}
+
+/// Type text into the focused app with the backend this session supports, or copy it when none does,
+/// and record the insertion so it can be undone
+#[tauri::command]
+async fn inject_text(text: String, window: Window) -> Result<platform::InjectionReceipt, AppError> {
+    let validated_text = validate_text(&text, Some(1), Some(50000))
+        .map_err(|e| AppError::Validation(e.to_string().into()))?;
+    if let Some(reason) = injection_safety::injection_blocked() {
+        return Err(AppError::Permission(reason));
+    }
+    let receipt = platform::inject_text(&validated_text).await?;
+    if receipt.needs_paste {
+        let _ = window.emit("injection-fallback", &receipt);
+        undo_history::record(undo_history::TextAction::ClipboardWrite {
+            text: validated_text,
+            previous: receipt.previous_clipboard.clone(),
+        })
+        .await;
+    } else {
+        undo_history::record(undo_history::TextAction::Injection {
+            text: validated_text,
+            method: undo_history::InjectionMethod::Typed,
+            previous_clipboard: None,
+        })
+        .await;
+    }
+    Ok(receipt)
+}
+
+/// Which injection, hotkey and probing backends work in this desktop session, and why the others do not
+#[tauri::command]
+async fn get_platform_capabilities() -> Result<platform::PlatformCapabilities, AppError> {
+    Ok(platform::capabilities().await)
+}
+
+/// Called by the frontend after it typed or pasted text, so the insertion can be undone
+#[tauri::command]
+async fn record_text_injection(
+    text: String,
+    method: undo_history::InjectionMethod,
+    previous_clipboard: Option<String>,
+) -> Result<undo_history::ActionRecord, AppError> {
+    if text.is_empty() {
+        return Err(AppError::Validation(ValidationError::EmptyInput));
+    }
+    Ok(undo_history::record(undo_history::TextAction::Injection {
+        text,
+        method,
+        previous_clipboard,
+    })
+    .await)
+}
+
+/// Revert the last injection, refinement, correction or clipboard write; the frontend performs the returned steps
+#[tauri::command]
+async fn undo_last_action(state: State<'_, AppState>, window: Window) -> Result<undo_history::UndoOutcome, AppError> {
+    let outcome = undo_history::undo_last_action(&state).await?;
+    let _ = window.emit("action-undone", &outcome);
+    Ok(outcome)
+}
+
+/// Repeat the last undone action
+#[tauri::command]
+async fn redo_action(state: State<'_, AppState>, window: Window) -> Result<undo_history::UndoOutcome, AppError> {
+    let outcome = undo_history::redo_action(&state).await?;
+    let _ = window.emit("action-redone", &outcome);
+    Ok(outcome)
+}
+
+#[tauri::command]
+async fn get_undo_history() -> Result<undo_history::UndoHistory, AppError> {
+    Ok(undo_history::current_history().await)
+}
+
+#[tauri::command]
+async fn list_domain_packs(state: State<'_, AppState>) -> Result<domain_packs::DomainPackSettings, AppError> {
+    Ok(state.settings.snapshot().domain_packs.clone())
+}
+
+/// Full contents of an installed pack: vocabulary, glossary, prompt templates and presets
+#[tauri::command]
+async fn get_domain_pack(id: String) -> Result<domain_packs::DomainPack, AppError> {
+    domain_packs::load_pack(&id)
+}
+
+/// Install or upgrade the pack file at `path`; it must be signed by a trusted key unless unsigned packs are allowed
+#[tauri::command]
+async fn install_domain_pack(
+    path: String,
+    allow_downgrade: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<domain_packs::InstalledPack, AppError> {
+    let contents = tokio::fs::read_to_string(&path)
+        .await
+        .map_err(|e| AppError::Configuration(format!("Could not read {}: {}", path, e)))?;
+    let (installed, active) = {
+        let mut guard = state.settings.write().await;
+        let settings = &mut *guard;
+        let installed = domain_packs::install_pack(
+            &mut settings.domain_packs,
+            &mut settings.keyword_boost,
+            &contents,
+            allow_downgrade.unwrap_or(false),
+        )?;
+        profiles::save_profile_settings(&storage::active_profile_id(), settings)?;
+        (installed, settings.domain_packs.active_pack.clone())
+    };
+    // An upgraded active pack brings new keywords and instructions
+    if active.as_deref() == Some(installed.id.as_str()) {
+        apply_domain_pack(&state).await;
+    }
+    Ok(installed)
+}
+
+#[tauri::command]
+async fn uninstall_domain_pack(id: String, state: State<'_, AppState>) -> Result<domain_packs::InstalledPack, AppError> {
+    let removed = {
+        let mut guard = state.settings.write().await;
+        let settings = &mut *guard;
+        let removed = domain_packs::uninstall_pack(&mut settings.domain_packs, &mut settings.keyword_boost, &id)?;
+        profiles::save_profile_settings(&storage::active_profile_id(), settings)?;
+        removed
+    };
+    apply_domain_pack(&state).await;
+    Ok(removed)
+}
+
+/// Switch to a pack, optionally one of its presets, or back to no pack with `None`
+#[tauri::command]
+async fn activate_domain_pack(
+    pack_id: Option<String>,
+    preset: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<domain_packs::DomainPackSettings, AppError> {
+    let domain_settings = {
+        let mut settings = state.settings.write().await;
+        let previous_pack = settings.domain_packs.active_pack.clone();
+        let profile = domain_packs::activate(&mut settings.domain_packs, pack_id, preset)?;
+        match profile {
+            Some(profile) => settings.keyword_boost.active_profile = Some(profile),
+            // Leaving a pack stops boosting its keywords, but keeps a profile the user picked themselves
+            None => {
+                let from_pack = previous_pack
+                    .and_then(|id| settings.domain_packs.find(&id).cloned())
+                    .map_or(false, |pack| {
+                        settings.keyword_boost.active_profile.as_ref().map_or(false, |p| pack.boost_profiles.contains(p))
+                    });
+                if from_pack {
+                    settings.keyword_boost.active_profile = None;
+                }
+            }
+        }
+        profiles::save_profile_settings(&storage::active_profile_id(), &settings)?;
+        settings.domain_packs.clone()
+    };
+    apply_domain_pack(&state).await;
+    Ok(domain_settings)
+}
+
+/// Accept packs signed with `public_key`, a base64 Ed25519 key
+#[tauri::command]
+async fn trust_domain_pack_key(
+    key_id: String,
+    public_key: String,
+    state: State<'_, AppState>,
+) -> Result<domain_packs::DomainPackSettings, AppError> {
+    if key_id.trim().is_empty() {
+        return Err(AppError::Validation(ValidationError::EmptyInput));
+    }
+    domain_packs::decode_public_key(&key_id, &public_key)?;
+    let mut settings = state.settings.write().await;
+    settings.domain_packs.trusted_keys.insert(key_id.trim().to_string(), public_key.trim().to_string());
+    profiles::save_profile_settings(&storage::active_profile_id(), &settings)?;
+    Ok(settings.domain_packs.clone())
+}
+
+#[tauri::command]
+async fn list_plugins() -> Result<Vec<plugins::PluginInfo>, AppError> {
+    Ok(plugins::list_plugins().await)
+}
+
+/// Install the plugin folder at `path`; it stays disabled until enabled with the permissions it declares
+#[tauri::command]
+async fn install_plugin(path: String) -> Result<plugins::PluginInfo, AppError> {
+    plugins::install_plugin(std::path::Path::new(&path)).await
+}
+
+/// Remove a plugin for all profiles
+#[tauri::command]
+async fn uninstall_plugin(id: String, state: State<'_, AppState>) -> Result<(), AppError> {
+    let mut settings = state.settings.write().await;
+    plugins::uninstall_plugin(&id, &mut settings.plugins).await?;
+    profiles::save_profile_settings(&storage::active_profile_id(), &settings)
+}
+
+/// Turn a plugin on; `granted` must include every permission its manifest asks for
+#[tauri::command]
+async fn enable_plugin(
+    id: String,
+    granted: plugins::PluginPermissions,
+    state: State<'_, AppState>,
+) -> Result<plugins::PluginInfo, AppError> {
+    let mut settings = state.settings.write().await;
+    let info = plugins::enable_plugin(&id, granted, &mut settings.plugins).await?;
+    profiles::save_profile_settings(&storage::active_profile_id(), &settings)?;
+    Ok(info)
+}
+
+#[tauri::command]
+async fn disable_plugin(id: String, state: State<'_, AppState>) -> Result<plugins::PluginInfo, AppError> {
+    let mut settings = state.settings.write().await;
+    let info = plugins::disable_plugin(&id, &mut settings.plugins).await?;
+    profiles::save_profile_settings(&storage::active_profile_id(), &settings)?;
+    Ok(info)
+}
+
+/// Restart a crashed plugin and close its circuit breaker
+#[tauri::command]
+async fn restart_plugin(id: String) -> Result<plugins::PluginInfo, AppError> {
+    plugins::restart_plugin(&id).await
+}
+
+/// The active profile's dictation stages and any problems with their order
+#[tauri::command]
+async fn get_pipeline(
+    state: State<'_, AppState>,
+) -> Result<(pipeline::PipelineSettings, pipeline::PipelineValidation), AppError> {
+    let pipeline = state.settings.snapshot().pipeline.clone();
+    let validation = pipeline::validate(&pipeline);
+    Ok((pipeline, validation))
+}
+
+/// Check a pipeline without saving it
+#[tauri::command]
+async fn validate_pipeline(pipeline: pipeline::PipelineSettings) -> Result<pipeline::PipelineValidation, AppError> {
+    Ok(pipeline::validate(&pipeline))
+}
+
+/// Replace the active profile's dictation stages; returns warnings about the order
+#[tauri::command]
+async fn set_pipeline(pipeline: pipeline::PipelineSettings, state: State<'_, AppState>) -> Result<Vec<String>, AppError> {
+    let warnings = pipeline::validate(&pipeline).into_result()?;
+    let mut settings = state.settings.write().await;
+    settings.pipeline = pipeline;
+    profiles::save_profile_settings(&storage::active_profile_id(), &settings)?;
+    Ok(warnings)
+}
+
+/// Begin filling `schema` by voice; dictation answers its fields until it is complete or cancelled
+#[tauri::command]
+async fn start_form_session(
+    schema: form_filling::FormSchema,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<form_filling::FormSessionStatus, AppError> {
+    form_filling::start_session(&state, &app, schema).await
+}
+
+/// Answer the current field with typed text, or with "skip", "go back" or "cancel"
+#[tauri::command]
+async fn submit_form_answer(
+    session_id: String,
+    answer: String,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<form_filling::FormSessionStatus, AppError> {
+    form_filling::answer(&state, &app, Some(&session_id), &answer).await
+}
+
+#[tauri::command]
+async fn get_form_session(session_id: String) -> Result<Option<form_filling::FormSessionStatus>, AppError> {
+    Ok(form_filling::session_status(&session_id).await)
+}
+
+#[tauri::command]
+async fn cancel_form_session(session_id: String, app: AppHandle) -> Result<bool, AppError> {
+    Ok(form_filling::cancel_session(&app, &session_id).await)
+}
+
+#[tauri::command]
+async fn list_snippets(state: State<'_, AppState>) -> Result<Vec<Snippet>, AppError> {
+    Ok(state.settings.snapshot().snippets.snippets.clone())
+}
+
+/// Create a snippet, or update the one with the same ID
+#[tauri::command]
+async fn save_snippet(snippet: Snippet, state: State<'_, AppState>) -> Result<Snippet, AppError> {
+    let mut settings = state.settings.write().await;
+    let snippet = integrations::snippets::prepare_snippet(snippet, &settings.snippets.snippets)
+        .map_err(|e| AppError::Validation(ValidationError::InvalidConfigValue(e)))?;
+    match settings.snippets.snippets.iter_mut().find(|known| known.id == snippet.id) {
+        Some(known) => *known = snippet.clone(),
+        None => settings.snippets.snippets.push(snippet.clone()),
+    }
+    profiles::save_profile_settings(&storage::active_profile_id(), &settings)?;
+    Ok(snippet)
+}
+
+#[tauri::command]
+async fn delete_snippet(id: String, state: State<'_, AppState>) -> Result<bool, AppError> {
+    let mut settings = state.settings.write().await;
+    let before = settings.snippets.snippets.len();
+    settings.snippets.snippets.retain(|snippet| snippet.id != id);
+    let removed = settings.snippets.snippets.len() != before;
+    if removed {
+        profiles::save_profile_settings(&storage::active_profile_id(), &settings)?;
+    }
+    Ok(removed)
+}
+
+/// Expand a snippet by name or alias without typing it, e.g. for a preview or a snippet picker
+#[tauri::command]
+async fn expand_snippet_by_name(name: String, state: State<'_, AppState>) -> Result<SnippetExpansion, AppError> {
+    let snippet = state
+        .settings
+        .lock()
+        .await
+        .snippets
+        .find(&name)
+        .cloned()
+        .ok_or_else(|| AppError::Configuration(format!("There is no snippet named '{}'", name)))?;
+    expand_snippet(&state, &snippet).await
+}
+
+/// Preview how spoken code is formatted, in the configured language unless another is given
+#[tauri::command]
+async fn format_code_dictation(
+    text: String,
+    language: Option<CodeLanguage>,
+    state: State<'_, AppState>,
+) -> Result<CodeFormatResult, AppError> {
+    let validated_text = validate_text(&text, Some(1), Some(5000))
+        .map_err(|e| AppError::Validation(e.to_string().into()))?;
+    let language = match language {
+        Some(language) => language,
+        None => state.settings.snapshot().code_dictation.language,
+    };
+    Ok(format_code(&validated_text, language))
+}
+
+/// Switch dictation between prose and code, optionally changing the code language
+#[tauri::command]
+async fn set_code_dictation_mode(
+    enabled: bool,
+    language: Option<CodeLanguage>,
+    state: State<'_, AppState>,
+) -> Result<CodeDictationSettings, AppError> {
+    let code_dictation = {
+        let mut settings = state.settings.write().await;
+        settings.code_dictation.enabled = enabled;
+        if let Some(language) = language {
+            settings.code_dictation.language = language;
+        }
+        settings.code_dictation.clone()
+    };
+    if let Some(processor) = state.text_processor.lock().await.as_mut() {
+        processor.set_code_language(code_dictation.language);
+    }
+    Ok(code_dictation)
+}
+
+#[tauri::command]
+async fn get_supported_languages_tauri() -> Result<Vec<Language>, String> {
+    Ok(get_supported_languages())
+}
+
+#[tauri::command]
+async fn is_language_supported_tauri(language_code: String) -> Result<bool, AppError> {
+    // Validate language code input
+    let validated_code = validate_language_code(&language_code)
+        .map_err(|e| AppError::Validation(e.to_string().into()))?;
+    
+    Ok(is_language_supported(&validated_code))
+}
+
+/// Languages known to the registry, optionally only those supporting one capability
+#[tauri::command]
+async fn list_languages(
+    capability: Option<LanguageCapability>,
+    include_disabled: Option<bool>,
+) -> Result<Vec<LanguageStatus>, AppError> {
+    let registry = get_language_registry()
+        .read()
+        .map_err(|_| AppError::Internal("Language registry lock poisoned".to_string()))?;
+    Ok(registry.languages(capability, include_disabled.unwrap_or(false)))
+}
+
+/// Models the app knows with their context window, modality, price and speed, optionally of one modality
+#[tauri::command]
+async fn list_models_registry(
+    modality: Option<ModelModality>,
+) -> Result<Vec<integrations::model_registry::ModelEntry>, AppError> {
+    Ok(integrations::model_registry::get_model_registry().models(modality))
+}
+
+#[tauri::command]
+async fn set_language_enabled(code: String, enabled: bool, state: State<'_, AppState>) -> Result<(), AppError> {
+    let validated_code = validate_language_code(&code)
+        .map_err(|e| AppError::Validation(e.to_string().into()))?;
+
+    if !enabled && state.settings.snapshot().language == validated_code {
+        return Err(AppError::Configuration(format!(
+            "{} is the active language; choose another language before disabling it",
+            validated_code
+        )));
+    }
+
+    get_language_registry()
+        .write()
+        .map_err(|_| AppError::Internal("Language registry lock poisoned".to_string()))?
+        .set_enabled(&validated_code, enabled)
+}
+
+/// Download models and punctuation rules for a language, reporting progress to the UI
+#[tauri::command]
+async fn download_language_resources(code: String, window: Window) -> Result<Vec<String>, AppError> {
+    let validated_code = validate_language_code(&code)
+        .map_err(|e| AppError::Validation(e.to_string().into()))?;
+
+    let installed = integrations::language_registry::download_language_resources(&validated_code, |progress| {
+        let _ = window.emit("language-download-progress", &progress);
+    })
+    .await?;
+    tracing::info!("Installed {} resource(s) for {}", installed.len(), validated_code);
+    Ok(installed)
+}
+
+/// Local speech models, installed or available, with disk usage against the quota
+#[tauri::command]
+async fn list_models(state: State<'_, AppState>) -> Result<ModelInventory, AppError> {
+    let settings = state.settings.snapshot().models.clone();
+    integrations::model_manager::list_models(&settings)
+}
+
+/// Download a catalog model, or a custom Whisper/GGUF model from `url`, reporting progress to the UI
+#[tauri::command]
+async fn download_model(
+    model_id: String,
+    url: Option<String>,
+    sha256: Option<String>,
+    state: State<'_, AppState>,
+    window: Window,
+) -> Result<InstalledModel, AppError> {
+    let settings = state.settings.snapshot().models.clone();
+    let installed = integrations::model_manager::download_model(&model_id, url, sha256, &settings, |progress| {
+        let _ = window.emit("model-download-progress", &progress);
+    })
+    .await?;
+    tracing::info!("Installed model {} ({} bytes)", installed.id, installed.size_bytes);
+    Ok(installed)
+}
+
+#[tauri::command]
+async fn remove_model(model_id: String) -> Result<(), AppError> {
+    integrations::model_manager::remove_model(&model_id)
+}
+
+// Original Tauri commands (updated)
+#[tauri::command]
+async fn get_settings(state: State<'_, AppState>) -> Result<Settings, AppError> {
+    Ok(Settings::clone(&state.settings.snapshot()))
+}
+
+#[tauri::command]
+async fn update_settings(new_settings: Settings, state: State<'_, AppState>, app: AppHandle) -> Result<(), AppError> {
+    // Validate settings inputs
+    let validated_language = validate_language_code(&new_settings.language)
+        .map_err(|e| AppError::Validation(e.to_string().into()))?;
+    
+    let validated_hotkey = validate_hotkey(&new_settings.hotkey)
+        .map_err(|e| AppError::Validation(e.to_string().into()))?;
+    
+    let validated_theme: Theme = new_settings.theme.parse()?;
+    let validated_voice_model = validate_model_id(&new_settings.voice_model)?;
+    validate_url(&new_settings.ai_ml_settings.base_url)?;
+    let ai = &new_settings.ai_ml_settings;
+    let models = integrations::model_registry::get_model_registry();
+    models.check("the default model", &ai.default_model, ModelModality::Text)?;
+    models.check("the text model", &ai.text_model, ModelModality::Text)?;
+    models.check("the translation model", &ai.translation_model, ModelModality::Text)?;
+    models.check("the context model", &ai.context_model, ModelModality::Text)?;
+    models.check("the voice model", &ai.voice_model, ModelModality::Speech)?;
+
+    let validated_read_aloud_hotkey = validate_hotkey(&new_settings.read_aloud.hotkey)
+        .map_err(|e| AppError::Validation(e.to_string().into()))?;
+    validate_numeric_value(new_settings.read_aloud.max_chars, 100, 100000, "max_chars")
+        .map_err(|e| AppError::Validation(e.to_string().into()))?;
+
+    validate_numeric_value(new_settings.assistant.max_tool_rounds, 1, 10, "max_tool_rounds")
+        .map_err(|e| AppError::Validation(e.to_string().into()))?;
+    validate_numeric_value(new_settings.feedback.exploration, 0.0, 5.0, "exploration")
+        .map_err(|e| AppError::Validation(e.to_string().into()))?;
+    for tool in &new_settings.assistant.webhook_tools {
+        assistant::validate_webhook_tool(tool)?;
+    }
+    if let Some(profile) = &new_settings.keyword_boost.active_profile {
+        if !new_settings.keyword_boost.profiles.contains_key(profile) {
+            return Err(AppError::Validation(ValidationError::InvalidConfigValue(format!(
+                "Active boost profile '{}' does not exist",
+                profile
+            ))));
+        }
+    }
+    if let Some(pack) = &new_settings.domain_packs.active_pack {
+        if new_settings.domain_packs.find(pack).is_none() {
+            return Err(AppError::Validation(ValidationError::InvalidConfigValue(format!(
+                "Active domain pack '{}' is not installed",
+                pack
+            ))));
+        }
+    }
+    for (key_id, public_key) in &new_settings.domain_packs.trusted_keys {
+        domain_packs::decode_public_key(key_id, public_key)?;
+    }
+    pipeline::validate(&new_settings.pipeline).into_result()?;
+    crash_reports::validate(&new_settings.crash_reports)?;
+    updater::validate(&new_settings.updates)?;
+    network::validate(&new_settings.network)?;
+    integrations::caption_filter::validate(&new_settings.streaming.profanity_filter)?;
+    integrations::result_cache::validate(&new_settings.result_cache)?;
+    integrations::emotion_tracking::validate(&new_settings.voice_recognition.emotion_tracking)?;
+    integrations::hypothesis_merge::validate(&new_settings.voice_recognition.hypothesis_merge)?;
+    caret::validate(&new_settings.overlay)?;
+    integrations::audio_capture::validate(&new_settings.audio_capture)?;
+    power::validate(&new_settings.power)?;
+    command_limits::validate(&new_settings.command_limits)?;
+    integrations::long_audio::validate(&new_settings.long_audio)?;
+    auto_submit::validate(&new_settings.auto_submit)?;
+    live_typing::validate(&new_settings.live_typing)?;
+    integrations::stt_providers::validate(&new_settings.speech_to_text)?;
+    integrations::provider_failover::validate(&new_settings.ai_ml_settings.failover)?;
+    cost_estimate::validate(&new_settings.cost_guard)?;
+    session_stats::validate(&new_settings.session_stats)?;
+    screen_ocr::validate(&new_settings.screen_ocr)?;
+
+    for folder in &new_settings.jobs.watch_folders {
+        if !std::path::Path::new(&folder.path).is_absolute() {
+            return Err(AppError::Validation(ValidationError::InvalidConfigValue(format!(
+                "Watch folder must be an absolute path: {}",
+                folder.path
+            ))));
+        }
+        if let jobs::WatchTrigger::Daily { hour, minute } = folder.trigger {
+            validate_numeric_value(hour, 0, 23, "hour")
+                .map_err(|e| AppError::Validation(e.to_string().into()))?;
+            validate_numeric_value(minute, 0, 59, "minute")
+                .map_err(|e| AppError::Validation(e.to_string().into()))?;
+        }
+    }
+
+    let mut settings = state.settings.write().await;
+    
+    // Update with validated values
+    let mut validated_settings = new_settings;
+    validated_settings.language = validated_language;
+    validated_settings.voice_model = validated_voice_model;
+    validated_settings.hotkey = validated_hotkey.to_string();
+    validated_settings.theme = validated_theme.to_string();
+    validated_settings.read_aloud.hotkey = validated_read_aloud_hotkey.to_string();
+    
+    get_caption_streamer().lock().await.configure(&validated_settings.streaming);
+    audit::configure(&validated_settings.audit);
+    command_limits::configure(&validated_settings.command_limits);
+    let changed_pronunciation =
+        Some(validated_settings.pronunciation.clone()).filter(|pronunciation| *pronunciation != settings.pronunciation);
+    let keyword_boost_changed = validated_settings.keyword_boost != settings.keyword_boost;
+    let domain_pack_changed = validated_settings.domain_packs.active_pack != settings.domain_packs.active_pack
+        || validated_settings.domain_packs.active_preset != settings.domain_packs.active_preset;
+    // The handler reads the other read-aloud options when it fires, so only a new binding needs re-registering
+    if validated_settings.read_aloud.hotkey != settings.read_aloud.hotkey
+        || validated_settings.read_aloud.enabled != settings.read_aloud.enabled
+    {
+        read_aloud::register_hotkey(&app, Some(&settings.read_aloud.hotkey), &validated_settings.read_aloud)?;
+    }
+    let network_changed = validated_settings.network != settings.network;
+    if network_changed {
+        // Also loads the CA bundle, so a missing file is reported before anything is saved
+        network::configure(&validated_settings.network)?;
+    }
+    integrations::result_cache::configure(
+        &validated_settings.result_cache,
+        validated_settings.voice_recognition.privacy_mode,
+    );
+    integrations::emotion_tracking::configure(&validated_settings.voice_recognition.emotion_tracking);
+    integrations::translation_formality::configure(&validated_settings.formality);
+    let ducking_disabled = !validated_settings.ducking.enabled;
+    let changed_code_language = Some(validated_settings.code_dictation.language)
+        .filter(|language| *language != settings.code_dictation.language);
+    *settings = validated_settings;
+    drop(settings);
+
+    if let Some(language) = changed_code_language {
+        if let Some(processor) = state.text_processor.lock().await.as_mut() {
+            processor.set_code_language(language);
+        }
+    }
+
+    if ducking_disabled {
+        audio_ducking::release(audio_ducking::DuckReason::Dictation, &app).await;
+        audio_ducking::release(audio_ducking::DuckReason::Speech, &app).await;
+    }
+
+    // Pushed once the new settings are committed, so the gateway never runs ahead of what readers see
+    if let Some(pronunciation) = changed_pronunciation {
+        if let Some(gateway) = state.ai_ml_gateway.lock().await.as_ref() {
+            gateway.set_pronunciations(pronunciation).await;
+        }
+    }
+    if network_changed {
+        if let Some(gateway) = state.ai_ml_gateway.lock().await.as_ref() {
+            gateway.reload_network().await.map_err(AppError::from)?;
+        }
+    }
+    if domain_pack_changed {
+        apply_domain_pack(&state).await;
+    } else if keyword_boost_changed {
+        apply_keyword_boost(&state).await;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_voice_status(state: State<'_, AppState>) -> Result<HashMap<String, serde_json::Value>, String> {
+    let voice_engine_state = state.voice_engine.lock().await;
+    
+    let mut status = HashMap::new();
+    if let Some(ref engine) = *voice_engine_state {
+        let engine_status = engine.status();
+        status.insert("state".to_string(), serde_json::to_value(&engine_status.state).unwrap_or_default());
+        status.insert("is_listening".to_string(), serde_json::Value::Bool(engine_status.is_listening));
+        status.insert("engine_type".to_string(), serde_json::Value::String(engine_status.engine_type));
+        status.insert("session_id".to_string(), serde_json::Value::String(engine_status.session_id));
+        status.insert("language".to_string(), serde_json::Value::String(engine_status.config.language));
+    } else {
+        status.insert("state".to_string(), serde_json::json!({ "state": "uninitialized" }));
+        status.insert("is_listening".to_string(), serde_json::Value::Bool(false));
+        status.insert("engine_type".to_string(), serde_json::Value::String("none".to_string()));
+    }
+    
+    Ok(status)
+}
+
+/// Open the microphone briefly and report the format negotiated with it, whether audio arrived and,
+/// when it failed, whether another app holds the device
+#[tauri::command]
+async fn diagnose_audio_capture(
+    state: State<'_, AppState>,
+) -> Result<integrations::audio_capture::CaptureDiagnosis, AppError> {
+    let settings = {
+        let settings = state.settings.snapshot();
+        bluetooth_audio::apply_input_preference(&settings.audio_capture, &settings.bluetooth_audio)
+    };
+    Ok(integrations::audio_capture::diagnose(&settings).await)
+}
+
+/// Microphones recording the current session when two are configured, and which devices took over after a loss
+#[tauri::command]
+async fn get_multi_mic_status() -> Result<Option<integrations::multi_mic::MultiMicStatus>, AppError> {
+    Ok(integrations::multi_mic::status())
+}
+
+/// Power source, battery level and what listening currently gives up to save power, probed fresh
+#[tauri::command]
+async fn get_power_profile(state: State<'_, AppState>) -> Result<power::PowerProfile, AppError> {
+    let settings = state.settings.snapshot().power.clone();
+    Ok(power::refresh(&settings).await)
+}
+
+/// Calls allowed and throttled per command category since startup
+#[tauri::command]
+async fn get_command_rate_metrics() -> Result<Vec<command_limits::CategoryMetrics>, AppError> {
+    Ok(command_limits::metrics())
+}
+
+/// Whether the default microphone is muted in hardware or by the OS, probed fresh
+#[tauri::command]
+async fn get_mic_mute_state() -> Result<mic_state::MicMuteState, AppError> {
+    Ok(mic_state::refresh().await)
+}
+
+/// Connected Bluetooth headset, its profile and whether dictation records through it, probed fresh
+#[tauri::command]
+async fn get_bluetooth_audio_status(
+    state: State<'_, AppState>,
+) -> Result<bluetooth_audio::BluetoothAudioStatus, AppError> {
+    let capturing = match state.voice_engine.lock().await.clone() {
+        Some(engine) => engine.status().state.captures_audio(),
+        None => false,
+    };
+    Ok(bluetooth_audio::refresh(capturing).await)
+}
+
+/// Readiness report for the onboarding flow
+#[tauri::command]
+async fn run_system_checks(state: State<'_, AppState>) -> Result<system_checks::SystemCheckReport, AppError> {
+    let settings = state.settings.snapshot();
+    Ok(system_checks::run_system_checks(&settings).await)
+}
+
+/// Export all user data into a new folder under `path`
+#[tauri::command]
+async fn export_all_user_data(
+    path: String,
+    state: State<'_, AppState>,
+) -> Result<data_management::ExportReport, AppError> {
+    data_management::export_all_user_data(std::path::Path::new(&path), &state).await
+}
+
+/// Purge the given data categories; `dry_run` reports what would be deleted without deleting it
+#[tauri::command]
+async fn purge_all_user_data(
+    categories: Vec<data_management::DataCategory>,
+    dry_run: bool,
+    state: State<'_, AppState>,
+) -> Result<data_management::PurgeReport, AppError> {
+    if categories.is_empty() {
+        return Err(AppError::Validation("At least one data category is required".to_string().into()));
+    }
+
+    let report = data_management::purge_all_user_data(&categories, dry_run, &state).await;
+    if !dry_run {
+        tracing::info!("Purged {} items ({} bytes) across {:?}", report.items.len(), report.total_bytes, categories);
+    }
+    Ok(report)
+}
+
+/// Audited AI requests matching `filter`, newest first
+#[tauri::command]
+async fn get_audit_log(filter: Option<audit::AuditFilter>) -> Result<Vec<audit::AuditEntry>, AppError> {
+    let filter = filter.unwrap_or_default();
+    tokio::task::spawn_blocking(move || audit::read(&filter))
+        .await
+        .map_err(|e| AppError::Internal(format!("Reading the audit log failed: {}", e)))?
+}
+
+/// Write audited requests matching `filter` to `path` as JSON or CSV
+#[tauri::command]
+async fn export_audit_log(
+    path: String,
+    format: audit::AuditExportFormat,
+    filter: Option<audit::AuditFilter>,
+) -> Result<audit::AuditExport, AppError> {
+    let filter = filter.unwrap_or_default();
+    tokio::task::spawn_blocking(move || audit::export(std::path::Path::new(&path), format, &filter))
+        .await
+        .map_err(|e| AppError::Internal(format!("Audit log export failed: {}", e)))?
+}
+
+/// Crash reports kept on this machine, newest first; `seen` is false for crashes not shown to the user yet
+#[tauri::command]
+async fn list_crash_reports() -> Result<Vec<crash_reports::CrashReport>, AppError> {
+    tokio::task::spawn_blocking(crash_reports::list)
+        .await
+        .map_err(|e| AppError::Internal(format!("Listing crash reports failed: {}", e)))?
+}
+
+/// Mark crash reports as shown; with no ids every report is marked
+#[tauri::command]
+async fn acknowledge_crash_reports(ids: Option<Vec<String>>) -> Result<usize, AppError> {
+    tokio::task::spawn_blocking(move || crash_reports::mark_seen(&ids.unwrap_or_default()))
+        .await
+        .map_err(|e| AppError::Internal(format!("Updating crash reports failed: {}", e)))?
+}
+
+/// Send one report to the configured endpoint; fails unless the user opted in to uploads
+#[tauri::command]
+async fn upload_crash_report(id: String, state: State<'_, AppState>) -> Result<crash_reports::CrashReport, AppError> {
+    let settings = state.settings.snapshot().crash_reports.clone();
+    crash_reports::upload(&id, &settings).await
+}
+
+#[tauri::command]
+async fn delete_crash_report(id: String) -> Result<bool, AppError> {
+    tokio::task::spawn_blocking(move || crash_reports::delete(&id))
+        .await
+        .map_err(|e| AppError::Internal(format!("Deleting the crash report failed: {}", e)))?
+}
+
+/// Step through proxy, CA bundle, DNS and an HTTPS request to `url` (the AI ML API by default) and report
+/// where it fails; pass `network` to try settings before saving them
+#[tauri::command]
+async fn test_network_connectivity(
+    url: Option<String>,
+    network: Option<network::NetworkSettings>,
+    state: State<'_, AppState>,
+) -> Result<network::ConnectivityReport, AppError> {
+    let (current, base_url) = {
+        let settings = state.settings.snapshot();
+        (settings.network.clone(), settings.ai_ml_settings.base_url.clone())
+    };
+    let url = url.unwrap_or(base_url);
+    Ok(network::test_connectivity(&network.unwrap_or(current), &url).await)
+}
+
+/// Bytes sent to and received from each AI service since launch or the last reset, with what upload
+/// compression saved and the measured upload throughput
+#[tauri::command]
+async fn get_traffic_metrics(reset: Option<bool>) -> Result<integrations::traffic::TrafficReport, AppError> {
+    let report = integrations::traffic::report();
+    if reset.unwrap_or(false) {
+        integrations::traffic::reset();
+    }
+    Ok(report)
+}
 
-            let result = gateway.process_enhanced_text(request).await
-                .map_err(|e| AppError::Custom(format!("Enhanced text processing failed: {}", e)))?;
-            
-            Ok(result)
-        } else {
-            Err(AppError::Custom("AI ML API Gateway not initialized".to_string()))
-        }
-    }).await
+/// Size, budget and hit rate of each result cache
+#[tauri::command]
+async fn get_cache_stats(state: State<'_, AppState>) -> Result<Vec<integrations::result_cache::CacheStats>, AppError> {
+    startup::ensure_started(&state, startup::Service::AiGateway).await?;
+    let gateway_state = state.ai_ml_gateway.lock().await;
+    let gateway = gateway_state
+        .as_ref()
+        .ok_or_else(|| AppError::NotInitialized("AI ML API".to_string()))?;
+    Ok(gateway.cache_stats().await)
 }
 
+/// Empty one result cache, in memory and on disk, or every cache when `kind` is not given
 #[tauri::command]
-async fn generate_enhanced_voice(
+async fn clear_cache(
+    kind: Option<integrations::result_cache::CacheKind>,
+    state: State<'_, AppState>,
+) -> Result<usize, AppError> {
+    startup::ensure_started(&state, startup::Service::AiGateway).await?;
+    let gateway_state = state.ai_ml_gateway.lock().await;
+    let gateway = gateway_state
+        .as_ref()
+        .ok_or_else(|| AppError::NotInitialized("AI ML API".to_string()))?;
+    Ok(gateway.clear_cache(kind).await)
+}
+
+/// `text` romanized with `scheme`; in privacy mode only what the on-device tables cover, so no kanji or hanzi
+#[tauri::command]
+async fn transliterate_text(
     text: String,
-    voice_config: VoiceConfiguration,
-    language: String,
-    emotion: Option<String>,
-    speed: Option<f32>,
-    pitch: Option<f32>,
-    output_format: VoiceOutputFormat,
-    post_processing: Vec<VoicePostProcessing>,
+    scheme: integrations::transliteration::TransliterationScheme,
     state: State<'_, AppState>,
-) -> Result<VoiceResult, AppError> {
-    // Validate input
-    let validated_text = validate_text(&text, Some(1), Some(5000))
+) -> Result<integrations::transliteration::Transliteration, AppError> {
+    use integrations::transliteration::{self, needs_model};
+
+    let validated_text = validate_text(&text, Some(1), Some(50000))
         .map_err(|e| AppError::Validation(e.to_string().into()))?;
+    if !needs_model(&validated_text, scheme) {
+        return Ok(transliteration::transliterate(None, &validated_text, scheme).await?);
+    }
+    if state.settings.snapshot().voice_recognition.privacy_mode {
+        return Err(AppError::Validation(ValidationError::InvalidConfigValue(format!(
+            "Transliterating this text with {} needs the text model, which privacy mode does not allow",
+            scheme.label()
+        ))));
+    }
 
-    let registry = get_error_boundary_registry();
-    let boundary = registry.get("ai_ml_api").await
-        .unwrap_or_else(|| Arc::new(ErrorBoundary::new("ai_ml_api".to_string(), None)));
+    startup::ensure_started(&state, startup::Service::AiGateway).await?;
+    let gateway = state.ai_ml_gateway.lock().await.clone();
+    match gateway {
+        Some(gateway) => Ok(gateway.transliterate(validated_text, scheme).await?),
+        None => Err(AppError::NotInitialized("AI ML API Gateway".to_string())),
+    }
+}
 
-    with_error_boundary!(boundary, async {
-        let ai_ml_gateway_state = state.ai_ml_gateway.lock().await;
-        
-        if let Some(ref gateway) = *ai_ml_gateway_state {
-            let request = EnhancedVoiceRequest {
-                id: Uuid::new_v4().to_string(),
-                text: validated_text,
-                voice_config,
-                language,
-                emotion,
-                speed,
-                pitch,
-                output_format,
-                post_processing,
-            };
+/// Text on the screen, or in `region` of it, read on-device; needs screen OCR turned on in settings
+#[tauri::command]
+async fn read_screen_text(
+    region: Option<screen_ocr::ScreenRegion>,
+    state: State<'_, AppState>,
+) -> Result<screen_ocr::ScreenText, AppError> {
+    screen_ocr::read_screen(&state.settings.snapshot().screen_ocr, region).await
+}
 
-            let result = gateway.generate_enhanced_voice(request).await
-                .map_err(|e| AppError::Custom(format!("Voice generation failed: {}", e)))?;
-            
-            Ok(result)
-        } else {
-            Err(AppError::Custom("AI ML API Gateway not initialized".to_string()))
-        }
-    }).await
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ScreenInstructionResult {
+    screen: screen_ocr::ScreenText,
+    result: String,
 }
 
+/// Carry out a spoken instruction like "summarize this" on the text visible on screen
 #[tauri::command]
-async fn translate_with_enhancement(
-    text: String,
-    from: Option<String>,
-    to: String,
+async fn process_screen_text(
+    instruction: String,
+    region: Option<screen_ocr::ScreenRegion>,
     state: State<'_, AppState>,
-) -> Result<TranslationResult, AppError> {
-    // Validate input
-    let validated_text = validate_text(&text, Some(1), Some(8000))
+) -> Result<ScreenInstructionResult, AppError> {
+    let instruction = validate_text(&instruction, Some(1), Some(2000))
         .map_err(|e| AppError::Validation(e.to_string().into()))?;
+    screen_instruction(&state, &instruction, region).await
+}
 
-    let registry = get_error_boundary_registry();
-    let boundary = registry.get("ai_ml_api").await
-        .unwrap_or_else(|| Arc::new(ErrorBoundary::new("ai_ml_api".to_string(), None)));
+/// Screen text for the text model; refused in privacy mode and when nothing was recognized
+async fn screen_text_for_model(
+    state: &AppState,
+    region: Option<screen_ocr::ScreenRegion>,
+) -> Result<screen_ocr::ScreenText, AppError> {
+    let settings = state.settings.snapshot();
+    if settings.voice_recognition.privacy_mode {
+        return Err(AppError::Permission(
+            "Screen text is not sent to the text model in privacy mode".to_string(),
+        ));
+    }
+    let screen = screen_ocr::read_screen(&settings.screen_ocr, region).await?;
+    if screen.text.is_empty() {
+        return Err(AppError::Validation(ValidationError::InvalidConfigValue(
+            "No text was recognized on screen".to_string(),
+        )));
+    }
+    Ok(screen)
+}
 
-    with_error_boundary!(boundary, async {
-        let ai_ml_gateway_state = state.ai_ml_gateway.lock().await;
-        
-        if let Some(ref gateway) = *ai_ml_gateway_state {
-            let result = gateway.translate_with_enhancement(validated_text, from, to).await
-                .map_err(|e| AppError::Custom(format!("Translation failed: {}", e)))?;
-            
-            Ok(result)
-        } else {
-            Err(AppError::Custom("AI ML API Gateway not initialized".to_string()))
-        }
-    }).await
+/// `instruction` carried out on the screen text, for the command and for "summarize this" spoken while dictating
+async fn screen_instruction(
+    state: &AppState,
+    instruction: &str,
+    region: Option<screen_ocr::ScreenRegion>,
+) -> Result<ScreenInstructionResult, AppError> {
+    let screen = screen_text_for_model(state, region).await?;
+    startup::ensure_started(state, startup::Service::AiGateway).await?;
+    let gateway = state
+        .ai_ml_gateway
+        .lock()
+        .await
+        .clone()
+        .ok_or_else(|| AppError::NotInitialized("AI ML API Gateway".to_string()))?;
+    let model = state.settings.snapshot().ai_ml_settings.text_model.clone();
+    let result = screen_ocr::apply_instruction(&gateway, &model, instruction, &screen).await?;
+    Ok(ScreenInstructionResult { screen, result })
 }
 
+/// Entities in `text` with their positions, for highlighting; found on-device in privacy mode or without the gateway
 #[tauri::command]
-async fn process_context_aware(
-    text: String,
-    context: EnhancedContext,
-    requires_understanding: bool,
-    include_sentiment: bool,
-    include_intent: bool,
-    memory_retention: bool,
-    state: State<'_, AppState>,
-) -> Result<ContextAwareResult, AppError> {
-    // Validate input
-    let validated_text = validate_text(&text, Some(1), Some(6000))
+async fn extract_entities(text: String, state: State<'_, AppState>) -> Result<integrations::EntityExtraction, AppError> {
+    let validated_text = validate_text(&text, Some(1), Some(50000))
         .map_err(|e| AppError::Validation(e.to_string().into()))?;
+    if state.settings.snapshot().voice_recognition.privacy_mode {
+        return Ok(integrations::extract_locally(&validated_text));
+    }
 
-    let registry = get_error_boundary_registry();
-    let boundary = registry.get("ai_ml_api").await
-        .unwrap_or_else(|| Arc::new(ErrorBoundary::new("ai_ml_api".to_string(), None)));
+    if let Err(e) = startup::ensure_started(&state, startup::Service::AiGateway).await {
+        log::debug!("Extracting entities locally, AI gateway unavailable: {}", e);
+    }
+    let gateway_state = state.ai_ml_gateway.lock().await;
+    match gateway_state.as_ref() {
+        Some(gateway) => Ok(gateway.extract_entities(validated_text).await),
+        None => Ok(integrations::extract_locally(&validated_text)),
+    }
+}
 
-    with_error_boundary!(boundary, async {
-        let ai_ml_gateway_state = state.ai_ml_gateway.lock().await;
-        
-        if let Some(ref gateway) = *ai_ml_gateway_state {
-            let request = ContextAwareRequest {
-                id: Uuid::new_v4().to_string(),
-                text: validated_text,
-                context,
-                requires_understanding,
-                include_sentiment,
-                include_intent,
-                memory_retention,
-            };
+/// Look for a newer version on the configured channel, or on `channel` without switching to it
+#[tauri::command]
+async fn check_for_updates(
+    channel: Option<updater::UpdateChannel>,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<updater::UpdateInfo, AppError> {
+    let settings = state.settings.snapshot().updates.clone();
+    updater::check(&app, &settings, channel).await
+}
 
-            let result = gateway.process_context_aware(request).await
-                .map_err(|e| AppError::Custom(format!("Context processing failed: {}", e)))?;
-            
-            Ok(result)
-        } else {
-            Err(AppError::Custom("AI ML API Gateway not initialized".to_string()))
-        }
-    }).await
+/// Download and install the latest version, reporting "update-progress", and restart into it
+#[tauri::command]
+async fn install_update(state: State<'_, AppState>, app: AppHandle) -> Result<(), AppError> {
+    let settings = state.settings.snapshot().updates.clone();
+    updater::install(&app, &settings).await
 }
 
+/// Record the dictation session from here on: utterances, their audio and every AI exchange
 #[tauri::command]
-async fn get_ai_ml_health_status(
-    state: State<'_, AppState>,
-) -> Result<HealthStatus, AppError> {
-    let ai_ml_gateway_state = state.ai_ml_gateway.lock().await;
-    
-    if let Some(ref gateway) = *ai_ml_gateway_state {
-        let health_status = gateway.check_health().await
-            .map_err(|e| AppError::Custom(format!("Health check failed: {}", e)))?;
-        
-        Ok(health_status)
-    } else {
-        Err(AppError::Custom("AI ML API Gateway not initialized".to_string()))
-    }
+async fn start_session_recording(state: State<'_, AppState>) -> Result<session_recording::SessionInfo, AppError> {
+    let settings = state.settings.snapshot();
+    tokio::task::spawn_blocking(move || session_recording::start(&settings))
+        .await
+        .map_err(|e| AppError::Internal(format!("Starting the recording failed: {}", e)))?
 }
 
-// Tauri Commands for text processing
 #[tauri::command]
-async fn initialize_text_processor(
+async fn stop_session_recording() -> Result<Option<session_recording::SessionInfo>, AppError> {
+    tokio::task::spawn_blocking(session_recording::stop)
+        .await
+        .map_err(|e| AppError::Internal(format!("Stopping the recording failed: {}", e)))?
+}
+
+/// Recorded sessions, newest first
+#[tauri::command]
+async fn list_recorded_sessions() -> Result<Vec<session_recording::SessionInfo>, AppError> {
+    tokio::task::spawn_blocking(session_recording::list)
+        .await
+        .map_err(|e| AppError::Internal(format!("Listing recorded sessions failed: {}", e)))?
+}
+
+#[tauri::command]
+async fn delete_recorded_session(id: String) -> Result<bool, AppError> {
+    tokio::task::spawn_blocking(move || session_recording::delete(&id))
+        .await
+        .map_err(|e| AppError::Internal(format!("Deleting the session failed: {}", e)))?
+}
+
+/// Run a recorded session's utterances through the pipeline again, with AI answers from the recording;
+/// each utterance is reported as "session-replay-progress" and nothing is typed
+#[tauri::command]
+async fn replay_session(
+    id: String,
     state: State<'_, AppState>,
-) -> Result<(), String> {
-    let mut text_processor_state = state.text_processor.lock().await;
-    
-    let config = get_default_config_for_context(ProcessingContext::Email);
-    let (event_sender, _event_receiver) = mpsc::unbounded_channel();
-    
-    let processor = AITextProcessor::new(config, event_sender);
-    *text_processor_state = Some(processor);
+    window: Window,
+) -> Result<session_recording::ReplayReport, AppError> {
+    session_recording::replay(&state, &window, &id).await
+}
 
-    Ok(())
+/// Encrypt history, drafts and recordings at rest, migrating existing files
+#[tauri::command]
+async fn enable_encryption(passphrase: Option<String>) -> Result<encryption::MigrationReport, AppError> {
+    tokio::task::spawn_blocking(move || encryption::get_data_vault().enable(passphrase.as_deref()))
+        .await
+        .map_err(|e| AppError::Internal(format!("Encryption migration failed: {}", e)))?
 }
 
+/// Unlock passphrase-protected data when the keychain entry is unavailable
 #[tauri::command]
-async fn process_text(
+async fn unlock_encryption(passphrase: String) -> Result<(), AppError> {
+    tokio::task::spawn_blocking(move || encryption::get_data_vault().unlock(&passphrase))
+        .await
+        .map_err(|e| AppError::Internal(format!("Unlock failed: {}", e)))?
+}
+
+#[tauri::command]
+async fn get_encryption_status() -> Result<encryption::EncryptionStatus, AppError> {
+    Ok(encryption::get_data_vault().status())
+}
+
+/// Run text through the content filter without sending it anywhere, for checking rules
+#[tauri::command]
+async fn test_content_filter(
     text: String,
-    context: String,
-    tone: String,
+    target: Option<OutputTarget>,
+    profile: Option<String>,
     state: State<'_, AppState>,
-) -> Result<ProcessingResult, AppError> {
-    // Validate and sanitize all inputs
+) -> Result<ContentFilterResult, AppError> {
     let validated_text = validate_text(&text, Some(1), Some(50000))
         .map_err(|e| AppError::Validation(e.to_string().into()))?;
-    
-    let validated_context = validate_config_value(&context, "context")
-        .map_err(|e| AppError::Validation(e.to_string().into()))?;
-    
-    let validated_tone = validate_config_value(&tone, "tone")
-        .map_err(|e| AppError::Validation(e.to_string().into()))?;
-
-    let registry = get_error_boundary_registry();
-    let boundary = registry.get("text_processor").await
-        .unwrap_or_else(|| Arc::new(ErrorBoundary::new("text_processor".to_string(), None)));
-
-    with_error_boundary!(boundary, async {
-        let text_processor_state = state.text_processor.lock().await;
-        
-        if let Some(ref processor) = *text_processor_state {
-            let processing_context = match validated_context.as_str() {
-                "email" => ProcessingContext::Email,
-                "code" => ProcessingContext::Code,
-                "document" => ProcessingContext::Document,
-                "social" => ProcessingContext::Social,
-                "formal" => ProcessingContext::Formal,
-                "casual" => ProcessingContext::Casual,
-                "technical" => ProcessingContext::Technical,
-                "creative" => ProcessingContext::Creative,
-                _ => ProcessingContext::Email,
-            };
-
-            let tone_type = match validated_tone.as_str() {
-                "professional" => ToneType::Professional,
-                "friendly" => ToneType::Friendly,
-                "formal" => ToneType::Formal,
-                "casual" => ToneType::Casual,
-                "empathetic" => ToneType::Empathetic,
-                "confident" => ToneType::Confident,
-                "persuasive" => ToneType::Persuasive,
-                "neutral" => ToneType::Neutral,
-                _ => ToneType::Professional,
-            };
 
-            let request = ProcessingRequest {
-                id: Uuid::new_v4().to_string(),
-                text: validated_text,
-                context: processing_context,
-                tone: tone_type,
-                options: ProcessingOptions {
-                    aggressiveness: 0.7,
-                    remove_fillers: true,
-                    preserve_formatting: false,
-                    smart_punctuation: true,
-                    auto_correct: true,
-                },
-                timestamp: std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs(),
-            };
+    let config = state.settings.snapshot().content_filters.config_for(profile.as_deref());
+    Ok(apply_content_filter(&validated_text, &config, target.unwrap_or(OutputTarget::Injection)))
+}
 
-            let result = processor.process_text(request).await
-                .map_err(|e| AppError::TextProcessing(e.to_string().into()))?;
-            Ok(result)
-        } else {
-            Err(AppError::TextProcessing(TextProcessingError::NotInitialized))
-        }
-    }).await
+/// Filter outgoing text with the active profile, notifying the UI when anything changed
+async fn filter_output(state: &AppState, window: &Window, text: &str, target: OutputTarget) -> String {
+    let config = state.settings.snapshot().content_filters.config_for(None);
+    let result = apply_content_filter(text, &config, target);
+    if result.changed() {
+        let _ = window.emit("content-filter-applied", &result);
+    }
+    match target {
+        // Unclosed direction overrides from a model or a paste would carry on into the rest of the document
+        OutputTarget::Injection | OutputTarget::Clipboard => unicode_text::balance_bidi(&result.text),
+        OutputTarget::Speech => result.text,
+    }
 }
 
+/// Replace the focus rules and return the state they produce right now
 #[tauri::command]
-async fn get_supported_languages_tauri() -> Result<Vec<Language>, String> {
-    Ok(get_supported_languages())
+async fn set_focus_rules(rules: focus::FocusSettings, state: State<'_, AppState>) -> Result<focus::FocusState, AppError> {
+    for app in rules.hotkey_blocked_apps.iter().chain(&rules.quiet_apps) {
+        validate_config_value(app, "app name")?;
+    }
+    state.settings.write().await.focus = rules.clone();
+    Ok(focus::refresh_focus(&rules).await)
 }
 
+/// Memory, cache and model state, including whether idle unloading is in effect
 #[tauri::command]
-async fn is_language_supported_tauri(language_code: String) -> Result<bool, AppError> {
-    // Validate language code input
-    let validated_code = validate_language_code(&language_code)
-        .map_err(|e| AppError::Validation(e.to_string().into()))?;
-    
-    Ok(is_language_supported(&validated_code))
+async fn get_resource_status(state: State<'_, AppState>) -> Result<idle::ResourceStatus, AppError> {
+    Ok(idle::resource_status(&state).await)
 }
 
-// Original Tauri commands (updated)
 #[tauri::command]
-async fn get_settings(state: State<'_, AppState>) -> Result<Settings, AppError> {
-    let settings = state.settings.lock().await;
-    Ok(settings.clone())
+async fn get_focus_state() -> Result<focus::FocusState, AppError> {
+    Ok(focus::current_focus_state())
 }
 
+/// Whether Secure Input is on and what it holds back, probed fresh
 #[tauri::command]
-async fn update_settings(new_settings: Settings, state: State<'_, AppState>) -> Result<(), AppError> {
-    // Validate settings inputs
-    let validated_language = validate_language_code(&new_settings.language)
-        .map_err(|e| AppError::Validation(e.to_string().into()))?;
-    
-    let validated_hotkey = validate_hotkey(&new_settings.hotkey)
-        .map_err(|e| AppError::Validation(e.to_string().into()))?;
-    
-    let validated_theme = validate_config_value(&new_settings.theme, "theme")
-        .map_err(|e| AppError::Validation(e.to_string().into()))?;
+async fn get_injection_safety_status(
+    state: State<'_, AppState>,
+) -> Result<injection_safety::InjectionSafetyStatus, AppError> {
+    let settings = state.settings.snapshot().injection_safety.clone();
+    Ok(injection_safety::refresh(&settings).await)
+}
 
-    let mut settings = state.settings.lock().await;
-    
-    // Update with validated values
-    let mut validated_settings = new_settings;
-    validated_settings.language = validated_language;
-    validated_settings.hotkey = validated_hotkey;
-    validated_settings.theme = validated_theme;
-    
-    *settings = validated_settings;
-    Ok(())
+/// Caret of the focused app in physical desktop pixels; `None` when no accessible text field has focus
+#[tauri::command]
+async fn get_caret_position(window: Window) -> Result<Option<caret::CaretPosition>, AppError> {
+    Ok(caret::caret_position(&window).await)
 }
 
+/// Turn follow-caret mode on or off; turning it on shows the overlay window
 #[tauri::command]
-async fn get_voice_status(state: State<'_, AppState>) -> Result<HashMap<String, serde_json::Value>, String> {
-    let voice_engine_state = state.voice_engine.lock().await;
-    
-    let mut status = HashMap::new();
-    if let Some(ref engine) = *voice_engine_state {
-        let engine_status = engine.get_status();
-        status.insert("is_listening".to_string(), serde_json::Value::Bool(engine_status.is_listening));
-        status.insert("engine_type".to_string(), serde_json::Value::String(engine_status.engine_type));
-        status.insert("session_id".to_string(), serde_json::Value::String(engine_status.session_id));
-        status.insert("language".to_string(), serde_json::Value::String(engine_status.config.language));
-    } else {
-        status.insert("is_listening".to_string(), serde_json::Value::Bool(false));
-        status.insert("engine_type".to_string(), serde_json::Value::String("none".to_string()));
+async fn set_overlay_follow_caret(
+    enabled: bool,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<caret::OverlaySettings, AppError> {
+    let mut settings = state.settings.write().await;
+    settings.overlay.follow_caret = enabled;
+    profiles::save_profile_settings(&storage::active_profile_id(), &settings)?;
+    let overlay = settings.overlay.clone();
+    drop(settings);
+
+    if enabled {
+        caret::overlay_window(&app)?
+            .show()
+            .map_err(|e| AppError::Internal(format!("Failed to show overlay window: {}", e)))?;
     }
-    
-    Ok(status)
+    Ok(overlay)
 }
 
 #[tauri::command]
@@ -717,7 +4499,7 @@ async fn get_app_info() -> Result<HashMap<String, String>, String> {
 
 // Event handling functions with proper error handling
 async fn handle_voice_events(
-    voice_engine_state: Arc<Mutex<Option<VoiceRecognitionEngine>>>,
+    voice_engine_state: Arc<Mutex<Option<VoiceEngineHandle>>>,
     window: Window,
 ) -> Result<(), AppError> {
     let registry = get_error_boundary_registry();
@@ -870,6 +4652,9 @@ fn handle_system_tray_event(event: SystemTrayEvent, app: &AppHandle) {
                 }
             }
             "quit" => {
+                // Other apps would otherwise stay quiet after we exit
+                tauri::async_runtime::block_on(audio_ducking::restore_all());
+                crash_reports::mark_clean_exit();
                 std::process::exit(0);
             }
             _ => {}
@@ -892,6 +4677,13 @@ fn handle_window_event(event: WindowEvent, app: &AppHandle) {
 
 #[tokio::main]
 async fn main() {
+    // A copy of the app started only to write minidumps for the main process
+    if crash_reports::run_as_monitor() {
+        return;
+    }
+    // Before anything else, so early panics and the previous run's crash are caught
+    crash_reports::install();
+
     // Initialize global components
     let resource_manager = get_resource_manager().clone();
     let error_registry = get_error_boundary_registry().clone();
@@ -906,6 +4698,30 @@ async fn main() {
     error_registry.register("voice_events".to_string(), 
         Arc::new(ErrorBoundary::new("voice_events".to_string(), None))).await;
 
+    if let Err(e) = encryption::get_data_vault().initialize() {
+        tracing::error!("Failed to load encryption settings: {}", e);
+    }
+
+    // Resolve the last active profile before anything reads per-user data
+    let active_profile = get_profile_manager().lock().await.active_id().to_string();
+    let initial_settings = profiles::load_profile_settings(&active_profile)
+        .map(|mut settings| {
+            settings.ai_ml_settings.api_key = Settings::default().ai_ml_settings.api_key;
+            settings
+        })
+        .unwrap_or_default();
+
+    if let Err(e) = plugins::load_installed(&initial_settings.plugins).await {
+        tracing::warn!("Plugins not loaded: {}", e);
+    }
+    audit::configure(&initial_settings.audit);
+    command_limits::configure(&initial_settings.command_limits);
+    if let Err(e) = network::configure(&initial_settings.network) {
+        tracing::warn!("Network settings not applied: {}", e);
+    }
+    integrations::result_cache::configure(&initial_settings.result_cache, initial_settings.voice_recognition.privacy_mode);
+    integrations::translation_formality::configure(&initial_settings.formality);
+
     // Start background tasks for memory management and error monitoring
     tokio::spawn(start_cleanup_task());
     tokio::spawn(start_error_monitoring_task());
@@ -921,42 +4737,266 @@ async fn main() {
             voice_engine: Arc::new(Mutex::new(None)),
             text_processor: Arc::new(Mutex::new(None)),
             ai_ml_gateway: Arc::new(Mutex::new(None)),
-            settings: Arc::new(Mutex::new(Settings::default())),
+            settings: Arc::new(settings_store::SettingsStore::new(initial_settings)),
             shortcuts: Arc::new(Mutex::new(HashMap::new())),
             event_handlers: Arc::new(Mutex::new(Vec::new())),
             resource_manager: resource_manager.clone(),
             error_boundaries: error_registry.clone(),
+            last_output: Arc::new(Mutex::new(None)),
+        })
+        .setup(|app| {
+            let state = app.state::<AppState>().inner().clone();
+            let app_handle = app.handle();
+            let settings = state.settings.clone();
+            tauri::async_runtime::spawn(async move {
+                let streaming = settings.snapshot().streaming.clone();
+                let mut streamer = get_caption_streamer().lock().await;
+                streamer.attach(app_handle);
+                streamer.configure(&streaming);
+            });
+            tauri::async_runtime::spawn(focus::run_focus_monitor(state.clone(), app.handle()));
+            tauri::async_runtime::spawn(idle::run_idle_monitor(state.clone(), app.handle()));
+            tauri::async_runtime::spawn(caret::run_caret_follower(state.clone(), app.handle()));
+            tauri::async_runtime::spawn(injection_safety::run_safety_monitor(state.clone(), app.handle()));
+            tauri::async_runtime::spawn(bluetooth_audio::run_bluetooth_monitor(state.clone(), app.handle()));
+            tauri::async_runtime::spawn(mic_state::run_mic_monitor(state.clone(), app.handle()));
+            tauri::async_runtime::spawn(power::run_power_monitor(state.clone(), app.handle()));
+            tauri::async_runtime::spawn(run_settings_listener(state.clone(), app.handle()));
+            let failover_handle = app.handle();
+            tauri::async_runtime::spawn(async move {
+                let mut events = integrations::provider_failover::subscribe();
+                loop {
+                    match events.recv().await {
+                        Ok(event) => {
+                            let _ = failover_handle.emit_all("provider-failover", &event);
+                        }
+                        Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                    }
+                }
+            });
+            let read_aloud_handle = app.handle();
+            let read_aloud_settings = state.settings.clone();
+            tauri::async_runtime::spawn(async move {
+                let read_aloud = read_aloud_settings.snapshot().read_aloud.clone();
+                if let Err(e) = read_aloud::register_hotkey(&read_aloud_handle, None, &read_aloud) {
+                    log::warn!("{}", e);
+                }
+            });
+            let crash_settings = state.settings.clone();
+            tauri::async_runtime::spawn(async move {
+                let crash_reports = crash_settings.snapshot().crash_reports.clone();
+                crash_reports::upload_pending(&crash_reports).await;
+            });
+            tauri::async_runtime::spawn(updater::run_update_checks(state.clone(), app.handle()));
+            tauri::async_runtime::spawn(jobs::run_scheduler(state, app.handle()));
+            Ok(())
         })
-        .invoke_handler(tauri::generate_handler![
+        .on_page_load(|window, _| {
+            if window.label() == "main" {
+                crash_reports::notify_unseen(&window);
+            }
+        })
+        .invoke_handler(command_limits::limited(tauri::generate_handler![
             // Voice recognition commands
+            initialize_all,
             initialize_voice_recognition,
             start_voice_listening,
             stop_voice_listening,
+            pause_voice_listening,
+            resume_voice_listening,
+            reconfigure_voice_recognition,
             
             // Text processing commands
             initialize_text_processor,
             process_text,
+            process_text_v1,
+            api_handshake,
             process_speech_with_ai,
+            analyze_utterance,
             
             // AI ML API commands
             initialize_ai_ml_api,
             process_enhanced_text,
+            enhance_text_v1,
+            pick_alternative,
             generate_enhanced_voice,
+            synthesize_v1,
             translate_with_enhancement,
+            translate_v1,
+            export_bilingual_translation,
+            set_default_formality,
             process_context_aware,
+            process_long_text,
+            transcribe_long_audio,
             get_ai_ml_health_status,
+            get_provider_routes,
+            estimate_operation_cost,
             
             // Language commands
             get_supported_languages_tauri,
             is_language_supported_tauri,
+            list_languages,
+            list_models_registry,
+            set_language_enabled,
+            download_language_resources,
+            list_models,
+            download_model,
+            remove_model,
             
             // Original commands
             get_settings,
             update_settings,
             get_voice_status,
+            start_calibration,
+            get_calibration_status,
+            list_user_profiles,
+            create_user_profile,
+            switch_user_profile,
+            list_jobs,
+            cancel_job,
+            resume_job,
+            discard_job,
+            queue_document_job,
+            list_destinations,
+            send_to_destination,
+            push_caption_text,
+            push_interim_transcript,
+            get_caption_stream_status,
+            get_dictation_stats,
+            record_processing_feedback,
+            rate_result,
+            get_feedback_summary,
+            compare_processing,
+            list_transcript_segments,
+            list_transcript_topics,
+            get_topic_state,
+            get_emotional_state,
+            retranscribe_segment,
+            set_focus_rules,
+            get_focus_state,
+            get_injection_safety_status,
+            get_caret_position,
+            set_overlay_follow_caret,
+            get_resource_status,
+            list_tts_voices,
+            get_voice_catalog,
+            register_custom_voice,
+            remove_custom_voice,
+            set_preferred_voice,
+            get_voice_for_language,
+            generate_voice_variations,
+            batch_generate_voice,
+            list_pronunciations,
+            add_pronunciation,
+            remove_pronunciation,
+            test_pronunciation,
+            speak_text_streaming,
+            control_speech_stream,
+            ack_speech_chunk,
+            read_selection_aloud,
+            set_speech_playback_active,
+            push_playback_reference,
+            list_stt_providers,
+            start_stt_stream,
+            push_stt_audio,
+            stop_stt_stream,
+            get_ducking_status,
+            start_assistant_session,
+            send_assistant_message,
+            end_assistant_session,
+            list_assistant_tools,
+            register_assistant_tool,
+            unregister_assistant_tool,
+            list_intent_rules,
+            add_intent_rule,
+            remove_intent_rule,
+            evaluate_intent_rules,
+            format_code_dictation,
+            set_code_dictation_mode,
+            list_snippets,
+            save_snippet,
+            delete_snippet,
+            expand_snippet_by_name,
+            start_form_session,
+            submit_form_answer,
+            get_form_session,
+            cancel_form_session,
+            get_keyword_boost,
+            set_boost_keywords,
+            delete_boost_profile,
+            activate_boost_profile,
+            list_domain_packs,
+            get_domain_pack,
+            install_domain_pack,
+            uninstall_domain_pack,
+            activate_domain_pack,
+            trust_domain_pack_key,
+            list_plugins,
+            install_plugin,
+            uninstall_plugin,
+            enable_plugin,
+            disable_plugin,
+            restart_plugin,
+            get_pipeline,
+            validate_pipeline,
+            set_pipeline,
+            list_pending_results,
+            approve_pending_result,
+            discard_pending_result,
+            get_audit_log,
+            export_audit_log,
+            start_session_recording,
+            stop_session_recording,
+            list_recorded_sessions,
+            delete_recorded_session,
+            replay_session,
+            list_crash_reports,
+            acknowledge_crash_reports,
+            upload_crash_report,
+            delete_crash_report,
+            check_for_updates,
+            test_network_connectivity,
+            get_traffic_metrics,
+            get_cache_stats,
+            clear_cache,
+            extract_entities,
+            transliterate_text,
+            read_screen_text,
+            process_screen_text,
+            install_update,
+            refine_last_result,
+            get_refinement_history,
+            record_text_injection,
+            inject_text,
+            get_platform_capabilities,
+            undo_last_action,
+            redo_action,
+            get_undo_history,
+            get_latency_report,
+            report_injection_latency,
+            run_system_checks,
+            diagnose_audio_capture,
+            get_multi_mic_status,
+            get_hypothesis_diagnostics,
+            get_bluetooth_audio_status,
+            get_mic_mute_state,
+            get_power_profile,
+            get_command_rate_metrics,
+            export_all_user_data,
+            purge_all_user_data,
+            enable_encryption,
+            unlock_encryption,
+            get_encryption_status,
+            test_content_filter,
             register_global_shortcut,
             get_app_info
-        ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        ]))
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|app, event| match event {
+            tauri::RunEvent::Exit => crash_reports::mark_clean_exit(),
+            tauri::RunEvent::Updater(event) => updater::handle_event(app, &event),
+            _ => {}
+        });
 }
\ No newline at end of file
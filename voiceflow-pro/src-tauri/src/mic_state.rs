@@ -0,0 +1,273 @@
+//! Microphone state for VoiceFlow Pro
+//! Follows the OS and hardware mute switch, pauses recognition while muted and publishes an unambiguous recording indicator
+
+use std::sync::RwLock;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tokio::sync::watch;
+
+use crate::integrations::voice_recognition::{EngineState, PauseReason, VoiceEngineStatus};
+#[cfg(not(target_os = "windows"))]
+use crate::system_activity::command_output;
+use crate::AppState;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MicMuteSettings {
+    /// Pause listening while the microphone is muted, instead of recognizing silence
+    pub pause_on_mute: bool,
+    /// Resume a session paused for the mute once the microphone is back on
+    pub resume_on_unmute: bool,
+}
+
+impl Default for MicMuteSettings {
+    fn default() -> Self {
+        Self {
+            pause_on_mute: true,
+            resume_on_unmute: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MicMuteState {
+    /// Whether this platform can report the mute state at all
+    pub supported: bool,
+    pub muted: bool,
+    /// Input volume from 0 to 1 where the platform reports it; macOS reports a muted input as zero
+    pub input_volume: Option<f32>,
+}
+
+/// Sent as "recording-indicator" whenever it changes; the UI and tray show a red dot while `recording` is set
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordingIndicator {
+    /// The microphone is open and audio reaches the recognizer
+    pub recording: bool,
+    /// The microphone is open but muted, so nothing is heard
+    pub muted: bool,
+    pub state: EngineState,
+    pub label: String,
+}
+
+impl RecordingIndicator {
+    fn new(state: EngineState, mute: &MicMuteState) -> Self {
+        let open = state.captures_audio();
+        let label = match (open, mute.muted) {
+            (true, false) => "Microphone on",
+            (true, true) => "Microphone open but muted",
+            (false, _) if state == EngineState::Paused => "Microphone paused",
+            (false, _) => "Microphone off",
+        };
+        Self {
+            recording: open && !mute.muted,
+            muted: open && mute.muted,
+            state,
+            label: label.to_string(),
+        }
+    }
+}
+
+static MUTE_STATE: RwLock<Option<MicMuteState>> = RwLock::new(None);
+static INDICATOR: RwLock<Option<RecordingIndicator>> = RwLock::new(None);
+
+/// Latest probed mute state; unmuted until the first probe has run
+pub fn current_mute_state() -> MicMuteState {
+    MUTE_STATE
+        .read()
+        .ok()
+        .and_then(|state| state.clone())
+        .unwrap_or_default()
+}
+
+/// Probe the mute state of the default input and publish it
+pub async fn refresh() -> MicMuteState {
+    let state = probe_mute().await;
+    if let Ok(mut current) = MUTE_STATE.write() {
+        *current = Some(state.clone());
+    }
+    state
+}
+
+#[cfg(target_os = "macos")]
+async fn probe_mute() -> MicMuteState {
+    // "missing value" when there is no input device
+    let volume = command_output("osascript", &["-e", "input volume of (get volume settings)"])
+        .await
+        .and_then(|output| output.trim().parse::<f32>().ok());
+    MicMuteState {
+        supported: volume.is_some(),
+        muted: volume == Some(0.0),
+        input_volume: volume.map(|volume| volume / 100.0),
+    }
+}
+
+#[cfg(target_os = "windows")]
+const ENDPOINT_MUTE_SOURCE: &str = r#"
+using System;
+using System.Runtime.InteropServices;
+
+[ComImport, Guid("BCDE0395-E52F-467C-8E3D-C4579291692E")] class MMDeviceEnumerator {}
+[ComImport, Guid("A95664D2-9614-4F35-A746-DE8DB63617E6"), InterfaceType(ComInterfaceType.InterfaceIsIUnknown)]
+interface IMMDeviceEnumerator { int EnumAudioEndpoints(int flow, int mask, out IntPtr devices); int GetDefaultAudioEndpoint(int flow, int role, out IMMDevice device); }
+[ComImport, Guid("D666063F-1587-4E43-81F1-B948E807363F"), InterfaceType(ComInterfaceType.InterfaceIsIUnknown)]
+interface IMMDevice { int Activate(ref Guid iid, int context, IntPtr parameters, [MarshalAs(UnmanagedType.IUnknown)] out object target); }
+[ComImport, Guid("5CDF2C82-841E-4546-9722-0CF74078229A"), InterfaceType(ComInterfaceType.InterfaceIsIUnknown)]
+interface IAudioEndpointVolume {
+    int RegisterControlChangeNotify(IntPtr notify); int UnregisterControlChangeNotify(IntPtr notify); int GetChannelCount(out int count);
+    int SetMasterVolumeLevel(float level, ref Guid context); int SetMasterVolumeLevelScalar(float level, ref Guid context);
+    int GetMasterVolumeLevel(out float level); int GetMasterVolumeLevelScalar(out float level);
+    int SetChannelVolumeLevel(uint channel, float level, ref Guid context); int SetChannelVolumeLevelScalar(uint channel, float level, ref Guid context);
+    int GetChannelVolumeLevel(uint channel, out float level); int GetChannelVolumeLevelScalar(uint channel, out float level);
+    int SetMute([MarshalAs(UnmanagedType.Bool)] bool mute, ref Guid context); int GetMute([MarshalAs(UnmanagedType.Bool)] out bool mute);
+}
+
+public static class CaptureMute {
+    public static string Read() {
+        var devices = (IMMDeviceEnumerator)(new MMDeviceEnumerator());
+        IMMDevice device;
+        // eCapture, eCommunications: the endpoint a dictation app records from by default
+        if (devices.GetDefaultAudioEndpoint(1, 2, out device) != 0) return "";
+        var iid = typeof(IAudioEndpointVolume).GUID;
+        object target;
+        device.Activate(ref iid, 23, IntPtr.Zero, out target);
+        var volume = (IAudioEndpointVolume)target;
+        bool muted; float level;
+        volume.GetMute(out muted);
+        volume.GetMasterVolumeLevelScalar(out level);
+        return (muted ? "muted " : "live ") + level.ToString(System.Globalization.CultureInfo.InvariantCulture);
+    }
+}
+"#;
+
+#[cfg(target_os = "windows")]
+async fn probe_mute() -> MicMuteState {
+    // Compiling the interop type takes longer than the poll interval, so it is done once in a session that stays up
+    static SESSION: std::sync::OnceLock<crate::system_activity::ScriptSession> = std::sync::OnceLock::new();
+    let session = SESSION.get_or_init(|| {
+        crate::system_activity::ScriptSession::powershell(&format!("Add-Type -TypeDefinition @'\n{}\n'@", ENDPOINT_MUTE_SOURCE))
+    });
+    let output = session.query("[CaptureMute]::Read()").await;
+    let Some((muted, level)) = output.as_deref().and_then(|output| output.trim().split_once(' ')) else {
+        return MicMuteState::default();
+    };
+    MicMuteState {
+        supported: true,
+        muted: muted == "muted",
+        input_volume: level.parse().ok(),
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+async fn probe_mute() -> MicMuteState {
+    // PulseAudio and PipeWire first, then the ALSA capture switch on systems without a sound server
+    if let Some(output) = command_output("pactl", &["get-source-mute", "@DEFAULT_SOURCE@"]).await {
+        let volume = command_output("pactl", &["get-source-volume", "@DEFAULT_SOURCE@"])
+            .await
+            .and_then(|volume| {
+                volume
+                    .split('/')
+                    .nth(1)
+                    .and_then(|percent| percent.trim().trim_end_matches('%').parse::<f32>().ok())
+            });
+        return MicMuteState {
+            supported: true,
+            muted: output.trim() == "Mute: yes",
+            input_volume: volume.map(|volume| volume / 100.0),
+        };
+    }
+    match command_output("amixer", &["get", "Capture"]).await {
+        Some(output) => MicMuteState {
+            supported: true,
+            muted: output.contains("[off]") && !output.contains("[on]"),
+            input_volume: None,
+        },
+        None => MicMuteState::default(),
+    }
+}
+
+/// Follow the mute switch and the engine together: emit "mic-mute-changed" when the mute flips,
+/// pause or resume listening to match it, and emit "recording-indicator" the moment recording starts or stops
+pub async fn run_mic_monitor(state: AppState, app: AppHandle) {
+    let mut previous_mute: Option<MicMuteState> = None;
+    let mut engine_status: Option<watch::Receiver<VoiceEngineStatus>> = None;
+    let mut probe = true;
+    loop {
+        let (settings, resources) = {
+            let settings = state.settings.snapshot();
+            (settings.mic_mute.clone(), settings.resources.clone())
+        };
+        if engine_status.is_none() {
+            engine_status = state.voice_engine.lock().await.as_ref().map(|engine| engine.watch_status());
+        }
+
+        let mute = if probe { refresh().await } else { current_mute_state() };
+        if previous_mute.as_ref() != Some(&mute) {
+            let _ = app.emit_all("mic-mute-changed", &mute);
+            apply_mute_hold(&state, &app, &mute, &settings).await;
+            previous_mute = Some(mute.clone());
+        }
+        let engine_state = engine_status
+            .as_ref()
+            .map_or(EngineState::Idle, |status| status.borrow().state.clone());
+        publish_indicator(&app, RecordingIndicator::new(engine_state, &mute));
+
+        // Engine changes wake the loop at once so the indicator never lags; only the timer probes the OS again
+        let interval = crate::idle::adjust_interval(POLL_INTERVAL, &resources);
+        probe = match engine_status.as_mut() {
+            Some(status) => tokio::select! {
+                _ = tokio::time::sleep(interval) => true,
+                changed = status.changed() => {
+                    if changed.is_err() {
+                        engine_status = None;
+                    }
+                    false
+                }
+            },
+            None => {
+                tokio::time::sleep(interval).await;
+                true
+            }
+        };
+    }
+}
+
+fn publish_indicator(app: &AppHandle, indicator: RecordingIndicator) {
+    let Ok(mut current) = INDICATOR.write() else {
+        return;
+    };
+    if current.as_ref() == Some(&indicator) {
+        return;
+    }
+    let _ = app.emit_all("recording-indicator", &indicator);
+    let tooltip = format!("VoiceFlow Pro: {}", indicator.label);
+    if let Err(e) = app.tray_handle().set_tooltip(&tooltip) {
+        tracing::debug!("Could not update the tray tooltip: {}", e);
+    }
+    *current = Some(indicator);
+}
+
+/// Pause a listening engine while the microphone is muted, and resume it afterwards unless the user paused it too
+async fn apply_mute_hold(state: &AppState, app: &AppHandle, mute: &MicMuteState, settings: &MicMuteSettings) {
+    let Some(engine) = state.voice_engine.lock().await.clone() else {
+        return;
+    };
+    let engine_status = engine.status();
+    let outcome = match (&engine_status.state, &engine_status.pause_reason, mute.muted) {
+        (EngineState::Listening, _, true) if settings.pause_on_mute => engine.pause(PauseReason::MicMuted).await,
+        (EngineState::Paused, Some(PauseReason::MicMuted), false) if settings.resume_on_unmute => {
+            engine.resume().await
+        }
+        _ => return,
+    };
+    match outcome {
+        Ok(engine_status) => {
+            let _ = app.emit_all("voice-status", engine_status.state.as_str());
+            let _ = app.emit_all("voice-auto-pause", &engine_status);
+        }
+        Err(e) => tracing::warn!("Microphone mute pause transition failed: {}", e),
+    }
+}
@@ -0,0 +1,339 @@
+//! Network settings for VoiceFlow Pro
+//! Proxy and custom CA configuration shared by every HTTP client, and a connectivity test that pinpoints what fails
+
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use reqwest::{Certificate, ClientBuilder, NoProxy, Proxy};
+use serde::{Deserialize, Serialize};
+use tokio::net::TcpStream;
+
+use crate::errors::{AppError, ValidationError};
+use crate::system_checks::CheckStatus;
+
+const PEM_BEGIN: &str = "-----BEGIN CERTIFICATE-----";
+const PEM_END: &str = "-----END CERTIFICATE-----";
+/// Per-step limit in `test_connectivity`
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkSettings {
+    /// `http://`, `https://`, `socks5://` or `socks5h://` URL; when unset the system proxy variables apply
+    pub proxy_url: Option<String>,
+    pub proxy_username: Option<String>,
+    pub proxy_password: Option<String>,
+    /// Hosts reached directly, comma separated as in NO_PROXY
+    pub no_proxy: String,
+    /// Honor HTTP_PROXY/HTTPS_PROXY/ALL_PROXY when no proxy is set here
+    pub use_system_proxy: bool,
+    /// PEM file with extra root certificates, for networks that intercept TLS
+    pub ca_bundle_path: Option<String>,
+}
+
+impl Default for NetworkSettings {
+    fn default() -> Self {
+        Self {
+            proxy_url: None,
+            proxy_username: None,
+            proxy_password: None,
+            no_proxy: "localhost,127.0.0.1,::1".to_string(),
+            use_system_proxy: true,
+            ca_bundle_path: None,
+        }
+    }
+}
+
+/// One step of a connectivity test
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectivityStep {
+    pub id: String,
+    pub status: CheckStatus,
+    pub message: String,
+    pub duration_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectivityReport {
+    pub url: String,
+    /// The proxy requests go through, without credentials
+    pub proxy: Option<String>,
+    pub steps: Vec<ConnectivityStep>,
+    /// True when the final request got an HTTP response
+    pub reachable: bool,
+}
+
+/// Settings plus the certificates loaded from the CA bundle, so files are read once per change
+#[derive(Clone)]
+struct ActiveNetwork {
+    settings: NetworkSettings,
+    certificates: Vec<Certificate>,
+}
+
+static ACTIVE: OnceLock<Mutex<Option<ActiveNetwork>>> = OnceLock::new();
+
+fn active() -> std::sync::MutexGuard<'static, Option<ActiveNetwork>> {
+    let active = ACTIVE.get_or_init(|| Mutex::new(None));
+    match active.lock() {
+        Ok(active) => active,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+/// Apply `settings` to every client built from now on
+pub fn configure(settings: &NetworkSettings) -> Result<(), AppError> {
+    validate(settings)?;
+    let certificates = match &settings.ca_bundle_path {
+        Some(path) => load_ca_bundle(path)?,
+        None => Vec::new(),
+    };
+    *active() = Some(ActiveNetwork {
+        settings: settings.clone(),
+        certificates,
+    });
+    Ok(())
+}
+
+pub fn validate(settings: &NetworkSettings) -> Result<(), AppError> {
+    let invalid = |message: String| AppError::Validation(ValidationError::InvalidConfigValue(message));
+    if let Some(url) = &settings.proxy_url {
+        let scheme = url.split("://").next().unwrap_or_default();
+        if !url.contains("://") || !matches!(scheme, "http" | "https" | "socks5" | "socks5h") {
+            return Err(invalid(format!(
+                "Proxy URL must start with http://, https://, socks5:// or socks5h://, got {:?}",
+                url
+            )));
+        }
+        Proxy::all(url.as_str()).map_err(|e| invalid(format!("Invalid proxy URL {:?}: {}", url, e)))?;
+    }
+    if settings.proxy_password.is_some() && settings.proxy_username.is_none() {
+        return Err(invalid("A proxy password needs a proxy username".to_string()));
+    }
+    if let Some(path) = &settings.ca_bundle_path {
+        if !std::path::Path::new(path).is_absolute() {
+            return Err(invalid(format!("CA bundle must be an absolute path: {}", path)));
+        }
+    }
+    Ok(())
+}
+
+/// A client builder with the configured proxy and extra root certificates; use it for every outbound client
+pub fn client_builder() -> ClientBuilder {
+    match active().clone() {
+        Some(active) => apply(reqwest::Client::builder(), &active.settings, active.certificates),
+        None => reqwest::Client::builder(),
+    }
+}
+
+fn apply(mut builder: ClientBuilder, settings: &NetworkSettings, certificates: Vec<Certificate>) -> ClientBuilder {
+    for certificate in certificates {
+        builder = builder.add_root_certificate(certificate);
+    }
+    match proxy(settings) {
+        Some(proxy) => builder.proxy(proxy),
+        None if !settings.use_system_proxy => builder.no_proxy(),
+        None => builder,
+    }
+}
+
+/// `client_builder` built with only a timeout, for the common case
+pub fn client(timeout: Duration) -> Result<reqwest::Client, AppError> {
+    client_builder()
+        .timeout(timeout)
+        .build()
+        .map_err(|e| AppError::Internal(format!("Failed to build HTTP client: {}", e)))
+}
+
+fn proxy(settings: &NetworkSettings) -> Option<Proxy> {
+    let url = settings.proxy_url.as_deref()?;
+    let mut proxy = Proxy::all(url).ok()?;
+    if let Some(username) = &settings.proxy_username {
+        proxy = proxy.basic_auth(username, settings.proxy_password.as_deref().unwrap_or_default());
+    }
+    Some(proxy.no_proxy(NoProxy::from_string(&settings.no_proxy)))
+}
+
+fn load_ca_bundle(path: &str) -> Result<Vec<Certificate>, AppError> {
+    let pem = std::fs::read_to_string(path)
+        .map_err(|e| AppError::Configuration(format!("Cannot read CA bundle {}: {}", path, e)))?;
+    let certificates = pem_blocks(&pem)
+        .map(|block| {
+            Certificate::from_pem(block.as_bytes())
+                .map_err(|e| AppError::Configuration(format!("Invalid certificate in {}: {}", path, e)))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if certificates.is_empty() {
+        return Err(AppError::Configuration(format!("{} contains no PEM certificates", path)));
+    }
+    Ok(certificates)
+}
+
+/// Each certificate of a PEM bundle; reqwest only parses one at a time
+fn pem_blocks(pem: &str) -> impl Iterator<Item = &str> {
+    pem.match_indices(PEM_BEGIN).filter_map(move |(start, _)| {
+        let end = pem[start..].find(PEM_END)? + start + PEM_END.len();
+        Some(&pem[start..end])
+    })
+}
+
+/// Walk through what a request to `url` under `settings` depends on, stopping at the first failure:
+/// network settings, the CA bundle, reaching the proxy or resolving the host, and the HTTPS request itself
+pub async fn test_connectivity(settings: &NetworkSettings, url: &str) -> ConnectivityReport {
+    let mut report = ConnectivityReport {
+        url: url.to_string(),
+        proxy: settings.proxy_url.as_deref().map(without_credentials),
+        steps: Vec::new(),
+        reachable: false,
+    };
+
+    let started = Instant::now();
+    let parsed = match reqwest::Url::parse(url) {
+        Ok(parsed) if parsed.host_str().is_some() => parsed,
+        _ => {
+            report.step("url", CheckStatus::Fail, format!("{:?} is not a valid URL", url), started);
+            return report;
+        }
+    };
+    if let Err(e) = validate(settings) {
+        report.step("settings", CheckStatus::Fail, e.to_string(), started);
+        return report;
+    }
+    report.step("settings", CheckStatus::Pass, "Network settings are valid.", started);
+
+    let mut certificates = Vec::new();
+    if let Some(path) = &settings.ca_bundle_path {
+        let started = Instant::now();
+        match load_ca_bundle(path) {
+            Ok(loaded) => {
+                report.step(
+                    "ca_bundle",
+                    CheckStatus::Pass,
+                    format!("Loaded {} certificate(s) from {}.", loaded.len(), path),
+                    started,
+                );
+                certificates = loaded;
+            }
+            Err(e) => {
+                report.step("ca_bundle", CheckStatus::Fail, e.to_string(), started);
+                return report;
+            }
+        }
+    }
+
+    let started = Instant::now();
+    match settings.proxy_url.as_deref().and_then(|proxy| reqwest::Url::parse(proxy).ok()) {
+        Some(proxy) => {
+            let host = proxy.host_str().unwrap_or_default().to_string();
+            let port = proxy.port_or_known_default().unwrap_or(1080);
+            match tokio::time::timeout(PROBE_TIMEOUT, TcpStream::connect((host.as_str(), port))).await {
+                Ok(Ok(_)) => report.step(
+                    "proxy",
+                    CheckStatus::Pass,
+                    format!("Proxy {}:{} accepts connections.", host, port),
+                    started,
+                ),
+                Ok(Err(e)) => {
+                    report.step("proxy", CheckStatus::Fail, format!("Cannot reach proxy {}:{}: {}", host, port, e), started);
+                    return report;
+                }
+                Err(_) => {
+                    report.step("proxy", CheckStatus::Fail, format!("Proxy {}:{} did not answer.", host, port), started);
+                    return report;
+                }
+            }
+        }
+        None => {
+            // Without a proxy configured here the host has to resolve locally
+            let host = parsed.host_str().unwrap_or_default().to_string();
+            let port = parsed.port_or_known_default().unwrap_or(443);
+            let resolved = tokio::time::timeout(PROBE_TIMEOUT, tokio::net::lookup_host((host.as_str(), port)))
+                .await
+                .ok()
+                .and_then(Result::ok)
+                .map_or(false, |mut addresses| addresses.next().is_some());
+            if resolved {
+                report.step("dns", CheckStatus::Pass, format!("{} resolves.", host), started);
+            } else if settings.use_system_proxy {
+                // A system proxy may still resolve it
+                report.step("dns", CheckStatus::Warning, format!("{} does not resolve on this machine.", host), started);
+            } else {
+                report.step("dns", CheckStatus::Fail, format!("{} does not resolve on this machine.", host), started);
+                return report;
+            }
+        }
+    }
+
+    let started = Instant::now();
+    let client = match apply(reqwest::Client::builder(), settings, certificates).timeout(PROBE_TIMEOUT).build() {
+        Ok(client) => client,
+        Err(e) => {
+            report.step("request", CheckStatus::Fail, format!("Failed to build HTTP client: {}", e), started);
+            return report;
+        }
+    };
+    match client.get(parsed).send().await {
+        Ok(response) if response.status().as_u16() == 407 => report.step(
+            "request",
+            CheckStatus::Fail,
+            "The proxy requires authentication; check the proxy username and password.",
+            started,
+        ),
+        Ok(response) => {
+            report.reachable = true;
+            report.step(
+                "request",
+                CheckStatus::Pass,
+                format!("Got HTTP {} from {}.", response.status().as_u16(), url),
+                started,
+            );
+        }
+        Err(e) => report.step("request", CheckStatus::Fail, describe_request_error(&e), started),
+    }
+    report
+}
+
+impl ConnectivityReport {
+    fn step(&mut self, id: &str, status: CheckStatus, message: impl Into<String>, started: Instant) {
+        self.steps.push(ConnectivityStep {
+            id: id.to_string(),
+            status,
+            message: message.into(),
+            duration_ms: started.elapsed().as_millis() as u64,
+        });
+    }
+}
+
+/// Name the likely cause, since reqwest's own message is often just "error sending request"
+fn describe_request_error(error: &reqwest::Error) -> String {
+    let mut detail = error.to_string();
+    let mut source = std::error::Error::source(error);
+    while let Some(inner) = source {
+        detail = format!("{}: {}", detail, inner);
+        source = inner.source();
+    }
+    let lower = detail.to_lowercase();
+    if lower.contains("certificate") || lower.contains("tls") || lower.contains("ssl") {
+        format!(
+            "TLS verification failed ({}). If your network inspects HTTPS traffic, add its root certificate as a CA bundle.",
+            detail
+        )
+    } else if error.is_timeout() {
+        format!("The request timed out ({}).", detail)
+    } else if error.is_connect() {
+        format!("Could not connect ({}). A firewall or proxy may be blocking the connection.", detail)
+    } else {
+        detail
+    }
+}
+
+/// Proxy URL safe to show and log
+fn without_credentials(url: &str) -> String {
+    match reqwest::Url::parse(url) {
+        Ok(mut parsed) => {
+            let _ = parsed.set_username("");
+            let _ = parsed.set_password(None);
+            parsed.to_string()
+        }
+        Err(_) => url.to_string(),
+    }
+}
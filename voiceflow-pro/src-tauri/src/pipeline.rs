@@ -0,0 +1,879 @@
+//! Dictation pipeline for VoiceFlow Pro
+//! Runs each profile's ordered list of text stages on a transcript and reports what every stage did and how long it took
+
+use std::time::Instant;
+
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use tauri::Window;
+use uuid::Uuid;
+
+use crate::errors::{AppError, ValidationError};
+use crate::feedback::{self, FeedbackSettings, FeedbackTask, Route};
+use crate::integrations::ai_text_processor::{
+    ChangeType, ProcessingContext, ProcessingMetadata, ProcessingOptions, ProcessingRequest, ProcessingResult, TextChange,
+    ToneType,
+};
+use crate::integrations::code_dictation::CodeDictationSettings;
+use crate::integrations::content_filter::{redact_pii, OutputTarget, PiiKind};
+use crate::integrations::length_limit::{self, LengthCompliance, LengthConstraint};
+use crate::integrations::preservation::{self, PreservedKind};
+use crate::integrations::bilingual::{BilingualLayout, BilingualText};
+use crate::integrations::{OutputFormat, TranslationContext, TranslationOptions};
+use crate::plugins::{self, StageHook};
+use crate::unicode_text::{contains_cjk, word_count};
+use crate::validation::validate_language_code;
+use crate::AppState;
+
+/// One step of the pipeline with its options
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "stage", rename_all = "snake_case")]
+pub enum PipelineStage {
+    /// Drops utterances too short to be intended speech, such as a cough picked up as "uh"
+    Vad {
+        #[serde(default = "default_min_words")]
+        min_words: usize,
+    },
+    Punctuation,
+    FillerRemoval {
+        /// Words and phrases removed wherever they stand alone; empty uses the built-in list
+        #[serde(default)]
+        fillers: Vec<String>,
+    },
+    PiiRedaction {
+        #[serde(default = "default_pii_kinds")]
+        kinds: Vec<PiiKind>,
+    },
+    /// Grammar, tone and code formatting by the text processor
+    Enhance {
+        /// Fixed tone; without one the tone is routed by result ratings
+        #[serde(default)]
+        tone: Option<ToneType>,
+        #[serde(default = "default_aggressiveness")]
+        aggressiveness: f32,
+        /// Most the result may be, e.g. tweet length; longer results get shortening passes
+        #[serde(default)]
+        length: Option<LengthConstraint>,
+        /// Spans put back exactly as dictated if enhancement alters them, for academic and legal text
+        #[serde(default)]
+        preserve: Vec<PreservedKind>,
+    },
+    Translate {
+        target_language: String,
+        #[serde(default)]
+        source_language: Option<String>,
+        /// Type the original with the translation, sentence by sentence, instead of the translation alone
+        #[serde(default)]
+        bilingual: Option<BilingualLayout>,
+        /// Send each translation to the frontend as sentence pairs in a "live-translation" event
+        #[serde(default)]
+        live: bool,
+    },
+    /// The enabled plugins' stages for `hook`
+    Plugins { hook: StageHook },
+    /// Applies the content filter and hands the text to the frontend to type; without it results are only shown
+    Inject,
+}
+
+fn default_min_words() -> usize {
+    1
+}
+
+fn default_pii_kinds() -> Vec<PiiKind> {
+    PiiKind::ALL.to_vec()
+}
+
+fn default_aggressiveness() -> f32 {
+    0.7
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StageKind {
+    Vad,
+    Punctuation,
+    FillerRemoval,
+    PiiRedaction,
+    Enhance,
+    Translate,
+    Plugins,
+    Inject,
+}
+
+impl PipelineStage {
+    pub fn kind(&self) -> StageKind {
+        match self {
+            PipelineStage::Vad { .. } => StageKind::Vad,
+            PipelineStage::Punctuation => StageKind::Punctuation,
+            PipelineStage::FillerRemoval { .. } => StageKind::FillerRemoval,
+            PipelineStage::PiiRedaction { .. } => StageKind::PiiRedaction,
+            PipelineStage::Enhance { .. } => StageKind::Enhance,
+            PipelineStage::Translate { .. } => StageKind::Translate,
+            PipelineStage::Plugins { .. } => StageKind::Plugins,
+            PipelineStage::Inject => StageKind::Inject,
+        }
+    }
+
+    /// Stages that rewrite prose and would break dictated code
+    fn prose_only(&self) -> bool {
+        matches!(
+            self.kind(),
+            StageKind::Punctuation | StageKind::FillerRemoval | StageKind::Translate
+        )
+    }
+
+    /// Stages that change what was said rather than how it is laid out; verbatim mode skips them
+    fn rewrites_words(&self) -> bool {
+        matches!(
+            self.kind(),
+            StageKind::FillerRemoval | StageKind::Enhance | StageKind::Translate | StageKind::Plugins
+        )
+    }
+}
+
+/// Verbatim transcription for court reporting and research interviews: fillers, false starts and
+/// spoken symbols stay exactly as recognized, and nothing is sent for rewriting
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VerbatimSettings {
+    pub enabled: bool,
+    /// Capitalize sentence starts and "I" in place of the Punctuation stage; no marks are added
+    pub capitalize: bool,
+    /// End an utterance that has no closing mark with a period
+    pub close_sentences: bool,
+}
+
+impl Default for VerbatimSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            capitalize: true,
+            close_sentences: false,
+        }
+    }
+}
+
+/// The stages a profile runs, in order
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PipelineSettings {
+    pub stages: Vec<PipelineStage>,
+}
+
+impl Default for PipelineSettings {
+    /// The flow dictation has always had
+    fn default() -> Self {
+        Self {
+            stages: vec![
+                PipelineStage::Vad { min_words: default_min_words() },
+                PipelineStage::Plugins { hook: StageHook::BeforeAi },
+                PipelineStage::FillerRemoval { fillers: Vec::new() },
+                PipelineStage::Punctuation,
+                PipelineStage::Enhance {
+                    tone: None,
+                    aggressiveness: default_aggressiveness(),
+                    length: None,
+                    preserve: Vec::new(),
+                },
+                PipelineStage::Plugins { hook: StageHook::AfterAi },
+                PipelineStage::Inject,
+            ],
+        }
+    }
+}
+
+/// Problems that stop a pipeline from being saved, and ones worth a second look
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PipelineValidation {
+    pub errors: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+impl PipelineValidation {
+    pub fn into_result(self) -> Result<Vec<String>, AppError> {
+        match self.errors.is_empty() {
+            true => Ok(self.warnings),
+            false => Err(AppError::Validation(ValidationError::InvalidConfigValue(format!(
+                "Invalid pipeline: {}",
+                self.errors.join("; ")
+            )))),
+        }
+    }
+}
+
+/// Check that the stages can run in the given order
+pub fn validate(settings: &PipelineSettings) -> PipelineValidation {
+    let mut validation = PipelineValidation::default();
+    let stages = &settings.stages;
+    let position = |kind: StageKind| stages.iter().position(|stage| stage.kind() == kind);
+    let plugin_position = |hook: StageHook| {
+        stages
+            .iter()
+            .position(|stage| matches!(stage, PipelineStage::Plugins { hook: h } if *h == hook))
+    };
+
+    for (index, stage) in stages.iter().enumerate() {
+        let repeated = stages[..index].iter().any(|earlier| match (earlier, stage) {
+            (PipelineStage::Plugins { hook: a }, PipelineStage::Plugins { hook: b }) => a == b,
+            (earlier, stage) => earlier.kind() == stage.kind() && stage.kind() != StageKind::Plugins,
+        });
+        if repeated {
+            validation.errors.push(format!("{:?} appears more than once", stage.kind()));
+        }
+        match stage {
+            PipelineStage::Vad { .. } if index != 0 => {
+                validation.errors.push("Vad must be the first stage".to_string());
+            }
+            PipelineStage::Inject if index + 1 != stages.len() => {
+                validation.errors.push("Inject must be the last stage".to_string());
+            }
+            PipelineStage::Enhance {
+                aggressiveness, length, ..
+            } => {
+                if !(0.0..=1.0).contains(aggressiveness) {
+                    validation.errors.push("Enhance aggressiveness must be between 0 and 1".to_string());
+                }
+                if let Some(Err(e)) = length.as_ref().map(LengthConstraint::validate) {
+                    validation.errors.push(e);
+                }
+            }
+            PipelineStage::Translate {
+                target_language,
+                source_language,
+                ..
+            } => {
+                for code in std::iter::once(target_language).chain(source_language) {
+                    if validate_language_code(code).is_err() {
+                        validation.errors.push(format!("'{}' is not a language code like \"de\" or \"pt-BR\"", code));
+                    }
+                }
+            }
+            PipelineStage::PiiRedaction { kinds } if kinds.is_empty() => {
+                validation.warnings.push("PiiRedaction has no kinds selected and changes nothing".to_string());
+            }
+            _ => {}
+        }
+    }
+
+    // Plugin hooks are named for where they run relative to AI processing
+    if let Some(enhance) = position(StageKind::Enhance) {
+        if plugin_position(StageHook::BeforeAi).map_or(false, |plugins| plugins > enhance) {
+            validation.errors.push("Plugins before_ai must come before Enhance".to_string());
+        }
+        if plugin_position(StageHook::AfterAi).map_or(false, |plugins| plugins < enhance) {
+            validation.errors.push("Plugins after_ai must come after Enhance".to_string());
+        }
+    }
+    if let (Some(before), Some(after)) = (plugin_position(StageHook::BeforeAi), plugin_position(StageHook::AfterAi)) {
+        if before > after {
+            validation.errors.push("Plugins before_ai must come before Plugins after_ai".to_string());
+        }
+    }
+
+    if let (Some(redaction), Some(translate)) = (position(StageKind::PiiRedaction), position(StageKind::Translate)) {
+        if redaction > translate {
+            validation
+                .warnings
+                .push("Translation sends text to the cloud before personal data is redacted".to_string());
+        }
+    }
+    if let (Some(fillers), Some(enhance)) = (position(StageKind::FillerRemoval), position(StageKind::Enhance)) {
+        if fillers > enhance {
+            validation
+                .warnings
+                .push("Filler words reach Enhance, which may rewrite around them".to_string());
+        }
+    }
+    if position(StageKind::Inject).is_none() {
+        validation
+            .warnings
+            .push("Without Inject, results are shown but never typed".to_string());
+    }
+    validation
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StageReport {
+    pub stage: StageKind,
+    pub duration_ms: f64,
+    pub changed: bool,
+    /// Why the stage did not run
+    pub skipped: Option<String>,
+    /// What the stage found or did, e.g. "2 filler word(s) removed"
+    pub detail: Option<String>,
+}
+
+/// Sent as "pipeline-report" and attached to the result
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PipelineReport {
+    pub stages: Vec<StageReport>,
+    pub total_ms: f64,
+    /// Stage that ended the run early, e.g. Vad dropping a stray sound
+    pub stopped_at: Option<StageKind>,
+    pub injected: bool,
+}
+
+/// The processed utterance and how it was produced
+pub struct PipelineRun {
+    pub result: ProcessingResult,
+    /// Route of the Enhance stage, for attributing ratings
+    pub route: Option<Route>,
+    pub injected: bool,
+}
+
+/// Run `settings`' stages over `transcript`; stages that do not apply are reported as skipped
+pub async fn run(
+    state: &AppState,
+    window: &Window,
+    settings: &PipelineSettings,
+    transcript: &str,
+    language: &str,
+    code_dictation: &CodeDictationSettings,
+    feedback_settings: &FeedbackSettings,
+    verbatim: &VerbatimSettings,
+) -> Result<PipelineRun, AppError> {
+    let started = Instant::now();
+    let mut result = crate::unprocessed_result(transcript.to_string(), transcript.to_string());
+    if code_dictation.enabled {
+        result.context_used = ProcessingContext::Code;
+    }
+    result.metadata.verbatim = verbatim.enabled;
+    let mut route = None;
+    let mut report = PipelineReport {
+        stages: Vec::new(),
+        total_ms: 0.0,
+        stopped_at: None,
+        injected: false,
+    };
+
+    for stage in &settings.stages {
+        let stage_started = Instant::now();
+        let before = result.processed_text.clone();
+        let mut skipped = None;
+        let mut detail = None;
+
+        if code_dictation.enabled && stage.prose_only() {
+            skipped = Some("Not applied to code".to_string());
+        } else if verbatim.enabled && stage.rewrites_words() {
+            skipped = Some("Verbatim mode keeps the words as spoken".to_string());
+        } else {
+            match stage {
+                PipelineStage::Vad { min_words } => {
+                    let words = word_count(&result.processed_text);
+                    // A lone "um" is part of the record in verbatim mode
+                    let only_fillers =
+                        !verbatim.enabled && remove_fillers(&result.processed_text, &[]).0.trim().is_empty();
+                    if words < *min_words || only_fillers {
+                        result.processed_text.clear();
+                        detail = Some(format!("Dropped {} word(s) as noise", words));
+                        report.stopped_at = Some(StageKind::Vad);
+                    }
+                }
+                PipelineStage::Punctuation if verbatim.enabled => {
+                    result.processed_text = format_verbatim(&result.processed_text, language, verbatim);
+                    detail = Some("Verbatim formatting".to_string());
+                }
+                PipelineStage::Punctuation => {
+                    result.processed_text = punctuate(&result.processed_text, language);
+                    if result.processed_text != before {
+                        result.changes_made.push(TextChange {
+                            change_type: ChangeType::Punctuation,
+                            original: before.clone(),
+                            replacement: result.processed_text.clone(),
+                            position: 0,
+                            confidence: 0.8,
+                        });
+                    }
+                }
+                PipelineStage::FillerRemoval { fillers } => {
+                    let (text, removed) = remove_fillers(&result.processed_text, fillers);
+                    for (position, filler) in &removed {
+                        result.changes_made.push(TextChange {
+                            change_type: ChangeType::FillerRemoval,
+                            original: filler.clone(),
+                            replacement: String::new(),
+                            position: *position,
+                            confidence: 0.9,
+                        });
+                    }
+                    result.metadata.filler_words_removed += removed.len();
+                    if !removed.is_empty() {
+                        detail = Some(format!("{} filler word(s) removed", removed.len()));
+                    }
+                    result.processed_text = text;
+                }
+                PipelineStage::PiiRedaction { kinds } => {
+                    let redaction = redact_pii(&result.processed_text, kinds);
+                    if redaction.changed() {
+                        let found: Vec<String> = redaction
+                            .found
+                            .iter()
+                            .map(|(kind, count)| format!("{} {:?}", count, kind))
+                            .collect();
+                        detail = Some(format!("Redacted {}", found.join(", ")));
+                    }
+                    result.processed_text = redaction.text;
+                }
+                PipelineStage::Enhance {
+                    tone,
+                    aggressiveness,
+                    length,
+                    preserve,
+                } => {
+                    // Code is as long as it needs to be
+                    let length = length.as_ref().filter(|_| !code_dictation.enabled);
+                    let dictated = result.processed_text.clone();
+                    match enhance(state, &result, tone.clone(), *aggressiveness, length, code_dictation, feedback_settings)
+                        .await?
+                    {
+                        Some((enhanced, chosen)) => {
+                            let original_text = std::mem::take(&mut result.original_text);
+                            let mut changes = std::mem::take(&mut result.changes_made);
+                            let fillers_removed = result.metadata.filler_words_removed;
+                            changes.extend(enhanced.changes_made.iter().cloned());
+                            result = ProcessingResult {
+                                id: result.id.clone(),
+                                original_text,
+                                changes_made: changes,
+                                ..enhanced
+                            };
+                            result.metadata.filler_words_removed += fillers_removed;
+                            route = chosen;
+                            if let Some(constraint) = length {
+                                let (shortened, compliance) = hold_to_length(state, &result.processed_text, constraint).await;
+                                if let Some(shortened) = shortened {
+                                    result.changes_made.push(TextChange {
+                                        change_type: ChangeType::Style,
+                                        original: std::mem::replace(&mut result.processed_text, shortened.clone()),
+                                        replacement: shortened,
+                                        position: 0,
+                                        confidence: 0.7,
+                                    });
+                                }
+                                detail = Some(format!(
+                                    "{} of at most {} {}{}",
+                                    compliance.length,
+                                    compliance.limit,
+                                    compliance.unit.label(),
+                                    if compliance.compliant { "" } else { ", over the limit" }
+                                ));
+                                result.metadata.length = Some(compliance);
+                            }
+                            if !preserve.is_empty() {
+                                let outcome = preservation::restore(&dictated, &result.processed_text, preserve);
+                                if !outcome.changes.is_empty() {
+                                    let conflicts = outcome.conflicts();
+                                    let restored = outcome.changes.len() - conflicts;
+                                    let note = format!("restored {} altered span(s), {} conflict(s)", restored, conflicts);
+                                    detail = Some(match detail.take() {
+                                        Some(detail) => format!("{}; {}", detail, note),
+                                        None => note,
+                                    });
+                                    result.processed_text = outcome.text;
+                                    result.changes_made.extend(outcome.changes);
+                                    // Putting spans back can lengthen the text again
+                                    if let Some(compliance) = result.metadata.length.as_mut() {
+                                        let attempts = compliance.attempts;
+                                        *compliance = length_limit::check(&compliance.constraint, &result.processed_text, attempts);
+                                    }
+                                }
+                            }
+                        }
+                        None => skipped = Some("Text processor not initialized".to_string()),
+                    }
+                }
+                PipelineStage::Translate {
+                    target_language,
+                    source_language,
+                    bilingual,
+                    live,
+                } => {
+                    // A gateway that fails to start only skips the stage, like one that was never set up
+                    if let Err(e) = crate::startup::ensure_started(state, crate::startup::Service::AiGateway).await {
+                        log::warn!("AI ML API Gateway did not start for translation: {}", e);
+                    }
+                    let gateway = state.ai_ml_gateway.lock().await;
+                    match gateway.as_ref() {
+                        Some(gateway) => {
+                            let translation = gateway
+                                .translate_with_enhancement(
+                                    result.processed_text.clone(),
+                                    source_language.clone().or_else(|| Some(language.to_string())),
+                                    target_language.clone(),
+                                    TranslationContext::default(),
+                                    TranslationOptions::default(),
+                                )
+                                .await
+                                .map_err(AppError::from)?;
+                            detail = Some(format!("Translated to {}", target_language));
+                            // Pairs are only worth aligning for a bilingual result or a live view
+                            let pairs = (bilingual.is_some() || *live).then(|| {
+                                BilingualText::align(
+                                    &result.processed_text,
+                                    &translation.translated_text,
+                                    &translation.source_language,
+                                    &translation.target_language,
+                                )
+                            });
+                            if let (true, Some(pairs)) = (*live, pairs.as_ref()) {
+                                let _ = window.emit("live-translation", pairs);
+                            }
+                            result.processed_text = match (bilingual, pairs) {
+                                (Some(layout), Some(pairs)) => pairs.render(*layout, &OutputFormat::PlainText),
+                                _ => translation.translated_text,
+                            };
+                        }
+                        None => skipped = Some("AI ML API Gateway not initialized".to_string()),
+                    }
+                }
+                PipelineStage::Plugins { hook } => {
+                    let (text, outcomes) = plugins::run_stages(*hook, &result.processed_text, language).await;
+                    if outcomes.is_empty() {
+                        skipped = Some("No enabled plugin has a stage here".to_string());
+                    } else {
+                        let failed = outcomes.iter().filter(|outcome| outcome.error.is_some()).count();
+                        detail = Some(format!("{} plugin stage(s), {} failed", outcomes.len(), failed));
+                        let _ = window.emit("plugin-stages", &outcomes);
+                    }
+                    result.processed_text = text;
+                }
+                PipelineStage::Inject => {
+                    result.processed_text =
+                        crate::filter_output(state, window, &result.processed_text, OutputTarget::Injection).await;
+                    report.injected = !result.processed_text.trim().is_empty();
+                }
+            }
+        }
+
+        report.stages.push(StageReport {
+            stage: stage.kind(),
+            duration_ms: stage_started.elapsed().as_secs_f64() * 1000.0,
+            changed: result.processed_text != before,
+            skipped,
+            detail,
+        });
+        if report.stopped_at.is_some() {
+            break;
+        }
+    }
+
+    result.metadata.word_count_before = word_count(transcript);
+    result.metadata.word_count_after = word_count(&result.processed_text);
+    report.total_ms = started.elapsed().as_secs_f64() * 1000.0;
+    result.processing_time_ms = report.total_ms as u64;
+    let injected = report.injected;
+    let _ = window.emit("pipeline-report", &report);
+    result.pipeline = Some(report);
+    Ok(PipelineRun { result, route, injected })
+}
+
+/// Run the text processor, routing the tone by ratings unless the stage fixes one
+async fn enhance(
+    state: &AppState,
+    current: &ProcessingResult,
+    tone: Option<ToneType>,
+    aggressiveness: f32,
+    length: Option<&LengthConstraint>,
+    code_dictation: &CodeDictationSettings,
+    feedback_settings: &FeedbackSettings,
+) -> Result<Option<(ProcessingResult, Option<Route>)>, AppError> {
+    // Rated tones win more often; with a single configured tone this is always that tone
+    let route = match tone {
+        Some(_) => None,
+        None => feedback::get_feedback_loop().lock().await.choose(
+            FeedbackTask::Dictation,
+            &feedback::dictation_routes(feedback_settings),
+            feedback_settings,
+        ),
+    };
+    let tone = tone.unwrap_or_else(|| route.as_ref().map_or(ToneType::Professional, feedback::route_tone));
+    // With a limit the model writes to it from the start rather than being shortened afterwards
+    if let Some(constraint) = length {
+        if let Some(result) = enhance_to_length(state, current, &tone, constraint).await {
+            return Ok(Some((result, route)));
+        }
+    }
+    let processor = state.text_processor.lock().await;
+    let Some(processor) = processor.as_ref() else {
+        return Ok(None);
+    };
+    let request = ProcessingRequest {
+        id: Uuid::new_v4().to_string(),
+        text: current.processed_text.clone(),
+        context: if code_dictation.enabled { ProcessingContext::Code } else { ProcessingContext::Email },
+        tone,
+        options: ProcessingOptions {
+            aggressiveness,
+            // Fillers and punctuation are stages of their own
+            remove_fillers: false,
+            preserve_formatting: false,
+            smart_punctuation: false,
+            auto_correct: true,
+            length: length.cloned(),
+        },
+        timestamp: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0),
+    };
+    let result = processor
+        .process_text(request)
+        .await
+        .map_err(|e| AppError::TextProcessing(e.to_string().into()))?;
+    Ok(Some((result, route)))
+}
+
+/// Enhance with the text model, the length limit in its prompt; `None` when the gateway is unavailable or fails,
+/// so the text processor takes over
+async fn enhance_to_length(
+    state: &AppState,
+    current: &ProcessingResult,
+    tone: &ToneType,
+    constraint: &LengthConstraint,
+) -> Option<ProcessingResult> {
+    if let Err(e) = crate::startup::ensure_started(state, crate::startup::Service::AiGateway).await {
+        log::warn!("AI ML API Gateway did not start for length-limited enhancement: {}", e);
+        return None;
+    }
+    let model = state.settings.snapshot().ai_ml_settings.text_model.clone();
+    let gateway = state.ai_ml_gateway.lock().await.clone()?;
+    let started = Instant::now();
+    let text = &current.processed_text;
+    let prompt = length_limit::enhancement_prompt(constraint, tone);
+    let enhanced = match gateway.complete_with_failover(model, prompt, text.clone(), Some(0.3)).await {
+        Ok((enhanced, _usage)) => enhanced.trim().to_string(),
+        Err(e) => {
+            log::warn!("Length-limited enhancement failed, using the text processor: {}", e);
+            return None;
+        }
+    };
+    if enhanced.is_empty() {
+        return None;
+    }
+    let mut changes_made = Vec::new();
+    if enhanced != *text {
+        changes_made.push(TextChange {
+            change_type: ChangeType::Style,
+            original: text.clone(),
+            replacement: enhanced.clone(),
+            position: 0,
+            confidence: 0.8,
+        });
+    }
+    Some(ProcessingResult {
+        id: Uuid::new_v4().to_string(),
+        original_text: text.clone(),
+        metadata: ProcessingMetadata {
+            readability_before: 0.0,
+            readability_after: 0.0,
+            word_count_before: word_count(text),
+            word_count_after: word_count(&enhanced),
+            sentences_processed: 0,
+            errors_corrected: 0,
+            filler_words_removed: 0,
+            verbatim: false,
+            length: None,
+        },
+        processed_text: enhanced,
+        changes_made,
+        confidence_score: 0.8,
+        processing_time_ms: started.elapsed().as_millis() as u64,
+        context_used: ProcessingContext::Email,
+        tone_applied: tone.clone(),
+        latency: None,
+        pipeline: None,
+    })
+}
+
+/// Shorten an enhanced result that came back over `constraint`; without the gateway the result is kept as it is
+async fn hold_to_length(state: &AppState, text: &str, constraint: &LengthConstraint) -> (Option<String>, LengthCompliance) {
+    let compliance = length_limit::check(constraint, text, 0);
+    if compliance.compliant {
+        return (None, compliance);
+    }
+    if let Err(e) = crate::startup::ensure_started(state, crate::startup::Service::AiGateway).await {
+        log::warn!("AI ML API Gateway did not start to shorten the result: {}", e);
+        return (None, compliance);
+    }
+    let model = state.settings.snapshot().ai_ml_settings.text_model.clone();
+    let gateway = state.ai_ml_gateway.lock().await;
+    let Some(gateway) = gateway.as_ref() else {
+        return (None, compliance);
+    };
+    match length_limit::enforce(gateway, &model, constraint, text).await {
+        Ok((shortened, compliance)) => (Some(shortened).filter(|shortened| shortened != text), compliance),
+        Err(e) => {
+            log::warn!("Result not shortened to its length limit: {}", e);
+            (None, compliance)
+        }
+    }
+}
+
+/// Hesitation sounds that are never meant as words
+const DEFAULT_FILLERS: &[&str] = &["um", "umm", "uh", "uhm", "erm", "er", "hmm", "mm"];
+/// Chinese and Japanese hesitations, longest first; CJK has no spaces, so these match without word boundaries
+const DEFAULT_CJK_FILLERS: &[&str] = &["えーと", "えっと", "ええと", "あのー", "うーん", "えー", "嗯", "呃"];
+/// Real words ("that", "well") that are fillers only when a pause mark follows them
+const CJK_PAUSE_FILLERS: &[&str] = &["那个", "就是", "あの", "その", "まあ"];
+/// Commas that follow a filler, ASCII and full-width
+const PAUSE_MARKS: &str = ",、，";
+
+/// Remove standalone fillers with the comma that usually follows them; returns what was removed and where
+fn remove_fillers(text: &str, fillers: &[String]) -> (String, Vec<(usize, String)>) {
+    let custom: Vec<&str> = fillers.iter().map(|f| f.trim()).filter(|f| !f.is_empty()).collect();
+    let (spaced, cjk, pause_only): (Vec<&str>, Vec<&str>, &[&str]) = if custom.is_empty() {
+        (DEFAULT_FILLERS.to_vec(), DEFAULT_CJK_FILLERS.to_vec(), CJK_PAUSE_FILLERS)
+    } else {
+        let (cjk, spaced) = custom.into_iter().partition(|f| contains_cjk(f));
+        (spaced, cjk, &[])
+    };
+    let escape = |words: &[&str]| words.iter().map(|f| regex::escape(f)).collect::<Vec<_>>().join("|");
+    let mut alternatives = Vec::new();
+    if !spaced.is_empty() {
+        alternatives.push(format!(r"\b(?:{})\b,?", escape(&spaced)));
+    }
+    if !cjk.is_empty() {
+        alternatives.push(format!("(?:{})[{}]?", escape(&cjk), PAUSE_MARKS));
+    }
+    if !pause_only.is_empty() {
+        alternatives.push(format!("(?:{})[{}]", escape(pause_only), PAUSE_MARKS));
+    }
+    let pattern = format!(r"(?:{})\s*", alternatives.join("|"));
+    let Ok(regex) = RegexBuilder::new(&pattern).case_insensitive(true).build() else {
+        return (text.to_string(), Vec::new());
+    };
+    let removed: Vec<(usize, String)> = regex
+        .find_iter(text)
+        .map(|m| {
+            let filler = m.as_str().trim_end_matches(|c: char| PAUSE_MARKS.contains(c) || c.is_whitespace());
+            (text[..m.start()].chars().count(), filler.to_string())
+        })
+        .collect();
+    if removed.is_empty() {
+        return (text.to_string(), removed);
+    }
+    let stripped = regex.replace_all(text, "");
+    // A filler at the end of a clause leaves "word ," or a doubled space behind
+    let tidied = Regex::new(r"\s+([,.;:!?])")
+        .map(|re| re.replace_all(&stripped, "$1").into_owned())
+        .unwrap_or_else(|_| stripped.into_owned());
+    let tidied = tidied.trim().trim_start_matches(|c: char| PAUSE_MARKS.contains(c)).trim_start().to_string();
+    (tidied, removed)
+}
+
+/// Capitalize sentence starts and the pronoun "I", and close the last sentence
+fn punctuate(text: &str, language: &str) -> String {
+    format_sentences(text, language, true)
+}
+
+/// Interim text cased the way the Punctuation stage will case the final text, but left open, so words typed
+/// live are not all taken back when the final result arrives capitalized
+pub fn format_interim(text: &str, language: &str, settings: &PipelineSettings, verbatim: &VerbatimSettings) -> String {
+    let punctuated = settings.stages.iter().any(|stage| matches!(stage, PipelineStage::Punctuation));
+    if !punctuated || (verbatim.enabled && !verbatim.capitalize) {
+        return text.to_string();
+    }
+    format_sentences(text, language, false)
+}
+
+/// Lay out a verbatim transcript without changing a word: whitespace is tidied and, if asked,
+/// sentences are capitalized and the last one closed
+fn format_verbatim(text: &str, language: &str, settings: &VerbatimSettings) -> String {
+    if settings.capitalize {
+        return format_sentences(text, language, settings.close_sentences);
+    }
+    let mut out = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if settings.close_sentences && out.chars().last().map_or(false, char::is_alphanumeric) {
+        out.push(sentence_stop(language));
+    }
+    out
+}
+
+fn sentence_stop(language: &str) -> char {
+    // Chinese and Japanese close sentences with a full-width stop
+    if language.starts_with("zh") || language.starts_with("ja") {
+        '。'
+    } else {
+        '.'
+    }
+}
+
+fn format_sentences(text: &str, language: &str, close: bool) -> String {
+    let text = text.trim();
+    if text.is_empty() {
+        return String::new();
+    }
+    let english = language.is_empty() || language.starts_with("en");
+    let mut out = String::with_capacity(text.len() + 1);
+    let mut sentence_start = true;
+    for word in text.split_whitespace() {
+        if !out.is_empty() {
+            out.push(' ');
+        }
+        let word = if english && (word == "i" || word.starts_with("i'")) {
+            let mut chars = word.chars();
+            chars.next();
+            format!("I{}", chars.as_str())
+        } else {
+            word.to_string()
+        };
+        if sentence_start {
+            let mut chars = word.chars();
+            if let Some(first) = chars.next() {
+                out.extend(first.to_uppercase());
+                out.push_str(chars.as_str());
+            }
+        } else {
+            out.push_str(&word);
+        }
+        sentence_start = word.ends_with(['.', '!', '?']);
+    }
+    if close && out.chars().last().map_or(false, char::is_alphanumeric) {
+        out.push(sentence_stop(language));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::golden;
+
+    #[test]
+    fn punctuation_matches_golden() {
+        for case in golden("punctuation") {
+            let input = case["input"].as_str().unwrap();
+            let language = case["language"].as_str().unwrap();
+            assert_eq!(punctuate(input, language), case["expected"].as_str().unwrap(), "input {:?}", input);
+        }
+    }
+
+    #[test]
+    fn filler_removal_matches_golden() {
+        for case in golden("filler_removal") {
+            let input = case["input"].as_str().unwrap();
+            let fillers: Vec<String> = serde_json::from_value(case["fillers"].clone()).unwrap_or_default();
+            let removed: Vec<(usize, String)> = serde_json::from_value(case["removed"].clone()).unwrap();
+            let (text, found) = remove_fillers(input, &fillers);
+            assert_eq!(text, case["expected"].as_str().unwrap(), "input {:?}", input);
+            assert_eq!(found, removed, "input {:?}", input);
+        }
+    }
+
+    #[test]
+    fn interim_text_is_cased_like_the_punctuation_stage_but_left_open() {
+        let settings = PipelineSettings::default();
+        let verbatim = VerbatimSettings::default();
+        assert_eq!(format_interim("so i think we", "en", &settings, &verbatim), "So I think we");
+        assert!(punctuate("so i think we", "en").starts_with(&format_interim("so i think we", "en", &settings, &verbatim)));
+
+        let unpunctuated = PipelineSettings { stages: vec![PipelineStage::Inject] };
+        assert_eq!(format_interim("so i think we", "en", &unpunctuated, &verbatim), "so i think we");
+    }
+
+    #[test]
+    fn vad_ignores_filler_only_utterances() {
+        assert!(remove_fillers("uh, um", &[]).0.is_empty());
+        assert!(!remove_fillers("um yes", &[]).0.is_empty());
+    }
+}
@@ -0,0 +1,441 @@
+//! Platform capabilities for VoiceFlow Pro
+//! Detects the desktop session, picks the text injection and hotkey backends that work in it, and reports what does not
+
+use std::process::Stdio;
+
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tracing::debug;
+
+use crate::errors::AppError;
+use crate::system_activity::{command_output, find_running_application};
+
+/// wlroots-based compositors implement the virtual-keyboard protocol `wtype` types through
+const WLROOTS_DESKTOPS: &[&str] = &["sway", "hyprland", "river", "wayfire", "labwc", "niri", "dwl", "cosmic"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionType {
+    X11,
+    Wayland,
+    Windows,
+    MacOs,
+    Unknown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Feature {
+    TextInjection,
+    GlobalHotkeys,
+    FocusedAppDetection,
+    FullscreenDetection,
+    IdleDetection,
+    SelectionReading,
+    CaretTracking,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Backend {
+    /// The OS API: SendInput, CGEvent/System Events, or the window system's own key grab
+    Native,
+    /// X11 XTest through xdotool
+    XTest,
+    /// The wlroots virtual-keyboard protocol through wtype
+    WlrVirtualKeyboard,
+    /// A kernel uinput device through ydotool, independent of the compositor
+    Uinput,
+    /// Only the X11 key grab, which under Wayland fires while an XWayland window has focus
+    XWaylandGrab,
+    /// Text is put on the clipboard for the user to paste
+    Clipboard,
+    Atspi,
+    Xprop,
+    Xprintidle,
+    PrimarySelection,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Capability {
+    pub feature: Feature,
+    pub supported: bool,
+    pub backend: Option<Backend>,
+    /// Why the feature is missing or limited, and what the user can install or change
+    pub reason: Option<String>,
+}
+
+impl Capability {
+    fn supported(feature: Feature, backend: Backend) -> Self {
+        Self {
+            feature,
+            supported: true,
+            backend: Some(backend),
+            reason: None,
+        }
+    }
+
+    fn limited(feature: Feature, backend: Backend, reason: &str) -> Self {
+        Self {
+            feature,
+            supported: true,
+            backend: Some(backend),
+            reason: Some(reason.to_string()),
+        }
+    }
+
+    fn unsupported(feature: Feature, reason: &str) -> Self {
+        Self {
+            feature,
+            supported: false,
+            backend: None,
+            reason: Some(reason.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlatformCapabilities {
+    pub session_type: SessionType,
+    /// XDG_CURRENT_DESKTOP, e.g. "GNOME" or "sway"
+    pub desktop: Option<String>,
+    /// Desktop portals the session offers, by interface name without the org.freedesktop.portal prefix
+    pub portals: Vec<String>,
+    pub features: Vec<Capability>,
+    pub unsupported: Vec<Feature>,
+}
+
+/// How `inject_text` delivered the text
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InjectionReceipt {
+    pub backend: Backend,
+    /// Set when the text only reached the clipboard and the user has to paste it
+    pub needs_paste: bool,
+    pub previous_clipboard: Option<String>,
+}
+
+/// Session type from the environment; a Wayland session with `DISPLAY` set still counts as Wayland
+pub fn session_type() -> SessionType {
+    if cfg!(target_os = "windows") {
+        return SessionType::Windows;
+    }
+    if cfg!(target_os = "macos") {
+        return SessionType::MacOs;
+    }
+    let env = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+    match env("XDG_SESSION_TYPE").as_deref() {
+        Some("wayland") => return SessionType::Wayland,
+        Some("x11") => return SessionType::X11,
+        _ => {}
+    }
+    if env("WAYLAND_DISPLAY").is_some() {
+        SessionType::Wayland
+    } else if env("DISPLAY").is_some() {
+        SessionType::X11
+    } else {
+        SessionType::Unknown
+    }
+}
+
+fn current_desktop() -> Option<String> {
+    std::env::var("XDG_CURRENT_DESKTOP").ok().filter(|desktop| !desktop.is_empty())
+}
+
+fn is_wlroots(desktop: Option<&str>) -> bool {
+    desktop.map_or(false, |desktop| {
+        let desktop = desktop.to_lowercase();
+        WLROOTS_DESKTOPS.iter().any(|name| desktop.split(':').any(|part| part == *name))
+    })
+}
+
+async fn has_tool(name: &str) -> bool {
+    if cfg!(target_os = "windows") {
+        return command_output("where", &[name]).await.is_some();
+    }
+    command_output("which", &[name]).await.is_some()
+}
+
+/// Portal interfaces exported on the session bus, e.g. "RemoteDesktop" or "GlobalShortcuts"
+async fn portals() -> Vec<String> {
+    let Some(output) = command_output(
+        "gdbus",
+        &[
+            "introspect",
+            "--session",
+            "--dest",
+            "org.freedesktop.portal.Desktop",
+            "--object-path",
+            "/org/freedesktop/portal/desktop",
+        ],
+    )
+    .await
+    else {
+        return Vec::new();
+    };
+    let mut portals: Vec<String> = output
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("interface org.freedesktop.portal."))
+        .map(|name| name.trim_end_matches(" {").to_string())
+        .collect();
+    portals.sort();
+    portals.dedup();
+    portals
+}
+
+/// The backend text injection uses in this session, or `None` when only the clipboard is left
+async fn typing_backend(session: SessionType, desktop: Option<&str>) -> Option<Backend> {
+    match session {
+        SessionType::Windows | SessionType::MacOs => Some(Backend::Native),
+        SessionType::X11 => has_tool("xdotool").await.then_some(Backend::XTest),
+        SessionType::Wayland => {
+            if is_wlroots(desktop) && has_tool("wtype").await {
+                Some(Backend::WlrVirtualKeyboard)
+            } else if has_tool("ydotool").await
+                && find_running_application(&["ydotoold".to_string()]).await.is_some()
+            {
+                Some(Backend::Uinput)
+            } else {
+                None
+            }
+        }
+        SessionType::Unknown => None,
+    }
+}
+
+async fn clipboard_backend(session: SessionType) -> bool {
+    match session {
+        SessionType::Windows | SessionType::MacOs => true,
+        SessionType::X11 => has_tool("xclip").await,
+        SessionType::Wayland => has_tool("wl-copy").await,
+        SessionType::Unknown => false,
+    }
+}
+
+/// Probe the session and the helper tools each feature depends on
+pub async fn capabilities() -> PlatformCapabilities {
+    let session = session_type();
+    let desktop = current_desktop();
+    let portals = if matches!(session, SessionType::X11 | SessionType::Wayland) { portals().await } else { Vec::new() };
+
+    let mut features = vec![
+        injection_capability(session, desktop.as_deref()).await,
+        hotkey_capability(session),
+    ];
+    match session {
+        SessionType::Windows | SessionType::MacOs => {
+            features.push(Capability::supported(Feature::FocusedAppDetection, Backend::Native));
+            if session == SessionType::MacOs {
+                features.push(Capability::supported(Feature::FullscreenDetection, Backend::Native));
+                features.push(Capability::supported(Feature::IdleDetection, Backend::Native));
+            } else {
+                features.push(Capability::unsupported(Feature::FullscreenDetection, "Not detected on Windows"));
+                features.push(Capability::unsupported(Feature::IdleDetection, "Not detected on Windows"));
+            }
+            features.push(Capability::supported(Feature::SelectionReading, Backend::Native));
+            features.push(Capability::supported(Feature::CaretTracking, Backend::Native));
+        }
+        SessionType::X11 => {
+            let xdotool = has_tool("xdotool").await;
+            features.push(if xdotool {
+                Capability::supported(Feature::FocusedAppDetection, Backend::XTest)
+            } else {
+                Capability::unsupported(Feature::FocusedAppDetection, "Install xdotool")
+            });
+            features.push(if xdotool && has_tool("xprop").await {
+                Capability::supported(Feature::FullscreenDetection, Backend::Xprop)
+            } else {
+                Capability::unsupported(Feature::FullscreenDetection, "Install xdotool and xprop")
+            });
+            features.push(if has_tool("xprintidle").await {
+                Capability::supported(Feature::IdleDetection, Backend::Xprintidle)
+            } else {
+                Capability::unsupported(Feature::IdleDetection, "Install xprintidle")
+            });
+            features.push(if has_tool("xclip").await {
+                Capability::supported(Feature::SelectionReading, Backend::PrimarySelection)
+            } else {
+                Capability::unsupported(Feature::SelectionReading, "Install xclip")
+            });
+            features.push(caret_capability().await);
+        }
+        SessionType::Wayland => {
+            let reason = "Wayland does not tell other clients which window has focus";
+            features.push(Capability::unsupported(Feature::FocusedAppDetection, reason));
+            features.push(Capability::unsupported(Feature::FullscreenDetection, reason));
+            features.push(Capability::unsupported(
+                Feature::IdleDetection,
+                "Wayland does not report input idle time to ordinary clients",
+            ));
+            features.push(if has_tool("wl-paste").await {
+                Capability::supported(Feature::SelectionReading, Backend::PrimarySelection)
+            } else {
+                Capability::unsupported(Feature::SelectionReading, "Install wl-clipboard")
+            });
+            features.push(caret_capability().await);
+        }
+        SessionType::Unknown => {
+            for feature in [
+                Feature::FocusedAppDetection,
+                Feature::FullscreenDetection,
+                Feature::IdleDetection,
+                Feature::SelectionReading,
+                Feature::CaretTracking,
+            ] {
+                features.push(Capability::unsupported(feature, "No graphical session was detected"));
+            }
+        }
+    }
+
+    let unsupported = features.iter().filter(|capability| !capability.supported).map(|capability| capability.feature).collect();
+    PlatformCapabilities {
+        session_type: session,
+        desktop,
+        portals,
+        features,
+        unsupported,
+    }
+}
+
+async fn injection_capability(session: SessionType, desktop: Option<&str>) -> Capability {
+    if let Some(backend) = typing_backend(session, desktop).await {
+        return Capability::supported(Feature::TextInjection, backend);
+    }
+    let install = match session {
+        SessionType::X11 => "Install xdotool to type dictation into other apps",
+        SessionType::Wayland if is_wlroots(desktop) => "Install wtype to type dictation into other apps",
+        SessionType::Wayland => "Install ydotool and start ydotoold to type dictation into other apps",
+        _ => "No way to type into other apps was found",
+    };
+    if clipboard_backend(session).await {
+        Capability::limited(
+            Feature::TextInjection,
+            Backend::Clipboard,
+            &format!("{}; until then dictation is copied to the clipboard for you to paste", install),
+        )
+    } else {
+        Capability::unsupported(Feature::TextInjection, install)
+    }
+}
+
+fn hotkey_capability(session: SessionType) -> Capability {
+    match session {
+        SessionType::Windows | SessionType::MacOs | SessionType::X11 => {
+            Capability::supported(Feature::GlobalHotkeys, Backend::Native)
+        }
+        SessionType::Wayland => Capability::limited(
+            Feature::GlobalHotkeys,
+            Backend::XWaylandGrab,
+            "Wayland only delivers hotkeys while an XWayland window has focus; bind a shortcut in the desktop's keyboard settings that opens VoiceFlow Pro",
+        ),
+        SessionType::Unknown => Capability::unsupported(Feature::GlobalHotkeys, "No graphical session was detected"),
+    }
+}
+
+async fn caret_capability() -> Capability {
+    let atspi = command_output("python3", &["-c", "import gi; gi.require_version('Atspi', '2.0')"]).await.is_some();
+    if !atspi {
+        return Capability::unsupported(Feature::CaretTracking, "Install the AT-SPI Python bindings (python3-gi, gir1.2-atspi-2.0)");
+    }
+    if session_type() == SessionType::Wayland {
+        Capability::limited(
+            Feature::CaretTracking,
+            Backend::Atspi,
+            "Some Wayland apps report positions relative to their window, so the overlay may land off the caret",
+        )
+    } else {
+        Capability::supported(Feature::CaretTracking, Backend::Atspi)
+    }
+}
+
+/// Whether registering an OS-wide hotkey can fail without that being the user's mistake
+pub fn hotkeys_may_be_unavailable() -> bool {
+    session_type() == SessionType::Wayland
+}
+
+/// Type `text` into the focused app with the session's backend, falling back to the clipboard when none works
+pub async fn inject_text(text: &str) -> Result<InjectionReceipt, AppError> {
+    let session = session_type();
+    let desktop = current_desktop();
+    if let Some(backend) = typing_backend(session, desktop.as_deref()).await {
+        match type_with(backend, text).await {
+            Ok(()) => {
+                return Ok(InjectionReceipt {
+                    backend,
+                    needs_paste: false,
+                    previous_clipboard: None,
+                })
+            }
+            Err(e) => debug!("Typing with {:?} failed, copying instead: {}", backend, e),
+        }
+    }
+
+    let previous_clipboard = crate::system_activity::clipboard_text().await;
+    let copied = match session {
+        SessionType::Wayland => run("wl-copy", &["--"], Some(text)).await,
+        SessionType::X11 => run("xclip", &["-selection", "clipboard"], Some(text)).await,
+        SessionType::MacOs => run("pbcopy", &[], Some(text)).await,
+        SessionType::Windows => {
+            let script = "[Console]::InputEncoding = [Text.Encoding]::UTF8; Set-Clipboard -Value ([Console]::In.ReadToEnd())";
+            run("powershell", &["-NoProfile", "-NonInteractive", "-Command", script], Some(text)).await
+        }
+        SessionType::Unknown => Err("no graphical session".to_string()),
+    };
+    copied.map_err(|e| AppError::Permission(format!("Text could not be typed or copied: {}", e)))?;
+    Ok(InjectionReceipt {
+        backend: Backend::Clipboard,
+        needs_paste: true,
+        previous_clipboard,
+    })
+}
+
+async fn type_with(backend: Backend, text: &str) -> Result<(), String> {
+    match backend {
+        Backend::XTest => run("xdotool", &["type", "--clearmodifiers", "--delay", "0", "--", text], None).await,
+        Backend::WlrVirtualKeyboard => run("wtype", &["--", text], None).await,
+        Backend::Uinput => run("ydotool", &["type", "--file", "-"], Some(text)).await,
+        Backend::Native if cfg!(target_os = "macos") => {
+            run(
+                "osascript",
+                &["-e", "on run argv", "-e", "tell application \"System Events\" to keystroke (item 1 of argv)", "-e", "end run", text],
+                None,
+            )
+            .await
+        }
+        Backend::Native if cfg!(target_os = "windows") => {
+            // SendKeys treats these as modifiers and groupings unless braced
+            let escaped: String = text
+                .chars()
+                .map(|c| match c {
+                    '+' | '^' | '%' | '~' | '(' | ')' | '{' | '}' | '[' | ']' => format!("{{{}}}", c),
+                    '\n' => "{ENTER}".to_string(),
+                    c => c.to_string(),
+                })
+                .collect();
+            // Without this stdin is decoded in the OEM code page and anything outside ASCII arrives garbled
+            let script = "[Console]::InputEncoding = [Text.Encoding]::UTF8; Add-Type -AssemblyName System.Windows.Forms; [System.Windows.Forms.SendKeys]::SendWait([Console]::In.ReadToEnd())";
+            run("powershell", &["-NoProfile", "-NonInteractive", "-Command", script], Some(&escaped)).await
+        }
+        other => Err(format!("{:?} cannot type text", other)),
+    }
+}
+
+/// Run a helper, feeding it `input` on stdin; the error carries its stderr
+pub(crate) async fn run(program: &str, args: &[&str], input: Option<&str>) -> Result<(), String> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("{} unavailable: {}", program, e))?;
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        stdin.write_all(input.as_bytes()).await.map_err(|e| e.to_string())?;
+    }
+    let output = child.wait_with_output().await.map_err(|e| e.to_string())?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!("{} exited with {}: {}", program, output.status, String::from_utf8_lossy(&output.stderr).trim()))
+    }
+}
@@ -0,0 +1,744 @@
+//! Plugin host for VoiceFlow Pro
+//! Runs third-party text-processing stages and destinations as subprocesses speaking JSON-RPC over stdio
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::sync::Mutex;
+use walkdir::WalkDir;
+
+use crate::error_boundary::{get_error_boundary_registry, ErrorBoundary, ErrorBoundaryConfig};
+use crate::errors::{AppError, ValidationError};
+use crate::integrations::destinations::{Destination, DestinationKind, DestinationMetadata, DestinationReceipt};
+use crate::storage::{ensure_data_dir, DataDir};
+
+const MANIFEST_FILE: &str = "plugin.json";
+/// Protocol revision sent in `initialize`; plugins reject revisions they do not know
+const API_VERSION: u32 = 1;
+const DEFAULT_TIMEOUT_MS: u64 = 3000;
+const MAX_TIMEOUT_MS: u64 = 30000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StageHook {
+    /// On the raw transcript, before AI processing
+    BeforeAi,
+    /// On the processed text, before output filtering and injection
+    AfterAi,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginStage {
+    pub name: String,
+    pub hook: StageHook,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginDestinationSpec {
+    /// Unique within the plugin; the destination id is "<plugin>.<id>"
+    pub id: String,
+    pub name: String,
+    pub kind: DestinationKind,
+}
+
+/// What a plugin may do; it only starts once the user has granted everything it declares
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PluginPermissions {
+    pub network: bool,
+    /// Directories the plugin reads or writes outside its own folder
+    pub filesystem: Vec<String>,
+}
+
+impl PluginPermissions {
+    fn covers(&self, requested: &PluginPermissions) -> bool {
+        (self.network || !requested.network) && requested.filesystem.iter().all(|path| self.filesystem.contains(path))
+    }
+}
+
+/// `plugin.json` at the root of a plugin folder
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginManifest {
+    pub id: String,
+    pub name: String,
+    pub version: String,
+    #[serde(default)]
+    pub description: String,
+    /// Executable, relative to the plugin folder when it contains a path separator, else looked up on PATH
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub stages: Vec<PluginStage>,
+    #[serde(default)]
+    pub destinations: Vec<PluginDestinationSpec>,
+    #[serde(default)]
+    pub permissions: PluginPermissions,
+    /// Per-call limit; a plugin that does not answer in time is restarted
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+fn default_timeout_ms() -> u64 {
+    DEFAULT_TIMEOUT_MS
+}
+
+/// Which plugins are switched on and what the user granted them
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PluginSettings {
+    pub enabled: Vec<String>,
+    pub granted: BTreeMap<String, PluginPermissions>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginInfo {
+    pub manifest: PluginManifest,
+    pub enabled: bool,
+    pub running: bool,
+    pub granted: PluginPermissions,
+    pub crashes: u32,
+    pub last_error: Option<String>,
+}
+
+/// Result of one stage, reported with the dictation so the UI can show what plugins changed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StageOutcome {
+    pub plugin_id: String,
+    pub stage: String,
+    pub changed: bool,
+    pub error: Option<String>,
+}
+
+struct PluginProcess {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: Lines<BufReader<ChildStdout>>,
+    next_id: u64,
+}
+
+struct LoadedPlugin {
+    manifest: PluginManifest,
+    dir: PathBuf,
+    enabled: bool,
+    granted: PluginPermissions,
+    process: Arc<Mutex<Option<PluginProcess>>>,
+    crashes: u32,
+    last_error: Option<String>,
+}
+
+#[derive(Default)]
+struct PluginHost {
+    plugins: BTreeMap<String, LoadedPlugin>,
+}
+
+static HOST: OnceLock<Mutex<PluginHost>> = OnceLock::new();
+/// Destinations of enabled plugins, readable from the synchronous destination list
+static DESTINATIONS: RwLock<Vec<(String, PluginDestinationSpec)>> = RwLock::new(Vec::new());
+
+fn host() -> &'static Mutex<PluginHost> {
+    HOST.get_or_init(|| Mutex::new(PluginHost::default()))
+}
+
+/// Read every installed plugin's manifest and apply the saved enable/grant choices
+pub async fn load_installed(settings: &PluginSettings) -> Result<Vec<PluginInfo>, AppError> {
+    let root = ensure_data_dir(DataDir::Plugins)?;
+    let mut host = host().lock().await;
+    for (_, plugin) in std::mem::take(&mut host.plugins) {
+        stop_process(&plugin).await;
+    }
+    let entries = std::fs::read_dir(&root)
+        .map_err(|e| AppError::Internal(format!("Failed to read {}: {}", root.display(), e)))?;
+    for entry in entries.filter_map(|entry| entry.ok()).filter(|entry| entry.path().is_dir()) {
+        match read_manifest(&entry.path()) {
+            Ok(manifest) => {
+                let granted = settings.granted.get(&manifest.id).cloned().unwrap_or_default();
+                let enabled = settings.enabled.contains(&manifest.id) && granted.covers(&manifest.permissions);
+                host.plugins.insert(
+                    manifest.id.clone(),
+                    LoadedPlugin {
+                        manifest,
+                        dir: entry.path(),
+                        enabled,
+                        granted,
+                        process: Arc::new(Mutex::new(None)),
+                        crashes: 0,
+                        last_error: None,
+                    },
+                );
+            }
+            Err(e) => log::warn!("Skipping plugin in {}: {}", entry.path().display(), e),
+        }
+    }
+    publish_destinations(&host);
+    Ok(host.plugins.values().map(info).collect())
+}
+
+pub async fn list_plugins() -> Vec<PluginInfo> {
+    host().lock().await.plugins.values().map(info).collect()
+}
+
+/// Copy a plugin folder into the plugins directory; it stays disabled until enabled with its permissions
+pub async fn install_plugin(source: &Path) -> Result<PluginInfo, AppError> {
+    let manifest = read_manifest(source)?;
+    let target = ensure_data_dir(DataDir::Plugins)?.join(&manifest.id);
+    let mut host = host().lock().await;
+    if let Some(existing) = host.plugins.remove(&manifest.id) {
+        stop_process(&existing).await;
+    }
+    if target.exists() {
+        std::fs::remove_dir_all(&target)
+            .map_err(|e| AppError::Internal(format!("Failed to replace {}: {}", target.display(), e)))?;
+    }
+    copy_dir(source, &target)?;
+    let plugin = LoadedPlugin {
+        manifest: manifest.clone(),
+        dir: target,
+        enabled: false,
+        granted: PluginPermissions::default(),
+        process: Arc::new(Mutex::new(None)),
+        crashes: 0,
+        last_error: None,
+    };
+    let plugin_info = info(&plugin);
+    host.plugins.insert(manifest.id, plugin);
+    publish_destinations(&host);
+    Ok(plugin_info)
+}
+
+pub async fn uninstall_plugin(id: &str, settings: &mut PluginSettings) -> Result<(), AppError> {
+    let mut host = host().lock().await;
+    let plugin = host
+        .plugins
+        .remove(id)
+        .ok_or_else(|| AppError::Configuration(format!("Plugin '{}' is not installed", id)))?;
+    stop_process(&plugin).await;
+    get_error_boundary_registry().remove(&boundary_name(id)).await;
+    std::fs::remove_dir_all(&plugin.dir)
+        .map_err(|e| AppError::Internal(format!("Failed to remove {}: {}", plugin.dir.display(), e)))?;
+    settings.enabled.retain(|enabled| enabled != id);
+    settings.granted.remove(id);
+    publish_destinations(&host);
+    Ok(())
+}
+
+/// Switch a plugin on with the permissions the user granted, which must cover what it declares
+pub async fn enable_plugin(id: &str, granted: PluginPermissions, settings: &mut PluginSettings) -> Result<PluginInfo, AppError> {
+    let mut host = host().lock().await;
+    let plugin = host
+        .plugins
+        .get_mut(id)
+        .ok_or_else(|| AppError::Configuration(format!("Plugin '{}' is not installed", id)))?;
+    if !granted.covers(&plugin.manifest.permissions) {
+        return Err(AppError::Validation(ValidationError::InvalidConfigValue(format!(
+            "Plugin '{}' needs network: {}, folders: {:?}",
+            id, plugin.manifest.permissions.network, plugin.manifest.permissions.filesystem
+        ))));
+    }
+    // A restart picks up a changed grant
+    stop_process(plugin).await;
+    plugin.enabled = true;
+    plugin.granted = granted.clone();
+    plugin.crashes = 0;
+    plugin.last_error = None;
+    get_error_boundary_registry()
+        .register(boundary_name(id), Arc::new(ErrorBoundary::new(boundary_name(id), Some(boundary_config()))))
+        .await;
+    if !settings.enabled.iter().any(|enabled| enabled == id) {
+        settings.enabled.push(id.to_string());
+    }
+    settings.granted.insert(id.to_string(), granted);
+    let plugin_info = info(plugin);
+    publish_destinations(&host);
+    Ok(plugin_info)
+}
+
+pub async fn disable_plugin(id: &str, settings: &mut PluginSettings) -> Result<PluginInfo, AppError> {
+    let mut host = host().lock().await;
+    let plugin = host
+        .plugins
+        .get_mut(id)
+        .ok_or_else(|| AppError::Configuration(format!("Plugin '{}' is not installed", id)))?;
+    stop_process(plugin).await;
+    plugin.enabled = false;
+    settings.enabled.retain(|enabled| enabled != id);
+    let plugin_info = info(plugin);
+    publish_destinations(&host);
+    Ok(plugin_info)
+}
+
+/// Stop a plugin's process and reset its error boundary; it starts again on its next call
+pub async fn restart_plugin(id: &str) -> Result<PluginInfo, AppError> {
+    let mut host = host().lock().await;
+    let plugin = host
+        .plugins
+        .get_mut(id)
+        .ok_or_else(|| AppError::Configuration(format!("Plugin '{}' is not installed", id)))?;
+    stop_process(plugin).await;
+    plugin.crashes = 0;
+    plugin.last_error = None;
+    if let Some(boundary) = get_error_boundary_registry().get(&boundary_name(id)).await {
+        boundary.reset().await;
+    }
+    Ok(info(plugin))
+}
+
+/// Pass `text` through every enabled stage for `hook`, in plugin id order.
+/// A failing stage is skipped and the text continues unchanged, so a broken plugin cannot stop dictation.
+pub async fn run_stages(hook: StageHook, text: &str, language: &str) -> (String, Vec<StageOutcome>) {
+    let stages: Vec<(String, String)> = {
+        let host = host().lock().await;
+        host.plugins
+            .values()
+            .filter(|plugin| plugin.enabled)
+            .flat_map(|plugin| {
+                plugin
+                    .manifest
+                    .stages
+                    .iter()
+                    .filter(|stage| stage.hook == hook)
+                    .map(|stage| (plugin.manifest.id.clone(), stage.name.clone()))
+            })
+            .collect()
+    };
+
+    let mut text = text.to_string();
+    let mut outcomes = Vec::new();
+    for (plugin_id, stage) in stages {
+        let params = json!({ "stage": stage, "hook": hook, "text": text, "language": language });
+        let outcome = match call(&plugin_id, "process", params).await {
+            Ok(reply) => match reply.get("text").and_then(Value::as_str) {
+                Some(processed) => {
+                    let changed = processed != text;
+                    text = processed.to_string();
+                    StageOutcome { plugin_id, stage, changed, error: None }
+                }
+                None => StageOutcome {
+                    plugin_id,
+                    stage,
+                    changed: false,
+                    error: Some("Reply has no text".to_string()),
+                },
+            },
+            Err(e) => {
+                log::warn!("Plugin stage {}/{} skipped: {}", plugin_id, stage, e);
+                StageOutcome { plugin_id, stage, changed: false, error: Some(e.to_string()) }
+            }
+        };
+        outcomes.push(outcome);
+    }
+    (text, outcomes)
+}
+
+/// Destinations offered by enabled plugins
+pub fn plugin_destinations() -> Vec<Box<dyn Destination>> {
+    let destinations = match DESTINATIONS.read() {
+        Ok(destinations) => destinations.clone(),
+        Err(poisoned) => poisoned.into_inner().clone(),
+    };
+    destinations
+        .into_iter()
+        .map(|(plugin_id, spec)| {
+            Box::new(PluginDestination {
+                id: format!("{}.{}", plugin_id, spec.id),
+                plugin_id,
+                spec,
+            }) as Box<dyn Destination>
+        })
+        .collect()
+}
+
+struct PluginDestination {
+    id: String,
+    plugin_id: String,
+    spec: PluginDestinationSpec,
+}
+
+#[async_trait]
+impl Destination for PluginDestination {
+    fn id(&self) -> &str {
+        &self.id
+    }
+    fn name(&self) -> &str {
+        &self.spec.name
+    }
+    fn kind(&self) -> DestinationKind {
+        self.spec.kind
+    }
+
+    async fn send(&self, text: &str, metadata: &DestinationMetadata) -> Result<DestinationReceipt, AppError> {
+        let params = json!({ "destination": self.spec.id, "text": text, "metadata": metadata });
+        let reply = call(&self.plugin_id, "send", params).await?;
+        let location = reply.get("location").and_then(Value::as_str).map(str::to_string);
+        let message = reply
+            .get("message")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("Sent to {}", self.spec.name));
+        Ok(self.receipt(location, message))
+    }
+}
+
+/// One JSON-RPC call inside the plugin's error boundary, starting the process if needed
+async fn call(plugin_id: &str, method: &str, params: Value) -> Result<Value, AppError> {
+    let (process, manifest, dir, granted) = {
+        let host = host().lock().await;
+        let plugin = host
+            .plugins
+            .get(plugin_id)
+            .filter(|plugin| plugin.enabled)
+            .ok_or_else(|| AppError::Configuration(format!("Plugin '{}' is not enabled", plugin_id)))?;
+        (plugin.process.clone(), plugin.manifest.clone(), plugin.dir.clone(), plugin.granted.clone())
+    };
+    if let Err(e) = check_permissions(&manifest, &dir, &granted) {
+        log::warn!("Plugin '{}' disabled: {}", plugin_id, e);
+        let mut host = host().lock().await;
+        if let Some(plugin) = host.plugins.get_mut(plugin_id) {
+            stop_process(plugin).await;
+            plugin.enabled = false;
+            plugin.last_error = Some(e.to_string());
+        }
+        publish_destinations(&host);
+        return Err(e);
+    }
+    let boundary = match get_error_boundary_registry().get(&boundary_name(plugin_id)).await {
+        Some(boundary) => boundary,
+        None => Arc::new(ErrorBoundary::new(boundary_name(plugin_id), Some(boundary_config()))),
+    };
+
+    let timeout = Duration::from_millis(manifest.timeout_ms.clamp(100, MAX_TIMEOUT_MS));
+    let mut crashed = false;
+    let crashed_flag = &mut crashed;
+    let result = boundary
+        .execute(move || async move {
+            let mut process = process.lock().await;
+            if process.is_none() {
+                *crashed_flag = true;
+                *process = Some(start_process(&manifest, &dir, &granted, timeout).await?);
+                *crashed_flag = false;
+            }
+            let Some(running) = process.as_mut() else {
+                return Err(AppError::Internal(format!("Plugin '{}' did not start", plugin_id)));
+            };
+            match tokio::time::timeout(timeout, running.request(method, params)).await {
+                Ok(Ok(Ok(value))) => Ok(value),
+                // The plugin answered with an error; the process is still healthy
+                Ok(Ok(Err(message))) => Err(AppError::Internal(format!("Plugin '{}' failed: {}", plugin_id, message))),
+                // Crashed, closed its output or sent garbage; a fresh process is started next time
+                Ok(Err(e)) => {
+                    *crashed_flag = true;
+                    kill(process.take()).await;
+                    Err(e)
+                }
+                Err(_) => {
+                    *crashed_flag = true;
+                    kill(process.take()).await;
+                    Err(AppError::Internal(format!(
+                        "Plugin '{}' did not answer within {} ms",
+                        plugin_id,
+                        timeout.as_millis()
+                    )))
+                }
+            }
+        })
+        .await;
+
+    if let Err(e) = &result {
+        let mut host = host().lock().await;
+        if let Some(plugin) = host.plugins.get_mut(plugin_id) {
+            if crashed {
+                plugin.crashes += 1;
+            }
+            plugin.last_error = Some(e.to_string());
+        }
+    }
+    result
+}
+
+impl PluginProcess {
+    /// Send one request and wait for its reply; the inner error is one the plugin reported itself
+    async fn request(&mut self, method: &str, params: Value) -> Result<Result<Value, String>, AppError> {
+        self.next_id += 1;
+        let id = self.next_id;
+        let mut line = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }).to_string();
+        line.push('\n');
+        self.stdin
+            .write_all(line.as_bytes())
+            .await
+            .map_err(|e| AppError::Internal(format!("Plugin input closed: {}", e)))?;
+        self.stdin
+            .flush()
+            .await
+            .map_err(|e| AppError::Internal(format!("Plugin input closed: {}", e)))?;
+
+        loop {
+            let line = self
+                .stdout
+                .next_line()
+                .await
+                .map_err(|e| AppError::Internal(format!("Plugin output unreadable: {}", e)))?
+                .ok_or_else(|| AppError::Internal("Plugin exited".to_string()))?;
+            let message: Value = match serde_json::from_str(&line) {
+                Ok(message) => message,
+                Err(_) => {
+                    log::debug!("Plugin wrote a non-JSON line: {}", line);
+                    continue;
+                }
+            };
+            // Notifications such as log lines carry no id
+            if message.get("id").and_then(Value::as_u64) != Some(id) {
+                if let Some(text) = message.pointer("/params/message").and_then(Value::as_str) {
+                    log::info!("Plugin: {}", text);
+                }
+                continue;
+            }
+            if let Some(error) = message.get("error") {
+                let text = error.get("message").and_then(Value::as_str).unwrap_or("unknown error");
+                return Ok(Err(text.to_string()));
+            }
+            return Ok(Ok(message.get("result").cloned().unwrap_or(Value::Null)));
+        }
+    }
+}
+
+async fn start_process(
+    manifest: &PluginManifest,
+    dir: &Path,
+    granted: &PluginPermissions,
+    timeout: Duration,
+) -> Result<PluginProcess, AppError> {
+    let program = if manifest.command.contains('/') || manifest.command.contains('\\') {
+        dir.join(&manifest.command).into_os_string()
+    } else {
+        manifest.command.clone().into()
+    };
+    let mut command = Command::new(program);
+    command
+        .args(&manifest.args)
+        .current_dir(dir)
+        // Only what the plugin was granted reaches it; nothing else from the user's environment
+        .env_clear()
+        .env("VOICEFLOW_PLUGIN_API", API_VERSION.to_string())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true);
+    for name in ["PATH", "SystemRoot", "LANG"] {
+        if let Ok(value) = std::env::var(name) {
+            command.env(name, value);
+        }
+    }
+    if !granted.filesystem.is_empty() {
+        for name in ["HOME", "USERPROFILE", "TMPDIR", "TEMP"] {
+            if let Ok(value) = std::env::var(name) {
+                command.env(name, value);
+            }
+        }
+    }
+
+    let mut child = command
+        .spawn()
+        .map_err(|e| AppError::Internal(format!("Plugin '{}' could not start: {}", manifest.id, e)))?;
+    let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+        return Err(AppError::Internal(format!("Plugin '{}' has no stdio", manifest.id)));
+    };
+    let mut process = PluginProcess {
+        child,
+        stdin,
+        stdout: BufReader::new(stdout).lines(),
+        next_id: 0,
+    };
+    let params = json!({ "api_version": API_VERSION, "plugin_dir": dir, "permissions": granted });
+    match tokio::time::timeout(timeout, process.request("initialize", params)).await {
+        Ok(Ok(Ok(_))) => Ok(process),
+        Ok(Ok(Err(message))) => {
+            kill(Some(process)).await;
+            Err(AppError::Configuration(format!("Plugin '{}' refused to start: {}", manifest.id, message)))
+        }
+        Ok(Err(e)) => {
+            kill(Some(process)).await;
+            Err(e)
+        }
+        Err(_) => {
+            kill(Some(process)).await;
+            Err(AppError::Internal(format!("Plugin '{}' did not initialize in time", manifest.id)))
+        }
+    }
+}
+
+async fn stop_process(plugin: &LoadedPlugin) {
+    let mut process = plugin.process.lock().await;
+    if let Some(running) = process.as_mut() {
+        // Polite shutdown first; the process is killed regardless
+        let _ = tokio::time::timeout(Duration::from_millis(500), running.request("shutdown", json!({}))).await;
+    }
+    kill(process.take()).await;
+}
+
+async fn kill(process: Option<PluginProcess>) {
+    if let Some(mut process) = process {
+        let _ = process.child.kill().await;
+    }
+}
+
+fn read_manifest(dir: &Path) -> Result<PluginManifest, AppError> {
+    let path = dir.join(MANIFEST_FILE);
+    let contents = std::fs::read_to_string(&path)
+        .map_err(|e| AppError::Configuration(format!("Could not read {}: {}", path.display(), e)))?;
+    let manifest: PluginManifest = serde_json::from_str(&contents).map_err(|e| {
+        AppError::Validation(ValidationError::InvalidConfigValue(format!("Invalid plugin manifest: {}", e)))
+    })?;
+    let id_ok = !manifest.id.is_empty()
+        && manifest.id.len() <= 64
+        && manifest.id.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    let invalid = |message: String| AppError::Validation(ValidationError::InvalidConfigValue(message));
+    if !id_ok {
+        return Err(invalid(format!("Plugin id '{}' must be lowercase letters, digits and hyphens", manifest.id)));
+    }
+    if manifest.command.trim().is_empty() || manifest.command.contains("..") {
+        return Err(invalid(format!("Plugin '{}' has no valid command", manifest.id)));
+    }
+    if manifest.stages.is_empty() && manifest.destinations.is_empty() {
+        return Err(invalid(format!("Plugin '{}' declares no stages or destinations", manifest.id)));
+    }
+    Ok(manifest)
+}
+
+/// Run before every call: the grant must still cover what the plugin declares, and the manifest on disk must not
+/// have changed what it asks for or runs since it was enabled
+fn check_permissions(manifest: &PluginManifest, dir: &Path, granted: &PluginPermissions) -> Result<(), AppError> {
+    if !granted.covers(&manifest.permissions) {
+        return Err(AppError::Permission(format!(
+            "Plugin '{}' declares permissions it was not granted",
+            manifest.id
+        )));
+    }
+    let on_disk = read_manifest(dir)?;
+    if on_disk.permissions != manifest.permissions || on_disk.command != manifest.command || on_disk.args != manifest.args {
+        return Err(AppError::Permission(format!(
+            "Plugin '{}' changed its manifest since it was enabled; enable it again to review its permissions",
+            manifest.id
+        )));
+    }
+    Ok(())
+}
+
+fn copy_dir(source: &Path, target: &Path) -> Result<(), AppError> {
+    for entry in WalkDir::new(source).into_iter().filter_map(|entry| entry.ok()) {
+        let Ok(relative) = entry.path().strip_prefix(source) else {
+            continue;
+        };
+        let destination = target.join(relative);
+        let result = if entry.file_type().is_dir() {
+            std::fs::create_dir_all(&destination)
+        } else {
+            std::fs::copy(entry.path(), &destination).map(|_| ())
+        };
+        result.map_err(|e| AppError::Internal(format!("Failed to copy {}: {}", entry.path().display(), e)))?;
+    }
+    Ok(())
+}
+
+fn publish_destinations(host: &PluginHost) {
+    let destinations = host
+        .plugins
+        .values()
+        .filter(|plugin| plugin.enabled)
+        .flat_map(|plugin| {
+            plugin
+                .manifest
+                .destinations
+                .iter()
+                .map(|spec| (plugin.manifest.id.clone(), spec.clone()))
+        })
+        .collect();
+    match DESTINATIONS.write() {
+        Ok(mut published) => *published = destinations,
+        Err(poisoned) => *poisoned.into_inner() = destinations,
+    }
+}
+
+fn info(plugin: &LoadedPlugin) -> PluginInfo {
+    PluginInfo {
+        manifest: plugin.manifest.clone(),
+        enabled: plugin.enabled,
+        running: plugin.process.try_lock().map_or(true, |process| process.is_some()),
+        granted: plugin.granted.clone(),
+        crashes: plugin.crashes,
+        last_error: plugin.last_error.clone(),
+    }
+}
+
+fn boundary_name(plugin_id: &str) -> String {
+    format!("plugin:{}", plugin_id)
+}
+
+/// Failed calls are not retried, so dictation never waits on recovery; after three failures in a minute
+/// the plugin's calls are refused until the breaker times out
+fn boundary_config() -> ErrorBoundaryConfig {
+    ErrorBoundaryConfig {
+        enable_automatic_recovery: false,
+        error_threshold: 3,
+        ..ErrorBoundaryConfig::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(permissions: PluginPermissions) -> PluginManifest {
+        PluginManifest {
+            id: "upper".to_string(),
+            name: "Upper".to_string(),
+            version: "1.0.0".to_string(),
+            description: String::new(),
+            command: "./upper".to_string(),
+            args: Vec::new(),
+            stages: vec![PluginStage { name: "upper".to_string(), hook: StageHook::AfterAi }],
+            destinations: Vec::new(),
+            permissions,
+            timeout_ms: DEFAULT_TIMEOUT_MS,
+        }
+    }
+
+    fn write_manifest(dir: &Path, manifest: &PluginManifest) {
+        std::fs::create_dir_all(dir).unwrap();
+        std::fs::write(dir.join(MANIFEST_FILE), serde_json::to_string(manifest).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn grants_must_cover_declared_permissions() {
+        let requested = PluginPermissions { network: true, filesystem: vec!["/docs".to_string()] };
+        assert!(!PluginPermissions::default().covers(&requested));
+        assert!(!PluginPermissions { network: true, filesystem: Vec::new() }.covers(&requested));
+        assert!(requested.covers(&requested));
+        assert!(requested.covers(&PluginPermissions::default()));
+    }
+
+    #[test]
+    fn calls_are_refused_when_the_manifest_asks_for_more() {
+        let dir = std::env::temp_dir().join(format!("voiceflow-plugin-test-{}", std::process::id()));
+        let loaded = manifest(PluginPermissions::default());
+        write_manifest(&dir, &loaded);
+        assert!(check_permissions(&loaded, &dir, &PluginPermissions::default()).is_ok());
+
+        let widened = manifest(PluginPermissions { network: true, filesystem: Vec::new() });
+        write_manifest(&dir, &widened);
+        let result = check_permissions(&loaded, &dir, &PluginPermissions::default());
+        assert!(matches!(result, Err(AppError::Permission(_))));
+        assert!(matches!(
+            check_permissions(&widened, &dir, &PluginPermissions::default()),
+            Err(AppError::Permission(_))
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,286 @@
+//! Power awareness for VoiceFlow Pro
+//! Detects battery power and trades listening sensitivity, interim updates and background jobs for battery life
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::errors::{AppError, ValidationError};
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+use crate::system_activity::command_output;
+use crate::AppState;
+
+/// Battery state changes slowly; half a minute between probes is plenty
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+static PROFILE: RwLock<Option<PowerProfile>> = RwLock::new(None);
+static LAST_INTERIM_MS: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PowerSavingMode {
+    /// Save power while running on battery
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PowerSource {
+    Ac,
+    Battery,
+    Unknown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PowerLevel {
+    Performance,
+    Saver,
+    /// At or below `low_battery_percent` while on battery
+    LowBattery,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PowerSettings {
+    pub mode: PowerSavingMode,
+    /// Battery charge at or below which the strongest savings apply
+    pub low_battery_percent: u8,
+    /// Multiplier on the speech threshold while saving, so background noise wakes the recognizer less often
+    pub vad_threshold_factor: f32,
+    /// Shortest gap between interim results while saving; final results are never held back
+    pub interim_interval_ms: u64,
+    /// Hold queued background jobs while saving power
+    pub defer_jobs: bool,
+}
+
+impl Default for PowerSettings {
+    fn default() -> Self {
+        Self {
+            mode: PowerSavingMode::Auto,
+            low_battery_percent: 20,
+            vad_threshold_factor: 1.5,
+            interim_interval_ms: 600,
+            defer_jobs: true,
+        }
+    }
+}
+
+pub fn validate(settings: &PowerSettings) -> Result<(), AppError> {
+    let invalid = |message: String| AppError::Validation(ValidationError::InvalidConfigValue(message));
+    if settings.low_battery_percent > 100 {
+        return Err(invalid(format!(
+            "Low battery threshold must be 0-100%, got {}",
+            settings.low_battery_percent
+        )));
+    }
+    if !(1.0..=3.0).contains(&settings.vad_threshold_factor) {
+        return Err(invalid(format!(
+            "Speech threshold factor must be 1.0-3.0, got {}",
+            settings.vad_threshold_factor
+        )));
+    }
+    if settings.interim_interval_ms > 5000 {
+        return Err(invalid(format!(
+            "Interim result interval must be at most 5000 ms, got {}",
+            settings.interim_interval_ms
+        )));
+    }
+    Ok(())
+}
+
+/// What the app currently gives up to save power
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PowerProfile {
+    pub source: PowerSource,
+    pub battery_percent: Option<u8>,
+    pub level: PowerLevel,
+    pub vad_threshold_factor: f32,
+    /// Zero when interim results pass through as they arrive
+    pub interim_interval_ms: u64,
+    pub defer_jobs: bool,
+    pub reason: Option<String>,
+}
+
+impl Default for PowerProfile {
+    fn default() -> Self {
+        Self {
+            source: PowerSource::Unknown,
+            battery_percent: None,
+            level: PowerLevel::Performance,
+            vad_threshold_factor: 1.0,
+            interim_interval_ms: 0,
+            defer_jobs: false,
+            reason: None,
+        }
+    }
+}
+
+/// Latest evaluated profile; full performance until the first probe has run
+pub fn current_profile() -> PowerProfile {
+    PROFILE.read().ok().and_then(|profile| profile.clone()).unwrap_or_default()
+}
+
+/// Whether an interim result may go out now, given the interval of the current profile
+pub fn allow_interim_update() -> bool {
+    let interval = current_profile().interim_interval_ms;
+    if interval == 0 {
+        return true;
+    }
+    let now = chrono::Utc::now().timestamp_millis().max(0) as u64;
+    let last = LAST_INTERIM_MS.load(Ordering::SeqCst);
+    if now.saturating_sub(last) < interval {
+        return false;
+    }
+    LAST_INTERIM_MS.store(now, Ordering::SeqCst);
+    true
+}
+
+/// Probe the power source and apply the settings
+pub async fn evaluate(settings: &PowerSettings) -> PowerProfile {
+    let (source, battery_percent) = probe_power().await;
+    let low = battery_percent.map_or(false, |percent| percent <= settings.low_battery_percent);
+    let saving = match settings.mode {
+        PowerSavingMode::Auto => source == PowerSource::Battery,
+        PowerSavingMode::Always => true,
+        PowerSavingMode::Never => false,
+    };
+    let level = if !saving {
+        PowerLevel::Performance
+    } else if low && source == PowerSource::Battery {
+        PowerLevel::LowBattery
+    } else {
+        PowerLevel::Saver
+    };
+    if level == PowerLevel::Performance {
+        return PowerProfile {
+            source,
+            battery_percent,
+            ..PowerProfile::default()
+        };
+    }
+
+    let reason = match (level, battery_percent) {
+        (PowerLevel::LowBattery, Some(percent)) => format!("Battery at {}%", percent),
+        _ if source == PowerSource::Battery => "Running on battery".to_string(),
+        _ => "Power saving is always on".to_string(),
+    };
+    PowerProfile {
+        source,
+        battery_percent,
+        level,
+        vad_threshold_factor: settings.vad_threshold_factor,
+        interim_interval_ms: settings.interim_interval_ms,
+        defer_jobs: settings.defer_jobs,
+        reason: Some(reason),
+    }
+}
+
+/// Re-evaluate now, e.g. after the settings changed, and publish the result
+pub async fn refresh(settings: &PowerSettings) -> PowerProfile {
+    let profile = evaluate(settings).await;
+    if let Ok(mut current) = PROFILE.write() {
+        *current = Some(profile.clone());
+    }
+    profile
+}
+
+/// Poll the power source, emitting "power-profile-changed" whenever the profile changes
+pub async fn run_power_monitor(state: AppState, app: AppHandle) {
+    let mut previous: Option<PowerProfile> = None;
+    loop {
+        let (settings, resources) = {
+            let settings = state.settings.snapshot();
+            (settings.power.clone(), settings.resources.clone())
+        };
+        let current = refresh(&settings).await;
+        if previous.as_ref() != Some(&current) {
+            let _ = app.emit_all("power-profile-changed", &current);
+            previous = Some(current);
+        }
+        tokio::time::sleep(crate::idle::adjust_interval(POLL_INTERVAL, &resources)).await;
+    }
+}
+
+#[cfg(target_os = "macos")]
+async fn probe_power() -> (PowerSource, Option<u8>) {
+    // "Now drawing from 'Battery Power'" followed by "-InternalBattery-0 (id=…)	85%; discharging; …"
+    let Some(output) = command_output("pmset", &["-g", "batt"]).await else {
+        return (PowerSource::Unknown, None);
+    };
+    let source = if output.contains("'Battery Power'") {
+        PowerSource::Battery
+    } else if output.contains("'AC Power'") {
+        PowerSource::Ac
+    } else {
+        PowerSource::Unknown
+    };
+    let percent = output
+        .split_whitespace()
+        .find_map(|word| word.strip_suffix("%;"))
+        .and_then(|percent| percent.parse().ok());
+    (source, percent)
+}
+
+#[cfg(target_os = "windows")]
+async fn probe_power() -> (PowerSource, Option<u8>) {
+    // BatteryStatus 1 is discharging; desktops without a battery print nothing
+    let Some(output) = command_output(
+        "powershell",
+        &[
+            "-NoProfile",
+            "-NonInteractive",
+            "-Command",
+            "$b = Get-CimInstance Win32_Battery | Select-Object -First 1; if ($b) { \"$($b.BatteryStatus) $($b.EstimatedChargeRemaining)\" }",
+        ],
+    )
+    .await
+    else {
+        return (PowerSource::Unknown, None);
+    };
+    match output.trim().split_once(' ') {
+        Some((status, percent)) => {
+            let source = if status == "1" { PowerSource::Battery } else { PowerSource::Ac };
+            (source, percent.parse().ok())
+        }
+        None => (PowerSource::Ac, None),
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+async fn probe_power() -> (PowerSource, Option<u8>) {
+    let Ok(supplies) = std::fs::read_dir("/sys/class/power_supply") else {
+        return (PowerSource::Unknown, None);
+    };
+    let read = |path: &std::path::Path, name: &str| {
+        std::fs::read_to_string(path.join(name))
+            .ok()
+            .map(|value| value.trim().to_string())
+    };
+    let mut mains_online = None;
+    let mut battery_percent = None;
+    for supply in supplies.filter_map(|entry| entry.ok()).map(|entry| entry.path()) {
+        match read(&supply, "type").as_deref() {
+            Some("Mains") => mains_online = Some(read(&supply, "online").as_deref() == Some("1")),
+            Some("Battery") if battery_percent.is_none() => {
+                battery_percent = read(&supply, "capacity").and_then(|capacity| capacity.parse().ok())
+            }
+            _ => {}
+        }
+    }
+    let source = match (mains_online, battery_percent) {
+        (Some(true), _) => PowerSource::Ac,
+        (Some(false), Some(_)) => PowerSource::Battery,
+        (None, None) => PowerSource::Unknown,
+        // A machine without a battery is on mains; one without a mains supply entry is not
+        (_, None) => PowerSource::Ac,
+        (None, Some(_)) => PowerSource::Battery,
+    };
+    (source, battery_percent)
+}
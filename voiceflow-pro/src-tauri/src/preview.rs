@@ -0,0 +1,226 @@
+//! Result preview for VoiceFlow Pro
+//! Holds processed dictation with a diff for review so nothing is typed until the user approves it
+
+use std::collections::VecDeque;
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use crate::feedback::Route;
+use crate::integrations::ai_text_processor::ProcessingResult;
+use crate::system_activity::frontmost_application;
+use crate::unicode_text::{diff_tokens, direction, strip_bidi_controls, TextDirection};
+
+/// Results waiting for approval; the oldest is discarded when more arrive
+const MAX_PENDING: usize = 20;
+/// Above this many word pairs the diff shows the whole text as replaced instead of aligning words
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PreviewSettings {
+    /// Hold every dictation for approval
+    pub enabled: bool,
+    /// Hold dictation only while one of these apps has focus, e.g. a mail client; matched case-insensitively
+    pub apps: Vec<String>,
+}
+
+impl PreviewSettings {
+    /// Whether dictation into `app` should be held
+    pub fn applies_to(&self, app: Option<&str>) -> bool {
+        self.enabled
+            || app.map_or(false, |app| {
+                self.apps
+                    .iter()
+                    .any(|listed| !listed.trim().is_empty() && listed.trim().eq_ignore_ascii_case(app))
+            })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffKind {
+    Unchanged,
+    Added,
+    Removed,
+}
+
+/// A run of words with the same fate, in reading order
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiffSegment {
+    pub kind: DiffKind,
+    pub text: String,
+    /// Set on right-to-left runs, so the UI can isolate each one and mixed-direction segments keep their order
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub direction: Option<TextDirection>,
+}
+
+/// Sent as "result-pending"; `result.processed_text` is what approval would type
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingResult {
+    pub id: String,
+    pub result: ProcessingResult,
+    /// From the transcript to the proposed text
+    pub diff: Vec<DiffSegment>,
+    /// App that had focus when the result was held
+    pub target_app: Option<String>,
+    pub created_at: u64,
+}
+
+struct PendingEntry {
+    pending: PendingResult,
+    route: Option<Route>,
+}
+
+static PENDING: OnceLock<Mutex<VecDeque<PendingEntry>>> = OnceLock::new();
+
+fn pending() -> &'static Mutex<VecDeque<PendingEntry>> {
+    PENDING.get_or_init(|| Mutex::new(VecDeque::new()))
+}
+
+/// Hold `result` until it is approved or discarded
+pub async fn hold(result: ProcessingResult, route: Option<Route>, target_app: Option<String>) -> PendingResult {
+    let target_app = match target_app {
+        Some(app) => Some(app),
+        None => frontmost_application().await,
+    };
+    let pending_result = PendingResult {
+        id: result.id.clone(),
+        diff: word_segments(&result.original_text, &result.processed_text),
+        result,
+        target_app,
+        created_at: now_secs(),
+    };
+    let mut queue = pending().lock().await;
+    queue.push_back(PendingEntry {
+        pending: pending_result.clone(),
+        route,
+    });
+    while queue.len() > MAX_PENDING {
+        queue.pop_front();
+    }
+    pending_result
+}
+
+/// Remove a held result for approval, with the route it was produced on
+pub async fn take(id: &str) -> Option<(PendingResult, Option<Route>)> {
+    let mut queue = pending().lock().await;
+    let position = queue.iter().position(|entry| entry.pending.id == id)?;
+    queue.remove(position).map(|entry| (entry.pending, entry.route))
+}
+
+pub async fn discard(id: &str) -> bool {
+    take(id).await.is_some()
+}
+
+/// Forget every held result
+pub async fn clear() {
+    pending().lock().await.clear();
+}
+
+/// Held results, oldest first
+pub async fn list() -> Vec<PendingResult> {
+    pending().lock().await.iter().map(|entry| entry.pending.clone()).collect()
+}
+
+/// Word-level diff from the longest common subsequence; case and punctuation changes count as changes.
+/// CJK text is compared character by character, and directional marks alone never count as a change
+pub fn word_segments(before: &str, after: &str) -> Vec<DiffSegment> {
+    let a_tokens = diff_tokens(before);
+    let b_tokens = diff_tokens(after);
+    let a: Vec<String> = a_tokens.iter().map(|(_, word)| strip_bidi_controls(word)).collect();
+    let b: Vec<String> = b_tokens.iter().map(|(_, word)| strip_bidi_controls(word)).collect();
+    let mut segments: Vec<DiffSegment> = Vec::new();
+    let mut push = |kind: DiffKind, (spaced, word): (bool, &str)| match segments.last_mut() {
+        Some(last) if last.kind == kind => {
+            if spaced {
+                last.text.push(' ');
+            }
+            last.text.push_str(word);
+        }
+        _ => segments.push(DiffSegment {
+            kind,
+            text: word.to_string(),
+            direction: None,
+        }),
+    };
+
+    if (a.len() + 1) * (b.len() + 1) > MAX_DIFF_CELLS {
+        a_tokens.iter().for_each(|token| push(DiffKind::Removed, *token));
+        b_tokens.iter().for_each(|token| push(DiffKind::Added, *token));
+        return with_directions(segments);
+    }
+
+    // lengths[i][j] is the common subsequence length of a[i..] and b[j..]
+    let width = b.len() + 1;
+    let mut lengths = vec![0usize; (a.len() + 1) * width];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lengths[i * width + j] = if a[i] == b[j] {
+                lengths[(i + 1) * width + j + 1] + 1
+            } else {
+                lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            push(DiffKind::Unchanged, b_tokens[j]);
+            i += 1;
+            j += 1;
+        } else if lengths[(i + 1) * width + j] >= lengths[i * width + j + 1] {
+            push(DiffKind::Removed, a_tokens[i]);
+            i += 1;
+        } else {
+            push(DiffKind::Added, b_tokens[j]);
+            j += 1;
+        }
+    }
+    a_tokens[i..].iter().for_each(|token| push(DiffKind::Removed, *token));
+    b_tokens[j..].iter().for_each(|token| push(DiffKind::Added, *token));
+    with_directions(segments)
+}
+
+fn with_directions(mut segments: Vec<DiffSegment>) -> Vec<DiffSegment> {
+    for segment in &mut segments {
+        segment.direction = direction(&segment.text).filter(|found| *found == TextDirection::Rtl);
+    }
+    segments
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::golden;
+
+    #[test]
+    fn word_diff_matches_golden() {
+        for case in golden("diff") {
+            let before = case["before"].as_str().unwrap();
+            let after = case["after"].as_str().unwrap();
+            let segments = serde_json::to_value(word_segments(before, after)).unwrap();
+            assert_eq!(segments, case["expected"], "{:?} -> {:?}", before, after);
+        }
+    }
+
+    #[test]
+    fn preview_applies_to_listed_apps() {
+        let settings = PreviewSettings {
+            enabled: false,
+            apps: vec!["Mail".to_string(), " ".to_string()],
+        };
+        assert!(settings.applies_to(Some("mail")));
+        assert!(!settings.applies_to(Some("Slack")));
+        assert!(!settings.applies_to(None));
+    }
+}
@@ -0,0 +1,387 @@
+//! Local user profiles for VoiceFlow Pro
+//! Gives each user on a shared computer their own settings, vocabulary, history and memories
+
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use argon2::password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::encryption::get_data_vault;
+use crate::errors::AppError;
+use crate::integrations::ConversationMemory;
+use crate::storage::{app_data_dir, profile_dir, set_active_profile, DEFAULT_PROFILE_ID};
+use crate::Settings;
+
+const INDEX_FILE: &str = "profiles.json";
+pub(crate) const SETTINGS_FILE: &str = "settings.json";
+pub(crate) const MEMORIES_FILE: &str = "memories.json";
+const MAX_PROFILES: usize = 16;
+const KEYCHAIN_SERVICE: &str = "com.voiceflow.pro";
+
+/// A profile as stored in the index
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ProfileRecord {
+    id: String,
+    name: String,
+    created_at: u64,
+    last_used_at: u64,
+    /// Argon2 PHC string; `None` for profiles without a lock
+    #[serde(default)]
+    passphrase_hash: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ProfileIndex {
+    active: String,
+    profiles: Vec<ProfileRecord>,
+}
+
+impl Default for ProfileIndex {
+    fn default() -> Self {
+        let now = now_secs();
+        Self {
+            active: DEFAULT_PROFILE_ID.to_string(),
+            profiles: vec![ProfileRecord {
+                id: DEFAULT_PROFILE_ID.to_string(),
+                name: "Default".to_string(),
+                created_at: now,
+                last_used_at: now,
+                passphrase_hash: None,
+            }],
+        }
+    }
+}
+
+/// A profile as shown to the UI
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserProfileSummary {
+    pub id: String,
+    pub name: String,
+    pub created_at: u64,
+    pub last_used_at: u64,
+    pub locked: bool,
+    pub active: bool,
+}
+
+/// Tracks the known profiles and which one is active
+#[derive(Debug)]
+pub struct ProfileManager {
+    index: ProfileIndex,
+}
+
+impl ProfileManager {
+    fn load() -> Self {
+        let index = app_data_dir()
+            .ok()
+            .and_then(|dir| fs::read_to_string(dir.join(INDEX_FILE)).ok())
+            .and_then(|json| match serde_json::from_str::<ProfileIndex>(&json) {
+                Ok(index) => Some(index),
+                Err(e) => {
+                    log::warn!("Ignoring invalid {}: {}", INDEX_FILE, e);
+                    None
+                }
+            })
+            .filter(|index| index.profiles.iter().any(|p| p.id == index.active))
+            .unwrap_or_default();
+        set_active_profile(&index.active);
+        Self { index }
+    }
+
+    pub fn active_id(&self) -> &str {
+        &self.index.active
+    }
+
+    pub fn list(&self) -> Vec<UserProfileSummary> {
+        self.index.profiles.iter().map(|record| self.summary(record)).collect()
+    }
+
+    /// Add a profile, optionally locked with a passphrase
+    pub fn create(&mut self, name: &str, passphrase: Option<&str>) -> Result<UserProfileSummary, AppError> {
+        if self.index.profiles.len() >= MAX_PROFILES {
+            return Err(AppError::Configuration(format!("At most {} profiles are supported", MAX_PROFILES)));
+        }
+        if self.index.profiles.iter().any(|p| p.name.eq_ignore_ascii_case(name)) {
+            return Err(AppError::Configuration(format!("A profile named '{}' already exists", name)));
+        }
+
+        let passphrase_hash = passphrase.map(hash_passphrase).transpose()?;
+        let now = now_secs();
+        let record = ProfileRecord {
+            id: Uuid::new_v4().simple().to_string(),
+            name: name.to_string(),
+            created_at: now,
+            last_used_at: now,
+            passphrase_hash,
+        };
+        let dir = profile_dir(&record.id)?;
+        fs::create_dir_all(&dir)
+            .map_err(|e| AppError::Internal(format!("Failed to create {}: {}", dir.display(), e)))?;
+
+        self.index.profiles.push(record.clone());
+        self.save()?;
+        Ok(self.summary(&record))
+    }
+
+    /// Check that `passphrase` opens the profile; unlocked profiles accept anything
+    pub fn verify(&self, id: &str, passphrase: Option<&str>) -> Result<(), AppError> {
+        let record = self.record(id)?;
+        let Some(hash) = &record.passphrase_hash else {
+            return Ok(());
+        };
+        let passphrase = passphrase
+            .ok_or_else(|| AppError::Security(format!("Profile '{}' is locked", record.name)))?;
+        let parsed = PasswordHash::new(hash).map_err(|e| AppError::Security(format!("Corrupt profile lock: {}", e)))?;
+        Argon2::default()
+            .verify_password(passphrase.as_bytes(), &parsed)
+            .map_err(|_| AppError::Security("Incorrect passphrase".to_string()))
+    }
+
+    /// Make `id` the active profile and point per-user storage at it
+    pub fn activate(&mut self, id: &str) -> Result<UserProfileSummary, AppError> {
+        let now = now_secs();
+        let record = self
+            .index
+            .profiles
+            .iter_mut()
+            .find(|p| p.id == id)
+            .ok_or_else(|| AppError::Configuration(format!("Unknown profile: {}", id)))?;
+        record.last_used_at = now;
+        let record = record.clone();
+
+        self.index.active = id.to_string();
+        self.save()?;
+        set_active_profile(id);
+        Ok(self.summary(&record))
+    }
+
+    fn record(&self, id: &str) -> Result<&ProfileRecord, AppError> {
+        self.index
+            .profiles
+            .iter()
+            .find(|p| p.id == id)
+            .ok_or_else(|| AppError::Configuration(format!("Unknown profile: {}", id)))
+    }
+
+    fn summary(&self, record: &ProfileRecord) -> UserProfileSummary {
+        UserProfileSummary {
+            id: record.id.clone(),
+            name: record.name.clone(),
+            created_at: record.created_at,
+            last_used_at: record.last_used_at,
+            locked: record.passphrase_hash.is_some(),
+            active: record.id == self.index.active,
+        }
+    }
+
+    fn save(&self) -> Result<(), AppError> {
+        let dir = app_data_dir()?;
+        fs::create_dir_all(&dir).map_err(|e| AppError::Internal(e.to_string()))?;
+        let json = serde_json::to_vec_pretty(&self.index).map_err(|e| AppError::Internal(e.to_string()))?;
+        fs::write(dir.join(INDEX_FILE), json)
+            .map_err(|e| AppError::Internal(format!("Failed to save profiles: {}", e)))
+    }
+}
+
+static PROFILE_MANAGER: std::sync::OnceLock<tokio::sync::Mutex<ProfileManager>> = std::sync::OnceLock::new();
+
+/// Get the global profile manager; loading it selects the last active profile for storage
+pub fn get_profile_manager() -> &'static tokio::sync::Mutex<ProfileManager> {
+    PROFILE_MANAGER.get_or_init(|| tokio::sync::Mutex::new(ProfileManager::load()))
+}
+
+/// Settings saved for a profile. The API key is machine-wide and never written to profile files;
+/// the profile's other credentials come back from the system keychain.
+pub fn load_profile_settings(id: &str) -> Option<Settings> {
+    let mut settings: Settings = read_json(&profile_dir(id).ok()?.join(SETTINGS_FILE))?;
+    for (name, value) in profile_secrets(&mut settings) {
+        match load_secret(id, name) {
+            Ok(secret) => *value = secret,
+            Err(e) => log::warn!("Could not load {} for profile '{}': {}", name, id, e),
+        }
+    }
+    Some(settings)
+}
+
+pub fn save_profile_settings(id: &str, settings: &Settings) -> Result<(), AppError> {
+    let mut settings = settings.clone();
+    settings.ai_ml_settings.api_key.clear();
+    settings.speech_to_text = settings.speech_to_text.without_keys();
+    // Without a keychain the credential lasts only for this session; it is never written to the file
+    for (name, value) in profile_secrets(&mut settings) {
+        if let Err(e) = store_secret(id, name, value.take()) {
+            log::warn!("Could not keep {} for profile '{}': {}", name, id, e);
+        }
+    }
+    write_json(&profile_dir(id)?.join(SETTINGS_FILE), &settings)
+}
+
+/// Remove a profile's credentials from the keychain, e.g. when its settings are purged
+pub fn forget_profile_secrets(id: &str) -> Result<(), AppError> {
+    for (name, _) in profile_secrets(&mut Settings::default()) {
+        store_secret(id, name, None)?;
+    }
+    Ok(())
+}
+
+/// Credentials a profile keeps in the keychain rather than in its settings file
+fn profile_secrets(settings: &mut Settings) -> [(&'static str, &mut Option<String>); 2] {
+    [
+        ("graph-access-token", &mut settings.destinations.graph_access_token),
+        ("obs-password", &mut settings.streaming.obs_password),
+    ]
+}
+
+fn keychain_entry(id: &str, name: &str) -> Result<keyring::Entry, AppError> {
+    // Tests use a scratch data directory and must leave the user's keychain alone
+    if cfg!(test) {
+        return Err(AppError::Security("Keychain is not used in tests".to_string()));
+    }
+    keyring::Entry::new(KEYCHAIN_SERVICE, &format!("profile-{}-{}", id, name))
+        .map_err(|e| AppError::Security(format!("Keychain unavailable: {}", e)))
+}
+
+fn load_secret(id: &str, name: &str) -> Result<Option<String>, AppError> {
+    match keychain_entry(id, name)?.get_password() {
+        Ok(secret) => Ok(Some(secret)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(AppError::Security(format!("Keychain read failed: {}", e))),
+    }
+}
+
+fn store_secret(id: &str, name: &str, value: Option<String>) -> Result<(), AppError> {
+    let entry = keychain_entry(id, name)?;
+    let result = match value.filter(|secret| !secret.is_empty()) {
+        Some(secret) => entry.set_password(&secret),
+        None => match entry.delete_password() {
+            Err(keyring::Error::NoEntry) => Ok(()),
+            other => other,
+        },
+    };
+    result.map_err(|e| AppError::Security(format!("Keychain write failed: {}", e)))
+}
+
+pub fn load_profile_memory(id: &str) -> Option<ConversationMemory> {
+    read_json(&profile_dir(id).ok()?.join(MEMORIES_FILE))
+}
+
+pub fn save_profile_memory(id: &str, memory: &ConversationMemory) -> Result<(), AppError> {
+    write_json(&profile_dir(id)?.join(MEMORIES_FILE), memory)
+}
+
+/// Profile files go through the data vault so they are encrypted whenever encryption is on
+fn read_json<T: for<'de> Deserialize<'de>>(path: &Path) -> Option<T> {
+    if !path.exists() {
+        return None;
+    }
+    let data = get_data_vault()
+        .read_file(path)
+        .map_err(|e| log::warn!("Could not read {}: {}", path.display(), e))
+        .ok()?;
+    serde_json::from_slice(&data)
+        .map_err(|e| log::warn!("Ignoring invalid {}: {}", path.display(), e))
+        .ok()
+}
+
+fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<(), AppError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| AppError::Internal(e.to_string()))?;
+    }
+    let json = serde_json::to_vec_pretty(value).map_err(|e| AppError::Internal(e.to_string()))?;
+    get_data_vault().write_file(path, &json)
+}
+
+/// A spoken change to the active profile's vocabulary
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "action", content = "term")]
+pub enum VocabularyCommand {
+    Add(String),
+    Remove(String),
+}
+
+/// Recognize "add <term> to my vocabulary" and "remove <term> from the vocabulary"
+pub fn parse_vocabulary_command(utterance: &str) -> Option<VocabularyCommand> {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    let pattern = PATTERN.get_or_init(|| {
+        Regex::new(
+            r#"(?i)^\s*(?:please\s+)?(add|remove|delete|forget)\s+(?:the\s+(?:word|term|name)\s+)?["'“]?(.+?)["'”]?\s+(?:to|from)\s+(?:my\s+|the\s+)?(?:vocabulary|dictionary|word\s+list)\s*[.!]?\s*$"#,
+        )
+        .expect("vocabulary command pattern is valid")
+    });
+    let captures = pattern.captures(utterance)?;
+    let term = captures.get(2)?.as_str().trim().to_string();
+    if term.is_empty() {
+        return None;
+    }
+    match captures.get(1)?.as_str().to_lowercase().as_str() {
+        "add" => Some(VocabularyCommand::Add(term)),
+        _ => Some(VocabularyCommand::Remove(term)),
+    }
+}
+
+/// Apply `command` to `vocabulary`, ignoring case; false when there was nothing to change
+pub fn apply_vocabulary_command(vocabulary: &mut Vec<String>, command: &VocabularyCommand) -> bool {
+    match command {
+        VocabularyCommand::Add(term) => {
+            if vocabulary.iter().any(|known| known.eq_ignore_ascii_case(term)) {
+                return false;
+            }
+            vocabulary.push(term.clone());
+            true
+        }
+        VocabularyCommand::Remove(term) => {
+            let before = vocabulary.len();
+            vocabulary.retain(|known| !known.eq_ignore_ascii_case(term));
+            vocabulary.len() != before
+        }
+    }
+}
+
+fn hash_passphrase(passphrase: &str) -> Result<String, AppError> {
+    if passphrase.chars().count() < 4 {
+        return Err(AppError::Validation("Profile passphrase must be at least 4 characters".to_string().into()));
+    }
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(passphrase.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| AppError::Security(format!("Could not hash passphrase: {}", e)))
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vocabulary_commands_need_the_word_vocabulary() {
+        assert_eq!(
+            parse_vocabulary_command("Add Kubernetes to my vocabulary."),
+            Some(VocabularyCommand::Add("Kubernetes".to_string()))
+        );
+        assert_eq!(
+            parse_vocabulary_command("please remove the word \"Smyth\" from the dictionary"),
+            Some(VocabularyCommand::Remove("Smyth".to_string()))
+        );
+        assert_eq!(parse_vocabulary_command("Add two cups of flour to the bowl."), None);
+    }
+
+    #[test]
+    fn adding_and_removing_ignores_case() {
+        let mut vocabulary = vec!["Kubernetes".to_string()];
+        assert!(!apply_vocabulary_command(&mut vocabulary, &VocabularyCommand::Add("kubernetes".to_string())));
+        assert!(apply_vocabulary_command(&mut vocabulary, &VocabularyCommand::Add("Grafana".to_string())));
+        assert!(apply_vocabulary_command(&mut vocabulary, &VocabularyCommand::Remove("KUBERNETES".to_string())));
+        assert_eq!(vocabulary, vec!["Grafana".to_string()]);
+    }
+}
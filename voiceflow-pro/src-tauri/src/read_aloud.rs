@@ -0,0 +1,177 @@
+//! Read-aloud for VoiceFlow Pro
+//! Speaks the text selected in any application on a global hotkey, optionally simplified or translated first
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, GlobalShortcutManager, Manager};
+
+use crate::errors::{AppError, ValidationError};
+use crate::integrations::content_filter::{apply_content_filter, OutputTarget};
+use crate::integrations::speech_stream::{self, SpeechStreamStatus};
+use crate::integrations::voice_selection::voice_for_language;
+use crate::integrations::{TranslationContext, TranslationOptions};
+use crate::system_activity::selected_text;
+use crate::{focus, platform, AppState};
+
+const SIMPLIFY_PROMPT: &str = "Rewrite the user's text in plain, simple language that is easy to follow when heard aloud. Keep every fact and instruction, use short sentences, spell out symbols and abbreviations, and return only the rewritten text.";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReadAloudSettings {
+    pub enabled: bool,
+    /// Three modifiers by default, as browsers and editors already use CmdOrCtrl+Shift+R for a hard reload
+    pub hotkey: String,
+    /// Rewrite the selection in plain language before speaking it
+    pub simplify: bool,
+    /// Translate the selection into this language before speaking it
+    pub translate_to: Option<String>,
+    /// TTS voice; the provider default when unset
+    pub voice: Option<String>,
+    /// Longer selections are cut here rather than read for minutes
+    pub max_chars: usize,
+}
+
+impl Default for ReadAloudSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            hotkey: "CmdOrCtrl+Alt+Shift+R".to_string(),
+            simplify: false,
+            translate_to: None,
+            voice: None,
+            max_chars: 20000,
+        }
+    }
+}
+
+/// What was read and the stream now playing it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadAloudOutcome {
+    pub original_text: String,
+    pub spoken_text: String,
+    pub simplified: bool,
+    pub translated_to: Option<String>,
+    pub truncated: bool,
+    pub stream: SpeechStreamStatus,
+}
+
+/// Grab the current selection, apply the configured simplification and translation, and start speaking it.
+/// `text` skips the selection grab, e.g. when the frontend already has the text.
+pub async fn read_selection(state: &AppState, app: AppHandle, text: Option<String>) -> Result<ReadAloudOutcome, AppError> {
+    let settings = state.settings.snapshot();
+    let read_aloud = settings.read_aloud.clone();
+
+    let original = match text {
+        Some(text) => text,
+        None => selected_text()
+            .await
+            .ok_or_else(|| AppError::Configuration("No text is selected".to_string()))?,
+    };
+    let original = original.trim().to_string();
+    if original.is_empty() {
+        return Err(AppError::Configuration("There is nothing to read aloud".to_string()));
+    }
+    let truncated = original.chars().count() > read_aloud.max_chars;
+    let mut text: String = original.chars().take(read_aloud.max_chars).collect();
+
+    if read_aloud.simplify || read_aloud.translate_to.is_some() {
+        crate::startup::ensure_started(state, crate::startup::Service::AiGateway).await?;
+        let gateway_state = state.ai_ml_gateway.lock().await;
+        let gateway = gateway_state
+            .as_ref()
+            .ok_or_else(|| AppError::NotInitialized("AI ML API".to_string()))?;
+        if read_aloud.simplify {
+            let (simplified, _usage) = gateway
+                .complete_with_failover(
+                    settings.ai_ml_settings.text_model.clone(),
+                    SIMPLIFY_PROMPT.to_string(),
+                    text,
+                    Some(0.3),
+                )
+                .await
+                .map_err(AppError::from)?;
+            text = simplified;
+        }
+        if let Some(target) = &read_aloud.translate_to {
+            text = gateway
+                .translate_with_enhancement(
+                    text,
+                    None,
+                    target.clone(),
+                    TranslationContext::default(),
+                    TranslationOptions::default(),
+                )
+                .await
+                .map_err(AppError::from)?
+                .translated_text;
+        }
+    }
+
+    let filter_config = settings.content_filters.config_for(None);
+    let text = apply_content_filter(&text, &filter_config, OutputTarget::Speech).text;
+    let language = read_aloud.translate_to.clone().unwrap_or(settings.language.clone());
+    let (model, voice) = voice_for_language(
+        &state.ai_ml_gateway,
+        &settings.voices,
+        &settings.ai_ml_settings.voice_model,
+        &language,
+        read_aloud.voice.clone(),
+    )
+    .await;
+    let stream = speech_stream::start_stream(app.clone(), state.ai_ml_gateway.clone(), &text, model, voice, language)
+    .await?;
+
+    let outcome = ReadAloudOutcome {
+        original_text: original,
+        spoken_text: text,
+        simplified: read_aloud.simplify,
+        translated_to: read_aloud.translate_to,
+        truncated,
+        stream,
+    };
+    let _ = app.emit_all("read-selection", &outcome);
+    Ok(outcome)
+}
+
+/// Bind the read-aloud hotkey, releasing `previous` first when the binding changed
+pub fn register_hotkey(app: &AppHandle, previous: Option<&str>, settings: &ReadAloudSettings) -> Result<(), AppError> {
+    let mut shortcuts = app.global_shortcut_manager();
+    if let Some(previous) = previous {
+        if shortcuts.is_registered(previous).unwrap_or(false) {
+            let _ = shortcuts.unregister(previous);
+        }
+    }
+    if !settings.enabled {
+        return Ok(());
+    }
+    if shortcuts.is_registered(&settings.hotkey).unwrap_or(false) {
+        return Ok(());
+    }
+
+    let handle = app.clone();
+    let registered = shortcuts
+        .register(&settings.hotkey, move || {
+            if focus::current_focus_state().block_hotkey {
+                return;
+            }
+            let app = handle.clone();
+            tauri::async_runtime::spawn(async move {
+                let state = app.state::<AppState>().inner().clone();
+                if let Err(e) = read_selection(&state, app.clone(), None).await {
+                    log::warn!("Read selection failed: {}", e);
+                    let _ = app.emit_all("read-selection-error", e.to_string());
+                }
+            });
+        });
+    match registered {
+        Ok(()) => Ok(()),
+        // Wayland compositors may refuse the grab outright; the feature stays usable from the UI
+        Err(e) if platform::hotkeys_may_be_unavailable() => {
+            log::warn!("Read-aloud hotkey {} not registered in this Wayland session: {}", settings.hotkey, e);
+            Ok(())
+        }
+        Err(e) => Err(AppError::Validation(ValidationError::InvalidConfigValue(format!(
+            "Cannot register read-aloud hotkey {}: {}",
+            settings.hotkey, e
+        )))),
+    }
+}
@@ -0,0 +1,257 @@
+//! Result refinement for VoiceFlow Pro
+//! Revises the last dictation on instructions like "make it shorter" while remembering every earlier revision
+
+use std::sync::OnceLock;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use crate::errors::AppError;
+use crate::feedback::{get_feedback_loop, refinement_routes, FeedbackTask};
+use crate::integrations::ai_text_processor::{
+    ChangeType, ProcessingContext, ProcessingMetadata, ProcessingResult, TextChange, ToneType,
+};
+use crate::undo_history::{self, TextAction};
+use crate::AppState;
+
+const REFINE_PROMPT: &str = "You revise dictated text on request. The original dictation and the instructions already applied are listed below; the user message is the current version. Apply the new instruction to the current version while keeping the earlier instructions in effect, and return only the revised text.";
+/// Revisions remembered per dictation; the oldest are forgotten first
+const MAX_REVISIONS: usize = 20;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Revision {
+    pub instruction: String,
+    pub before: String,
+    pub after: String,
+    pub created_at: u64,
+}
+
+/// The dictation being refined: what was said and how it has been revised since
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RefinementHistory {
+    pub original_text: String,
+    /// Text as first processed, before any refinement
+    pub processed_text: String,
+    pub revisions: Vec<Revision>,
+}
+
+/// Sent as "result-refined"; the frontend replaces the previously typed text with `result.processed_text`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RefinementOutcome {
+    pub instruction: String,
+    pub previous_text: String,
+    pub result: ProcessingResult,
+    pub history: RefinementHistory,
+}
+
+static HISTORY: OnceLock<Mutex<Option<RefinementHistory>>> = OnceLock::new();
+
+fn history() -> &'static Mutex<Option<RefinementHistory>> {
+    HISTORY.get_or_init(|| Mutex::new(None))
+}
+
+/// Start a new refinement history for a freshly dictated result
+pub async fn record(original_text: &str, processed_text: &str) {
+    *history().lock().await = Some(RefinementHistory {
+        original_text: original_text.to_string(),
+        processed_text: processed_text.to_string(),
+        revisions: Vec::new(),
+    });
+}
+
+pub async fn current_history() -> Option<RefinementHistory> {
+    history().lock().await.clone()
+}
+
+/// Style words a dictation can be made more or less of
+const STYLES: &str = "formal|casual|concise|detailed|friendly|polite|professional|technical|direct|enthusiastic";
+/// Comparatives that revise a dictation; "make it later" or "make it bigger" are ordinary dictation
+const COMPARATIVES: &str = "shorter|longer|simpler|clearer|friendlier|politer|briefer|tighter|warmer|punchier";
+
+/// Recognize spoken refinements: "make it shorter", "more formal", "rephrase that as a question"
+/// Only listed comparatives and style words count, so dictating "make it later", "more coffee please" or
+/// "rewrite this section by Friday" is still typed
+pub fn parse_refinement_command(utterance: &str) -> Option<String> {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    let pattern = PATTERN.get_or_init(|| {
+        let degree = r"(?:a\s+bit\s+|a\s+little\s+|much\s+|slightly\s+)?";
+        Regex::new(&format!(
+            r"(?i)^\s*(?:please\s+)?(?:(make\s+(?:it|that|this)\s+(?:sound\s+)?{degree}(?:{comparatives}|(?:more|less)\s+(?:{styles})|{styles}))|({degree}(?:more|less)\s+(?:{styles}))|((?:rephrase|rewrite|reword)\s+(?:it|that)(?:\s+(?:as\s+(?:a\s+)?(?:question|list|bullet\s+points|statement|summary)|(?:more|less)\s+(?:{styles})|in\s+(?:a\s+)?(?:{styles})\s+(?:tone|way)))?))\s*[.!]?\s*$",
+            degree = degree,
+            comparatives = COMPARATIVES,
+            styles = STYLES,
+        ))
+        .expect("refinement pattern is valid")
+    });
+    let captures = pattern.captures(utterance)?;
+    let instruction = captures.iter().skip(1).flatten().next()?.as_str().trim();
+    Some(instruction.to_string())
+}
+
+/// Apply `instruction` to the last dictation, keeping the earlier revisions in effect
+pub async fn refine_last_result(state: &AppState, instruction: &str) -> Result<RefinementOutcome, AppError> {
+    let started = std::time::Instant::now();
+    let current = state
+        .last_output
+        .lock()
+        .await
+        .clone()
+        .ok_or_else(|| AppError::Configuration("There is no dictated text to refine yet".to_string()))?;
+    // Corrections and snippets change the last output without a history; the text then starts a new one
+    let mut refinement = match current_history().await {
+        Some(existing) if latest_text(&existing) == current => existing,
+        _ => RefinementHistory {
+            original_text: current.clone(),
+            processed_text: current.clone(),
+            revisions: Vec::new(),
+        },
+    };
+
+    let mut system_prompt = format!("{}\n\nOriginal dictation:\n{}", REFINE_PROMPT, refinement.original_text);
+    if !refinement.revisions.is_empty() {
+        system_prompt.push_str("\n\nInstructions already applied, in order:");
+        for revision in &refinement.revisions {
+            system_prompt.push_str(&format!("\n- {}", revision.instruction));
+        }
+    }
+    system_prompt.push_str(&format!("\n\nNew instruction: {}", instruction));
+
+    let (text_model, feedback_settings) = {
+        let settings = state.settings.snapshot();
+        (settings.ai_ml_settings.text_model.clone(), settings.feedback.clone())
+    };
+    let route = get_feedback_loop().lock().await.choose(
+        FeedbackTask::Refinement,
+        &refinement_routes(&feedback_settings, &text_model),
+        &feedback_settings,
+    );
+    let model = route.as_ref().map_or(text_model, |route| route.model.clone());
+    let refined = {
+        crate::startup::ensure_started(state, crate::startup::Service::AiGateway).await?;
+        let gateway_state = state.ai_ml_gateway.lock().await;
+        let gateway = gateway_state
+            .as_ref()
+            .ok_or_else(|| AppError::NotInitialized("AI ML API".to_string()))?;
+        let (refined, _usage) = gateway
+            .complete_with_failover(model, system_prompt, current.clone(), Some(0.4))
+            .await
+            .map_err(AppError::from)?;
+        refined.trim().to_string()
+    };
+
+    refinement.revisions.push(Revision {
+        instruction: instruction.to_string(),
+        before: current.clone(),
+        after: refined.clone(),
+        created_at: now_secs(),
+    });
+    if refinement.revisions.len() > MAX_REVISIONS {
+        refinement.revisions.remove(0);
+    }
+    *history().lock().await = Some(refinement.clone());
+    *state.last_output.lock().await = Some(refined.clone());
+    undo_history::record(TextAction::Enhancement {
+        before: current.clone(),
+        after: refined.clone(),
+        reinjected: true,
+    })
+    .await;
+
+    let result = ProcessingResult {
+        id: Uuid::new_v4().to_string(),
+        original_text: refinement.original_text.clone(),
+        processed_text: refined.clone(),
+        // Every revision so far, so the result shows how the text got from the dictation to here
+        changes_made: refinement
+            .revisions
+            .iter()
+            .map(|revision| TextChange {
+                change_type: ChangeType::Style,
+                original: revision.before.clone(),
+                replacement: revision.after.clone(),
+                position: 0,
+                confidence: 0.8,
+            })
+            .collect(),
+        confidence_score: 0.8,
+        processing_time_ms: started.elapsed().as_millis() as u64,
+        context_used: ProcessingContext::Document,
+        tone_applied: ToneType::Neutral,
+        metadata: ProcessingMetadata {
+            readability_before: 0.0,
+            readability_after: 0.0,
+            word_count_before: refinement.original_text.split_whitespace().count(),
+            word_count_after: refined.split_whitespace().count(),
+            sentences_processed: 0,
+            errors_corrected: 0,
+            filler_words_removed: 0,
+            verbatim: false,
+            length: None,
+        },
+        latency: None,
+        pipeline: None,
+    };
+    if let (true, Some(route)) = (feedback_settings.enabled, route) {
+        get_feedback_loop()
+            .lock()
+            .await
+            .note_result(&result.id, FeedbackTask::Refinement, &current, &refined, route);
+    }
+    Ok(RefinementOutcome {
+        instruction: instruction.to_string(),
+        previous_text: current,
+        result,
+        history: refinement,
+    })
+}
+
+fn latest_text(history: &RefinementHistory) -> &str {
+    history
+        .revisions
+        .last()
+        .map_or(history.processed_text.as_str(), |revision| revision.after.as_str())
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn listed_refinements_are_commands() {
+        for utterance in [
+            "Make it shorter.",
+            "make that a bit more formal",
+            "Please make it sound friendlier!",
+            "less technical",
+            "Rephrase that as a question.",
+            "rewrite it in a casual tone",
+        ] {
+            assert!(parse_refinement_command(utterance).is_some(), "{}", utterance);
+        }
+        assert_eq!(parse_refinement_command("Make it shorter."), Some("Make it shorter".to_string()));
+    }
+
+    #[test]
+    fn ordinary_dictation_is_not_a_command() {
+        for utterance in [
+            "Make it later.",
+            "Make it happen",
+            "More coffee please.",
+            "Shorter.",
+            "Rewrite this section before Friday.",
+            "Rewrite this.",
+            "make it sound like we agreed",
+        ] {
+            assert_eq!(parse_refinement_command(utterance), None, "{}", utterance);
+        }
+    }
+}
@@ -0,0 +1,577 @@
+//! Session recording for VoiceFlow Pro
+//! Captures a dictation session's utterances, audio and AI exchanges to a file and replays them through the pipeline
+
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::Window;
+use uuid::Uuid;
+
+use crate::audit::AuditService;
+use crate::encryption::{get_data_vault, is_encrypted};
+use crate::errors::{AppError, ValidationError};
+use crate::history::SegmentAudio;
+use crate::integrations::ai_text_processor::ProcessingResult;
+use crate::integrations::{AIMLError, AIMLUsage, TranscriptionResponse};
+use crate::pipeline::{self, PipelineStage};
+use crate::storage::{data_path, ensure_data_dir, DataDir};
+use crate::{AppState, Settings};
+
+const FORMAT_VERSION: u32 = 1;
+/// Sessions kept on disk; the oldest are deleted when a new recording starts
+const MAX_SESSIONS: usize = 20;
+
+/// First line of a session file. Every line is sealed with the data key and base64-encoded, so recordings
+/// can only be made while data encryption is on and unlocked
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionHeader {
+    pub version: u32,
+    pub id: String,
+    pub started_at: u64,
+    pub app_version: String,
+    /// Settings when recording started, without the API key; replay runs under these
+    pub settings: Settings,
+}
+
+/// What an AI request returned, in a form the client can hand back unchanged
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AiPayload {
+    Chat { content: String, usage: Option<AIMLUsage> },
+    /// The assistant message as returned, tool calls included
+    ToolChat { message: Value, usage: Option<AIMLUsage> },
+    Transcription { response: TranscriptionResponse },
+    Speech { audio_base64: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SessionEvent {
+    /// A recognized utterance as the frontend sent it, with its audio when there was any
+    Utterance {
+        transcript: String,
+        audio: Option<SegmentAudio>,
+        preview: Option<bool>,
+    },
+    AiExchange {
+        service: AuditService,
+        model: String,
+        /// Full prompt text; session files hold verbatim speech and stay on this machine unless shared
+        prompt: String,
+        response: Option<AiPayload>,
+        error: Option<String>,
+    },
+    /// How the preceding utterance ended
+    Outcome {
+        result: Option<ProcessingResult>,
+        error: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedEvent {
+    /// Milliseconds since recording started
+    pub at_ms: u64,
+    #[serde(flatten)]
+    pub event: SessionEvent,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionInfo {
+    pub id: String,
+    pub started_at: u64,
+    pub path: String,
+    pub utterances: usize,
+    pub ai_exchanges: usize,
+    pub bytes: u64,
+    /// Still being recorded
+    pub active: bool,
+}
+
+/// One utterance fed through the pipeline again
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayedUtterance {
+    pub index: usize,
+    pub transcript: String,
+    pub recorded_text: Option<String>,
+    pub replayed_text: Option<String>,
+    pub matches: bool,
+    pub error: Option<String>,
+    /// Why the utterance was not replayed, e.g. it was a voice command rather than dictation
+    pub skipped: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayReport {
+    pub session_id: String,
+    pub utterances: Vec<ReplayedUtterance>,
+    pub matched: usize,
+    pub mismatched: usize,
+    /// AI requests answered from the recording
+    pub ai_replayed: usize,
+    /// AI requests the recording had no answer for
+    pub ai_missing: usize,
+}
+
+struct Recorder {
+    id: String,
+    file: File,
+    started: Instant,
+}
+
+struct RecordedExchange {
+    service: AuditService,
+    model: String,
+    prompt: String,
+    response: Option<AiPayload>,
+    error: Option<String>,
+    used: bool,
+}
+
+#[derive(Default)]
+struct Replay {
+    exchanges: Vec<RecordedExchange>,
+    replayed: usize,
+    missing: usize,
+}
+
+/// Checked on every AI request, so the common case of not recording costs no lock
+static RECORDING: AtomicBool = AtomicBool::new(false);
+/// Set while any replay runs, so recording and a second replay are refused meanwhile
+static REPLAYING: AtomicBool = AtomicBool::new(false);
+static RECORDER: OnceLock<Mutex<Option<Recorder>>> = OnceLock::new();
+
+tokio::task_local! {
+    /// The replay a request belongs to; only the replay's own task sees it, so live requests made meanwhile
+    /// still reach the network
+    static ACTIVE_REPLAY: Mutex<Replay>;
+}
+
+fn recorder() -> std::sync::MutexGuard<'static, Option<Recorder>> {
+    let lock = RECORDER.get_or_init(|| Mutex::new(None));
+    match lock.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+pub fn is_recording() -> bool {
+    RECORDING.load(Ordering::Relaxed)
+}
+
+/// Start writing a new session file
+pub fn start(settings: &Settings) -> Result<SessionInfo, AppError> {
+    if REPLAYING.load(Ordering::Relaxed) {
+        return Err(AppError::Configuration("Cannot record while a session is being replayed".to_string()));
+    }
+    if is_recording() {
+        return Err(AppError::Configuration("A session is already being recorded".to_string()));
+    }
+    if !get_data_vault().status().unlocked {
+        return Err(AppError::Security(
+            "Session recordings hold verbatim speech; turn on and unlock data encryption to record".to_string(),
+        ));
+    }
+
+    let dir = ensure_data_dir(DataDir::Sessions)?;
+    prune(MAX_SESSIONS.saturating_sub(1))?;
+    let mut settings = settings.clone();
+    settings.ai_ml_settings.api_key.clear();
+    settings.network.proxy_password = None;
+    settings.speech_to_text = settings.speech_to_text.without_keys();
+    let header = SessionHeader {
+        version: FORMAT_VERSION,
+        id: Uuid::new_v4().to_string(),
+        started_at: now_secs(),
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        settings,
+    };
+    let path = dir.join(format!("{}.jsonl", header.id));
+    let mut file = OpenOptions::new()
+        .create_new(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| AppError::Internal(format!("Failed to create {}: {}", path.display(), e)))?;
+    write_line(&mut file, &header)?;
+
+    let mut current = recorder();
+    if current.is_some() {
+        drop(current);
+        let _ = fs::remove_file(&path);
+        return Err(AppError::Configuration("A session is already being recorded".to_string()));
+    }
+    *current = Some(Recorder {
+        id: header.id.clone(),
+        file,
+        started: Instant::now(),
+    });
+    RECORDING.store(true, Ordering::Relaxed);
+    drop(current);
+    info(&header.id)
+}
+
+/// Finish the current recording, if any
+pub fn stop() -> Result<Option<SessionInfo>, AppError> {
+    let finished = {
+        let mut current = recorder();
+        RECORDING.store(false, Ordering::Relaxed);
+        current.take()
+    };
+    match finished {
+        Some(recorder) => info(&recorder.id).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Append an event to the current recording; failures are logged, never returned
+pub fn record(event: SessionEvent) {
+    if !is_recording() {
+        return;
+    }
+    let mut current = recorder();
+    let Some(recorder) = current.as_mut() else {
+        return;
+    };
+    let recorded = RecordedEvent {
+        at_ms: recorder.started.elapsed().as_millis() as u64,
+        event,
+    };
+    if let Err(e) = write_line(&mut recorder.file, &recorded) {
+        log::warn!("Failed to write session recording: {}", e);
+    }
+}
+
+pub fn record_ai(service: AuditService, model: &str, prompt: &str, outcome: Result<AiPayload, &AIMLError>) {
+    let (response, error) = match outcome {
+        Ok(payload) => (Some(payload), None),
+        Err(e) => (None, Some(e.to_string())),
+    };
+    record(SessionEvent::AiExchange {
+        service,
+        model: model.to_string(),
+        prompt: prompt.to_string(),
+        response,
+        error,
+    });
+}
+
+/// For a request made by a replay, the recorded answer to the first unused matching request; every such request
+/// is answered from the recording so a replay never reaches the network
+pub fn replay_ai(service: AuditService, model: &str, prompt: &str) -> Option<Result<AiPayload, AIMLError>> {
+    ACTIVE_REPLAY
+        .try_with(|replay| {
+            let mut replay = match replay.lock() {
+                Ok(guard) => guard,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            answer_from(&mut replay, service, model, prompt)
+        })
+        .ok()
+}
+
+fn answer_from(replay: &mut Replay, service: AuditService, model: &str, prompt: &str) -> Result<AiPayload, AIMLError> {
+    let found = replay
+        .exchanges
+        .iter_mut()
+        .find(|exchange| !exchange.used && exchange.service == service && exchange.model == model && exchange.prompt == prompt);
+    let Some(exchange) = found else {
+        replay.missing += 1;
+        return Err(AIMLError::ServiceUnavailable(
+            "The replayed session has no recorded response for this request".to_string(),
+        ));
+    };
+    exchange.used = true;
+    let answer = match (&exchange.response, &exchange.error) {
+        (Some(response), _) => Ok(response.clone()),
+        (None, error) => Err(AIMLError::ServiceUnavailable(format!(
+            "Recorded failure: {}",
+            error.as_deref().unwrap_or("unknown error")
+        ))),
+    };
+    replay.replayed += 1;
+    answer
+}
+
+/// Recorded sessions, newest first
+pub fn list() -> Result<Vec<SessionInfo>, AppError> {
+    let dir = data_path(DataDir::Sessions)?;
+    let Ok(entries) = fs::read_dir(&dir) else {
+        return Ok(Vec::new());
+    };
+    let mut sessions: Vec<SessionInfo> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            name.strip_suffix(".jsonl").map(str::to_string)
+        })
+        .filter_map(|id| info(&id).ok())
+        .collect();
+    sessions.sort_by(|a, b| b.started_at.cmp(&a.started_at));
+    Ok(sessions)
+}
+
+pub fn delete(id: &str) -> Result<bool, AppError> {
+    if recorder().as_ref().map_or(false, |recorder| recorder.id == id) {
+        return Err(AppError::Configuration("Stop the recording before deleting it".to_string()));
+    }
+    let path = session_path(id)?;
+    if !path.exists() {
+        return Ok(false);
+    }
+    fs::remove_file(&path).map_err(|e| AppError::Internal(format!("Failed to delete {}: {}", path.display(), e)))?;
+    Ok(true)
+}
+
+/// Feed every recorded utterance through the pipeline again under the recorded settings, answering AI requests
+/// from the recording, and compare the results; nothing is typed
+pub async fn replay(state: &AppState, window: &Window, id: &str) -> Result<ReplayReport, AppError> {
+    if is_recording() {
+        return Err(AppError::Configuration("Stop recording before replaying a session".to_string()));
+    }
+    if REPLAYING.swap(true, Ordering::SeqCst) {
+        return Err(AppError::Configuration("Another session is being replayed".to_string()));
+    }
+    // Cleared however the replay ends
+    struct ReplayGuard;
+    impl Drop for ReplayGuard {
+        fn drop(&mut self) {
+            REPLAYING.store(false, Ordering::SeqCst);
+        }
+    }
+    let _guard = ReplayGuard;
+
+    let (header, events) = load(id)?;
+    let recorded = Replay {
+        exchanges: events
+            .iter()
+            .filter_map(|recorded| match &recorded.event {
+                SessionEvent::AiExchange {
+                    service,
+                    model,
+                    prompt,
+                    response,
+                    error,
+                } => Some(RecordedExchange {
+                    service: *service,
+                    model: model.clone(),
+                    prompt: prompt.clone(),
+                    response: response.clone(),
+                    error: error.clone(),
+                    used: false,
+                }),
+                _ => None,
+            })
+            .collect(),
+        ..Replay::default()
+    };
+    if state.text_processor.lock().await.is_none() {
+        crate::start_text_processor(state).await?;
+    }
+
+    let (utterances, ai_replayed, ai_missing) = ACTIVE_REPLAY
+        .scope(Mutex::new(recorded), async {
+            let utterances = replay_utterances(state, window, &header.settings, &events).await;
+            let (ai_replayed, ai_missing) = ACTIVE_REPLAY
+                .try_with(|replay| {
+                    let replay = match replay.lock() {
+                        Ok(guard) => guard,
+                        Err(poisoned) => poisoned.into_inner(),
+                    };
+                    (replay.replayed, replay.missing)
+                })
+                .unwrap_or_default();
+            (utterances, ai_replayed, ai_missing)
+        })
+        .await;
+    let compared = utterances.iter().filter(|utterance| utterance.skipped.is_none());
+    Ok(ReplayReport {
+        session_id: header.id,
+        matched: compared.clone().filter(|utterance| utterance.matches).count(),
+        mismatched: compared.filter(|utterance| !utterance.matches).count(),
+        utterances,
+        ai_replayed,
+        ai_missing,
+    })
+}
+
+async fn replay_utterances(
+    state: &AppState,
+    window: &Window,
+    settings: &Settings,
+    events: &[RecordedEvent],
+) -> Vec<ReplayedUtterance> {
+    let mut utterances = Vec::new();
+    for (index, transcript, recorded) in utterances_with_outcomes(events) {
+        let recorded_text = recorded.and_then(|result| result.map(|result| result.processed_text.clone()));
+        let mut replayed = ReplayedUtterance {
+            index,
+            transcript: transcript.to_string(),
+            recorded_text,
+            replayed_text: None,
+            matches: false,
+            error: None,
+            skipped: None,
+        };
+
+        match recorded {
+            Some(Some(result)) if result.pipeline.is_none() => {
+                replayed.skipped = Some("Handled as a voice command, not by the pipeline".to_string());
+            }
+            _ => {
+                // The tone the recording's Enhance stage picked, so rating-based routing cannot change it
+                let mut stages = settings.pipeline.clone();
+                if let Some(Some(result)) = recorded {
+                    for stage in &mut stages.stages {
+                        if let PipelineStage::Enhance { tone, .. } = stage {
+                            tone.get_or_insert_with(|| result.tone_applied.clone());
+                        }
+                    }
+                }
+                match pipeline::run(
+                    state,
+                    window,
+                    &stages,
+                    transcript,
+                    &settings.language,
+                    &settings.code_dictation,
+                    &settings.feedback,
+                    &settings.verbatim,
+                )
+                .await
+                {
+                    Ok(run) => {
+                        replayed.matches = replayed.recorded_text.as_deref() == Some(run.result.processed_text.as_str());
+                        replayed.replayed_text = Some(run.result.processed_text);
+                    }
+                    Err(e) => replayed.error = Some(e.to_string()),
+                }
+            }
+        }
+        let _ = window.emit("session-replay-progress", &replayed);
+        utterances.push(replayed);
+    }
+    utterances
+}
+
+/// Each utterance with its recorded outcome: `None` if the session ended first, `Some(None)` if it failed
+fn utterances_with_outcomes(events: &[RecordedEvent]) -> Vec<(usize, &str, Option<Option<&ProcessingResult>>)> {
+    let mut utterances = Vec::new();
+    for (position, recorded) in events.iter().enumerate() {
+        let SessionEvent::Utterance { transcript, .. } = &recorded.event else {
+            continue;
+        };
+        let outcome = events[position + 1..]
+            .iter()
+            .take_while(|later| !matches!(later.event, SessionEvent::Utterance { .. }))
+            .find_map(|later| match &later.event {
+                SessionEvent::Outcome { result, .. } => Some(result.as_ref()),
+                _ => None,
+            });
+        utterances.push((utterances.len(), transcript.as_str(), outcome));
+    }
+    utterances
+}
+
+fn load(id: &str) -> Result<(SessionHeader, Vec<RecordedEvent>), AppError> {
+    let path = session_path(id)?;
+    let contents = fs::read_to_string(&path)
+        .map_err(|e| AppError::Configuration(format!("Session {} could not be read: {}", id, e)))?;
+    let mut lines = contents.lines();
+    let header: SessionHeader = lines
+        .next()
+        .and_then(read_line)
+        .ok_or_else(|| AppError::Configuration(format!("Session {} has no valid header or is locked", id)))?;
+    if header.version > FORMAT_VERSION {
+        return Err(AppError::Configuration(format!(
+            "Session {} was recorded by a newer version of VoiceFlow Pro",
+            id
+        )));
+    }
+    // A line cut short when the app was closed mid-write is skipped
+    let events = lines.filter_map(read_line).collect();
+    Ok((header, events))
+}
+
+fn info(id: &str) -> Result<SessionInfo, AppError> {
+    let path = session_path(id)?;
+    let contents = fs::read_to_string(&path)
+        .map_err(|e| AppError::Internal(format!("Failed to read {}: {}", path.display(), e)))?;
+    let mut lines = contents.lines();
+    let header: SessionHeader = lines
+        .next()
+        .and_then(read_line)
+        .ok_or_else(|| AppError::Internal(format!("{} has no valid header or is locked", path.display())))?;
+    let (mut utterances, mut ai_exchanges) = (0, 0);
+    for line in lines {
+        // Only the tag is needed, so events are not fully parsed
+        match read_line::<Value>(line).as_ref().and_then(|event| event["type"].as_str()) {
+            Some("utterance") => utterances += 1,
+            Some("ai_exchange") => ai_exchanges += 1,
+            _ => {}
+        }
+    }
+    Ok(SessionInfo {
+        active: recorder().as_ref().map_or(false, |recorder| recorder.id == header.id),
+        id: header.id,
+        started_at: header.started_at,
+        path: path.display().to_string(),
+        utterances,
+        ai_exchanges,
+        bytes: contents.len() as u64,
+    })
+}
+
+/// Delete the oldest sessions so at most `keep` remain
+fn prune(keep: usize) -> Result<(), AppError> {
+    for session in list()?.into_iter().skip(keep).filter(|session| !session.active) {
+        let _ = fs::remove_file(&session.path);
+    }
+    Ok(())
+}
+
+/// Session ids are generated UUIDs, so anything else is rejected rather than joined into a path
+fn session_path(id: &str) -> Result<PathBuf, AppError> {
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_hexdigit() || c == '-') {
+        return Err(AppError::Validation(ValidationError::InvalidConfigValue(format!(
+            "Invalid session id {:?}",
+            id
+        ))));
+    }
+    Ok(data_path(DataDir::Sessions)?.join(format!("{}.jsonl", id)))
+}
+
+fn write_line<T: Serialize>(file: &mut File, value: &T) -> Result<(), AppError> {
+    let sealed = get_data_vault().seal(&serde_json::to_vec(value)?)?;
+    if !is_encrypted(&sealed) {
+        return Err(AppError::Security("Data encryption is off; session events are not written".to_string()));
+    }
+    let mut line = BASE64.encode(sealed).into_bytes();
+    line.push(b'\n');
+    file.write_all(&line)
+        .map_err(|e| AppError::Internal(format!("Failed to write session recording: {}", e)))
+}
+
+/// Decrypt and parse one line; plaintext JSON lines from recordings made before encryption are read as they are
+fn read_line<T: DeserializeOwned>(line: &str) -> Option<T> {
+    if line.starts_with('{') {
+        return serde_json::from_str(line).ok();
+    }
+    let sealed = BASE64.decode(line).ok()?;
+    let json = get_data_vault().open(sealed).ok()?;
+    serde_json::from_slice(&json).ok()
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
@@ -0,0 +1,294 @@
+//! Running dictation statistics for VoiceFlow Pro
+//! Keeps word and character counts, reading time and speaking pace of the current session, updated utterance by utterance
+
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use crate::errors::{AppError, ValidationError};
+use crate::history::SegmentAudio;
+use crate::integrations::audio_upload::wav_duration;
+use crate::integrations::stt_providers::SttWord;
+use crate::latency::ClientTimings;
+
+/// Longest stretch one utterance is taken to have been spoken for, so a bad timestamp cannot swamp the pace
+const MAX_UTTERANCE_SPEECH: Duration = Duration::from_secs(120);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionStatsSettings {
+    pub enabled: bool,
+    /// Shortest time between two "dictation-stats" events for interim text; finished utterances are always reported
+    pub interval_ms: u64,
+    /// Silent reading speed used for the reading time estimate
+    pub reading_wpm: u32,
+}
+
+impl Default for SessionStatsSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_ms: 500,
+            reading_wpm: 238,
+        }
+    }
+}
+
+pub fn validate(settings: &SessionStatsSettings) -> Result<(), AppError> {
+    let invalid = |message: String| AppError::Validation(ValidationError::InvalidConfigValue(message));
+    if !(100..=10_000).contains(&settings.interval_ms) {
+        return Err(invalid(format!(
+            "Dictation stats interval must be 100-10000 ms, got {}",
+            settings.interval_ms
+        )));
+    }
+    if !(50..=1000).contains(&settings.reading_wpm) {
+        return Err(invalid(format!(
+            "Reading speed must be 50-1000 words per minute, got {}",
+            settings.reading_wpm
+        )));
+    }
+    Ok(())
+}
+
+/// Sent as "dictation-stats" while a session runs
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SessionStats {
+    pub session_id: String,
+    /// Words of finished utterances plus the one still being spoken
+    pub words: usize,
+    pub characters: usize,
+    /// Part of `words` from the utterance still being spoken, which may yet change
+    pub pending_words: usize,
+    pub utterances: usize,
+    pub reading_time_secs: u64,
+    /// Recognized speech in finished utterances, from word timings or the utterance's audio
+    pub speaking_secs: u64,
+    /// Words per minute of speaking time, over the utterances whose length is known; none until a few seconds
+    /// have been spoken
+    pub speaking_wpm: Option<u32>,
+}
+
+#[derive(Debug)]
+struct SessionCounter {
+    session_id: String,
+    settings: SessionStatsSettings,
+    words: usize,
+    characters: usize,
+    pending_words: usize,
+    pending_characters: usize,
+    utterances: usize,
+    speaking: Duration,
+    /// Words of the utterances counted in `speaking`
+    timed_words: usize,
+    /// Speech heard for the utterance still being spoken
+    pending_speech: Option<Duration>,
+    last_emitted: Option<Instant>,
+}
+
+impl SessionCounter {
+    fn new(session_id: &str, settings: &SessionStatsSettings) -> Self {
+        Self {
+            session_id: session_id.to_string(),
+            settings: settings.clone(),
+            words: 0,
+            characters: 0,
+            pending_words: 0,
+            pending_characters: 0,
+            utterances: 0,
+            speaking: Duration::ZERO,
+            timed_words: 0,
+            pending_speech: None,
+            last_emitted: None,
+        }
+    }
+
+    fn add_speech(&mut self, speech: Duration) {
+        let pending = self.pending_speech.unwrap_or_default() + speech;
+        self.pending_speech = Some(pending.min(MAX_UTTERANCE_SPEECH));
+    }
+
+    fn finish(&mut self, text: &str) {
+        let words = text.split_whitespace().count();
+        self.words += words;
+        self.characters += text.trim().chars().count();
+        if let Some(speech) = self.pending_speech.take() {
+            self.speaking += speech;
+            self.timed_words += words;
+        }
+        self.pending_words = 0;
+        self.pending_characters = 0;
+        self.utterances += 1;
+    }
+
+    fn stats(&mut self) -> SessionStats {
+        self.last_emitted = Some(Instant::now());
+        let words = self.words + self.pending_words;
+        let minutes = self.speaking.as_secs_f64() / 60.0;
+        SessionStats {
+            session_id: self.session_id.clone(),
+            words,
+            characters: self.characters + self.pending_characters,
+            pending_words: self.pending_words,
+            utterances: self.utterances,
+            reading_time_secs: (words as f64 * 60.0 / self.settings.reading_wpm.max(1) as f64).round() as u64,
+            speaking_secs: self.speaking.as_secs(),
+            speaking_wpm: (self.speaking >= Duration::from_secs(5))
+                .then(|| (self.timed_words as f64 / minutes).round() as u32),
+        }
+    }
+
+    fn due(&self) -> bool {
+        self.last_emitted
+            .map_or(true, |at| at.elapsed() >= Duration::from_millis(self.settings.interval_ms))
+    }
+}
+
+fn counter() -> &'static Mutex<Option<SessionCounter>> {
+    static COUNTER: OnceLock<Mutex<Option<SessionCounter>>> = OnceLock::new();
+    COUNTER.get_or_init(|| Mutex::new(None))
+}
+
+/// Start counting for a new dictation session; the previous session's totals are dropped
+pub async fn start_session(session_id: &str, settings: &SessionStatsSettings) {
+    *counter().lock().await = settings.enabled.then(|| SessionCounter::new(session_id, settings));
+}
+
+/// Speech the recognizer heard, from the first to the last word of a final result
+pub async fn observe_words(words: &[SttWord]) {
+    let (Some(first), Some(last)) = (words.first(), words.last()) else {
+        return;
+    };
+    let secs = (last.end - first.start).max(0.0);
+    if let Some(counter) = counter().lock().await.as_mut() {
+        counter.add_speech(Duration::from_secs_f32(secs));
+    }
+}
+
+/// Length of a finished utterance from the frontend's speech marks, or from its WAV recording;
+/// ignored when word timings already told how long it was
+pub async fn observe_utterance(timings: &ClientTimings, audio: Option<&SegmentAudio>) {
+    let mut counter = counter().lock().await;
+    let Some(counter) = counter.as_mut().filter(|counter| counter.pending_speech.is_none()) else {
+        return;
+    };
+    if let Some(speech) = utterance_speech(timings, audio) {
+        counter.add_speech(speech);
+    }
+}
+
+fn utterance_speech(timings: &ClientTimings, audio: Option<&SegmentAudio>) -> Option<Duration> {
+    let started = timings.first_audio_ms.or(timings.capture_started_ms);
+    if let (Some(started), Some(ended)) = (started, timings.speech_ended_ms) {
+        return (ended > started).then(|| Duration::from_millis(ended - started));
+    }
+    let audio = audio.filter(|audio| audio.format.eq_ignore_ascii_case("wav"))?;
+    let (_, secs) = wav_duration(&BASE64.decode(audio.data_base64.as_bytes()).ok()?)?;
+    Some(Duration::from_secs_f32(secs))
+}
+
+/// Count the utterance still being spoken; stats come back when the throttle interval has passed
+pub async fn observe_interim(text: &str) -> Option<SessionStats> {
+    let mut counter = counter().lock().await;
+    let counter = counter.as_mut()?;
+    counter.pending_words = text.split_whitespace().count();
+    counter.pending_characters = text.trim().chars().count();
+    counter.due().then(|| counter.stats())
+}
+
+/// Add text typed for a finished utterance to the session totals
+pub async fn observe_final(text: &str) -> Option<SessionStats> {
+    let mut counter = counter().lock().await;
+    let counter = counter.as_mut()?;
+    counter.finish(text);
+    Some(counter.stats())
+}
+
+/// Drop the pending words of an utterance that typed nothing, e.g. a voice command
+pub async fn end_utterance() -> Option<SessionStats> {
+    let mut counter = counter().lock().await;
+    let counter = counter.as_mut()?;
+    counter.pending_speech = None;
+    if counter.pending_words == 0 {
+        return None;
+    }
+    counter.pending_words = 0;
+    counter.pending_characters = 0;
+    Some(counter.stats())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counter() -> SessionCounter {
+        SessionCounter::new("session", &SessionStatsSettings::default())
+    }
+
+    #[test]
+    fn pace_follows_recognized_speech() {
+        let mut counter = counter();
+        counter.add_speech(Duration::from_secs(6));
+        counter.finish("one two three four five six seven eight nine ten eleven twelve");
+        let stats = counter.stats();
+        assert_eq!(stats.speaking_secs, 6);
+        assert_eq!(stats.speaking_wpm, Some(120));
+    }
+
+    #[test]
+    fn untimed_utterances_do_not_inflate_the_pace() {
+        let mut counter = counter();
+        counter.finish("words typed with no idea how long they took to say");
+        assert_eq!(counter.stats().speaking_wpm, None);
+
+        counter.add_speech(Duration::from_secs(10));
+        counter.finish("ten words spoken over exactly ten seconds of speech here");
+        let stats = counter.stats();
+        assert_eq!(stats.words, 21);
+        assert_eq!(stats.speaking_wpm, Some(60));
+    }
+
+    #[test]
+    fn utterance_length_comes_from_speech_marks_or_audio() {
+        let timings = ClientTimings {
+            first_audio_ms: Some(1_000),
+            speech_ended_ms: Some(4_500),
+            ..ClientTimings::default()
+        };
+        assert_eq!(utterance_speech(&timings, None), Some(Duration::from_millis(3_500)));
+
+        // One second of 16 kHz mono silence
+        let mut wav = Vec::new();
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&(36u32 + 32_000).to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes());
+        wav.extend_from_slice(&16_000u32.to_le_bytes());
+        wav.extend_from_slice(&32_000u32.to_le_bytes());
+        wav.extend_from_slice(&2u16.to_le_bytes());
+        wav.extend_from_slice(&16u16.to_le_bytes());
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&32_000u32.to_le_bytes());
+        wav.extend(std::iter::repeat(0u8).take(32_000));
+        let audio = SegmentAudio {
+            data_base64: BASE64.encode(&wav),
+            format: "wav".to_string(),
+        };
+        assert_eq!(utterance_speech(&ClientTimings::default(), Some(&audio)), Some(Duration::from_secs(1)));
+        assert_eq!(utterance_speech(&ClientTimings::default(), None), None);
+    }
+
+    #[test]
+    fn one_utterance_counts_for_at_most_two_minutes() {
+        let mut counter = counter();
+        counter.add_speech(Duration::from_secs(100));
+        counter.add_speech(Duration::from_secs(100));
+        counter.finish("a long monologue");
+        assert_eq!(counter.stats().speaking_secs, MAX_UTTERANCE_SPEECH.as_secs());
+    }
+}
@@ -0,0 +1,96 @@
+//! Settings store for VoiceFlow Pro
+//! Serves settings as cheap shared snapshots, serializes writers and broadcasts every committed change
+
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, RwLock};
+
+use tokio::sync::{broadcast, Mutex, MutexGuard};
+
+use crate::Settings;
+
+/// Changes a slow subscriber may fall behind by before it only sees the latest
+const CHANGE_CAPACITY: usize = 16;
+
+/// Readers take an `Arc` of the current settings without waiting on writers; a write works on a
+/// draft that replaces the snapshot, and is announced to subscribers, when its guard drops
+pub struct SettingsStore {
+    current: RwLock<Arc<Settings>>,
+    writer: Mutex<()>,
+    changes: broadcast::Sender<Arc<Settings>>,
+}
+
+impl SettingsStore {
+    pub fn new(settings: Settings) -> Self {
+        let (changes, _) = broadcast::channel(CHANGE_CAPACITY);
+        Self {
+            current: RwLock::new(Arc::new(settings)),
+            writer: Mutex::new(()),
+            changes,
+        }
+    }
+
+    /// The settings as of the last committed write
+    pub fn snapshot(&self) -> Arc<Settings> {
+        match self.current.read() {
+            Ok(current) => current.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    /// Start a write; other writers wait until this guard drops, readers keep the previous snapshot until then
+    pub async fn write(&self) -> SettingsWriteGuard<'_> {
+        let writer = self.writer.lock().await;
+        SettingsWriteGuard {
+            draft: Some(Settings::clone(&self.snapshot())),
+            store: self,
+            changed: false,
+            _writer: writer,
+        }
+    }
+
+    /// Receive each committed change, e.g. to reconfigure a long-lived service without polling
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<Settings>> {
+        self.changes.subscribe()
+    }
+
+    fn commit(&self, settings: Settings) {
+        let settings = Arc::new(settings);
+        match self.current.write() {
+            Ok(mut current) => *current = settings.clone(),
+            Err(poisoned) => *poisoned.into_inner() = settings.clone(),
+        }
+        // No subscribers yet is not an error
+        let _ = self.changes.send(settings);
+    }
+}
+
+/// Mutable access to a settings draft; committed on drop if it was borrowed mutably
+pub struct SettingsWriteGuard<'a> {
+    store: &'a SettingsStore,
+    draft: Option<Settings>,
+    changed: bool,
+    _writer: MutexGuard<'a, ()>,
+}
+
+impl Deref for SettingsWriteGuard<'_> {
+    type Target = Settings;
+
+    fn deref(&self) -> &Settings {
+        self.draft.as_ref().expect("settings draft is only taken on drop")
+    }
+}
+
+impl DerefMut for SettingsWriteGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Settings {
+        self.changed = true;
+        self.draft.as_mut().expect("settings draft is only taken on drop")
+    }
+}
+
+impl Drop for SettingsWriteGuard<'_> {
+    fn drop(&mut self) {
+        if let (true, Some(draft)) = (self.changed, self.draft.take()) {
+            self.store.commit(draft);
+        }
+    }
+}
@@ -0,0 +1,309 @@
+//! Startup orchestration for VoiceFlow Pro
+//! Starts backend services in dependency order with timeouts and reports each one as "init-progress"
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use futures_util::future::join_all;
+use serde::{Deserialize, Serialize};
+use tauri::Window;
+use tokio::sync::Mutex;
+
+use crate::errors::{AppError, ErrorCode, ValidationError};
+use crate::AppState;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Service {
+    TextProcessor,
+    VoiceRecognition,
+    AiGateway,
+}
+
+impl Service {
+    pub const ALL: [Service; 3] = [Service::TextProcessor, Service::VoiceRecognition, Service::AiGateway];
+
+    /// Services that must be running first
+    fn dependencies(self) -> &'static [Service] {
+        match self {
+            // Recognized speech goes straight to the text processor
+            Service::VoiceRecognition => &[Service::TextProcessor],
+            Service::TextProcessor | Service::AiGateway => &[],
+        }
+    }
+
+    /// Only services that need no window can be started on first use
+    fn can_defer(self) -> bool {
+        matches!(self, Service::AiGateway)
+    }
+
+    fn default_timeout(self) -> Duration {
+        match self {
+            // Connecting verifies the key over the network
+            Service::AiGateway => Duration::from_secs(45),
+            Service::TextProcessor | Service::VoiceRecognition => Duration::from_secs(15),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InitState {
+    Pending,
+    Starting,
+    Ready,
+    /// Starts the first time something needs it
+    Deferred,
+    Failed,
+    TimedOut,
+    /// Not attempted because a dependency did not start
+    Skipped,
+}
+
+/// Sent as "init-progress" each time a service changes state
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InitProgress {
+    pub service: Service,
+    pub state: InitState,
+    pub message: Option<String>,
+    pub error_code: Option<ErrorCode>,
+    pub duration_ms: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InitOptions {
+    /// Services to bring up, with their dependencies; empty means all
+    pub services: Vec<Service>,
+    /// Services to start on first use instead of now; defaults to the AI gateway
+    pub defer: Option<Vec<Service>>,
+    /// Per-service timeout replacing the defaults
+    pub timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InitReport {
+    pub services: Vec<InitProgress>,
+    /// Every service is running or deferred
+    pub ready: bool,
+    pub duration_ms: u64,
+}
+
+#[derive(Default)]
+struct Orchestrator {
+    deferred: BTreeSet<Service>,
+    /// Where progress of deferred services is reported when they start
+    window: Option<Window>,
+    timeout: Option<Duration>,
+}
+
+static ORCHESTRATOR: OnceLock<Mutex<Orchestrator>> = OnceLock::new();
+
+fn orchestrator() -> &'static Mutex<Orchestrator> {
+    ORCHESTRATOR.get_or_init(|| Mutex::new(Orchestrator::default()))
+}
+
+/// Start the requested services, each wave of services whose dependencies are up running in parallel
+pub async fn initialize_all(state: &AppState, window: &Window, options: InitOptions) -> Result<InitReport, AppError> {
+    let started = Instant::now();
+    let requested: BTreeSet<Service> = match options.services.is_empty() {
+        true => Service::ALL.into_iter().collect(),
+        false => options.services.into_iter().collect(),
+    };
+    let mut deferred: BTreeSet<Service> = options
+        .defer
+        .unwrap_or_else(|| vec![Service::AiGateway])
+        .into_iter()
+        .filter(|service| requested.contains(service))
+        .collect();
+    if let Some(service) = deferred.iter().find(|service| !service.can_defer()) {
+        return Err(AppError::Validation(ValidationError::InvalidConfigValue(format!(
+            "{:?} cannot be started on first use",
+            service
+        ))));
+    }
+
+    let mut pending: BTreeSet<Service> = requested.difference(&deferred).copied().collect();
+    loop {
+        let missing: Vec<Service> = pending
+            .iter()
+            .flat_map(|service| service.dependencies())
+            .filter(|dependency| !pending.contains(dependency))
+            .copied()
+            .collect();
+        if missing.is_empty() {
+            break;
+        }
+        pending.extend(missing);
+    }
+    // A deferred service that something else depends on starts now after all
+    deferred.retain(|service| !pending.contains(service));
+
+    let timeout = options.timeout_secs.map(Duration::from_secs);
+    {
+        let mut orchestrator = orchestrator().lock().await;
+        orchestrator.deferred = deferred.clone();
+        orchestrator.window = Some(window.clone());
+        orchestrator.timeout = timeout;
+    }
+
+    let mut outcomes: BTreeMap<Service, InitProgress> = BTreeMap::new();
+    for &service in &deferred {
+        let progress = init_progress(service, InitState::Deferred, Some("Starts on first use".to_string()), None, 0);
+        emit(window, &progress);
+        outcomes.insert(service, progress);
+    }
+    for &service in &pending {
+        emit(window, &init_progress(service, InitState::Pending, None, None, 0));
+    }
+
+    while !pending.is_empty() {
+        let wave: Vec<Service> = pending
+            .iter()
+            .copied()
+            .filter(|service| service.dependencies().iter().all(|dependency| !pending.contains(dependency)))
+            .collect();
+        if wave.is_empty() {
+            // The graph is fixed and acyclic, so this only guards against a future mistake in it
+            return Err(AppError::Internal(format!("Startup dependency cycle among {:?}", pending)));
+        }
+        pending.retain(|service| !wave.contains(service));
+
+        let mut runnable = Vec::new();
+        for service in wave {
+            let failed = service
+                .dependencies()
+                .iter()
+                .find(|dependency| outcomes.get(dependency).map_or(false, |p| p.state != InitState::Ready));
+            match failed {
+                Some(dependency) => {
+                    let skipped = init_progress(
+                        service,
+                        InitState::Skipped,
+                        Some(format!("Needs {:?}, which did not start", dependency)),
+                        None,
+                        0,
+                    );
+                    emit(window, &skipped);
+                    outcomes.insert(service, skipped);
+                }
+                None => runnable.push(service),
+            }
+        }
+
+        let results = join_all(runnable.into_iter().map(|service| start(state, Some(window), service, timeout))).await;
+        for (progress, _) in results {
+            outcomes.insert(progress.service, progress);
+        }
+    }
+
+    let services: Vec<InitProgress> = outcomes.into_values().collect();
+    Ok(InitReport {
+        ready: services
+            .iter()
+            .all(|p| matches!(p.state, InitState::Ready | InitState::Deferred)),
+        services,
+        duration_ms: started.elapsed().as_millis() as u64,
+    })
+}
+
+/// Start a deferred service before its first use; services that were not deferred are left to their callers
+pub async fn ensure_started(state: &AppState, service: Service) -> Result<(), AppError> {
+    let (window, timeout) = {
+        let orchestrator = orchestrator().lock().await;
+        if !orchestrator.deferred.contains(&service) {
+            return Ok(());
+        }
+        (orchestrator.window.clone(), orchestrator.timeout)
+    };
+    let (_, result) = start(state, window.as_ref(), service, timeout).await;
+    // A failed start stays deferred so the next use tries again
+    if result.is_ok() {
+        orchestrator().lock().await.deferred.remove(&service);
+    }
+    result
+}
+
+async fn start(
+    state: &AppState,
+    window: Option<&Window>,
+    service: Service,
+    timeout: Option<Duration>,
+) -> (InitProgress, Result<(), AppError>) {
+    let started = Instant::now();
+    if let Some(window) = window {
+        emit(window, &init_progress(service, InitState::Starting, None, None, 0));
+    }
+    let limit = timeout.unwrap_or_else(|| service.default_timeout());
+    let result = match tokio::time::timeout(limit, start_service(state, window, service)).await {
+        Ok(result) => result,
+        Err(_) => Err(AppError::Timeout(format!("{:?} did not start within {}s", service, limit.as_secs()))),
+    };
+
+    let elapsed = started.elapsed().as_millis() as u64;
+    let progress = match &result {
+        Ok(()) => init_progress(service, InitState::Ready, None, None, elapsed),
+        Err(e) => {
+            log::warn!("{:?} failed to start: {}", service, e);
+            let init_state = match e {
+                AppError::Timeout(_) => InitState::TimedOut,
+                _ => InitState::Failed,
+            };
+            init_progress(service, init_state, Some(e.to_string()), Some(e.code()), elapsed)
+        }
+    };
+    if let Some(window) = window {
+        emit(window, &progress);
+    }
+    (progress, result)
+}
+
+/// Services that are already running count as started
+async fn start_service(state: &AppState, window: Option<&Window>, service: Service) -> Result<(), AppError> {
+    match service {
+        Service::TextProcessor => {
+            if state.text_processor.lock().await.is_some() {
+                return Ok(());
+            }
+            crate::start_text_processor(state).await
+        }
+        Service::VoiceRecognition => {
+            if state.voice_engine.lock().await.is_some() {
+                return Ok(());
+            }
+            let window = window.ok_or_else(|| AppError::Internal("Voice recognition needs the main window".to_string()))?;
+            crate::start_voice_recognition(state, window).await
+        }
+        Service::AiGateway => {
+            if state.ai_ml_gateway.lock().await.is_some() {
+                return Ok(());
+            }
+            match crate::start_ai_ml_gateway(state).await {
+                // Another caller connected it first
+                Err(_) if state.ai_ml_gateway.lock().await.is_some() => Ok(()),
+                result => result,
+            }
+        }
+    }
+}
+
+fn init_progress(
+    service: Service,
+    state: InitState,
+    message: Option<String>,
+    error_code: Option<ErrorCode>,
+    duration_ms: u64,
+) -> InitProgress {
+    InitProgress {
+        service,
+        state,
+        message,
+        error_code,
+        duration_ms,
+    }
+}
+
+fn emit(window: &Window, progress: &InitProgress) {
+    let _ = window.emit("init-progress", progress);
+}
@@ -0,0 +1,142 @@
+//! Storage locations for VoiceFlow Pro
+//! Resolves the per-user application data directory and its well-known subdirectories
+
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+use crate::errors::AppError;
+
+/// Directory name under the platform data directory
+const APP_DIR_NAME: &str = "VoiceFlow Pro";
+/// Profile that owns the legacy top-level data directories
+pub const DEFAULT_PROFILE_ID: &str = "default";
+const PROFILES_DIR_NAME: &str = "profiles";
+/// Replaces the platform location, so tests and E2E runs never touch real user data
+const DATA_DIR_ENV: &str = "VOICEFLOW_DATA_DIR";
+
+/// Profile whose per-user directories `data_path` resolves to
+static ACTIVE_PROFILE: RwLock<Option<String>> = RwLock::new(None);
+
+/// Well-known subdirectories of the application data directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataDir {
+    Models,
+    Recordings,
+    History,
+    Drafts,
+    Cache,
+    Languages,
+    DomainPacks,
+    Plugins,
+    Audit,
+    Sessions,
+    Crashes,
+}
+
+impl DataDir {
+    fn name(self) -> &'static str {
+        match self {
+            DataDir::Models => "models",
+            DataDir::Recordings => "recordings",
+            DataDir::History => "history",
+            DataDir::Drafts => "drafts",
+            DataDir::Cache => "cache",
+            DataDir::Languages => "languages",
+            DataDir::DomainPacks => "domain_packs",
+            DataDir::Plugins => "plugins",
+            DataDir::Audit => "audit",
+            DataDir::Sessions => "sessions",
+            DataDir::Crashes => "crashes",
+        }
+    }
+
+    /// Models, language resources and plugins are installed once for all profiles;
+    /// each profile decides which plugins it enables. Crash reports belong to the app, not a profile
+    fn is_shared(self) -> bool {
+        matches!(self, DataDir::Models | DataDir::Languages | DataDir::Plugins | DataDir::Crashes)
+    }
+}
+
+/// Root of all per-user application data
+pub fn app_data_dir() -> Result<PathBuf, AppError> {
+    if let Some(dir) = std::env::var_os(DATA_DIR_ENV).filter(|dir| !dir.is_empty()) {
+        return Ok(PathBuf::from(dir));
+    }
+    tauri::api::path::data_dir()
+        .map(|dir| dir.join(APP_DIR_NAME))
+        .ok_or_else(|| AppError::Configuration("Could not resolve the user data directory".to_string()))
+}
+
+/// Root directory of a user profile; the default profile keeps the original top-level layout
+pub fn profile_dir(profile_id: &str) -> Result<PathBuf, AppError> {
+    let root = app_data_dir()?;
+    Ok(if profile_id == DEFAULT_PROFILE_ID {
+        root
+    } else {
+        root.join(PROFILES_DIR_NAME).join(profile_id)
+    })
+}
+
+/// Point per-user data directories at another profile
+pub fn set_active_profile(profile_id: &str) {
+    let mut active = match ACTIVE_PROFILE.write() {
+        Ok(active) => active,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    *active = Some(profile_id.to_string());
+}
+
+pub fn active_profile_id() -> String {
+    ACTIVE_PROFILE
+        .read()
+        .ok()
+        .and_then(|active| active.clone())
+        .unwrap_or_else(|| DEFAULT_PROFILE_ID.to_string())
+}
+
+/// Root directory of the active profile
+pub fn active_profile_dir() -> Result<PathBuf, AppError> {
+    profile_dir(&active_profile_id())
+}
+
+/// Path of a subdirectory, without creating it
+pub fn data_path(dir: DataDir) -> Result<PathBuf, AppError> {
+    profile_data_path(&active_profile_id(), dir)
+}
+
+/// Path of a subdirectory for a specific profile, which need not be the active one
+pub fn profile_data_path(profile_id: &str, dir: DataDir) -> Result<PathBuf, AppError> {
+    let root = if dir.is_shared() { app_data_dir()? } else { profile_dir(profile_id)? };
+    Ok(root.join(dir.name()))
+}
+
+/// A subdirectory as it exists in every profile, for operations that span all users
+pub fn data_paths_all_profiles(dir: DataDir) -> Result<Vec<PathBuf>, AppError> {
+    if dir.is_shared() {
+        return Ok(vec![app_data_dir()?.join(dir.name())]);
+    }
+    Ok(profile_dirs_all()?.into_iter().map(|(_, path)| path.join(dir.name())).collect())
+}
+
+/// Every profile's ID and root directory, the default profile first
+pub fn profile_dirs_all() -> Result<Vec<(String, PathBuf)>, AppError> {
+    let root = app_data_dir()?;
+    let mut dirs = vec![(DEFAULT_PROFILE_ID.to_string(), root.clone())];
+    if let Ok(entries) = std::fs::read_dir(root.join(PROFILES_DIR_NAME)) {
+        dirs.extend(
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.path().is_dir())
+                .map(|entry| (entry.file_name().to_string_lossy().into_owned(), entry.path())),
+        );
+    }
+    Ok(dirs)
+}
+
+/// Path of a subdirectory, creating it if needed
+pub fn ensure_data_dir(dir: DataDir) -> Result<PathBuf, AppError> {
+    let path = data_path(dir)?;
+    std::fs::create_dir_all(&path)
+        .map_err(|e| AppError::Internal(format!("Failed to create {}: {}", path.display(), e)))?;
+    Ok(path)
+}
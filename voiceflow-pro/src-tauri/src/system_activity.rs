@@ -0,0 +1,431 @@
+//! System activity probing for VoiceFlow Pro
+//! Detects user idleness, screen locks and running call applications using platform tools
+
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+use tracing::debug;
+
+/// Snapshot of what the user's machine is doing
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SystemActivity {
+    /// Seconds since the last keyboard/mouse input, when the platform exposes it
+    pub idle_seconds: Option<u64>,
+    /// Whether the session's screen is locked
+    pub screen_locked: bool,
+    /// First configured call application found running
+    pub active_call_app: Option<String>,
+}
+
+/// Probe idle time, lock state and running call applications
+pub async fn probe_system_activity(call_applications: &[String]) -> SystemActivity {
+    SystemActivity {
+        idle_seconds: idle_seconds().await,
+        screen_locked: screen_locked().await,
+        active_call_app: find_running_application(call_applications).await,
+    }
+}
+
+/// Run a command and return its stdout, or None if it is unavailable or fails
+pub(crate) async fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    match Command::new(program).args(args).output().await {
+        Ok(output) if output.status.success() => {
+            Some(String::from_utf8_lossy(&output.stdout).into_owned())
+        }
+        Ok(output) => {
+            debug!("{} exited with {}", program, output.status);
+            None
+        }
+        Err(e) => {
+            debug!("{} unavailable: {}", program, e);
+            None
+        }
+    }
+}
+
+/// Printed by a session script after each reply so the reader knows where it ends
+pub(crate) const SESSION_END_MARKER: &str = "<<voiceflow-end>>";
+const SESSION_QUERY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// An interpreter kept running with its helpers already loaded, so a query costs a line over stdin rather than
+/// a new process and, for PowerShell, a fresh `Add-Type` compile. The script reads one request per line and
+/// prints `SESSION_END_MARKER` after each reply; requests are answered in order.
+pub(crate) struct ScriptSession {
+    program: &'static str,
+    args: Vec<String>,
+    process: tokio::sync::Mutex<Option<SessionProcess>>,
+}
+
+struct SessionProcess {
+    // Killed when the session drops it after a failed query
+    _child: tokio::process::Child,
+    stdin: tokio::process::ChildStdin,
+    stdout: tokio::io::BufReader<tokio::process::ChildStdout>,
+}
+
+impl ScriptSession {
+    pub(crate) fn new(program: &'static str, args: Vec<String>) -> Self {
+        Self {
+            program,
+            args,
+            process: tokio::sync::Mutex::new(None),
+        }
+    }
+
+    /// PowerShell with `setup` evaluated once; each query is a single-line expression
+    #[cfg(target_os = "windows")]
+    pub(crate) fn powershell(setup: &str) -> Self {
+        let script = format!(
+            "[Console]::OutputEncoding = [Text.Encoding]::UTF8; [Console]::InputEncoding = [Text.Encoding]::UTF8; {}; \
+             while ($null -ne ($line = [Console]::In.ReadLine())) {{ \
+             try {{ Invoke-Expression $line | Out-String -Stream | ForEach-Object {{ [Console]::Out.WriteLine($_) }} }} catch {{ }}; \
+             [Console]::Out.WriteLine('{}'); [Console]::Out.Flush() }}",
+            setup, SESSION_END_MARKER
+        );
+        Self::new(
+            "powershell",
+            vec!["-NoProfile".to_string(), "-NonInteractive".to_string(), "-Command".to_string(), script],
+        )
+    }
+
+    /// What the script printed for `request`, or None if it failed; a process that errors or hangs is replaced
+    /// next time
+    pub(crate) async fn query(&self, request: &str) -> Option<String> {
+        let mut process = self.process.lock().await;
+        if process.is_none() {
+            *process = self.spawn().map_err(|e| debug!("{} unavailable: {}", self.program, e)).ok();
+        }
+        let running = process.as_mut()?;
+        match tokio::time::timeout(SESSION_QUERY_TIMEOUT, running.exchange(request)).await {
+            Ok(Ok(output)) => Some(output),
+            Ok(Err(e)) => {
+                debug!("{} session failed: {}", self.program, e);
+                *process = None;
+                None
+            }
+            Err(_) => {
+                debug!("{} session did not answer in time", self.program);
+                *process = None;
+                None
+            }
+        }
+    }
+
+    fn spawn(&self) -> std::io::Result<SessionProcess> {
+        let mut child = Command::new(self.program)
+            .args(&self.args)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::null())
+            .kill_on_drop(true)
+            .spawn()?;
+        let stdin = child.stdin.take().ok_or_else(|| std::io::Error::new(std::io::ErrorKind::BrokenPipe, "no stdin"))?;
+        let stdout = child.stdout.take().ok_or_else(|| std::io::Error::new(std::io::ErrorKind::BrokenPipe, "no stdout"))?;
+        Ok(SessionProcess {
+            _child: child,
+            stdin,
+            stdout: tokio::io::BufReader::new(stdout),
+        })
+    }
+}
+
+impl SessionProcess {
+    async fn exchange(&mut self, request: &str) -> std::io::Result<String> {
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+
+        self.stdin.write_all(request.replace(['\r', '\n'], " ").as_bytes()).await?;
+        self.stdin.write_all(b"\n").await?;
+        self.stdin.flush().await?;
+        let mut output = String::new();
+        loop {
+            let mut line = String::new();
+            if self.stdout.read_line(&mut line).await? == 0 {
+                return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "session script exited"));
+            }
+            if line.trim_end() == SESSION_END_MARKER {
+                return Ok(output);
+            }
+            output.push_str(line.trim_end_matches(['\r', '\n']));
+            output.push('\n');
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+async fn idle_seconds() -> Option<u64> {
+    // HIDIdleTime is reported in nanoseconds
+    let output = command_output("ioreg", &["-c", "IOHIDSystem"]).await?;
+    output
+        .lines()
+        .find(|line| line.contains("HIDIdleTime"))
+        .and_then(|line| line.rsplit('=').next())
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .map(|nanos| nanos / 1_000_000_000)
+}
+
+#[cfg(target_os = "linux")]
+async fn idle_seconds() -> Option<u64> {
+    // xprintidle reports milliseconds; not available under every Wayland compositor
+    let output = command_output("xprintidle", &[]).await?;
+    output.trim().parse::<u64>().ok().map(|millis| millis / 1000)
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
+async fn idle_seconds() -> Option<u64> {
+    None
+}
+
+#[cfg(target_os = "macos")]
+async fn screen_locked() -> bool {
+    command_output("ioreg", &["-n", "Root", "-d1"])
+        .await
+        .map(|output| output.contains("\"CGSSessionScreenIsLocked\"=Yes"))
+        .unwrap_or(false)
+}
+
+/// Process holding macOS Secure Input, which password fields and some terminals turn on to keep
+/// other apps from reading or synthesizing keystrokes
+#[cfg(target_os = "macos")]
+pub async fn secure_input_pid() -> Option<u32> {
+    let output = command_output("ioreg", &["-n", "Root", "-d1"]).await?;
+    let (_, rest) = output.split_once("\"kCGSSessionSecureInputPID\"=")?;
+    let pid = rest.split(|c: char| !c.is_ascii_digit()).next()?.parse::<u32>().ok()?;
+    Some(pid).filter(|pid| *pid != 0)
+}
+
+#[cfg(not(target_os = "macos"))]
+pub async fn secure_input_pid() -> Option<u32> {
+    None
+}
+
+/// Name of a running process, e.g. the app holding Secure Input
+pub async fn process_name(pid: u32) -> Option<String> {
+    let pid = pid.to_string();
+    let output = if cfg!(target_os = "windows") {
+        let filter = format!("PID eq {}", pid);
+        let output = command_output("tasklist", &["/FI", &filter, "/FO", "CSV", "/NH"]).await?;
+        output.split(',').next()?.trim_matches('"').to_string()
+    } else {
+        command_output("ps", &["-p", &pid, "-o", "comm="]).await?
+    };
+    let name = output.trim().rsplit('/').next()?.to_string();
+    Some(name).filter(|name| !name.is_empty())
+}
+
+#[cfg(target_os = "linux")]
+async fn screen_locked() -> bool {
+    let session = std::env::var("XDG_SESSION_ID").unwrap_or_else(|_| "self".to_string());
+    command_output("loginctl", &["show-session", &session, "-p", "LockedHint"])
+        .await
+        .map(|output| output.trim() == "LockedHint=yes")
+        .unwrap_or(false)
+}
+
+#[cfg(target_os = "windows")]
+async fn screen_locked() -> bool {
+    // LogonUI only runs while the lock or login screen is shown
+    command_output("tasklist", &["/FI", "IMAGENAME eq LogonUI.exe", "/NH"])
+        .await
+        .map(|output| output.to_lowercase().contains("logonui.exe"))
+        .unwrap_or(false)
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
+async fn screen_locked() -> bool {
+    false
+}
+
+/// Return the first of `names` that matches a running process (case-insensitive substring)
+pub async fn find_running_application(names: &[String]) -> Option<String> {
+    if names.is_empty() {
+        return None;
+    }
+
+    let listing = if cfg!(target_os = "windows") {
+        command_output("tasklist", &["/FO", "CSV", "/NH"]).await?
+    } else {
+        command_output("ps", &["-A", "-o", "comm="]).await?
+    };
+    let listing = listing.to_lowercase();
+
+    names
+        .iter()
+        .find(|name| !name.is_empty() && listing.contains(&name.to_lowercase()))
+        .cloned()
+}
+
+/// Name of the application that currently has keyboard focus
+#[cfg(target_os = "macos")]
+pub async fn frontmost_application() -> Option<String> {
+    let output = command_output(
+        "osascript",
+        &["-e", "tell application \"System Events\" to get name of first application process whose frontmost is true"],
+    )
+    .await?;
+    Some(output.trim().to_string()).filter(|name| !name.is_empty())
+}
+
+#[cfg(target_os = "linux")]
+pub async fn frontmost_application() -> Option<String> {
+    // X11 only; Wayland compositors do not expose the focused window to clients
+    let output = command_output("xdotool", &["getactivewindow", "getwindowclassname"]).await?;
+    Some(output.trim().to_string()).filter(|name| !name.is_empty())
+}
+
+#[cfg(target_os = "windows")]
+pub async fn frontmost_application() -> Option<String> {
+    static SESSION: std::sync::OnceLock<ScriptSession> = std::sync::OnceLock::new();
+    let session = SESSION.get_or_init(|| {
+        ScriptSession::powershell(
+            "Add-Type -Name W -Namespace U -MemberDefinition '[DllImport(\"user32.dll\")] public static extern System.IntPtr GetForegroundWindow(); [DllImport(\"user32.dll\")] public static extern int GetWindowThreadProcessId(System.IntPtr h, out int p);'",
+        )
+    });
+    let output = session
+        .query("$p = 0; [void][U.W]::GetWindowThreadProcessId([U.W]::GetForegroundWindow(), [ref]$p); (Get-Process -Id $p).ProcessName")
+        .await?;
+    Some(output.trim().to_string()).filter(|name| !name.is_empty())
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
+pub async fn frontmost_application() -> Option<String> {
+    None
+}
+
+/// Whether the OS is in do-not-disturb / focus mode; `None` when it cannot be determined
+#[cfg(target_os = "macos")]
+pub async fn do_not_disturb_enabled() -> Option<bool> {
+    // Focus modes record their active assertions here (macOS 12+)
+    let path = std::path::PathBuf::from(std::env::var_os("HOME")?).join("Library/DoNotDisturb/DB/Assertions.json");
+    let contents = tokio::fs::read_to_string(path).await.ok()?;
+    let json: serde_json::Value = serde_json::from_str(&contents).ok()?;
+    let records = json["data"].as_array()?.first()?.get("storeAssertionRecords");
+    Some(records.and_then(|r| r.as_array()).map_or(false, |r| !r.is_empty()))
+}
+
+#[cfg(target_os = "linux")]
+pub async fn do_not_disturb_enabled() -> Option<bool> {
+    // GNOME turns banners off while do-not-disturb is on
+    let output = command_output("gsettings", &["get", "org.gnome.desktop.notifications", "show-banners"]).await?;
+    Some(output.trim() == "false")
+}
+
+#[cfg(target_os = "windows")]
+pub async fn do_not_disturb_enabled() -> Option<bool> {
+    let output = command_output(
+        "reg",
+        &[
+            "query",
+            r"HKCU\Software\Microsoft\Windows\CurrentVersion\Notifications\Settings",
+            "/v",
+            "NOC_GLOBAL_SETTING_TOASTS_ENABLED",
+        ],
+    )
+    .await?;
+    Some(output.contains("0x0"))
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
+pub async fn do_not_disturb_enabled() -> Option<bool> {
+    None
+}
+
+/// Whether the focused window is full screen, e.g. a slideshow or screen share
+#[cfg(target_os = "macos")]
+pub async fn frontmost_is_fullscreen() -> bool {
+    command_output(
+        "osascript",
+        &[
+            "-e",
+            "tell application \"System Events\" to get value of attribute \"AXFullScreen\" of front window of (first application process whose frontmost is true)",
+        ],
+    )
+    .await
+    .map(|output| output.trim() == "true")
+    .unwrap_or(false)
+}
+
+#[cfg(target_os = "linux")]
+pub async fn frontmost_is_fullscreen() -> bool {
+    let Some(window) = command_output("xdotool", &["getactivewindow"]).await else {
+        return false;
+    };
+    command_output("xprop", &["-id", window.trim(), "_NET_WM_STATE"])
+        .await
+        .map(|output| output.contains("_NET_WM_STATE_FULLSCREEN"))
+        .unwrap_or(false)
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
+pub async fn frontmost_is_fullscreen() -> bool {
+    false
+}
+
+/// Text currently selected in the focused application.
+/// Copies the selection and restores the previous clipboard text where the platform has no direct API.
+#[cfg(target_os = "macos")]
+pub async fn selected_text() -> Option<String> {
+    // The short first delay lets the user release the hotkey so the copy is a plain cmd+C
+    let script = r#"
+delay 0.25
+set saved to ""
+try
+    set saved to the clipboard as text
+end try
+set the clipboard to ""
+tell application "System Events" to keystroke "c" using command down
+delay 0.2
+set picked to ""
+try
+    set picked to the clipboard as text
+end try
+set the clipboard to saved
+return picked"#;
+    let output = command_output("osascript", &["-e", script]).await?;
+    Some(output.trim_end_matches('\n').to_string()).filter(|text| !text.trim().is_empty())
+}
+
+#[cfg(target_os = "linux")]
+pub async fn selected_text() -> Option<String> {
+    // X11 and most Wayland compositors keep the selection in PRIMARY, so the clipboard is never touched
+    let output = match command_output("xclip", &["-o", "-selection", "primary"]).await {
+        Some(output) => output,
+        None => command_output("wl-paste", &["--primary", "--no-newline"]).await?,
+    };
+    Some(output).filter(|text| !text.trim().is_empty())
+}
+
+#[cfg(target_os = "windows")]
+pub async fn selected_text() -> Option<String> {
+    let script = "Add-Type -AssemblyName System.Windows.Forms; Start-Sleep -Milliseconds 250; $saved = [System.Windows.Forms.Clipboard]::GetText(); [System.Windows.Forms.Clipboard]::Clear(); [System.Windows.Forms.SendKeys]::SendWait('^c'); Start-Sleep -Milliseconds 200; $picked = [System.Windows.Forms.Clipboard]::GetText(); if ($saved) { [System.Windows.Forms.Clipboard]::SetText($saved) }; $picked";
+    let output = command_output("powershell", &["-NoProfile", "-NonInteractive", "-Sta", "-Command", script]).await?;
+    Some(output.trim_end_matches(['\r', '\n']).to_string()).filter(|text| !text.trim().is_empty())
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
+pub async fn selected_text() -> Option<String> {
+    None
+}
+
+/// Current clipboard text, read so a later undo can put it back
+#[cfg(target_os = "macos")]
+pub async fn clipboard_text() -> Option<String> {
+    command_output("pbpaste", &[]).await
+}
+
+#[cfg(target_os = "linux")]
+pub async fn clipboard_text() -> Option<String> {
+    match command_output("xclip", &["-o", "-selection", "clipboard"]).await {
+        Some(output) => Some(output),
+        None => command_output("wl-paste", &["--no-newline"]).await,
+    }
+}
+
+#[cfg(target_os = "windows")]
+pub async fn clipboard_text() -> Option<String> {
+    let output = command_output("powershell", &["-NoProfile", "-NonInteractive", "-Command", "Get-Clipboard -Raw"]).await?;
+    Some(output.trim_end_matches(['\r', '\n']).to_string())
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
+pub async fn clipboard_text() -> Option<String> {
+    None
+}
@@ -0,0 +1,399 @@
+//! First-run readiness checks for VoiceFlow Pro
+//! Verifies microphone access, audio devices, API credentials, local models and disk space
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::integrations::{AIMLClient, AIMLError};
+use crate::storage::{app_data_dir, data_path, DataDir};
+use crate::Settings;
+
+/// Free space below which downloads and recordings are likely to fail
+const MIN_FREE_DISK_BYTES: u64 = 1024 * 1024 * 1024;
+
+/// Outcome of a single check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Pass,
+    Warning,
+    Fail,
+    Unknown,
+}
+
+/// Action the onboarding UI can offer to resolve a failed check
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FixAction {
+    pub label: String,
+    /// Settings deep link or documentation URL opened with the shell
+    pub url: Option<String>,
+    /// Tauri command the UI can invoke instead of opening a link
+    pub command: Option<String>,
+}
+
+/// A single readiness check
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SystemCheck {
+    pub id: String,
+    pub name: String,
+    pub status: CheckStatus,
+    pub message: String,
+    pub fix_action: Option<FixAction>,
+}
+
+/// Readiness report rendered by the onboarding flow
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SystemCheckReport {
+    /// True when no check failed; warnings do not block dictation
+    pub ready: bool,
+    pub platform: String,
+    pub checks: Vec<SystemCheck>,
+    pub generated_at: u64,
+}
+
+/// Run every readiness check; individual failures are reported, never returned as errors
+pub async fn run_system_checks(settings: &Settings) -> SystemCheckReport {
+    let (microphone, devices, api_key) = tokio::join!(
+        check_microphone_permission(),
+        check_audio_devices(),
+        check_api_key(settings),
+    );
+    let checks = vec![
+        microphone,
+        devices,
+        api_key,
+        check_local_model(settings),
+        check_disk_space(),
+    ];
+
+    SystemCheckReport {
+        ready: checks.iter().all(|check| check.status != CheckStatus::Fail),
+        platform: std::env::consts::OS.to_string(),
+        checks,
+        generated_at: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+    }
+}
+
+fn check(id: &str, name: &str, status: CheckStatus, message: impl Into<String>, fix_action: Option<FixAction>) -> SystemCheck {
+    SystemCheck {
+        id: id.to_string(),
+        name: name.to_string(),
+        status,
+        message: message.into(),
+        fix_action,
+    }
+}
+
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+fn open_url(label: &str, url: &str) -> Option<FixAction> {
+    Some(FixAction {
+        label: label.to_string(),
+        url: Some(url.to_string()),
+        command: None,
+    })
+}
+
+fn run_command(label: &str, command: &str) -> Option<FixAction> {
+    Some(FixAction {
+        label: label.to_string(),
+        url: None,
+        command: Some(command.to_string()),
+    })
+}
+
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+async fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = tokio::process::Command::new(program).args(args).output().await.ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(target_os = "macos")]
+async fn check_microphone_permission() -> SystemCheck {
+    // TCC does not expose the grant to other processes; the first capture triggers the system prompt
+    check(
+        "microphone_permission",
+        "Microphone permission",
+        CheckStatus::Unknown,
+        "macOS asks for microphone access the first time dictation starts. If it was denied, enable VoiceFlow Pro under Privacy & Security > Microphone.",
+        open_url(
+            "Open Microphone privacy settings",
+            "x-apple.systempreferences:com.apple.preference.security?Privacy_Microphone",
+        ),
+    )
+}
+
+#[cfg(target_os = "windows")]
+async fn check_microphone_permission() -> SystemCheck {
+    let fix = open_url("Open microphone privacy settings", "ms-settings:privacy-microphone");
+    let consent = command_output(
+        "reg",
+        &[
+            "query",
+            r"HKCU\Software\Microsoft\Windows\CurrentVersion\CapabilityAccessManager\ConsentStore\microphone",
+            "/v",
+            "Value",
+        ],
+    )
+    .await;
+
+    match consent {
+        Some(output) if output.contains("Deny") => check(
+            "microphone_permission",
+            "Microphone permission",
+            CheckStatus::Fail,
+            "Microphone access is turned off for desktop apps.",
+            fix,
+        ),
+        Some(output) if output.contains("Allow") => check(
+            "microphone_permission",
+            "Microphone permission",
+            CheckStatus::Pass,
+            "Microphone access is allowed.",
+            None,
+        ),
+        _ => check(
+            "microphone_permission",
+            "Microphone permission",
+            CheckStatus::Unknown,
+            "Could not read the microphone privacy setting.",
+            fix,
+        ),
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+async fn check_microphone_permission() -> SystemCheck {
+    // No OS-level consent on Linux; access is governed by device node permissions
+    let snd = Path::new("/dev/snd");
+    let readable = std::fs::read_dir(snd).is_ok();
+    if readable {
+        check(
+            "microphone_permission",
+            "Microphone permission",
+            CheckStatus::Pass,
+            "Audio devices are accessible to this user.",
+            None,
+        )
+    } else {
+        check(
+            "microphone_permission",
+            "Microphone permission",
+            CheckStatus::Fail,
+            "This user cannot access /dev/snd. Add the user to the 'audio' group or check the sandbox permissions.",
+            None,
+        )
+    }
+}
+
+async fn check_audio_devices() -> SystemCheck {
+    let input_count = count_input_devices().await;
+    match input_count {
+        Some(0) => check(
+            "audio_devices",
+            "Audio input device",
+            CheckStatus::Fail,
+            "No microphone was found. Connect a microphone or headset.",
+            run_command("Check again", "run_system_checks"),
+        ),
+        Some(count) => check(
+            "audio_devices",
+            "Audio input device",
+            CheckStatus::Pass,
+            format!("{} input device(s) available.", count),
+            None,
+        ),
+        None => check(
+            "audio_devices",
+            "Audio input device",
+            CheckStatus::Unknown,
+            "Could not enumerate audio devices on this system.",
+            None,
+        ),
+    }
+}
+
+#[cfg(target_os = "macos")]
+async fn count_input_devices() -> Option<usize> {
+    let output = command_output("system_profiler", &["SPAudioDataType"]).await?;
+    Some(output.lines().filter(|line| line.trim_start().starts_with("Input Channels:")).count())
+}
+
+#[cfg(target_os = "windows")]
+async fn count_input_devices() -> Option<usize> {
+    let output = command_output(
+        "powershell",
+        &[
+            "-NoProfile",
+            "-Command",
+            "(Get-PnpDevice -Class AudioEndpoint -Status OK | Where-Object { $_.FriendlyName -match 'Microphone|Input|Headset' }).Count",
+        ],
+    )
+    .await?;
+    output.trim().parse().ok().or(Some(0))
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+async fn count_input_devices() -> Option<usize> {
+    // Capture devices appear as pcmC<card>D<device>c nodes
+    let entries = std::fs::read_dir("/dev/snd").ok()?;
+    Some(
+        entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                name.starts_with("pcm") && name.ends_with('c')
+            })
+            .count(),
+    )
+}
+
+async fn check_api_key(settings: &Settings) -> SystemCheck {
+    let ai = &settings.ai_ml_settings;
+    if ai.mock.is_active() {
+        return check(
+            "api_key",
+            "AI ML API key",
+            CheckStatus::Pass,
+            "Mock mode is on; AI features answer with canned responses and no key is needed.",
+            None,
+        );
+    }
+    if ai.api_key.trim().is_empty() {
+        return check(
+            "api_key",
+            "AI ML API key",
+            CheckStatus::Fail,
+            "No API key is configured. Enhancement, translation and voice generation are unavailable.",
+            run_command("Add API key", "update_settings"),
+        );
+    }
+
+    let http_client = crate::network::client_builder().build().unwrap_or_default();
+    let client = AIMLClient::new(ai.api_key.clone(), ai.base_url.clone(), http_client);
+    // The models list is public, so the key itself is checked against an authenticated route; neither is billed
+    let result = match client.verify_key().await {
+        Ok(()) => client.list_model_ids().await,
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(models) if !models.is_empty() => {
+            check("api_key", "AI ML API key", CheckStatus::Pass, "API key is valid.", None)
+        }
+        Ok(_) => check(
+            "api_key",
+            "AI ML API key",
+            CheckStatus::Warning,
+            "The API accepted the key but listed no models.",
+            None,
+        ),
+        Err(AIMLError::AuthError(_)) => check(
+            "api_key",
+            "AI ML API key",
+            CheckStatus::Fail,
+            "The API rejected the configured key.",
+            run_command("Update API key", "update_settings"),
+        ),
+        Err(e) => check(
+            "api_key",
+            "AI ML API key",
+            CheckStatus::Warning,
+            format!("Could not verify the key: {}", e),
+            run_command("Retry", "run_system_checks"),
+        ),
+    }
+}
+
+fn check_local_model(settings: &Settings) -> SystemCheck {
+    let model = &settings.voice_model;
+    // Local models are only required for on-device recognition
+    let missing_status = if settings.voice_recognition.privacy_mode {
+        CheckStatus::Fail
+    } else {
+        CheckStatus::Warning
+    };
+
+    let models_dir = match data_path(DataDir::Models) {
+        Ok(dir) => dir,
+        Err(e) => return check("local_model", "Local speech model", CheckStatus::Unknown, e.to_string(), None),
+    };
+
+    if model_present(&models_dir, model) {
+        check(
+            "local_model",
+            "Local speech model",
+            CheckStatus::Pass,
+            format!("Model '{}' is installed.", model),
+            None,
+        )
+    } else {
+        check(
+            "local_model",
+            "Local speech model",
+            missing_status,
+            format!("Model '{}' is not installed; offline and privacy-mode dictation need it.", model),
+            run_command("Download model", "download_model"),
+        )
+    }
+}
+
+/// A model is installed as `<models>/<id>` or `<models>/<id>.<ext>`
+fn model_present(models_dir: &Path, model: &str) -> bool {
+    if models_dir.join(model).exists() {
+        return true;
+    }
+    std::fs::read_dir(models_dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .any(|entry| entry.path().file_stem().map(|stem| stem == model).unwrap_or(false))
+        })
+        .unwrap_or(false)
+}
+
+fn check_disk_space() -> SystemCheck {
+    let target = match app_data_dir() {
+        Ok(dir) => existing_ancestor(dir),
+        Err(e) => return check("disk_space", "Disk space", CheckStatus::Unknown, e.to_string(), None),
+    };
+
+    match fs2::available_space(&target) {
+        Ok(free) if free < MIN_FREE_DISK_BYTES => check(
+            "disk_space",
+            "Disk space",
+            CheckStatus::Warning,
+            format!("Only {} MB free; recordings and model downloads may fail.", free / (1024 * 1024)),
+            None,
+        ),
+        Ok(free) => check(
+            "disk_space",
+            "Disk space",
+            CheckStatus::Pass,
+            format!("{} MB free.", free / (1024 * 1024)),
+            None,
+        ),
+        Err(e) => check(
+            "disk_space",
+            "Disk space",
+            CheckStatus::Unknown,
+            format!("Could not read free space: {}", e),
+            None,
+        ),
+    }
+}
+
+/// The data directory may not exist on first run; measure the volume it will live on
+fn existing_ancestor(mut path: PathBuf) -> PathBuf {
+    while !path.exists() {
+        if !path.pop() {
+            break;
+        }
+    }
+    path
+}
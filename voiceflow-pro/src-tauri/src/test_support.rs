@@ -0,0 +1,157 @@
+//! Test support for VoiceFlow Pro
+//! Builds an `AppState` on Tauri's mock runtime so command handlers can be called directly, offline and against a throwaway data directory
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+
+use serde_json::Value;
+use tauri::test::{mock_app, MockRuntime};
+use tauri::{App, Manager, State};
+use tokio::sync::Mutex;
+
+use crate::error_boundary::get_error_boundary_registry;
+use crate::integrations::mock_provider::MockSettings;
+use crate::memory::get_resource_manager;
+use crate::settings_store::SettingsStore;
+use crate::{AppState, Settings};
+
+const GOLDEN_TEXT_PIPELINE: &str = include_str!("../tests/golden/text_pipeline.json");
+
+static DATA_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Data directory shared by every test in this process; the first call points storage and the AI client at it
+pub fn data_dir() -> &'static Path {
+    DATA_DIR.get_or_init(|| {
+        let dir = std::env::temp_dir().join(format!("voiceflow-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).expect("test data directory can be created");
+        std::env::set_var("VOICEFLOW_DATA_DIR", &dir);
+        std::env::set_var("VOICEFLOW_MOCK", "1");
+        dir
+    })
+}
+
+/// Default settings with no API key and mock responses that arrive without delay
+pub fn test_settings() -> Settings {
+    let mut settings = Settings::default();
+    settings.ai_ml_settings.api_key.clear();
+    settings.ai_ml_settings.mock = MockSettings {
+        enabled: true,
+        latency_ms: 0,
+    };
+    settings
+}
+
+/// A headless app managing a fresh `AppState`; nothing is started until a test asks for it
+pub struct TestApp {
+    app: App<MockRuntime>,
+}
+
+impl TestApp {
+    pub fn new() -> Self {
+        Self::with_settings(test_settings())
+    }
+
+    pub fn with_settings(settings: Settings) -> Self {
+        data_dir();
+        let app = mock_app();
+        app.manage(AppState {
+            voice_engine: Arc::new(Mutex::new(None)),
+            text_processor: Arc::new(Mutex::new(None)),
+            ai_ml_gateway: Arc::new(Mutex::new(None)),
+            settings: Arc::new(SettingsStore::new(settings)),
+            shortcuts: Arc::new(Mutex::new(HashMap::new())),
+            event_handlers: Arc::new(Mutex::new(Vec::new())),
+            resource_manager: get_resource_manager().clone(),
+            error_boundaries: get_error_boundary_registry().clone(),
+            last_output: Arc::new(Mutex::new(None)),
+        });
+        Self { app }
+    }
+
+    /// What a command handler receives as `State<'_, AppState>`
+    pub fn state(&self) -> State<'_, AppState> {
+        self.app.state::<AppState>()
+    }
+}
+
+/// Cases of one section of the text pipeline golden file
+pub fn golden(section: &str) -> Vec<Value> {
+    let file: Value = serde_json::from_str(GOLDEN_TEXT_PIPELINE).expect("golden file is valid JSON");
+    file[section]
+        .as_array()
+        .cloned()
+        .unwrap_or_else(|| panic!("golden file has no {:?} section", section))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::PipelineSettings;
+    use crate::{profiles, storage};
+
+    #[tokio::test]
+    async fn process_text_needs_the_text_processor() {
+        let app = TestApp::new();
+        let result = crate::process_text(
+            "hello there".to_string(),
+            "email".to_string(),
+            "professional".to_string(),
+            app.state(),
+        )
+        .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn process_text_cleans_up_dictation() {
+        let app = TestApp::new();
+        crate::initialize_text_processor(app.state()).await.unwrap();
+        let result = crate::process_text(
+            "hey your going to the demo".to_string(),
+            "email".to_string(),
+            "professional".to_string(),
+            app.state(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.processed_text, "Hello you're going to the demo.");
+        assert_eq!(result.original_text, "hey your going to the demo");
+    }
+
+    #[tokio::test]
+    async fn pipeline_is_saved_to_the_test_data_dir() {
+        let app = TestApp::new();
+        crate::set_pipeline(PipelineSettings::default(), app.state()).await.unwrap();
+
+        assert!(storage::app_data_dir().unwrap().starts_with(data_dir()));
+        let saved = profiles::load_profile_settings(&storage::active_profile_id()).unwrap();
+        assert_eq!(saved.pipeline, PipelineSettings::default());
+        let (pipeline, validation) = crate::get_pipeline(app.state()).await.unwrap();
+        assert_eq!(pipeline, saved.pipeline);
+        assert!(validation.errors.is_empty());
+    }
+
+    #[tokio::test]
+    async fn ai_gateway_runs_offline_in_mock_mode() {
+        let app = TestApp::new();
+        let state = app.state();
+        crate::start_ai_ml_gateway(&state).await.unwrap();
+
+        let gateway = state.ai_ml_gateway.lock().await;
+        let (reply, usage) = gateway
+            .as_ref()
+            .unwrap()
+            .complete_with_model(
+                "gpt-4o".to_string(),
+                "You clean up dictated text.".to_string(),
+                "um hello there".to_string(),
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(reply, "Hello there.");
+        assert!(usage.is_some());
+    }
+}
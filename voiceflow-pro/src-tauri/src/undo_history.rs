@@ -0,0 +1,221 @@
+//! Undo history for VoiceFlow Pro
+//! Records what each dictation session changed in other apps and works out the keystrokes or clipboard restore that revert it
+
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use crate::errors::AppError;
+use crate::system_activity::frontmost_application;
+use crate::unicode_text::grapheme_count;
+use crate::AppState;
+
+/// Actions kept per session; the oldest can no longer be undone
+const MAX_ACTIONS: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InjectionMethod {
+    Typed,
+    /// Put on the clipboard and pasted, replacing what the clipboard held
+    Pasted,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TextAction {
+    /// A refinement or correction rewrote the last dictation
+    Enhancement {
+        before: String,
+        after: String,
+        /// Whether `after` replaced `before` in the target app, rather than only in VoiceFlow
+        reinjected: bool,
+    },
+    /// Text the frontend typed or pasted into the focused app
+    Injection {
+        text: String,
+        method: InjectionMethod,
+        previous_clipboard: Option<String>,
+    },
+    ClipboardWrite { text: String, previous: Option<String> },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActionRecord {
+    pub id: String,
+    pub action: TextAction,
+    /// App that had focus when the action happened; keystrokes are only replayed into the same app
+    pub target_app: Option<String>,
+    pub created_at: u64,
+}
+
+/// One step the frontend performs to revert or repeat an action
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Compensation {
+    /// One press per user-perceived character; editors delete a combined emoji or accented letter in one go
+    Backspace { count: usize },
+    TypeText { text: String },
+    SetClipboard { text: String },
+}
+
+/// Sent as "action-undone" or "action-redone"
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UndoOutcome {
+    pub record: ActionRecord,
+    pub redo: bool,
+    /// Steps for the frontend to perform, in order
+    pub compensations: Vec<Compensation>,
+    /// Why the target app is left as it is, e.g. because another app has focus now; the action then
+    /// stays on its stack, to be undone or redone once the app can take keystrokes again
+    pub skipped_reason: Option<String>,
+    pub can_undo: bool,
+    pub can_redo: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UndoHistory {
+    pub session_id: Option<String>,
+    pub undo: Vec<ActionRecord>,
+    pub redo: Vec<ActionRecord>,
+}
+
+static HISTORY: OnceLock<Mutex<UndoHistory>> = OnceLock::new();
+
+fn history() -> &'static Mutex<UndoHistory> {
+    HISTORY.get_or_init(|| Mutex::new(UndoHistory::default()))
+}
+
+/// Switch to `session_id`'s history; actions from an earlier session can no longer be undone
+pub async fn start_session(session_id: &str) {
+    let mut history = history().lock().await;
+    if history.session_id.as_deref() != Some(session_id) {
+        *history = UndoHistory {
+            session_id: Some(session_id.to_string()),
+            ..UndoHistory::default()
+        };
+    }
+}
+
+/// Record an action; anything undone before it can no longer be redone
+pub async fn record(action: TextAction) -> ActionRecord {
+    let record = ActionRecord {
+        id: Uuid::new_v4().to_string(),
+        action,
+        target_app: frontmost_application().await,
+        created_at: now_secs(),
+    };
+    let mut history = history().lock().await;
+    history.redo.clear();
+    history.undo.push(record.clone());
+    if history.undo.len() > MAX_ACTIONS {
+        history.undo.remove(0);
+    }
+    record
+}
+
+pub async fn current_history() -> UndoHistory {
+    history().lock().await.clone()
+}
+
+/// Revert the most recent action
+pub async fn undo_last_action(state: &AppState) -> Result<UndoOutcome, AppError> {
+    step(state, false).await
+}
+
+/// Repeat the most recently undone action
+pub async fn redo_action(state: &AppState) -> Result<UndoOutcome, AppError> {
+    step(state, true).await
+}
+
+async fn step(state: &AppState, redo: bool) -> Result<UndoOutcome, AppError> {
+    let record = {
+        let mut history = history().lock().await;
+        let stack = if redo { &mut history.redo } else { &mut history.undo };
+        stack.pop().ok_or_else(|| {
+            AppError::Configuration(format!("There is nothing to {}", if redo { "redo" } else { "undo" }))
+        })?
+    };
+
+    let mut compensations = compensations_for(&record.action, redo);
+    let mut skipped_reason = None;
+    let touches_app = compensations
+        .iter()
+        .any(|step| !matches!(step, Compensation::SetClipboard { .. }));
+    if touches_app {
+        if let Some(reason) = crate::injection_safety::injection_blocked() {
+            compensations.retain(|step| matches!(step, Compensation::SetClipboard { .. }));
+            skipped_reason = Some(reason);
+        } else if let Some(target) = &record.target_app {
+            let focused = frontmost_application().await;
+            // Keystrokes sent to another app would delete the wrong text; unknown focus is trusted
+            if focused.as_deref().map_or(false, |app| app != target) {
+                compensations.retain(|step| matches!(step, Compensation::SetClipboard { .. }));
+                skipped_reason = Some(format!("{} no longer has focus", target));
+            }
+        }
+    }
+
+    let reverted = skipped_reason.is_none();
+    if reverted {
+        if let TextAction::Enhancement { before, after, .. } = &record.action {
+            *state.last_output.lock().await = Some(if redo { after.clone() } else { before.clone() });
+        }
+    }
+
+    let mut history = history().lock().await;
+    // A skipped step leaves the action where it was, since the app still shows it; a redo would type it twice
+    if redo == reverted {
+        history.undo.push(record.clone());
+    } else {
+        history.redo.push(record.clone());
+    }
+    Ok(UndoOutcome {
+        record,
+        redo,
+        compensations,
+        skipped_reason,
+        can_undo: !history.undo.is_empty(),
+        can_redo: !history.redo.is_empty(),
+    })
+}
+
+/// Steps that revert `action`, or repeat it when `redo` is set
+pub fn compensations_for(action: &TextAction, redo: bool) -> Vec<Compensation> {
+    match action {
+        TextAction::Enhancement { before, after, reinjected } => {
+            if !reinjected {
+                return Vec::new();
+            }
+            let (remove, insert) = if redo { (before, after) } else { (after, before) };
+            vec![
+                Compensation::Backspace { count: grapheme_count(remove) },
+                Compensation::TypeText { text: insert.clone() },
+            ]
+        }
+        TextAction::Injection { text, method, previous_clipboard } => {
+            if redo {
+                return vec![Compensation::TypeText { text: text.clone() }];
+            }
+            let mut steps = vec![Compensation::Backspace { count: grapheme_count(text) }];
+            if let (InjectionMethod::Pasted, Some(previous)) = (method, previous_clipboard) {
+                steps.push(Compensation::SetClipboard { text: previous.clone() });
+            }
+            steps
+        }
+        TextAction::ClipboardWrite { text, previous } => match (redo, previous) {
+            (true, _) => vec![Compensation::SetClipboard { text: text.clone() }],
+            (false, Some(previous)) => vec![Compensation::SetClipboard { text: previous.clone() }],
+            (false, None) => Vec::new(),
+        },
+    }
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
@@ -0,0 +1,332 @@
+//! Unicode text handling for VoiceFlow Pro
+//! Grapheme counts, word and sentence segmentation for scripts written without spaces, and bidi safety for right-to-left text
+
+use serde::{Deserialize, Serialize};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// Embedding, override and isolate controls plus the directional marks
+const BIDI_CONTROLS: &[char] = &[
+    '\u{200E}', '\u{200F}', '\u{061C}', '\u{202A}', '\u{202B}', '\u{202C}', '\u{202D}', '\u{202E}', '\u{2066}', '\u{2067}',
+    '\u{2068}', '\u{2069}',
+];
+
+/// Full stops that end a sentence even with no space after them
+const CJK_TERMINALS: &[char] = &['。', '！', '？', '｡'];
+const TERMINALS: &[char] = &[
+    '.', '!', '?', '‼', '⁇', '。', '！', '？', '｡', '؟', '۔', '।', '॥', '።', '։', '\u{037E}',
+];
+/// Closing quotes and brackets that belong to the sentence they follow
+const CLOSERS: &[char] = &['"', '\'', ')', ']', '”', '’', '」', '』', '）', '】', '〕', '»', '›'];
+/// Punctuation that may not begin a line in CJK text (kinsoku)
+const NO_LINE_START: &[char] = &[
+    '、', '。', '，', '．', '！', '？', '：', '；', '）', '」', '』', '】', '〕', '・', 'ー', '…', '々', 'ゝ', 'ゞ', ',', '.', '!', '?',
+];
+/// Opening brackets that may not end a line
+const NO_LINE_END: &[char] = &['（', '「', '『', '【', '〔', '(', '['];
+
+/// Words a full stop follows without ending the sentence, lower-case and without the final stop
+const ABBREVIATIONS: &[(&str, &[&str])] = &[
+    ("en", &["mr", "mrs", "ms", "dr", "prof", "sr", "jr", "st", "vs", "etc", "e.g", "i.e", "approx", "inc", "ltd", "co", "fig"]),
+    ("de", &["z.b", "bzw", "usw", "ca", "nr", "dr", "prof", "hr", "fr", "str", "vgl", "evtl", "ggf", "u.a", "d.h", "etc"]),
+    ("fr", &["m", "mme", "mlle", "dr", "etc", "p.ex", "cf", "env", "av", "bd"]),
+    ("es", &["sr", "sra", "srta", "dr", "dra", "ud", "uds", "etc", "p.ej", "pág", "núm"]),
+    ("it", &["sig", "sig.ra", "dott", "ecc", "pag", "prof"]),
+    ("pt", &["sr", "sra", "dr", "dra", "etc", "pág", "prof"]),
+    ("nl", &["dhr", "mevr", "bijv", "enz", "blz", "dr", "prof"]),
+];
+/// Abbreviations that are also ordinary words, so they only count as one before a number: "No. 5" but "I said no."
+const NUMBER_ABBREVIATIONS: &[&str] = &["no"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TextDirection {
+    Ltr,
+    Rtl,
+}
+
+/// Length as a reader counts it: "é" written with a combining accent or a flag emoji is one
+pub fn grapheme_count(text: &str) -> usize {
+    text.graphemes(true).count()
+}
+
+/// Han ideographs and Japanese kana, written without spaces between words; Hangul is spaced and left out
+pub fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30FF}'
+            | '\u{31F0}'..='\u{31FF}'
+            | '\u{3400}'..='\u{4DBF}'
+            | '\u{4E00}'..='\u{9FFF}'
+            | '\u{F900}'..='\u{FAFF}'
+            | '\u{FF66}'..='\u{FF9F}'
+            | '\u{20000}'..='\u{2FFFF}'
+    )
+}
+
+/// CJK symbols and full-width punctuation
+pub fn is_cjk_punctuation(c: char) -> bool {
+    matches!(c, '\u{3000}'..='\u{303F}' | '\u{FF01}'..='\u{FF0F}' | '\u{FF1A}'..='\u{FF20}' | '\u{FF3B}'..='\u{FF40}' | '\u{FF5B}'..='\u{FF65}')
+}
+
+pub fn contains_cjk(text: &str) -> bool {
+    text.chars().any(is_cjk)
+}
+
+/// Hebrew, Arabic, Syriac, Thaana, N'Ko and their presentation forms
+fn is_strong_rtl(c: char) -> bool {
+    matches!(c,
+        '\u{0590}'..='\u{08FF}' | '\u{FB1D}'..='\u{FDFF}' | '\u{FE70}'..='\u{FEFF}' | '\u{10800}'..='\u{10FFF}' | '\u{1E800}'..='\u{1EFFF}'
+    )
+}
+
+/// Direction of the first strongly directional character, as `dir="auto"` would pick it
+pub fn direction(text: &str) -> Option<TextDirection> {
+    text.chars().find_map(|c| {
+        if is_strong_rtl(c) {
+            Some(TextDirection::Rtl)
+        } else if c.is_alphabetic() {
+            Some(TextDirection::Ltr)
+        } else {
+            None
+        }
+    })
+}
+
+pub fn is_bidi_control(c: char) -> bool {
+    BIDI_CONTROLS.contains(&c)
+}
+
+pub fn strip_bidi_controls(text: &str) -> String {
+    text.chars().filter(|c| !is_bidi_control(*c)).collect()
+}
+
+/// Close embeddings and isolates left open and drop stray terminators, so text typed into another app
+/// cannot flip the direction of whatever follows it there
+pub fn balance_bidi(text: &str) -> String {
+    if !text.chars().any(is_bidi_control) {
+        return text.to_string();
+    }
+    // Open embeddings/overrides (closed by PDF) and isolates (closed by PDI), innermost last
+    let mut open: Vec<char> = Vec::new();
+    let mut out = String::with_capacity(text.len() + 4);
+    for c in text.chars() {
+        match c {
+            '\u{202A}'..='\u{202B}' | '\u{202D}'..='\u{202E}' | '\u{2066}'..='\u{2068}' => {
+                open.push(c);
+                out.push(c);
+            }
+            '\u{202C}' => {
+                if matches!(open.last(), Some('\u{202A}'..='\u{202E}')) {
+                    open.pop();
+                    out.push(c);
+                }
+            }
+            '\u{2069}' => {
+                // A PDI also closes any embeddings opened inside its isolate
+                if let Some(at) = open.iter().rposition(|o| matches!(o, '\u{2066}'..='\u{2068}')) {
+                    for _ in open.drain(at + 1..) {
+                        out.push('\u{202C}');
+                    }
+                    open.pop();
+                    out.push(c);
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+    for c in open.into_iter().rev() {
+        out.push(if matches!(c, '\u{2066}'..='\u{2068}') { '\u{2069}' } else { '\u{202C}' });
+    }
+    out
+}
+
+/// Words by Unicode word boundaries; Han and kana come out a character at a time, the usual unit for counting them
+pub fn words(text: &str) -> Vec<&str> {
+    text.unicode_words().collect()
+}
+
+pub fn word_count(text: &str) -> usize {
+    text.unicode_words().count()
+}
+
+/// Tokens for word-level diffs, with whether whitespace came before each one: whitespace-separated words,
+/// and characters and punctuation inside CJK runs
+pub fn diff_tokens(text: &str) -> Vec<(bool, &str)> {
+    let mut tokens = Vec::new();
+    for word in text.split_whitespace() {
+        if !contains_cjk(word) {
+            tokens.push((true, word));
+            continue;
+        }
+        for (index, piece) in word.split_word_bounds().enumerate() {
+            tokens.push((index == 0, piece));
+        }
+    }
+    tokens
+}
+
+/// A piece of text a caption line may break before
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BreakUnit<'a> {
+    pub text: &'a str,
+    /// Whether the unit is separated from the one before by a space
+    pub space_before: bool,
+}
+
+/// Line-break opportunities: between spaced words, and between graphemes of CJK text except around brackets and before closing punctuation
+pub fn break_units(text: &str) -> Vec<BreakUnit<'_>> {
+    let mut units: Vec<BreakUnit<'_>> = Vec::new();
+    for word in text.split_whitespace() {
+        if !contains_cjk(word) && !word.chars().any(is_cjk_punctuation) {
+            units.push(BreakUnit { text: word, space_before: true });
+            continue;
+        }
+        let mut start = 0;
+        for (offset, grapheme) in word.grapheme_indices(true) {
+            let joins_previous = offset > 0 && grapheme.chars().next().is_some_and(|c| NO_LINE_START.contains(&c));
+            let previous = word[..offset].chars().last();
+            let after_opener = previous.is_some_and(|c| NO_LINE_END.contains(&c));
+            let previous_is_cjk = previous.is_some_and(|c| is_cjk(c) || is_cjk_punctuation(c));
+            let current_is_cjk = grapheme.chars().next().is_some_and(|c| is_cjk(c) || is_cjk_punctuation(c));
+            if offset > start && !joins_previous && !after_opener && (previous_is_cjk || current_is_cjk) {
+                units.push(BreakUnit { text: &word[start..offset], space_before: start == 0 });
+                start = offset;
+            }
+        }
+        units.push(BreakUnit { text: &word[start..], space_before: start == 0 });
+    }
+    units
+}
+
+/// 1.0 minus the character edit distance relative to the longer string
+pub fn similarity(a: &str, b: &str) -> f32 {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 1.0;
+    }
+
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    1.0 - previous[b.len()] as f32 / longest as f32
+}
+
+/// Columns the text takes in a monospaced caption: full-width characters count twice
+pub fn display_width(text: &str) -> usize {
+    UnicodeWidthStr::width(text)
+}
+
+/// Split text into sentences, keeping terminal punctuation, closing quotes and following whitespace with each.
+/// `language` picks the abbreviations that do not end a sentence; pass "" when it is unknown
+pub fn split_sentences(text: &str, language: &str) -> Vec<String> {
+    let base = language.split(['-', '_']).next().unwrap_or_default().to_lowercase();
+    let abbreviations = ABBREVIATIONS
+        .iter()
+        .find(|(code, _)| *code == base)
+        .or_else(|| ABBREVIATIONS.first())
+        .map_or(&[][..], |(_, words)| *words);
+    // Greek writes the question mark as a semicolon; Thai separates sentences with a space alone
+    let greek = base == "el";
+    let thai = base == "th";
+
+    let mut sentences = Vec::new();
+    let mut current = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(ch) = chars.next() {
+        current.push(ch);
+        if thai && ch.is_whitespace() {
+            while chars.peek().is_some_and(|c| c.is_whitespace()) {
+                current.extend(chars.next());
+            }
+            sentences.push(std::mem::take(&mut current));
+            continue;
+        }
+        if !(TERMINALS.contains(&ch) || (greek && ch == ';')) {
+            continue;
+        }
+        if ch == '.' {
+            let next_word: String = chars
+                .clone()
+                .skip_while(|c| c.is_whitespace())
+                .take_while(|c| !c.is_whitespace())
+                .collect();
+            if !ends_sentence_at_stop(&current, &next_word, abbreviations, &base) {
+                continue;
+            }
+        }
+        while let Some(&next) = chars.peek() {
+            if CLOSERS.contains(&next) || TERMINALS.contains(&next) {
+                current.push(next);
+                chars.next();
+            } else {
+                break;
+            }
+        }
+        let ends_here = CJK_TERMINALS.contains(&ch) || chars.peek().map_or(true, |c| c.is_whitespace());
+        if ends_here {
+            while let Some(&next) = chars.peek() {
+                if next.is_whitespace() {
+                    current.push(next);
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            sentences.push(std::mem::take(&mut current));
+        }
+    }
+    if !current.is_empty() {
+        sentences.push(current);
+    }
+    sentences
+}
+
+/// Whether the full stop just pushed onto `current` ends the sentence rather than an abbreviation or initial;
+/// `next_word` is the word after it, if any
+fn ends_sentence_at_stop(current: &str, next_word: &str, abbreviations: &[&str], language: &str) -> bool {
+    let before = &current[..current.len() - 1];
+    let word = before
+        .rsplit(|c: char| c.is_whitespace() || CLOSERS.contains(&c) || c == '(' || c == '[')
+        .next()
+        .unwrap_or_default();
+    if word.is_empty() {
+        return true;
+    }
+    let lower = word.to_lowercase();
+    if abbreviations.contains(&lower.as_str()) {
+        return false;
+    }
+    if NUMBER_ABBREVIATIONS.contains(&lower.as_str()) && next_word.starts_with(|c: char| c.is_ascii_digit()) {
+        return false;
+    }
+    // "J. Smith": a single capital is an initial
+    let mut letters = word.chars();
+    if let (Some(first), None) = (letters.next(), letters.next()) {
+        if first.is_uppercase() {
+            return false;
+        }
+    }
+    // German ordinals: "am 3. Mai"
+    !(language == "de" && word.chars().all(|c| c.is_ascii_digit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_is_an_abbreviation_only_before_a_number() {
+        assert_eq!(split_sentences("See No. 5 on the list.", "en"), vec!["See No. 5 on the list."]);
+        assert_eq!(split_sentences("I said no. Then I left.", "en"), vec!["I said no. ", "Then I left."]);
+        assert_eq!(split_sentences("The answer is no.", "en"), vec!["The answer is no."]);
+    }
+}
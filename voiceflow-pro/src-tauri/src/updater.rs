@@ -0,0 +1,234 @@
+//! Self-update for VoiceFlow Pro
+//! Checks the release channel the user picked on a schedule and installs signed updates through Tauri's updater
+
+use std::sync::{Mutex, OnceLock};
+
+use serde::{Deserialize, Serialize};
+use tauri::updater::UpdaterEvent;
+use tauri::{AppHandle, Manager};
+
+use crate::errors::{AppError, ValidationError};
+use crate::AppState;
+
+/// Where update manifests are fetched from. `{channel}` is filled in here, the other placeholders by Tauri.
+/// Updates are always full bundles; Tauri's updater has no patch format
+const DEFAULT_ENDPOINT: &str =
+    "https://updates.voiceflow.pro/{channel}/{{target}}/{{arch}}/{{current_version}}";
+/// Delay before the first background check, so startup is not slowed down
+const FIRST_CHECK_DELAY_SECS: u64 = 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdateChannel {
+    Stable,
+    /// Pre-releases; switching back to stable waits for stable to overtake the installed version
+    Beta,
+}
+
+impl UpdateChannel {
+    fn as_str(self) -> &'static str {
+        match self {
+            UpdateChannel::Stable => "stable",
+            UpdateChannel::Beta => "beta",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UpdateSettings {
+    pub channel: UpdateChannel,
+    /// Check in the background and announce new versions; installing always needs the user
+    pub auto_check: bool,
+    pub check_interval_hours: u64,
+    /// Manifest URL template, see `DEFAULT_ENDPOINT`
+    pub endpoint: String,
+}
+
+impl Default for UpdateSettings {
+    fn default() -> Self {
+        Self {
+            channel: UpdateChannel::Stable,
+            auto_check: true,
+            check_interval_hours: 24,
+            endpoint: DEFAULT_ENDPOINT.to_string(),
+        }
+    }
+}
+
+/// Result of the latest check
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateInfo {
+    pub channel: UpdateChannel,
+    pub current_version: String,
+    pub available: bool,
+    pub latest_version: Option<String>,
+    pub release_notes: Option<String>,
+    /// Publish date from the manifest
+    pub published_at: Option<String>,
+    pub checked_at: u64,
+}
+
+/// Sent as "update-progress" while an update downloads and installs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "stage", rename_all = "snake_case")]
+pub enum UpdateProgress {
+    Downloading { downloaded: u64, total: Option<u64> },
+    Downloaded,
+    Installed,
+    Failed { error: String },
+}
+
+/// Bytes received for the download in progress
+static DOWNLOADED: OnceLock<Mutex<u64>> = OnceLock::new();
+
+pub fn validate(settings: &UpdateSettings) -> Result<(), AppError> {
+    let invalid = |message: String| AppError::Validation(ValidationError::InvalidConfigValue(message));
+    if !settings.endpoint.starts_with("https://") {
+        return Err(invalid("Update endpoint must use https".to_string()));
+    }
+    if !(1..=24 * 7).contains(&settings.check_interval_hours) {
+        return Err(invalid(format!(
+            "Update check interval must be 1-168 hours, got {}",
+            settings.check_interval_hours
+        )));
+    }
+    Ok(())
+}
+
+/// Without the public key in `tauri.updater.pubkey` no update signature can be verified, so such builds
+/// neither check for nor install updates
+fn require_signing_key(app: &AppHandle) -> Result<(), AppError> {
+    if app.config().tauri.updater.pubkey.trim().is_empty() {
+        return Err(AppError::Configuration(
+            "This build has no update signing key, so updates are turned off".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn endpoint(settings: &UpdateSettings, channel: UpdateChannel) -> String {
+    settings.endpoint.replace("{channel}", channel.as_str())
+}
+
+/// Ask the channel's endpoint for a newer version; the manifest's signature is checked when installing
+pub async fn check(app: &AppHandle, settings: &UpdateSettings, channel: Option<UpdateChannel>) -> Result<UpdateInfo, AppError> {
+    validate(settings)?;
+    require_signing_key(app)?;
+    let channel = channel.unwrap_or(settings.channel);
+    let current_version = app.package_info().version.to_string();
+
+    let response = app
+        .updater()
+        .endpoints(&[endpoint(settings, channel)])
+        .check()
+        .await;
+    Ok(match response {
+        Ok(update) if update.is_update_available() => UpdateInfo {
+            channel,
+            current_version,
+            available: true,
+            latest_version: Some(update.latest_version().to_string()),
+            release_notes: update.body().cloned(),
+            published_at: update.date().map(|date| date.to_string()),
+            checked_at: now_secs(),
+        },
+        Ok(_) | Err(tauri::updater::Error::UpToDate) => UpdateInfo {
+            channel,
+            current_version,
+            available: false,
+            latest_version: None,
+            release_notes: None,
+            published_at: None,
+            checked_at: now_secs(),
+        },
+        Err(e) => return Err(AppError::Network(format!("Update check failed: {}", e))),
+    })
+}
+
+/// Download, verify and install the newest version on the configured channel, then restart into it
+pub async fn install(app: &AppHandle, settings: &UpdateSettings) -> Result<(), AppError> {
+    validate(settings)?;
+    require_signing_key(app)?;
+    let update = match app.updater().endpoints(&[endpoint(settings, settings.channel)]).check().await {
+        Ok(update) if update.is_update_available() => update,
+        Ok(_) | Err(tauri::updater::Error::UpToDate) => {
+            return Err(AppError::Configuration("VoiceFlow Pro is already up to date".to_string()))
+        }
+        Err(e) => return Err(AppError::Network(format!("Update check failed: {}", e))),
+    };
+
+    tracing::info!("Installing VoiceFlow Pro {}", update.latest_version());
+    // Apps ducked for read-aloud would otherwise stay quiet through the restart
+    crate::audio_ducking::restore_all().await;
+    if let Err(e) = update.download_and_install().await {
+        let error = e.to_string();
+        let _ = app.emit_all("update-progress", UpdateProgress::Failed { error: error.clone() });
+        return Err(AppError::Internal(format!("Update install failed: {}", error)));
+    }
+
+    crate::crash_reports::mark_clean_exit();
+    app.restart();
+    Ok(())
+}
+
+/// Forward the updater's download events to the frontend; called from the app's run loop
+pub fn handle_event(app: &AppHandle, event: &UpdaterEvent) {
+    let downloaded = DOWNLOADED.get_or_init(|| Mutex::new(0));
+    let mut downloaded = match downloaded.lock() {
+        Ok(downloaded) => downloaded,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let progress = match event {
+        UpdaterEvent::Pending => {
+            *downloaded = 0;
+            return;
+        }
+        UpdaterEvent::DownloadProgress {
+            chunk_length,
+            content_length,
+        } => {
+            *downloaded += *chunk_length as u64;
+            UpdateProgress::Downloading {
+                downloaded: *downloaded,
+                total: *content_length,
+            }
+        }
+        UpdaterEvent::Downloaded => UpdateProgress::Downloaded,
+        UpdaterEvent::Updated => UpdateProgress::Installed,
+        UpdaterEvent::Error(error) => UpdateProgress::Failed { error: error.clone() },
+        _ => return,
+    };
+    let _ = app.emit_all("update-progress", progress);
+}
+
+/// Check for updates on the configured interval for the lifetime of the app, announcing new versions
+/// as "update-available"
+pub async fn run_update_checks(state: AppState, app: AppHandle) {
+    if let Err(e) = require_signing_key(&app) {
+        tracing::warn!("{}", e);
+        return;
+    }
+    tokio::time::sleep(tokio::time::Duration::from_secs(FIRST_CHECK_DELAY_SECS)).await;
+    loop {
+        let settings = state.settings.snapshot().updates.clone();
+        if settings.auto_check {
+            match check(&app, &settings, None).await {
+                Ok(info) if info.available => {
+                    let _ = app.emit_all("update-available", &info);
+                }
+                Ok(_) => {}
+                Err(e) => tracing::warn!("{}", e),
+            }
+        }
+        let interval = tokio::time::Duration::from_secs(settings.check_interval_hours.max(1) * 3600);
+        tokio::time::sleep(interval).await;
+    }
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
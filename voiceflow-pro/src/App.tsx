@@ -100,12 +100,11 @@ const App: React.FC = () => {
         setAppInfo(appData);
         setSupportedLanguages(languagesData);
 
-        // Initialize voice recognition
-        const mainWindow = new Window('main');
-        await invoke('initialize_voice_recognition', { window: mainWindow });
-        
-        // Initialize text processor
-        await invoke('initialize_text_processor');
+        // Start backend services in dependency order; the AI gateway connects on first use
+        const report = await invoke<{ ready: boolean; services: unknown[] }>('initialize_all', { options: {} });
+        if (!report.ready) {
+          console.warn('Some services failed to start:', report.services);
+        }
 
         console.log('VoiceFlow Pro initialized successfully');
       } catch (err) {